@@ -75,6 +75,8 @@ fn setup_heif() {
 
 fn main() {
     println!("Build script");
+    // the shader macros of notan check for its wgpu backend
+    println!("cargo:rustc-check-cfg=cfg(feature, values(\"wgpu\"))");
     // #[cfg(windows)]
     match std::process::Command::new("convert")
         .args(vec!["res/oculante.png", "icon.ico"])
@@ -0,0 +1,95 @@
+use egui::PointerButton;
+use notan_core::keyboard::KeyCode;
+use notan_core::mouse::MouseButton;
+
+pub(crate) fn to_egui_pointer(btn: &MouseButton) -> Option<egui::PointerButton> {
+    Some(match btn {
+        MouseButton::Left => PointerButton::Primary,
+        MouseButton::Right => PointerButton::Secondary,
+        MouseButton::Middle => PointerButton::Middle,
+        MouseButton::Other(_) => return None,
+    })
+}
+
+pub(crate) fn to_egui_key(key: &KeyCode) -> Option<egui::Key> {
+    Some(match key {
+        KeyCode::Down => egui::Key::ArrowDown,
+        KeyCode::Left => egui::Key::ArrowLeft,
+        KeyCode::Right => egui::Key::ArrowRight,
+        KeyCode::Up => egui::Key::ArrowUp,
+
+        KeyCode::Escape => egui::Key::Escape,
+        KeyCode::Tab => egui::Key::Tab,
+        KeyCode::Back => egui::Key::Backspace,
+        KeyCode::Return => egui::Key::Enter,
+        KeyCode::Space => egui::Key::Space,
+
+        KeyCode::Insert => egui::Key::Insert,
+        KeyCode::Delete => egui::Key::Delete,
+        KeyCode::Home => egui::Key::Home,
+        KeyCode::End => egui::Key::End,
+        KeyCode::PageUp => egui::Key::PageUp,
+        KeyCode::PageDown => egui::Key::PageDown,
+
+        KeyCode::Key0 => egui::Key::Num0,
+        KeyCode::Key1 => egui::Key::Num1,
+        KeyCode::Key2 => egui::Key::Num2,
+        KeyCode::Key3 => egui::Key::Num3,
+        KeyCode::Key4 => egui::Key::Num4,
+        KeyCode::Key5 => egui::Key::Num5,
+        KeyCode::Key6 => egui::Key::Num6,
+        KeyCode::Key7 => egui::Key::Num7,
+        KeyCode::Key8 => egui::Key::Num8,
+        KeyCode::Key9 => egui::Key::Num9,
+
+        KeyCode::A => egui::Key::A,
+        KeyCode::B => egui::Key::B,
+        KeyCode::C => egui::Key::C,
+        KeyCode::D => egui::Key::D,
+        KeyCode::E => egui::Key::E,
+        KeyCode::F => egui::Key::F,
+        KeyCode::G => egui::Key::G,
+        KeyCode::H => egui::Key::H,
+        KeyCode::I => egui::Key::I,
+        KeyCode::J => egui::Key::J,
+        KeyCode::K => egui::Key::K,
+        KeyCode::L => egui::Key::L,
+        KeyCode::M => egui::Key::M,
+        KeyCode::N => egui::Key::N,
+        KeyCode::O => egui::Key::O,
+        KeyCode::P => egui::Key::P,
+        KeyCode::Q => egui::Key::Q,
+        KeyCode::R => egui::Key::R,
+        KeyCode::S => egui::Key::S,
+        KeyCode::T => egui::Key::T,
+        KeyCode::U => egui::Key::U,
+        KeyCode::V => egui::Key::V,
+        KeyCode::W => egui::Key::W,
+        KeyCode::X => egui::Key::X,
+        KeyCode::Y => egui::Key::Y,
+        KeyCode::Z => egui::Key::Z,
+
+        KeyCode::F1 => egui::Key::F1,
+        KeyCode::F2 => egui::Key::F2,
+        KeyCode::F3 => egui::Key::F3,
+        KeyCode::F4 => egui::Key::F4,
+        KeyCode::F5 => egui::Key::F5,
+        KeyCode::F6 => egui::Key::F6,
+        KeyCode::F7 => egui::Key::F7,
+        KeyCode::F8 => egui::Key::F8,
+        KeyCode::F9 => egui::Key::F9,
+        KeyCode::F10 => egui::Key::F10,
+        KeyCode::F11 => egui::Key::F11,
+        KeyCode::F12 => egui::Key::F12,
+        KeyCode::F13 => egui::Key::F13,
+        KeyCode::F14 => egui::Key::F14,
+        KeyCode::F15 => egui::Key::F15,
+        KeyCode::F16 => egui::Key::F16,
+        KeyCode::F17 => egui::Key::F17,
+        KeyCode::F18 => egui::Key::F18,
+        KeyCode::F19 => egui::Key::F19,
+        KeyCode::F20 => egui::Key::F20,
+
+        _ => return None,
+    })
+}
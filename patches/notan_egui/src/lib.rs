@@ -0,0 +1,11 @@
+mod config;
+mod extension;
+mod input;
+mod plugin;
+
+pub use config::EguiConfig;
+pub use extension::{EguiCallbackFn, EguiExtension, EguiRegisterTexture};
+pub use plugin::{EguiPlugin, EguiPluginSugar};
+
+pub use egui::load::SizedTexture;
+pub use egui::*;
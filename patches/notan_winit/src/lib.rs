@@ -0,0 +1,12 @@
+mod backend;
+mod clipboard;
+mod keyboard;
+mod mouse;
+mod touch;
+mod window;
+
+mod gl_manager;
+pub mod prelude;
+
+pub use backend::*;
+pub use notan_glow::texture_source::*;
@@ -0,0 +1 @@
+pub use notan_glow::prelude::*;
@@ -0,0 +1,177 @@
+//! Screen reader support. egui describes its widgets to AccessKit, and an adapter passes them on to
+//! UI Automation on Windows, the accessibility API of macOS, or AT-SPI on Linux and the BSDs. egui
+//! only builds the tree once assistive technology asks for it.
+
+use crate::redraw::Waker;
+use log::debug;
+#[cfg(all(unix, not(target_os = "macos")))]
+use log::warn;
+use notan::backend::WinitBackend;
+use notan::egui::accesskit::{ActionHandler, ActionRequest};
+use notan::egui::{self, EguiPlugin};
+use notan::prelude::{App, Plugins};
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use raw_window_handle::RawWindowHandle;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+#[cfg(target_os = "windows")]
+type Adapter = accesskit_windows::SubclassingAdapter;
+#[cfg(target_os = "macos")]
+type Adapter = accesskit_macos::SubclassingAdapter;
+#[cfg(all(unix, not(target_os = "macos")))]
+type Adapter = accesskit_unix::Adapter;
+
+/// The adapter of the window, and the actions it received
+pub struct Accessibility {
+    adapter: Adapter,
+    actions: Receiver<ActionRequest>,
+    /// Focus and bounds of the window as last told to AT-SPI, which can't ask the window
+    #[cfg(all(unix, not(target_os = "macos")))]
+    window: Option<(bool, egui::accesskit::Rect)>,
+}
+
+impl fmt::Debug for Accessibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Accessibility").finish_non_exhaustive()
+    }
+}
+
+/// Passes actions on to the event loop, from the thread of the platform API
+struct Actions {
+    sender: Mutex<Sender<ActionRequest>>,
+    waker: Waker,
+}
+
+impl ActionHandler for Actions {
+    fn do_action(&self, request: ActionRequest) {
+        _ = self
+            .sender
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .send(request);
+        self.waker.wake();
+    }
+}
+
+impl Accessibility {
+    /// Connect the window of `app` to the accessibility API, if the platform has one
+    pub fn new(app: &App, ctx: &egui::Context, waker: Waker) -> Option<Self> {
+        let (sender, actions) = mpsc::channel();
+        let handler = Box::new(Actions {
+            sender: Mutex::new(sender),
+            waker: waker.clone(),
+        });
+        // called when assistive technology first asks for the tree
+        let source = {
+            let ctx = ctx.clone();
+            move || {
+                ctx.enable_accesskit();
+                waker.wake();
+                ctx.accesskit_placeholder_tree_update()
+            }
+        };
+        let backend = app.backend.downcast_ref::<WinitBackend>()?;
+
+        #[cfg(target_os = "windows")]
+        let adapter = match backend.raw_window_handle()? {
+            RawWindowHandle::Win32(handle) => Adapter::new(
+                accesskit_windows::HWND(handle.hwnd as isize),
+                source,
+                handler,
+            ),
+            _ => return None,
+        };
+        #[cfg(target_os = "macos")]
+        let adapter = match backend.raw_window_handle()? {
+            // the window outlives the adapter, which is dropped with the app state
+            RawWindowHandle::AppKit(handle) => unsafe {
+                Adapter::for_window(handle.ns_window, source, handler)
+            },
+            _ => return None,
+        };
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let adapter = {
+            _ = backend;
+            match Adapter::new(
+                "oculante".into(),
+                "egui".into(),
+                "0.23".into(),
+                source,
+                handler,
+            ) {
+                Some(adapter) => adapter,
+                None => {
+                    warn!("No screen reader support without the AT-SPI bus");
+                    return None;
+                }
+            }
+        };
+        debug!("Accessibility adapter connected");
+
+        Some(Self {
+            adapter,
+            actions,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            window: None,
+        })
+    }
+
+    /// Give the actions received since the last frame to egui. Call before running egui.
+    pub fn send_actions(&self, plugins: &Plugins) {
+        if let Some(mut egui) = plugins.get_mut::<EguiPlugin>() {
+            for request in self.actions.try_iter() {
+                egui.accesskit_action(request);
+            }
+        }
+    }
+
+    /// Pass the tree egui built on to the platform. Call after running egui.
+    pub fn update(&mut self, app: &mut App, plugins: &Plugins) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        self.update_window(app);
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        _ = app;
+
+        let update = match plugins
+            .get_mut::<EguiPlugin>()
+            .and_then(|mut egui| egui.take_accesskit_update())
+        {
+            Some(update) => update,
+            None => return,
+        };
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        if let Some(events) = self.adapter.update_if_active(|| update) {
+            events.raise();
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        self.adapter.update(update);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn update_window(&mut self, app: &mut App) {
+        let focused = app.window().is_focused();
+        let (x, y) = app.window().position();
+        let (width, height) = app.window().size();
+        let scale = app.window().dpi();
+        let bounds = egui::accesskit::Rect::new(
+            x as f64,
+            y as f64,
+            x as f64 + width as f64 * scale,
+            y as f64 + height as f64 * scale,
+        );
+        let window = Some((focused, bounds));
+        if self.window == window {
+            return;
+        }
+        let (previous_focus, previous_bounds) = self.window.unzip();
+        if previous_focus != Some(focused) {
+            self.adapter.update_window_focus_state(focused);
+        }
+        if previous_bounds != Some(bounds) {
+            self.adapter.set_root_window_bounds(bounds, bounds);
+        }
+        self.window = window;
+    }
+}
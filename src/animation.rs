@@ -0,0 +1,249 @@
+//! Playback of animated images. The decoder sends every frame once, and frames are shown from
+//! memory here, so playback can be paused, stepped and sped up.
+
+use anyhow::Result;
+use image::RgbaImage;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Shortest time a frame is shown, about 60 fps
+const MIN_DELAY: u64 = 17;
+/// How long frames without a delay are shown
+const DEFAULT_DELAY: u64 = 40;
+
+#[derive(Debug)]
+pub struct AnimationPlayer {
+    /// Frames with how long they are shown, in milliseconds
+    pub frames: Vec<(RgbaImage, u16)>,
+    /// The frame that is shown
+    pub index: usize,
+    pub playing: bool,
+    /// Playback speed, 1 is the speed of the file
+    pub speed: f32,
+    /// Start over after the last frame, or stop there
+    pub looping: bool,
+    /// Columns of exported spritesheets
+    pub sheet_columns: usize,
+    /// The frame at `index` still needs to be shown
+    changed: bool,
+    last_advance: Instant,
+}
+
+impl AnimationPlayer {
+    /// A player showing the first frame
+    pub fn new(first: RgbaImage, delay: u16) -> Self {
+        Self {
+            frames: vec![(first, delay)],
+            index: 0,
+            playing: true,
+            speed: 1.,
+            looping: true,
+            sheet_columns: 8,
+            changed: false,
+            last_advance: Instant::now(),
+        }
+    }
+
+    /// Add a frame that was decoded
+    pub fn push(&mut self, buffer: RgbaImage, delay: u16) {
+        self.frames.push((buffer, delay));
+    }
+
+    /// How long the frame at `index` is shown at the current speed
+    pub fn delay(&self, index: usize) -> Duration {
+        let delay = self.frames.get(index).map(|(_, d)| *d).unwrap_or_default();
+        let micros = frame_delay(delay) as f32 * 1000. / self.speed.max(0.01);
+        Duration::from_micros(micros.round() as u64)
+    }
+
+    /// Move to the next frame if it is due while playing
+    pub fn advance(&mut self) {
+        if !self.playing || self.last_advance.elapsed() < self.delay(self.index) {
+            return;
+        }
+        if self.index + 1 >= self.frames.len() && !self.looping {
+            self.playing = false;
+            return;
+        }
+        self.last_advance = Instant::now();
+        self.seek(self.index + 1);
+    }
+
+    /// When the next frame is due, while playing
+    pub fn next_due(&self) -> Option<Instant> {
+        self.playing
+            .then(|| self.last_advance + self.delay(self.index))
+    }
+
+    /// Pause and go to the next or previous frame
+    pub fn step(&mut self, forward: bool) {
+        self.playing = false;
+        let count = self.frames.len();
+        if forward {
+            self.seek(self.index + 1);
+        } else {
+            self.seek(self.index + count - 1);
+        }
+    }
+
+    /// Go to the frame at `index`, wrapping around
+    pub fn seek(&mut self, index: usize) {
+        self.index = index % self.frames.len();
+        self.changed = true;
+    }
+
+    /// The frame to show, once after it changed
+    pub fn take_frame(&mut self) -> Option<RgbaImage> {
+        if !std::mem::take(&mut self.changed) {
+            return None;
+        }
+        self.frames
+            .get(self.index)
+            .map(|(buffer, _)| buffer.clone())
+    }
+
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+        // play from the start once a stopped animation reached its end
+        if self.playing && !self.looping && self.index + 1 >= self.frames.len() {
+            self.seek(0);
+        }
+        self.last_advance = Instant::now();
+    }
+
+    /// Length of one loop at the speed of the file
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .iter()
+            .map(|(_, delay)| Duration::from_millis(frame_delay(*delay)))
+            .sum()
+    }
+}
+
+/// How long a frame with `delay` in the file is shown, in milliseconds
+fn frame_delay(delay: u16) -> u64 {
+    match delay {
+        0 => DEFAULT_DELAY,
+        delay => (delay as u64).max(MIN_DELAY),
+    }
+}
+
+/// File name of a frame of `path` in an export, like `name_0001.png`
+pub fn frame_name(path: &Path, index: usize) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "frame".to_string());
+    format!("{stem}_{:04}.png", index + 1)
+}
+
+/// Save every frame as a numbered PNG in `folder`
+pub fn save_frames(frames: &[(RgbaImage, u16)], path: &Path, folder: &Path) -> Result<()> {
+    std::fs::create_dir_all(folder)?;
+    for (index, (buffer, _)) in frames.iter().enumerate() {
+        buffer.save(folder.join(frame_name(path, index)))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AtlasFrame {
+    pub filename: String,
+    pub frame: AtlasRect,
+    /// In milliseconds
+    pub duration: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AtlasMeta {
+    pub image: String,
+    pub size: AtlasRect,
+    pub columns: usize,
+}
+
+/// Where each frame is in a spritesheet, in the JSON array format of TexturePacker that most
+/// engines read
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Atlas {
+    pub frames: Vec<AtlasFrame>,
+    pub meta: AtlasMeta,
+}
+
+/// Pack the frames of `path` into a grid, row by row, and describe where they are
+pub fn spritesheet(frames: &[(RgbaImage, u16)], columns: usize, path: &Path) -> (RgbaImage, Atlas) {
+    let columns = columns.clamp(1, frames.len().max(1));
+    let rows = (frames.len() + columns - 1) / columns;
+    let cell_width = frames
+        .iter()
+        .map(|(f, _)| f.width())
+        .max()
+        .unwrap_or_default();
+    let cell_height = frames
+        .iter()
+        .map(|(f, _)| f.height())
+        .max()
+        .unwrap_or_default();
+    let mut sheet = RgbaImage::new(cell_width * columns as u32, cell_height * rows as u32);
+    let mut atlas_frames = vec![];
+    for (index, (buffer, delay)) in frames.iter().enumerate() {
+        let x = (index % columns) as u32 * cell_width;
+        let y = (index / columns) as u32 * cell_height;
+        image::imageops::replace(&mut sheet, buffer, x as i64, y as i64);
+        atlas_frames.push(AtlasFrame {
+            filename: frame_name(path, index),
+            frame: AtlasRect {
+                x,
+                y,
+                w: buffer.width(),
+                h: buffer.height(),
+            },
+            duration: frame_delay(*delay),
+        });
+    }
+    let atlas = Atlas {
+        frames: atlas_frames,
+        meta: AtlasMeta {
+            image: sheet_name(path, "png"),
+            size: AtlasRect {
+                x: 0,
+                y: 0,
+                w: sheet.width(),
+                h: sheet.height(),
+            },
+            columns,
+        },
+    };
+    (sheet, atlas)
+}
+
+fn sheet_name(path: &Path, extension: &str) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "animation".to_string());
+    format!("{stem}_sheet.{extension}")
+}
+
+/// Save a spritesheet of the frames and its JSON atlas in `folder`. Returns the sheet's path.
+pub fn save_spritesheet(
+    frames: &[(RgbaImage, u16)],
+    columns: usize,
+    path: &Path,
+    folder: &Path,
+) -> Result<PathBuf> {
+    let (sheet, atlas) = spritesheet(frames, columns, path);
+    let sheet_path = folder.join(&atlas.meta.image);
+    sheet.save(&sheet_path)?;
+    let json = std::fs::File::create(folder.join(sheet_name(path, "json")))?;
+    serde_json::to_writer_pretty(json, &atlas)?;
+    Ok(sheet_path)
+}
@@ -0,0 +1,192 @@
+//! Animated GIF, WebP and PNG files assembled from still frames, like a numbered sequence or the
+//! images of a folder. The reverse of exporting the frames of an animation.
+
+use anyhow::{bail, Result};
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+use strum::Display;
+use strum_macros::EnumIter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumIter)]
+pub enum AnimationFormat {
+    #[strum(serialize = "GIF")]
+    Gif,
+    #[strum(serialize = "WebP")]
+    Webp,
+    #[strum(serialize = "APNG")]
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+            Self::Apng => "png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationSettings {
+    pub format: AnimationFormat,
+    pub fps: f32,
+    /// How often the animation plays, 0 is forever
+    pub loops: u16,
+    /// WebP quality from 0 to 100, where 100 is lossless
+    pub quality: u8,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self {
+            format: AnimationFormat::Gif,
+            fps: 12.,
+            loops: 0,
+            quality: 90,
+        }
+    }
+}
+
+impl AnimationSettings {
+    /// How long each frame is shown, in milliseconds
+    pub fn delay(&self) -> u32 {
+        (1000. / self.fps.max(0.1)).round() as u32
+    }
+}
+
+/// The encoded animation. All frames need the size of the first.
+pub fn encode(frames: &[RgbaImage], settings: &AnimationSettings) -> Result<Vec<u8>> {
+    let first = match frames.first() {
+        Some(first) => first,
+        None => bail!("No frames"),
+    };
+    if let Some(other) = frames.iter().find(|f| f.dimensions() != first.dimensions()) {
+        bail!(
+            "All frames need to be {}x{}, one is {}x{}",
+            first.width(),
+            first.height(),
+            other.width(),
+            other.height()
+        );
+    }
+    match settings.format {
+        AnimationFormat::Gif => encode_gif(frames, settings),
+        AnimationFormat::Webp => encode_webp(frames, settings),
+        AnimationFormat::Apng => encode_apng(frames, settings),
+    }
+}
+
+fn encode_gif(frames: &[RgbaImage], settings: &AnimationSettings) -> Result<Vec<u8>> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    let mut data = vec![];
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut data, 10);
+        encoder.set_repeat(match settings.loops {
+            0 => Repeat::Infinite,
+            loops => Repeat::Finite(loops),
+        })?;
+        let delay = image::Delay::from_numer_denom_ms(settings.delay(), 1);
+        for frame in frames {
+            encoder.encode_frame(image::Frame::from_parts(frame.clone(), 0, 0, delay))?;
+        }
+    }
+    Ok(data)
+}
+
+fn encode_apng(frames: &[RgbaImage], settings: &AnimationSettings) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    {
+        let (width, height) = frames[0].dimensions();
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_animated(frames.len() as u32, settings.loops as u32)?;
+        encoder.set_frame_delay(settings.delay().min(u16::MAX as u32) as u16, 1000)?;
+        let mut writer = encoder.write_header()?;
+        for frame in frames {
+            writer.write_image_data(frame.as_raw())?;
+        }
+        writer.finish()?;
+    }
+    Ok(data)
+}
+
+fn encode_webp(frames: &[RgbaImage], settings: &AnimationSettings) -> Result<Vec<u8>> {
+    use libwebp_sys::*;
+    let (width, height) = frames[0].dimensions();
+    let mut config = WebPConfig::new_with_preset(WebPPreset::WEBP_PRESET_DEFAULT, 75.)
+        .map_err(|_| anyhow::anyhow!("Can't configure the WebP encoder"))?;
+    config.quality = settings.quality.min(100) as f32;
+    config.lossless = (settings.quality >= 100) as i32;
+    unsafe {
+        let mut options = std::mem::MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+        if WebPAnimEncoderOptionsInitInternal(options.as_mut_ptr(), WEBP_MUX_ABI_VERSION as i32)
+            == 0
+        {
+            bail!("Can't configure the WebP encoder");
+        }
+        let mut options = options.assume_init();
+        options.anim_params.loop_count = settings.loops as i32;
+        let encoder = WebPAnimEncoderNewInternal(
+            width as i32,
+            height as i32,
+            &options,
+            WEBP_MUX_ABI_VERSION as i32,
+        );
+        if encoder.is_null() {
+            bail!("Can't create the WebP encoder");
+        }
+        let result = (|| {
+            let mut timestamp = 0;
+            for frame in frames {
+                let mut picture = WebPPicture::new()
+                    .map_err(|_| anyhow::anyhow!("Can't create a WebP picture"))?;
+                picture.use_argb = 1;
+                picture.width = width as i32;
+                picture.height = height as i32;
+                let imported =
+                    WebPPictureImportRGBA(&mut picture, frame.as_ptr(), width as i32 * 4);
+                let added = imported != 0
+                    && WebPAnimEncoderAdd(encoder, &mut picture, timestamp, &config) != 0;
+                WebPPictureFree(&mut picture);
+                if !added {
+                    bail!("Can't encode a WebP frame");
+                }
+                timestamp += settings.delay() as i32;
+            }
+            // the end of the last frame
+            WebPAnimEncoderAdd(encoder, std::ptr::null_mut(), timestamp, std::ptr::null());
+            let mut webp_data = WebPData::default();
+            if WebPAnimEncoderAssemble(encoder, &mut webp_data) == 0 {
+                bail!("Can't assemble the WebP animation");
+            }
+            let data = std::slice::from_raw_parts(webp_data.bytes, webp_data.size).to_vec();
+            WebPDataClear(&mut webp_data);
+            Ok(data)
+        })();
+        WebPAnimEncoderDelete(encoder);
+        result
+    }
+}
+
+/// A file named `name` in `folder` for the format, which doesn't overwrite another file
+pub fn default_path(folder: &Path, name: &str, format: AnimationFormat) -> PathBuf {
+    let name = if name.is_empty() { "animation" } else { name };
+    let extension = format.extension();
+    let mut path = folder.join(format!("{name}.{extension}"));
+    let mut count = 1;
+    while path.exists() {
+        path = folder.join(format!("{name}_{count}.{extension}"));
+        count += 1;
+    }
+    path
+}
+
+/// State of the create animation window
+#[derive(Debug, Default)]
+pub struct AnimationEncoder {
+    pub enabled: bool,
+    pub settings: AnimationSettings,
+    /// Frames are loaded and encoded on a thread
+    pub running: bool,
+}
@@ -1,15 +1,50 @@
+#[cfg(feature = "update")]
+use crate::update::AvailableUpdate;
 use crate::{
-    image_editing::EditState,
+    animation::AnimationPlayer,
+    animation_encoder::AnimationEncoder,
+    channel_pack::ChannelPacker,
+    checksum::{ChecksumView, FileChecksum},
+    clipboard_watch::ClipboardWatcher,
+    colormap::DataView,
+    compression::CompressionView,
+    dataset::DatasetReview,
+    dicom::DicomView,
+    favourites::{FavouritesExport, Import},
+    fits::FitsView,
+    folder_stats::{FolderStats, StatsWindow},
+    gpu::{ChannelShader, PixelShader},
+    heightmap::HeightmapView,
+    image_editing::{EditPreset, EditState},
+    instance::InstanceRequest,
+    metadata::EditableMetadata,
+    mjpeg::{StreamStatus, StreamViewer},
+    montage::Montage,
+    profile::LineProfile,
+    redraw::Redraw,
+    remote::RemoteBrowser,
+    scripting::Script,
+    scroll_reader::{DecodedPage, ScrollReader},
     scrubber::Scrubber,
-    settings::PersistentSettings,
-    utils::{ExtendedImageInfo, Frame, Player},
+    search::{Search, SearchIndex},
+    selection::Selection,
+    sequence::SequencePlayer,
+    settings::{PersistentSettings, Sampling},
+    sharpness::{Scores, SharpnessCache},
+    similarity::{HashCache, Hashes},
+    size_estimate::{Estimate, SizeEstimates},
+    soft_proof::SoftProof,
+    sorting::Sorting,
+    utils::{AlphaMode, ColorEncoding, ExtendedImageInfo, Frame, Player},
 };
 use image::RgbaImage;
 use nalgebra::Vector2;
 use notan::{egui::epaint::ahash::HashMap, prelude::Texture, AppState};
 use std::{
     path::PathBuf,
+    sync::atomic::AtomicBool,
     sync::mpsc::{self, Receiver, Sender},
+    sync::Arc,
 };
 
 #[derive(Debug, Clone)]
@@ -47,7 +82,21 @@ pub struct OculanteState {
     pub image_geometry: ImageGeometry,
     pub compare_list: HashMap<PathBuf, ImageGeometry>,
     pub drag_enabled: bool,
+    /// The visible part on the minimap is being dragged
+    pub minimap_drag: bool,
     pub reset_image: bool,
+    /// The next reset follows the zoom policy, as a new image was opened
+    pub apply_zoom_policy: bool,
+    /// Keep the view for the next image, e.g. to compare images
+    pub keep_view: bool,
+    /// Keep zoom and offset exactly for images of these dimensions
+    pub lock_view: Option<(u32, u32)>,
+    /// Save the viewport after this many frames, so closing menus are not captured
+    pub capture_viewport: Option<u8>,
+    /// Show the image as printed through a printer profile
+    pub soft_proof: Option<SoftProof>,
+    /// Move keyboard focus to the main menu, to use the UI without a mouse
+    pub focus_menu: bool,
     pub message: Option<Message>,
     /// Is the image fully loaded?
     pub is_loaded: bool,
@@ -66,35 +115,226 @@ pub struct OculanteState {
     /// The Player, responsible for loading and sending Frames
     pub player: Player,
     pub current_texture: Option<Texture>,
+    /// The unedited image, to compare it with the edits
+    pub original_texture: Option<Texture>,
+    /// The current image blurred, while its folder is
+    pub blur_texture: Option<Texture>,
     pub current_path: Option<PathBuf>,
     pub current_image: Option<RgbaImage>,
+    /// The current image is a reduced decode, see `FrameSource::Preview`
+    pub is_preview: bool,
+    /// The image that is shown while it decodes, see `FrameSource::Partial`
+    pub partial_of: Option<PathBuf>,
     pub settings_enabled: bool,
     pub image_info: Option<ExtendedImageInfo>,
     pub tiling: usize,
     pub mouse_grab: bool,
     pub key_grab: bool,
     pub edit_state: EditState,
+    /// Named edit chains, stored in the config dir
+    pub edit_presets: Vec<EditPreset>,
+    /// User scripts, see `scripting`
+    pub scripts: Vec<Script>,
+    /// Windowing of the current image, if it is a DICOM file
+    pub dicom: Option<DicomView>,
+    /// Stretch of the current image, if it is a FITS file
+    pub fits: Option<FitsView>,
+    /// Resolution of the file at the path, in pixels per inch
+    pub print_dpi: Option<(PathBuf, Option<f32>)>,
+    /// Alpha mode detected for the image at the path
+    pub alpha_detected: Option<(PathBuf, AlphaMode)>,
+    /// Alpha modes chosen for images in this session
+    pub alpha_overrides: HashMap<PathBuf, AlphaMode>,
+    /// Color encodings chosen for images in this session
+    pub color_encodings: HashMap<PathBuf, ColorEncoding>,
+    /// Encoding of the current JPEG or PNG file
+    pub compression: Option<CompressionView>,
+    /// Colormap of single channel images
+    pub data_view: Option<DataView>,
+    /// Descriptive metadata of the file at the path, while it is edited
+    pub metadata: Option<(PathBuf, EditableMetadata)>,
+    /// The location entered for the file of `metadata`
+    pub geotag: String,
+    /// Set by the --all-files flag for this session only
+    pub all_files: bool,
+    /// Set by the --read-only flag for this session only
+    pub read_only: bool,
     pub pointer_over_ui: bool,
     /// Things that perisist between launches
     pub persistent_settings: PersistentSettings,
+    /// Settings imported from a file
+    pub settings_channel: (Sender<PersistentSettings>, Receiver<PersistentSettings>),
+    /// Requests of later launches, the tray icon and the hotkey while in background mode
+    pub instance_channel: (Sender<InstanceRequest>, Receiver<InstanceRequest>),
+    /// The tray icon and hotkey of background mode
+    #[cfg(feature = "tray")]
+    pub tray: Option<crate::tray::Tray>,
+    /// Passes the UI on to screen readers
+    #[cfg(feature = "accessibility")]
+    pub accessibility: Option<crate::accessibility::Accessibility>,
+    /// Screenshots taken on a thread, or `None` if the capture was cancelled
+    pub screenshot_channel: (
+        Sender<Result<Option<PathBuf>, String>>,
+        Receiver<Result<Option<PathBuf>, String>>,
+    ),
+    /// Show the log viewer
+    pub log_enabled: bool,
+    /// Most verbose level shown in the log viewer
+    pub log_level: log::LevelFilter,
+    /// Crash log of a previous run to offer
+    pub crash_log: Option<PathBuf>,
+    #[cfg(feature = "update")]
+    pub update_channel: (Sender<AvailableUpdate>, Receiver<AvailableUpdate>),
+    #[cfg(feature = "update")]
+    pub available_update: Option<AvailableUpdate>,
+    /// Show the changelog of the available update
+    #[cfg(feature = "update")]
+    pub show_update: bool,
     pub always_on_top: bool,
     pub network_mode: bool,
+    /// Labels sent with the last image received over the network
+    pub network_metadata: Option<crate::net::NetMetadata>,
+    pub network_metadata_channel: (
+        Sender<Option<crate::net::NetMetadata>>,
+        Receiver<Option<crate::net::NetMetadata>>,
+    ),
+    pub stream: StreamViewer,
+    pub stream_status_channel: (Sender<StreamStatus>, Receiver<StreamStatus>),
+    pub remote: RemoteBrowser,
+    pub dataset: DatasetReview,
+    pub sorting: Sorting,
+    pub scroll_reader: ScrollReader,
+    /// Pages decoded on a thread while scroll reading
+    pub scroll_reader_channel: (Sender<DecodedPage>, Receiver<DecodedPage>),
+    pub favourites: FavouritesExport,
+    /// Favourites collected into a folder so far, and their number
+    pub favourites_channel: (Sender<(usize, usize)>, Receiver<(usize, usize)>),
+    /// Favourites read from a file on a thread
+    pub favourites_import_channel: (Sender<Import>, Receiver<Import>),
+    /// The images of a remote folder, listed on a thread, and the one to show
+    pub remote_channel: (
+        Sender<Result<(Vec<PathBuf>, usize), String>>,
+        Receiver<Result<(Vec<PathBuf>, usize), String>>,
+    ),
     /// how long the toast message appears
     pub toast_cooldown: f32,
     /// data to transform image once fullscreen is entered/left
     pub fullscreen_offset: Option<(i32, i32)>,
     /// List of images to cycle through. Usually the current dir or dropped files
     pub scrubber: Scrubber,
+    /// Numbered image sequence the current image belongs to
+    pub sequence: Option<SequencePlayer>,
+    /// Playback of the current image, if it is animated
+    pub animation: Option<AnimationPlayer>,
+    pub animation_encoder: AnimationEncoder,
+    /// Animations created on a thread, or `None` if saving was cancelled
+    pub animation_encoder_channel: (
+        Sender<Option<Result<PathBuf, String>>>,
+        Receiver<Option<Result<PathBuf, String>>>,
+    ),
+    /// Folder listings made on a thread
+    pub scrubber_channel: (Sender<Scrubber>, Receiver<Scrubber>),
+    /// Sharpness scores of images seen or listed so far
+    pub sharpness: SharpnessCache,
+    /// Scores of folder images computed on a thread
+    pub sharpness_channel: (Sender<Scores>, Receiver<Scores>),
+    /// Perceptual hashes of images seen or listed so far
+    pub hashes: HashCache,
+    /// Hashes of folder images computed on a thread
+    pub hash_channel: (Sender<Hashes>, Receiver<Hashes>),
+    /// The image and its hash the folder is filtered by similarity to
+    pub similar_to: Option<(PathBuf, u64)>,
+    pub search: Search,
+    /// Search indices built on a thread
+    pub search_channel: (Sender<SearchIndex>, Receiver<SearchIndex>),
+    pub folder_stats: StatsWindow,
+    /// Folder statistics computed on a thread
+    pub folder_stats_channel: (Sender<FolderStats>, Receiver<FolderStats>),
+    pub heightmap: HeightmapView,
+    pub profile: LineProfile,
+    pub selection: Selection,
+    pub size_estimates: SizeEstimates,
+    /// Size estimates of an image computed on a thread
+    pub size_estimates_channel: (
+        Sender<(Option<PathBuf>, Vec<Estimate>)>,
+        Receiver<(Option<PathBuf>, Vec<Estimate>)>,
+    ),
+    pub checksum: ChecksumView,
+    /// Checksums of files computed on a thread
+    pub checksum_channel: (
+        Sender<(PathBuf, Result<FileChecksum, String>)>,
+        Receiver<(PathBuf, Result<FileChecksum, String>)>,
+    ),
+    pub channel_pack: ChannelPacker,
+    /// Channel packing sources picked in a dialog, by channel index
+    pub channel_pack_channel: (Sender<(usize, PathBuf)>, Receiver<(usize, PathBuf)>),
+    pub montage: Montage,
+    /// Montage sources picked in a dialog
+    pub montage_channel: (Sender<Vec<PathBuf>>, Receiver<Vec<PathBuf>>),
+    pub clipboard_watch: ClipboardWatcher,
+    /// Images copied while the clipboard is watched
+    pub clipboard_watch_channel: (Sender<RgbaImage>, Receiver<RgbaImage>),
+    /// Set to stop watching the hot folder
+    pub hot_folder_watch: Option<Arc<AtomicBool>>,
+    /// Images that arrived in the hot folder
+    pub hot_folder_channel: (Sender<PathBuf>, Receiver<PathBuf>),
+    #[cfg(feature = "ocr")]
+    pub ocr: crate::ocr::TextWindow,
+    /// Recognized text from a thread
+    #[cfg(feature = "ocr")]
+    pub ocr_channel: (
+        Sender<Result<String, String>>,
+        Receiver<Result<String, String>>,
+    ),
     pub checker_texture: Option<Texture>,
-    pub redraw: bool,
+    /// Shader used to preview pixel edits on the GPU, created on first use
+    pub pixel_shader: Option<PixelShader>,
+    /// Shader drawing computed channel views, created on first use
+    pub channel_shader: Option<ChannelShader>,
+    /// The current texture is drawn through the channel shader
+    pub channel_view_gpu: bool,
+    /// Decides when frames are drawn
+    pub redraw: Redraw,
     pub first_start: bool,
 }
 
 impl OculanteState {
+    /// Whether files with unknown extensions are opened and listed
+    pub fn load_all_files(&self) -> bool {
+        self.persistent_settings.load_all_files || self.all_files
+    }
+
+    /// How the current image is sampled: its own mode, or the default
+    pub fn sampling(&self) -> Sampling {
+        self.current_path
+            .as_ref()
+            .and_then(|path| self.persistent_settings.image_sampling.get(path))
+            .copied()
+            .unwrap_or(self.persistent_settings.sampling)
+    }
+
+    /// Whether files may not be changed
+    pub fn is_read_only(&self) -> bool {
+        self.persistent_settings.read_only || self.read_only
+    }
+
+    /// Whether files may be changed. Warns if not.
+    pub fn check_writable(&self) -> bool {
+        if self.is_read_only() {
+            self.send_message_warn("Files can't be changed in read-only mode");
+            return false;
+        }
+        true
+    }
+
     pub fn send_message(&self, msg: &str) {
         _ = self.message_channel.0.send(Message::info(msg));
     }
 
+    pub fn send_message_warn(&self, msg: &str) {
+        _ = self.message_channel.0.send(Message::warn(msg));
+    }
+
     pub fn send_message_err(&self, msg: &str) {
         _ = self.message_channel.0.send(Message::err(msg));
     }
@@ -110,7 +350,14 @@ impl Default for OculanteState {
             },
             compare_list: Default::default(),
             drag_enabled: Default::default(),
+            minimap_drag: Default::default(),
             reset_image: Default::default(),
+            apply_zoom_policy: Default::default(),
+            keep_view: Default::default(),
+            lock_view: Default::default(),
+            capture_viewport: Default::default(),
+            soft_proof: Default::default(),
+            focus_menu: Default::default(),
             message: Default::default(),
             is_loaded: Default::default(),
             cursor: Default::default(),
@@ -125,7 +372,11 @@ impl Default for OculanteState {
             extended_info_loading: Default::default(),
             mouse_delta: Default::default(),
             current_texture: Default::default(),
+            original_texture: Default::default(),
+            blur_texture: Default::default(),
             current_image: Default::default(),
+            is_preview: Default::default(),
+            partial_of: Default::default(),
             current_path: Default::default(),
             settings_enabled: Default::default(),
             image_info: Default::default(),
@@ -133,15 +384,94 @@ impl Default for OculanteState {
             mouse_grab: Default::default(),
             key_grab: Default::default(),
             edit_state: Default::default(),
+            edit_presets: Default::default(),
+            scripts: Default::default(),
+            dicom: Default::default(),
+            fits: Default::default(),
+            compression: Default::default(),
+            print_dpi: Default::default(),
+            alpha_detected: Default::default(),
+            alpha_overrides: Default::default(),
+            color_encodings: Default::default(),
+            data_view: Default::default(),
+            metadata: Default::default(),
+            geotag: Default::default(),
+            all_files: Default::default(),
+            read_only: Default::default(),
             pointer_over_ui: Default::default(),
             persistent_settings: Default::default(),
+            settings_channel: mpsc::channel(),
+            instance_channel: mpsc::channel(),
+            #[cfg(feature = "tray")]
+            tray: None,
+            #[cfg(feature = "accessibility")]
+            accessibility: None,
+            screenshot_channel: mpsc::channel(),
+            log_enabled: Default::default(),
+            log_level: log::LevelFilter::Info,
+            crash_log: Default::default(),
+            #[cfg(feature = "update")]
+            update_channel: mpsc::channel(),
+            #[cfg(feature = "update")]
+            available_update: None,
+            #[cfg(feature = "update")]
+            show_update: false,
             always_on_top: Default::default(),
             network_mode: Default::default(),
+            network_metadata: None,
+            network_metadata_channel: mpsc::channel(),
+            stream: Default::default(),
+            stream_status_channel: mpsc::channel(),
+            remote: Default::default(),
+            dataset: Default::default(),
+            sorting: Default::default(),
+            scroll_reader: Default::default(),
+            scroll_reader_channel: mpsc::channel(),
+            favourites: Default::default(),
+            favourites_channel: mpsc::channel(),
+            favourites_import_channel: mpsc::channel(),
+            remote_channel: mpsc::channel(),
             window_size: Default::default(),
             toast_cooldown: Default::default(),
             fullscreen_offset: Default::default(),
             scrubber: Default::default(),
+            scrubber_channel: mpsc::channel(),
+            sharpness: Default::default(),
+            sharpness_channel: mpsc::channel(),
+            hashes: Default::default(),
+            hash_channel: mpsc::channel(),
+            similar_to: Default::default(),
+            search: Default::default(),
+            search_channel: mpsc::channel(),
+            folder_stats: Default::default(),
+            folder_stats_channel: mpsc::channel(),
+            heightmap: Default::default(),
+            profile: Default::default(),
+            selection: Default::default(),
+            size_estimates: Default::default(),
+            size_estimates_channel: mpsc::channel(),
+            checksum: Default::default(),
+            checksum_channel: mpsc::channel(),
+            channel_pack: Default::default(),
+            channel_pack_channel: mpsc::channel(),
+            montage: Default::default(),
+            montage_channel: mpsc::channel(),
+            clipboard_watch: Default::default(),
+            clipboard_watch_channel: mpsc::channel(),
+            hot_folder_watch: None,
+            hot_folder_channel: mpsc::channel(),
+            #[cfg(feature = "ocr")]
+            ocr: Default::default(),
+            #[cfg(feature = "ocr")]
+            ocr_channel: mpsc::channel(),
+            sequence: Default::default(),
+            animation: Default::default(),
+            animation_encoder: Default::default(),
+            animation_encoder_channel: mpsc::channel(),
             checker_texture: Default::default(),
+            pixel_shader: Default::default(),
+            channel_shader: Default::default(),
+            channel_view_gpu: Default::default(),
             redraw: Default::default(),
             first_start: true,
         }
@@ -0,0 +1,97 @@
+//! Headless decode benchmark, run with `oculante --bench <folder>`.
+//!
+//! Every supported image in the folder is decoded once and the time until its first complete
+//! frame is collected per format. Compare the output of builds with different features (e.g.
+//! with and without `turbo`) or of different versions to spot regressions.
+
+use crate::image_loader::open_image;
+use crate::scrubber::get_image_filenames_for_directory;
+use crate::utils::FrameSource;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default)]
+pub struct FormatStats {
+    pub files: usize,
+    pub failed: usize,
+    pub total: Duration,
+    pub min: Option<Duration>,
+    pub max: Duration,
+    /// Decoded pixels of all files
+    pub pixels: u64,
+}
+
+impl FormatStats {
+    fn add(&mut self, duration: Duration, pixels: u64) {
+        self.files += 1;
+        self.total += duration;
+        self.min = Some(self.min.map(|m| m.min(duration)).unwrap_or(duration));
+        self.max = self.max.max(duration);
+        self.pixels += pixels;
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.total / self.files.max(1) as u32
+    }
+
+    /// Decoding speed in megapixels per second
+    pub fn megapixels_per_second(&self) -> f64 {
+        self.pixels as f64 / 1_000_000. / self.total.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Decode all images in `folder` and collect statistics by lower case file extension
+pub fn run(folder: &Path, all_files: bool) -> Result<BTreeMap<String, FormatStats>> {
+    if !folder.is_dir() {
+        bail!("{} is not a folder", folder.display());
+    }
+    let mut stats: BTreeMap<String, FormatStats> = BTreeMap::new();
+    for path in get_image_filenames_for_directory(folder, all_files)? {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "none".into());
+        let entry = stats.entry(extension).or_default();
+        let start = Instant::now();
+        // progressive decoders send partial frames first
+        let frame = open_image(&path)
+            .ok()
+            .and_then(|frames| frames.iter().find(|f| f.source != FrameSource::Partial));
+        match frame {
+            Some(frame) => {
+                let (width, height) = frame.buffer.dimensions();
+                entry.add(start.elapsed(), width as u64 * height as u64);
+            }
+            None => {
+                eprintln!("Could not decode {}", path.display());
+                entry.failed += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// A table of the statistics, one line per format
+pub fn report(stats: &BTreeMap<String, FormatStats>) -> String {
+    let ms = |d: Duration| format!("{:.1}", d.as_secs_f64() * 1000.);
+    let mut lines = vec![format!(
+        "{:<8} {:>6} {:>6} {:>10} {:>9} {:>9} {:>9} {:>8}",
+        "format", "files", "failed", "total ms", "mean ms", "min ms", "max ms", "MP/s"
+    )];
+    for (format, s) in stats {
+        lines.push(format!(
+            "{:<8} {:>6} {:>6} {:>10} {:>9} {:>9} {:>9} {:>8.1}",
+            format,
+            s.files,
+            s.failed,
+            ms(s.total),
+            ms(s.mean()),
+            ms(s.min.unwrap_or_default()),
+            ms(s.max),
+            s.megapixels_per_second()
+        ));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,26 @@
+//! Content blur, to review flagged images where others can see the screen. Images of blurred
+//! folders are shown blurred while the reveal key is not held.
+
+use image::RgbaImage;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// The long side of the reduced image. Stretched with linear filtering, no detail is left.
+const BLUR_SIZE: u32 = 24;
+
+/// A tiny copy of the image that looks blurred when drawn at the size of the image
+pub fn blurred(img: &RgbaImage) -> RgbaImage {
+    let long = img.width().max(img.height()).max(1) as f32;
+    let scale = (BLUR_SIZE as f32 / long).min(1.0);
+    let width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let height = ((img.height() as f32 * scale).round() as u32).max(1);
+    image::imageops::thumbnail(img, width, height)
+}
+
+/// Whether the image at `path` is blurred: if all are, or if it is in a blurred folder or below
+pub fn is_blurred(path: Option<&Path>, blur_all: bool, folders: &HashSet<PathBuf>) -> bool {
+    blur_all
+        || path
+            .map(|p| folders.iter().any(|folder| p.starts_with(folder)))
+            .unwrap_or_default()
+}
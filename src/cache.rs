@@ -1,11 +1,16 @@
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
-    time::Instant,
+    sync::{mpsc::Sender, Arc},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
+use anyhow::{anyhow, Context, Result};
 use image::RgbaImage;
-use log::debug;
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Debug)]
 pub struct Cache {
@@ -28,6 +33,11 @@ impl Cache {
         self.data.clear()
     }
 
+    /// Memory used by all cached images
+    pub fn bytes(&self) -> usize {
+        self.data.values().map(|c| c.data.len()).sum()
+    }
+
     pub fn insert(&mut self, path: &Path, img: RgbaImage) {
         self.data.insert(
             path.into(),
@@ -56,3 +66,268 @@ impl Cache {
         }
     }
 }
+
+/// Decoded images stored on disk, so large files open instantly in later sessions
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    pub dir: PathBuf,
+    /// Maximum size of all cached files in bytes
+    pub max_size: u64,
+}
+
+impl DiskCache {
+    /// Images smaller than this are decoded quickly anyway
+    pub const MIN_PIXELS: u64 = 1_000_000;
+
+    pub fn new(max_size_mb: u64) -> Result<Self> {
+        Ok(Self {
+            dir: Self::default_dir()?,
+            max_size: max_size_mb * 1024 * 1024,
+        })
+    }
+
+    pub fn default_dir() -> Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .ok_or(anyhow!("Can't get cache dir"))?
+            .join("oculante")
+            .join("decoded"))
+    }
+
+    /// The cache file of an image. It changes when the file is modified.
+    fn entry(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.dir.join(format!("{}.qoi", file_key(path)?)))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<RgbaImage> {
+        let entry = self.entry(path)?;
+        let data = std::fs::read(&entry).ok()?;
+        let img = image::load_from_memory_with_format(&data, image::ImageFormat::Qoi).ok()?;
+        debug!("Disk cache hit for {}", path.display());
+        // pruning goes by modification time, so this keeps recently used entries
+        if let Ok(file) = std::fs::File::options().write(true).open(&entry) {
+            _ = file.set_modified(SystemTime::now());
+        }
+        Some(img.into_rgba8())
+    }
+
+    pub fn insert(&self, path: &Path, img: &RgbaImage) -> Result<()> {
+        if (img.width() as u64 * img.height() as u64) < Self::MIN_PIXELS {
+            return Ok(());
+        }
+        let entry = self.entry(path).ok_or(anyhow!("Can't create cache key"))?;
+        if entry.is_file() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        // an interrupted write never leaves a partial entry behind
+        let part = entry.with_extension("part");
+        img.save_with_format(&part, image::ImageFormat::Qoi)?;
+        std::fs::rename(part, entry)?;
+        self.prune()
+    }
+
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        std::fs::read_dir(&self.dir)
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|e| {
+                        let meta = e.metadata().ok()?;
+                        Some((e.path(), meta.len(), meta.modified().ok()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Drop the entry of an image, e.g. because the file is about to change
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        if let Some(entry) = self.entry(path).filter(|e| e.is_file()) {
+            std::fs::remove_file(entry)?;
+        }
+        Ok(())
+    }
+
+    /// Total size of all cached files in bytes
+    pub fn size(&self) -> u64 {
+        self.entries().iter().map(|e| e.1).sum()
+    }
+
+    /// Delete the least recently used files until the cache fits its size
+    pub fn prune(&self) -> Result<()> {
+        let mut entries = self.entries();
+        let mut size: u64 = entries.iter().map(|e| e.1).sum();
+        entries.sort_by_key(|e| e.2);
+        for (path, len, _) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            debug!("Disk cache limit hit, deleting {}", path.display());
+            std::fs::remove_file(path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        if self.dir.is_dir() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies a file along with its size and modification time, so cached data of a file is not
+/// used after it changes. The hash is the same for every build, so caches survive updates.
+fn file_key(path: &Path) -> Option<String> {
+    let meta = std::fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(
+        std::fs::canonicalize(path)
+            .ok()?
+            .to_string_lossy()
+            .as_bytes(),
+    );
+    hasher.update(modified.as_secs().to_le_bytes());
+    hasher.update(modified.subsec_nanos().to_le_bytes());
+    hasher.update(meta.len().to_le_bytes());
+    Some(
+        hasher.finalize()[..8]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+    )
+}
+
+/// Small results computed from image files, like scores or hashes, saved as JSON in the cache dir
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileCache<T> {
+    entries: HashMap<String, T>,
+    /// Where the cache is saved
+    #[serde(skip)]
+    file: Option<PathBuf>,
+    /// Files being computed right now
+    #[serde(skip)]
+    pending: HashSet<PathBuf>,
+    /// Keys of the files looked up so far. Computing one reads the file system, which is too slow
+    /// to repeat for each file of a folder whenever it is arranged.
+    #[serde(skip)]
+    keys: RefCell<HashMap<PathBuf, String>>,
+}
+
+impl<T> Default for FileCache<T> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+            file: Default::default(),
+            pending: Default::default(),
+            keys: Default::default(),
+        }
+    }
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> FileCache<T> {
+    /// A file named `name` in oculante's cache dir
+    pub fn default_file(name: &str) -> Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .ok_or(anyhow!("Can't get cache dir"))?
+            .join("oculante")
+            .join(name))
+    }
+
+    /// Load the cache from a file, or start an empty one that will be saved there
+    pub fn load(file: &Path) -> Self {
+        let mut cache = std::fs::File::open(file)
+            .ok()
+            .and_then(|f| serde_json::from_reader::<_, Self>(f).ok())
+            .unwrap_or_default();
+        debug!(
+            "Loaded {} entries from {}",
+            cache.entries.len(),
+            file.display()
+        );
+        cache.file = Some(file.to_path_buf());
+        cache
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = self.file.as_ref().context("The cache has no file")?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // an interrupted write never leaves a partial cache behind
+        let part = file.with_extension("part");
+        serde_json::to_writer(std::fs::File::create(&part)?, self)?;
+        std::fs::rename(part, file)?;
+        Ok(())
+    }
+
+    /// The key of `path`, computed once per run. Files changed since are only looked up under
+    /// their new key after a restart or an `insert`.
+    fn key(&self, path: &Path) -> Option<String> {
+        if let Some(key) = self.keys.borrow().get(path) {
+            return Some(key.clone());
+        }
+        let key = file_key(path)?;
+        self.keys
+            .borrow_mut()
+            .insert(path.to_path_buf(), key.clone());
+        Some(key)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<T> {
+        self.entries.get(&self.key(path)?).cloned()
+    }
+
+    pub fn insert(&mut self, path: &Path, value: T) {
+        self.pending.remove(path);
+        // the value belongs to the file as it is now
+        if let Some(key) = file_key(path) {
+            self.keys.get_mut().insert(path.to_path_buf(), key.clone());
+            self.entries.insert(key, value);
+        }
+    }
+
+    /// The files that are neither cached nor being computed. They are marked as pending.
+    pub fn claim_missing(&mut self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let missing = paths
+            .iter()
+            .filter(|path| !self.pending.contains(*path) && self.get(path).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        self.pending.extend(missing.iter().cloned());
+        missing
+    }
+}
+
+/// Results of `compute_files`
+pub type FileResults<T> = Vec<(PathBuf, T)>;
+
+/// Run `compute` on files on a thread, sending the results in batches of `batch` files. Files that
+/// fail are skipped. `polling` is dropped when all files are done.
+pub fn compute_files<T: Send + 'static>(
+    paths: Vec<PathBuf>,
+    compute: fn(&Path) -> Result<T>,
+    batch: usize,
+    sender: Sender<FileResults<T>>,
+    polling: Arc<()>,
+) {
+    std::thread::spawn(move || {
+        let _polling = polling;
+        for chunk in paths.chunks(batch.max(1)) {
+            let results = chunk
+                .iter()
+                .filter_map(|path| match compute(path) {
+                    Ok(result) => Some((path.clone(), result)),
+                    Err(e) => {
+                        warn!("Can't analyze {}: {e}", path.display());
+                        None
+                    }
+                })
+                .collect();
+            if sender.send(results).is_err() {
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,74 @@
+//! Channel packing for texture workflows: splitting an image into one grayscale file per channel,
+//! and combining up to four grayscale sources into the channels of one image.
+
+use anyhow::{bail, Result};
+use image::{GrayImage, RgbaImage};
+use std::path::{Path, PathBuf};
+
+/// Channel names, used as file name suffixes
+pub const CHANNELS: [&str; 4] = ["r", "g", "b", "a"];
+
+/// One grayscale image per channel, in RGBA order
+pub fn split(img: &RgbaImage) -> [GrayImage; 4] {
+    [0, 1, 2, 3].map(|c| {
+        GrayImage::from_fn(img.width(), img.height(), |x, y| {
+            image::Luma([img.get_pixel(x, y)[c]])
+        })
+    })
+}
+
+/// Where `split` results of `path` are saved: `<name>_r.png` and so on in `folder`
+pub fn split_paths(path: &Path, folder: &Path) -> [PathBuf; 4] {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    CHANNELS.map(|c| folder.join(format!("{stem}_{c}.png")))
+}
+
+/// Save the channels of `img` as grayscale PNG files
+pub fn save_split(img: &RgbaImage, paths: &[PathBuf; 4]) -> Result<()> {
+    for (channel, path) in split(img).iter().zip(paths) {
+        channel.save(path)?;
+    }
+    Ok(())
+}
+
+/// An image with a channel from each source, which must have the same size. Channels without a
+/// source are black, or opaque for alpha.
+pub fn combine(sources: &[Option<GrayImage>; 4]) -> Result<RgbaImage> {
+    let mut dimensions: Option<(u32, u32)> = None;
+    for source in sources.iter().flatten() {
+        match dimensions {
+            Some(d) if d != source.dimensions() => bail!(
+                "The sources have different sizes: {}x{} and {}x{}",
+                d.0,
+                d.1,
+                source.width(),
+                source.height()
+            ),
+            _ => dimensions = Some(source.dimensions()),
+        }
+    }
+    let (width, height) = match dimensions {
+        Some(d) => d,
+        None => bail!("No source selected"),
+    };
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let mut pixel = image::Rgba([0, 0, 0, 255]);
+        for (c, source) in sources.iter().enumerate() {
+            if let Some(source) = source {
+                pixel[c] = source.get_pixel(x, y)[0];
+            }
+        }
+        pixel
+    }))
+}
+
+/// State of the channel packing window
+#[derive(Debug, Default)]
+pub struct ChannelPacker {
+    pub enabled: bool,
+    /// Files to take the red, green, blue and alpha channels from, as grayscale
+    pub sources: [Option<PathBuf>; 4],
+}
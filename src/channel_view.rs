@@ -0,0 +1,358 @@
+//! Computed channel views: a per-pixel expression of the color channels, shown as a gray value.
+//!
+//! Expressions are compiled to a small stack program that `gpu::ChannelShader` evaluates while
+//! drawing. `ChannelExpr::apply` runs the same program on the CPU if the shader is unavailable,
+//! so both must stay in sync.
+
+use anyhow::{anyhow, bail, Result};
+use image::RgbaImage;
+use rayon::prelude::ParallelIterator;
+use rayon::slice::ParallelSliceMut;
+
+/// Maximum number of instructions the shader can evaluate
+pub const MAX_INSTRUCTIONS: usize = 64;
+/// Size of the shader's value stack
+pub const MAX_STACK: usize = 16;
+
+/// Names of the values an expression can use, in the order of their index
+const VARIABLES: [(&str, &str); 8] = [
+    ("r", "red"),
+    ("g", "green"),
+    ("b", "blue"),
+    ("a", "alpha"),
+    ("l", "luma"),
+    ("h", "hue"),
+    ("s", "saturation"),
+    ("c", "chroma"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Const(f32),
+    /// Push the value at this index of `VARIABLES`
+    Var(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    Abs,
+    Sqrt,
+    Min,
+    Max,
+    Pow,
+}
+
+impl Instruction {
+    /// The code of the instruction in the shader, and its argument
+    fn encode(&self) -> [f32; 2] {
+        match self {
+            Self::Const(value) => [0., *value],
+            Self::Var(index) => [1., *index as f32],
+            Self::Add => [2., 0.],
+            Self::Sub => [3., 0.],
+            Self::Mul => [4., 0.],
+            Self::Div => [5., 0.],
+            Self::Neg => [6., 0.],
+            Self::Abs => [7., 0.],
+            Self::Sqrt => [8., 0.],
+            Self::Min => [9., 0.],
+            Self::Max => [10., 0.],
+            Self::Pow => [11., 0.],
+        }
+    }
+
+    /// How many values the instruction takes from the stack
+    fn arity(&self) -> usize {
+        match self {
+            Self::Const(_) | Self::Var(_) => 0,
+            Self::Neg | Self::Abs | Self::Sqrt => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// The values of a pixel an expression can use: rgba, Rec. 709 luma, and HSV hue, saturation
+/// and chroma, all from 0 to 1
+pub fn pixel_variables(p: [f32; 4]) -> [f32; 8] {
+    let hi = p[0].max(p[1]).max(p[2]);
+    let lo = p[0].min(p[1]).min(p[2]);
+    let chroma = hi - lo;
+    let hue = if chroma <= 0. {
+        0.
+    } else if hi == p[0] {
+        ((p[1] - p[2]) / chroma).rem_euclid(6.)
+    } else if hi == p[1] {
+        (p[2] - p[0]) / chroma + 2.
+    } else {
+        (p[0] - p[1]) / chroma + 4.
+    };
+    let saturation = if hi > 0. { chroma / hi } else { 0. };
+    let luma = 0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2];
+    [p[0], p[1], p[2], p[3], luma, hue / 6., saturation, chroma]
+}
+
+/// A compiled channel expression, like `r - g` or `max(r, b) * a`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelExpr {
+    pub program: Vec<Instruction>,
+}
+
+impl ChannelExpr {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+            program: vec![],
+        };
+        if parser.tokens.is_empty() {
+            bail!("The expression is empty");
+        }
+        parser.expr()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            bail!("Unexpected {token}");
+        }
+        let program = parser.program;
+        if program.len() > MAX_INSTRUCTIONS {
+            bail!("The expression is too long");
+        }
+        let mut depth = 0_usize;
+        for instruction in &program {
+            depth = depth - instruction.arity() + 1;
+            if depth > MAX_STACK {
+                bail!("The expression is nested too deeply");
+            }
+        }
+        Ok(Self { program })
+    }
+
+    /// Lambert shading of a tangent space normal map lit from `light`. With `green_down`, green
+    /// points down like in DirectX normal maps.
+    pub fn normal_lighting(light: [f32; 3], green_down: bool) -> Self {
+        let x = "(r * 2 - 1)";
+        let y = if green_down {
+            "(1 - g * 2)"
+        } else {
+            "(g * 2 - 1)"
+        };
+        let z = "(b * 2 - 1)";
+        let expr = format!(
+            "max(({x} * {} + {y} * {} + {z} * {}) / sqrt({x} ^ 2 + {y} ^ 2 + {z} ^ 2 + 0.000001), 0)",
+            light[0], light[1], light[2]
+        );
+        // fits into the shader and only uses known names
+        Self::parse(&expr).unwrap_or(Self { program: vec![] })
+    }
+
+    /// The value of the expression for a pixel with channels from 0 to 1
+    pub fn eval(&self, p: [f32; 4]) -> f32 {
+        let vars = pixel_variables(p);
+        let mut stack: Vec<f32> = Vec::with_capacity(MAX_STACK);
+        for instruction in &self.program {
+            let value = match instruction {
+                Instruction::Const(value) => *value,
+                Instruction::Var(index) => vars[*index],
+                Instruction::Neg => -stack.pop().unwrap_or_default(),
+                Instruction::Abs => stack.pop().unwrap_or_default().abs(),
+                Instruction::Sqrt => stack.pop().unwrap_or_default().max(0.).sqrt(),
+                binary => {
+                    let b = stack.pop().unwrap_or_default();
+                    let a = stack.pop().unwrap_or_default();
+                    match binary {
+                        Instruction::Add => a + b,
+                        Instruction::Sub => a - b,
+                        Instruction::Mul => a * b,
+                        Instruction::Div => a / b,
+                        Instruction::Min => a.min(b),
+                        Instruction::Max => a.max(b),
+                        _ => a.powf(b),
+                    }
+                }
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap_or_default()
+    }
+
+    /// The program as shader uniforms: a vec4 with the instruction count, then one vec4
+    /// `[code, argument, 0, 0]` per instruction
+    pub fn encode(&self) -> Vec<f32> {
+        let mut data = vec![self.program.len() as f32, 0., 0., 0.];
+        for instruction in &self.program {
+            data.extend_from_slice(&instruction.encode());
+            data.extend_from_slice(&[0., 0.]);
+        }
+        data
+    }
+
+    /// The image as a gray view of the expression, clamped to 0 - 1
+    pub fn apply(&self, img: &RgbaImage) -> RgbaImage {
+        let mut updated_img = img.clone();
+        updated_img.par_chunks_mut(4).for_each(|pixel| {
+            let p = [0, 1, 2, 3].map(|i| pixel[i] as f32 / 255.);
+            let value = (self.eval(p).clamp(0., 1.) * 255.).round() as u8;
+            pixel[0] = value;
+            pixel[1] = value;
+            pixel[2] = value;
+            pixel[3] = 255;
+        });
+        updated_img
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Name(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Name(name) => write!(f, "{name}"),
+            Self::Symbol(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                number.push(c);
+                chars.next();
+            }
+            let value = number
+                .parse()
+                .map_err(|_| anyhow!("{number} is not a number"))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Name(name.to_lowercase()));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            bail!("Unexpected {c}");
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser emitting instructions in postfix order
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    program: Vec<Instruction>,
+}
+
+impl Parser {
+    fn next_symbol(&mut self, symbols: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(c)) if symbols.contains(*c) => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        self.next_symbol(&symbol.to_string())
+            .map(|_| ())
+            .ok_or(anyhow!("Missing {symbol}"))
+    }
+
+    fn expr(&mut self) -> Result<()> {
+        self.term()?;
+        while let Some(op) = self.next_symbol("+-") {
+            self.term()?;
+            self.program.push(match op {
+                '+' => Instruction::Add,
+                _ => Instruction::Sub,
+            });
+        }
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<()> {
+        self.unary()?;
+        while let Some(op) = self.next_symbol("*/") {
+            self.unary()?;
+            self.program.push(match op {
+                '*' => Instruction::Mul,
+                _ => Instruction::Div,
+            });
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<()> {
+        if self.next_symbol("-").is_some() {
+            self.unary()?;
+            self.program.push(Instruction::Neg);
+            return Ok(());
+        }
+        self.atom()?;
+        if self.next_symbol("^").is_some() {
+            self.unary()?;
+            self.program.push(Instruction::Pow);
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self) -> Result<()> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(anyhow!("The expression ends early"))?;
+        self.pos += 1;
+        match token {
+            Token::Number(value) => self.program.push(Instruction::Const(value)),
+            Token::Symbol('(') => {
+                self.expr()?;
+                self.expect(')')?;
+            }
+            Token::Name(name) => {
+                if let Some(index) = VARIABLES
+                    .iter()
+                    .position(|(short, long)| name == *short || name == *long)
+                {
+                    self.program.push(Instruction::Var(index));
+                    return Ok(());
+                }
+                let (function, args) = match name.as_str() {
+                    "abs" => (Instruction::Abs, 1),
+                    "sqrt" => (Instruction::Sqrt, 1),
+                    "min" => (Instruction::Min, 2),
+                    "max" => (Instruction::Max, 2),
+                    "pow" => (Instruction::Pow, 2),
+                    _ => bail!("Unknown name {name}"),
+                };
+                self.expect('(')?;
+                for i in 0..args {
+                    if i > 0 {
+                        self.expect(',')?;
+                    }
+                    self.expr()?;
+                }
+                self.expect(')')?;
+                self.program.push(function);
+            }
+            Token::Symbol(c) => bail!("Unexpected {c}"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! SHA-256 checksums of image files, and other files below the open folder with the same content.
+
+use crate::cache::FileCache;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Name of the cache file
+pub const CACHE_FILE: &str = "checksums.json";
+
+/// Hex encoded SHA-256 of files
+pub type ChecksumCache = FileCache<String>;
+
+/// The hex encoded SHA-256 of the contents of a file
+pub fn sha256(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// The checksum of a file and its duplicates
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChecksum {
+    pub path: PathBuf,
+    pub sha256: String,
+    /// Other files among the candidates with the same content
+    pub duplicates: Vec<PathBuf>,
+}
+
+impl FileChecksum {
+    /// Hash `path`, and those of the `candidates` that have the same size. Hashes are reused from
+    /// `cache` as long as the files are unchanged.
+    pub fn compute(path: &Path, candidates: &[PathBuf], cache: &mut ChecksumCache) -> Result<Self> {
+        let mut hash = |path: &Path| -> Result<String> {
+            if let Some(hash) = cache.get(path) {
+                return Ok(hash);
+            }
+            let hash = sha256(path)?;
+            cache.insert(path, hash.clone());
+            Ok(hash)
+        };
+        let sha256 = hash(path)?;
+        let size = std::fs::metadata(path)?.len();
+        let canonical = std::fs::canonicalize(path)?;
+        let duplicates = candidates
+            .iter()
+            .filter(|p| std::fs::metadata(p).map(|m| m.len()).ok() == Some(size))
+            .filter(|p| std::fs::canonicalize(p).ok().as_ref() != Some(&canonical))
+            .filter(|p| hash(p).ok().as_ref() == Some(&sha256))
+            .cloned()
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            sha256,
+            duplicates,
+        })
+    }
+}
+
+/// Checksum of the current image for the info panel
+#[derive(Debug, Default)]
+pub struct ChecksumView {
+    /// The file the checksum is for
+    pub path: Option<PathBuf>,
+    /// The checksum, or why it could not be computed
+    pub checksum: Option<Result<FileChecksum, String>>,
+}
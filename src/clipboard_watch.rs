@@ -0,0 +1,100 @@
+//! Watching the clipboard: images copied in any app are shown as they arrive, and kept in a
+//! history, e.g. to collect references.
+
+use crate::redraw::Waker;
+use image::RgbaImage;
+use log::debug;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the clipboard is read
+const INTERVAL: Duration = Duration::from_millis(500);
+/// Images kept in the history, older ones are dropped
+pub const HISTORY_SIZE: usize = 20;
+
+/// Tells images apart without keeping a copy to compare with
+pub fn fingerprint(img: &RgbaImage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    img.dimensions().hash(&mut hasher);
+    img.as_raw().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn clipboard_image(clipboard: &mut arboard::Clipboard) -> Option<RgbaImage> {
+    let data = clipboard.get_image().ok()?;
+    RgbaImage::from_raw(
+        data.width as u32,
+        data.height as u32,
+        data.bytes.into_owned(),
+    )
+}
+
+/// Send images copied from now on until the returned flag is set. What is on the clipboard
+/// already is skipped. `waker` wakes the app for each image.
+pub fn watch(sender: Sender<RgbaImage>, waker: Waker) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || {
+        let mut clipboard = match arboard::Clipboard::new() {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                log::error!("Can't watch the clipboard: {e}");
+                return;
+            }
+        };
+        let mut last = clipboard_image(&mut clipboard).map(|img| fingerprint(&img));
+        while !stopped.load(Ordering::Relaxed) {
+            thread::sleep(INTERVAL);
+            if let Some(img) = clipboard_image(&mut clipboard) {
+                let print = fingerprint(&img);
+                if last != Some(print) {
+                    last = Some(print);
+                    if sender.send(img).is_err() {
+                        break;
+                    }
+                    waker.wake();
+                }
+            }
+        }
+        debug!("Stopped watching the clipboard");
+    });
+    stop
+}
+
+/// State of the clipboard watcher window
+#[derive(Debug, Default)]
+pub struct ClipboardWatcher {
+    pub enabled: bool,
+    /// Set to stop the watching thread
+    pub watching: Option<Arc<AtomicBool>>,
+    /// Copied images, newest first
+    pub history: Vec<RgbaImage>,
+    /// The history entry that is shown
+    pub selected: Option<usize>,
+}
+
+impl ClipboardWatcher {
+    pub fn is_watching(&self) -> bool {
+        self.watching.is_some()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.watching.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Put an image at the front of the history. An image that was copied before moves there.
+    pub fn add(&mut self, img: RgbaImage) {
+        let print = fingerprint(&img);
+        self.history.retain(|entry| fingerprint(entry) != print);
+        self.history.insert(0, img);
+        self.history.truncate(HISTORY_SIZE);
+        self.selected = Some(0);
+    }
+}
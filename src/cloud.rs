@@ -0,0 +1,648 @@
+//! Images in cloud object storage, like dataset samples in S3 or Google Cloud Storage buckets.
+//! Objects are downloaded on demand into a mirror in the cache, like files of remote folders, and
+//! large ones in parallel ranges. Requests are signed with AWS Signature Version 4, which GCS
+//! accepts with HMAC keys through its XML API.
+
+use crate::utils::is_ext_compatible;
+use anyhow::{anyhow, bail, Result};
+use hmac::{Hmac, Mac};
+use log::debug;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// Objects larger than this are downloaded in parts of this size
+const PART_SIZE: u64 = 8 << 20;
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Provider {
+    S3,
+    Gcs,
+}
+
+impl Provider {
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Self::S3 => "s3",
+            Self::Gcs => "gs",
+        }
+    }
+
+    fn from_scheme(scheme: &str) -> Option<Self> {
+        match scheme {
+            "s3" => Some(Self::S3),
+            "gs" => Some(Self::Gcs),
+            _ => None,
+        }
+    }
+}
+
+/// An object like `s3://bucket/key`, or all objects below a prefix ending in `/`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectUri {
+    pub provider: Provider,
+    pub bucket: String,
+    pub key: String,
+}
+
+impl ObjectUri {
+    pub fn is_uri(uri: &str) -> bool {
+        uri.trim().starts_with("s3://") || uri.trim().starts_with("gs://")
+    }
+
+    pub fn parse(uri: &str) -> Result<Self> {
+        let (scheme, rest) = uri
+            .trim()
+            .split_once("://")
+            .ok_or_else(|| anyhow!("{uri} is no URI"))?;
+        let provider = Provider::from_scheme(scheme)
+            .ok_or_else(|| anyhow!("Only s3:// and gs:// URIs are supported"))?;
+        let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            bail!("No bucket in {uri}");
+        }
+        if key.split('/').any(|c| c == "." || c == "..") {
+            bail!("The key of {uri} is not supported");
+        }
+        Ok(Self {
+            provider,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    pub fn uri(&self) -> String {
+        format!("{}://{}/{}", self.provider.scheme(), self.bucket, self.key)
+    }
+
+    /// Whether this names a prefix rather than an image
+    pub fn is_folder(&self) -> bool {
+        self.key.is_empty() || self.key.ends_with('/') || !is_ext_compatible(Path::new(&self.key))
+    }
+
+    /// The prefix the object is in
+    pub fn folder(&self) -> Self {
+        let key = match self.key.trim_end_matches('/').rfind('/') {
+            Some(end) => self.key[..=end].to_string(),
+            None => String::new(),
+        };
+        Self {
+            key,
+            ..self.clone()
+        }
+    }
+
+    /// Where objects are mirrored
+    pub fn mirror_root() -> Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .ok_or(anyhow!("Can't get cache dir"))?
+            .join("oculante")
+            .join("cloud"))
+    }
+
+    /// The local path of the object, or folder of a prefix
+    pub fn mirror(&self) -> Result<PathBuf> {
+        let mut path = Self::mirror_root()?
+            .join(self.provider.scheme())
+            .join(&self.bucket);
+        for component in self.key.split('/').filter(|c| !c.is_empty()) {
+            path.push(component);
+        }
+        Ok(path)
+    }
+
+    /// The object or prefix a local path is the mirror of
+    pub fn from_mirror(path: &Path, is_folder: bool) -> Option<Self> {
+        let relative = path.strip_prefix(Self::mirror_root().ok()?).ok()?;
+        let mut components = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string());
+        let provider = Provider::from_scheme(&components.next()?)?;
+        let bucket = components.next()?;
+        let mut key = components.collect::<Vec<_>>().join("/");
+        if is_folder && !key.is_empty() {
+            key.push('/');
+        }
+        Some(Self {
+            provider,
+            bucket,
+            key,
+        })
+    }
+}
+
+/// Keys from the environment, or the AWS credentials file for S3. GCS takes HMAC keys in
+/// `GCS_ACCESS_KEY_ID` and `GCS_SECRET_ACCESS_KEY`, or an OAuth token in
+/// `GOOGLE_OAUTH_ACCESS_TOKEN`, e.g. from `gcloud auth print-access-token`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+    pub bearer_token: Option<String>,
+}
+
+impl Credentials {
+    /// `None` if no credentials are configured, so requests are anonymous
+    pub fn load(provider: Provider) -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        match provider {
+            Provider::S3 => {
+                if let (Some(access_key), Some(secret_key)) =
+                    (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+                {
+                    return Some(Self {
+                        access_key,
+                        secret_key,
+                        session_token: var("AWS_SESSION_TOKEN"),
+                        bearer_token: None,
+                    });
+                }
+                let file = std::fs::read_to_string(aws_file("credentials")?).ok()?;
+                let profile = aws_profile(&file, &var("AWS_PROFILE").unwrap_or("default".into()));
+                let value = |key: &str| {
+                    profile
+                        .iter()
+                        .find(|(k, _)| k == key)
+                        .map(|(_, v)| v.clone())
+                };
+                Some(Self {
+                    access_key: value("aws_access_key_id")?,
+                    secret_key: value("aws_secret_access_key")?,
+                    session_token: value("aws_session_token"),
+                    bearer_token: None,
+                })
+            }
+            Provider::Gcs => {
+                if let Some(token) = var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+                    return Some(Self {
+                        bearer_token: Some(token),
+                        ..Default::default()
+                    });
+                }
+                Some(Self {
+                    access_key: var("GCS_ACCESS_KEY_ID")?,
+                    secret_key: var("GCS_SECRET_ACCESS_KEY")?,
+                    ..Default::default()
+                })
+            }
+        }
+    }
+}
+
+fn aws_file(name: &str) -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".aws").join(name))
+}
+
+/// The keys of a profile in an AWS config or credentials file
+pub fn aws_profile(file: &str, profile: &str) -> Vec<(String, String)> {
+    let mut values = vec![];
+    let mut current = false;
+    for line in file.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let section = section.trim();
+            // the config file prefixes profiles other than the default one
+            current = section == profile || section.strip_prefix("profile ") == Some(profile);
+        } else if let Some((key, value)) = line.split_once('=').filter(|_| current) {
+            values.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    values
+}
+
+fn region(provider: Provider) -> String {
+    if provider == Provider::Gcs {
+        return "auto".into();
+    }
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    var("AWS_REGION")
+        .or_else(|| var("AWS_DEFAULT_REGION"))
+        .or_else(|| {
+            let file = std::fs::read_to_string(aws_file("config")?).ok()?;
+            aws_profile(&file, &var("AWS_PROFILE").unwrap_or("default".into()))
+                .into_iter()
+                .find(|(k, _)| k == "region")
+                .map(|(_, v)| v)
+        })
+        .unwrap_or("us-east-1".into())
+}
+
+/// Percent encoding as the signature expects it. Slashes are kept in paths.
+pub fn uri_encode(value: &str, keep_slash: bool) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if keep_slash => "/".into(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The time as `20130524T000000Z`
+pub fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    // the civil date of a day count, by Howard Hinnant
+    let z = secs.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let time = secs.rem_euclid(86400);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// The `Authorization` header of a request. `headers` are signed and must include `host`,
+/// `x-amz-content-sha256` and `x-amz-date`.
+pub fn sign(
+    method: &str,
+    path: &str,
+    query: &[(&str, &str)],
+    headers: &[(&str, &str)],
+    credentials: &Credentials,
+    region: &str,
+) -> String {
+    let mut query: Vec<String> = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", uri_encode(k, false), uri_encode(v, false)))
+        .collect();
+    query.sort();
+    let mut headers: Vec<(String, String)> = headers
+        .iter()
+        .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+        .collect();
+    headers.sort();
+    let value = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical = format!(
+        "{method}\n{}\n{}\n{}\n{signed_headers}\n{}",
+        uri_encode(path, true),
+        query.join("&"),
+        headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{v}\n"))
+            .collect::<String>(),
+        value("x-amz-content-sha256"),
+    );
+    let date = value("x-amz-date");
+    let scope = format!("{}/{region}/s3/aws4_request", &date[..8.min(date.len())]);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical.as_bytes()))
+    );
+    let mut key = format!("AWS4{}", credentials.secret_key).into_bytes();
+    for part in scope.split('/') {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={}",
+        credentials.access_key,
+        hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+    )
+}
+
+/// Send a request for the object or bucket of `uri`. S3 buckets in another region are asked
+/// again there.
+fn request(
+    method: reqwest::Method,
+    uri: &ObjectUri,
+    query: &[(&str, &str)],
+    range: Option<(u64, u64)>,
+) -> Result<reqwest::blocking::Response> {
+    let credentials = Credentials::load(uri.provider);
+    let mut region = region(uri.provider);
+    for _ in 0..2 {
+        let endpoint = std::env::var("AWS_ENDPOINT_URL_S3")
+            .or_else(|_| std::env::var("AWS_ENDPOINT_URL"))
+            .ok()
+            .filter(|_| uri.provider == Provider::S3);
+        // custom endpoints, like MinIO, and GCS address buckets by path
+        let (base, host, path) = match (&endpoint, uri.provider) {
+            (Some(endpoint), _) => {
+                let endpoint = endpoint.trim_end_matches('/');
+                let host = endpoint
+                    .split_once("://")
+                    .map(|(_, h)| h)
+                    .unwrap_or(endpoint);
+                (
+                    endpoint.to_string(),
+                    host.to_string(),
+                    format!("/{}/{}", uri.bucket, uri.key),
+                )
+            }
+            (None, Provider::Gcs) => (
+                "https://storage.googleapis.com".into(),
+                "storage.googleapis.com".into(),
+                format!("/{}/{}", uri.bucket, uri.key),
+            ),
+            (None, Provider::S3) => {
+                let host = format!("{}.s3.{region}.amazonaws.com", uri.bucket);
+                (format!("https://{host}"), host, format!("/{}", uri.key))
+            }
+        };
+        let mut url = format!("{base}{}", uri_encode(&path, true));
+        if !query.is_empty() {
+            url += "?";
+            url += &query
+                .iter()
+                .map(|(k, v)| format!("{}={}", uri_encode(k, false), uri_encode(v, false)))
+                .collect::<Vec<_>>()
+                .join("&");
+        }
+        let date = amz_date(SystemTime::now());
+        let range = range.map(|(start, end)| format!("bytes={start}-{end}"));
+        let mut headers = vec![
+            ("host", host.as_str()),
+            ("x-amz-content-sha256", EMPTY_SHA256),
+            ("x-amz-date", date.as_str()),
+        ];
+        if let Some(range) = &range {
+            headers.push(("range", range));
+        }
+        let mut builder = reqwest::blocking::Client::new()
+            .request(method.clone(), &url)
+            .header(reqwest::header::USER_AGENT, "oculante");
+        match &credentials {
+            Some(Credentials {
+                bearer_token: Some(token),
+                ..
+            }) => builder = builder.bearer_auth(token),
+            Some(credentials) => {
+                if let Some(token) = &credentials.session_token {
+                    headers.push(("x-amz-security-token", token));
+                }
+                let authorization = sign(
+                    method.as_str(),
+                    &path,
+                    query,
+                    &headers,
+                    credentials,
+                    &region,
+                );
+                builder = builder.header(reqwest::header::AUTHORIZATION, authorization);
+            }
+            None => (),
+        }
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            builder = builder.header(*name, *value);
+        }
+        let response = builder.send()?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let bucket_region = response
+            .headers()
+            .get("x-amz-bucket-region")
+            .and_then(|r| r.to_str().ok())
+            .map(|r| r.to_string());
+        match bucket_region {
+            Some(bucket_region) if bucket_region != region && endpoint.is_none() => {
+                debug!("{} is in {bucket_region}", uri.bucket);
+                region = bucket_region;
+            }
+            _ => {
+                let status = response.status();
+                let body = response.text().unwrap_or_default();
+                let message = error_message(&body);
+                bail!(
+                    "{} answered {status}{}",
+                    uri.uri(),
+                    message.map(|m| format!(": {m}")).unwrap_or_default()
+                );
+            }
+        }
+    }
+    bail!("{} is not reachable in any region", uri.uri())
+}
+
+/// Call `visit` with the names of the enclosing elements and the unescaped text of each text node,
+/// and with `None` when an element ends
+fn read_xml(xml: &str, mut visit: impl FnMut(&[Vec<u8>], Option<String>)) -> Result<()> {
+    let mut reader = Reader::from_str(xml);
+    let mut buffer = vec![];
+    let mut elements = vec![];
+    loop {
+        match reader.read_event(&mut buffer)? {
+            Event::Start(e) => elements.push(e.name().to_vec()),
+            Event::End(_) => {
+                visit(&elements, None);
+                elements.pop();
+            }
+            Event::Text(e) => visit(&elements, Some(e.unescape_and_decode(&reader)?)),
+            Event::Eof => break,
+            _ => (),
+        }
+        buffer.clear();
+    }
+    Ok(())
+}
+
+/// Whether the innermost of `elements` are `names`
+fn is_in(elements: &[Vec<u8>], names: &[&[u8]]) -> bool {
+    elements.len() >= names.len()
+        && elements[elements.len() - names.len()..]
+            .iter()
+            .zip(names)
+            .all(|(element, name)| element == name)
+}
+
+/// The message of an error response
+fn error_message(xml: &str) -> Option<String> {
+    let mut message = None;
+    _ = read_xml(xml, |elements, text| {
+        if is_in(elements, &[b"Error", b"Message"]) {
+            message = message.take().or(text);
+        }
+    });
+    message
+}
+
+/// The keys and sizes of the objects in a page of a bucket listing, and the token of the next
+pub type Listing = (Vec<(String, u64)>, Option<String>);
+
+/// Parse a page of a bucket listing
+pub fn parse_listing(xml: &str) -> Result<Listing> {
+    let mut objects = vec![];
+    let mut next = None;
+    let (mut key, mut size) = (None, None);
+    read_xml(xml, |elements, text| match text {
+        Some(text) if is_in(elements, &[b"Contents", b"Key"]) => key = Some(text),
+        Some(text) if is_in(elements, &[b"Contents", b"Size"]) => size = text.trim().parse().ok(),
+        Some(text) if is_in(elements, &[b"NextContinuationToken"]) => next = Some(text),
+        None if is_in(elements, &[b"Contents"]) => {
+            if let (Some(key), Some(size)) = (key.take(), size.take()) {
+                objects.push((key, size));
+            }
+        }
+        _ => (),
+    })?;
+    Ok((objects, next))
+}
+
+/// Whether `name` is a single file name, which can't lead out of the folder it is joined to
+pub fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !name.contains('/')
+}
+
+/// The images directly below a prefix as paths in the mirror. Downloaded objects whose size
+/// changed are removed, so they are downloaded again.
+pub fn list_images(folder: &ObjectUri) -> Result<Vec<PathBuf>> {
+    let mirror = folder.mirror()?;
+    std::fs::create_dir_all(&mirror)?;
+    let mut images = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let mut query = vec![
+            ("list-type", "2"),
+            ("prefix", folder.key.as_str()),
+            ("delimiter", "/"),
+        ];
+        if let Some(token) = &token {
+            query.push(("continuation-token", token));
+        }
+        let listing = request(
+            reqwest::Method::GET,
+            &ObjectUri {
+                key: String::new(),
+                ..folder.clone()
+            },
+            &query,
+            None,
+        )?
+        .text()?;
+        let (objects, next) = parse_listing(&listing)?;
+        for (key, size) in objects {
+            // keys like `../x.png` must not leave the mirror
+            let name = match key.strip_prefix(folder.key.as_str()) {
+                Some(name) if is_file_name(name) => name,
+                _ => continue,
+            };
+            let path = mirror.join(name);
+            if !is_ext_compatible(&path) {
+                continue;
+            }
+            let cached = std::fs::metadata(&path).map(|m| m.len()).ok();
+            if cached.map(|cached| cached != size).unwrap_or_default() {
+                debug!("{key} changed in the bucket");
+                _ = std::fs::remove_file(&path);
+            }
+            images.push(path);
+        }
+        token = next;
+        if token.is_none() {
+            break;
+        }
+    }
+    images.sort_unstable_by(|a, b| {
+        lexical_sort::natural_lexical_cmp(
+            &a.file_name().unwrap_or_default().to_string_lossy(),
+            &b.file_name().unwrap_or_default().to_string_lossy(),
+        )
+    });
+    Ok(images)
+}
+
+/// The byte ranges, both ends included, an object of `size` is downloaded in
+pub fn parts(size: u64) -> Vec<(u64, u64)> {
+    (0..size)
+        .step_by(PART_SIZE as usize)
+        .map(|start| (start, (start + PART_SIZE).min(size) - 1))
+        .collect()
+}
+
+/// Download the object to `target`, large ones in parallel ranges
+fn download(uri: &ObjectUri, target: &Path) -> Result<()> {
+    let size = request(reqwest::Method::HEAD, uri, &[], None)?
+        .content_length()
+        .unwrap_or_default();
+    let data = if size > PART_SIZE {
+        debug!("Downloading {} in {} parts", uri.uri(), parts(size).len());
+        parts(size)
+            .into_par_iter()
+            .map(|range| Ok(request(reqwest::Method::GET, uri, &[], Some(range))?.bytes()?))
+            .collect::<Result<Vec<_>>>()?
+            .concat()
+    } else {
+        request(reqwest::Method::GET, uri, &[], None)?
+            .bytes()?
+            .to_vec()
+    };
+    std::fs::write(target, data)?;
+    Ok(())
+}
+
+/// List the prefix if `path` is in the mirror, or the prefix of an object in it
+pub fn list_mirror(path: &Path) -> Option<Result<Vec<PathBuf>>> {
+    let folder = if path.is_dir() { path } else { path.parent()? };
+    ObjectUri::from_mirror(folder, true).map(|folder| list_images(&folder))
+}
+
+/// Download `path` if it is an object in the mirror. Returns `None` for other paths.
+pub fn fetch(path: &Path) -> Option<Result<()>> {
+    let uri = ObjectUri::from_mirror(path, false)?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    debug!("Downloading {}", uri.uri());
+    // a partly downloaded object is never taken for a complete one
+    let part = path.with_file_name(format!(".{name}.part"));
+    Some(download(&uri, &part).and_then(|_| Ok(std::fs::rename(&part, path)?)))
+}
+
+/// List the prefix of `uri`. Returns the images and the one it names, or the first.
+pub fn open(uri: &str) -> Result<(Vec<PathBuf>, usize)> {
+    let uri = ObjectUri::parse(uri)?;
+    if uri.is_folder() {
+        let folder = ObjectUri {
+            key: format!("{}/", uri.key.trim_end_matches('/'))
+                .trim_start_matches('/')
+                .to_string(),
+            ..uri
+        };
+        return Ok((list_images(&folder)?, 0));
+    }
+    let images = list_images(&uri.folder())?;
+    let object = uri.mirror()?;
+    let index = images.iter().position(|p| *p == object).unwrap_or_default();
+    Ok((images, index))
+}
@@ -0,0 +1,195 @@
+//! False color display of single channel data images, like 16 bit depth or sensor data.
+//!
+//! The samples are kept at their full precision, so the range mapped to the colormap can be
+//! changed interactively.
+
+use anyhow::Result;
+use image::{ColorType, RgbaImage};
+use std::path::{Path, PathBuf};
+use strum::Display;
+use strum_macros::EnumIter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumIter)]
+pub enum Colormap {
+    Gray,
+    Viridis,
+    Magma,
+    Turbo,
+    Jet,
+}
+
+/// Sixth degree polynomial fits of the matplotlib colormaps
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_3, 0.005_407_344, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_5, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_03],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+const MAGMA: [[f32; 3]; 7] = [
+    [-0.002_136_485, -0.000_749_655, -0.005_386_128],
+    [0.251_660_54, 0.677_523_24, 2.494_026_6],
+    [8.353_717, -3.577_719_5, 0.314_467_9],
+    [-27.668_733, 14.264_731, -13.649_213],
+    [52.176_14, -27.943_607, 12.944_169],
+    [-50.768_524, 29.046_583, 4.234_153],
+    [18.655_705, -11.489_774, -5.601_961_5],
+];
+/// Fifth degree polynomial fit of Turbo
+const TURBO: [[f32; 3]; 6] = [
+    [0.135_721_38, 0.091_402_61, 0.106_673_3],
+    [4.615_392_6, 2.194_188_4, 12.641_946],
+    [-42.660_324, 4.842_966_6, -60.582_047],
+    [132.131_08, -14.185_033, 110.362_77],
+    [-152.942_4, 4.277_299, -89.903_11],
+    [59.286_38, 2.829_566, 27.348_25],
+];
+
+fn polynomial(coefficients: &[[f32; 3]], v: f32) -> [f32; 3] {
+    let mut c = [0.; 3];
+    for (i, channel) in c.iter_mut().enumerate() {
+        *channel = coefficients
+            .iter()
+            .rev()
+            .fold(0., |acc, coefficient| acc * v + coefficient[i]);
+    }
+    c
+}
+
+impl Colormap {
+    /// The color of a value from 0 to 1
+    pub fn color(&self, v: f32) -> [u8; 3] {
+        let v = v.clamp(0., 1.);
+        let c = match self {
+            Self::Gray => [v; 3],
+            Self::Viridis => polynomial(&VIRIDIS, v),
+            Self::Magma => polynomial(&MAGMA, v),
+            Self::Turbo => polynomial(&TURBO, v),
+            Self::Jet => [3., 2., 1.].map(|offset| 1.5 - (4. * v - offset).abs()),
+        };
+        c.map(|c| (c.clamp(0., 1.) * 255.).round() as u8)
+    }
+
+    /// Colors of 256 evenly spaced values
+    pub fn lut(&self) -> Vec<[u8; 3]> {
+        (0..256).map(|i| self.color(i as f32 / 255.)).collect()
+    }
+}
+
+/// The samples of a single channel image
+#[derive(Debug, Clone, Default)]
+pub struct DataImage {
+    pub width: u32,
+    pub height: u32,
+    pub samples: Vec<f32>,
+    /// Lowest and highest sample value
+    pub range: (f32, f32),
+    /// Highest value of the sample type, like 65535 for 16 bit
+    pub max: f32,
+}
+
+impl DataImage {
+    /// Read a grayscale image, or `None` if it has color
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let img = image::io::Reader::open(path)?
+            .with_guessed_format()?
+            .decode()?;
+        let max = match img.color() {
+            ColorType::L8 | ColorType::La8 => u8::MAX as f32,
+            ColorType::L16 | ColorType::La16 => u16::MAX as f32,
+            _ => return Ok(None),
+        };
+        let img = img.into_luma16();
+        // 8 bit samples are scaled to 16 bit above
+        let scale = max / u16::MAX as f32;
+        let samples = img
+            .iter()
+            .map(|v| (*v as f32 * scale).round())
+            .collect::<Vec<_>>();
+        Ok(Some(Self::new(img.width(), img.height(), samples, max)))
+    }
+
+    pub fn new(width: u32, height: u32, samples: Vec<f32>, max: f32) -> Self {
+        let range = samples
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            });
+        Self {
+            width,
+            height,
+            samples,
+            range,
+            max,
+        }
+    }
+
+    /// Map the samples from `low` to `high` to the colormap
+    pub fn render(&self, colormap: Colormap, low: f32, high: f32) -> RgbaImage {
+        let lut = colormap.lut();
+        let span = (high - low).max(f32::EPSILON);
+        let mut img = RgbaImage::new(self.width, self.height);
+        for (pixel, v) in img.pixels_mut().zip(&self.samples) {
+            let [r, g, b] = lut[(((v - low) / span).clamp(0., 1.) * 255.).round() as usize];
+            *pixel = image::Rgba([r, g, b, 255]);
+        }
+        img
+    }
+}
+
+/// Interactive colormap of the current single channel image
+#[derive(Debug, Clone)]
+pub struct DataView {
+    pub path: PathBuf,
+    /// None if the image has color
+    pub image: Option<DataImage>,
+    pub colormap: Colormap,
+    /// Map the range of the samples instead of `low` to `high`
+    pub autoscale: bool,
+    pub low: f32,
+    pub high: f32,
+    /// The image is displayed through this view
+    pub active: bool,
+}
+
+impl DataView {
+    /// Read the image at `path`, keeping the settings of the `previous` view
+    pub fn new(path: &Path, previous: Option<&DataView>) -> Self {
+        let image = DataImage::open(path).ok().flatten();
+        let mut view = Self {
+            path: path.to_path_buf(),
+            colormap: Colormap::Viridis,
+            autoscale: true,
+            low: 0.,
+            high: image.as_ref().map(|i| i.max).unwrap_or(1.),
+            active: false,
+            image,
+        };
+        if let Some(previous) = previous {
+            view.colormap = previous.colormap;
+            view.autoscale = previous.autoscale;
+            view.active = previous.active;
+            if !previous.autoscale {
+                (view.low, view.high) = (previous.low, previous.high);
+            }
+        }
+        view
+    }
+
+    /// The range of sample values mapped to the colormap
+    pub fn range(&self) -> Option<(f32, f32)> {
+        let image = self.image.as_ref()?;
+        Some(if self.autoscale {
+            image.range
+        } else {
+            (self.low, self.high)
+        })
+    }
+
+    pub fn render(&self) -> Option<RgbaImage> {
+        let (low, high) = self.range()?;
+        Some(self.image.as_ref()?.render(self.colormap, low, high))
+    }
+}
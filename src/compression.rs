@@ -0,0 +1,281 @@
+//! How JPEG and PNG files were encoded, read from their headers: JPEG quantization, chroma
+//! subsampling and coding process, PNG bit depth, color type and ancillary chunks.
+
+use anyhow::{bail, Result};
+use std::io::{BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// The example luminance quantization table of the JPEG standard, which libjpeg scales by quality
+const STANDARD_LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantTable {
+    pub id: u8,
+    /// In zigzag order, as stored in the file
+    pub values: Vec<u16>,
+}
+
+impl QuantTable {
+    pub fn min(&self) -> u16 {
+        self.values.iter().copied().min().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> u16 {
+        self.values.iter().copied().max().unwrap_or_default()
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.values.iter().map(|v| *v as f32).sum::<f32>() / self.values.len().max(1) as f32
+    }
+}
+
+/// A color component of a JPEG frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Component {
+    pub id: u8,
+    pub horizontal: u8,
+    pub vertical: u8,
+    pub table: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JpegInfo {
+    /// The start of frame marker, like 0xC0 for baseline
+    pub frame: u8,
+    pub components: Vec<Component>,
+    pub tables: Vec<QuantTable>,
+}
+
+impl JpegInfo {
+    pub fn progressive(&self) -> bool {
+        matches!(self.frame, 0xC2 | 0xC6 | 0xCA | 0xCE)
+    }
+
+    pub fn process(&self) -> String {
+        let process = match self.frame & 0x03 {
+            0 if self.frame == 0xC0 => "Baseline",
+            0 | 1 => "Extended sequential",
+            2 => "Progressive",
+            _ => "Lossless",
+        };
+        if self.frame >= 0xC9 {
+            format!("{process}, arithmetic coding")
+        } else {
+            process.to_string()
+        }
+    }
+
+    /// Chroma subsampling in J:a:b notation
+    pub fn subsampling(&self) -> String {
+        let (luma, chroma) = match self.components.as_slice() {
+            [_] => return "Grayscale".to_string(),
+            [y, cb, cr] if cb.horizontal == cr.horizontal && cb.vertical == cr.vertical => (y, cb),
+            [_, _, _] => return "Mixed".to_string(),
+            components => return format!("{} components", components.len()),
+        };
+        let h = luma.horizontal / chroma.horizontal.max(1);
+        let v = luma.vertical / chroma.vertical.max(1);
+        match (h, v) {
+            (1, 1) => "4:4:4".to_string(),
+            (2, 1) => "4:2:2".to_string(),
+            (2, 2) => "4:2:0".to_string(),
+            (1, 2) => "4:4:0".to_string(),
+            (4, 1) => "4:1:1".to_string(),
+            (4, 2) => "4:1:0".to_string(),
+            (h, v) => format!("{h}x{v}"),
+        }
+    }
+
+    /// The libjpeg quality the luminance table was most likely scaled with
+    pub fn quality(&self) -> Option<u8> {
+        let luma = self.components.first()?.table;
+        let table = self.tables.iter().find(|t| t.id == luma)?;
+        let sum = table.values.iter().map(|v| *v as f32).sum::<f32>();
+        let standard = STANDARD_LUMINANCE.iter().map(|v| *v as f32).sum::<f32>();
+        let scale = sum * 100. / standard;
+        let quality = if scale <= 100. {
+            (200. - scale) / 2.
+        } else {
+            5000. / scale
+        };
+        Some(quality.round().clamp(1., 100.) as u8)
+    }
+
+    fn read(reader: &mut (impl Read + Seek)) -> Result<Self> {
+        let mut info = Self::default();
+        loop {
+            let mut marker = [0; 2];
+            reader.read_exact(&mut marker)?;
+            if marker[0] != 0xFF {
+                bail!("Expected a marker");
+            }
+            match marker[1] {
+                // fill bytes, and markers without segments
+                0xFF => {
+                    reader.seek(std::io::SeekFrom::Current(-1))?;
+                    continue;
+                }
+                0x01 | 0xD0..=0xD7 => continue,
+                // the entropy coded data follows, all tables that matter come before
+                0xDA | 0xD9 => break,
+                _ => (),
+            }
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            let length = u16::from_be_bytes(length).saturating_sub(2) as usize;
+            let mut segment = vec![0; length];
+            reader.read_exact(&mut segment)?;
+            match marker[1] {
+                0xDB => info.tables.extend(read_quant_tables(&segment)),
+                // frames, except the huffman and arithmetic table markers in between
+                0xC0..=0xCF if !matches!(marker[1], 0xC4 | 0xC8 | 0xCC) => {
+                    info.frame = marker[1];
+                    info.components = segment
+                        .get(6..)
+                        .unwrap_or_default()
+                        .chunks_exact(3)
+                        .take(segment.get(5).copied().unwrap_or_default() as usize)
+                        .map(|c| Component {
+                            id: c[0],
+                            horizontal: c[1] >> 4,
+                            vertical: c[1] & 0x0F,
+                            table: c[2],
+                        })
+                        .collect();
+                }
+                _ => (),
+            }
+        }
+        if info.components.is_empty() {
+            bail!("No frame header");
+        }
+        Ok(info)
+    }
+}
+
+fn read_quant_tables(segment: &[u8]) -> Vec<QuantTable> {
+    let mut tables = vec![];
+    let mut rest = segment;
+    while let Some((&header, data)) = rest.split_first() {
+        let wide = header >> 4 == 1;
+        let size = if wide { 128 } else { 64 };
+        if data.len() < size {
+            break;
+        }
+        let values = if wide {
+            data[..size]
+                .chunks_exact(2)
+                .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                .collect()
+        } else {
+            data[..size].iter().map(|v| *v as u16).collect()
+        };
+        tables.push(QuantTable {
+            id: header & 0x0F,
+            values,
+        });
+        rest = &data[size..];
+    }
+    tables
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PngInfo {
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub interlaced: bool,
+    /// Names of the ancillary chunks in file order, with how often they occur
+    pub ancillary: Vec<(String, usize)>,
+}
+
+impl PngInfo {
+    pub fn color_type_name(&self) -> &'static str {
+        match self.color_type {
+            0 => "Grayscale",
+            2 => "RGB",
+            3 => "Indexed",
+            4 => "Grayscale + alpha",
+            6 => "RGBA",
+            _ => "Unknown",
+        }
+    }
+
+    fn read(reader: &mut (impl Read + Seek)) -> Result<Self> {
+        let mut info = Self::default();
+        loop {
+            let mut header = [0; 8];
+            reader.read_exact(&mut header)?;
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let name = String::from_utf8_lossy(&header[4..]).to_string();
+            if name == "IHDR" {
+                let mut ihdr = [0; 13];
+                reader.read_exact(&mut ihdr)?;
+                info.bit_depth = ihdr[8];
+                info.color_type = ihdr[9];
+                info.interlaced = ihdr[12] == 1;
+                reader.seek(std::io::SeekFrom::Current(length as i64 - 13 + 4))?;
+                continue;
+            }
+            if name == "IEND" {
+                break;
+            }
+            // ancillary chunks start with a lower case letter
+            if header[4].is_ascii_lowercase() {
+                match info.ancillary.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, count)) => *count += 1,
+                    None => info.ancillary.push((name, 1)),
+                }
+            }
+            // skip the data and the CRC
+            reader.seek(std::io::SeekFrom::Current(length as i64 + 4))?;
+        }
+        if info.bit_depth == 0 {
+            bail!("No header chunk");
+        }
+        Ok(info)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompressionInfo {
+    Jpeg(JpegInfo),
+    Png(PngInfo),
+}
+
+impl CompressionInfo {
+    /// Read the headers of a JPEG or PNG file, or `None` for other formats
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let mut reader = BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic[..2] == [0xFF, 0xD8] {
+            reader.seek(std::io::SeekFrom::Start(2))?;
+            return Ok(Some(Self::Jpeg(JpegInfo::read(&mut reader)?)));
+        }
+        if magic == *b"\x89PNG\r\n\x1a\n" {
+            return Ok(Some(Self::Png(PngInfo::read(&mut reader)?)));
+        }
+        Ok(None)
+    }
+}
+
+/// Compression of the current file for the info panel
+#[derive(Debug, Clone)]
+pub struct CompressionView {
+    pub path: PathBuf,
+    /// None for other formats, or if the headers can't be read
+    pub info: Option<CompressionInfo>,
+}
+
+impl CompressionView {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            info: CompressionInfo::read(path).ok().flatten(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Crash logs. A panic writes the panic, a backtrace, system info and the recent log to a file in
+//! the config directory, which is offered on the next start.
+
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+pub fn dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or(anyhow!("Can't get config dir"))?
+        .join("oculante"))
+}
+
+/// The crash log of the last crash that was not looked at yet
+pub fn log_path() -> Result<PathBuf> {
+    Ok(dir()?.join("crash.log"))
+}
+
+/// A crash log that was looked at is kept here until the next crash
+pub fn seen_log_path() -> Result<PathBuf> {
+    Ok(dir()?.join("crash_seen.log"))
+}
+
+/// The content of a crash log
+pub fn report(panic: &str, backtrace: &str) -> String {
+    let log = crate::logging::recent(log::LevelFilter::Trace)
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "Oculante {} crashed\nSystem: {} {} ({})\nThread: {}\n\n{panic}\n\nBacktrace:\n{backtrace}\n\nRecent log:\n{log}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::FAMILY,
+        std::thread::current().name().unwrap_or("unnamed"),
+    )
+}
+
+/// Write a crash log on panics of the main thread, in addition to the default output. Must be
+/// called on the main thread. Panics of other threads only get the default output.
+pub fn install_hook() {
+    let main_thread = std::thread::current().id();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().id() != main_thread {
+            default_hook(info);
+            return;
+        }
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        let path = log_path().and_then(|path| {
+            std::fs::create_dir_all(dir()?)?;
+            std::fs::write(&path, report(&info.to_string(), &backtrace))?;
+            Ok(path)
+        });
+        if let Ok(path) = path {
+            eprintln!("Crash log written to {}", path.display());
+        }
+        default_hook(info);
+    }));
+}
+
+/// The crash log of a previous run that was not looked at yet
+pub fn pending() -> Option<PathBuf> {
+    log_path().ok().filter(|path| path.is_file())
+}
+
+/// Keep the crash log, but don't offer it again
+pub fn dismiss() -> Result<PathBuf> {
+    let seen = seen_log_path()?;
+    std::fs::rename(log_path()?, &seen)?;
+    Ok(seen)
+}
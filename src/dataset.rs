@@ -0,0 +1,503 @@
+//! Dataset review: the annotations of an image dataset, from COCO JSON, YOLO label files, a CSV
+//! of `path,label,bbox` or JSON lines, drawn over each image while scrubbing. Images are accepted or rejected
+//! with a key, and the verdicts are written to a results CSV next to the annotations.
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Box colors, one per class in turn
+pub const PALETTE: [[u8; 3]; 10] = [
+    [31, 119, 180],
+    [255, 127, 14],
+    [44, 160, 44],
+    [214, 39, 40],
+    [148, 103, 189],
+    [140, 86, 75],
+    [227, 119, 194],
+    [127, 127, 127],
+    [188, 189, 34],
+    [23, 190, 207],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub label: String,
+    /// x, y, width and height, in pixels or as fractions of the image size. Image level labels,
+    /// like classes of a classification dataset, have none.
+    pub rect: Option<[f32; 4]>,
+    pub normalized: bool,
+}
+
+impl Annotation {
+    /// The box in pixels of an image of this size
+    pub fn pixel_rect(&self, width: u32, height: u32) -> Option<[f32; 4]> {
+        let [x, y, w, h] = self.rect?;
+        if self.normalized {
+            let (width, height) = (width as f32, height as f32);
+            Some([x * width, y * height, w * width, h * height])
+        } else {
+            Some([x, y, w, h])
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    Rejected,
+}
+
+impl Verdict {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "accepted" => Some(Self::Accepted),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    /// Annotations of every image, by file name
+    Table(HashMap<String, Vec<Annotation>>),
+    /// YOLO label files, read when their image is shown. They are looked up in this folder, in
+    /// the `labels` folder next to the `images` folder, and next to the image.
+    Yolo {
+        labels: Option<PathBuf>,
+        names: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    /// The annotations file or folder
+    pub path: PathBuf,
+    source: Source,
+    /// Class names, sorted
+    pub classes: Vec<String>,
+    /// Classes that are not drawn
+    pub hidden: HashSet<String>,
+    pub verdicts: BTreeMap<PathBuf, Verdict>,
+}
+
+/// The records of a CSV, header included, with fields trimmed. Records may differ in length.
+pub fn read_csv(csv: &str) -> Vec<Vec<String>> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes())
+        .records()
+        .filter_map(|record| record.ok())
+        .map(|record| record.iter().map(String::from).collect())
+        .collect()
+}
+
+/// A CSV record of `fields` and its line break. Fields with separators, quotes or line breaks
+/// are quoted.
+pub fn csv_record(fields: &[&str]) -> String {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    // writing to memory can't fail
+    _ = writer.write_record(fields);
+    let record = writer.into_inner().unwrap_or_default();
+    String::from_utf8(record).unwrap_or_default()
+}
+
+fn file_name(path: &str) -> String {
+    path.rsplit(['/', '\\']).next().unwrap_or(path).to_string()
+}
+
+/// Read COCO JSON, with boxes as x, y, width and height in pixels
+pub fn parse_coco(json: &str) -> Result<HashMap<String, Vec<Annotation>>> {
+    let coco: serde_json::Value = serde_json::from_str(json)?;
+    let categories: HashMap<u64, String> = coco["categories"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| Some((c["id"].as_u64()?, c["name"].as_str()?.to_string())))
+        .collect();
+    let images: HashMap<u64, String> = coco["images"]
+        .as_array()
+        .context("No images in the COCO file")?
+        .iter()
+        .filter_map(|i| Some((i["id"].as_u64()?, file_name(i["file_name"].as_str()?))))
+        .collect();
+    let mut table: HashMap<String, Vec<Annotation>> =
+        images.values().map(|name| (name.clone(), vec![])).collect();
+    for annotation in coco["annotations"].as_array().into_iter().flatten() {
+        let image = match annotation["image_id"]
+            .as_u64()
+            .and_then(|id| images.get(&id))
+        {
+            Some(image) => image,
+            None => continue,
+        };
+        let category = annotation["category_id"].as_u64().unwrap_or_default();
+        let bbox: Vec<f32> = annotation["bbox"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_f64().map(|v| v as f32))
+            .collect();
+        table.entry(image.clone()).or_default().push(Annotation {
+            label: categories
+                .get(&category)
+                .cloned()
+                .unwrap_or_else(|| category.to_string()),
+            rect: (bbox.len() == 4).then(|| [bbox[0], bbox[1], bbox[2], bbox[3]]),
+            normalized: false,
+        });
+    }
+    Ok(table)
+}
+
+/// Whether all of a box is within 0 and 1, so it is given in fractions of the image
+fn is_normalized(rect: &[f32]) -> bool {
+    rect.iter().all(|v| (0.0..=1.0).contains(v))
+}
+
+/// Read a CSV of `path,label` with an optional box. Boxes are x, y, width and height, or
+/// corners if the header names `xmax` or `x2`. Boxes within 0 to 1 are fractions of the image.
+pub fn parse_csv(csv: &str) -> HashMap<String, Vec<Annotation>> {
+    let mut table: HashMap<String, Vec<Annotation>> = HashMap::new();
+    let mut corners = false;
+    for (i, fields) in read_csv(csv).into_iter().enumerate() {
+        if fields.len() < 2 || fields[0].is_empty() {
+            continue;
+        }
+        let numbers: Vec<f32> = fields[2..].iter().filter_map(|f| f.parse().ok()).collect();
+        let is_header =
+            numbers.len() < fields.len() - 2 || Path::new(&fields[0]).extension().is_none();
+        if i == 0 && is_header {
+            let header = fields.join(",").to_lowercase();
+            corners = header.contains("xmax") || header.contains("x2");
+            continue;
+        }
+        let rect = (numbers.len() >= 4).then(|| {
+            let [x, y, a, b] = [numbers[0], numbers[1], numbers[2], numbers[3]];
+            if corners {
+                [x, y, a - x, b - y]
+            } else {
+                [x, y, a, b]
+            }
+        });
+        table
+            .entry(file_name(&fields[0]))
+            .or_default()
+            .push(Annotation {
+                label: fields[1].clone(),
+                rect,
+                normalized: numbers.len() >= 4 && is_normalized(&numbers[..4]),
+            });
+    }
+    table
+}
+
+/// Read JSON lines, one object per image or annotation, like the `metadata.jsonl` of Hugging Face
+/// image folders. The image is in `file_name`, `image` or `path`, its label in `label`, `class` or
+/// `category`, with an optional `bbox` of x, y, width and height. Several boxes go in `objects`,
+/// as lists of `bbox` and `category`. Boxes within 0 to 1 are fractions of the image.
+pub fn parse_jsonl(jsonl: &str) -> Result<HashMap<String, Vec<Annotation>>> {
+    fn text(value: &serde_json::Value) -> Option<String> {
+        match value {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+    fn rect(value: &serde_json::Value) -> Option<[f32; 4]> {
+        let values: Vec<f32> = value
+            .as_array()?
+            .iter()
+            .filter_map(|v| v.as_f64().map(|v| v as f32))
+            .collect();
+        (values.len() == 4).then(|| [values[0], values[1], values[2], values[3]])
+    }
+    fn annotation(label: Option<String>, rect: Option<[f32; 4]>) -> Annotation {
+        Annotation {
+            label: label.unwrap_or_default(),
+            rect,
+            normalized: rect.map(|r| is_normalized(&r)).unwrap_or_default(),
+        }
+    }
+
+    let mut table: HashMap<String, Vec<Annotation>> = HashMap::new();
+    for (i, line) in jsonl.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let object: serde_json::Value =
+            serde_json::from_str(line).with_context(|| format!("Line {} is no JSON", i + 1))?;
+        let image = match ["file_name", "image", "path"]
+            .iter()
+            .find_map(|key| object[key].as_str())
+        {
+            Some(image) => file_name(image),
+            None => continue,
+        };
+        let label = ["label", "class", "category"]
+            .iter()
+            .find_map(|key| text(&object[key]));
+        let annotations = table.entry(image).or_default();
+        let objects = &object["objects"];
+        if let Some(boxes) = objects["bbox"].as_array() {
+            let categories = ["category", "categories", "label", "labels"]
+                .iter()
+                .find_map(|key| objects[key].as_array());
+            for (j, bbox) in boxes.iter().enumerate() {
+                let category = categories.and_then(|c| c.get(j)).and_then(text);
+                annotations.push(annotation(category.or_else(|| label.clone()), rect(bbox)));
+            }
+        } else if label.is_some() || object["bbox"].is_array() {
+            annotations.push(annotation(label, rect(&object["bbox"])));
+        }
+    }
+    Ok(table)
+}
+
+/// Read a YOLO label file of `class x_center y_center width height`, as fractions of the image
+pub fn parse_yolo(labels: &str, names: &[String]) -> Vec<Annotation> {
+    labels
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let class: usize = fields.next()?.parse().ok()?;
+            let values: Vec<f32> = fields.take(4).filter_map(|v| v.parse().ok()).collect();
+            if values.len() < 4 {
+                return None;
+            }
+            let [x, y, w, h] = [values[0], values[1], values[2], values[3]];
+            Some(Annotation {
+                label: names
+                    .get(class)
+                    .cloned()
+                    .unwrap_or_else(|| class.to_string()),
+                rect: Some([x - w / 2., y - h / 2., w, h]),
+                normalized: true,
+            })
+        })
+        .collect()
+}
+
+/// Class names of a YOLO dataset, one per line, or the `names` of a `data.yaml`
+pub fn parse_names(text: &str, yaml: bool) -> Vec<String> {
+    let clean = |name: &str| {
+        name.trim()
+            .trim_matches(|c| c == '\'' || c == '"')
+            .to_string()
+    };
+    if !yaml {
+        return text.lines().map(clean).filter(|n| !n.is_empty()).collect();
+    }
+    let mut names = vec![];
+    let mut in_names = false;
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("names:") {
+            let rest = rest.trim();
+            // a flow list, like `names: ['cat', 'dog']`
+            if let Some(list) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+                return list
+                    .split(',')
+                    .map(clean)
+                    .filter(|n| !n.is_empty())
+                    .collect();
+            }
+            in_names = true;
+            continue;
+        }
+        if !in_names {
+            continue;
+        }
+        if !line.starts_with(' ') && !line.starts_with('\t') && !line.starts_with('-') {
+            break;
+        }
+        let item = line.trim();
+        // a block list, or a map of indices
+        if let Some(name) = item.strip_prefix('-') {
+            names.push(clean(name));
+        } else if let Some((_, name)) = item.split_once(':') {
+            names.push(clean(name));
+        }
+    }
+    names
+}
+
+impl Dataset {
+    /// Open COCO JSON, a CSV, JSON lines, or YOLO labels by their folder, class names file or
+    /// `data.yaml`
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let source = match extension.as_str() {
+            "json" => Source::Table(parse_coco(&std::fs::read_to_string(path)?)?),
+            "csv" => Source::Table(parse_csv(&std::fs::read_to_string(path)?)),
+            "jsonl" => Source::Table(parse_jsonl(&std::fs::read_to_string(path)?)?),
+            "yaml" | "yml" | "txt" | "names" => Source::Yolo {
+                labels: None,
+                names: parse_names(&std::fs::read_to_string(path)?, extension.starts_with('y')),
+            },
+            _ if path.is_dir() => {
+                let names = [path, path.parent().unwrap_or(path)]
+                    .iter()
+                    .flat_map(|dir| {
+                        ["classes.txt", "obj.names", "data.yaml"]
+                            .iter()
+                            .map(move |name| dir.join(name))
+                    })
+                    .find(|file| file.is_file())
+                    .and_then(|file| {
+                        let yaml = file.extension().map(|e| e == "yaml").unwrap_or_default();
+                        Some(parse_names(&std::fs::read_to_string(file).ok()?, yaml))
+                    })
+                    .unwrap_or_default();
+                Source::Yolo {
+                    labels: Some(path.to_path_buf()),
+                    names,
+                }
+            }
+            _ => bail!("Open COCO JSON, a CSV, JSON lines, or a folder of YOLO labels"),
+        };
+        let mut classes: Vec<String> = match &source {
+            Source::Table(table) => table
+                .values()
+                .flatten()
+                .map(|a| a.label.clone())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+            Source::Yolo { names, .. } => names.clone(),
+        };
+        classes.sort();
+        let mut dataset = Self {
+            path: path.to_path_buf(),
+            source,
+            classes,
+            hidden: Default::default(),
+            verdicts: Default::default(),
+        };
+        if let Ok(results) = std::fs::read_to_string(dataset.results_file()) {
+            dataset.verdicts = parse_results(&results);
+        }
+        Ok(dataset)
+    }
+
+    /// Where verdicts are written, next to the annotations
+    pub fn results_file(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.path.with_file_name(format!("{stem}_review.csv"))
+    }
+
+    /// The label file of a YOLO image
+    fn yolo_file(labels: &Option<PathBuf>, image: &Path) -> Option<PathBuf> {
+        let name = format!("{}.txt", image.file_stem()?.to_string_lossy());
+        let mut candidates = vec![];
+        if let Some(labels) = labels {
+            candidates.push(labels.join(&name));
+        }
+        // images/train/a.jpg has its labels in labels/train/a.txt
+        let components: Vec<_> = image.components().collect();
+        if let Some(i) = components.iter().rposition(|c| c.as_os_str() == "images") {
+            let mut path: PathBuf = components[..i].iter().collect();
+            path.push("labels");
+            path.extend(&components[i + 1..components.len() - 1]);
+            candidates.push(path.join(&name));
+        }
+        candidates.push(image.with_file_name(&name));
+        candidates.into_iter().find(|c| c.is_file())
+    }
+
+    pub fn annotations(&self, image: &Path) -> Vec<Annotation> {
+        match &self.source {
+            Source::Table(table) => image
+                .file_name()
+                .and_then(|name| table.get(&*name.to_string_lossy()))
+                .cloned()
+                .unwrap_or_default(),
+            Source::Yolo { labels, names } => Self::yolo_file(labels, image)
+                .and_then(|file| std::fs::read_to_string(file).ok())
+                .map(|text| parse_yolo(&text, names))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Annotations of classes that are not hidden
+    pub fn visible(&self, image: &Path) -> Vec<Annotation> {
+        let mut annotations = self.annotations(image);
+        annotations.retain(|a| !self.hidden.contains(&a.label));
+        annotations
+    }
+
+    pub fn color(&self, label: &str) -> [u8; 3] {
+        let index = self
+            .classes
+            .iter()
+            .position(|c| c == label)
+            .unwrap_or_default();
+        PALETTE[index % PALETTE.len()]
+    }
+
+    /// Record a verdict and write all of them to the results file
+    pub fn judge(&mut self, image: &Path, verdict: Verdict) -> Result<()> {
+        self.verdicts.insert(image.to_path_buf(), verdict);
+        let mut csv = csv_record(&["path", "verdict"]);
+        for (path, verdict) in &self.verdicts {
+            csv += &csv_record(&[&path.to_string_lossy(), verdict.name()]);
+        }
+        std::fs::write(self.results_file(), csv)?;
+        Ok(())
+    }
+
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.verdicts.values().filter(|v| **v == verdict).count()
+    }
+}
+
+pub fn parse_results(csv: &str) -> BTreeMap<PathBuf, Verdict> {
+    read_csv(csv)
+        .into_iter()
+        .skip(1)
+        .filter_map(|fields| {
+            Some((
+                PathBuf::from(fields.first()?),
+                Verdict::from_name(fields.get(1)?)?,
+            ))
+        })
+        .collect()
+}
+
+/// State of the dataset review window
+#[derive(Debug)]
+pub struct DatasetReview {
+    pub enabled: bool,
+    pub dataset: Option<Dataset>,
+    /// Draw the annotations over the image
+    pub show: bool,
+    /// Advance to the next image after a verdict
+    pub advance: bool,
+}
+
+impl Default for DatasetReview {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dataset: None,
+            show: true,
+            advance: true,
+        }
+    }
+}
@@ -0,0 +1,472 @@
+//! A fixed pool of decoder threads fed by a priority queue.
+//!
+//! The image that should be shown next always goes first, prefetching of its neighbours only
+//! uses idle workers. Requests for a file that is already queued or being decoded are merged,
+//! and a newer request for the current image drops older ones that have not started yet.
+//! This keeps fast scrubbing from starting a decode thread per skipped image.
+
+use crate::appstate::Message;
+use crate::cache::DiskCache;
+use crate::image_editing::{self, ImageOperation};
+use crate::image_loader::{open_image, open_jpeg_reduced};
+use crate::thumbnails::{self, ThumbnailSize};
+use crate::utils::{Frame, FrameSource};
+use image::RgbaImage;
+use log::{debug, error, info};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Prefetch,
+    Current,
+}
+
+/// Where the frames of the current image are delivered
+#[derive(Debug)]
+pub struct LoadRequest {
+    pub texture_sender: Sender<Frame>,
+    pub message_sender: Sender<Message>,
+    pub stop_receiver: Receiver<()>,
+}
+
+/// Settings that apply to every decode
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    pub max_texture_size: u32,
+    pub disk_cache: Option<DiskCache>,
+    pub write_thumbnails: bool,
+    /// Decode large JPEGs at about this size for the current image
+    pub downsample_to: Option<(u32, u32)>,
+}
+
+/// A file at a point in time, so a modified file is not merged with an old request.
+/// The flag marks reduced decodes, which can't serve a request for the full image.
+type JobKey = (PathBuf, Option<SystemTime>, bool);
+
+fn job_key(path: &Path, reduced: bool) -> JobKey {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    (path.to_path_buf(), modified, reduced)
+}
+
+#[derive(Debug)]
+struct Job {
+    key: JobKey,
+    priority: Priority,
+    request: Option<LoadRequest>,
+    options: DecodeOptions,
+}
+
+/// How long a file took to decode, for diagnostics
+#[derive(Debug, Clone)]
+pub struct LoadTiming {
+    pub path: PathBuf,
+    pub priority: Priority,
+    /// Time until the first complete frame
+    pub duration: Duration,
+}
+
+/// How many recent timings are kept
+const MAX_TIMINGS: usize = 20;
+
+#[derive(Debug, Default)]
+struct Queue {
+    pending: Vec<Job>,
+    /// Files being decoded, and who is waiting for them
+    in_flight: HashMap<JobKey, Option<LoadRequest>>,
+    /// Most recent first
+    timings: VecDeque<LoadTiming>,
+    shutdown: bool,
+}
+
+impl Queue {
+    /// The pending job with the highest priority, oldest first
+    fn pop(&mut self) -> Option<Job> {
+        let index = self
+            .pending
+            .iter()
+            .enumerate()
+            .max_by(|(ia, a), (ib, b)| a.priority.cmp(&b.priority).then(ib.cmp(ia)))
+            .map(|(i, _)| i)?;
+        Some(self.pending.remove(index))
+    }
+}
+
+type Shared = Arc<(Mutex<Queue>, Condvar)>;
+
+#[derive(Debug)]
+pub struct DecodePool {
+    shared: Shared,
+    /// Finished prefetches, to be moved into the memory cache
+    pub prefetched: Receiver<(PathBuf, RgbaImage)>,
+}
+
+impl DecodePool {
+    pub fn new(workers: usize) -> Self {
+        let shared: Shared = Default::default();
+        let (prefetch_sender, prefetched) = mpsc::channel();
+        for i in 0..workers.max(1) {
+            let shared = shared.clone();
+            let prefetch_sender = prefetch_sender.clone();
+            _ = thread::Builder::new()
+                .name(format!("decoder {i}"))
+                .spawn(move || worker(shared, prefetch_sender));
+        }
+        Self { shared, prefetched }
+    }
+
+    /// A pool sized for this machine, leaving room for the UI
+    pub fn with_default_workers() -> Self {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2);
+        Self::new((threads / 2).clamp(2, 4))
+    }
+
+    /// Decode `path` and send its frames to `request`.
+    /// Older requests for the current image that have not started are dropped.
+    pub fn load(&self, path: &Path, request: LoadRequest, options: DecodeOptions) {
+        let key = job_key(path, options.downsample_to.is_some());
+        let (lock, condvar) = &*self.shared;
+        let mut queue = match lock.lock() {
+            Ok(queue) => queue,
+            Err(_) => return,
+        };
+        queue.pending.retain(|job| job.request.is_none());
+        // images still decoding for an earlier request end up in the memory cache instead
+        for waiting in queue.in_flight.values_mut() {
+            *waiting = None;
+        }
+        // a full decode serves every request
+        let full_key = job_key(path, false);
+        let running = if queue.in_flight.contains_key(&full_key) {
+            &full_key
+        } else {
+            &key
+        };
+        if let Some(waiting) = queue.in_flight.get_mut(running) {
+            debug!("Already decoding {}", path.display());
+            *waiting = Some(request);
+            return;
+        }
+        if let Some(job) = queue
+            .pending
+            .iter_mut()
+            .find(|job| job.key == full_key || job.key == key)
+        {
+            job.priority = Priority::Current;
+            job.request = Some(request);
+            return;
+        }
+        queue.pending.push(Job {
+            key,
+            priority: Priority::Current,
+            request: Some(request),
+            options,
+        });
+        condvar.notify_one();
+    }
+
+    /// Decode images in the background to show them instantly later
+    pub fn prefetch(&self, paths: &[PathBuf], options: DecodeOptions) {
+        let (lock, condvar) = &*self.shared;
+        let mut queue = match lock.lock() {
+            Ok(queue) => queue,
+            Err(_) => return,
+        };
+        // neighbours of an image seen earlier are no longer interesting
+        queue.pending.retain(|job| job.request.is_some());
+        for path in paths {
+            let key = job_key(path, false);
+            if queue.in_flight.contains_key(&key) {
+                continue;
+            }
+            queue.pending.push(Job {
+                key,
+                priority: Priority::Prefetch,
+                request: None,
+                options: DecodeOptions {
+                    downsample_to: None,
+                    ..options.clone()
+                },
+            });
+            condvar.notify_one();
+        }
+    }
+
+    /// Number of queued and running decodes
+    pub fn queue_len(&self) -> (usize, usize) {
+        self.shared
+            .0
+            .lock()
+            .map(|queue| (queue.pending.len(), queue.in_flight.len()))
+            .unwrap_or_default()
+    }
+
+    /// Recently finished decodes, most recent first
+    pub fn timings(&self) -> Vec<LoadTiming> {
+        self.shared
+            .0
+            .lock()
+            .map(|queue| queue.timings.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for DecodePool {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.shared;
+        if let Ok(mut queue) = lock.lock() {
+            queue.shutdown = true;
+        }
+        condvar.notify_all();
+    }
+}
+
+fn worker(shared: Shared, prefetch_sender: Sender<(PathBuf, RgbaImage)>) {
+    let (lock, condvar) = &*shared;
+    loop {
+        let job = {
+            let mut queue = match lock.lock() {
+                Ok(queue) => queue,
+                Err(_) => return,
+            };
+            loop {
+                if queue.shutdown {
+                    return;
+                }
+                if let Some(mut job) = queue.pop() {
+                    queue.in_flight.insert(job.key.clone(), job.request.take());
+                    break job;
+                }
+                queue = match condvar.wait(queue) {
+                    Ok(queue) => queue,
+                    Err(_) => return,
+                };
+            }
+        };
+        // whoever waits for the file is only known once it is decoded
+        let take_request = || {
+            lock.lock()
+                .ok()
+                .and_then(|mut queue| queue.in_flight.remove(&job.key))
+                .flatten()
+        };
+        // partial frames go to whoever waits for the file at that moment
+        let send_partial = |frame: Frame| {
+            if let Ok(mut queue) = lock.lock() {
+                if let Some(waiting) = queue.in_flight.get_mut(&job.key) {
+                    let stopped = waiting
+                        .as_ref()
+                        .map(|request| request.stop_receiver.try_recv().is_ok())
+                        .unwrap_or_default();
+                    if stopped {
+                        *waiting = None;
+                    } else if let Some(request) = waiting {
+                        _ = request.texture_sender.send(frame);
+                    }
+                }
+            }
+        };
+        let duration = decode(
+            &job.key.0,
+            &job.options,
+            take_request,
+            send_partial,
+            &prefetch_sender,
+        );
+        if let (Some(duration), Ok(mut queue)) = (duration, lock.lock()) {
+            queue.timings.push_front(LoadTiming {
+                path: job.key.0.clone(),
+                priority: job.priority,
+                duration,
+            });
+            queue.timings.truncate(MAX_TIMINGS);
+        }
+    }
+}
+
+fn decode(
+    path: &Path,
+    options: &DecodeOptions,
+    take_request: impl FnOnce() -> Option<LoadRequest>,
+    send_partial: impl Fn(Frame),
+    prefetch_sender: &Sender<(PathBuf, RgbaImage)>,
+) -> Option<Duration> {
+    let start = Instant::now();
+    // files of remote folders are downloaded first
+    if let Err(e) = crate::remote::fetch(path) {
+        error!("{e}");
+        if let Some(request) = take_request() {
+            _ = request
+                .message_sender
+                .send(Message::LoadError(e.to_string()));
+        }
+        return None;
+    }
+    let preview = options
+        .downsample_to
+        .filter(|_| is_jpeg(path))
+        .and_then(|target| {
+            open_jpeg_reduced(path, target)
+                .map_err(|e| debug!("Could not decode a preview: {e}"))
+                .ok()
+                .flatten()
+        })
+        .map(Frame::new_preview);
+    // a preview or cached image goes through the same checks as a freshly decoded one
+    let cached = preview
+        .or_else(|| {
+            options
+                .disk_cache
+                .as_ref()
+                .and_then(|c| c.get(path))
+                .map(Frame::new_still)
+        })
+        .map(|frame| {
+            let (sender, receiver) = mpsc::channel();
+            _ = sender.send(frame);
+            receiver
+        });
+    let is_decoded = cached.is_none();
+
+    let frames = match cached.map(Ok).unwrap_or_else(|| open_image(path)) {
+        Ok(frames) => frames,
+        Err(e) => {
+            error!("{e}");
+            if let Some(request) = take_request() {
+                _ = request
+                    .message_sender
+                    .send(Message::LoadError(e.to_string()));
+            }
+            return None;
+        }
+    };
+    let first = loop {
+        match frames.recv() {
+            Ok(frame) if frame.source == FrameSource::Partial => {
+                // too large images are only scaled down once they are complete
+                if frame.buffer.width().max(frame.buffer.height()) <= options.max_texture_size {
+                    send_partial(frame);
+                }
+            }
+            Ok(frame) => break frame,
+            Err(_) => {
+                if let Some(request) = take_request() {
+                    _ = request.message_sender.send(Message::LoadError(format!(
+                        "Could not decode {}",
+                        path.display()
+                    )));
+                }
+                return None;
+            }
+        }
+    };
+    let decoded = start.elapsed();
+
+    if first.source != FrameSource::Still && first.source != FrameSource::Preview {
+        // animations are played as they decode and are not prefetched
+        if let Some(request) = take_request() {
+            thread::spawn(move || play_animation(first, frames, request));
+        }
+        return Some(decoded);
+    }
+
+    let is_preview = first.source == FrameSource::Preview;
+    let original = first.buffer.clone();
+    let largest_side = first.buffer.width().max(first.buffer.height());
+    let frame = fit_texture(first, options.max_texture_size);
+    match take_request() {
+        Some(request) => {
+            if request.stop_receiver.try_recv().is_ok() {
+                info!("Stopped from receiver.");
+            } else {
+                if largest_side > options.max_texture_size {
+                    _ = request.message_sender.send(Message::warn(
+                        "This image exceeded the maximum resolution and will be be scaled down.",
+                    ));
+                }
+                _ = request.texture_sender.send(frame);
+            }
+        }
+        None if is_preview => (),
+        None => {
+            debug!("Prefetched {}", path.display());
+            _ = prefetch_sender.send((path.to_path_buf(), frame.buffer));
+        }
+    }
+
+    // store the full image once it is displayed
+    if let Some(cache) = options.disk_cache.as_ref().filter(|_| is_decoded) {
+        if let Err(e) = cache.insert(path, &original) {
+            error!("Could not write to disk cache: {e}");
+        }
+    }
+    if let Some(cache) = thumbnails::dir().ok().filter(|_| options.write_thumbnails) {
+        if thumbnails::get(&cache, path, ThumbnailSize::Large).is_none() {
+            if let Err(e) = thumbnails::create(&cache, path, &original, ThumbnailSize::Large) {
+                debug!("Could not write thumbnail: {e}");
+            }
+        }
+    }
+    Some(decoded)
+}
+
+fn is_jpeg(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    extension == "jpg" || extension == "jpeg"
+}
+
+/// Scale a frame down if it does not fit on a texture
+fn fit_texture(mut frame: Frame, max_texture_size: u32) -> Frame {
+    let (width, height) = frame.buffer.dimensions();
+    let largest_side = width.max(height);
+    if largest_side > max_texture_size {
+        let scale_factor = max_texture_size as f32 / largest_side as f32;
+        let new_dimensions = (
+            (width as f32 * scale_factor).min(max_texture_size as f32) as u32,
+            (height as f32 * scale_factor).min(max_texture_size as f32) as u32,
+        );
+        let op = ImageOperation::Resize {
+            dimensions: new_dimensions,
+            aspect: true,
+            filter: image_editing::ScaleFilter::Box,
+            percent: None,
+        };
+        _ = op.process_image(&mut frame.buffer);
+    }
+    frame
+}
+
+/// Send all frames of an animation, which `animation::AnimationPlayer` plays back
+fn play_animation(first: Frame, frames: Receiver<Frame>, request: LoadRequest) {
+    let LoadRequest {
+        texture_sender,
+        stop_receiver,
+        ..
+    } = request;
+    let mut is_first = true;
+
+    for f in std::iter::once(first).chain(frames.iter()) {
+        if stop_receiver.try_recv().is_ok() {
+            info!("Stopped from receiver.");
+            return;
+        }
+        if f.source == FrameSource::Animation {
+            if is_first {
+                _ = texture_sender.send(Frame::new_reset(f.buffer, f.delay));
+            } else {
+                _ = texture_sender.send(f);
+            }
+        }
+        is_first = false;
+    }
+}
@@ -0,0 +1,239 @@
+//! Registry for additional image decoders.
+//!
+//! Formats that are too niche for the core loader implement `Decoder` and are registered at
+//! startup. `open_image` asks the registry first, matching by file extension or magic bytes.
+//!
+//! With the `plugins` feature, decoders can also be loaded from shared libraries in
+//! `<config dir>/oculante/plugins`. A plugin exports these C functions:
+//!
+//! ```c
+//! // Comma separated, lower case file extensions, e.g. "fits,fit"
+//! const char* oculante_plugin_extensions(void);
+//! // Decode into a newly allocated RGBA8 buffer of width * height * 4 bytes. Returns 0 on success.
+//! int oculante_plugin_decode(const char* path, uint32_t* width, uint32_t* height, uint8_t** rgba);
+//! // Release a buffer returned by oculante_plugin_decode
+//! void oculante_plugin_free(uint8_t* rgba, size_t len);
+//! // Optional: return 1 if the file header belongs to this format
+//! int oculante_plugin_probe(const uint8_t* header, size_t len);
+//! ```
+
+use anyhow::Result;
+use image::RgbaImage;
+use log::{debug, info};
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// How many bytes of a file are passed to `Decoder::probe`
+pub const HEADER_LEN: usize = 256;
+
+/// A decoder for an image format
+pub trait Decoder: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Lower case file extensions handled by this decoder
+    fn extensions(&self) -> Vec<String>;
+
+    /// Check the first `HEADER_LEN` bytes of a file, for files with an unknown extension
+    fn probe(&self, _header: &[u8]) -> bool {
+        false
+    }
+
+    fn decode(&self, path: &Path) -> Result<RgbaImage>;
+}
+
+static DECODERS: RwLock<Vec<Arc<dyn Decoder>>> = RwLock::new(Vec::new());
+
+/// Make a decoder available to the image loader
+pub fn register(decoder: Arc<dyn Decoder>) {
+    info!(
+        "Registered decoder {} for {:?}",
+        decoder.name(),
+        decoder.extensions()
+    );
+    if let Ok(mut decoders) = DECODERS.write() {
+        decoders.push(decoder);
+    }
+}
+
+/// File extensions of all registered decoders
+pub fn extensions() -> Vec<String> {
+    DECODERS
+        .read()
+        .map(|decoders| decoders.iter().flat_map(|d| d.extensions()).collect())
+        .unwrap_or_default()
+}
+
+/// Find a registered decoder for a file, by extension first and by its header otherwise
+pub fn find(path: &Path) -> Option<Arc<dyn Decoder>> {
+    let decoders = DECODERS.read().ok()?;
+    if decoders.is_empty() {
+        return None;
+    }
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    if let Some(decoder) = decoders
+        .iter()
+        .find(|d| d.extensions().contains(&extension))
+    {
+        return Some(decoder.clone());
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    std::fs::File::open(path)
+        .ok()?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .ok()?;
+    decoders.iter().find(|d| d.probe(&header)).cloned()
+}
+
+/// Register the decoders shipped with oculante and, if enabled, all plugins
+pub fn register_all() {
+    register(Arc::new(crate::dicom::DicomDecoder));
+    register(Arc::new(crate::fits::FitsDecoder));
+
+    #[cfg(feature = "plugins")]
+    match plugin::load_all() {
+        Ok(plugins) => {
+            for p in plugins {
+                register(Arc::new(p));
+            }
+        }
+        Err(e) => debug!("No decoder plugins loaded: {e}"),
+    }
+    debug!(
+        "{} additional decoders",
+        DECODERS.read().map(|d| d.len()).unwrap_or_default()
+    );
+}
+
+#[cfg(feature = "plugins")]
+pub mod plugin {
+    use super::Decoder;
+    use anyhow::{anyhow, bail, Context, Result};
+    use image::RgbaImage;
+    use libloading::{Library, Symbol};
+    use log::{error, info};
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::path::{Path, PathBuf};
+
+    type ExtensionsFn = unsafe extern "C" fn() -> *const c_char;
+    type DecodeFn = unsafe extern "C" fn(*const c_char, *mut u32, *mut u32, *mut *mut u8) -> c_int;
+    type FreeFn = unsafe extern "C" fn(*mut u8, usize);
+    type ProbeFn = unsafe extern "C" fn(*const u8, usize) -> c_int;
+
+    /// A decoder loaded from a shared library
+    #[derive(Debug)]
+    pub struct DylibDecoder {
+        path: PathBuf,
+        extensions: Vec<String>,
+        library: Library,
+    }
+
+    impl DylibDecoder {
+        pub fn load(path: &Path) -> Result<Self> {
+            // Safety: loading a library runs its initializers. Plugins are trusted like the app itself.
+            let library = unsafe { Library::new(path)? };
+            let extensions = unsafe {
+                let extensions: Symbol<ExtensionsFn> =
+                    library.get(b"oculante_plugin_extensions\0")?;
+                let ptr = extensions();
+                if ptr.is_null() {
+                    bail!("Plugin returned no extensions");
+                }
+                CStr::from_ptr(ptr).to_string_lossy().to_string()
+            };
+            // make sure the required functions exist before the decoder is used
+            unsafe {
+                library.get::<DecodeFn>(b"oculante_plugin_decode\0")?;
+                library.get::<FreeFn>(b"oculante_plugin_free\0")?;
+            }
+            Ok(Self {
+                path: path.to_path_buf(),
+                extensions: extensions
+                    .split(',')
+                    .map(|e| e.trim().to_lowercase())
+                    .filter(|e| !e.is_empty())
+                    .collect(),
+                library,
+            })
+        }
+    }
+
+    impl Decoder for DylibDecoder {
+        fn name(&self) -> String {
+            self.path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        }
+
+        fn extensions(&self) -> Vec<String> {
+            self.extensions.clone()
+        }
+
+        fn probe(&self, header: &[u8]) -> bool {
+            unsafe {
+                match self.library.get::<ProbeFn>(b"oculante_plugin_probe\0") {
+                    Ok(probe) => probe(header.as_ptr(), header.len()) == 1,
+                    Err(_) => false,
+                }
+            }
+        }
+
+        fn decode(&self, path: &Path) -> Result<RgbaImage> {
+            let c_path = CString::new(path.to_string_lossy().as_bytes())?;
+            let (mut width, mut height) = (0_u32, 0_u32);
+            let mut data: *mut u8 = std::ptr::null_mut();
+            unsafe {
+                let decode: Symbol<DecodeFn> = self.library.get(b"oculante_plugin_decode\0")?;
+                let free: Symbol<FreeFn> = self.library.get(b"oculante_plugin_free\0")?;
+                let status = decode(c_path.as_ptr(), &mut width, &mut height, &mut data);
+                if status != 0 || data.is_null() {
+                    bail!("{} could not decode the image ({status})", self.name());
+                }
+                let len = width as usize * height as usize * 4;
+                let buffer = std::slice::from_raw_parts(data, len).to_vec();
+                free(data, len);
+                RgbaImage::from_raw(width, height, buffer)
+                    .context("Plugin returned an invalid image buffer")
+            }
+        }
+    }
+
+    pub fn dir() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or(anyhow!("Can't get config dir"))?
+            .join("oculante")
+            .join("plugins"))
+    }
+
+    /// Load all shared libraries in the plugin dir
+    pub fn load_all() -> Result<Vec<DylibDecoder>> {
+        let mut plugins = vec![];
+        for entry in std::fs::read_dir(dir()?)?.flatten() {
+            let path = entry.path();
+            let is_library = path
+                .extension()
+                .map(|e| e == std::env::consts::DLL_EXTENSION)
+                .unwrap_or_default();
+            if !is_library {
+                continue;
+            }
+            match DylibDecoder::load(&path) {
+                Ok(p) => {
+                    info!("Loaded plugin {}", path.display());
+                    plugins.push(p)
+                }
+                Err(e) => error!("Could not load plugin {}: {e}", path.display()),
+            }
+        }
+        Ok(plugins)
+    }
+}
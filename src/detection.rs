@@ -0,0 +1,258 @@
+//! Lightweight face and subject detection to help culling photos. Faces are skin coloured regions
+//! shaped like a face, with darker features inside. The subject is the region with the most
+//! detail, which is usually the part in focus. Both are heuristics on a small copy of the image,
+//! not trained models, so expect misses on unusual lighting.
+
+use image::{imageops, Rgba, RgbaImage};
+
+/// Images are analyzed with their longer side scaled down to this
+const ANALYSIS_SIZE: u32 = 256;
+/// Skin regions smaller than this fraction of the image are ignored
+const MIN_FACE_AREA: f32 = 0.002;
+/// Side of the cells the detail of the image is measured in, at analysis size
+const CELL: u32 = 8;
+/// Images with less mean gradient in their most detailed cell have no subject
+const MIN_DETAIL: f32 = 6.;
+/// Upper limit of faces reported
+const MAX_FACES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionKind {
+    Face,
+    Subject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub kind: DetectionKind,
+    /// x, y, width and height in image pixels
+    pub rect: (u32, u32, u32, u32),
+    /// How certain the detection is, from 0 to 1
+    pub score: f32,
+}
+
+/// Find faces and the main subject of an image
+pub fn detect(img: &RgbaImage) -> Vec<Detection> {
+    if img.width() < 16 || img.height() < 16 {
+        return vec![];
+    }
+    let small = if img.width().max(img.height()) > ANALYSIS_SIZE {
+        let factor = ANALYSIS_SIZE as f32 / img.width().max(img.height()) as f32;
+        imageops::thumbnail(
+            img,
+            ((img.width() as f32 * factor) as u32).max(1),
+            ((img.height() as f32 * factor) as u32).max(1),
+        )
+    } else {
+        img.clone()
+    };
+    let scale_x = img.width() as f32 / small.width() as f32;
+    let scale_y = img.height() as f32 / small.height() as f32;
+
+    let mut detections = faces(&small);
+    detections.extend(subject(&small));
+    for detection in &mut detections {
+        let (x, y, w, h) = detection.rect;
+        let x0 = (x as f32 * scale_x) as u32;
+        let y0 = (y as f32 * scale_y) as u32;
+        let x1 = (((x + w) as f32 * scale_x).ceil() as u32).min(img.width());
+        let y1 = (((y + h) as f32 * scale_y).ceil() as u32).min(img.height());
+        detection.rect = (x0, y0, x1 - x0, y1 - y0);
+    }
+    detections
+}
+
+fn luma(p: &Rgba<u8>) -> f32 {
+    0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32
+}
+
+/// YCbCr skin chroma rule. It only looks at the hue and saturation of a pixel, not its brightness,
+/// so it holds for pale and dark skin alike. Near black pixels are left out, as their chroma is
+/// mostly noise. Skin under coloured light is missed, and skin coloured surfaces like wood or sand
+/// match as well.
+pub fn is_skin(p: &Rgba<u8>) -> bool {
+    if p[3] < 128 || luma(p) < 30. {
+        return false;
+    }
+    let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+    let cb = 128. - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+    let cr = 128. + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+    // warm, but not grey: red chroma clearly above blue chroma
+    (77. ..=127.).contains(&cb) && (130. ..=173.).contains(&cr) && cr - cb >= 5.
+}
+
+/// A connected region of skin pixels
+struct Region {
+    area: u32,
+    luma: f32,
+    min: (u32, u32),
+    max: (u32, u32),
+}
+
+fn faces(img: &RgbaImage) -> Vec<Detection> {
+    let (width, height) = img.dimensions();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let skin = img.pixels().map(is_skin).collect::<Vec<_>>();
+
+    // label the 4-connected skin regions
+    let mut labels = vec![0_usize; skin.len()];
+    let mut regions: Vec<Region> = vec![];
+    let mut stack = vec![];
+    for start in 0..skin.len() {
+        if !skin[start] || labels[start] != 0 {
+            continue;
+        }
+        let label = regions.len() + 1;
+        let (x, y) = (start as u32 % width, start as u32 / width);
+        let mut region = Region {
+            area: 0,
+            luma: 0.,
+            min: (x, y),
+            max: (x, y),
+        };
+        labels[start] = label;
+        stack.push((x, y));
+        while let Some((x, y)) = stack.pop() {
+            region.area += 1;
+            region.luma += luma(img.get_pixel(x, y));
+            region.min = (region.min.0.min(x), region.min.1.min(y));
+            region.max = (region.max.0.max(x), region.max.1.max(y));
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbours.iter().cloned() {
+                if nx < width && ny < height && skin[index(nx, ny)] && labels[index(nx, ny)] == 0 {
+                    labels[index(nx, ny)] = label;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        region.luma /= region.area as f32;
+        regions.push(region);
+    }
+
+    let min_area = (MIN_FACE_AREA * (width * height) as f32).max(48.);
+    let mut faces = regions
+        .iter()
+        .filter_map(|region| {
+            let w = region.max.0 - region.min.0 + 1;
+            let h = region.max.1 - region.min.1 + 1;
+            let aspect = h as f32 / w as f32;
+            // an ellipse fills 79% of its bounding box
+            let fill = region.area as f32 / (w * h) as f32;
+            if (region.area as f32) < min_area
+                || w < 8
+                || h < 8
+                || !(0.8..=2.2).contains(&aspect)
+                || !(0.45..=0.95).contains(&fill)
+            {
+                return None;
+            }
+            // eyes and brows: darker pixels in the upper middle of the region. Dark features
+            // have skin chroma as well, so they can be part of the region
+            let (x0, x1) = (region.min.0 + w * 3 / 20, region.max.0 - w * 3 / 20);
+            let (y0, y1) = (region.min.1 + h / 5, region.min.1 + h * 3 / 5);
+            let mut band = 0;
+            let mut features = 0;
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    band += 1;
+                    if luma(img.get_pixel(x, y)) < region.luma * 0.85 {
+                        features += 1;
+                    }
+                }
+            }
+            let features = features as f32 / band as f32;
+            if !(0.01..=0.5).contains(&features) {
+                return None;
+            }
+            let shape = 1. - (fill - 0.79).abs() / 0.79;
+            Some(Detection {
+                kind: DetectionKind::Face,
+                rect: (region.min.0, region.min.1, w, h),
+                score: (shape * (features / 0.05).min(1.)).clamp(0., 1.),
+            })
+        })
+        .collect::<Vec<_>>();
+    faces.sort_by_key(|face| std::cmp::Reverse(face.rect.2 * face.rect.3));
+    faces.truncate(MAX_FACES);
+    faces
+}
+
+/// The connected cells with clearly more detail than the image overall, the most detailed group
+fn subject(img: &RgbaImage) -> Option<Detection> {
+    let (width, height) = img.dimensions();
+    let lumas = img.pixels().map(luma).collect::<Vec<_>>();
+    let at = |x: u32, y: u32| lumas[(y.min(height - 1) * width + x.min(width - 1)) as usize];
+
+    let (columns, rows) = (width.div_ceil(CELL), height.div_ceil(CELL));
+    let mut energy = vec![0_f32; (columns * rows) as usize];
+    let mut counts = vec![0_u32; energy.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let dx = at(x + 1, y) - at(x.saturating_sub(1), y);
+            let dy = at(x, y + 1) - at(x, y.saturating_sub(1));
+            let cell = ((y / CELL) * columns + x / CELL) as usize;
+            energy[cell] += dx.abs() + dy.abs();
+            counts[cell] += 1;
+        }
+    }
+    for (e, count) in energy.iter_mut().zip(&counts) {
+        *e /= *count as f32;
+    }
+    let mean = energy.iter().sum::<f32>() / energy.len() as f32;
+    let max = energy.iter().cloned().fold(0., f32::max);
+    if max < MIN_DETAIL {
+        return None;
+    }
+    let threshold = (mean * 1.5).max(max * 0.35);
+
+    // 8-connected clusters of detailed cells, keeping the one with the most detail
+    let mut visited = vec![false; energy.len()];
+    // total detail, cells and bounds in cells
+    let mut best: Option<(f32, u32, [u32; 4])> = None;
+    for start in 0..energy.len() {
+        if visited[start] || energy[start] < threshold {
+            continue;
+        }
+        let (column, row) = (start as u32 % columns, start as u32 / columns);
+        let (mut total, mut cells) = (0., 0);
+        let (mut min, mut max) = ((column, row), (column, row));
+        visited[start] = true;
+        let mut stack = vec![(column, row)];
+        while let Some((c, r)) = stack.pop() {
+            total += energy[(r * columns + c) as usize];
+            cells += 1;
+            min = (min.0.min(c), min.1.min(r));
+            max = (max.0.max(c), max.1.max(r));
+            for nr in r.saturating_sub(1)..=(r + 1).min(rows - 1) {
+                for nc in c.saturating_sub(1)..=(c + 1).min(columns - 1) {
+                    let i = (nr * columns + nc) as usize;
+                    if !visited[i] && energy[i] >= threshold {
+                        visited[i] = true;
+                        stack.push((nc, nr));
+                    }
+                }
+            }
+        }
+        if best.map(|(t, ..)| total > t).unwrap_or(true) {
+            best = Some((total, cells, [min.0, min.1, max.0, max.1]));
+        }
+    }
+    let (total, cells, [left, top, right, bottom]) = best?;
+    let (x, y) = (left * CELL, top * CELL);
+    let w = ((right + 1) * CELL).min(width) - x;
+    let h = ((bottom + 1) * CELL).min(height) - y;
+    // detail everywhere means there is no distinct subject
+    if (w * h) as f32 > 0.9 * (width * height) as f32 {
+        return None;
+    }
+    Some(Detection {
+        kind: DetectionKind::Subject,
+        rect: (x, y, w, h),
+        score: (1. - mean / (total / cells as f32)).clamp(0., 1.),
+    })
+}
@@ -0,0 +1,404 @@
+//! Minimal DICOM reader for uncompressed images.
+//!
+//! Supports implicit and explicit VR little endian transfer syntaxes with 8/16/32 bit
+//! monochrome or 8 bit RGB pixel data. Only the first frame of multi-frame files is read.
+//! Monochrome samples are kept with their rescale applied, so they can be windowed interactively.
+
+use crate::decoders::Decoder;
+use anyhow::{bail, Context, Result};
+use image::RgbaImage;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+
+const IMPLICIT_LE: &str = "1.2.840.10008.1.2";
+const EXPLICIT_LE: &str = "1.2.840.10008.1.2.1";
+
+const ITEM: (u16, u16) = (0xFFFE, 0xE000);
+const ITEM_END: (u16, u16) = (0xFFFE, 0xE00D);
+const SEQUENCE_END: (u16, u16) = (0xFFFE, 0xE0DD);
+const PIXEL_DATA: (u16, u16) = (0x7FE0, 0x0010);
+
+/// Window center and width, in rescaled sample values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub center: f32,
+    pub width: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DicomImage {
+    pub width: u32,
+    pub height: u32,
+    /// Rescaled monochrome samples, empty for color images
+    pub samples: Vec<f32>,
+    /// Color pixel data
+    pub color: Option<RgbaImage>,
+    /// MONOCHROME1: low values are displayed bright
+    pub invert: bool,
+    /// Window stored in the file
+    pub default_window: Option<Window>,
+    /// Lowest and highest sample value
+    pub range: (f32, f32),
+}
+
+#[derive(Debug, Default)]
+struct Tags {
+    transfer_syntax: String,
+    rows: u32,
+    columns: u32,
+    bits_allocated: u32,
+    signed: bool,
+    samples_per_pixel: u32,
+    photometric: String,
+    planar: bool,
+    slope: Option<f32>,
+    intercept: Option<f32>,
+    window_center: Option<f32>,
+    window_width: Option<f32>,
+}
+
+/// Tag, VR and value length (None if undefined)
+type ElementHeader = ((u16, u16), [u8; 2], Option<usize>);
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    explicit: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&mut self) -> Result<u16> {
+        let v = self
+            .data
+            .get(self.pos..self.pos + 2)
+            .context("Unexpected end of file")?;
+        self.pos += 2;
+        Ok(u16::from_le_bytes(v.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let v = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .context("Unexpected end of file")?;
+        self.pos += 4;
+        Ok(u32::from_le_bytes(v.try_into()?))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let v = self
+            .data
+            .get(self.pos..self.pos + len)
+            .context("Unexpected end of file")?;
+        self.pos += len;
+        Ok(v)
+    }
+
+    /// Read the header of the next element
+    fn element(&mut self) -> Result<ElementHeader> {
+        let tag = (self.u16()?, self.u16()?);
+        // items and delimiters never have a VR
+        if tag.0 == 0xFFFE || !(self.explicit || tag.0 == 0x0002) {
+            let len = self.u32()?;
+            return Ok((tag, *b"UN", (len != u32::MAX).then_some(len as usize)));
+        }
+        let vr: [u8; 2] = self.bytes(2)?.try_into()?;
+        let len = match &vr {
+            b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN"
+            | b"UR" | b"UT" | b"UV" => {
+                self.pos += 2;
+                self.u32()?
+            }
+            _ => self.u16()? as u32,
+        };
+        Ok((tag, vr, (len != u32::MAX).then_some(len as usize)))
+    }
+
+    /// Skip the content of an element with undefined length, up to its delimiter
+    fn skip_undefined(&mut self) -> Result<()> {
+        // count nested elements of undefined length instead of recursing, files can nest deeply
+        let mut depth = 1_usize;
+        while depth > 0 {
+            let (tag, _, len) = self.element()?;
+            match (tag, len) {
+                (SEQUENCE_END, _) | (ITEM_END, _) => depth -= 1,
+                (_, Some(len)) if tag != ITEM => self.pos += len,
+                (_, None) => depth += 1,
+                // items of defined length hold elements, read through them
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
+
+/// Parse the first value of a decimal string, such as "40\400"
+fn decimal(value: &[u8]) -> Option<f32> {
+    text(value).split('\\').next()?.trim().parse().ok()
+}
+
+impl DicomImage {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// Check for the "DICM" marker after the preamble
+    pub fn is_dicom(header: &[u8]) -> bool {
+        header.get(128..132) == Some(b"DICM")
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let start = if Self::is_dicom(data) { 132 } else { 0 };
+        // files without meta information don't name their transfer syntax, look for a VR instead
+        let explicit = data
+            .get(start + 4..start + 6)
+            .map(|vr| vr.iter().all(|c| c.is_ascii_uppercase()))
+            .unwrap_or_default();
+        let mut reader = Reader {
+            data,
+            pos: start,
+            explicit,
+        };
+        let mut tags = Tags {
+            samples_per_pixel: 1,
+            ..Default::default()
+        };
+        let mut pixels = None;
+
+        while reader.pos < data.len() {
+            let (tag, vr, len) = reader.element()?;
+            let len = match len {
+                Some(len) => len,
+                None if tag == PIXEL_DATA => bail!("Compressed DICOM pixel data is not supported"),
+                None => {
+                    reader.skip_undefined()?;
+                    continue;
+                }
+            };
+            if &vr == b"SQ" {
+                reader.pos += len;
+                continue;
+            }
+            let value = reader.bytes(len)?;
+            let us = || {
+                value
+                    .get(0..2)
+                    .map(|v| u16::from_le_bytes([v[0], v[1]]) as u32)
+            };
+            match tag {
+                (0x0002, 0x0010) => {
+                    tags.transfer_syntax = text(value);
+                    if tags.transfer_syntax != IMPLICIT_LE && tags.transfer_syntax != EXPLICIT_LE {
+                        bail!(
+                            "DICOM transfer syntax {} is not supported",
+                            tags.transfer_syntax
+                        );
+                    }
+                    // the meta group is always explicit, the rest follows the transfer syntax
+                    reader.explicit = tags.transfer_syntax == EXPLICIT_LE;
+                }
+                (0x0028, 0x0002) => tags.samples_per_pixel = us().unwrap_or(1),
+                (0x0028, 0x0004) => tags.photometric = text(value),
+                (0x0028, 0x0006) => tags.planar = us() == Some(1),
+                (0x0028, 0x0010) => tags.rows = us().unwrap_or_default(),
+                (0x0028, 0x0011) => tags.columns = us().unwrap_or_default(),
+                (0x0028, 0x0100) => tags.bits_allocated = us().unwrap_or_default(),
+                (0x0028, 0x0103) => tags.signed = us() == Some(1),
+                (0x0028, 0x1050) => tags.window_center = decimal(value),
+                (0x0028, 0x1051) => tags.window_width = decimal(value),
+                (0x0028, 0x1052) => tags.intercept = decimal(value),
+                (0x0028, 0x1053) => tags.slope = decimal(value),
+                PIXEL_DATA => {
+                    pixels = Some(value);
+                    break;
+                }
+                _ => (),
+            }
+        }
+
+        let pixels = pixels.context("DICOM file has no pixel data")?;
+        Self::from_pixels(&tags, pixels)
+    }
+
+    fn from_pixels(tags: &Tags, pixels: &[u8]) -> Result<Self> {
+        let (width, height) = (tags.columns, tags.rows);
+        let count = width as usize * height as usize;
+        if count == 0 {
+            bail!("DICOM image has no size");
+        }
+
+        if tags.samples_per_pixel == 3 {
+            if tags.bits_allocated != 8 {
+                bail!("Only 8 bit color DICOM images are supported");
+            }
+            let plane = pixels
+                .get(..count * 3)
+                .context("DICOM pixel data is too short")?;
+            let color = RgbaImage::from_fn(width, height, |x, y| {
+                let i = y as usize * width as usize + x as usize;
+                let rgb = if tags.planar {
+                    [plane[i], plane[count + i], plane[2 * count + i]]
+                } else {
+                    [plane[i * 3], plane[i * 3 + 1], plane[i * 3 + 2]]
+                };
+                image::Rgba([rgb[0], rgb[1], rgb[2], 255])
+            });
+            return Ok(Self {
+                width,
+                height,
+                color: Some(color),
+                range: (0., 255.),
+                ..Default::default()
+            });
+        }
+
+        let bytes = (tags.bits_allocated as usize).div_ceil(8);
+        if !matches!(bytes, 1 | 2 | 4) {
+            bail!("{} bit DICOM images are not supported", tags.bits_allocated);
+        }
+        let raw = pixels
+            .get(..count * bytes)
+            .context("DICOM pixel data is too short")?;
+        let slope = tags.slope.unwrap_or(1.);
+        let intercept = tags.intercept.unwrap_or_default();
+        let samples = raw
+            .chunks_exact(bytes)
+            .map(|c| {
+                let v = match (bytes, tags.signed) {
+                    (1, false) => c[0] as f32,
+                    (1, true) => c[0] as i8 as f32,
+                    (2, false) => u16::from_le_bytes([c[0], c[1]]) as f32,
+                    (2, true) => i16::from_le_bytes([c[0], c[1]]) as f32,
+                    (4, false) => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32,
+                    (4, true) => i32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32,
+                    _ => 0.,
+                };
+                v * slope + intercept
+            })
+            .collect::<Vec<_>>();
+        let range = samples
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+        let default_window = match (tags.window_center, tags.window_width) {
+            (Some(center), Some(width)) if width > 0. => Some(Window { center, width }),
+            _ => None,
+        };
+        Ok(Self {
+            width,
+            height,
+            samples,
+            color: None,
+            invert: tags.photometric == "MONOCHROME1",
+            default_window,
+            range,
+        })
+    }
+
+    /// A window covering all sample values
+    pub fn full_window(&self) -> Window {
+        Window {
+            center: (self.range.0 + self.range.1) / 2.,
+            width: (self.range.1 - self.range.0).max(1.),
+        }
+    }
+
+    /// The window to show when the image is opened
+    pub fn initial_window(&self) -> Window {
+        self.default_window.unwrap_or_else(|| self.full_window())
+    }
+
+    /// Map the samples to displayable pixels using a linear window
+    pub fn render(&self, window: Window) -> RgbaImage {
+        if let Some(color) = &self.color {
+            return color.clone();
+        }
+        let low = window.center - window.width / 2.;
+        let width = window.width.max(f32::EPSILON);
+        let mut img = RgbaImage::new(self.width, self.height);
+        for (p, v) in img.pixels_mut().zip(&self.samples) {
+            let mut val = ((v - low) / width).clamp(0., 1.);
+            if self.invert {
+                val = 1. - val;
+            }
+            let val = (val * 255.) as u8;
+            *p = image::Rgba([val, val, val, 255]);
+        }
+        img
+    }
+}
+
+/// Interactive windowing of the current DICOM image
+#[derive(Debug, Clone)]
+pub struct DicomView {
+    pub path: PathBuf,
+    /// None if the file is not a DICOM image
+    pub image: Option<DicomImage>,
+    pub window: Window,
+}
+
+impl DicomView {
+    pub fn new(path: &Path) -> Self {
+        let image = DicomDecoder
+            .matches(path)
+            .then(|| DicomImage::open(path).ok())
+            .flatten();
+        let window = image
+            .as_ref()
+            .map(|i| i.initial_window())
+            .unwrap_or(Window {
+                center: 0.,
+                width: 1.,
+            });
+        Self {
+            path: path.to_path_buf(),
+            image,
+            window,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DicomDecoder;
+
+impl DicomDecoder {
+    fn matches(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        if self.extensions().contains(&extension) {
+            return true;
+        }
+        let mut header = [0_u8; 132];
+        std::fs::File::open(path)
+            .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut header))
+            .is_ok()
+            && DicomImage::is_dicom(&header)
+    }
+}
+
+impl Decoder for DicomDecoder {
+    fn name(&self) -> String {
+        "DICOM".into()
+    }
+
+    fn extensions(&self) -> Vec<String> {
+        vec!["dcm".into(), "dicom".into()]
+    }
+
+    fn probe(&self, header: &[u8]) -> bool {
+        DicomImage::is_dicom(header)
+    }
+
+    fn decode(&self, path: &Path) -> Result<RgbaImage> {
+        let image = DicomImage::open(path)?;
+        Ok(image.render(image.initial_window()))
+    }
+}
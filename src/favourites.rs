@@ -0,0 +1,277 @@
+//! The favourites of a folder, exported as a list for other apps or collected into a folder.
+//! Favourites are imported from such lists or from the settings of another installation, and
+//! backed up as lists from time to time.
+
+use crate::dataset::{csv_record, read_csv};
+use crate::sorting::target_path;
+use anyhow::{Context, Result};
+use log::error;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Instant, SystemTime};
+
+/// Backups kept of the favourites
+const MAX_BACKUPS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListFormat {
+    /// One path per line
+    #[default]
+    Text,
+    Csv,
+    /// A playlist, which slideshow and media apps open
+    M3u,
+}
+
+impl ListFormat {
+    pub const ALL: [Self; 3] = [Self::Text, Self::Csv, Self::M3u];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "Text",
+            Self::Csv => "CSV",
+            Self::M3u => "M3U playlist",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Csv => "csv",
+            Self::M3u => "m3u",
+        }
+    }
+
+    pub fn render(&self, paths: &[PathBuf]) -> String {
+        let mut list = match self {
+            Self::Text => String::new(),
+            Self::Csv => "path,name\n".to_string(),
+            Self::M3u => "#EXTM3U\n".to_string(),
+        };
+        for path in paths {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let path = path.to_string_lossy();
+            list += &match self {
+                Self::Text => format!("{path}\n"),
+                Self::Csv => csv_record(&[&path, &name]),
+                Self::M3u => format!("#EXTINF:-1,{name}\n{path}\n"),
+            };
+        }
+        list
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollectMode {
+    #[default]
+    Copy,
+    /// Takes no space, but only works on the same drive
+    HardLink,
+}
+
+/// The favourites in `folder`, in the order of the folder
+pub fn in_folder(favourites: &HashSet<PathBuf>, folder: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = favourites
+        .iter()
+        .filter(|p| p.parent() == Some(folder))
+        .cloned()
+        .collect();
+    paths.sort_unstable_by(|a, b| {
+        lexical_sort::natural_lexical_cmp(
+            &a.file_name()
+                .map(|f| f.to_string_lossy())
+                .unwrap_or_default(),
+            &b.file_name()
+                .map(|f| f.to_string_lossy())
+                .unwrap_or_default(),
+        )
+    });
+    paths
+}
+
+/// Copy or link the files into `destination`, sending the number done after each one. Files
+/// that fail are skipped, and their number is returned.
+pub fn collect(
+    paths: &[PathBuf],
+    destination: &Path,
+    mode: CollectMode,
+    progress: &Sender<(usize, usize)>,
+) -> Result<usize> {
+    std::fs::create_dir_all(destination)
+        .with_context(|| format!("Can't create {}", destination.display()))?;
+    let mut failed = 0;
+    for (i, path) in paths.iter().enumerate() {
+        let target = target_path(path, destination);
+        let result = match mode {
+            CollectMode::Copy => std::fs::copy(path, &target).map(|_| ()),
+            CollectMode::HardLink => std::fs::hard_link(path, &target),
+        };
+        if let Err(e) = result {
+            error!("Could not collect {}: {e}", path.display());
+            failed += 1;
+        }
+        _ = progress.send((i + 1, paths.len()));
+    }
+    Ok(failed)
+}
+
+/// The paths of a list: one per line, a CSV with a `path` column, or an M3U playlist. Relative
+/// paths are relative to `base`, the folder of the list.
+pub fn parse_list(text: &str, base: &Path) -> Vec<PathBuf> {
+    let is_entry = |line: &str| !line.is_empty() && !line.starts_with('#');
+    let is_csv = match text.lines().map(|l| l.trim()).find(|l| is_entry(l)) {
+        Some(header) => header.to_lowercase().starts_with("path,") || header.contains(",path"),
+        None => false,
+    };
+    let paths: Vec<String> = if is_csv {
+        // quoted paths may span lines
+        let mut records = read_csv(text)
+            .into_iter()
+            .filter(|record| record.first().map(|f| is_entry(f)).unwrap_or_default());
+        let column = records.next().and_then(|header| {
+            header
+                .iter()
+                .position(|field| field.eq_ignore_ascii_case("path"))
+        });
+        records
+            .filter_map(|record| record.get(column?).cloned())
+            .collect()
+    } else {
+        text.lines()
+            .map(|l| l.trim())
+            .filter(|l| is_entry(l))
+            .map(String::from)
+            .collect()
+    };
+    paths
+        .into_iter()
+        // without `./` parts, so they match the paths of the folder
+        .map(|path| base.join(path.trim()).components().collect())
+        .collect()
+}
+
+/// The favourites in a list or in the settings file of another installation
+pub fn read_favourites(file: &Path) -> Result<Vec<PathBuf>> {
+    let text = std::fs::read_to_string(file)?;
+    if let Ok(settings) = serde_json::from_str::<serde_json::Value>(&text) {
+        let favourites = settings["favourite_images"]
+            .as_array()
+            .context("No favourites in these settings")?;
+        return Ok(favourites
+            .iter()
+            .filter_map(|p| p.as_str())
+            .map(PathBuf::from)
+            .collect());
+    }
+    Ok(parse_list(&text, file.parent().unwrap_or(Path::new(""))))
+}
+
+/// Favourites to import, split into existing and missing files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Import {
+    pub found: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+}
+
+impl Import {
+    pub fn check(paths: Vec<PathBuf>) -> Self {
+        let (found, missing) = paths.into_iter().partition(|p| p.exists());
+        Self { found, missing }
+    }
+}
+
+/// Where the favourites are backed up: `oculante/favourites` in the local data folder, not beside
+/// the settings file, so a sync client replacing that file leaves the backups alone
+pub fn backup_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("oculante").join("favourites"))
+}
+
+/// The backups in `dir`, oldest first
+pub fn backups(dir: &Path) -> Vec<PathBuf> {
+    let mut backups: Vec<(u64, PathBuf)> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let time = path
+                .file_stem()?
+                .to_str()?
+                .strip_prefix("favourites_")?
+                .parse()
+                .ok()?;
+            Some((time, path))
+        })
+        .collect();
+    backups.sort();
+    backups.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Save the favourites as a list named by `time` in `dir`, unless the newest backup has them
+/// already, and delete the oldest backups. Returns the new backup.
+pub fn backup(
+    favourites: &HashSet<PathBuf>,
+    dir: &Path,
+    time: SystemTime,
+) -> Result<Option<PathBuf>> {
+    let mut paths: Vec<PathBuf> = favourites.iter().cloned().collect();
+    paths.sort();
+    let list = ListFormat::Text.render(&paths);
+    let backups = backups(dir);
+    let newest = backups.last().and_then(|b| std::fs::read_to_string(b).ok());
+    if newest.as_ref() == Some(&list) || (newest.is_none() && favourites.is_empty()) {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(dir)?;
+    let time = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let file = dir.join(format!("favourites_{time}.txt"));
+    std::fs::write(&file, list)?;
+    for old in backups.iter().rev().skip(MAX_BACKUPS - 1) {
+        _ = std::fs::remove_file(old);
+    }
+    Ok(Some(file))
+}
+
+/// How the favourites of another app and ours are brought together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the favourites of both
+    Merge,
+    Mine,
+    Theirs,
+}
+
+impl Resolution {
+    pub fn apply(&self, mine: &mut HashSet<PathBuf>, theirs: HashSet<PathBuf>) {
+        match self {
+            Self::Merge => mine.extend(theirs),
+            Self::Mine => (),
+            Self::Theirs => *mine = theirs,
+        }
+    }
+}
+
+/// State of the favourites window
+#[derive(Debug, Default)]
+pub struct FavouritesExport {
+    pub enabled: bool,
+    pub format: ListFormat,
+    pub mode: CollectMode,
+    /// Files done and the total while collecting
+    pub progress: Option<(usize, usize)>,
+    /// Files of the last import that don't exist
+    pub missing: Vec<PathBuf>,
+    /// The favourites of the settings file, if another app changed them, like a sync client or
+    /// a second instance
+    pub conflict: Option<HashSet<PathBuf>>,
+    /// When the settings file was checked for changes
+    pub checked: Option<Instant>,
+    pub backed_up: Option<Instant>,
+}
@@ -0,0 +1,218 @@
+//! Hand files to the file manager and other apps of the desktop, with per-OS implementations
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use anyhow::anyhow;
+use anyhow::{bail, Result};
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+use image::RgbaImage;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use log::debug;
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+use notan::prelude::App;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+
+/// Quote a string for a single quoted PowerShell argument
+#[cfg(any(target_os = "windows", test))]
+pub fn powershell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Quote a string for AppleScript
+#[cfg(any(target_os = "macos", test))]
+pub fn applescript_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn check(mut command: Command) -> Result<()> {
+    let status = command.status()?;
+    if !status.success() {
+        bail!("{:?} failed with {status}", command.get_program());
+    }
+    Ok(())
+}
+
+/// Show the file selected in the file manager
+pub fn reveal(path: &Path) -> Result<()> {
+    let path = std::fs::canonicalize(path)?;
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // explorer returns an error code even if it worked
+        Command::new("explorer")
+            .raw_arg(format!("/select,\"{}\"", path.display()))
+            .spawn()?;
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(&path);
+        check(command)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // file managers implementing the freedesktop interface select the file
+        let mut command = Command::new("dbus-send");
+        command.args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+            &format!("array:string:{}", crate::thumbnails::uri(&path)?),
+            "string:",
+        ]);
+        command
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        if check(command).is_ok() {
+            return Ok(());
+        }
+        let mut command = Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(&path));
+        check(command)
+    }
+}
+
+/// Put the file on the clipboard as a file, not as its path, so it can be pasted into file
+/// managers and chats
+pub fn copy_file(path: &Path) -> Result<()> {
+    let path = std::fs::canonicalize(path)?;
+    #[cfg(target_os = "windows")]
+    {
+        let path = path.to_string_lossy();
+        let path = path.trim_start_matches(r"\\?\");
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!("Set-Clipboard -LiteralPath {}", powershell_quote(path)),
+        ]);
+        check(command)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            &format!(
+                "set the clipboard to POSIX file {}",
+                applescript_quote(&path.to_string_lossy())
+            ),
+        ]);
+        check(command)
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        use std::io::Write;
+        let uri = crate::thumbnails::uri(&path)?;
+        let mut command = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            let mut command = Command::new("wl-copy");
+            command.args(["--type", "text/uri-list"]);
+            command
+        } else {
+            let mut command = Command::new("xclip");
+            command.args(["-selection", "clipboard", "-t", "text/uri-list"]);
+            command
+        };
+        // both keep serving the clipboard in the background once they have read the input
+        let mut child = command.stdin(std::process::Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(format!("{uri}\r\n").as_bytes())?;
+        }
+        if !child.wait()?.success() {
+            bail!("Could not copy {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Whether files can be dragged into other apps. Windows and macOS start a native drag from the
+/// window, Linux needs a drag helper.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub fn can_drag_out() -> bool {
+    true
+}
+
+/// Whether files can be dragged into other apps. Windows and macOS start a native drag from the
+/// window, Linux needs a drag helper.
+#[cfg(target_os = "linux")]
+pub fn can_drag_out() -> bool {
+    drag_helper().is_some()
+}
+
+/// The drag helper to drag files into other apps with, if installed. The native drag source on
+/// Linux needs a GTK window, which the window of oculante is not, so dragon opens a window to drag
+/// the file from instead.
+#[cfg(target_os = "linux")]
+pub fn drag_helper() -> Option<&'static Path> {
+    static HELPER: OnceLock<Option<PathBuf>> = OnceLock::new();
+    HELPER
+        .get_or_init(|| {
+            let paths = std::env::var_os("PATH")?;
+            ["dragon-drop", "dragon"].iter().find_map(|name| {
+                std::env::split_paths(&paths)
+                    .map(|dir| dir.join(name))
+                    .find(|helper| helper.is_file())
+            })
+        })
+        .as_deref()
+}
+
+/// Drag the file into other apps from the window, with `preview` under the cursor. Returns once it
+/// was dropped.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub fn drag_out(app: &App, path: &Path, preview: Option<&RgbaImage>) -> Result<()> {
+    use notan::backend::WinitBackend;
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+    struct Window(RawWindowHandle);
+    // the handle stays valid while the window is open, which outlasts the drag
+    unsafe impl HasRawWindowHandle for Window {
+        fn raw_window_handle(&self) -> RawWindowHandle {
+            self.0
+        }
+    }
+
+    let Some(window) = app
+        .backend
+        .downcast_ref::<WinitBackend>()
+        .and_then(|backend| backend.raw_window_handle())
+    else {
+        bail!("There is no window to drag from");
+    };
+    let mut image = Vec::new();
+    if let Some(preview) = preview {
+        image::imageops::thumbnail(preview, 96, 96).write_to(
+            &mut std::io::Cursor::new(&mut image),
+            image::ImageFormat::Png,
+        )?;
+    }
+    drag::start_drag(
+        &Window(window),
+        drag::DragItem::Files(vec![std::fs::canonicalize(path)?]),
+        drag::Image::Raw(image),
+        |result, _| debug!("Drag ended: {result:?}"),
+        Default::default(),
+    )
+    .map_err(|e| anyhow!("{e}"))
+}
+
+/// Start dragging the file into other apps from the window of the drag helper
+#[cfg(target_os = "linux")]
+pub fn drag_out(_app: &App, path: &Path, _preview: Option<&RgbaImage>) -> Result<()> {
+    let Some(helper) = drag_helper() else {
+        bail!("dragon is not installed");
+    };
+    Command::new(helper)
+        .args(["--and-exit", "--on-top"])
+        .arg(path)
+        .spawn()?;
+    Ok(())
+}
@@ -0,0 +1,258 @@
+//! FITS reader for astronomical images.
+//!
+//! Reads the primary HDU with any BITPIX, as a single plane or as three planes (RGB).
+//! Samples are kept as floats so the display stretch can be changed interactively.
+
+use crate::decoders::Decoder;
+use anyhow::{bail, Context, Result};
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+use strum::Display;
+use strum_macros::EnumIter;
+
+const BLOCK: usize = 2880;
+const CARD: usize = 80;
+
+/// How normalized sample values are mapped to display brightness
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumIter)]
+pub enum Stretch {
+    Linear,
+    Log,
+    Asinh,
+}
+
+impl Stretch {
+    fn apply(&self, v: f32) -> f32 {
+        match self {
+            Self::Linear => v,
+            Self::Log => (1. + 1000. * v).ln() / 1001_f32.ln(),
+            Self::Asinh => (10. * v).asinh() / 10_f32.asinh(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FitsImage {
+    pub width: u32,
+    pub height: u32,
+    /// One plane for monochrome images, three for color
+    pub planes: Vec<Vec<f32>>,
+    /// Lowest and highest value of each plane
+    pub ranges: Vec<(f32, f32)>,
+}
+
+/// Find the value of a header keyword
+fn keyword<'a>(cards: &'a [String], key: &str) -> Option<&'a str> {
+    cards.iter().find_map(|card| {
+        // invalid bytes of a card are replaced by wider chars, which can't be keywords
+        let (k, v) = (card.get(..8)?, card.get(8..)?);
+        if k.trim() != key || !v.starts_with("= ") {
+            return None;
+        }
+        // strip comments
+        Some(v[2..].split('/').next().unwrap_or_default().trim())
+    })
+}
+
+fn number<T: std::str::FromStr>(cards: &[String], key: &str) -> Option<T> {
+    keyword(cards, key)?.parse().ok()
+}
+
+impl FitsImage {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn is_fits(header: &[u8]) -> bool {
+        header.starts_with(b"SIMPLE  =")
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if !Self::is_fits(data) {
+            bail!("Not a FITS file");
+        }
+        let mut cards = vec![];
+        let mut pos = 0;
+        loop {
+            let card = data
+                .get(pos..pos + CARD)
+                .context("FITS header has no END")?;
+            pos += CARD;
+            let card = String::from_utf8_lossy(card).to_string();
+            if card.trim_end() == "END" {
+                break;
+            }
+            cards.push(card);
+        }
+        // data starts at the next block
+        let start = pos.div_ceil(BLOCK) * BLOCK;
+
+        let bitpix: i32 = number(&cards, "BITPIX").context("FITS header has no BITPIX")?;
+        let naxis: usize = number(&cards, "NAXIS").unwrap_or_default();
+        if naxis < 2 {
+            bail!("FITS file has no image in its primary HDU");
+        }
+        let width: u32 = number(&cards, "NAXIS1").context("FITS header has no NAXIS1")?;
+        let height: u32 = number(&cards, "NAXIS2").context("FITS header has no NAXIS2")?;
+        let depth: usize = if naxis > 2 {
+            number(&cards, "NAXIS3").unwrap_or(1)
+        } else {
+            1
+        };
+        let bzero: f64 = number(&cards, "BZERO").unwrap_or_default();
+        let bscale: f64 = number(&cards, "BSCALE").unwrap_or(1.);
+
+        if ![8, 16, 32, 64, -32, -64].contains(&bitpix) {
+            bail!("FITS BITPIX {bitpix} is not supported");
+        }
+        let bytes = (bitpix.unsigned_abs() / 8) as usize;
+        let count = (width as usize)
+            .checked_mul(height as usize)
+            .context("FITS image is too large")?;
+        if count == 0 {
+            bail!("FITS image has no size");
+        }
+        let planes = if depth >= 3 { 3 } else { 1 };
+        let end = count
+            .checked_mul(planes * bytes)
+            .and_then(|size| size.checked_add(start))
+            .context("FITS image is too large")?;
+        let raw = data.get(start..end).context("FITS data is too short")?;
+        let read = |c: &[u8]| -> f64 {
+            match bitpix {
+                8 => c[0] as f64,
+                16 => i16::from_be_bytes([c[0], c[1]]) as f64,
+                32 => i32::from_be_bytes([c[0], c[1], c[2], c[3]]) as f64,
+                64 => i64::from_be_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]) as f64,
+                -32 => f32::from_be_bytes([c[0], c[1], c[2], c[3]]) as f64,
+                _ => f64::from_be_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]),
+            }
+        };
+
+        let planes = raw
+            .chunks_exact(count * bytes)
+            .map(|plane| {
+                plane
+                    .chunks_exact(bytes)
+                    .map(|c| (read(c) * bscale + bzero) as f32)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let ranges = planes
+            .iter()
+            .map(|p| {
+                p.iter()
+                    .filter(|v| v.is_finite())
+                    .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)))
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            planes,
+            ranges,
+        })
+    }
+
+    /// Map the samples to displayable pixels
+    pub fn render(&self, stretch: Stretch, black: [f32; 3], white: [f32; 3]) -> RgbaImage {
+        let levels = self
+            .planes
+            .iter()
+            .zip(&self.ranges)
+            .enumerate()
+            .map(|(i, (plane, (lo, hi)))| {
+                let span = (hi - lo).max(f32::EPSILON);
+                let (b, w) = (black[i], white[i].max(black[i] + f32::EPSILON));
+                plane
+                    .iter()
+                    .map(|v| {
+                        let normalized = ((v - lo) / span - b) / (w - b);
+                        let v = stretch.apply(normalized.clamp(0., 1.));
+                        // NaN marks missing data
+                        if v.is_nan() {
+                            0
+                        } else {
+                            (v * 255.) as u8
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // FITS rows start at the bottom
+        let (width, height) = (self.width as usize, self.height as usize);
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let i = (height - 1 - y as usize) * width + x as usize;
+            let c = |p: usize| levels.get(p).unwrap_or(&levels[0])[i];
+            image::Rgba([c(0), c(1), c(2), 255])
+        })
+    }
+}
+
+/// Interactive stretch of the current FITS image
+#[derive(Debug, Clone)]
+pub struct FitsView {
+    pub path: PathBuf,
+    /// None if the file is not a FITS image
+    pub image: Option<FitsImage>,
+    pub stretch: Stretch,
+    /// Black point per channel, relative to the value range
+    pub black: [f32; 3],
+    /// White point per channel, relative to the value range
+    pub white: [f32; 3],
+}
+
+impl FitsView {
+    pub fn new(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        let mut header = [0_u8; 9];
+        let is_fits = FitsDecoder.extensions().contains(&extension)
+            || std::fs::File::open(path)
+                .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut header))
+                .is_ok()
+                && FitsImage::is_fits(&header);
+        Self {
+            path: path.to_path_buf(),
+            image: is_fits.then(|| FitsImage::open(path).ok()).flatten(),
+            stretch: Stretch::Asinh,
+            black: [0.; 3],
+            white: [1.; 3],
+        }
+    }
+
+    pub fn render(&self) -> Option<RgbaImage> {
+        Some(
+            self.image
+                .as_ref()?
+                .render(self.stretch, self.black, self.white),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct FitsDecoder;
+
+impl Decoder for FitsDecoder {
+    fn name(&self) -> String {
+        "FITS".into()
+    }
+
+    fn extensions(&self) -> Vec<String> {
+        vec!["fits".into(), "fit".into(), "fts".into()]
+    }
+
+    fn probe(&self, header: &[u8]) -> bool {
+        FitsImage::is_fits(header)
+    }
+
+    fn decode(&self, path: &Path) -> Result<RgbaImage> {
+        let image = FitsImage::open(path)?;
+        Ok(image.render(Stretch::Asinh, [0.; 3], [1.; 3]))
+    }
+}
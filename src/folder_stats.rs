@@ -0,0 +1,180 @@
+//! A summary of the images in a folder: formats, sizes, dimensions, dates and cameras. The
+//! facts read from each file are cached in the cache dir.
+
+use crate::cache::FileCache;
+use crate::scrubber::get_image_filenames_for_directory;
+use exif::{In, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Name of the cache file
+pub const CACHE_FILE: &str = "folder_stats.json";
+
+/// Upper limits in megapixels of the dimension classes
+const MEGAPIXEL_CLASSES: [(f64, &str); 6] = [
+    (1., "< 1 MP"),
+    (4., "1 - 4 MP"),
+    (12., "4 - 12 MP"),
+    (24., "12 - 24 MP"),
+    (50., "24 - 50 MP"),
+    (f64::INFINITY, "> 50 MP"),
+];
+
+pub type FactsCache = FileCache<FileFacts>;
+
+/// What is read from a single file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileFacts {
+    pub dimensions: Option<(u32, u32)>,
+    /// Month the image was taken, or else modified, as `YYYY-MM`
+    pub month: Option<String>,
+    /// EXIF make and model
+    pub camera: Option<String>,
+}
+
+impl FileFacts {
+    pub fn read(path: &Path) -> Self {
+        let exif = std::fs::File::open(path).ok().and_then(|file| {
+            exif::Reader::new()
+                .read_from_container(&mut std::io::BufReader::new(file))
+                .ok()
+        });
+        let ascii = |tag: Tag| {
+            let field = exif.as_ref()?.get_field(tag, In::PRIMARY)?;
+            match &field.value {
+                exif::Value::Ascii(s) => s
+                    .first()
+                    .map(|s| String::from_utf8_lossy(s).trim().to_string())
+                    .filter(|s| !s.is_empty()),
+                _ => None,
+            }
+        };
+        let make = ascii(Tag::Make);
+        let model = ascii(Tag::Model);
+        let camera = match (make, model) {
+            // models often repeat the make, like Canon / Canon EOS R5
+            (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+            (Some(make), Some(model)) => Some(format!("{make} {model}")),
+            (make, model) => make.or(model),
+        };
+        let taken = ascii(Tag::DateTimeOriginal)
+            .filter(|date| date.len() >= 7 && date[..4].chars().all(|c| c.is_ascii_digit()))
+            .map(|date| format!("{}-{}", &date[..4], &date[5..7]));
+        let modified = || {
+            let secs = std::fs::metadata(path)
+                .ok()?
+                .modified()
+                .ok()?
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_secs();
+            let (year, month) = year_month(secs as i64 / 86_400);
+            Some(format!("{year:04}-{month:02}"))
+        };
+        Self {
+            dimensions: image::image_dimensions(path).ok(),
+            month: taken.or_else(modified),
+            camera,
+        }
+    }
+}
+
+/// Year and month of a day since 1970, in the proleptic Gregorian calendar
+pub fn year_month(days: i64) -> (i64, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FolderStats {
+    pub folder: PathBuf,
+    pub files: usize,
+    pub total_size: u64,
+    /// Lower case extension, with the number of files and their size
+    pub formats: BTreeMap<String, (usize, u64)>,
+    /// Number of files per megapixel class, smallest first
+    pub dimensions: Vec<(String, usize)>,
+    /// Number of files per month, as `YYYY-MM`
+    pub months: BTreeMap<String, usize>,
+    /// Number of files per camera, most used first
+    pub cameras: Vec<(String, usize)>,
+}
+
+impl FolderStats {
+    /// Summarize the images of a folder, reading files missing in `cache`
+    pub fn compute(folder: &Path, all_files: bool, cache: &mut FactsCache) -> Self {
+        let files = get_image_filenames_for_directory(folder, all_files).unwrap_or_default();
+        let mut stats = Self {
+            folder: folder.to_path_buf(),
+            files: files.len(),
+            dimensions: MEGAPIXEL_CLASSES
+                .iter()
+                .map(|(_, label)| (label.to_string(), 0))
+                .collect(),
+            ..Default::default()
+        };
+        let mut unknown_dimensions = 0;
+        let mut cameras: BTreeMap<String, usize> = Default::default();
+        for path in &files {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+            stats.total_size += size;
+            let extension = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let format = stats.formats.entry(extension).or_default();
+            format.0 += 1;
+            format.1 += size;
+
+            let facts = cache.get(path).unwrap_or_else(|| {
+                let facts = FileFacts::read(path);
+                cache.insert(path, facts.clone());
+                facts
+            });
+            match facts.dimensions {
+                Some((width, height)) => {
+                    let megapixels = width as f64 * height as f64 / 1_000_000.;
+                    let class = MEGAPIXEL_CLASSES
+                        .iter()
+                        .position(|(limit, _)| megapixels < *limit)
+                        .unwrap_or_default();
+                    stats.dimensions[class].1 += 1;
+                }
+                None => unknown_dimensions += 1,
+            }
+            if let Some(month) = facts.month {
+                *stats.months.entry(month).or_default() += 1;
+            }
+            if let Some(camera) = facts.camera {
+                *cameras.entry(camera).or_default() += 1;
+            }
+        }
+        if unknown_dimensions > 0 {
+            stats
+                .dimensions
+                .push(("Unknown".to_string(), unknown_dimensions));
+        }
+        stats.cameras = cameras.into_iter().collect();
+        stats.cameras.sort_by(|a, b| b.1.cmp(&a.1));
+        stats
+    }
+}
+
+/// State of the folder statistics window
+#[derive(Debug, Default)]
+pub struct StatsWindow {
+    pub enabled: bool,
+    pub stats: Option<FolderStats>,
+    /// Statistics are being computed
+    pub computing: bool,
+}
@@ -0,0 +1,326 @@
+//! GPU preview path for per-pixel edit operators, and computed channel views.
+//!
+//! While a slider is being dragged, the pixel operator stack is evaluated in a fragment
+//! shader on the already uploaded texture instead of on the CPU. The final result is
+//! always computed by the CPU once the interaction ends, so both paths must stay in sync
+//! with `ImageOperation::process_pixel`.
+
+use crate::image_editing::{ImageOperation, ImgOpItem};
+use anyhow::{anyhow, Result};
+use notan::draw::*;
+use notan::prelude::*;
+
+/// Maximum number of operators the shader can evaluate in one pass
+pub const MAX_GPU_OPS: usize = 32;
+
+/// Every operator occupies two vec4: `[code, a, b, c]` and an rgba color/vector argument
+const OP_STRIDE: usize = 8;
+const UNIFORM_LEN: usize = 4 + MAX_GPU_OPS * OP_STRIDE;
+
+//language=glsl
+const PIXEL_OPS_FRAGMENT: ShaderSource = notan::fragment_shader! {
+    r#"
+    #version 450
+    precision highp float;
+
+    layout(location = 0) in vec2 v_uvs;
+    layout(location = 1) in vec4 v_color;
+
+    layout(binding = 0) uniform sampler2D u_texture;
+
+    layout(set = 0, binding = 1) uniform PixelOps {
+        vec4 u_info;
+        vec4 u_ops[64];
+    };
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        vec4 p = texture(u_texture, v_uvs);
+        int count = int(u_info.x);
+        for (int i = 0; i < count; i++) {
+            vec4 op = u_ops[i * 2];
+            vec4 arg = u_ops[i * 2 + 1];
+            int code = int(op.x);
+            if (code == 1) {
+                // Brightness
+                p.rgb += vec3(op.y);
+            } else if (code == 2) {
+                // Exposure
+                p.rgb *= pow(2.0, op.y);
+            } else if (code == 3) {
+                // Equalize
+                p.rgb = mix(vec3(op.y), vec3(op.z), p.rgb);
+            } else if (code == 4) {
+                // Posterize
+                p.rgb = floor(p.rgb * op.y + 0.5) / op.y;
+            } else if (code == 5) {
+                // Fill
+                p = mix(p, arg, arg.a);
+            } else if (code == 6) {
+                // Desaturate
+                float val = p.r * 0.59 + p.g * 0.3 + p.b * 0.11;
+                p.rgb = mix(p.rgb, vec3(val), op.y);
+            } else if (code == 7) {
+                // Channel swap
+                p[int(op.y)] = p[int(op.z)];
+            } else if (code == 8) {
+                // Multiply
+                p *= arg;
+            } else if (code == 9) {
+                // Add
+                p += arg;
+            } else if (code == 10) {
+                // Invert
+                p.rgb = 1.0 - p.rgb;
+            } else if (code == 11) {
+                // Multiply by alpha
+                p.rgb *= p.a;
+            } else if (code == 12) {
+                // Divide by alpha
+                p.rgb /= p.a;
+            } else if (code == 13) {
+                // Contrast
+                p.rgb = (op.y * p.rgb - 0.5) + 0.5;
+            }
+        }
+        color = clamp(p, 0.0, 1.0) * v_color;
+    }
+    "#
+};
+
+/// A custom image pipeline that applies the pixel operator stack while drawing
+#[derive(Debug)]
+pub struct PixelShader {
+    pipeline: Pipeline,
+    uniforms: Buffer,
+}
+
+impl PixelShader {
+    pub fn new(gfx: &mut Graphics) -> Result<Self> {
+        let pipeline = create_image_pipeline(gfx, Some(&PIXEL_OPS_FRAGMENT))
+            .map_err(|e| anyhow!("Can't create pixel shader: {e}"))?;
+        let uniforms = gfx
+            .create_uniform_buffer(1, "PixelOps")
+            .with_data(&[0_f32; UNIFORM_LEN])
+            .build()
+            .map_err(|e| anyhow!("Can't create uniform buffer: {e}"))?;
+        Ok(Self { pipeline, uniforms })
+    }
+
+    /// Upload the operators produced by `encode_pixel_ops`
+    pub fn set_operators(&self, gfx: &mut Graphics, data: &[f32]) {
+        let mut uniforms = [0_f32; UNIFORM_LEN];
+        let len = data.len().min(UNIFORM_LEN);
+        uniforms[..len].copy_from_slice(&data[..len]);
+        gfx.set_buffer_data(&self.uniforms, &uniforms);
+    }
+
+    /// Use this shader for all following images drawn on `draw`
+    pub fn begin(&self, draw: &mut Draw) {
+        draw.image_pipeline()
+            .pipeline(&self.pipeline)
+            .uniform_buffer(&self.uniforms);
+    }
+
+    /// Restore the default image pipeline
+    pub fn end(&self, draw: &mut Draw) {
+        draw.image_pipeline().remove();
+    }
+}
+
+/// Translate a pixel operator stack into shader uniforms.
+/// Returns `None` if any operator can't be done on the GPU, in which case the CPU path must be used.
+pub fn encode_pixel_ops(stack: &[ImgOpItem]) -> Option<Vec<f32>> {
+    let stack = stack.iter().filter(|item| item.active).collect::<Vec<_>>();
+    if stack.len() > MAX_GPU_OPS {
+        return None;
+    }
+    let mut data = vec![0_f32; 4];
+    data[0] = stack.len() as f32;
+
+    for item in stack {
+        if item.has_mask() {
+            return None;
+        }
+        let (code, args, color): (f32, [f32; 3], [f32; 4]) = match &item.operation {
+            ImageOperation::Brightness(amt) => (1., [*amt as f32 / 255., 0., 0.], [0.; 4]),
+            ImageOperation::Exposure(amt) => (2., [*amt as f32 / 100. * 4., 0., 0.], [0.; 4]),
+            ImageOperation::Equalize(bounds) => (
+                3.,
+                [bounds.0 as f32 / 255., bounds.1 as f32 / 255., 0.],
+                [0.; 4],
+            ),
+            ImageOperation::Posterize(levels) => (4., [*levels as f32, 0., 0.], [0.; 4]),
+            ImageOperation::Fill(col) => (5., [0.; 3], col.map(|c| c as f32 / 255.)),
+            ImageOperation::Desaturate(amt) => (6., [*amt as f32 / 100., 0., 0.], [0.; 4]),
+            ImageOperation::ChannelSwap(channels) => (
+                7.,
+                [channels.0 as usize as f32, channels.1 as usize as f32, 0.],
+                [0.; 4],
+            ),
+            ImageOperation::Mult(amt) => (
+                8.,
+                [0.; 3],
+                [
+                    amt[0] as f32 / 255.,
+                    amt[1] as f32 / 255.,
+                    amt[2] as f32 / 255.,
+                    1.,
+                ],
+            ),
+            ImageOperation::Add(amt) => (
+                9.,
+                [0.; 3],
+                [
+                    amt[0] as f32 / 255.,
+                    amt[1] as f32 / 255.,
+                    amt[2] as f32 / 255.,
+                    0.,
+                ],
+            ),
+            ImageOperation::Invert => (10., [0.; 3], [0.; 4]),
+            ImageOperation::MMult => (11., [0.; 3], [0.; 4]),
+            ImageOperation::MDiv => (12., [0.; 3], [0.; 4]),
+            ImageOperation::Contrast(val) => {
+                let factor: f32 = (1.015_686_3 * (*val as f32 / 255. + 1.0))
+                    / (1.0 * (1.015_686_3 - *val as f32 / 255.));
+                (13., [factor, 0., 0.], [0.; 4])
+            }
+            // HSV, gradient maps, expressions and noise stay on the CPU
+            _ => return None,
+        };
+        data.push(code);
+        data.extend_from_slice(&args);
+        data.extend_from_slice(&color);
+    }
+    Some(data)
+}
+
+const CHANNEL_UNIFORM_LEN: usize = 4 + crate::channel_view::MAX_INSTRUCTIONS * 4;
+
+//language=glsl
+const CHANNEL_VIEW_FRAGMENT: ShaderSource = notan::fragment_shader! {
+    r#"
+    #version 450
+    precision highp float;
+
+    layout(location = 0) in vec2 v_uvs;
+    layout(location = 1) in vec4 v_color;
+
+    layout(binding = 0) uniform sampler2D u_texture;
+
+    layout(set = 0, binding = 1) uniform ChannelView {
+        vec4 u_info;
+        vec4 u_program[64];
+    };
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        vec4 p = texture(u_texture, v_uvs);
+        float hi = max(p.r, max(p.g, p.b));
+        float lo = min(p.r, min(p.g, p.b));
+        float chroma = hi - lo;
+        float hue = 0.0;
+        if (chroma > 0.0) {
+            if (hi == p.r) {
+                hue = mod((p.g - p.b) / chroma, 6.0);
+            } else if (hi == p.g) {
+                hue = (p.b - p.r) / chroma + 2.0;
+            } else {
+                hue = (p.r - p.g) / chroma + 4.0;
+            }
+        }
+        float vars[8] = float[8](
+            p.r, p.g, p.b, p.a,
+            dot(p.rgb, vec3(0.2126, 0.7152, 0.0722)),
+            hue / 6.0,
+            hi > 0.0 ? chroma / hi : 0.0,
+            chroma
+        );
+        float stack[16];
+        int top = 0;
+        int count = int(u_info.x);
+        for (int i = 0; i < count; i++) {
+            vec4 ins = u_program[i];
+            int code = int(ins.x);
+            if (code == 0) {
+                stack[top] = ins.y;
+                top++;
+            } else if (code == 1) {
+                stack[top] = vars[int(ins.y)];
+                top++;
+            } else if (code == 6) {
+                stack[top - 1] = -stack[top - 1];
+            } else if (code == 7) {
+                stack[top - 1] = abs(stack[top - 1]);
+            } else if (code == 8) {
+                stack[top - 1] = sqrt(max(stack[top - 1], 0.0));
+            } else {
+                float b = stack[top - 1];
+                float a = stack[top - 2];
+                top--;
+                if (code == 2) {
+                    a += b;
+                } else if (code == 3) {
+                    a -= b;
+                } else if (code == 4) {
+                    a *= b;
+                } else if (code == 5) {
+                    a /= b;
+                } else if (code == 9) {
+                    a = min(a, b);
+                } else if (code == 10) {
+                    a = max(a, b);
+                } else {
+                    a = pow(a, b);
+                }
+                stack[top - 1] = a;
+            }
+        }
+        float value = top > 0 ? clamp(stack[0], 0.0, 1.0) : 0.0;
+        color = vec4(vec3(value), 1.0) * v_color;
+    }
+    "#
+};
+
+/// A custom image pipeline that shows a computed channel view while drawing
+#[derive(Debug)]
+pub struct ChannelShader {
+    pipeline: Pipeline,
+    uniforms: Buffer,
+}
+
+impl ChannelShader {
+    pub fn new(gfx: &mut Graphics) -> Result<Self> {
+        let pipeline = create_image_pipeline(gfx, Some(&CHANNEL_VIEW_FRAGMENT))
+            .map_err(|e| anyhow!("Can't create channel shader: {e}"))?;
+        let uniforms = gfx
+            .create_uniform_buffer(1, "ChannelView")
+            .with_data(&[0_f32; CHANNEL_UNIFORM_LEN])
+            .build()
+            .map_err(|e| anyhow!("Can't create uniform buffer: {e}"))?;
+        Ok(Self { pipeline, uniforms })
+    }
+
+    /// Upload the program produced by `ChannelExpr::encode`
+    pub fn set_program(&self, gfx: &mut Graphics, data: &[f32]) {
+        let mut uniforms = [0_f32; CHANNEL_UNIFORM_LEN];
+        let len = data.len().min(CHANNEL_UNIFORM_LEN);
+        uniforms[..len].copy_from_slice(&data[..len]);
+        gfx.set_buffer_data(&self.uniforms, &uniforms);
+    }
+
+    /// Use this shader for all following images drawn on `draw`
+    pub fn begin(&self, draw: &mut Draw) {
+        draw.image_pipeline()
+            .pipeline(&self.pipeline)
+            .uniform_buffer(&self.uniforms);
+    }
+
+    /// Restore the default image pipeline
+    pub fn end(&self, draw: &mut Draw) {
+        draw.image_pipeline().remove();
+    }
+}
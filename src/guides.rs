@@ -0,0 +1,162 @@
+//! Composition guides drawn over the image, to judge crops and compositions
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+/// A line from one point to another, in image pixels
+pub type Line = ((f32, f32), (f32, f32));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+pub enum Guide {
+    Thirds,
+    /// Like thirds, but divided by the golden ratio
+    PhiGrid,
+    GoldenSpiral,
+    Center,
+    /// Action and title safe areas of a 16:9 frame
+    SafeArea,
+}
+
+impl Guide {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Thirds => "Rule of thirds",
+            Self::PhiGrid => "Golden ratio grid",
+            Self::GoldenSpiral => "Golden spiral",
+            Self::Center => "Center",
+            Self::SafeArea => "16:9 safe areas",
+        }
+    }
+
+    /// The lines of this guide over an image of `width` x `height`
+    pub fn lines(&self, width: f32, height: f32) -> Vec<Line> {
+        match self {
+            Self::Thirds => grid(width, height, 1. / 3.),
+            Self::PhiGrid => grid(width, height, 1. - 1. / PHI),
+            Self::GoldenSpiral => golden_spiral(width, height),
+            Self::Center => {
+                let (x, y) = (width / 2., height / 2.);
+                let arm = width.min(height) / 20.;
+                vec![((x - arm, y), (x + arm, y)), ((x, y - arm), (x, y + arm))]
+            }
+            Self::SafeArea => {
+                // the largest 16:9 frame in the image
+                let (frame_width, frame_height) = if width / height > 16. / 9. {
+                    (height * 16. / 9., height)
+                } else {
+                    (width, width * 9. / 16.)
+                };
+                let mut lines = vec![];
+                for area in [1.0, 0.93, 0.9].iter() {
+                    let (w, h) = (frame_width * area, frame_height * area);
+                    lines.extend(rect((width - w) / 2., (height - h) / 2., w, h));
+                }
+                lines
+            }
+        }
+    }
+}
+
+const PHI: f32 = 1.618_034;
+
+/// Two horizontal and two vertical lines at `fraction` from each side
+fn grid(width: f32, height: f32, fraction: f32) -> Vec<Line> {
+    let mut lines = vec![];
+    for f in [fraction, 1. - fraction].iter() {
+        lines.push(((width * f, 0.), (width * f, height)));
+        lines.push(((0., height * f), (width, height * f)));
+    }
+    lines
+}
+
+fn rect(x: f32, y: f32, w: f32, h: f32) -> Vec<Line> {
+    vec![
+        ((x, y), (x + w, y)),
+        ((x + w, y), (x + w, y + h)),
+        ((x + w, y + h), (x, y + h)),
+        ((x, y + h), (x, y)),
+    ]
+}
+
+/// The spiral of quarter circles in squares cut off a golden rectangle, stretched to the image
+fn golden_spiral(width: f32, height: f32) -> Vec<Line> {
+    use std::f32::consts::FRAC_PI_2;
+    const SEGMENTS: usize = 12;
+
+    // in a landscape golden rectangle of PHI x 1
+    let mut points = vec![];
+    let (mut x, mut y, mut w, mut h) = (0., 0., PHI, 1.);
+    for turn in 0..10 {
+        let s = w.min(h);
+        // arc center and start angle, the square is cut on the left, top, right and bottom in turn
+        let (center, start) = match turn % 4 {
+            0 => {
+                let c = (x + s, y + s);
+                x += s;
+                w -= s;
+                (c, 2.0)
+            }
+            1 => {
+                let c = (x, y + s);
+                y += s;
+                h -= s;
+                (c, 3.0)
+            }
+            2 => {
+                let c = (x + w - s, y);
+                w -= s;
+                (c, 0.0)
+            }
+            _ => {
+                let c = (x + w, y + h - s);
+                h -= s;
+                (c, 1.0)
+            }
+        };
+        for i in 0..=SEGMENTS {
+            let angle = (start + i as f32 / SEGMENTS as f32) * FRAC_PI_2;
+            points.push((center.0 + s * angle.cos(), center.1 + s * angle.sin()));
+        }
+    }
+
+    let portrait = height > width;
+    let lines = points.windows(2).map(|p| {
+        let point = |(px, py): (f32, f32)| {
+            if portrait {
+                (py * width, px / PHI * height)
+            } else {
+                (px / PHI * width, py * height)
+            }
+        };
+        (point(p[0]), point(p[1]))
+    });
+    lines.collect()
+}
+
+/// Common aspect ratios to matte the image to
+pub const MATTE_RATIOS: [(&str, f32); 7] = [
+    ("2.39:1", 2.39),
+    ("1.85:1", 1.85),
+    ("16:9", 16. / 9.),
+    ("3:2", 1.5),
+    ("1:1", 1.),
+    ("4:5", 0.8),
+    ("9:16", 9. / 16.),
+];
+
+/// The bars covering an image of `width` x `height` outside of the largest centered frame with
+/// `ratio` (width / height), as x, y, width and height in image pixels
+pub fn matte(width: f32, height: f32, ratio: f32) -> Vec<(f32, f32, f32, f32)> {
+    if ratio <= 0. || width <= 0. || height <= 0. {
+        return vec![];
+    }
+    if width / height > ratio {
+        // pillarbox
+        let bar = (width - height * ratio) / 2.;
+        vec![(0., 0., bar, height), (width - bar, 0., bar, height)]
+    } else {
+        // letterbox
+        let bar = (height - width / ratio) / 2.;
+        vec![(0., 0., width, bar), (0., height - bar, width, bar)]
+    }
+}
@@ -0,0 +1,303 @@
+//! A 3D preview of depth and height maps: a grid mesh displaced by the luminance of the image,
+//! rendered to a texture shown in a window and turned around with orbit controls.
+
+use anyhow::{anyhow, Result};
+use image::{imageops::FilterType, RgbaImage};
+use notan::egui::{load::SizedTexture, EguiRegisterTexture};
+use notan::math::{Mat4, Vec3};
+use notan::prelude::*;
+use std::path::PathBuf;
+
+/// Grid vertices along the longer side of the image
+pub const DEFAULT_RESOLUTION: u32 = 256;
+/// Size of the rendered preview in pixels
+const PREVIEW_SIZE: (u32, u32) = (480, 360);
+
+//language=glsl
+const MESH_VERTEX: ShaderSource = notan::vertex_shader! {
+    r#"
+    #version 450
+    layout(location = 0) in vec4 a_position;
+    layout(location = 1) in vec4 a_color;
+
+    layout(location = 0) out vec4 v_color;
+
+    layout(set = 0, binding = 0) uniform Locals {
+        mat4 u_matrix;
+    };
+
+    void main() {
+        v_color = a_color;
+        gl_Position = u_matrix * a_position;
+    }
+    "#
+};
+
+//language=glsl
+const MESH_FRAGMENT: ShaderSource = notan::fragment_shader! {
+    r#"
+    #version 450
+    precision mediump float;
+
+    layout(location = 0) in vec4 v_color;
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        color = v_color;
+    }
+    "#
+};
+
+/// A grid with a position and a color per vertex, and two triangles per cell
+#[derive(Debug, Clone, Default)]
+pub struct HeightMesh {
+    /// x, y, z, r, g, b, a of every vertex
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+impl HeightMesh {
+    /// Displace a grid by the luminance of `img`, with `resolution` vertices along its longer side.
+    /// The image lies in the xz plane, its longer side from -1 to 1, and is raised by up to
+    /// `scale` along y. Bright is high, or low if `invert`ed. Vertices are colored like the image
+    /// and shaded by a light from above.
+    pub fn build(img: &RgbaImage, resolution: u32, scale: f32, invert: bool) -> Self {
+        let longer = img.width().max(img.height()).max(1) as f32;
+        let columns = ((img.width() as f32 / longer * resolution as f32).round() as u32).max(2);
+        let rows = ((img.height() as f32 / longer * resolution as f32).round() as u32).max(2);
+        let grid = image::imageops::resize(img, columns, rows, FilterType::Triangle);
+        let (half_width, half_depth) = (img.width() as f32 / longer, img.height() as f32 / longer);
+
+        let heights = grid
+            .pixels()
+            .map(|p| {
+                let luma =
+                    (0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32) / 255.;
+                if invert {
+                    (1. - luma) * scale
+                } else {
+                    luma * scale
+                }
+            })
+            .collect::<Vec<_>>();
+        let height = |x: u32, z: u32| heights[(z * columns + x) as usize];
+        let step = (
+            half_width * 2. / (columns - 1) as f32,
+            half_depth * 2. / (rows - 1) as f32,
+        );
+        let light = Vec3::new(0.4, 0.8, 0.4).normalize();
+
+        let mut mesh = Self::default();
+        for z in 0..rows {
+            for x in 0..columns {
+                // central differences, one sided at the borders
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(columns - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(rows - 1));
+                let dx = (height(x1, z) - height(x0, z)) / ((x1 - x0) as f32 * step.0);
+                let dz = (height(x, z1) - height(x, z0)) / ((z1 - z0) as f32 * step.1);
+                let normal = Vec3::new(-dx, 1., -dz).normalize();
+                let shade = 0.3 + 0.7 * normal.dot(light).max(0.);
+                let p = grid.get_pixel(x, z);
+                mesh.vertices.extend_from_slice(&[
+                    -half_width + x as f32 * step.0,
+                    height(x, z),
+                    -half_depth + z as f32 * step.1,
+                    p[0] as f32 / 255. * shade,
+                    p[1] as f32 / 255. * shade,
+                    p[2] as f32 / 255. * shade,
+                    1.,
+                ]);
+            }
+        }
+        for z in 0..rows - 1 {
+            for x in 0..columns - 1 {
+                let i = z * columns + x;
+                mesh.indices.extend_from_slice(&[
+                    i,
+                    i + columns,
+                    i + 1,
+                    i + 1,
+                    i + columns,
+                    i + columns + 1,
+                ]);
+            }
+        }
+        mesh
+    }
+}
+
+/// A camera turning around the center of the mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    /// Rotation around the vertical axis in radians
+    pub yaw: f32,
+    /// Angle above the ground in radians
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Self {
+            yaw: 0.,
+            pitch: 0.8,
+            distance: 3.,
+        }
+    }
+}
+
+impl Orbit {
+    pub fn eye(&self) -> Vec3 {
+        self.distance
+            * Vec3::new(
+                self.pitch.cos() * self.yaw.sin(),
+                self.pitch.sin(),
+                self.pitch.cos() * self.yaw.cos(),
+            )
+    }
+
+    /// Turn by a mouse drag of `delta` pixels, keeping the camera above the ground
+    pub fn drag(&mut self, delta: (f32, f32)) {
+        self.yaw -= delta.0 * 0.01;
+        self.pitch = (self.pitch + delta.1 * 0.01).clamp(0.05, 1.55);
+    }
+
+    /// Move closer for positive `amount`
+    pub fn zoom(&mut self, amount: f32) {
+        self.distance = (self.distance * (-amount * 0.002).exp()).clamp(0.5, 20.);
+    }
+
+    /// The model view projection matrix for a view of `aspect` width / height
+    pub fn matrix(&self, aspect: f32) -> Mat4 {
+        let projection = Mat4::perspective_rh_gl(45_f32.to_radians(), aspect, 0.05, 100.);
+        let view = Mat4::look_at_rh(self.eye(), Vec3::ZERO, Vec3::Y);
+        projection * view
+    }
+}
+
+/// The pipeline, buffers and render target of the preview
+#[derive(Debug)]
+pub struct HeightRenderer {
+    pipeline: Pipeline,
+    vertices: Buffer,
+    indices: Buffer,
+    uniforms: Buffer,
+    index_count: u32,
+    target: RenderTexture,
+    pub texture: SizedTexture,
+}
+
+impl HeightRenderer {
+    pub fn new(gfx: &mut Graphics, mesh: &HeightMesh) -> Result<Self> {
+        let pipeline = gfx
+            .create_pipeline()
+            .from(&MESH_VERTEX, &MESH_FRAGMENT)
+            .with_vertex_info(&Self::vertex_info())
+            .with_depth_stencil(DepthStencil {
+                write: true,
+                compare: CompareMode::Less,
+            })
+            .build()
+            .map_err(|e| anyhow!("Can't create height map pipeline: {e}"))?;
+        let (vertices, indices) = Self::mesh_buffers(gfx, mesh)?;
+        let uniforms = gfx
+            .create_uniform_buffer(0, "Locals")
+            .with_data(&Mat4::IDENTITY)
+            .build()
+            .map_err(|e| anyhow!("Can't create uniform buffer: {e}"))?;
+        let target = gfx
+            .create_render_texture(PREVIEW_SIZE.0, PREVIEW_SIZE.1)
+            .with_depth()
+            .build()
+            .map_err(|e| anyhow!("Can't create render texture: {e}"))?;
+        let texture = gfx.egui_register_texture(&target);
+        Ok(Self {
+            pipeline,
+            vertices,
+            indices,
+            uniforms,
+            index_count: mesh.indices.len() as u32,
+            target,
+            texture,
+        })
+    }
+
+    fn vertex_info() -> VertexInfo {
+        VertexInfo::new()
+            .attr(0, VertexFormat::Float32x3)
+            .attr(1, VertexFormat::Float32x4)
+    }
+
+    fn mesh_buffers(gfx: &mut Graphics, mesh: &HeightMesh) -> Result<(Buffer, Buffer)> {
+        let vertices = gfx
+            .create_vertex_buffer()
+            .with_info(&Self::vertex_info())
+            .with_data(&mesh.vertices)
+            .build()
+            .map_err(|e| anyhow!("Can't create vertex buffer: {e}"))?;
+        let indices = gfx
+            .create_index_buffer()
+            .with_data(&mesh.indices)
+            .build()
+            .map_err(|e| anyhow!("Can't create index buffer: {e}"))?;
+        Ok((vertices, indices))
+    }
+
+    pub fn set_mesh(&mut self, gfx: &mut Graphics, mesh: &HeightMesh) -> Result<()> {
+        (self.vertices, self.indices) = Self::mesh_buffers(gfx, mesh)?;
+        self.index_count = mesh.indices.len() as u32;
+        Ok(())
+    }
+
+    /// Draw the mesh seen from `orbit` into the preview texture
+    pub fn render(&self, gfx: &mut Graphics, orbit: &Orbit, background: Color) {
+        let aspect = PREVIEW_SIZE.0 as f32 / PREVIEW_SIZE.1 as f32;
+        // render textures start with the bottom row, but egui shows them from the top
+        let flip = Mat4::from_scale(Vec3::new(1., -1., 1.));
+        gfx.set_buffer_data(&self.uniforms, &(flip * orbit.matrix(aspect)));
+        let mut renderer = gfx.create_renderer();
+        renderer.begin(Some(ClearOptions {
+            color: Some(background),
+            depth: Some(1.0),
+            stencil: None,
+        }));
+        renderer.set_pipeline(&self.pipeline);
+        renderer.bind_buffers(&[&self.vertices, &self.indices, &self.uniforms]);
+        renderer.draw(0, self.index_count as i32);
+        renderer.end();
+        gfx.render_to(&self.target, &renderer);
+    }
+}
+
+/// State of the 3D preview window
+#[derive(Debug)]
+pub struct HeightmapView {
+    pub enabled: bool,
+    pub orbit: Orbit,
+    /// Height of white relative to the longer side of the image
+    pub scale: f32,
+    /// Dark is high, like in depth maps where near is dark
+    pub invert: bool,
+    pub renderer: Option<HeightRenderer>,
+    /// The image the mesh was built from
+    pub mesh_source: Option<PathBuf>,
+    /// The mesh needs to be built again, even for the same image
+    pub needs_mesh: bool,
+    /// The preview texture needs to be drawn again
+    pub needs_render: bool,
+}
+
+impl Default for HeightmapView {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            orbit: Default::default(),
+            scale: 0.3,
+            invert: false,
+            renderer: None,
+            mesh_source: None,
+            needs_mesh: true,
+            needs_render: true,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Hot folders: a folder that receives images, like camera tethering or render output, is watched
+//! and each new image is shown as soon as it is completely written.
+
+use crate::redraw::Waker;
+use crate::scrubber::get_image_filenames_for_directory;
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the folder is listed
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Files that appear in a folder. A new file counts once its size stayed the same between two
+/// listings, so files that are still written are not opened.
+#[derive(Debug, Default)]
+pub struct Arrivals {
+    known: HashSet<PathBuf>,
+    /// New files with their size at the last listing
+    pending: HashMap<PathBuf, u64>,
+}
+
+impl Arrivals {
+    /// Files that are in the folder already don't count as new
+    pub fn new(existing: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            known: existing.into_iter().collect(),
+            pending: Default::default(),
+        }
+    }
+
+    /// The files that arrived completely since the last listing, in the order of `files`
+    pub fn update(&mut self, files: &[(PathBuf, u64)]) -> Vec<PathBuf> {
+        let mut arrived = vec![];
+        let mut pending = HashMap::new();
+        for (path, size) in files {
+            if self.known.contains(path) {
+                continue;
+            }
+            if *size > 0 && self.pending.get(path) == Some(size) {
+                self.known.insert(path.clone());
+                arrived.push(path.clone());
+            } else {
+                pending.insert(path.clone(), *size);
+            }
+        }
+        // deleted files are forgotten, so they count as new when they come back
+        let present: HashSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+        self.known.retain(|path| present.contains(path));
+        self.pending = pending;
+        arrived
+    }
+}
+
+fn list(folder: &Path, all_files: bool) -> Vec<(PathBuf, u64)> {
+    get_image_filenames_for_directory(folder, all_files)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let size = std::fs::metadata(&path)
+                .map(|m| m.len())
+                .unwrap_or_default();
+            (path, size)
+        })
+        .collect()
+}
+
+/// Send images that arrive in `folder` until the returned flag is set. `waker` wakes the app for
+/// each of them.
+pub fn watch(
+    folder: PathBuf,
+    all_files: bool,
+    sender: Sender<PathBuf>,
+    waker: Waker,
+) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || {
+        let mut arrivals = Arrivals::new(list(&folder, all_files).into_iter().map(|(p, _)| p));
+        while !stopped.load(Ordering::Relaxed) {
+            thread::sleep(INTERVAL);
+            for path in arrivals.update(&list(&folder, all_files)) {
+                if sender.send(path).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        }
+        debug!("Stopped watching {}", folder.display());
+    });
+    stop
+}
+
+/// Play the notification sound of the desktop, without waiting for it
+pub fn play_sound() {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            // the sound plays asynchronously, and stops when PowerShell exits
+            "[System.Media.SystemSounds]::Asterisk.Play(); Start-Sleep -Milliseconds 800",
+        ]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("afplay");
+        command.arg("/System/Library/Sounds/Glass.aiff");
+        command
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new("canberra-gtk-play");
+        command.args(["-i", "message-new-instant"]);
+        command
+    };
+    thread::spawn(move || {
+        if let Err(e) = command.stdout(Stdio::null()).stderr(Stdio::null()).status() {
+            debug!("Can't play a sound: {e}");
+        }
+    });
+}
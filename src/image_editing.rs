@@ -3,10 +3,13 @@ use std::fmt;
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 
+use crate::appstate::ImageGeometry;
 use crate::paint::PaintStroke;
 use crate::ui::EguiExt;
+use crate::FONT;
 
-use anyhow::Result;
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{anyhow, Result};
 use evalexpr::*;
 use fast_image_resize as fr;
 use image::{imageops, DynamicImage, RgbaImage};
@@ -16,7 +19,10 @@ use notan::egui::{self, lerp, vec2, DragValue, Id, Sense, Vec2};
 use notan::egui::{Response, Ui};
 use palette::{rgb::Rgb, Hsl, IntoColor};
 use rand::{thread_rng, Rng};
-use rayon::{iter::ParallelIterator, slice::ParallelSliceMut};
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    slice::{ParallelSlice, ParallelSliceMut},
+};
 use serde::{Deserialize, Serialize};
 
 use egui_phosphor::variants::regular::*;
@@ -35,9 +41,290 @@ pub struct EditState {
     pub paint_fade: bool,
     #[serde(skip, default = "default_brushes")]
     pub brushes: Vec<RgbaImage>,
-    pub pixel_op_stack: Vec<ImageOperation>,
-    pub image_op_stack: Vec<ImageOperation>,
+    pub pixel_op_stack: Vec<ImgOpItem>,
+    pub image_op_stack: Vec<ImgOpItem>,
     pub export_extension: String,
+    /// Evaluate edits at full resolution while adjusting them instead of using a downscaled proxy
+    #[serde(default)]
+    pub full_res_preview: bool,
+    #[serde(skip)]
+    /// The image operators were evaluated on a proxy and need to be computed at full resolution
+    pub preview_pending: bool,
+    #[serde(skip)]
+    /// The pixel operators were evaluated on a proxy and need to be computed at full resolution
+    pub pixel_preview_pending: bool,
+    #[serde(skip)]
+    /// Downscaled result of the image operators, used as the base of the pixel operators while previewing
+    pub preview_base: Option<RgbaImage>,
+    #[serde(skip)]
+    /// The result after each step of the image operator stack, so that only changed steps need to be evaluated again
+    pub image_op_cache: Vec<(ImgOpItem, RgbaImage)>,
+    #[serde(skip)]
+    /// The texture holds the result of the image operators and the pixel operators are applied while drawing
+    pub gpu_preview: bool,
+    #[serde(skip)]
+    /// Show the original image left of this fraction of its width, to compare it with the edits
+    pub split: Option<f32>,
+    #[serde(skip)]
+    /// The line between the original and the edited image is being dragged
+    pub split_drag: bool,
+    #[serde(skip)]
+    /// Show the whole original image while the compare key is held
+    pub show_original: bool,
+    #[serde(skip, default = "default_auto_strength")]
+    /// How strongly auto enhance changes the image, from 0 to 1
+    pub auto_strength: f32,
+    #[serde(skip)]
+    /// What auto enhance put at the start of the pixel stack, to replace it when run again
+    pub auto_ops: Option<Vec<ImgOpItem>>,
+}
+
+/// Largest side of the proxy image used to preview operators while dragging
+pub const PREVIEW_SIZE: u32 = 1024;
+
+impl EditState {
+    /// The factor an image of `dimensions` is scaled by while previewing edits.
+    /// Returns 1.0 if no proxy is needed.
+    pub fn preview_factor(&self, dimensions: (u32, u32)) -> f32 {
+        if self.full_res_preview {
+            return 1.0;
+        }
+        (PREVIEW_SIZE as f32 / dimensions.0.max(dimensions.1).max(1) as f32).min(1.0)
+    }
+
+    /// A proxy is displayed instead of the full resolution result
+    pub fn previewing(&self) -> bool {
+        self.preview_pending || self.pixel_preview_pending
+    }
+
+    /// The edits rotate or flip the image, so its original EXIF orientation no longer applies
+    pub fn reorients(&self) -> bool {
+        self.image_op_stack.iter().any(|item| {
+            item.active
+                && matches!(
+                    item.operation,
+                    ImageOperation::Rotate(_) | ImageOperation::Flip(_)
+                )
+        })
+    }
+
+    /// Where the original and the edited image meet on screen, for an original `width` pixels wide
+    pub fn split_line(&self, geometry: &ImageGeometry, width: u32) -> Option<f32> {
+        self.split
+            .map(|split| geometry.offset.x + split * width as f32 * geometry.scale)
+    }
+
+    /// Move the line between the original and the edited image to `x` on screen
+    pub fn move_split(&mut self, x: f32, geometry: &ImageGeometry, width: u32) {
+        if width > 0 && geometry.scale > 0. {
+            let split = (x - geometry.offset.x) / (width as f32 * geometry.scale);
+            self.split = Some(split.clamp(0., 1.));
+        }
+    }
+
+    /// The operators of the last auto enhance are still at the start of the pixel stack, unchanged
+    pub fn auto_enhanced(&self) -> bool {
+        match &self.auto_ops {
+            Some(ops) => {
+                self.pixel_op_stack.len() >= ops.len()
+                    && self
+                        .pixel_op_stack
+                        .iter()
+                        .zip(ops)
+                        .all(|(item, auto)| item.same_result(auto))
+            }
+            None => false,
+        }
+    }
+
+    /// Put auto enhance operators at the start of the pixel stack, replacing the ones from the
+    /// last time if they were not changed since
+    pub fn set_auto_ops(&mut self, ops: Vec<ImgOpItem>) {
+        if self.auto_enhanced() {
+            let previous = self
+                .auto_ops
+                .as_ref()
+                .map(|ops| ops.len())
+                .unwrap_or_default();
+            self.pixel_op_stack.drain(..previous);
+        }
+        self.pixel_op_stack.splice(0..0, ops.iter().cloned());
+        self.auto_ops = Some(ops);
+    }
+
+    /// The mask of an operator is being painted, so the mouse should not move the image
+    pub fn mask_painting(&self) -> bool {
+        self.image_op_stack
+            .iter()
+            .chain(&self.pixel_op_stack)
+            .any(|item| item.mask_editing)
+    }
+}
+
+/// A named edit chain that can be applied to any image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditPreset {
+    pub name: String,
+    pub image_op_stack: Vec<ImgOpItem>,
+    pub pixel_op_stack: Vec<ImgOpItem>,
+}
+
+impl EditPreset {
+    /// Capture the edit stacks. Masks are painted for a specific image, so they are not kept.
+    pub fn new(name: &str, edit_state: &EditState) -> Self {
+        let strip_mask = |stack: &Vec<ImgOpItem>| {
+            stack
+                .iter()
+                .map(|item| ImgOpItem {
+                    mask: vec![],
+                    mask_editing: false,
+                    ..item.clone()
+                })
+                .collect()
+        };
+        Self {
+            name: name.into(),
+            image_op_stack: strip_mask(&edit_state.image_op_stack),
+            pixel_op_stack: strip_mask(&edit_state.pixel_op_stack),
+        }
+    }
+
+    /// Replace the edit stacks with this preset.
+    /// The results are cleared, so they are evaluated again by the edit panel.
+    pub fn apply(&self, edit_state: &mut EditState) {
+        edit_state.image_op_stack = self.image_op_stack.clone();
+        edit_state.pixel_op_stack = self.pixel_op_stack.clone();
+        edit_state.result_image_op = Default::default();
+        edit_state.result_pixel_op = Default::default();
+    }
+
+    fn path() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or(anyhow!("Can't get config dir"))?
+            .join("oculante")
+            .join("presets.json"))
+    }
+
+    pub fn load_all() -> Result<Vec<Self>> {
+        let f = std::fs::File::open(Self::path()?)?;
+        Ok(serde_json::from_reader(f)?)
+    }
+
+    pub fn save_all(presets: &[Self]) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let f = std::fs::File::create(path)?;
+        Ok(serde_json::to_writer_pretty(f, presets)?)
+    }
+}
+
+/// An operator in one of the edit stacks, optionally limited to a painted mask
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredOperation")]
+pub struct ImgOpItem {
+    pub operation: ImageOperation,
+    /// Brush strokes defining where the operator applies. Without strokes, it applies everywhere.
+    pub mask: Vec<PaintStroke>,
+    /// Disabled operators stay in the stack, but are skipped
+    pub active: bool,
+    #[serde(skip)]
+    /// Painting on the image adds to the mask of this operator
+    pub mask_editing: bool,
+}
+
+/// Edits saved by older versions only contain the plain operator
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredOperation {
+    Item {
+        operation: ImageOperation,
+        #[serde(default)]
+        mask: Vec<PaintStroke>,
+        #[serde(default = "default_active")]
+        active: bool,
+    },
+    Plain(ImageOperation),
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl From<StoredOperation> for ImgOpItem {
+    fn from(stored: StoredOperation) -> Self {
+        match stored {
+            StoredOperation::Item {
+                operation,
+                mask,
+                active,
+            } => Self {
+                mask,
+                active,
+                ..Self::new(operation)
+            },
+            StoredOperation::Plain(operation) => Self::new(operation),
+        }
+    }
+}
+
+impl ImgOpItem {
+    pub fn new(operation: ImageOperation) -> Self {
+        Self {
+            operation,
+            mask: vec![],
+            active: true,
+            mask_editing: false,
+        }
+    }
+
+    /// Both items produce the same result
+    pub fn same_result(&self, other: &Self) -> bool {
+        self.operation == other.operation && self.mask == other.mask && self.active == other.active
+    }
+
+    pub fn has_mask(&self) -> bool {
+        self.mask.iter().any(|stroke| !stroke.is_empty())
+    }
+
+    /// Rasterize the mask into per-pixel weights from 0-1, or None if the operator is not masked
+    pub fn mask_weights(&self, dimensions: (u32, u32), brushes: &[RgbaImage]) -> Option<Vec<f32>> {
+        if !self.has_mask() {
+            return None;
+        }
+        let mut canvas = RgbaImage::new(dimensions.0, dimensions.1);
+        for stroke in self.mask.iter().filter(|stroke| !stroke.is_empty()) {
+            stroke.render(&mut canvas, brushes);
+        }
+        Some(canvas.pixels().map(|p| p[3] as f32 / 255.).collect())
+    }
+
+    /// Process the operator, only keeping the result where the mask is painted.
+    /// Operators that change the image size ignore the mask.
+    pub fn process_image(&self, img: &mut RgbaImage, brushes: &[RgbaImage]) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        let weights = match self.mask_weights(img.dimensions(), brushes) {
+            Some(weights) => weights,
+            None => return self.operation.process_image(img),
+        };
+        let original = img.clone();
+        self.operation.process_image(img)?;
+        if img.dimensions() != original.dimensions() {
+            return Ok(());
+        }
+        img.par_chunks_mut(4)
+            .zip(original.par_chunks(4))
+            .zip(weights.par_iter())
+            .for_each(|((p, o), w)| {
+                for c in 0..4 {
+                    p[c] = lerp(o[c] as f32..=p[c] as f32, *w).round() as u8;
+                }
+            });
+        Ok(())
+    }
 }
 
 impl Default for EditState {
@@ -53,10 +340,25 @@ impl Default for EditState {
             pixel_op_stack: vec![],
             image_op_stack: vec![],
             export_extension: "png".into(),
+            full_res_preview: false,
+            preview_pending: false,
+            pixel_preview_pending: false,
+            preview_base: None,
+            image_op_cache: vec![],
+            gpu_preview: false,
+            split: None,
+            split_drag: false,
+            show_original: false,
+            auto_strength: default_auto_strength(),
+            auto_ops: None,
         }
     }
 }
 
+fn default_auto_strength() -> f32 {
+    1.0
+}
+
 fn default_brushes() -> Vec<RgbaImage> {
     vec![
         image::load_from_memory(include_bytes!("../res/brushes/brush1.png"))
@@ -93,6 +395,74 @@ pub enum ScaleFilter {
     CatmullRom,
     Mitchell,
     Lanczos3,
+    /// Content aware: removes the least noticeable seams instead of scaling everything
+    SeamCarving,
+}
+
+/// Where to place an overlay on the image
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// All anchors in reading order, so they can be shown as a 3x3 grid
+    pub const ALL: [Anchor; 9] = [
+        Self::TopLeft,
+        Self::Top,
+        Self::TopRight,
+        Self::Left,
+        Self::Center,
+        Self::Right,
+        Self::BottomLeft,
+        Self::Bottom,
+        Self::BottomRight,
+    ];
+
+    /// Top left position of an item of `size` placed inside `canvas`, keeping `margin` pixels to the border
+    pub fn position(&self, canvas: (u32, u32), size: (u32, u32), margin: u32) -> (i64, i64) {
+        let index = Self::ALL.iter().position(|a| a == self).unwrap_or_default() as i64;
+        let place = |column: i64, canvas: u32, size: u32| {
+            let free = canvas as i64 - size as i64;
+            match column {
+                0 => margin as i64,
+                1 => free / 2,
+                _ => free - margin as i64,
+            }
+        };
+        (
+            place(index % 3, canvas.0, size.0),
+            place(index / 3, canvas.1, size.1),
+        )
+    }
+}
+
+fn anchor_ui(ui: &mut Ui, anchor: &mut Anchor, response: &mut Response) {
+    egui::Grid::new("anchor")
+        .spacing(vec2(2., 2.))
+        .show(ui, |ui| {
+            for (i, a) in Anchor::ALL.iter().enumerate() {
+                if ui
+                    .selectable_label(anchor == a, "  ")
+                    .on_hover_text(format!("{a:?}"))
+                    .clicked()
+                {
+                    *anchor = *a;
+                    response.changed = true;
+                }
+                if i % 3 == 2 {
+                    ui.end_row();
+                }
+            }
+        });
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
@@ -119,18 +489,72 @@ pub enum ImageOperation {
     ChannelSwap((Channel, Channel)),
     Invert,
     Blur(u8),
+    Denoise {
+        strength: u8,
+        radius: u8,
+    },
+    Sharpen {
+        amt: u8,
+        radius: u8,
+    },
     MMult,
     MDiv,
     Resize {
         dimensions: (u32, u32),
         aspect: bool,
         filter: ScaleFilter,
+        /// If set, scale relative to the incoming image instead of using `dimensions`
+        #[serde(default)]
+        percent: Option<u32>,
     },
     /// Left, right, top, bottom
     // x,y (top left corner of crop), width, height
     // 1.0 equals 10000
     Crop([u32; 4]),
+    /// Crop away uniform borders, whose pixels differ by at most this much from the corner
+    Trim(u8),
+    /// Corners of the quad to rectify: top left, top right, bottom right, bottom left
+    // 1.0 equals 10000
+    Perspective {
+        corners: [(u32, u32); 4],
+        #[serde(skip)]
+        /// Show the unmodified image while corners are being placed
+        editing: bool,
+    },
+    /// Rotate by an arbitrary angle in 1/100 degrees, optionally cropping away the empty corners
+    Straighten {
+        angle: i32,
+        crop: bool,
+        #[serde(skip)]
+        /// A horizon line is being drawn on the unmodified image
+        editing: bool,
+    },
     LUT(String),
+    /// Composite an image file on top. Size and margin are relative to the image width,
+    /// so the same watermark can be applied to images of any size.
+    Watermark {
+        path: String,
+        anchor: Anchor,
+        /// Percent of the image width
+        scale: u8,
+        /// Percent of the image width
+        margin: u8,
+        opacity: u8,
+    },
+    /// Render text on top of the image. An empty font path uses the built-in font.
+    Text {
+        text: String,
+        font: String,
+        /// Percent of the image height
+        size: u8,
+        color: [u8; 4],
+        /// Percent of the font size
+        outline: u8,
+        outline_color: [u8; 4],
+        anchor: Anchor,
+        /// Percent of the image width
+        margin: u8,
+    },
 }
 
 impl fmt::Display for ImageOperation {
@@ -147,7 +571,12 @@ impl fmt::Display for ImageOperation {
             Self::Add(_) => write!(f, "➕ Add color"),
             Self::Fill(_) => write!(f, "{PAINT_BUCKET} Fill color"),
             Self::Blur(_) => write!(f, "{DROP} Blur"),
+            Self::Denoise { .. } => write!(f, "{SPARKLE} Denoise"),
+            Self::Sharpen { .. } => write!(f, "{DIAMOND} Sharpen"),
             Self::Crop(_) => write!(f, "{CROP} Crop"),
+            Self::Trim(_) => write!(f, "{SCISSORS} Trim borders"),
+            Self::Perspective { .. } => write!(f, "{PERSPECTIVE} Perspective"),
+            Self::Straighten { .. } => write!(f, "{LINE_SEGMENT} Straighten"),
             Self::Flip(_) => write!(f, "{SWAP} Flip"),
             Self::Rotate(_) => write!(f, "{ARROW_CLOCKWISE} Rotate"),
             Self::Invert => write!(f, "{SELECTION_INVERSE} Invert"),
@@ -160,6 +589,8 @@ impl fmt::Display for ImageOperation {
             Self::MMult => write!(f, "✖ Multiply with alpha"),
             Self::MDiv => write!(f, "➗ Divide by alpha"),
             Self::LUT(_) => write!(f, "{FILM_STRIP} Apply Color LUT"),
+            Self::Watermark { .. } => write!(f, "{STAMP} Watermark"),
+            Self::Text { .. } => write!(f, "{TEXT_T} Text"),
             // _ => write!(f, "Not implemented Display"),
         }
     }
@@ -169,17 +600,56 @@ impl ImageOperation {
     pub fn is_per_pixel(&self) -> bool {
         match self {
             Self::Blur(_) => false,
+            Self::Denoise { .. } => false,
+            Self::Sharpen { .. } => false,
             Self::Resize { .. } => false,
             // Self::GradientMap { .. } => false,
             Self::Crop(_) => false,
+            Self::Trim(_) => false,
+            Self::Perspective { .. } => false,
+            Self::Straighten { .. } => false,
             Self::Rotate(_) => false,
             Self::Flip(_) => false,
             Self::ChromaticAberration(_) => false,
             Self::LUT(_) => false,
+            Self::Watermark { .. } => false,
+            Self::Text { .. } => false,
             _ => true,
         }
     }
 
+    /// Return a copy of this operator with all pixel-based values scaled by `factor`.
+    /// This is needed to make operators look the same on a downscaled preview.
+    pub fn scaled(&self, factor: f32) -> Self {
+        let scale = |v: u8| ((v as f32 * factor).round() as u8).max(1);
+        match self {
+            Self::Blur(amt) => Self::Blur((*amt as f32 * factor).round() as u8),
+            Self::Denoise { strength, radius } => Self::Denoise {
+                strength: *strength,
+                radius: scale(*radius),
+            },
+            Self::Sharpen { amt, radius } => Self::Sharpen {
+                amt: *amt,
+                radius: scale(*radius),
+            },
+            Self::Resize {
+                dimensions,
+                aspect,
+                filter,
+                percent,
+            } => Self::Resize {
+                dimensions: (
+                    ((dimensions.0 as f32 * factor) as u32).max(1),
+                    ((dimensions.1 as f32 * factor) as u32).max(1),
+                ),
+                aspect: *aspect,
+                filter: *filter,
+                percent: *percent,
+            },
+            _ => self.clone(),
+        }
+    }
+
     // Add functionality about how to draw UI here
     pub fn ui(&mut self, ui: &mut Ui) -> Response {
         // ui.label_i(&format!("{}", self));
@@ -259,6 +729,129 @@ impl ImageOperation {
                 })
                 .inner
             }
+            Self::Watermark {
+                path,
+                anchor,
+                scale,
+                margin,
+                opacity,
+            } => {
+                let mut r = ui.allocate_response(Vec2::ZERO, Sense::click());
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        let t = ui.add(
+                            egui::TextEdit::singleline(path)
+                                .hint_text("Path to image")
+                                .desired_width(ui.available_width() - 40.),
+                        );
+                        if t.lost_focus() {
+                            r.changed = true;
+                        }
+                        #[cfg(feature = "file_open")]
+                        if ui.button(FOLDER).on_hover_text("Browse").clicked() {
+                            if let Some(file) = rfd::FileDialog::new()
+                                .add_filter("Image", &["png", "webp", "tif", "tiff"])
+                                .pick_file()
+                            {
+                                *path = file.to_string_lossy().to_string();
+                                r.changed = true;
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        anchor_ui(ui, anchor, &mut r);
+                        ui.vertical(|ui| {
+                            for (val, range, label) in [
+                                (scale, 1..=100, "Size "),
+                                (margin, 0..=25, "Margin "),
+                                (opacity, 0..=100, "Opacity "),
+                            ] {
+                                if ui
+                                    .add(DragValue::new(val).clamp_range(range).prefix(label).suffix("%"))
+                                    .changed()
+                                {
+                                    r.changed = true;
+                                }
+                            }
+                        });
+                    });
+                });
+                r
+            }
+            Self::Text {
+                text,
+                font,
+                size,
+                color,
+                outline,
+                outline_color,
+                anchor,
+                margin,
+            } => {
+                let mut r = ui.allocate_response(Vec2::ZERO, Sense::click());
+                ui.vertical(|ui| {
+                    if ui
+                        .add(egui::TextEdit::multiline(text).desired_rows(2))
+                        .changed()
+                    {
+                        r.changed = true;
+                    }
+                    ui.horizontal(|ui| {
+                        let t = ui.add(
+                            egui::TextEdit::singleline(font)
+                                .hint_text("Built-in font")
+                                .desired_width(ui.available_width() - 40.),
+                        );
+                        if t.lost_focus() {
+                            r.changed = true;
+                        }
+                        #[cfg(feature = "file_open")]
+                        if ui.button(FOLDER).on_hover_text("Select font file").clicked() {
+                            if let Some(file) = rfd::FileDialog::new()
+                                .add_filter("Font", &["ttf", "otf"])
+                                .pick_file()
+                            {
+                                *font = file.to_string_lossy().to_string();
+                                r.changed = true;
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        anchor_ui(ui, anchor, &mut r);
+                        ui.vertical(|ui| {
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add(DragValue::new(size).clamp_range(1..=50).prefix("Size ").suffix("%"))
+                                    .changed()
+                                {
+                                    r.changed = true;
+                                }
+                                if ui.color_edit_button_srgba_unmultiplied(color).changed() {
+                                    r.changed = true;
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add(DragValue::new(outline).clamp_range(0..=20).prefix("Outline ").suffix("%"))
+                                    .changed()
+                                {
+                                    r.changed = true;
+                                }
+                                if ui.color_edit_button_srgba_unmultiplied(outline_color).changed() {
+                                    r.changed = true;
+                                }
+                            });
+                            if ui
+                                .add(DragValue::new(margin).clamp_range(0..=25).prefix("Margin ").suffix("%"))
+                                .changed()
+                            {
+                                r.changed = true;
+                            }
+                        });
+                    });
+                });
+                r
+            }
             Self::ChannelSwap(val) => {
                 let mut r = ui.allocate_response(Vec2::ZERO, Sense::click());
                 let combo_width = 50.;
@@ -314,6 +907,28 @@ impl ImageOperation {
                 r
             }
             Self::Blur(val) => ui.slider_styled(val, 0..=20),
+            Self::Denoise { strength, radius } => {
+                let mut r = ui.slider_styled(strength, 0..=100);
+                if ui
+                    .add(DragValue::new(radius).clamp_range(1..=8).prefix("r "))
+                    .on_hover_text("Radius of the neighbourhood in pixels. Larger is slower.")
+                    .changed()
+                {
+                    r.changed = true
+                }
+                r
+            }
+            Self::Sharpen { amt, radius } => {
+                let mut r = ui.slider_styled(amt, 0..=200);
+                if ui
+                    .add(DragValue::new(radius).clamp_range(1..=20).prefix("r "))
+                    .on_hover_text("Radius of the unsharp mask")
+                    .changed()
+                {
+                    r.changed = true
+                }
+                r
+            }
             Self::Noise { amt, mono } => {
                 let mut r = ui.slider_styled(amt, 0..=100);
                 if ui.checkbox(mono, "Grey").changed() {
@@ -512,6 +1127,9 @@ impl ImageOperation {
                 r
             }
             Self::Desaturate(val) => ui.slider_styled(val, 0..=100),
+            Self::Trim(tolerance) => ui
+                .slider_styled(tolerance, 0..=128)
+                .on_hover_text("How much border pixels may differ from the top left corner, for noisy scans or compressed screenshots"),
             Self::Contrast(val) => ui.slider_styled(val, -128..=128),
             Self::Crop(bounds) => {
                 let mut float_bounds = bounds.map(|b| b as f32 / 10000.);
@@ -565,6 +1183,58 @@ impl ImageOperation {
                 })
                 .inner
             }
+            Self::Perspective { corners, editing } => {
+                ui.horizontal(|ui| {
+                    let mut r = ui
+                        .selectable_label(*editing, format!("{HAND_GRABBING} Place corners"))
+                        .on_hover_text("Drag the corners on the image to the edges of the area that should become a rectangle");
+                    if r.clicked() {
+                        *editing = !*editing;
+                        r.changed = true;
+                    }
+                    if ui.button("Reset").clicked() {
+                        *corners = [(0, 0), (10000, 0), (10000, 10000), (0, 10000)];
+                        r.changed = true;
+                    }
+                    r
+                })
+                .inner
+            }
+            Self::Straighten {
+                angle,
+                crop,
+                editing,
+            } => {
+                let mut degrees = *angle as f32 / 100.;
+                ui.horizontal(|ui| {
+                    let mut r = ui.add(
+                        egui::DragValue::new(&mut degrees)
+                            .speed(0.05)
+                            .clamp_range(-45.0..=45.0)
+                            .max_decimals(2)
+                            .suffix("°"),
+                    );
+                    if r.changed() {
+                        *angle = (degrees * 100.).round() as i32;
+                    }
+                    let r1 = ui
+                        .selectable_label(*editing, format!("{RULER} Horizon"))
+                        .on_hover_text("Draw a line on the image along something that should be level");
+                    if r1.clicked() {
+                        *editing = !*editing;
+                        r.changed = true;
+                    }
+                    if ui
+                        .checkbox(crop, "Crop")
+                        .on_hover_text("Crop away the empty corners")
+                        .changed()
+                    {
+                        r.changed = true;
+                    }
+                    r
+                })
+                .inner
+            }
             Self::Equalize(bounds) => {
                 let available_w_single_spacing =
                     ui.available_width() - ui.style().spacing.item_spacing.x * 1.;
@@ -643,10 +1313,31 @@ impl ImageOperation {
                 dimensions,
                 aspect,
                 filter,
+                percent,
             } => {
                 let ratio = dimensions.1 as f32 / dimensions.0 as f32;
 
                 ui.horizontal(|ui| {
+                    if let Some(pct) = percent {
+                        let mut r0 = ui.add(
+                            egui::DragValue::new(pct)
+                                .speed(1.)
+                                .clamp_range(1..=1000)
+                                .suffix("%"),
+                        );
+                        // Same as below, only update on release
+                        r0.changed = r0.drag_released() || r0.lost_focus();
+                        let r1 = ui
+                            .selectable_label(true, "%")
+                            .on_hover_text("Switch to pixel dimensions");
+                        if r1.clicked() {
+                            *percent = None;
+                            r0.changed = true;
+                        }
+                        filter_combo(ui, filter, &mut r0);
+                        return r0;
+                    }
+
                     let mut r0 = ui.add(
                         egui::DragValue::new(&mut dimensions.0)
                             .speed(4.)
@@ -687,22 +1378,16 @@ impl ImageOperation {
                     // more snappy.
                     r0.changed = r0.drag_released() || r1.drag_released() || r2.changed();
 
-                    egui::ComboBox::from_id_source("filter")
-                        .selected_text(format!("{filter:?}"))
-                        .show_ui(ui, |ui| {
-                            for f in [
-                                ScaleFilter::Box,
-                                ScaleFilter::Bilinear,
-                                ScaleFilter::Hamming,
-                                ScaleFilter::CatmullRom,
-                                ScaleFilter::Mitchell,
-                                ScaleFilter::Lanczos3,
-                            ] {
-                                if ui.selectable_value(filter, f, format!("{f:?}")).clicked() {
-                                    r0.changed = true;
-                                }
-                            }
-                        });
+                    if ui
+                        .selectable_label(false, "%")
+                        .on_hover_text("Switch to percentage of the image size")
+                        .clicked()
+                    {
+                        *percent = Some(100);
+                        r0.changed = true;
+                    }
+
+                    filter_combo(ui, filter, &mut r0);
 
                     r0
                 })
@@ -720,6 +1405,16 @@ impl ImageOperation {
                     *img = imageops::blur(img, *amt as f32);
                 }
             }
+            Self::Denoise { strength, radius } => {
+                if *strength != 0 {
+                    *img = bilateral_filter(img, *radius as u32, *strength as f32 / 100.);
+                }
+            }
+            Self::Sharpen { amt, radius } => {
+                if *amt != 0 {
+                    unsharp_mask(img, *radius as f32 / 2., *amt as f32 / 100.);
+                }
+            }
             Self::LUT(lut_name) => {
                 use lutgen::identity::correct_image;
                 let mut external_image = DynamicImage::ImageRgba8(img.clone()).to_rgb8();
@@ -733,6 +1428,71 @@ impl ImageOperation {
                 }
                 *img = DynamicImage::ImageRgb8(external_image).to_rgba8();
             }
+            Self::Watermark {
+                path,
+                anchor,
+                scale,
+                margin,
+                opacity,
+            } => {
+                if path.is_empty() {
+                    return Ok(());
+                }
+                let mut mark = image::open(path)?.into_rgba8();
+                let width = (img.width() * *scale as u32 / 100).max(1);
+                let height =
+                    ((mark.height() as f32 * width as f32 / mark.width() as f32) as u32).max(1);
+                mark = imageops::resize(&mark, width, height, imageops::FilterType::CatmullRom);
+                for p in mark.pixels_mut() {
+                    p[3] = (p[3] as u32 * *opacity as u32 / 100) as u8;
+                }
+                let (x, y) = anchor.position(
+                    img.dimensions(),
+                    mark.dimensions(),
+                    img.width() * *margin as u32 / 100,
+                );
+                imageops::overlay(img, &mark, x, y);
+            }
+            Self::Text {
+                text,
+                font,
+                size,
+                color,
+                outline,
+                outline_color,
+                anchor,
+                margin,
+            } => {
+                if text.trim().is_empty() {
+                    return Ok(());
+                }
+                let font_data = if font.is_empty() {
+                    FONT.to_vec()
+                } else {
+                    std::fs::read(font)?
+                };
+                let size = (img.height() * *size as u32) as f32 / 100.;
+                let align = match anchor {
+                    Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0.0,
+                    Anchor::Top | Anchor::Center | Anchor::Bottom => 0.5,
+                    _ => 1.0,
+                };
+                let stamp = render_text(
+                    text,
+                    &font_data,
+                    size,
+                    *color,
+                    size * *outline as f32 / 100.,
+                    *outline_color,
+                    align,
+                )?;
+                let (x, y) = anchor.position(
+                    img.dimensions(),
+                    stamp.dimensions(),
+                    img.width() * *margin as u32 / 100,
+                );
+                imageops::overlay(img, &stamp, x, y);
+            }
             Self::Crop(dim) => {
                 if *dim != [0, 0, 0, 0] {
                     let window = cropped_range(dim, &(img.width(), img.height()));
@@ -741,10 +1501,30 @@ impl ImageOperation {
                     *img = sub_img.to_image();
                 }
             }
+            Self::Trim(tolerance) => {
+                if let Some([x, y, width, height]) = trimmed_range(img, *tolerance) {
+                    *img = imageops::crop_imm(img, x, y, width, height).to_image();
+                }
+            }
             Self::Resize {
-                dimensions, filter, ..
+                dimensions,
+                filter,
+                percent,
+                ..
             } => {
-                if *dimensions != Default::default() {
+                let dimensions = match percent {
+                    Some(pct) => (
+                        (img.width() * pct / 100).max(1),
+                        (img.height() * pct / 100).max(1),
+                    ),
+                    None => *dimensions,
+                };
+                if dimensions == img.dimensions() {
+                    return Ok(());
+                }
+                if *filter == ScaleFilter::SeamCarving {
+                    *img = seam_carve(img, dimensions.0, dimensions.1);
+                } else if dimensions != Default::default() {
                     let filter = match filter {
                         ScaleFilter::Box => fr::FilterType::Box,
                         ScaleFilter::Bilinear => fr::FilterType::Bilinear,
@@ -752,6 +1532,8 @@ impl ImageOperation {
                         ScaleFilter::CatmullRom => fr::FilterType::CatmullRom,
                         ScaleFilter::Mitchell => fr::FilterType::Mitchell,
                         ScaleFilter::Lanczos3 => fr::FilterType::Lanczos3,
+                        // handled above
+                        ScaleFilter::SeamCarving => fr::FilterType::Lanczos3,
                     };
 
                     let width = NonZeroU32::new(img.width()).unwrap_or(anyhow::Context::context(
@@ -798,6 +1580,20 @@ impl ImageOperation {
                     )?;
                 }
             }
+            Self::Perspective { corners, editing } => {
+                if !*editing {
+                    *img = perspective_warp(img, corners);
+                }
+            }
+            Self::Straighten {
+                angle,
+                crop,
+                editing,
+            } => {
+                if !*editing && *angle != 0 {
+                    *img = rotate_arbitrary(img, *angle as f32 / 100., *crop);
+                }
+            }
             Self::Rotate(angle) => {
                 match angle {
                     90 => *img = image::imageops::rotate90(img),
@@ -999,6 +1795,465 @@ pub fn desaturate(p: &mut Vector4<f32>, factor: f32) {
     p[1] = egui::lerp(p[1]..=val, factor);
     p[2] = egui::lerp(p[2]..=val, factor);
 }
+
+/// Warp the quad given by `corners` (top left, top right, bottom right, bottom left, 1.0 = 10000)
+/// to a rectangle. The output size is derived from the longest edges of the quad.
+pub fn perspective_warp(img: &RgbaImage, corners: &[(u32, u32); 4]) -> RgbaImage {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let p = corners.map(|c| (c.0 as f32 / 10000. * width, c.1 as f32 / 10000. * height));
+    let identity = [(0., 0.), (width, 0.), (width, height), (0., height)];
+    if p == identity {
+        return img.clone();
+    }
+
+    let dist = |a: (f32, f32), b: (f32, f32)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+    let out_w = dist(p[0], p[1]).max(dist(p[3], p[2])).round().max(1.) as u32;
+    let out_h = dist(p[0], p[3]).max(dist(p[1], p[2])).round().max(1.) as u32;
+
+    // Projective mapping from the unit square to the quad (Heckbert, 1989)
+    let (dx1, dx2, dx3) = (
+        p[1].0 - p[2].0,
+        p[3].0 - p[2].0,
+        p[0].0 - p[1].0 + p[2].0 - p[3].0,
+    );
+    let (dy1, dy2, dy3) = (
+        p[1].1 - p[2].1,
+        p[3].1 - p[2].1,
+        p[0].1 - p[1].1 + p[2].1 - p[3].1,
+    );
+    let det = dx1 * dy2 - dx2 * dy1;
+    let (g, h) = if det.abs() < f32::EPSILON {
+        (0., 0.)
+    } else {
+        ((dx3 * dy2 - dx2 * dy3) / det, (dx1 * dy3 - dx3 * dy1) / det)
+    };
+    let a = p[1].0 - p[0].0 + g * p[1].0;
+    let b = p[3].0 - p[0].0 + h * p[3].0;
+    let d = p[1].1 - p[0].1 + g * p[1].1;
+    let e = p[3].1 - p[0].1 + h * p[3].1;
+
+    let mut out = RgbaImage::new(out_w, out_h);
+    out.par_chunks_mut(out_w as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let v = (y as f32 + 0.5) / out_h as f32;
+            for (x, px) in row.chunks_mut(4).enumerate() {
+                let u = (x as f32 + 0.5) / out_w as f32;
+                let w = g * u + h * v + 1.;
+                let src_x = (a * u + b * v + p[0].0) / w;
+                let src_y = (d * u + e * v + p[0].1) / w;
+                px.copy_from_slice(&sample_bilinear(img, src_x - 0.5, src_y - 0.5));
+            }
+        });
+    out
+}
+
+/// Rotate clockwise by `degrees` around the center. With `crop`, the result is the largest
+/// rectangle of the original aspect ratio that contains no empty corners.
+pub fn rotate_arbitrary(img: &RgbaImage, degrees: f32, crop: bool) -> RgbaImage {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let scale = if crop {
+        let (sin, cos) = (sin.abs(), cos.abs());
+        (width / (width * cos + height * sin)).min(height / (width * sin + height * cos))
+    } else {
+        1.0
+    };
+    let out_w = (width * scale).round().max(1.) as u32;
+    let out_h = (height * scale).round().max(1.) as u32;
+
+    let mut out = RgbaImage::new(out_w, out_h);
+    out.par_chunks_mut(out_w as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let dy = y as f32 + 0.5 - out_h as f32 / 2.;
+            for (x, px) in row.chunks_mut(4).enumerate() {
+                let dx = x as f32 + 0.5 - out_w as f32 / 2.;
+                let src_x = cos * dx + sin * dy + width / 2.;
+                let src_y = -sin * dx + cos * dy + height / 2.;
+                if src_x >= 0. && src_y >= 0. && src_x <= width && src_y <= height {
+                    px.copy_from_slice(&sample_bilinear(img, src_x - 0.5, src_y - 0.5));
+                }
+            }
+        });
+    out
+}
+
+/// The rotation in degrees that levels a line drawn from `start` to `end`.
+/// Lines steeper than 45° are treated as verticals.
+pub fn level_angle(start: (f32, f32), end: (f32, f32)) -> f32 {
+    let mut angle = (end.1 - start.1).atan2(end.0 - start.0).to_degrees();
+    // direction of the line does not matter
+    if angle > 90. {
+        angle -= 180.
+    } else if angle < -90. {
+        angle += 180.
+    }
+    if angle > 45. {
+        angle -= 90.
+    } else if angle < -45. {
+        angle += 90.
+    }
+    -angle
+}
+
+/// Sample a pixel at a fractional position, clamping to the image borders
+fn sample_bilinear(img: &RgbaImage, x: f32, y: f32) -> [u8; 4] {
+    let max_x = img.width() as f32 - 1.;
+    let max_y = img.height() as f32 - 1.;
+    let (x, y) = (x.clamp(0., max_x), y.clamp(0., max_y));
+    let (x0, y0) = (x.floor(), y.floor());
+    let (x1, y1) = ((x0 + 1.).min(max_x), (y0 + 1.).min(max_y));
+    let (fx, fy) = (x - x0, y - y0);
+    let tl = img.get_pixel(x0 as u32, y0 as u32);
+    let tr = img.get_pixel(x1 as u32, y0 as u32);
+    let bl = img.get_pixel(x0 as u32, y1 as u32);
+    let br = img.get_pixel(x1 as u32, y1 as u32);
+    let mut result = [0; 4];
+    for c in 0..4 {
+        let top = lerp(tl[c] as f32..=tr[c] as f32, fx);
+        let bottom = lerp(bl[c] as f32..=br[c] as f32, fx);
+        result[c] = lerp(top..=bottom, fy).round() as u8;
+    }
+    result
+}
+
+/// Render (multi line) text with an optional outline into a tightly fitting image.
+/// `align` moves shorter lines from left (0.0) to right (1.0).
+pub fn render_text(
+    text: &str,
+    font_data: &[u8],
+    size: f32,
+    color: [u8; 4],
+    outline: f32,
+    outline_color: [u8; 4],
+    align: f32,
+) -> Result<RgbaImage> {
+    let font = FontRef::try_from_slice(font_data)?;
+    let font = font.as_scaled(PxScale::from(size.max(1.)));
+    let pad = outline.ceil() as i32 + 1;
+    let line_height = font.height() + font.line_gap();
+
+    let line_width = |line: &str| {
+        let mut prev = None;
+        line.chars()
+            .map(|c| {
+                let id = font.glyph_id(c);
+                let kern = prev.map(|p| font.kern(p, id)).unwrap_or_default();
+                prev = Some(id);
+                kern + font.h_advance(id)
+            })
+            .sum::<f32>()
+    };
+    let lines = text.lines().collect::<Vec<_>>();
+    let widths = lines.iter().map(|l| line_width(l)).collect::<Vec<_>>();
+    let max_width = widths.iter().cloned().fold(0., f32::max);
+
+    let width = (max_width.ceil() as i32 + pad * 2) as usize;
+    let height = ((line_height * lines.len() as f32).ceil() as i32 + pad * 2) as usize;
+    let mut coverage = vec![0_f32; width * height];
+
+    for (i, line) in lines.iter().enumerate() {
+        let mut x = pad as f32 + (max_width - widths[i]) * align;
+        let baseline = pad as f32 + font.ascent() + line_height * i as f32;
+        let mut prev = None;
+        for c in line.chars() {
+            let id = font.glyph_id(c);
+            if let Some(p) = prev {
+                x += font.kern(p, id);
+            }
+            prev = Some(id);
+            let glyph = id.with_scale_and_position(font.scale(), point(x, baseline));
+            x += font.h_advance(id);
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, c| {
+                    let px = bounds.min.x as i32 + gx as i32;
+                    let py = bounds.min.y as i32 + gy as i32;
+                    if px >= 0 && py >= 0 && (px as usize) < width && (py as usize) < height {
+                        let v = &mut coverage[py as usize * width + px as usize];
+                        *v = v.max(c);
+                    }
+                });
+            }
+        }
+    }
+
+    // grow the text coverage by the outline radius
+    let radius = outline.round() as i32;
+    let outline_coverage = if radius > 0 {
+        let offsets = (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+            .filter(|(x, y)| x * x + y * y <= radius * radius)
+            .collect::<Vec<_>>();
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as i32, (i / width) as i32);
+                offsets
+                    .iter()
+                    .map(|(ox, oy)| (x + ox, y + oy))
+                    .filter(|(nx, ny)| {
+                        *nx >= 0 && *ny >= 0 && (*nx as usize) < width && (*ny as usize) < height
+                    })
+                    .map(|(nx, ny)| coverage[ny as usize * width + nx as usize])
+                    .fold(0., f32::max)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        vec![0.; width * height]
+    };
+
+    Ok(RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        let i = y as usize * width + x as usize;
+        let text_alpha = coverage[i] * color[3] as f32 / 255.;
+        let outline_alpha = outline_coverage[i] * outline_color[3] as f32 / 255.;
+        let alpha = text_alpha + outline_alpha * (1. - text_alpha);
+        if alpha <= 0. {
+            return image::Rgba([0, 0, 0, 0]);
+        }
+        let mut px = [0; 4];
+        for c in 0..3 {
+            px[c] = ((color[c] as f32 * text_alpha
+                + outline_color[c] as f32 * outline_alpha * (1. - text_alpha))
+                / alpha)
+                .round() as u8;
+        }
+        px[3] = (alpha * 255.).round() as u8;
+        image::Rgba(px)
+    }))
+}
+
+fn filter_combo(ui: &mut Ui, filter: &mut ScaleFilter, response: &mut Response) {
+    egui::ComboBox::from_id_source("filter")
+        .selected_text(format!("{filter:?}"))
+        .show_ui(ui, |ui| {
+            for f in [
+                ScaleFilter::Box,
+                ScaleFilter::Bilinear,
+                ScaleFilter::Hamming,
+                ScaleFilter::CatmullRom,
+                ScaleFilter::Mitchell,
+                ScaleFilter::Lanczos3,
+                ScaleFilter::SeamCarving,
+            ] {
+                let mut r = ui.selectable_value(filter, f, format!("{f:?}"));
+                if f == ScaleFilter::SeamCarving {
+                    r = r.on_hover_text("Content aware shrinking. Slow on large images.");
+                }
+                if r.clicked() {
+                    response.changed = true;
+                }
+            }
+        });
+}
+
+/// Content aware resize: repeatedly remove the connected path of pixels with the least energy,
+/// first for columns, then for rows. Only shrinking is content aware, growing falls back to a regular resize.
+pub fn seam_carve(img: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let mut result = remove_vertical_seams(img, img.width().saturating_sub(width));
+    result = imageops::rotate90(&result);
+    result = remove_vertical_seams(&result, result.width().saturating_sub(height));
+    result = imageops::rotate270(&result);
+    if result.dimensions() != (width, height) {
+        result = imageops::resize(&result, width, height, imageops::FilterType::Lanczos3);
+    }
+    result
+}
+
+fn remove_vertical_seams(img: &RgbaImage, count: u32) -> RgbaImage {
+    let stride = img.width() as usize;
+    let height = img.height() as usize;
+    let count = count.min(img.width().saturating_sub(1)) as usize;
+    if count == 0 || height == 0 {
+        return img.clone();
+    }
+
+    let mut pixels = img.pixels().map(|p| p.0).collect::<Vec<_>>();
+    let mut luma = pixels
+        .iter()
+        .map(|p| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32)
+        .collect::<Vec<_>>();
+    let mut cost = vec![0_f32; stride * height];
+    let mut seam = vec![0_usize; height];
+    let mut width = stride;
+
+    for _ in 0..count {
+        // accumulate the minimal energy needed to reach every pixel from the top
+        for y in 0..height {
+            let row = y * stride;
+            let up = y.saturating_sub(1) * stride;
+            let down = (y + 1).min(height - 1) * stride;
+            for x in 0..width {
+                let left = x.saturating_sub(1);
+                let right = (x + 1).min(width - 1);
+                let energy = (luma[row + right] - luma[row + left]).abs()
+                    + (luma[down + x] - luma[up + x]).abs();
+                let above = if y == 0 {
+                    0.
+                } else {
+                    (left..=right)
+                        .map(|i| cost[up + i])
+                        .fold(f32::MAX, f32::min)
+                };
+                cost[row + x] = energy + above;
+            }
+        }
+
+        // walk back up along the cheapest path
+        let min_index = |y: usize, range: std::ops::RangeInclusive<usize>| {
+            range
+                .min_by(|a, b| cost[y * stride + a].total_cmp(&cost[y * stride + b]))
+                .unwrap_or_default()
+        };
+        seam[height - 1] = min_index(height - 1, 0..=width - 1);
+        for y in (0..height - 1).rev() {
+            let x = seam[y + 1];
+            seam[y] = min_index(y, x.saturating_sub(1)..=(x + 1).min(width - 1));
+        }
+
+        for (y, x) in seam.iter().enumerate() {
+            let row = y * stride;
+            pixels[row..row + width].copy_within(x + 1.., *x);
+            luma[row..row + width].copy_within(x + 1.., *x);
+        }
+        width -= 1;
+    }
+
+    RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        image::Rgba(pixels[y as usize * stride + x as usize])
+    })
+}
+
+/// Edge-preserving smoothing: every pixel becomes the weighted average of its neighbourhood,
+/// where weights fall off with spatial distance and with color difference.
+/// `strength` is expected from 0-1 and controls how different colors may be to still get blended.
+pub fn bilateral_filter(img: &RgbaImage, radius: u32, strength: f32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let radius = radius.max(1) as i32;
+
+    let sigma_spatial = radius as f32 / 2. + 0.5;
+    let spatial_weights = (-radius..=radius)
+        .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+        .map(|(x, y)| (-((x * x + y * y) as f32) / (2. * sigma_spatial * sigma_spatial)).exp())
+        .collect::<Vec<_>>();
+
+    // color distance is the sum of absolute channel differences, so at most 3 * 255
+    let sigma_range = (strength * 100.).max(1.);
+    let range_weights = (0..=765)
+        .map(|d| (-((d * d) as f32) / (2. * sigma_range * sigma_range)).exp())
+        .collect::<Vec<_>>();
+
+    let mut out = img.clone();
+    out.par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let y = y as i32;
+            for x in 0..width as i32 {
+                let center = img.get_pixel(x as u32, y as u32);
+                let mut sum = [0_f32; 4];
+                let mut weight_sum = 0.;
+                let mut i = 0;
+                for ny in y - radius..=y + radius {
+                    for nx in x - radius..=x + radius {
+                        let spatial = spatial_weights[i];
+                        i += 1;
+                        if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                            continue;
+                        }
+                        let n = img.get_pixel(nx as u32, ny as u32);
+                        let dist = (0..3)
+                            .map(|c| (center[c] as i32 - n[c] as i32).unsigned_abs() as usize)
+                            .sum::<usize>();
+                        let w = spatial * range_weights[dist];
+                        for c in 0..4 {
+                            sum[c] += n[c] as f32 * w;
+                        }
+                        weight_sum += w;
+                    }
+                }
+                let px = &mut row[x as usize * 4..x as usize * 4 + 4];
+                for c in 0..4 {
+                    px[c] = (sum[c] / weight_sum).round() as u8;
+                }
+            }
+        });
+    out
+}
+
+/// Sharpen by adding the difference between the image and a blurred version of it.
+/// `amount` of 1.0 adds the full difference once.
+pub fn unsharp_mask(img: &mut RgbaImage, sigma: f32, amount: f32) {
+    let blurred = imageops::blur(img, sigma.max(0.5));
+    img.par_chunks_mut(4)
+        .zip(blurred.par_chunks(4))
+        .for_each(|(p, b)| {
+            for c in 0..3 {
+                let diff = p[c] as f32 - b[c] as f32;
+                p[c] = (p[c] as f32 + diff * amount).clamp(0., 255.) as u8;
+            }
+        });
+}
+
+/// Process the image operator stack, starting from the cached result of the last unchanged step.
+/// The cache is updated with the result of every step evaluated.
+pub fn process_image_stack(
+    img: &RgbaImage,
+    stack: &[ImgOpItem],
+    cache: &mut Vec<(ImgOpItem, RgbaImage)>,
+    brushes: &[RgbaImage],
+) -> RgbaImage {
+    let unchanged = cache
+        .iter()
+        .zip(stack)
+        .take_while(|((cached, _), item)| cached.same_result(item))
+        .count();
+    cache.truncate(unchanged);
+    let mut result = cache
+        .last()
+        .map(|(_, cached)| cached.clone())
+        .unwrap_or_else(|| img.clone());
+    for item in &stack[unchanged..] {
+        if let Err(e) = item.process_image(&mut result, brushes) {
+            error!("{e}")
+        }
+        cache.push((item.clone(), result.clone()));
+    }
+    result
+}
+
+/// Downscale an image by `factor` to be used as a preview proxy
+pub fn preview_proxy(img: &RgbaImage, factor: f32) -> RgbaImage {
+    if factor >= 1.0 {
+        return img.clone();
+    }
+    imageops::thumbnail(
+        img,
+        ((img.width() as f32 * factor) as u32).max(1),
+        ((img.height() as f32 * factor) as u32).max(1),
+    )
+}
+
+/// Process image operators on a proxy downscaled by `factor`.
+/// This keeps the UI responsive while operators are being adjusted.
+pub fn process_image_preview(
+    img: &RgbaImage,
+    operators: &[ImgOpItem],
+    brushes: &[RgbaImage],
+    factor: f32,
+) -> RgbaImage {
+    let mut proxy = preview_proxy(img, factor);
+    for item in operators {
+        let item = ImgOpItem {
+            operation: item.operation.scaled(factor),
+            ..item.clone()
+        };
+        if let Err(e) = item.process_image(&mut proxy, brushes) {
+            error!("{e}")
+        }
+    }
+    proxy
+}
+
 pub fn builtin_luts() -> HashMap<String, Vec<u8>> {
     let mut luts = HashMap::new();
     luts.insert(
@@ -1059,6 +2314,116 @@ pub fn process_pixels(buffer: &mut RgbaImage, operators: &Vec<ImageOperation>) {
         });
 }
 
+/// Process a stack of pixel operators, blending each one with its mask if it has one
+pub fn process_pixel_stack(buffer: &mut RgbaImage, stack: &[ImgOpItem], brushes: &[RgbaImage]) {
+    let stack = stack.iter().filter(|item| item.active).collect::<Vec<_>>();
+    let masks = stack
+        .iter()
+        .map(|item| item.mask_weights(buffer.dimensions(), brushes))
+        .collect::<Vec<_>>();
+
+    if masks.iter().all(|mask| mask.is_none()) {
+        let operators = stack
+            .iter()
+            .map(|item| item.operation.clone())
+            .collect::<Vec<_>>();
+        process_pixels(buffer, &operators);
+        return;
+    }
+
+    buffer.par_chunks_mut(4).enumerate().for_each(|(i, px)| {
+        let mut float_pixel =
+            Vector4::new(px[0] as f32, px[1] as f32, px[2] as f32, px[3] as f32) / 255.;
+
+        for (item, mask) in stack.iter().zip(&masks) {
+            let unmodified = float_pixel;
+            if let Err(e) = item.operation.process_pixel(&mut float_pixel) {
+                error!("{e}")
+            }
+            if let Some(mask) = mask {
+                float_pixel = unmodified.lerp(&float_pixel, mask[i]);
+            }
+        }
+
+        float_pixel *= 255.;
+
+        px[0] = (float_pixel[0]) as u8;
+        px[1] = (float_pixel[1]) as u8;
+        px[2] = (float_pixel[2]) as u8;
+        px[3] = (float_pixel[3]) as u8;
+    });
+}
+
+/// Operators that balance the colors and stretch the contrast of `img`, as a starting point for
+/// editing. The white balance assumes the average color is gray and the contrast stretch ignores
+/// the darkest and brightest half percent of the pixels. `strength` goes from 0 (no change) to 1.
+pub fn auto_enhance(img: &RgbaImage, strength: f32) -> Vec<ImgOpItem> {
+    const CLIP: f32 = 0.005;
+    // looking at about a quarter million pixels is plenty
+    let step = ((img.width() as usize * img.height() as usize) / 250_000).max(1);
+    let pixels = img
+        .pixels()
+        .step_by(step)
+        .filter(|p| p[3] > 0)
+        .collect::<Vec<_>>();
+    if pixels.is_empty() {
+        return vec![];
+    }
+    let strength = strength.clamp(0., 1.);
+
+    // gray world white balance, darkening the channels brighter than the darkest one
+    let mut sums = [0_f32; 3];
+    for p in &pixels {
+        for c in 0..3 {
+            sums[c] += p[c] as f32;
+        }
+    }
+    let darkest = sums.iter().cloned().fold(f32::MAX, f32::min);
+    let gains = sums.map(|sum| {
+        let gain = if sum > 0. { darkest / sum } else { 1. };
+        lerp(1.0..=gain, strength)
+    });
+
+    // contrast stretch of the balanced brightness
+    let mut histogram = [0_usize; 256];
+    for p in &pixels {
+        let luma = 0.299 * p[0] as f32 * gains[0]
+            + 0.587 * p[1] as f32 * gains[1]
+            + 0.114 * p[2] as f32 * gains[2];
+        histogram[(luma.round() as usize).min(255)] += 1;
+    }
+    let percentile = |fraction: f32| {
+        let target = (pixels.len() as f32 * fraction) as usize;
+        let mut count = 0;
+        for (value, n) in histogram.iter().enumerate() {
+            count += n;
+            if count > target {
+                return value as f32 / 255.;
+            }
+        }
+        1.
+    };
+    let black = percentile(CLIP) * strength;
+    let white = 1. - (1. - percentile(1. - CLIP)) * strength;
+
+    let mut ops = vec![];
+    if gains.iter().any(|gain| *gain < 0.995) {
+        ops.push(ImgOpItem::new(ImageOperation::Mult(
+            gains.map(|gain| (gain * 255.).round() as u8),
+        )));
+    }
+    if white - black > 0.01 && (black > 0.002 || white < 0.998) {
+        // Equalize maps 0 and 1 to these bounds
+        let range = 255. / (white - black);
+        let dark = -black * range;
+        ops.push(ImgOpItem::new(ImageOperation::Equalize((
+            dark.round() as i32,
+            (dark + range).round() as i32,
+        ))));
+    }
+    ops
+}
+
 /// Crop a left,top (x,y) plus x/y window safely into absolute pixel units.
 /// The crop is expected in UV coords, 0-1, encoded as 8 bit (0-255)
 pub fn cropped_range(crop: &[u32; 4], img_dim: &(u32, u32)) -> [u32; 4] {
@@ -1086,34 +2451,112 @@ pub fn cropped_range(crop: &[u32; 4], img_dim: &(u32, u32)) -> [u32; 4] {
     crop
 }
 
+/// How much border pixels may differ from the corner when trimming borders in one go
+pub const TRIM_TOLERANCE: u8 = 10;
+
+/// The content of an image without its uniform borders, as x, y, width and height. Rows and
+/// columns at the edges are cut off while all their pixels differ by at most `tolerance` from the
+/// top left pixel. Returns None if the whole image is uniform.
+pub fn trimmed_range(img: &RgbaImage, tolerance: u8) -> Option<[u32; 4]> {
+    let border = *img.get_pixel_checked(0, 0)?;
+    let is_border = |x: u32, y: u32| {
+        img.get_pixel(x, y)
+            .0
+            .iter()
+            .zip(border.0.iter())
+            .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+    };
+    let (width, height) = img.dimensions();
+    let row = |y: u32| (0..width).all(|x| is_border(x, y));
+    let top = (0..height).find(|y| !row(*y))?;
+    let bottom = (0..height).rev().find(|y| !row(*y))?;
+    let column = |x: u32| (top..=bottom).all(|y| is_border(x, y));
+    let left = (0..width).find(|x| !column(*x))?;
+    let right = (0..width).rev().find(|x| !column(*x))?;
+    Some([left, top, right - left + 1, bottom - top + 1])
+}
+
+/// The bounds of a crop operator that keeps `range`, as x, y, width and height, of an image of
+/// `img_dim`. The reverse of `cropped_range`, rounded to keep all of `range`.
+pub fn crop_bounds(range: [u32; 4], img_dim: (u32, u32)) -> [u32; 4] {
+    let (width, height) = (img_dim.0.max(1) as u64, img_dim.1.max(1) as u64);
+    let fraction = |pixels: u32, size: u64| (pixels as u64 * 10000 / size) as u32;
+    [
+        fraction(range[0], width),
+        fraction(range[1], height),
+        fraction(img_dim.0.saturating_sub(range[0] + range[2]), width),
+        fraction(img_dim.1.saturating_sub(range[1] + range[3]), height),
+    ]
+}
+
+/// Size and block layout of a JPEG, used to tell which lossless transforms are available
+#[cfg(feature = "turbo")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LosslessInfo {
+    pub width: usize,
+    pub height: usize,
+    /// Width and height of an MCU block. Lossless crops snap to multiples of it.
+    pub mcu: (usize, usize),
+}
+
+#[cfg(feature = "turbo")]
+impl LosslessInfo {
+    pub fn read(p: &std::path::Path) -> anyhow::Result<Self> {
+        let header = turbojpeg::read_header(&std::fs::read(p)?)?;
+        Ok(Self::from_header(&header))
+    }
+
+    fn from_header(header: &turbojpeg::DecompressHeader) -> Self {
+        Self {
+            width: header.width,
+            height: header.height,
+            mcu: (header.subsamp.mcu_width(), header.subsamp.mcu_height()),
+        }
+    }
+
+    /// A transform is perfect if no partial MCU blocks on the right or bottom edge need to move.
+    /// Imperfect transforms discard these edge blocks.
+    pub fn is_perfect(&self, op: turbojpeg::TransformOp) -> bool {
+        use turbojpeg::TransformOp;
+        let right = self.width % self.mcu.0 == 0;
+        let bottom = self.height % self.mcu.1 == 0;
+        match op {
+            TransformOp::None | TransformOp::Transpose => true,
+            TransformOp::Hflip | TransformOp::Rot270 => right,
+            TransformOp::Vflip | TransformOp::Rot90 => bottom,
+            TransformOp::Rot180 | TransformOp::Transverse => right && bottom,
+        }
+    }
+
+    /// Move the crop origin to the previous MCU boundary, growing the crop so it still covers
+    /// the requested area, and keep it inside the image.
+    pub fn align_crop(&self, crop: turbojpeg::TransformCrop) -> turbojpeg::TransformCrop {
+        let x = crop.x.min(self.width.saturating_sub(1)) / self.mcu.0 * self.mcu.0;
+        let y = crop.y.min(self.height.saturating_sub(1)) / self.mcu.1 * self.mcu.1;
+        turbojpeg::TransformCrop {
+            x,
+            y,
+            width: crop.width.map(|w| (w + crop.x - x).min(self.width - x)),
+            height: crop.height.map(|h| (h + crop.y - y).min(self.height - y)),
+        }
+    }
+}
+
 /// Transform a JPEG losslessly
 #[cfg(feature = "turbo")]
 pub fn lossless_tx(p: &std::path::Path, transform: turbojpeg::Transform) -> anyhow::Result<()> {
     let jpeg_data = std::fs::read(p)?;
 
-    let mut decompressor = turbojpeg::Decompressor::new()?;
-
     // read the JPEG header
-    let header = decompressor.read_header(&jpeg_data)?;
-    let mcu_h = header.subsamp.mcu_height();
-    let mcu_w = header.subsamp.mcu_width();
-
-    debug!("h {mcu_h} w {mcu_w}");
+    let info = LosslessInfo::from_header(&turbojpeg::read_header(&jpeg_data)?);
+    debug!("{:?}", info);
 
-    // make sure crop is aligned to mcu bounds
     let mut transform = transform;
-    if let Some(c) = transform.crop.as_mut() {
-        c.x = (c.x as f32 / mcu_w as f32) as usize * mcu_w;
-        c.y = (c.y as f32 / mcu_h as f32) as usize * mcu_h;
-        // the start point may have shifted, make sure we don't go over bounds
-        // if let Some(crop_w) = c.width.as_mut() {
-        //     *crop_w = *crop_w;
-        // }
-        // if let Some(crop_h) = c.height.as_mut() {
-        //     // *crop_h = (*crop_h + c.y).min(header.height - c.y);
-        // }
-        debug!("jpg crop transform {:#?}", c);
-    }
+    // make sure crop is aligned to mcu bounds
+    transform.crop = transform.crop.map(|c| info.align_crop(c));
+    debug!("jpg crop transform {:#?}", transform.crop);
+    // drop partial edge blocks instead of leaving them untransformed
+    transform.trim = !info.is_perfect(transform.op);
 
     // apply the transformation
     let transformed_data = turbojpeg::transform(&transform, &jpeg_data)?;
@@ -1,3 +1,4 @@
+use crate::decoders;
 use crate::utils::{fit, Frame, FrameSource};
 use crate::FONT;
 use libwebp_sys::{WebPDecodeRGBA, WebPGetInfo};
@@ -23,11 +24,29 @@ use zune_png::zune_core::options::DecoderOptions;
 use zune_png::zune_core::result::DecodingResult;
 use zune_png::PngDecoder;
 
+/// Images with more pixels are shown while they decode, if the format allows it
+const PROGRESSIVE_PIXELS: u64 = 16_000_000;
+/// How often a partially decoded image is shown
+const PROGRESSIVE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+/// PNGs wider or higher than this are not decoded
+const PNG_MAX_SIDE: usize = 50000;
+
 /// Open an image from disk and send it somewhere
 pub fn open_image(img_location: &Path) -> Result<Receiver<Frame>> {
     let (sender, receiver): (Sender<Frame>, Receiver<Frame>) = channel();
     let img_location = (*img_location).to_owned();
 
+    if let Some(decoder) = decoders::find(&img_location) {
+        debug!(
+            "Decoding {} with {}",
+            img_location.display(),
+            decoder.name()
+        );
+        let img = decoder.decode(&img_location)?;
+        _ = sender.send(Frame::new_still(img));
+        return Ok(receiver);
+    }
+
     match img_location
         .extension()
         .unwrap_or_default()
@@ -420,12 +439,15 @@ pub fn open_image(img_location: &Path) -> Result<Receiver<Frame>> {
             return Ok(receiver);
         }
         "png" => {
+            if let Some(receiver) = open_png_progressive(&img_location)? {
+                return Ok(receiver);
+            }
             let contents = std::fs::read(&img_location)?;
             let mut decoder = PngDecoder::new(&contents);
             decoder.set_options(
                 DecoderOptions::new_fast()
-                    .set_max_height(50000)
-                    .set_max_width(50000),
+                    .set_max_height(PNG_MAX_SIDE)
+                    .set_max_width(PNG_MAX_SIDE),
             );
             match decoder.decode().map_err(|e| anyhow!("{:?}", e))? {
                 // 16 bpp data
@@ -692,9 +714,12 @@ pub fn open_image(img_location: &Path) -> Result<Receiver<Frame>> {
             }
         }
         _ => {
-            // All other supported image files are handled by using `image`
+            // All other supported image files are handled by using `image`.
+            // The format is guessed from the content, for files with missing or wrong extensions.
             info!("Loading using image library");
-            let img = image::open(img_location)?;
+            let img = image::io::Reader::open(img_location)?
+                .with_guessed_format()?
+                .decode()?;
             // col.add_still(img.to_rgba8());
             _ = sender.send(Frame::new_still(img.to_rgba8()));
             return Ok(receiver);
@@ -704,6 +729,165 @@ pub fn open_image(img_location: &Path) -> Result<Receiver<Frame>> {
     Ok(receiver)
 }
 
+/// Decode large, non-interlaced 8 bit PNGs row by row on a thread, sending the part decoded so
+/// far at intervals so the image appears while it loads. Returns None for other PNGs, and for
+/// headers this decoder can't read, which are left to the regular decoder.
+fn open_png_progressive(img_location: &Path) -> Result<Option<Receiver<Frame>>> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(img_location)?));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = match decoder.read_info() {
+        Ok(reader) => reader,
+        Err(e) => {
+            debug!("Not decoding PNG progressively: {e}");
+            return Ok(None);
+        }
+    };
+    let info = reader.info();
+    let (width, height) = (info.width, info.height);
+    if width as usize > PNG_MAX_SIDE || height as usize > PNG_MAX_SIDE {
+        bail!("PNG of {width}x{height} is too large");
+    }
+    if info.interlaced
+        || info.bit_depth == png::BitDepth::Sixteen
+        || info.animation_control.is_some()
+        || (width as u64 * height as u64) < PROGRESSIVE_PIXELS
+    {
+        return Ok(None);
+    }
+    let (color_type, _) = reader.output_color_type();
+    debug!("Decoding {width}x{height} PNG progressively");
+
+    let (sender, receiver): (Sender<Frame>, Receiver<Frame>) = channel();
+    std::thread::spawn(move || {
+        let mut buffer = RgbaImage::new(width, height);
+        let stride = width as usize * 4;
+        let mut last_update = std::time::Instant::now();
+        for y in 0..height as usize {
+            let out = &mut buffer.as_mut()[y * stride..(y + 1) * stride];
+            let row = match reader.next_row() {
+                Ok(Some(row)) => row.data(),
+                Ok(None) => break,
+                Err(e) => {
+                    error!("{e}");
+                    return;
+                }
+            };
+            match color_type {
+                png::ColorType::Rgba => out.copy_from_slice(row),
+                png::ColorType::Rgb => {
+                    for (px, c) in out.chunks_exact_mut(4).zip(row.chunks_exact(3)) {
+                        px.copy_from_slice(&[c[0], c[1], c[2], 255]);
+                    }
+                }
+                png::ColorType::GrayscaleAlpha => {
+                    for (px, c) in out.chunks_exact_mut(4).zip(row.chunks_exact(2)) {
+                        px.copy_from_slice(&[c[0], c[0], c[0], c[1]]);
+                    }
+                }
+                png::ColorType::Grayscale => {
+                    for (px, c) in out.chunks_exact_mut(4).zip(row) {
+                        px.copy_from_slice(&[*c, *c, *c, 255]);
+                    }
+                }
+                png::ColorType::Indexed => {
+                    error!("PNG palette was not expanded");
+                    return;
+                }
+            }
+            if last_update.elapsed() > PROGRESSIVE_INTERVAL {
+                // nobody is waiting for this image anymore
+                if sender.send(Frame::new_partial(buffer.clone())).is_err() {
+                    return;
+                }
+                last_update = std::time::Instant::now();
+            }
+        }
+        _ = sender.send(Frame::new_still(buffer));
+    });
+    Ok(Some(receiver))
+}
+
+/// Decode an image for analysis, JPEGs at a reduced size that still covers `target`
+pub fn open_reduced(img_location: &Path, target: (u32, u32)) -> Result<RgbaImage> {
+    let is_jpeg = matches!(
+        image::ImageFormat::from_path(img_location),
+        Ok(image::ImageFormat::Jpeg)
+    );
+    if is_jpeg {
+        if let Ok(Some(img)) = open_jpeg_reduced(img_location, target) {
+            return Ok(img);
+        }
+    }
+    // progressive decoders send partial frames first
+    Ok(open_image(img_location)?
+        .iter()
+        .find(|f| f.source != FrameSource::Partial)
+        .context("No image decoded")?
+        .buffer)
+}
+
+/// Decode a JPEG at a reduced size that still covers `target`, using the DCT scaling of the decoder.
+/// This skips most of the work for images much larger than the screen.
+/// Returns None if the image is not larger than `target`.
+pub fn open_jpeg_reduced(img_location: &Path, target: (u32, u32)) -> Result<Option<RgbaImage>> {
+    let jpeg_data = std::fs::read(img_location)?;
+
+    #[cfg(feature = "turbo")]
+    {
+        let mut decompressor = turbojpeg::Decompressor::new()?;
+        let header = decompressor.read_header(&jpeg_data)?;
+        // the decoder scales in eighths
+        let fit =
+            (target.0 as f32 / header.width as f32).min(target.1 as f32 / header.height as f32);
+        let eighths = ((fit * 8.).ceil() as usize).clamp(1, 8);
+        if eighths == 8 {
+            return Ok(None);
+        }
+        let width = (header.width * eighths).div_ceil(8);
+        let height = (header.height * eighths).div_ceil(8);
+        let mut image = turbojpeg::Image {
+            pixels: vec![0; 4 * width * height],
+            width,
+            pitch: 4 * width,
+            height,
+            format: turbojpeg::PixelFormat::RGBA,
+        };
+        decompressor.decompress(&jpeg_data, image.as_deref_mut())?;
+        debug!(
+            "Reduced JPEG from {}x{} to {width}x{height}",
+            header.width, header.height
+        );
+        Ok(RgbaImage::from_raw(
+            width as u32,
+            height as u32,
+            image.pixels,
+        ))
+    }
+
+    #[cfg(not(feature = "turbo"))]
+    {
+        use image::ImageDecoder;
+        use std::convert::TryFrom;
+        let mut decoder = image::codecs::jpeg::JpegDecoder::new(std::io::Cursor::new(&jpeg_data))?;
+        let (width, height) = decoder.dimensions();
+        let fit = (target.0 as f32 / width as f32).min(target.1 as f32 / height as f32);
+        if fit >= 1. {
+            return Ok(None);
+        }
+        let requested =
+            |side: u32| u16::try_from((side as f32 * fit).ceil() as u32).unwrap_or(u16::MAX);
+        let reduced = decoder.scale(requested(width), requested(height))?;
+        if (reduced.0 as u32, reduced.1 as u32) == (width, height) {
+            return Ok(None);
+        }
+        debug!(
+            "Reduced JPEG from {width}x{height} to {}x{}",
+            reduced.0, reduced.1
+        );
+        Ok(Some(DynamicImage::from_decoder(decoder)?.into_rgba8()))
+    }
+}
+
 fn tonemap_rgba(px: [f32; 4]) -> [u8; 4] {
     [
         tonemap_f32(px[0]),
@@ -743,7 +927,6 @@ fn u16_to_u8(p: u16) -> u8 {
     ((p as f32 / u16::MAX as f32) * u8::MAX as f32) as u8
 }
 
-
 fn load_raw(img_location: &Path) -> Result<RgbaImage> {
     let export_job = Export::new(
         Input::ByFile(&img_location.to_string_lossy()),
@@ -768,5 +951,4 @@ fn load_raw(img_location: &Path) -> Result<RgbaImage> {
         .context("can't decode raw output as image")?;
     // make it a Dynamic image
     Ok(DynamicImage::ImageRgb8(x).to_rgba8())
-    
 }
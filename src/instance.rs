@@ -0,0 +1,172 @@
+//! Background mode: a hidden oculante keeps running and is shown again from its tray icon, its
+//! global hotkey (see `tray`), or by launching `oculante --show [image]`.
+//!
+//! The running instance reads one request per connection from a channel only the current user
+//! can write to: a Unix domain socket in the runtime folder of the user, or a named pipe on
+//! Windows, whose default ACL only lets its owner, administrators and the system write.
+
+use crate::redraw::Waker;
+use crate::screenshot::CaptureArea;
+use anyhow::{bail, Result};
+use log::{error, info};
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstanceRequest {
+    /// Show the window with the last image
+    Show,
+    /// Show the window and open an image
+    Open(PathBuf),
+    /// Take a screenshot and open it
+    Capture(CaptureArea),
+    /// Quit, e.g. from the tray menu
+    Quit,
+}
+
+impl InstanceRequest {
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Show => "show\n".into(),
+            Self::Open(path) => format!("open {}\n", path.display()),
+            Self::Capture(area) => format!("capture {}\n", area.name()),
+            Self::Quit => "quit\n".into(),
+        }
+    }
+
+    pub fn decode(line: &str) -> Result<Self> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        match line.split_once(' ') {
+            None if line == "show" => Ok(Self::Show),
+            None if line == "quit" => Ok(Self::Quit),
+            Some(("open", path)) if !path.is_empty() => Ok(Self::Open(path.into())),
+            Some(("capture", area)) => match CaptureArea::from_name(area) {
+                Some(area) => Ok(Self::Capture(area)),
+                None => bail!("Unknown capture area {area}"),
+            },
+            _ => bail!("Unknown request {line}"),
+        }
+    }
+}
+
+/// The socket of the current user, in its runtime folder
+#[cfg(not(target_os = "windows"))]
+fn socket_path() -> Result<PathBuf> {
+    match dirs::runtime_dir().or_else(dirs::cache_dir) {
+        Some(dir) => Ok(dir.join("oculante.sock")),
+        None => bail!("There is no runtime folder for the socket"),
+    }
+}
+
+/// The pipe of the current user, below `\\.\pipe\`. The name only avoids clashes, access is
+/// limited by the ACL.
+#[cfg(target_os = "windows")]
+fn pipe_name() -> String {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    format!("oculante-{user}")
+}
+
+/// Pass the request of one connection on and wake the app, whose window may be hidden. Returns
+/// false once nobody receives them any more.
+fn receive(stream: impl Read, sender: &Sender<InstanceRequest>, waker: &Waker) -> bool {
+    let mut line = String::new();
+    if BufReader::new(stream).read_line(&mut line).is_err() {
+        return true;
+    }
+    match InstanceRequest::decode(&line) {
+        Ok(request) => {
+            let received = sender.send(request).is_ok();
+            waker.wake();
+            received
+        }
+        Err(e) => {
+            error!("{e}");
+            true
+        }
+    }
+}
+
+/// Pass requests of later launches to `sender`. Fails if another instance is listening already.
+#[cfg(not(target_os = "windows"))]
+pub fn listen(sender: Sender<InstanceRequest>, waker: Waker) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if LISTENING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let path = socket_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(&path).is_ok() {
+                bail!("Another instance is running in background mode");
+            }
+            // left behind by an instance that did not exit cleanly
+            std::fs::remove_file(&path)?;
+            UnixListener::bind(&path)?
+        }
+        Err(e) => return Err(e.into()),
+    };
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    LISTENING.store(true, Ordering::Relaxed);
+    info!("Background mode listening on {}", path.display());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if !receive(stream, &sender, &waker) {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Pass requests of later launches to `sender`. Fails if another instance is listening already.
+#[cfg(target_os = "windows")]
+pub fn listen(sender: Sender<InstanceRequest>, waker: Waker) -> Result<()> {
+    use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+
+    if LISTENING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if LocalSocketStream::connect(pipe_name()).is_ok() {
+        bail!("Another instance is running in background mode");
+    }
+    let listener = LocalSocketListener::bind(pipe_name())?;
+    LISTENING.store(true, Ordering::Relaxed);
+    info!("Background mode listening on {}", pipe_name());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if !receive(stream, &sender, &waker) {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Send a request to an instance running in background mode
+#[cfg(not(target_os = "windows"))]
+pub fn send(request: &InstanceRequest) -> Result<()> {
+    use std::io::Write;
+    let mut stream = std::os::unix::net::UnixStream::connect(socket_path()?)?;
+    stream.write_all(request.encode().as_bytes())?;
+    Ok(())
+}
+
+/// Send a request to an instance running in background mode
+#[cfg(target_os = "windows")]
+pub fn send(request: &InstanceRequest) -> Result<()> {
+    use std::io::Write;
+    let mut stream = interprocess::local_socket::LocalSocketStream::connect(pipe_name())?;
+    stream.write_all(request.encode().as_bytes())?;
+    Ok(())
+}
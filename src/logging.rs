@@ -0,0 +1,128 @@
+//! Logging to stderr through `env_logger`, while the recent records are also kept in memory. The
+//! windows build has no console, so this is the only way to see them there.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// How many records are kept
+const CAPACITY: usize = 1000;
+
+/// The least severe records that are kept. Release builds stay at info.
+const KEPT_LEVEL: Level = if cfg!(debug_assertions) {
+    Level::Debug
+} else {
+    Level::Info
+};
+
+static RECENT: Mutex<LogBuffer> = Mutex::new(LogBuffer::new(CAPACITY));
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub level: Level,
+    /// Seconds since the unix epoch
+    pub time: f64,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.3} {:<5} {}: {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+struct Logger {
+    console: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= KEPT_LEVEL || self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.matches(record) {
+            self.console.log(record);
+        }
+        // dependencies can be very chatty
+        if record.level() <= KEPT_LEVEL && record.target().starts_with("oculante")
+            || record.level() <= Level::Warn
+        {
+            push(LogLine {
+                level: record.level(),
+                time: SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+    }
+}
+
+/// The newest lines up to a capacity
+#[derive(Debug)]
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Keep a line, dropping the oldest ones beyond the capacity
+    pub fn push(&mut self, line: LogLine) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// The lines at `level` or more severe, oldest first
+    pub fn lines(&self, level: LevelFilter) -> Vec<LogLine> {
+        self.lines
+            .iter()
+            .filter(|line| line.level <= level)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn push(line: LogLine) {
+    if let Ok(mut recent) = RECENT.lock() {
+        recent.push(line);
+    }
+}
+
+/// The kept lines at `level` or more severe, oldest first
+pub fn recent(level: LevelFilter) -> Vec<LogLine> {
+    RECENT
+        .lock()
+        .map(|recent| recent.lines(level))
+        .unwrap_or_default()
+}
+
+/// Log to stderr as configured by `RUST_LOG` and keep recent records in memory
+pub fn init() {
+    let console = env_logger::Builder::from_default_env().build();
+    let max_level = console.filter().max(KEPT_LEVEL.to_level_filter());
+    if log::set_boxed_logger(Box::new(Logger { console })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
@@ -0,0 +1,35 @@
+//! A magnifier next to the cursor, to inspect single pixels without zooming the view.
+
+/// Distance of the loupe from the cursor, in screen points
+pub const CURSOR_GAP: f32 = 24.;
+
+/// The part of the image the loupe shows, as UV min and max: `size` points at `zoom` around
+/// the pixel under `cursor`, so that pixel is in the center.
+pub fn uv_rect(cursor: (f32, f32), dimensions: (u32, u32), size: f32, zoom: f32) -> [[f32; 2]; 2] {
+    let (width, height) = (dimensions.0.max(1) as f32, dimensions.1.max(1) as f32);
+    let center = (
+        (cursor.0.floor() + 0.5) / width,
+        (cursor.1.floor() + 0.5) / height,
+    );
+    let half = (
+        size / zoom.max(1.) / 2. / width,
+        size / zoom.max(1.) / 2. / height,
+    );
+    [
+        [center.0 - half.0, center.1 - half.1],
+        [center.0 + half.0, center.1 + half.1],
+    ]
+}
+
+/// The top left corner of the loupe: below right of the cursor, or on the other side where it
+/// would leave the screen.
+pub fn position(cursor: (f32, f32), size: f32, screen: (f32, f32)) -> (f32, f32) {
+    let place = |cursor: f32, screen: f32| {
+        if cursor + CURSOR_GAP + size > screen {
+            (cursor - CURSOR_GAP - size).max(0.)
+        } else {
+            cursor + CURSOR_GAP
+        }
+    };
+    (place(cursor.0, screen.0), place(cursor.1, screen.1))
+}
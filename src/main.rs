@@ -1,6 +1,7 @@
 #![windows_subsystem = "windows"]
 
 use clap::Arg;
+use clap::ArgMatches;
 use clap::Command;
 use log::debug;
 use log::error;
@@ -12,7 +13,7 @@ use notan::app::Event;
 use notan::draw::*;
 use notan::egui::{self, *};
 use notan::prelude::*;
-use shortcuts::key_pressed;
+use shortcuts::{key_held, key_pressed};
 use std::path::PathBuf;
 use std::sync::mpsc;
 use std::time::Duration;
@@ -23,21 +24,79 @@ pub mod shortcuts;
 #[cfg(feature = "turbo")]
 use crate::image_editing::lossless_tx;
 use crate::scrubber::find_first_image_in_directory;
-use crate::settings::set_system_theme;
-use crate::settings::ColorTheme;
+use crate::settings::{Sampling, ZoomPolicy};
 use crate::shortcuts::InputEvent::*;
 mod utils;
 use utils::*;
+#[cfg(feature = "accessibility")]
+mod accessibility;
+mod animation;
+mod animation_encoder;
 mod appstate;
+mod bench;
+mod blur;
+mod channel_pack;
+mod channel_view;
+mod checksum;
+mod clipboard_watch;
+#[cfg(feature = "cloud")]
+mod cloud;
+mod colormap;
+mod compression;
+mod crash;
+mod dataset;
+mod decode_pool;
+mod decoders;
+#[cfg(feature = "detection")]
+mod detection;
+mod dicom;
+mod favourites;
+mod file_manager;
+mod fits;
+mod folder_stats;
+mod gpu;
+mod guides;
+mod heightmap;
+mod hot_folder;
 mod image_loader;
+mod instance;
 use appstate::*;
 // mod events;
+mod logging;
+mod loupe;
 #[cfg(target_os = "macos")]
 mod mac;
+mod metadata;
+mod minimap;
+mod mjpeg;
+mod montage;
 mod net;
+#[cfg(feature = "ocr")]
+mod ocr;
+mod open_with;
+mod pipe;
+mod print_size;
+mod profile;
 use net::*;
+mod redraw;
+use redraw::Reason;
+mod remote;
+mod screenshot;
+mod scripting;
+mod scroll_reader;
+mod search;
+mod selection;
+mod sequence;
+mod sharpness;
+mod similarity;
+mod size_estimate;
+mod soft_proof;
+mod sorting;
 #[cfg(test)]
 mod tests;
+mod thumbnails;
+#[cfg(feature = "tray")]
+mod tray;
 mod ui;
 #[cfg(feature = "update")]
 mod update;
@@ -57,9 +116,48 @@ fn main() -> Result<(), String> {
     }
     // on debug builds, override log level
     #[cfg(debug_assertions)]
-    {
-        std::env::set_var("RUST_LOG", "debug");
-        let _ = env_logger::try_init();
+    std::env::set_var("RUST_LOG", "debug");
+    logging::init();
+    crash::install_hook();
+
+    // Filter out strange mac args
+    let args: Vec<String> = std::env::args().filter(|a| !a.contains("psn_")).collect();
+    let matches = cli().get_matches_from(args);
+    let capture = matches
+        .value_of("capture")
+        .and_then(screenshot::CaptureArea::from_name);
+    if let Some(area) = capture {
+        // let an instance in background mode take it, if there is one
+        if instance::send(&instance::InstanceRequest::Capture(area)).is_ok() {
+            return Ok(());
+        }
+    }
+    if matches.is_present("show") {
+        // hand over to an instance in background mode, if there is one
+        let request = match matches.value_of("INPUT") {
+            Some(path) => instance::InstanceRequest::Open(
+                std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)),
+            ),
+            None => instance::InstanceRequest::Show,
+        };
+        if instance::send(&request).is_ok() {
+            return Ok(());
+        }
+    }
+    if let Some(folder) = matches.value_of("bench") {
+        decoders::register_all();
+        let stats = bench::run(
+            std::path::Path::new(folder),
+            matches.is_present("all-files"),
+        )
+        .map_err(|e| e.to_string())?;
+        println!(
+            "Oculante {} decode benchmark, turbo {}",
+            env!("CARGO_PKG_VERSION"),
+            if cfg!(feature = "turbo") { "on" } else { "off" }
+        );
+        println!("{}", bench::report(&stats));
+        return Ok(());
     }
 
     let icon_data = include_bytes!("../icon.ico");
@@ -108,9 +206,9 @@ fn main() -> Result<(), String> {
         let _ = mac::launch();
     }
 
-    // Unfortunately we need to load the persistent settings here, too - the window settings need
-    // to be set before window creation
-    match settings::PersistentSettings::load() {
+    // Settings are loaded once here, as the window settings need to be set before window
+    // creation. They are handed to `init` afterwards.
+    let settings = match settings::PersistentSettings::load() {
         Ok(settings) => {
             window_config.vsync = settings.vsync;
             window_config.lazy_loop = !settings.force_redraw;
@@ -127,35 +225,37 @@ fn main() -> Result<(), String> {
                 ));
                 window_config = window_config.set_title(&title_string);
             }
+            Some(settings)
         }
         Err(e) => {
             error!("Could not load settings: {e}");
+            None
         }
-    }
+    };
     window_config.always_on_top = true;
     window_config.min_size = Some((1, 1));
     window_config.max_size = None;
 
     info!("Starting oculante.");
-    notan::init_with(init)
-        .add_config(window_config)
-        .add_config(EguiConfig)
-        .add_config(DrawConfig)
-        .event(event)
-        .update(update)
-        .draw(drawe)
-        .build()
+    notan::init_with(
+        move |app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins| {
+            init(app, gfx, plugins, matches, settings)
+        },
+    )
+    .add_config(window_config)
+    .add_config(EguiConfig)
+    .add_config(DrawConfig)
+    .event(event)
+    .update(update)
+    .draw(drawe)
+    .build()
 }
 
-fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
-    info!("Now matching arguments {:?}", std::env::args());
-    // Filter out strange mac args
-    let args: Vec<String> = std::env::args().filter(|a| !a.contains("psn_")).collect();
-
-    let matches = Command::new("Oculante")
+fn cli() -> Command<'static> {
+    Command::new("Oculante")
         .arg(
             Arg::new("INPUT")
-                .help("Display this image")
+                .help("Display this image, an MJPEG stream at an http:// or tcp:// URL, float frames written to a pipe://, or a remote folder at an sftp://, smb://, s3:// or gs:// URL")
                 // .required(true)
                 .index(1),
         )
@@ -165,6 +265,18 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
                 .help("Listen on port")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("all-files")
+                .long("all-files")
+                .takes_value(false)
+                .help("Also open files with unknown or missing extensions"),
+        )
+        .arg(
+            Arg::new("read-only")
+                .long("read-only")
+                .takes_value(false)
+                .help("Never change, move or delete files"),
+        )
         .arg(
             Arg::new("chainload")
                 .required(false)
@@ -172,35 +284,102 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
                 .short('c')
                 .help("Chainload on Mac"),
         )
-        .get_matches_from(args);
-
-    debug!("Completed argument parsing.");
+        .arg(
+            Arg::new("show")
+                .long("show")
+                .takes_value(false)
+                .help("Show an oculante running in background mode instead of starting a new one"),
+        )
+        .arg(
+            Arg::new("capture")
+                .long("capture")
+                .takes_value(true)
+                .possible_values(["screen", "region"])
+                .help("Take a screenshot of the screen or of a region and open it"),
+        )
+        .arg(
+            Arg::new("bench")
+                .long("bench")
+                .takes_value(true)
+                .hide(true)
+                .help("Decode all images in a folder and print timings"),
+        )
+}
 
-    let maybe_img_location = matches.value_of("INPUT").map(PathBuf::from);
+fn init(
+    app: &mut App,
+    gfx: &mut Graphics,
+    plugins: &mut Plugins,
+    matches: ArgMatches,
+    settings: Option<settings::PersistentSettings>,
+) -> OculanteState {
+    info!("Arguments: {:?}", matches);
+
+    let stream_url = matches.value_of("INPUT").filter(|input| {
+        ["http://", "https://", "tcp://", "pipe://"]
+            .iter()
+            .any(|scheme| input.starts_with(scheme))
+    });
+    let remote_url = matches.value_of("INPUT").filter(|input| {
+        ["sftp://", "smb://", "s3://", "gs://"]
+            .iter()
+            .any(|scheme| input.starts_with(scheme))
+    });
+    let maybe_img_location = matches
+        .value_of("INPUT")
+        .filter(|_| stream_url.is_none() && remote_url.is_none())
+        .map(PathBuf::from);
 
     let mut state = OculanteState {
         texture_channel: mpsc::channel(),
         // current_path: maybe_img_location.cloned(/),
         ..Default::default()
     };
+    if let Some(waker) = app
+        .backend
+        .downcast_ref::<notan::backend::WinitBackend>()
+        .and_then(|backend| backend.waker())
+    {
+        state.redraw.set_waker(waker);
+    }
 
-    match settings::PersistentSettings::load() {
-        Ok(settings) => {
+    match settings {
+        Some(settings) => {
             state.persistent_settings = settings;
+            // settings may have been saved while a folder view was in use
+            state.persistent_settings.leave_folder();
             info!("Successfully loaded previous settings.")
         }
-        Err(e) => {
-            warn!("Settings failed to load: {e}. This may happen after application updates. Generating a fresh file.");
+        None => {
+            warn!("Settings failed to load. This may happen after application updates. Generating a fresh file.");
             state.persistent_settings = Default::default();
             state.persistent_settings.save();
         }
     }
 
+    state.all_files = matches.is_present("all-files");
+    state.read_only = matches.is_present("read-only");
+
+    // Decoders are needed for the first image, the rest of the setup happens while it loads
+    decoders::register_all();
+
     state.player = Player::new(
         state.texture_channel.0.clone(),
         state.persistent_settings.max_cache,
         gfx.limits().max_texture_size,
     );
+    state.player.write_thumbnails = state.persistent_settings.write_thumbnails;
+    remote::set_credentials(state.persistent_settings.remote_credentials.clone());
+    state.player.set_disk_cache(
+        state.persistent_settings.disk_cache,
+        state.persistent_settings.disk_cache_size,
+    );
+    if let Ok(file) = sharpness::SharpnessCache::default_file(sharpness::CACHE_FILE) {
+        state.sharpness = sharpness::SharpnessCache::load(&file);
+    }
+    if let Ok(file) = similarity::HashCache::default_file(similarity::CACHE_FILE) {
+        state.hashes = similarity::HashCache::load(&file);
+    }
 
     debug!("Image is: {:?}", maybe_img_location);
 
@@ -211,11 +390,15 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
         if let Ok(maybe_location_metadata) = location.metadata() {
             if maybe_location_metadata.is_dir() {
                 // Folder - Pick first image from the folder...
-                if let Ok(first_img_location) = find_first_image_in_directory(location) {
+                if let Ok(first_img_location) =
+                    find_first_image_in_directory(location, state.load_all_files())
+                {
                     start_img_location = Some(first_img_location);
                 }
-            } else if is_ext_compatible(location) {
-                // Image File with a usable extension
+            } else if is_ext_compatible(location)
+                || (state.load_all_files() && is_content_compatible(location))
+            {
+                // Image File with a usable extension, or content if enabled
                 start_img_location = Some(location.clone());
             } else {
                 // Unsupported extension
@@ -236,11 +419,34 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
         }
     }
 
+    if let Some(url) = stream_url {
+        play_stream(&mut state, url);
+    }
+
+    if let Some(url) = remote_url {
+        open_remote(&mut state, url);
+    }
+
+    match image_editing::EditPreset::load_all() {
+        Ok(presets) => state.edit_presets = presets,
+        Err(e) => debug!("No edit presets loaded: {e}"),
+    }
+
+    match scripting::Script::load_all() {
+        Ok(scripts) => state.scripts = scripts,
+        Err(e) => debug!("No scripts loaded: {e}"),
+    }
+
     if let Some(port) = matches.value_of("l") {
         match port.parse::<i32>() {
             Ok(p) => {
                 state.message = Some(Message::info(&format!("Listening on {p}")));
-                recv(p, state.texture_channel.0.clone());
+                recv(
+                    p,
+                    state.texture_channel.0.clone(),
+                    state.network_metadata_channel.0.clone(),
+                    state.redraw.waker(),
+                );
                 state.current_path = Some(PathBuf::from(&format!("network port {p}")));
                 state.network_mode = true;
             }
@@ -248,6 +454,33 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
         }
     }
 
+    if let Some(area) = matches
+        .value_of("capture")
+        .and_then(screenshot::CaptureArea::from_name)
+    {
+        _ = state
+            .instance_channel
+            .0
+            .send(instance::InstanceRequest::Capture(area));
+    }
+
+    state.crash_log = crash::pending();
+
+    #[cfg(feature = "update")]
+    {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let day = 24 * 60 * 60;
+        if state.persistent_settings.check_for_updates
+            && now.saturating_sub(state.persistent_settings.last_update_check) > day
+        {
+            state.persistent_settings.last_update_check = now;
+            update::check_in_background(&mut state, false);
+        }
+    }
+
     // Set up egui style
     plugins.egui(|ctx| {
         let mut fonts = FontDefinitions::default();
@@ -263,11 +496,6 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
             .insert(0, "my_font".to_owned());
 
         egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
-        match state.persistent_settings.theme {
-            ColorTheme::Light => ctx.set_visuals(Visuals::light()),
-            ColorTheme::Dark => ctx.set_visuals(Visuals::dark()),
-            ColorTheme::System => set_system_theme(ctx),
-        }
 
         let mut style: egui::Style = (*ctx.style()).clone();
         let font_scale = 0.80;
@@ -277,58 +505,38 @@ fn init(gfx: &mut Graphics, plugins: &mut Plugins) -> OculanteState {
         style.text_styles.get_mut(&TextStyle::Small).unwrap().size = 15. * font_scale;
         style.text_styles.get_mut(&TextStyle::Heading).unwrap().size = 22. * font_scale;
         debug!("Accent color: {:?}", state.persistent_settings.accent_color);
-        style.visuals.selection.bg_fill = Color32::from_rgb(
-            state.persistent_settings.accent_color[0],
-            state.persistent_settings.accent_color[1],
-            state.persistent_settings.accent_color[2],
-        );
-
-        let accent_color = style.visuals.selection.bg_fill.to_array();
-
-        let accent_color_luma = (accent_color[0] as f32 * 0.299
-            + accent_color[1] as f32 * 0.587
-            + accent_color[2] as f32 * 0.114)
-            .max(0.)
-            .min(255.) as u8;
-        let accent_color_luma = if accent_color_luma < 80 { 220 } else { 80 };
-        // Set text on highlighted elements
-        style.visuals.selection.stroke = Stroke::new(2.0, Color32::from_gray(accent_color_luma));
         ctx.set_fonts(fonts);
 
         ctx.set_style(style);
-    });
+        settings::apply_style(ctx, &state.persistent_settings);
 
-    // load checker texture
-    if let Ok(checker_image) = image::load_from_memory(include_bytes!("../res/checker.png")) {
-        // state.checker_texture = checker_image.into_rgba8().to_texture(gfx);
-        // No mipmaps for the checker pattern!
-        let img = checker_image.into_rgba8();
-        state.checker_texture = gfx
-            .create_texture()
-            .from_bytes(&img, img.width(), img.height())
-            .with_mipmaps(false)
-            .with_format(notan::prelude::TextureFormat::SRgba8)
-            .build()
-            .ok();
-    }
+        #[cfg(feature = "accessibility")]
+        {
+            state.accessibility = accessibility::Accessibility::new(app, ctx, state.redraw.waker());
+        }
+    });
 
     state
 }
 
 fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
+    state.redraw.invalidate(Reason::Input);
     match evt {
         Event::KeyUp { .. } => {
             // Fullscreen needs to be on key up on mac (bug)
             if key_pressed(app, state, Fullscreen) {
                 toggle_fullscreen(app, state);
             }
+            if key_pressed(app, state, NextDisplay) {
+                move_to_next_display(app, state);
+            }
         }
         Event::KeyDown { .. } => {
             debug!("key down");
 
             // return;
             // pan image with keyboard
-            let delta = 40.;
+            let delta = state.persistent_settings.pan_step;
             if key_pressed(app, state, PanRight) {
                 state.image_geometry.offset.x += delta;
                 limit_offset(app, state);
@@ -345,36 +553,142 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                 state.image_geometry.offset.y += delta;
                 limit_offset(app, state);
             }
+            // pan by the size of the window
+            let (width, height) = app.window().size();
+            if key_pressed(app, state, PanPageRight) {
+                state.image_geometry.offset.x += width as f32;
+                limit_offset(app, state);
+            }
+            if key_pressed(app, state, PanPageLeft) {
+                state.image_geometry.offset.x -= width as f32;
+                limit_offset(app, state);
+            }
+            if key_pressed(app, state, PanPageDown) {
+                state.image_geometry.offset.y += height as f32;
+                limit_offset(app, state);
+            }
+            if key_pressed(app, state, PanPageUp) {
+                state.image_geometry.offset.y -= height as f32;
+                limit_offset(app, state);
+            }
             if key_pressed(app, state, CompareNext) {
                 compare_next(state);
             }
             if key_pressed(app, state, ResetView) {
                 state.reset_image = true
             }
+            if key_pressed(app, state, LockView) {
+                toggle_lock_view(state);
+            }
+            if key_pressed(app, state, PlaySequence) {
+                if let Some(sequence) = &mut state.sequence {
+                    sequence.toggle();
+                } else if let Some(animation) = &mut state.animation {
+                    animation.toggle();
+                }
+            }
+            if key_pressed(app, state, NextFrame) {
+                if let Some(animation) = &mut state.animation {
+                    animation.step(true);
+                }
+            }
+            if key_pressed(app, state, PreviousFrame) {
+                if let Some(animation) = &mut state.animation {
+                    animation.step(false);
+                }
+            }
+            if key_pressed(app, state, CaptureViewport) {
+                state.capture_viewport = Some(0);
+            }
+            if key_pressed(app, state, Screenshot) {
+                take_screenshot(app, state, screenshot::CaptureArea::Screen);
+            }
+            if key_pressed(app, state, ScreenshotRegion) && screenshot::supports_region() {
+                take_screenshot(app, state, screenshot::CaptureArea::Region);
+            }
+            if key_pressed(app, state, Search) {
+                toggle_search(state);
+            }
+            if key_pressed(app, state, FocusMenu) {
+                if state.persistent_settings.zen_mode {
+                    toggle_zen_mode(state, app);
+                }
+                state.focus_menu = true;
+            }
             if key_pressed(app, state, ZenMode) {
                 toggle_zen_mode(state, app);
             }
-            if key_pressed(app, state, ZoomActualSize) {
+            // while sorting, the number keys pick a folder instead of a zoom level
+            let sort_index =
+                if state.sorting.active && !state.key_grab && app.keyboard.down.len() == 1 {
+                    app.keyboard
+                        .pressed
+                        .iter()
+                        .find_map(|key| sorting::sort_key(&format!("{:?}", key)))
+                } else {
+                    None
+                };
+            if let Some(index) = sort_index {
+                sort_image(state, index);
+            }
+            if key_pressed(app, state, ScrollReading) {
+                toggle_scroll_reader(state);
+            }
+            // while reading, the arrow and page keys scroll
+            if state.scroll_reader.active && !state.key_grab && app.keyboard.down.len() == 1 {
+                let (width, height) = app.window().size();
+                let step = state.persistent_settings.pan_step;
+                let delta: f32 = app
+                    .keyboard
+                    .pressed
+                    .iter()
+                    .filter_map(|key| {
+                        scroll_reader::scroll_key(&format!("{:?}", key), step, height as f32)
+                    })
+                    .sum();
+                if delta != 0. {
+                    let width = width as f32 * state.persistent_settings.scroll_reader_width;
+                    state.scroll_reader.scroll(delta, width, height as f32);
+                }
+            }
+            if key_pressed(app, state, ToggleSorting) {
+                state.sorting.active = !state.sorting.active;
+                if state.sorting.active {
+                    state.send_message("Sorting with the number keys");
+                } else {
+                    state.send_message("Stopped sorting");
+                }
+            }
+            if sort_index.is_none() && key_pressed(app, state, ZoomActualSize) {
                 set_zoom(1.0, None, state);
             }
-            if key_pressed(app, state, ZoomDouble) {
+            if sort_index.is_none() && key_pressed(app, state, ZoomDouble) {
                 set_zoom(2.0, None, state);
             }
-            if key_pressed(app, state, ZoomThree) {
+            if sort_index.is_none() && key_pressed(app, state, ZoomThree) {
                 set_zoom(3.0, None, state);
             }
-            if key_pressed(app, state, ZoomFour) {
+            if sort_index.is_none() && key_pressed(app, state, ZoomFour) {
                 set_zoom(4.0, None, state);
             }
-            if key_pressed(app, state, ZoomFive) {
+            if sort_index.is_none() && key_pressed(app, state, ZoomFive) {
                 set_zoom(5.0, None, state);
             }
-            if key_pressed(app, state, Quit) {
+            if key_pressed(app, state, ZoomPrintSize) {
+                zoom_to_print_size(app, state);
+            }
+            if key_pressed(app, state, ZoomNextWhole) {
+                set_zoom(next_whole_zoom(state.image_geometry.scale), None, state);
+            }
+            if key_pressed(app, state, Quit) && state.persistent_settings.background_mode {
+                hide_to_background(app, state);
+            } else if key_pressed(app, state, Quit) {
+                state.persistent_settings.leave_folder();
                 state.persistent_settings.save_blocking();
                 app.backend.exit();
             }
             #[cfg(feature = "turbo")]
-            if key_pressed(app, state, LosslessRotateRight) {
+            if key_pressed(app, state, LosslessRotateRight) && state.check_writable() {
                 debug!("Lossless rotate right");
 
                 if let Some(p) = &state.current_path {
@@ -395,7 +709,7 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                 }
             }
             #[cfg(feature = "turbo")]
-            if key_pressed(app, state, LosslessRotateLeft) {
+            if key_pressed(app, state, LosslessRotateLeft) && state.check_writable() {
                 debug!("Lossless rotate left");
                 if let Some(p) = &state.current_path {
                     if lossless_tx(
@@ -418,16 +732,19 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
             }
             #[cfg(feature = "file_open")]
             if key_pressed(app, state, Browse) {
-                state.redraw = true;
                 browse_for_image_path(state);
             }
             if key_pressed(app, state, NextImage) {
-                if state.is_loaded {
+                if state.scroll_reader.active {
+                    state.scroll_reader.turn(1);
+                } else if state.is_loaded {
                     next_image(state)
                 }
             }
             if key_pressed(app, state, PreviousImage) {
-                if state.is_loaded {
+                if state.scroll_reader.active {
+                    state.scroll_reader.turn(-1);
+                } else if state.is_loaded {
                     prev_image(state)
                 }
             }
@@ -452,15 +769,53 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
             if key_pressed(app, state, EditMode) {
                 state.persistent_settings.edit_enabled = !state.persistent_settings.edit_enabled;
             }
+            if key_pressed(app, state, ReviewAccept) {
+                judge_image(state, dataset::Verdict::Accepted);
+            }
+            if key_pressed(app, state, ReviewReject) {
+                judge_image(state, dataset::Verdict::Rejected);
+            }
+            if key_pressed(app, state, ToggleFavourite) {
+                toggle_favourite(state);
+            }
+            if key_pressed(app, state, ToggleLoupe) {
+                state.persistent_settings.loupe_enabled = !state.persistent_settings.loupe_enabled;
+            }
+            if key_pressed(app, state, ApplyPreset) {
+                let preset = state
+                    .edit_presets
+                    .iter()
+                    .find(|p| Some(&p.name) == state.persistent_settings.last_preset.as_ref());
+                if let Some(preset) = preset {
+                    preset.apply(&mut state.edit_state);
+                    state.persistent_settings.edit_enabled = true;
+                } else {
+                    state.send_message_err("No edit preset selected");
+                }
+            }
+            if let Some(script) = state
+                .scripts
+                .iter()
+                .find(|s| shortcuts::keys_pressed(app, state, &s.keys))
+                .cloned()
+            {
+                scripting::run_script(&script, state);
+            }
             #[cfg(not(target_os = "netbsd"))]
-            if key_pressed(app, state, DeleteFile) {
+            if key_pressed(app, state, DeleteFile) && state.check_writable() {
                 if let Some(p) = &state.current_path {
                     _ = trash::delete(p);
                     state.send_message("Deleted image");
                 }
             }
             if key_pressed(app, state, ZoomIn) {
-                let delta = zoomratio(3.5, state.image_geometry.scale);
+                let delta = snap_delta(
+                    state,
+                    zoomratio(
+                        state.persistent_settings.key_zoom_step,
+                        state.image_geometry.scale,
+                    ),
+                );
                 let new_scale = state.image_geometry.scale + delta;
                 // limit scale
                 if new_scale > 0.05 && new_scale < 40. {
@@ -479,7 +834,13 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                 }
             }
             if key_pressed(app, state, ZoomOut) {
-                let delta = zoomratio(-3.5, state.image_geometry.scale);
+                let delta = snap_delta(
+                    state,
+                    zoomratio(
+                        -state.persistent_settings.key_zoom_step,
+                        state.image_geometry.scale,
+                    ),
+                );
                 let new_scale = state.image_geometry.scale + delta;
                 // limit scale
                 if new_scale > 0.05 && new_scale < 40. {
@@ -524,10 +885,28 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                 ),
                 app.window().size(),
             );
+            state.persistent_settings.leave_folder();
             state.persistent_settings.save_blocking();
         }
         Event::MouseWheel { delta_y, .. } => {
-            if !state.pointer_over_ui {
+            let delta_y = if state.persistent_settings.invert_scroll {
+                -delta_y
+            } else {
+                delta_y
+            };
+            if !state.pointer_over_ui && state.scroll_reader.active {
+                let (width, height) = app.window().size();
+                let page_width = &mut state.persistent_settings.scroll_reader_width;
+                if app.keyboard.ctrl() {
+                    *page_width = (*page_width * (1. + delta_y / 500.)).clamp(0.1, 1.);
+                } else {
+                    // a notch of the wheel scrolls about 100 points
+                    let width = width as f32 * *page_width;
+                    state
+                        .scroll_reader
+                        .scroll(-delta_y * 2., width, height as f32);
+                }
+            } else if !state.pointer_over_ui {
                 if app.keyboard.ctrl() {
                     // Change image to next/prev
                     // - map scroll-down == next, as that's the natural scrolling direction
@@ -544,6 +923,8 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                         state.image_geometry.scale,
                     );
                     trace!("Delta {delta}, raw {delta_y}");
+                    let delta =
+                        snap_delta(state, delta * state.persistent_settings.zoom_multiplier);
                     let new_scale = state.image_geometry.scale + delta;
                     // limit scale
                     if new_scale > 0.01 && new_scale < 40. {
@@ -551,10 +932,9 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                             state.image_geometry.offset,
                             state.cursor,
                             state.image_geometry.scale,
-                            delta * state.persistent_settings.zoom_multiplier,
+                            delta,
                         );
-                        state.image_geometry.scale +=
-                            delta * state.persistent_settings.zoom_multiplier;
+                        state.image_geometry.scale += delta;
                     }
                 }
             }
@@ -562,8 +942,10 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
 
         Event::Drop(file) => {
             if let Some(p) = file.path {
-                if let Some(ext) = p.extension() {
-                    if SUPPORTED_EXTENSIONS.contains(&ext.to_string_lossy().to_string().as_str()) {
+                if p.extension().is_some() || state.load_all_files() {
+                    if is_ext_compatible(&p)
+                        || (state.load_all_files() && is_content_compatible(&p))
+                    {
                         state.is_loaded = false;
                         state.current_image = None;
                         state.player.load(&p, state.message_channel.0.clone());
@@ -574,13 +956,70 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
                 }
             }
         }
-        Event::MouseDown { button, .. } => {
+        Event::MouseDown { button, x, y } => {
             state.drag_enabled = true;
             match button {
                 MouseButton::Left => {
                     if !state.mouse_grab {
                         state.drag_enabled = true;
                     }
+                    let point = Vector2::new(x as f32, y as f32);
+                    // grab the line between the original and the edited image
+                    if let Some(img) = &state.current_image {
+                        let split_line = state
+                            .edit_state
+                            .split_line(&state.image_geometry, img.width())
+                            .filter(|_| state.persistent_settings.edit_enabled);
+                        let top = state.image_geometry.offset.y;
+                        let bottom = top + img.height() as f32 * state.image_geometry.scale;
+                        if let Some(line) = split_line {
+                            if (point.x - line).abs() < 8.
+                                && point.y >= top
+                                && point.y <= bottom
+                                && !state.mouse_grab
+                            {
+                                state.edit_state.split_drag = true;
+                                state.drag_enabled = false;
+                            }
+                        }
+                    }
+                    // alt-click samples the canvas background from the image
+                    if app.keyboard.alt() && !state.mouse_grab {
+                        let cursor = state.cursor_relative;
+                        let sample = state
+                            .current_image
+                            .as_ref()
+                            .filter(|_| cursor.x >= 0. && cursor.y >= 0.)
+                            .and_then(|img| img.get_pixel_checked(cursor.x as u32, cursor.y as u32))
+                            .copied();
+                        if let Some(p) = sample {
+                            state.persistent_settings.background_color = [p[0], p[1], p[2]];
+                            state.drag_enabled = false;
+                            state.send_message(&format!(
+                                "Background set to {},{},{}",
+                                p[0], p[1], p[2]
+                            ));
+                        }
+                    }
+                    if let Some(minimap) = minimap::Minimap::of(state) {
+                        if minimap.contains(point) && !state.mouse_grab {
+                            // jump there, unless the visible part is grabbed to drag it
+                            if !minimap.viewport_contains(
+                                point,
+                                state.window_size,
+                                &state.image_geometry,
+                            ) {
+                                state.image_geometry.offset = minimap.center_on(
+                                    point,
+                                    state.window_size,
+                                    &state.image_geometry,
+                                );
+                                limit_offset(app, state);
+                            }
+                            state.minimap_drag = true;
+                            state.drag_enabled = false;
+                        }
+                    }
                 }
                 MouseButton::Middle => {
                     state.drag_enabled = true;
@@ -589,7 +1028,11 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
             }
         }
         Event::MouseUp { button, .. } => match button {
-            MouseButton::Left | MouseButton::Middle => state.drag_enabled = false,
+            MouseButton::Left | MouseButton::Middle => {
+                state.drag_enabled = false;
+                state.minimap_drag = false;
+                state.edit_state.split_drag = false;
+            }
             _ => {}
         },
         _ => {
@@ -599,8 +1042,88 @@ fn event(app: &mut App, state: &mut OculanteState, evt: Event) {
 }
 
 fn update(app: &mut App, state: &mut OculanteState) {
+    if !app.window().visible() {
+        state.redraw.throttle_hidden();
+    }
+
     if state.first_start {
         app.window().set_always_on_top(false);
+        // the tray icon needs the event loop to be running
+        if state.persistent_settings.background_mode {
+            enable_background_mode(state);
+        }
+    }
+
+    if let Ok(request) = state.instance_channel.1.try_recv() {
+        match request {
+            instance::InstanceRequest::Capture(area) => take_screenshot(app, state, area),
+            instance::InstanceRequest::Quit => {
+                state.persistent_settings.leave_folder();
+                state.persistent_settings.save_blocking();
+                app.backend.exit();
+            }
+            request => {
+                if let instance::InstanceRequest::Open(path) = request {
+                    load_image_from_path(&path, state);
+                }
+                app.window().set_visible(true);
+            }
+        }
+    }
+
+    if let Ok(images) = state.remote_channel.1.try_recv() {
+        state.remote.listing = false;
+        match images {
+            Ok((images, _)) if images.is_empty() => {
+                state.send_message_warn("No images in this folder")
+            }
+            Ok((images, index)) => {
+                load_image_from_path(&images[index], state);
+                state.scrubber = scrubber::Scrubber {
+                    index,
+                    entries: images,
+                    wrap: state.persistent_settings.wrap_folder,
+                };
+                state.remote.enabled = false;
+            }
+            Err(e) => state.send_message_err(&e),
+        }
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Some(status) = state.stream_status_channel.1.try_iter().last() {
+        if state.stream.playing.is_some() {
+            state.stream.status = Some(status);
+            state.redraw.invalidate(Reason::Ui);
+        }
+    }
+
+    if let Some(metadata) = state.network_metadata_channel.1.try_iter().last() {
+        state.network_metadata = metadata;
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    // show the newest image if several arrived at once
+    if let Some(path) = state.hot_folder_channel.1.try_iter().last() {
+        load_image_from_path(&path, state);
+        if state.persistent_settings.hot_folder_sound {
+            hot_folder::play_sound();
+        }
+        if state.persistent_settings.hot_folder_raise && !app.window().is_focused() {
+            app.window().set_visible(true);
+            // raise the window, unless it stays on top anyway
+            app.window().set_always_on_top(true);
+            app.window().set_always_on_top(state.always_on_top);
+        }
+    }
+
+    if let Ok(screenshot) = state.screenshot_channel.1.try_recv() {
+        app.window().set_visible(true);
+        match screenshot {
+            Ok(Some(path)) => load_image_from_path(&path, state),
+            Ok(None) => state.send_message("Screenshot cancelled"),
+            Err(e) => state.send_message_err(&format!("Could not take a screenshot: {e}")),
+        }
     }
 
     // dbg!(format!("upg {}", app.timer.elapsed_f32()));
@@ -615,6 +1138,45 @@ fn update(app: &mut App, state: &mut OculanteState) {
         }
     }
 
+    // locked views compare full images
+    let downsample = state.persistent_settings.downsample_on_decode && state.lock_view.is_none();
+    state.player.downsample_to = downsample.then(|| {
+        let (width, height) = app.window().size();
+        (width.max(1), height.max(1))
+    });
+    // advance a playing sequence once the current frame is shown
+    if let Some(sequence) = &mut state.sequence {
+        let current = state
+            .current_path
+            .as_ref()
+            .and_then(|p| sequence.sequence.position(p))
+            .unwrap_or_default();
+        let next = if state.is_loaded {
+            sequence.advance(current)
+        } else {
+            None
+        };
+        if let Some((index, path)) = next {
+            let upcoming = sequence.upcoming(index, 4);
+            load_image_from_path(&path, state);
+            state.player.prefetch(&upcoming);
+        }
+    }
+    if let Some(due) = state.sequence.as_ref().and_then(|s| s.next_due()) {
+        state.redraw.wake_at(due);
+    }
+    state.redraw.poll(Reason::Log, state.log_enabled);
+
+    // load the full image once the preview is not detailed enough
+    if state.is_preview
+        && state.player.full_resolution.is_none()
+        && (state.image_geometry.scale > 1.0 || state.persistent_settings.edit_enabled)
+    {
+        if let Some(p) = state.current_path.clone() {
+            state.player.load_full(&p, state.message_channel.0.clone());
+        }
+    }
+
     // Save every 1.5 secs
     let t = app.timer.elapsed_f32() % 1.5;
     if t <= 0.01 {
@@ -639,11 +1201,29 @@ fn update(app: &mut App, state: &mut OculanteState) {
             limit_offset(app, state);
         }
     }
+    if state.minimap_drag {
+        if let Some(minimap) = minimap::Minimap::of(state) {
+            state.image_geometry.offset += minimap.pan(state.mouse_delta, &state.image_geometry);
+            limit_offset(app, state);
+        }
+    }
+    if state.edit_state.split_drag {
+        if let Some(img) = &state.current_image {
+            state
+                .edit_state
+                .move_split(state.cursor.x, &state.image_geometry, img.width());
+        }
+    }
 
     // Since we can't access the window in the event loop, we store it in the state
     state.window_size = app.window().size().size_vec();
 
-    if state.persistent_settings.info_enabled || state.edit_state.painting {
+    if state.persistent_settings.info_enabled
+        || state.edit_state.painting
+        || state.edit_state.mask_painting()
+        || state.profile.enabled
+        || state.selection.enabled
+    {
         state.cursor_relative = pos_from_coord(
             state.image_geometry.offset,
             state.cursor,
@@ -655,6 +1235,38 @@ fn update(app: &mut App, state: &mut OculanteState) {
         );
     }
 
+    // drag a line for the profile tool
+    if state.profile.enabled {
+        if app.mouse.left_was_pressed() && !state.pointer_over_ui {
+            state.profile.line = Some((state.cursor_relative, state.cursor_relative));
+            state.profile.dragging = true;
+        }
+        if let Some(line) = &mut state.profile.line {
+            if state.profile.dragging {
+                line.1 = state.cursor_relative;
+            }
+        }
+        if !app.mouse.left_is_down() {
+            state.profile.dragging = false;
+        }
+    }
+
+    // drag a rectangle for the selection tool
+    if state.selection.enabled {
+        if app.mouse.left_was_pressed() && !state.pointer_over_ui {
+            state.selection.corners = Some((state.cursor_relative, state.cursor_relative));
+            state.selection.dragging = true;
+        }
+        if let Some(corners) = &mut state.selection.corners {
+            if state.selection.dragging {
+                corners.1 = state.cursor_relative;
+            }
+        }
+        if !app.mouse.left_is_down() {
+            state.selection.dragging = false;
+        }
+    }
+
     // make sure that in edit mode, RGBA is set.
     // This is a bit lazy. but instead of writing lots of stuff for an ubscure feature,
     // let's disable it here.
@@ -662,16 +1274,27 @@ fn update(app: &mut App, state: &mut OculanteState) {
         state.persistent_settings.current_channel = ColorChannel::Rgba;
     }
 
-    // redraw if extended info is missing so we make sure it's promply displayed
-    if state.persistent_settings.info_enabled && state.image_info.is_none() {
-        app.window().request_frame();
-    }
+    // poll if extended info is missing so we make sure it's promply displayed
+    state.redraw.poll(
+        Reason::Info,
+        state.persistent_settings.info_enabled
+            && state.image_info.is_none()
+            && state.current_image.is_some(),
+    );
 
     // check extended info has been sent
     if let Ok(info) = state.extended_info_channel.1.try_recv() {
         debug!("Received extended image info for {}", info.name);
+        if let Some(path) = &state.current_path {
+            if path.to_string_lossy() == info.name && state.sharpness.get(path).is_none() {
+                state.sharpness.insert(path, info.sharpness);
+                if let Err(e) = state.sharpness.save() {
+                    warn!("Can't save sharpness scores: {e}");
+                }
+            }
+        }
         state.image_info = Some(info);
-        app.window().request_frame();
+        state.redraw.invalidate(Reason::Info);
     }
 
     // Only receive messages if current one is cleared
@@ -685,6 +1308,7 @@ fn update(app: &mut App, state: &mut OculanteState) {
             debug!("Received message: {:?}", msg);
             match msg {
                 Message::LoadError(_) => {
+                    state.animation = None;
                     state.current_image = None;
                     state.is_loaded = true;
                     state.current_texture = None;
@@ -712,47 +1336,307 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
         _ = state.persistent_settings.save();
     }
 
+    if let Ok(mut scrubber) = state.scrubber_channel.1.try_recv() {
+        // a listing of the previous folder may arrive late
+        if let Some(p) = state
+            .current_path
+            .as_ref()
+            .filter(|_| !state.search.browsing)
+        {
+            if scrubber.select(p) {
+                scrubber.wrap = state.persistent_settings.wrap_folder;
+                update_sequence(state, &scrubber.entries);
+                state.scrubber = scrubber;
+                arrange_folder(state);
+                let neighbours = state.scrubber.neighbours();
+                state.player.prefetch(&neighbours);
+                state.redraw.invalidate(Reason::Ui);
+            }
+        }
+    }
+
+    if let Ok(scores) = state.sharpness_channel.1.try_recv() {
+        for (path, score) in &scores {
+            state.sharpness.insert(path, *score);
+        }
+        if let Err(e) = state.sharpness.save() {
+            warn!("Can't save sharpness scores: {e}");
+        }
+        arrange_folder(state);
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Ok(index) = state.search_channel.1.try_recv() {
+        state.search.results = index.search(&state.search.query);
+        state.search.index = Some(index);
+        state.search.indexing = false;
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Ok(stats) = state.folder_stats_channel.1.try_recv() {
+        state.folder_stats.stats = Some(stats);
+        state.folder_stats.computing = false;
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    #[cfg(feature = "ocr")]
+    if let Ok(text) = state.ocr_channel.1.try_recv() {
+        match &text {
+            Ok(text) if text.is_empty() => state.send_message_warn("No text found"),
+            Ok(text) => {
+                match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+                    Ok(_) => state.send_message("Text copied"),
+                    Err(e) => state.send_message_err(&format!("Could not copy: {e}")),
+                }
+            }
+            Err(e) => state.send_message_err(&format!("Could not extract text: {e}")),
+        }
+        state.ocr.text = Some(text);
+        state.ocr.running = false;
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Ok((path, checksum)) = state.checksum_channel.1.try_recv() {
+        if state.checksum.path.as_ref() == Some(&path) {
+            state.checksum.checksum = Some(checksum);
+            state.redraw.invalidate(Reason::Ui);
+        }
+    }
+    update_checksum(state);
+
+    if let Ok(created) = state.animation_encoder_channel.1.try_recv() {
+        state.animation_encoder.running = false;
+        match created {
+            Some(Ok(path)) => state.send_message(&format!("Animation saved to {}", path.display())),
+            Some(Err(e)) => state.send_message_err(&format!("Could not create the animation: {e}")),
+            None => (),
+        }
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    sync_favourites(state);
+
+    if let Ok(import) = state.favourites_import_channel.1.try_recv() {
+        let favourites = &mut state.persistent_settings.favourite_images;
+        let added = import
+            .found
+            .into_iter()
+            .filter(|p| favourites.insert(p.clone()))
+            .count();
+        if import.missing.is_empty() {
+            state.send_message(&format!("Imported {added} favourites"));
+        } else {
+            state.send_message_err(&format!(
+                "Imported {added} favourites, {} files are missing",
+                import.missing.len()
+            ));
+            state.favourites.enabled = true;
+        }
+        state.favourites.missing = import.missing;
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    while let Ok((done, total)) = state.favourites_channel.1.try_recv() {
+        state.favourites.progress = (done < total).then(|| (done, total));
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    while let Ok((index, img)) = state.scroll_reader_channel.1.try_recv() {
+        state.scroll_reader.loading.remove(&index);
+        if state.scroll_reader.active && img.width() > 0 {
+            let ratio = img.height() as f32 / img.width() as f32;
+            state.scroll_reader.ratios.insert(index, ratio);
+            if let Some(texture) = img.to_texture(gfx, state.persistent_settings.sampling) {
+                state.scroll_reader.textures.insert(index, texture);
+            }
+        }
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Ok((channel, path)) = state.channel_pack_channel.1.try_recv() {
+        state.channel_pack.sources[channel] = Some(path);
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    let mut copied = false;
+    while let Ok(img) = state.clipboard_watch_channel.1.try_recv() {
+        state.clipboard_watch.add(img);
+        copied = true;
+    }
+    if copied {
+        show_clipboard_entry(state, 0);
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Ok(paths) = state.montage_channel.1.try_recv() {
+        state.montage.sources.extend(paths);
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    if let Ok((path, estimates)) = state.size_estimates_channel.1.try_recv() {
+        if state.size_estimates.path == path {
+            state.size_estimates.estimates = Some(estimates);
+            state.redraw.invalidate(Reason::Ui);
+        }
+    }
+    update_size_estimates(state);
+
+    update_data_view(state, gfx);
+
+    if let Ok(hashes) = state.hash_channel.1.try_recv() {
+        for (path, hash) in &hashes {
+            state.hashes.insert(path, *hash);
+        }
+        if let Err(e) = state.hashes.save() {
+            warn!("Can't save image hashes: {e}");
+        }
+        arrange_folder(state);
+        state.redraw.invalidate(Reason::Ui);
+    }
+
+    #[cfg(feature = "update")]
+    if let Ok(update) = state.update_channel.1.try_recv() {
+        state.available_update = Some(update);
+        state.redraw.invalidate(Reason::Ui);
+    }
+
     // check if a new texture has been sent
-    if let Ok(frame) = state.texture_channel.1.try_recv() {
+    // later frames of an animation are collected by its player, which shows them
+    let mut received = state.texture_channel.1.try_recv().ok();
+    while let Some(frame) = received.take() {
+        if frame.source != FrameSource::Animation {
+            received = Some(frame);
+            break;
+        }
+        if let Some(animation) = &mut state.animation {
+            animation.push(frame.buffer, frame.delay);
+            state.redraw.invalidate(Reason::Ui);
+        }
+        received = state.texture_channel.1.try_recv().ok();
+    }
+    // only the newest frame of a live stream is shown, so it doesn't fall behind
+    while received
+        .as_ref()
+        .map(|f| f.source == FrameSource::Stream)
+        .unwrap_or_default()
+    {
+        match state.texture_channel.1.try_recv() {
+            Ok(next) => received = Some(next),
+            Err(_) => break,
+        }
+    }
+    if let Some(frame) = received {
         let img = frame.buffer;
         debug!("Received image buffer: {:?}", img.dimensions());
+        state.redraw.invalidate(Reason::Image);
         state.image_dimension = img.dimensions();
         // state.current_texture = img.to_texture(gfx);
 
         // debug!("Frame source: {:?}", frame.source);
 
+        if let Some(folder) = state.current_path.as_ref().and_then(|p| p.parent()) {
+            if !state.network_mode && frame.source != FrameSource::Animation {
+                state.persistent_settings.enter_folder(folder);
+            }
+        }
+
         set_title(app, state);
 
-        // fill image sequence
-        if let Some(p) = &state.current_path {
-            state.scrubber = scrubber::Scrubber::new(p);
-            state.scrubber.wrap = state.persistent_settings.wrap_folder;
+        let sequence_playing = state
+            .sequence
+            .as_ref()
+            .map(|s| s.playing)
+            .unwrap_or_default();
+
+        // fill image sequence. The folder is listed on a thread, as it may be large.
+        if let Some(p) = state.current_path.clone() {
+            // until then, a file from another folder is the only entry
+            if !state.scrubber.select(&p) {
+                state.scrubber = scrubber::Scrubber {
+                    index: 0,
+                    entries: vec![p.clone()],
+                    wrap: state.persistent_settings.wrap_folder,
+                };
+            }
+            if !state.network_mode
+                && !sequence_playing
+                && !state.search.browsing
+                && matches!(
+                    frame.source,
+                    FrameSource::Still | FrameSource::Preview | FrameSource::AnimationStart
+                )
+            {
+                list_folder(state, p.clone());
+            }
 
-            // debug!("{:#?} from {}", &state.scrubber, p.display());
-            if !state.persistent_settings.recent_images.contains(p) {
+            if !sequence_playing && !state.persistent_settings.recent_images.contains(&p) {
                 state.persistent_settings.recent_images.insert(0, p.clone());
                 state.persistent_settings.recent_images.truncate(10);
             }
         }
 
+        // the full image replacing its preview keeps the view
+        let replaces_preview = state.is_preview
+            && frame.source == FrameSource::Still
+            && state.player.full_resolution.is_some()
+            && state.player.full_resolution == state.current_path;
+        if replaces_preview {
+            if let Some(preview) = &state.current_image {
+                state.image_geometry.scale *= preview.width() as f32 / img.width() as f32;
+            }
+        }
+        state.is_preview = frame.source == FrameSource::Preview;
+        // a locked view stays exactly where it is for images of the same size, and so does
+        // a playing sequence
+        let locked = sequence_playing
+            || match state.lock_view {
+                Some(dimensions) if dimensions == img.dimensions() => true,
+                Some((width, height)) => {
+                    if frame.source == FrameSource::Still && !replaces_preview {
+                        state.send_message_warn(&format!(
+                            "View is locked for {width}x{height} images, this one is {}x{}",
+                            img.width(),
+                            img.height()
+                        ));
+                    }
+                    false
+                }
+                None => false,
+            };
+        // parts of an image and its completion keep the view of the first part
+        let continues_partial =
+            state.partial_of.is_some() && state.partial_of == state.current_path;
+        state.partial_of = if frame.source == FrameSource::Partial {
+            state.current_path.clone()
+        } else {
+            None
+        };
+
         match frame.source {
-            FrameSource::Still => {
-                debug!("Received still");
+            FrameSource::Still | FrameSource::Preview => {
+                debug!("Received {:?}", frame.source);
                 state.edit_state.result_image_op = Default::default();
                 state.edit_state.result_pixel_op = Default::default();
 
-                if !state.persistent_settings.keep_view {
-                    state.reset_image = true;
+                if !state.keep_view {
+                    if !replaces_preview && !continues_partial && !locked {
+                        state.reset_image = true;
+                        state.apply_zoom_policy = true;
+                    }
 
                     if let Some(p) = state.current_path.clone() {
-                        if state.persistent_settings.max_cache != 0 {
+                        if state.persistent_settings.max_cache != 0
+                            && frame.source == FrameSource::Still
+                        {
                             state.player.cache.insert(&p, img.clone());
                         }
                     }
                 }
+                state.keep_view = false;
                 // always reset if first image
                 if state.current_texture.is_none() {
                     state.reset_image = true;
+                    state.apply_zoom_policy = false;
                 }
 
                 if !state.persistent_settings.keep_edits {
@@ -791,54 +1675,70 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
                         }
                     }
                 }
-                state.redraw = false;
+                state.animation = None;
                 state.image_info = None;
             }
+            FrameSource::Partial => {
+                if !continues_partial
+                    && !locked
+                    && (!state.keep_view || state.current_texture.is_none())
+                {
+                    state.reset_image = true;
+                    state.apply_zoom_policy = state.current_texture.is_some();
+                }
+            }
+            FrameSource::Stream => {
+                // the first frame, or a new size, is fit into the window
+                let resized =
+                    state.current_image.as_ref().map(|i| i.dimensions()) != Some(img.dimensions());
+                if resized && !locked {
+                    state.reset_image = true;
+                    state.apply_zoom_policy = state.current_texture.is_some();
+                }
+                state.animation = None;
+            }
             FrameSource::EditResult => {
                 // debug!("EditResult");
                 // state.edit_state.is_processing = false;
             }
             FrameSource::AnimationStart => {
-                state.redraw = true;
+                state.animation = Some(animation::AnimationPlayer::new(img.clone(), frame.delay));
                 state.reset_image = true
             }
-            FrameSource::Animation => {
-                state.redraw = true;
-            }
+            FrameSource::Animation => (),
         }
 
+        let filters = texture_filters(state.sampling(), img.dimensions());
         if let Some(tex) = &mut state.current_texture {
-            if tex.width() as u32 == img.width() && tex.height() as u32 == img.height() {
+            // the next image may be sampled differently
+            if tex.width() as u32 == img.width()
+                && tex.height() as u32 == img.height()
+                && (*tex.min_filter(), *tex.mag_filter()) == filters
+            {
                 img.update_texture(gfx, tex);
             } else {
-                state.current_texture =
-                    img.to_texture(gfx, state.persistent_settings.linear_mag_filter);
+                state.current_texture = img.to_texture(gfx, state.sampling());
             }
         } else {
             debug!("Setting texture");
-            state.current_texture =
-                img.to_texture(gfx, state.persistent_settings.linear_mag_filter);
+            state.current_texture = img.to_texture(gfx, state.sampling());
         }
 
         state.is_loaded = true;
 
-        match &state.persistent_settings.current_channel {
-            // Unpremultiply the image
-            ColorChannel::Rgb => {
-                state.current_texture =
-                    unpremult(&img).to_texture(gfx, state.persistent_settings.linear_mag_filter)
-            }
-            // Do nuttin'
-            ColorChannel::Rgba => (),
-            // Display the channel
-            _ => {
-                state.current_texture =
-                    solo_channel(&img, state.persistent_settings.current_channel as usize)
-                        .to_texture(gfx, state.persistent_settings.linear_mag_filter)
-            }
+        if frame.source != FrameSource::Partial {
+            detect_alpha_mode(&img, state);
+        }
+        state.channel_view_gpu = prepare_channel_shader(state, gfx);
+        if let Some(shown) = display_image(&img, state) {
+            state.current_texture = shown.to_texture(gfx, state.sampling());
         }
         state.current_image = Some(img);
-        if state.persistent_settings.info_enabled {
+        state.original_texture = None;
+        state.blur_texture = None;
+        // cached edit steps belong to the previous image
+        state.edit_state.image_op_cache.clear();
+        if state.persistent_settings.info_enabled && frame.source != FrameSource::Partial {
             debug!("Sending extended info");
             send_extended_info(
                 &state.current_image,
@@ -848,24 +1748,54 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
         }
     }
 
-    if state.redraw {
-        trace!("Force redraw");
-        app.window().request_frame();
+    // show the next frame of a playing animation
+    if let Some(animation) = &mut state.animation {
+        animation.advance();
+    }
+    if let Some(img) = state.animation.as_mut().and_then(|a| a.take_frame()) {
+        show_animation_frame(img, state, gfx);
+    }
+    if let Some(due) = state.animation.as_ref().and_then(|a| a.next_due()) {
+        state.redraw.wake_at(due);
     }
 
+    // keep checking until the complete image arrives
+    state.redraw.poll(
+        Reason::Loading,
+        state.current_path.is_some()
+            && (!state.is_loaded
+                || state.partial_of.is_some()
+                || state.is_preview && state.player.full_resolution.is_some()),
+    );
+
     if state.reset_image {
         let window_size = app.window().size().size_vec();
+        // new images follow the zoom policy, other resets fit the image
+        let policy = if state.apply_zoom_policy {
+            state.persistent_settings.zoom_policy
+        } else {
+            ZoomPolicy::FitIfLarger
+        };
+        let print_zoom = if policy == ZoomPolicy::PrintSize {
+            print_zoom(app, state)
+        } else {
+            None
+        };
         if let Some(current_image) = &state.current_image {
             let img_size = current_image.size_vec();
-            let scale_factor = (window_size.x / img_size.x)
-                .min(window_size.y / img_size.y)
-                .min(1.0);
-            state.image_geometry.scale = scale_factor;
+            state.image_geometry.scale = print_zoom.unwrap_or_else(|| {
+                policy.scale(
+                    (img_size.x, img_size.y),
+                    (window_size.x, window_size.y),
+                    state.image_geometry.scale,
+                )
+            });
             state.image_geometry.offset =
                 window_size / 2.0 - (img_size * state.image_geometry.scale) / 2.0;
 
             debug!("Image has been reset.");
             state.reset_image = false;
+            state.apply_zoom_policy = false;
         }
         // app.window().request_frame();
     }
@@ -879,23 +1809,142 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
     //     }
     // }
 
-    if let Some(texture) = &state.current_texture {
+    let comparing = state.persistent_settings.edit_enabled
+        && (state.edit_state.split.is_some() || state.edit_state.show_original);
+    if comparing && state.original_texture.is_none() {
+        if let Some(img) = &state.current_image {
+            state.original_texture = img.to_texture(gfx, state.sampling());
+        }
+    }
+
+    let blurred = blur::is_blurred(
+        state.current_path.as_deref(),
+        state.persistent_settings.blur_all,
+        &state.persistent_settings.blurred_folders,
+    ) && !key_held(app, state, RevealImage);
+    if blurred && state.blur_texture.is_none() {
+        if let Some(img) = &state.current_image {
+            state.blur_texture = blur::blurred(img).to_texture(gfx, Sampling::Linear);
+        }
+    }
+
+    if state.scroll_reader.active {
+        let window = state.window_size;
+        let width = window.x * state.persistent_settings.scroll_reader_width;
+        // pages may have turned out larger or smaller than estimated
+        state.scroll_reader.scroll(0., width, window.y);
+        stream_pages(state, width, window.y);
+        let reveal = key_held(app, state, RevealImage);
+        let reader = &state.scroll_reader;
+        let x = (window.x - width) / 2.;
+        let mut y = -reader.position.1;
+        for index in reader.visible(width, window.y) {
+            let height = reader.page_height(index, width);
+            // pages of blurred folders stay hidden
+            let hidden = !reveal
+                && blur::is_blurred(
+                    Some(&reader.pages[index]),
+                    state.persistent_settings.blur_all,
+                    &state.persistent_settings.blurred_folders,
+                );
+            match reader.textures.get(&index).filter(|_| !hidden) {
+                Some(texture) => {
+                    draw.image(texture).position(x, y).size(width, height);
+                }
+                None => {
+                    draw.rect((x, y), (width, height))
+                        .color(Color::new(0.5, 0.5, 0.5, 0.1));
+                }
+            }
+            y += height;
+        }
+    }
+
+    let blur_texture = state.blur_texture.as_ref().filter(|_| blurred);
+    if let Some(texture) = state
+        .current_texture
+        .as_ref()
+        .filter(|_| !state.scroll_reader.active)
+    {
+        // While editing, the texture may hold a downscaled proxy of the image
+        let texture_scale = if state.edit_state.previewing() && texture.width() > 0. {
+            state.image_dimension.0 as f32 / texture.width()
+        } else {
+            1.0
+        };
+        let scale = state.image_geometry.scale * texture_scale;
         if state.persistent_settings.show_checker_background {
+            // created on first use, to keep it out of startup
+            if state.checker_texture.is_none() {
+                state.checker_texture = checker_texture(gfx);
+            }
             if let Some(checker) = &state.checker_texture {
                 draw.pattern(checker)
                     // .size(texture.width() as f32, texture.height() as f32)
-                    .size(texture.width() as f32 * state.image_geometry.scale * state.tiling as f32, texture.height() as f32 * state.image_geometry.scale* state.tiling as f32)
+                    .size(texture.width() * scale * state.tiling as f32, texture.height() * scale * state.tiling as f32)
                     .blend_mode(BlendMode::ADD)
                     .translate(state.image_geometry.offset.x, state.image_geometry.offset.y)
                     // .scale(state.image_geometry.scale, state.image_geometry.scale)
                     ;
             }
         }
-        if state.tiling < 2 {
+        if let Some(blur_texture) = blur_texture {
+            draw.image(blur_texture)
+                .size(texture.width(), texture.height())
+                .blend_mode(BlendMode::NORMAL)
+                .scale(scale, scale)
+                .translate(state.image_geometry.offset.x, state.image_geometry.offset.y);
+        } else if state.tiling < 2 {
+            // Pixel edits are applied by a shader while sliders are dragged
+            let pixel_shader = state
+                .pixel_shader
+                .as_ref()
+                .filter(|_| state.edit_state.gpu_preview && state.persistent_settings.edit_enabled);
+            // computed channel views too
+            let channel_shader = state
+                .channel_shader
+                .as_ref()
+                .filter(|_| state.channel_view_gpu && pixel_shader.is_none());
+            if let Some(shader) = pixel_shader {
+                shader.begin(&mut draw);
+            }
+            if let Some(shader) = channel_shader {
+                shader.begin(&mut draw);
+            }
             draw.image(texture)
                 .blend_mode(BlendMode::NORMAL)
-                .scale(state.image_geometry.scale, state.image_geometry.scale)
+                .scale(scale, scale)
                 .translate(state.image_geometry.offset.x, state.image_geometry.offset.y);
+            if let Some(shader) = pixel_shader {
+                shader.end(&mut draw);
+            }
+            if let Some(shader) = channel_shader {
+                shader.end(&mut draw);
+            }
+            // the original left of the split line, or all of it while the compare key is held
+            let original = state.original_texture.as_ref().filter(|_| comparing);
+            let split = if state.edit_state.show_original {
+                Some(1.0)
+            } else {
+                state.edit_state.split
+            };
+            if let (Some(original), Some(split)) = (original, split) {
+                let (width, height) = (original.width() * split, original.height());
+                let geometry = &state.image_geometry;
+                draw.image(original)
+                    .crop((0., 0.), (width, height))
+                    .size(width, height)
+                    .blend_mode(BlendMode::NORMAL)
+                    .scale(geometry.scale, geometry.scale)
+                    .translate(geometry.offset.x, geometry.offset.y);
+                if !state.edit_state.show_original {
+                    let x = geometry.offset.x + width * geometry.scale;
+                    let bottom = geometry.offset.y + height * geometry.scale;
+                    draw.line((x, geometry.offset.y), (x, bottom))
+                        .width(2.)
+                        .color(Color::WHITE);
+                }
+            }
         } else {
             draw.pattern(texture)
                 .scale(state.image_geometry.scale, state.image_geometry.scale)
@@ -916,30 +1965,111 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
                     a: 0.5,
                 })
                 .blend_mode(BlendMode::ADD)
-                .scale(state.image_geometry.scale, state.image_geometry.scale)
+                .scale(scale, scale)
                 .translate(state.image_geometry.offset.x, state.image_geometry.offset.y);
         }
 
-        if state.persistent_settings.show_minimap {
-            // let offset_x = app.window().size().0 as f32 - state.image_dimension.0 as f32;
-            let offset_x = 0.0;
+        let guide_color = Color::new(1., 1., 1., 0.6);
+        let geometry = &state.image_geometry;
+        let screen = |(x, y): (f32, f32)| {
+            (
+                geometry.offset.x + x * geometry.scale,
+                geometry.offset.y + y * geometry.scale,
+            )
+        };
+        let (width, height) = state.image_dimension;
+        if let Some(ratio) = state.persistent_settings.matte_ratio {
+            for (x, y, w, h) in guides::matte(width as f32, height as f32, ratio) {
+                let (x, y) = screen((x, y));
+                let scale = state.image_geometry.scale;
+                draw.rect((x, y), (w * scale, h * scale))
+                    .color(Color::BLACK)
+                    .alpha(state.persistent_settings.matte_opacity);
+            }
+        }
+        for guide in &state.persistent_settings.guides {
+            for (from, to) in guide.lines(width as f32, height as f32) {
+                draw.line(screen(from), screen(to))
+                    .width(1.)
+                    .color(guide_color);
+            }
+        }
+        #[cfg(feature = "detection")]
+        if let Some(info) = state
+            .image_info
+            .as_ref()
+            .filter(|_| state.persistent_settings.info_enabled)
+        {
+            for detection in &info.detections {
+                let (x, y, w, h) = detection.rect;
+                let color = match detection.kind {
+                    detection::DetectionKind::Face => Color::YELLOW,
+                    detection::DetectionKind::Subject => Color::new(0.3, 0.8, 1., 1.),
+                };
+                let size = (w as f32 * geometry.scale, h as f32 * geometry.scale);
+                draw.rect(screen((x as f32, y as f32)), size)
+                    .stroke(2.)
+                    .color(color);
+            }
+        }
+
+        if let Some(minimap) = minimap::Minimap::of(state) {
+            let scale = minimap.scale * texture_scale;
+            draw.image(blur_texture.unwrap_or(texture))
+                .size(texture.width(), texture.height())
+                .blend_mode(BlendMode::NORMAL)
+                .translate(minimap.origin.x, minimap.origin.y)
+                .scale(scale, scale);
+            let size = minimap.size();
+            draw.rect((minimap.origin.x, minimap.origin.y), (size.x, size.y))
+                .stroke(1.0)
+                .color(Color::GRAY);
+            let (top_left, size) = minimap.viewport(state.window_size, &state.image_geometry);
+            draw.rect((top_left.x, top_left.y), (size.x, size.y))
+                .stroke(1.5)
+                .color(Color::WHITE);
+        }
 
-            let scale = 200. / app.window().size().0 as f32;
-            let show_minimap = state.image_dimension.0 as f32 * state.image_geometry.scale
-                > app.window().size().0 as f32;
+        if let Some((start, end)) = state.profile.line.filter(|_| state.profile.enabled) {
+            // through the centers of the pixels
+            let geometry = &state.image_geometry;
+            let screen = |p: Vector2<f32>| {
+                (
+                    geometry.offset.x + (p.x + 0.5) * geometry.scale,
+                    geometry.offset.y + (p.y + 0.5) * geometry.scale,
+                )
+            };
+            let (start, end) = (screen(start), screen(end));
+            draw.line(start, end).width(2.).color(Color::YELLOW);
+            draw.circle(3.)
+                .position(start.0, start.1)
+                .color(Color::YELLOW);
+        }
 
-            if show_minimap {
-                draw.image(texture)
-                    .blend_mode(BlendMode::NORMAL)
-                    .translate(offset_x, 100.)
-                    .scale(scale, scale);
-            }
+        if let Some((x, y, width, height)) = state
+            .selection
+            .rect(state.image_dimension)
+            .filter(|_| state.selection.enabled)
+        {
+            let geometry = &state.image_geometry;
+            draw.rect(
+                (
+                    geometry.offset.x + x as f32 * geometry.scale,
+                    geometry.offset.y + y as f32 * geometry.scale,
+                ),
+                (
+                    width as f32 * geometry.scale,
+                    height as f32 * geometry.scale,
+                ),
+            )
+            .stroke(1.5)
+            .color(Color::YELLOW);
         }
 
         // Draw a brush preview when paint mode is on
-        if state.edit_state.painting {
+        if state.edit_state.painting || state.edit_state.mask_painting() {
             if let Some(stroke) = state.edit_state.paint_strokes.last() {
-                let dim = texture.width().min(texture.height()) / 50.;
+                let dim = texture.width().min(texture.height()) * texture_scale / 50.;
                 draw.circle(20.)
                     // .translate(state.cursor_relative.x, state.cursor_relative.y)
                     .alpha(0.5)
@@ -966,6 +2096,11 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
         }
     }
 
+    #[cfg(feature = "accessibility")]
+    if let Some(accessibility) = &state.accessibility {
+        accessibility.send_actions(plugins);
+    }
+
     let egui_output = plugins.egui(|ctx| {
         // the top menu bar
         ctx.request_repaint_after(Duration::from_secs(1));
@@ -1045,7 +2180,103 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
             edit_ui(app, ctx, state, gfx);
         }
 
-        if !state.is_loaded {
+        let sequence_playing = state
+            .sequence
+            .as_ref()
+            .map(|s| s.playing)
+            .unwrap_or_default();
+        if state.sequence.is_some() && !state.persistent_settings.zen_mode {
+            egui::TopBottomPanel::bottom("sequence").show(ctx, |ui| {
+                sequence_ui(state, ui);
+            });
+        }
+        let animated = state
+            .animation
+            .as_ref()
+            .map(|a| a.frames.len() > 1)
+            .unwrap_or_default();
+        if animated && state.sequence.is_none() && !state.persistent_settings.zen_mode {
+            egui::TopBottomPanel::bottom("animation").show(ctx, |ui| {
+                animation_ui(state, ui);
+            });
+        }
+        if sequence_playing {
+            sequence_osd(ctx, state);
+        }
+        if state.network_mode && !state.persistent_settings.zen_mode {
+            network_osd(ctx, state);
+        }
+        if state.dataset.show && !state.persistent_settings.zen_mode {
+            dataset_osd(ctx, state);
+        }
+        if state.scroll_reader.active && !state.persistent_settings.zen_mode {
+            scroll_reader_osd(ctx, state);
+        }
+        if state.persistent_settings.loupe_enabled
+            && !blurred
+            && !state.scroll_reader.active
+            && !state.persistent_settings.zen_mode
+        {
+            loupe_osd(ctx, state, gfx);
+        }
+        #[cfg(feature = "update")]
+        update_ui(ctx, state);
+        crash_ui(ctx, state);
+        if state.log_enabled {
+            log_ui(ctx, state);
+        }
+        if state.search.enabled {
+            search_ui(ctx, state);
+        }
+        if state.folder_stats.enabled {
+            folder_stats_ui(ctx, state);
+        }
+        if state.size_estimates.enabled {
+            size_estimate_ui(ctx, state);
+        }
+        if state.channel_pack.enabled {
+            channel_pack_ui(ctx, state);
+        }
+        if state.montage.enabled {
+            montage_ui(ctx, state);
+        }
+        if state.clipboard_watch.enabled {
+            clipboard_watch_ui(ctx, state);
+        }
+        if state.stream.enabled {
+            stream_ui(ctx, state);
+        }
+        if state.remote.enabled {
+            remote_ui(ctx, state);
+        }
+        if state.dataset.enabled {
+            dataset_ui(ctx, state);
+        }
+        if state.sorting.enabled {
+            sorting_ui(ctx, state);
+        }
+        if state.favourites.enabled {
+            favourites_ui(ctx, state);
+        }
+        if state.animation_encoder.enabled {
+            animation_encoder_ui(ctx, state);
+        }
+        #[cfg(feature = "ocr")]
+        if state.ocr.enabled {
+            text_ui(ctx, state);
+        }
+        colorbar_ui(ctx, state);
+        if state.profile.enabled {
+            profile_ui(ctx, state);
+        }
+        if state.heightmap.enabled {
+            heightmap_ui(ctx, state, gfx);
+        }
+        if state.persistent_settings.current_channel == ColorChannel::NormalMap {
+            normal_map_ui(ctx, state, gfx);
+        }
+
+        if !state.is_loaded && !sequence_playing {
             egui::TopBottomPanel::bottom("loader").show_animated(
                 ctx,
                 state.current_path.is_some(),
@@ -1056,7 +2287,6 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
                             ui.label(format!("Loading {}", p.display()));
                         });
                     }
-                    app.window().request_frame();
                 },
             );
         }
@@ -1066,7 +2296,13 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
 
         // if there is interaction on the ui (dragging etc)
         // we don't want zoom & pan to work, so we "grab" the pointer
-        if ctx.is_using_pointer() || state.edit_state.painting || ctx.is_pointer_over_area() {
+        if ctx.is_using_pointer()
+            || state.edit_state.painting
+            || state.edit_state.mask_painting()
+            || state.profile.enabled
+            || state.selection.enabled
+            || ctx.is_pointer_over_area()
+        {
             state.mouse_grab = true;
         } else {
             state.mouse_grab = false;
@@ -1081,9 +2317,6 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
         settings_ui(app, ctx, state, gfx);
     });
 
-    if state.network_mode {
-        app.window().request_frame();
-    }
     // if state.edit_state.is_processing {
     //     app.window().request_frame();
     // }
@@ -1094,27 +2327,117 @@ fn drawe(app: &mut App, gfx: &mut Graphics, plugins: &mut Plugins, state: &mut O
         c[1] as f32 / 255.,
         c[2] as f32 / 255.,
     ));
-    gfx.render(&draw);
-    gfx.render(&egui_output);
+    let capture = match state.capture_viewport {
+        Some(0) => {
+            state.capture_viewport = None;
+            // the capture is at the physical resolution of the window
+            let (width, height) = gfx.size();
+            let dpi = gfx.dpi() as f32;
+            gfx.create_render_texture((width as f32 * dpi) as u32, (height as f32 * dpi) as u32)
+                .build()
+                .map_err(|e| state.send_message_err(&format!("Could not capture viewport: {e}")))
+                .ok()
+        }
+        Some(frames) => {
+            state.capture_viewport = Some(frames - 1);
+            state.redraw.invalidate(Reason::Ui);
+            None
+        }
+        None => None,
+    };
+    match capture {
+        // egui output can only be rendered once, so the frame is drawn from the capture
+        Some(target) => {
+            gfx.render_to(&target, &draw);
+            gfx.render_to(&target, &egui_output);
+            let mut screen = gfx.create_draw();
+            screen
+                .image(&target)
+                .size(app.window().width() as f32, app.window().height() as f32);
+            gfx.render(&screen);
+            let mut pixels = vec![0; target.width() as usize * target.height() as usize * 4];
+            match gfx.read_pixels(&target).read_to(&mut pixels) {
+                Ok(_) => save_capture(
+                    pixels,
+                    (target.width() as u32, target.height() as u32),
+                    state.persistent_settings.capture_folder.clone(),
+                    state.message_channel.0.clone(),
+                ),
+                Err(e) => state.send_message_err(&format!("Could not capture viewport: {e}")),
+            }
+        }
+        None => {
+            gfx.render(&draw);
+            gfx.render(&egui_output);
+        }
+    }
     if egui_output.needs_repaint() {
+        state.redraw.invalidate(Reason::Ui);
+    }
+    #[cfg(feature = "accessibility")]
+    if let Some(accessibility) = &mut state.accessibility {
+        accessibility.update(app, plugins);
+    }
+    if state.redraw.end_frame() {
         app.window().request_frame();
     }
 }
 
+/// Detect the sequence of the current image in a new folder listing. A sequence that is still
+/// there keeps its playback settings.
+fn update_sequence(state: &mut OculanteState, entries: &[PathBuf]) {
+    let detected = state
+        .current_path
+        .as_ref()
+        .filter(|_| state.persistent_settings.detect_sequences)
+        .and_then(|p| sequence::Sequence::detect(p, entries));
+    match (&mut state.sequence, detected) {
+        (Some(player), Some(detected)) if player.sequence.name() == detected.name() => {
+            let last = detected.frames.len() - 1;
+            // a loop up to the end includes new frames
+            if player.range.1 >= player.sequence.frames.len() - 1 {
+                player.range.1 = last;
+            }
+            player.range = (player.range.0.min(last), player.range.1.min(last));
+            player.sequence = detected;
+        }
+        (_, detected) => state.sequence = detected.map(sequence::SequencePlayer::new),
+    }
+}
+
+fn checker_texture(gfx: &mut Graphics) -> Option<Texture> {
+    let img = image::load_from_memory(include_bytes!("../res/checker.png"))
+        .ok()?
+        .into_rgba8();
+    // No mipmaps for the checker pattern!
+    gfx.create_texture()
+        .from_bytes(&img, img.width(), img.height())
+        .with_mipmaps(false)
+        .with_format(notan::prelude::TextureFormat::SRgba8)
+        .build()
+        .ok()
+}
+
 // Show file browser to select image to load
 #[cfg(feature = "file_open")]
 fn browse_for_image_path(state: &mut OculanteState) {
     let start_directory = state.persistent_settings.last_open_directory.clone();
     let load_sender = state.load_channel.0.clone();
-    state.redraw = true;
+    let dialog = state.redraw.poll_while_alive(Reason::Dialog);
     std::thread::spawn(move || {
+        let _dialog = dialog;
+        let extensions = utils::SUPPORTED_EXTENSIONS
+            .iter()
+            .map(|e| e.to_string())
+            .chain(decoders::extensions())
+            .collect::<Vec<_>>();
         let uppercase_lowercase_ext = [
-            utils::SUPPORTED_EXTENSIONS
-                .into_iter()
+            extensions
+                .iter()
                 .map(|e| e.to_ascii_lowercase())
                 .collect::<Vec<_>>(),
-            utils::SUPPORTED_EXTENSIONS
-                .into_iter()
+            extensions
+                .iter()
                 .map(|e| e.to_ascii_uppercase())
                 .collect::<Vec<_>>(),
         ]
@@ -1151,6 +2474,16 @@ fn limit_offset(app: &mut App, state: &mut OculanteState) {
         .max(-scaled_image_size.1);
 }
 
+/// The zoom step `delta`, changed to land on whole zoom levels if snapping is on
+fn snap_delta(state: &OculanteState, delta: f32) -> f32 {
+    if state.persistent_settings.snap_zoom {
+        let scale = state.image_geometry.scale;
+        snapped_zoom(scale, scale + delta) - scale
+    } else {
+        delta
+    }
+}
+
 fn set_zoom(scale: f32, from_center: Option<Vector2<f32>>, state: &mut OculanteState) {
     let delta = scale - state.image_geometry.scale;
     let zoom_point = from_center.unwrap_or(state.cursor);
@@ -0,0 +1,724 @@
+//! Editing descriptive metadata: title, description, keywords, copyright and date. It is written
+//! to the EXIF data of JPEG, PNG and WebP files, or to an XMP sidecar next to any file.
+//! GPS locations are always written to the EXIF data.
+
+use anyhow::{anyhow, bail, Context, Result};
+use exif::{Field, In, Tag, Value};
+use img_parts::{Bytes, DynImage, ImageEXIF};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Windows specific EXIF tags, the only EXIF fields for titles and keywords
+const XP_TITLE: Tag = Tag(exif::Context::Tiff, 0x9C9B);
+const XP_KEYWORDS: Tag = Tag(exif::Context::Tiff, 0x9C9E);
+
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+/// The XMP properties written by `EditableMetadata`
+const XMP_PROPERTIES: [&[u8]; 5] = [
+    b"dc:title",
+    b"dc:description",
+    b"dc:rights",
+    b"dc:subject",
+    b"xmp:CreateDate",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditableMetadata {
+    pub title: String,
+    pub description: String,
+    /// Separated by commas or semicolons
+    pub keywords: String,
+    pub copyright: String,
+    /// As `YYYY:MM:DD HH:MM:SS`, like in EXIF
+    pub date: String,
+}
+
+impl EditableMetadata {
+    /// The metadata of a file, from its XMP sidecar if there is one, else from its EXIF data
+    pub fn read(path: &Path) -> Result<Self> {
+        let sidecar = sidecar_path(path);
+        if sidecar.is_file() {
+            return Ok(Self::from_xmp(&std::fs::read_to_string(sidecar)?));
+        }
+        let exif = match exif::Reader::new()
+            .read_from_container(&mut std::io::BufReader::new(std::fs::File::open(path)?))
+        {
+            Ok(exif) => exif,
+            // no metadata yet
+            Err(exif::Error::NotFound(_)) => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let ascii = |tag: Tag| {
+            exif.get_field(tag, In::PRIMARY)
+                .and_then(|field| match &field.value {
+                    Value::Ascii(strings) => strings
+                        .first()
+                        .map(|s| String::from_utf8_lossy(s).trim().to_string()),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        };
+        let xp = |tag: Tag| {
+            exif.get_field(tag, In::PRIMARY)
+                .and_then(|field| match &field.value {
+                    Value::Byte(bytes) => Some(decode_ucs2(bytes)),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        };
+        Ok(Self {
+            title: xp(XP_TITLE),
+            description: ascii(Tag::ImageDescription),
+            keywords: split_keywords(&xp(XP_KEYWORDS)).join(", "),
+            copyright: ascii(Tag::Copyright),
+            date: ascii(Tag::DateTimeOriginal),
+        })
+    }
+
+    /// The fields that are set in `other` replace the ones here
+    pub fn merged(&self, other: &Self) -> Self {
+        let pick = |a: &String, b: &String| {
+            if b.trim().is_empty() {
+                a.clone()
+            } else {
+                b.clone()
+            }
+        };
+        Self {
+            title: pick(&self.title, &other.title),
+            description: pick(&self.description, &other.description),
+            keywords: pick(&self.keywords, &other.keywords),
+            copyright: pick(&self.copyright, &other.copyright),
+            date: pick(&self.date, &other.date),
+        }
+    }
+
+    /// The EXIF fields for this metadata. Empty values remove the field.
+    pub fn exif_fields(&self) -> Result<Vec<(Tag, Option<Value>)>> {
+        let ascii = |s: &str| Some(s.trim()).filter(|s| !s.is_empty()).map(ascii_value);
+        let xp = |s: &str| {
+            Some(s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| Value::Byte(encode_ucs2(s)))
+        };
+        let date = match self.date.trim() {
+            "" => None,
+            date => Some(ascii_value(&exif_date(date)?)),
+        };
+        Ok(vec![
+            (XP_TITLE, xp(&self.title)),
+            (Tag::ImageDescription, ascii(&self.description)),
+            (XP_KEYWORDS, xp(&split_keywords(&self.keywords).join(";"))),
+            (Tag::Copyright, ascii(&self.copyright)),
+            (Tag::DateTimeOriginal, date.clone()),
+            (Tag::DateTimeDigitized, date),
+        ])
+    }
+
+    /// Write the metadata into the file if its format allows it and no sidecar is wanted, else
+    /// to its sidecar. Returns where it was written.
+    pub fn save(&self, path: &Path, sidecar: bool) -> Result<PathBuf> {
+        let supported = matches!(
+            image::ImageFormat::from_path(path),
+            Ok(image::ImageFormat::Jpeg | image::ImageFormat::Png | image::ImageFormat::WebP)
+        );
+        if sidecar || !supported {
+            self.write_sidecar(path)
+        } else {
+            self.write_exif(path)?;
+            Ok(path.to_path_buf())
+        }
+    }
+
+    /// Write the metadata to the EXIF data of a file
+    pub fn write_exif(&self, path: &Path) -> Result<()> {
+        update_exif(path, &self.exif_fields()?)
+    }
+
+    /// Write the metadata to the XMP sidecar of a file. A sidecar of another app, like the develop
+    /// settings of a raw converter, keeps everything but the fields edited here.
+    pub fn write_sidecar(&self, path: &Path) -> Result<PathBuf> {
+        let sidecar = sidecar_path(path);
+        let xmp = match std::fs::read_to_string(&sidecar) {
+            Ok(existing) => self
+                .merge_xmp(&existing)
+                .with_context(|| format!("Can't update {}", sidecar.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => self.to_xmp()?,
+            Err(e) => return Err(e.into()),
+        };
+        let part = sidecar.with_extension("xmp.part");
+        std::fs::write(&part, xmp)?;
+        std::fs::rename(&part, &sidecar)?;
+        Ok(sidecar)
+    }
+
+    pub fn to_xmp(&self) -> Result<String> {
+        let properties = self.xmp_properties()?;
+        Ok(format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+  <rdf:Description rdf:about=\"\"
+    xmlns:dc=\"{DC_NAMESPACE}\"
+    xmlns:xmp=\"{XMP_NAMESPACE}\">
+{properties}  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end=\"w\"?>
+"
+        ))
+    }
+
+    /// Put the metadata into an existing XMP packet. The properties edited here are replaced,
+    /// whether they are elements or attributes, all others are copied unchanged. The new
+    /// properties go into the first `rdf:Description`.
+    pub fn merge_xmp(&self, existing: &str) -> Result<String> {
+        let properties = self.xmp_properties()?;
+        let mut reader = Reader::from_str(existing);
+        let mut writer = Writer::new(vec![]);
+        let mut buffer = vec![];
+        // depth of the replaced property being skipped
+        let mut skipping = 0;
+        let mut depth = 0;
+        // depth of the description the properties go into
+        let mut target = None;
+        let mut inserted = false;
+        loop {
+            let event = reader.read_event(&mut buffer)?;
+            if skipping > 0 {
+                match event {
+                    Event::Start(_) => skipping += 1,
+                    Event::End(_) => skipping -= 1,
+                    Event::Eof => bail!("The XMP packet ends early"),
+                    _ => (),
+                }
+                buffer.clear();
+                continue;
+            }
+            match event {
+                Event::Start(e) if XMP_PROPERTIES.contains(&e.name()) => skipping = 1,
+                Event::Empty(e) if XMP_PROPERTIES.contains(&e.name()) => (),
+                Event::Start(e) if e.name() == b"rdf:Description" => {
+                    depth += 1;
+                    let first = target.is_none() && !inserted;
+                    if first {
+                        target = Some(depth);
+                    }
+                    writer.write_event(Event::Start(xmp_description(&e, first)?))?;
+                }
+                Event::Empty(e) if e.name() == b"rdf:Description" => {
+                    if target.is_none() && !inserted {
+                        let description = xmp_description(&e, true)?;
+                        writer.write_event(Event::Start(description.clone()))?;
+                        writer.write(properties.as_bytes())?;
+                        writer.write_event(Event::End(description.to_end()))?;
+                        inserted = true;
+                    } else {
+                        writer.write_event(Event::Empty(xmp_description(&e, false)?))?;
+                    }
+                }
+                Event::Start(e) => {
+                    depth += 1;
+                    writer.write_event(Event::Start(e))?;
+                }
+                Event::End(e) => {
+                    if target == Some(depth) {
+                        writer.write(properties.as_bytes())?;
+                        target = None;
+                        inserted = true;
+                    }
+                    depth -= 1;
+                    writer.write_event(Event::End(e))?;
+                }
+                Event::Eof => break,
+                e => writer.write_event(e)?,
+            }
+            buffer.clear();
+        }
+        if !inserted {
+            bail!("The XMP packet has no rdf:Description");
+        }
+        Ok(String::from_utf8(writer.into_inner())?)
+    }
+
+    fn xmp_properties(&self) -> Result<String> {
+        let mut properties = String::new();
+        let mut alt = |name: &str, value: &str| {
+            if !value.trim().is_empty() {
+                properties += &format!(
+                    "   <{name}><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></{name}>\n",
+                    escape(value.trim())
+                );
+            }
+        };
+        alt("dc:title", &self.title);
+        alt("dc:description", &self.description);
+        alt("dc:rights", &self.copyright);
+        let keywords = split_keywords(&self.keywords);
+        if !keywords.is_empty() {
+            let items = keywords
+                .iter()
+                .map(|k| format!("<rdf:li>{}</rdf:li>", escape(k)))
+                .collect::<String>();
+            properties += &format!("   <dc:subject><rdf:Bag>{items}</rdf:Bag></dc:subject>\n");
+        }
+        if !self.date.trim().is_empty() {
+            let date = exif_date(&self.date)?;
+            // 2024:01:02 03:04:05 to 2024-01-02T03:04:05
+            let date = format!("{}T{}", date[..10].replace(':', "-"), &date[11..]);
+            properties += &format!("   <xmp:CreateDate>{date}</xmp:CreateDate>\n");
+        }
+        Ok(properties)
+    }
+
+    /// Read the fields from an XMP packet. This is no full XML parser, but enough for the common
+    /// layouts written by photo tools.
+    pub fn from_xmp(xmp: &str) -> Self {
+        let first = |name: &str| xmp_items(xmp, name).into_iter().next().unwrap_or_default();
+        let date = xmp_property(xmp, "xmp:CreateDate")
+            .or_else(|| xmp_property(xmp, "photoshop:DateCreated"))
+            .and_then(|date| exif_date(&date).ok())
+            .unwrap_or_default();
+        Self {
+            title: first("dc:title"),
+            description: first("dc:description"),
+            keywords: xmp_items(xmp, "dc:subject").join(", "),
+            copyright: first("dc:rights"),
+            date,
+        }
+    }
+}
+
+/// A location in decimal degrees, positive to the north and east
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoTag {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl fmt::Display for GeoTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.6}, {:.6}", self.latitude, self.longitude)
+    }
+}
+
+impl GeoTag {
+    /// The GPS tags that are replaced or removed when geotagging
+    const TAGS: [Tag; 7] = [
+        Tag::GPSVersionID,
+        Tag::GPSLatitudeRef,
+        Tag::GPSLatitude,
+        Tag::GPSLongitudeRef,
+        Tag::GPSLongitude,
+        Tag::GPSAltitudeRef,
+        Tag::GPSAltitude,
+    ];
+
+    /// Parse coordinates as pasted from maps or other tools, like `48.8584, 2.2945`,
+    /// `48.8584 N 2.2945 E` or `48°51'30.2"N 2°17'40.2"E`. Latitude comes first unless the
+    /// hemispheres say otherwise.
+    pub fn parse(s: &str) -> Result<Self> {
+        enum Token {
+            Number(f64),
+            Hemisphere(char),
+        }
+        let mut tokens = vec![];
+        let mut number = String::new();
+        let flush = |number: &mut String, tokens: &mut Vec<Token>| -> Result<()> {
+            if !number.is_empty() {
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .with_context(|| format!("{number} is not a number"))?,
+                ));
+                number.clear();
+            }
+            Ok(())
+        };
+        for c in s.chars() {
+            match c.to_ascii_uppercase() {
+                '0'..='9' | '.' => number.push(c),
+                '-' | '+' => {
+                    flush(&mut number, &mut tokens)?;
+                    number.push(c);
+                }
+                h @ ('N' | 'S' | 'E' | 'W') => {
+                    flush(&mut number, &mut tokens)?;
+                    tokens.push(Token::Hemisphere(h));
+                }
+                _ => flush(&mut number, &mut tokens)?,
+            }
+        }
+        flush(&mut number, &mut tokens)?;
+
+        // Group the numbers of each coordinate. Hemispheres end a group, or start one if the
+        // text begins with a hemisphere.
+        let prefixed = matches!(tokens.first(), Some(Token::Hemisphere(_)));
+        let mut groups: Vec<(Vec<f64>, Option<char>)> = vec![];
+        let mut current: (Vec<f64>, Option<char>) = (vec![], None);
+        for token in tokens {
+            match token {
+                Token::Number(n) => current.0.push(n),
+                Token::Hemisphere(h) if prefixed => {
+                    if !current.0.is_empty() {
+                        groups.push(std::mem::take(&mut current));
+                    }
+                    current.1 = Some(h);
+                }
+                Token::Hemisphere(h) => {
+                    current.1 = Some(h);
+                    groups.push(std::mem::take(&mut current));
+                }
+            }
+        }
+        if !current.0.is_empty() {
+            groups.push(current);
+        }
+        // Without hemispheres, split the numbers evenly
+        if groups.len() == 1 && groups[0].1.is_none() && groups[0].0.len() % 2 == 0 {
+            let mut numbers = groups.remove(0).0;
+            let second = numbers.split_off(numbers.len() / 2);
+            groups = vec![(numbers, None), (second, None)];
+        }
+        if groups.len() != 2 || groups.iter().any(|(n, _)| n.is_empty() || n.len() > 3) {
+            bail!("Coordinates look like 48.8584, 2.2945 or 48°51'30\"N 2°17'40\"E");
+        }
+
+        let degrees = |(numbers, hemisphere): &(Vec<f64>, Option<char>)| -> Result<f64> {
+            let get = |i: usize| numbers.get(i).cloned().unwrap_or_default();
+            if get(1) < 0. || get(1) >= 60. || get(2) < 0. || get(2) >= 60. {
+                bail!("Minutes and seconds must be between 0 and 60");
+            }
+            let value = get(0).abs() + get(1) / 60. + get(2) / 3600.;
+            let negative = get(0).is_sign_negative() || matches!(hemisphere, Some('S' | 'W'));
+            Ok(if negative { -value } else { value })
+        };
+        let (mut latitude, mut longitude) = (degrees(&groups[0])?, degrees(&groups[1])?);
+        if matches!(groups[0].1, Some('E' | 'W')) || matches!(groups[1].1, Some('N' | 'S')) {
+            std::mem::swap(&mut latitude, &mut longitude);
+        }
+        if latitude.abs() > 90. {
+            bail!("The latitude must be between -90 and 90");
+        }
+        if longitude.abs() > 180. {
+            bail!("The longitude must be between -180 and 180");
+        }
+        Ok(Self {
+            latitude,
+            longitude,
+        })
+    }
+
+    /// The location in the EXIF data of a file, if there is one
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let exif = match exif::Reader::new()
+            .read_from_container(&mut std::io::BufReader::new(std::fs::File::open(path)?))
+        {
+            Ok(exif) => exif,
+            Err(exif::Error::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let coordinate = |tag: Tag, reference: Tag, negative: u8| {
+            let dms = match &exif.get_field(tag, In::PRIMARY)?.value {
+                Value::Rational(dms) if !dms.is_empty() => dms.clone(),
+                _ => return None,
+            };
+            let value = dms
+                .iter()
+                .zip([1., 60., 3600.])
+                .map(|(r, unit)| r.to_f64() / unit)
+                .sum::<f64>();
+            let negative = match &exif.get_field(reference, In::PRIMARY)?.value {
+                Value::Ascii(s) => s.first().and_then(|s| s.first()) == Some(&negative),
+                _ => false,
+            };
+            Some(if negative { -value } else { value })
+        };
+        let latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S');
+        let longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W');
+        Ok(latitude.zip(longitude).map(|(latitude, longitude)| Self {
+            latitude,
+            longitude,
+        }))
+    }
+
+    /// The GPS fields for a location, or the ones to remove if there is none
+    pub fn exif_fields(location: Option<&Self>) -> Vec<(Tag, Option<Value>)> {
+        let location = match location {
+            Some(location) => location,
+            None => return Self::TAGS.iter().map(|tag| (*tag, None)).collect(),
+        };
+        // Degrees, minutes and seconds with four decimals
+        let dms = |value: f64| {
+            let seconds = (value.abs() * 3600. * 10000.).round() as u64;
+            let (degrees, rest) = (seconds / 36_000_000, seconds % 36_000_000);
+            let (minutes, seconds) = (rest / 600_000, rest % 600_000);
+            Value::Rational(vec![
+                (degrees as u32, 1).into(),
+                (minutes as u32, 1).into(),
+                (seconds as u32, 10000).into(),
+            ])
+        };
+        let reference = |value: f64, positive: &str, negative: &str| {
+            ascii_value(if value < 0. { negative } else { positive })
+        };
+        vec![
+            (Tag::GPSVersionID, Some(Value::Byte(vec![2, 3, 0, 0]))),
+            (
+                Tag::GPSLatitudeRef,
+                Some(reference(location.latitude, "N", "S")),
+            ),
+            (Tag::GPSLatitude, Some(dms(location.latitude))),
+            (
+                Tag::GPSLongitudeRef,
+                Some(reference(location.longitude, "E", "W")),
+            ),
+            (Tag::GPSLongitude, Some(dms(location.longitude))),
+            // a previous altitude most likely belongs to another place
+            (Tag::GPSAltitudeRef, None),
+            (Tag::GPSAltitude, None),
+        ]
+    }
+
+    /// Set the location of a JPEG, PNG or WebP file, or remove it
+    pub fn write(location: Option<&Self>, path: &Path) -> Result<()> {
+        update_exif(path, &Self::exif_fields(location))
+    }
+}
+
+/// Sidecars replace the extension, like `IMG_0001.xmp` for `IMG_0001.jpg`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("xmp")
+}
+
+/// A copy of an `rdf:Description` start tag without the replaced properties. The one receiving
+/// the new properties also declares their namespaces.
+fn xmp_description(start: &BytesStart, target: bool) -> Result<BytesStart<'static>> {
+    let mut description = BytesStart::owned_name(start.name().to_vec());
+    let mut namespaces = (false, false);
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        match attribute.key {
+            b"xmlns:dc" => namespaces.0 = true,
+            b"xmlns:xmp" => namespaces.1 = true,
+            key if XMP_PROPERTIES.contains(&key) => continue,
+            _ => (),
+        }
+        description.push_attribute(attribute);
+    }
+    if target && !namespaces.0 {
+        description.push_attribute(("xmlns:dc", DC_NAMESPACE));
+    }
+    if target && !namespaces.1 {
+        description.push_attribute(("xmlns:xmp", XMP_NAMESPACE));
+    }
+    Ok(description)
+}
+
+/// Set or remove EXIF fields of a JPEG, PNG or WebP file, keeping the others and the thumbnail.
+/// The file is replaced only once it is completely written.
+pub fn update_exif(path: &Path, changes: &[(Tag, Option<Value>)]) -> Result<()> {
+    let mut image = DynImage::from_bytes(std::fs::read(path)?.into())?
+        .context("Metadata can only be written to JPEG, PNG and WebP files")?;
+    let exif = match image.exif() {
+        Some(data) => Some(exif::Reader::new().read_raw(data.to_vec())?),
+        None => None,
+    };
+    let existing = || exif.iter().flat_map(|exif| exif.fields());
+    if let Some(field) = existing().find(|field| matches!(field.value, Value::Unknown(..))) {
+        bail!(
+            "The EXIF field {} of {} has a type that can't be written back",
+            field.tag,
+            path.display()
+        );
+    }
+    let mut fields = existing()
+        .filter(|field| {
+            field.ifd_num != In::PRIMARY || changes.iter().all(|(tag, _)| *tag != field.tag)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    fields.extend(changes.iter().filter_map(|(tag, value)| {
+        value.clone().map(|value| Field {
+            tag: *tag,
+            ifd_num: In::PRIMARY,
+            value,
+        })
+    }));
+
+    // the thumbnail image is referenced by offsets, which change, so it is passed on as data
+    let thumbnail_values = |tag: Tag| {
+        exif.as_ref()
+            .and_then(|exif| exif.get_field(tag, In::THUMBNAIL))
+            .and_then(|field| field.value.iter_uint())
+            .map(|values| values.map(|v| v as usize).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    let slice = |offset: usize, length: usize| {
+        exif.as_ref()?
+            .buf()
+            .get(offset..offset.checked_add(length)?)
+    };
+    let jpeg = match (
+        thumbnail_values(Tag::JPEGInterchangeFormat).first(),
+        thumbnail_values(Tag::JPEGInterchangeFormatLength).first(),
+    ) {
+        (Some(offset), Some(length)) => slice(*offset, *length),
+        _ => None,
+    };
+    let strips = thumbnail_values(Tag::StripOffsets)
+        .into_iter()
+        .zip(thumbnail_values(Tag::StripByteCounts))
+        .map(|(offset, length)| slice(offset, length))
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+
+    let data = if fields.iter().all(|field| field.ifd_num != In::PRIMARY) {
+        None
+    } else {
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        if let Some(jpeg) = jpeg {
+            writer.set_jpeg(jpeg, In::THUMBNAIL);
+        }
+        if !strips.is_empty() {
+            writer.set_strips(&strips, In::THUMBNAIL);
+        }
+        let little_endian = exif.as_ref().map(|exif| exif.little_endian());
+        let mut buffer = Cursor::new(vec![]);
+        writer
+            .write(&mut buffer, little_endian.unwrap_or_default())
+            .map_err(|e| anyhow!("Can't write EXIF: {e}"))?;
+        Some(Bytes::from(buffer.into_inner()))
+    };
+    image.set_exif(data);
+    let mut out = vec![];
+    image.encoder().write_to(&mut out)?;
+    // an interrupted write never leaves a truncated image behind
+    let name = path.file_name().context("No file name")?.to_string_lossy();
+    let part = path.with_file_name(format!(".{name}.part"));
+    std::fs::write(&part, out)?;
+    std::fs::set_permissions(&part, std::fs::metadata(path)?.permissions())?;
+    std::fs::rename(&part, path)?;
+    Ok(())
+}
+
+fn ascii_value(s: &str) -> Value {
+    Value::Ascii(vec![s.as_bytes().to_vec()])
+}
+
+/// The XP tags hold null terminated UCS-2 text
+fn encode_ucs2(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|c| c.to_le_bytes())
+        .collect()
+}
+
+fn decode_ucs2(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// Keywords separated by semicolons or commas
+pub fn split_keywords(s: &str) -> Vec<String> {
+    s.split(&[';', ','][..])
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .collect()
+}
+
+/// A date in EXIF format. Any separators are accepted, the time is optional.
+pub fn exif_date(s: &str) -> Result<String> {
+    let parts = s
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if parts.len() != 3 && parts.len() < 6 {
+        bail!("Dates look like 2024-12-31 or 2024-12-31 23:59:59");
+    }
+    let get = |i: usize| parts.get(i).cloned().unwrap_or_default();
+    let (year, month, day) = (get(0), get(1), get(2));
+    let (hour, minute, second) = (get(3), get(4), get(5));
+    if year < 1000 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        bail!("{s} is not a valid date");
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        bail!("{s} is not a valid time");
+    }
+    Ok(format!(
+        "{year:04}:{month:02}:{day:02} {hour:02}:{minute:02}:{second:02}"
+    ))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The escaped content of an element, or the value of an attribute of that name
+fn xmp_raw<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}>");
+    let close = format!("</{name}>");
+    if let Some(start) = xmp.find(&open) {
+        let rest = &xmp[start + open.len()..];
+        let end = rest.find(&close)?;
+        return Some(rest[..end].trim());
+    }
+    let attribute = format!("{name}=\"");
+    let start = xmp.find(&attribute)? + attribute.len();
+    let end = xmp[start..].find('"')?;
+    Some(&xmp[start..start + end])
+}
+
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    xmp_raw(xmp, name).map(unescape)
+}
+
+/// The list items of an element, or its plain value
+fn xmp_items(xmp: &str, name: &str) -> Vec<String> {
+    let content = match xmp_raw(xmp, name) {
+        Some(content) => content,
+        None => return vec![],
+    };
+    let items = if content.contains("<rdf:li") {
+        content
+            .split("<rdf:li")
+            .skip(1)
+            .filter_map(|item| {
+                let start = item.find('>')? + 1;
+                let end = item.find("</rdf:li>")?;
+                item.get(start..end)
+            })
+            .collect()
+    } else {
+        vec![content]
+    };
+    items
+        .into_iter()
+        .map(|item| unescape(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
@@ -0,0 +1,142 @@
+//! The minimap shows the whole image and the visible part of it while zoomed in. Dragging the
+//! visible part moves the view, clicking elsewhere on the minimap jumps there.
+
+use nalgebra::Vector2;
+
+use crate::appstate::{ImageGeometry, OculanteState};
+use crate::settings::MinimapCorner;
+
+/// Distance to the sides of the window
+const MARGIN: f32 = 10.;
+/// Distance to the top and bottom of the window, leaving room for the menu and message bars
+const BAR_MARGIN: f32 = 50.;
+
+fn rect_contains(top_left: Vector2<f32>, size: Vector2<f32>, point: Vector2<f32>) -> bool {
+    let bottom_right = top_left + size;
+    point.x >= top_left.x
+        && point.y >= top_left.y
+        && point.x <= bottom_right.x
+        && point.y <= bottom_right.y
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minimap {
+    /// Top left corner on screen
+    pub origin: Vector2<f32>,
+    /// Screen pixels per image pixel
+    pub scale: f32,
+    pub image_size: Vector2<f32>,
+}
+
+impl Minimap {
+    /// The minimap for a view, if the image does not fit the window. It takes up about a fifth
+    /// of the window.
+    pub fn new(
+        window: Vector2<f32>,
+        image_size: Vector2<f32>,
+        geometry: &ImageGeometry,
+        corner: MinimapCorner,
+    ) -> Option<Self> {
+        let scaled = image_size * geometry.scale;
+        if image_size.x <= 0.
+            || image_size.y <= 0.
+            || (scaled.x <= window.x && scaled.y <= window.y)
+        {
+            return None;
+        }
+        let bounds = Vector2::new(
+            (window.x * 0.2).clamp(80., 300.),
+            (window.y * 0.2).clamp(80., 300.),
+        );
+        let scale = (bounds.x / image_size.x).min(bounds.y / image_size.y);
+        let size = image_size * scale;
+        let x = match corner {
+            MinimapCorner::TopLeft | MinimapCorner::BottomLeft => MARGIN,
+            MinimapCorner::TopRight | MinimapCorner::BottomRight => window.x - MARGIN - size.x,
+        };
+        let y = match corner {
+            MinimapCorner::TopLeft | MinimapCorner::TopRight => BAR_MARGIN,
+            MinimapCorner::BottomLeft | MinimapCorner::BottomRight => {
+                window.y - BAR_MARGIN - size.y
+            }
+        };
+        Some(Self {
+            origin: Vector2::new(x, y),
+            scale,
+            image_size,
+        })
+    }
+
+    /// The minimap of the current view
+    pub fn of(state: &OculanteState) -> Option<Self> {
+        if !state.persistent_settings.show_minimap {
+            return None;
+        }
+        Self::new(
+            state.window_size,
+            Vector2::new(
+                state.image_dimension.0 as f32,
+                state.image_dimension.1 as f32,
+            ),
+            &state.image_geometry,
+            state.persistent_settings.minimap_corner,
+        )
+    }
+
+    pub fn size(&self) -> Vector2<f32> {
+        self.image_size * self.scale
+    }
+
+    /// Whether a point on screen is on the minimap
+    pub fn contains(&self, point: Vector2<f32>) -> bool {
+        rect_contains(self.origin, self.size(), point)
+    }
+
+    /// The visible part of the image on the minimap, as top left corner and size
+    pub fn viewport(
+        &self,
+        window: Vector2<f32>,
+        geometry: &ImageGeometry,
+    ) -> (Vector2<f32>, Vector2<f32>) {
+        let clamp = |p: Vector2<f32>| {
+            Vector2::new(
+                p.x.clamp(0., self.image_size.x),
+                p.y.clamp(0., self.image_size.y),
+            )
+        };
+        let top_left = clamp(-geometry.offset / geometry.scale);
+        let bottom_right = clamp((window - geometry.offset) / geometry.scale);
+        (
+            self.origin + top_left * self.scale,
+            (bottom_right - top_left) * self.scale,
+        )
+    }
+
+    /// Whether a point on screen is within the visible part on the minimap
+    pub fn viewport_contains(
+        &self,
+        point: Vector2<f32>,
+        window: Vector2<f32>,
+        geometry: &ImageGeometry,
+    ) -> bool {
+        let (top_left, size) = self.viewport(window, geometry);
+        rect_contains(top_left, size, point)
+    }
+
+    /// The image offset that centers the window on the part of the image under a point on the
+    /// minimap
+    pub fn center_on(
+        &self,
+        point: Vector2<f32>,
+        window: Vector2<f32>,
+        geometry: &ImageGeometry,
+    ) -> Vector2<f32> {
+        let image_point = (point - self.origin) / self.scale;
+        window / 2. - image_point * geometry.scale
+    }
+
+    /// How much the image offset changes when the visible part is dragged by `delta`
+    pub fn pan(&self, delta: Vector2<f32>, geometry: &ImageGeometry) -> Vector2<f32> {
+        -delta / self.scale * geometry.scale
+    }
+}
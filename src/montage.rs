@@ -0,0 +1,79 @@
+//! Montages: images placed side by side or stacked into one image, like before and after
+//! composites.
+
+use anyhow::{bail, Result};
+use image::RgbaImage;
+use std::path::PathBuf;
+use strum::Display;
+use strum_macros::EnumIter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumIter)]
+pub enum Direction {
+    #[strum(serialize = "Side by side")]
+    Horizontal,
+    #[strum(serialize = "Stacked")]
+    Vertical,
+}
+
+/// The images in a row or column, `gap` pixels apart and centered across it, on `background`
+pub fn stitch(
+    images: &[RgbaImage],
+    direction: Direction,
+    gap: u32,
+    background: [u8; 4],
+) -> Result<RgbaImage> {
+    if images.is_empty() {
+        bail!("No images to stitch");
+    }
+    let gaps = gap * (images.len() as u32 - 1);
+    let (width, height) = match direction {
+        Direction::Horizontal => (
+            images.iter().map(|i| i.width()).sum::<u32>() + gaps,
+            images.iter().map(|i| i.height()).max().unwrap_or_default(),
+        ),
+        Direction::Vertical => (
+            images.iter().map(|i| i.width()).max().unwrap_or_default(),
+            images.iter().map(|i| i.height()).sum::<u32>() + gaps,
+        ),
+    };
+    let mut montage = RgbaImage::from_pixel(width, height, image::Rgba(background));
+    let mut offset = 0;
+    for img in images {
+        let (x, y) = match direction {
+            Direction::Horizontal => (offset, (height - img.height()) / 2),
+            Direction::Vertical => ((width - img.width()) / 2, offset),
+        };
+        image::imageops::overlay(&mut montage, img, x as i64, y as i64);
+        offset += gap
+            + match direction {
+                Direction::Horizontal => img.width(),
+                Direction::Vertical => img.height(),
+            };
+    }
+    Ok(montage)
+}
+
+/// State of the montage window
+#[derive(Debug)]
+pub struct Montage {
+    pub enabled: bool,
+    /// Images in the order they are placed
+    pub sources: Vec<PathBuf>,
+    pub direction: Direction,
+    /// Pixels between the images
+    pub gap: u32,
+    /// Color of gaps and of the space next to smaller images
+    pub background: [u8; 4],
+}
+
+impl Default for Montage {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sources: vec![],
+            direction: Direction::Horizontal,
+            gap: 0,
+            background: [255, 255, 255, 255],
+        }
+    }
+}
@@ -1,5 +1,6 @@
+use crate::redraw::Waker;
 use crate::utils::Frame;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use log::{error, info};
 use std::convert::TryInto;
 use std::io::Read;
@@ -7,7 +8,93 @@ use std::net::{Shutdown, TcpListener, TcpStream};
 use std::sync::mpsc::Sender;
 use std::thread;
 
-fn handle_client(mut stream: TcpStream, texture_sender: Sender<Frame>) -> Result<()> {
+/// The first line of a header of `key: value` lines before an image, which ends with an empty
+/// line. Images without it are shown without metadata.
+pub const HEADER_MAGIC: &[u8] = b"OCULANTE";
+/// Longer headers are not waited for
+const MAX_HEADER: usize = 64 * 1024;
+
+/// Labels sent along with an image, e.g. by a render pipeline
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetMetadata {
+    pub name: Option<String>,
+    pub frame: Option<u64>,
+    /// Other fields, in the order they were sent
+    pub fields: Vec<(String, String)>,
+}
+
+impl NetMetadata {
+    /// Read `key: value` lines. The keys `name` and `frame` are known, others are kept as they are.
+    pub fn parse(header: &str) -> Self {
+        let mut metadata = Self::default();
+        for line in header.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            match key.to_lowercase().as_str() {
+                "name" => metadata.name = Some(value.to_string()),
+                "frame" if value.parse::<u64>().is_ok() => metadata.frame = value.parse().ok(),
+                _ => metadata.fields.push((key.to_string(), value.to_string())),
+            }
+        }
+        metadata
+    }
+
+    /// The header to send before an image
+    pub fn encode(&self) -> Vec<u8> {
+        let mut header = format!("{}\n", String::from_utf8_lossy(HEADER_MAGIC));
+        if let Some(name) = &self.name {
+            header += &format!("name: {name}\n");
+        }
+        if let Some(frame) = self.frame {
+            header += &format!("frame: {frame}\n");
+        }
+        for (key, value) in &self.fields {
+            header += &format!("{key}: {value}\n");
+        }
+        header += "\n";
+        header.into_bytes()
+    }
+}
+
+/// The metadata and image data of a received message, or `None` while the header is incomplete
+pub fn split_header(buf: &[u8]) -> Result<Option<(Option<NetMetadata>, &[u8])>> {
+    if !buf.starts_with(HEADER_MAGIC) {
+        if HEADER_MAGIC.starts_with(buf) {
+            return Ok(None);
+        }
+        return Ok(Some((None, buf)));
+    }
+    // the header ends with an empty line
+    let head = &buf[..buf.len().min(MAX_HEADER)];
+    let end = (0..head.len()).find_map(|i| {
+        if head[i..].starts_with(b"\n\n") {
+            Some((i, 2))
+        } else if head[i..].starts_with(b"\n\r\n") {
+            Some((i, 3))
+        } else {
+            None
+        }
+    });
+    match end {
+        Some((pos, len)) => {
+            let header = String::from_utf8_lossy(&buf[..pos]);
+            // skip the magic line
+            let fields = header.split_once('\n').map(|(_, f)| f).unwrap_or_default();
+            Ok(Some((Some(NetMetadata::parse(fields)), &buf[pos + len..])))
+        }
+        None if buf.len() >= MAX_HEADER => bail!("The header is longer than {MAX_HEADER} bytes"),
+        None => Ok(None),
+    }
+}
+
+fn handle_client(
+    mut stream: TcpStream,
+    texture_sender: Sender<Frame>,
+    metadata_sender: Sender<Option<NetMetadata>>,
+    waker: Waker,
+) -> Result<()> {
     let mut data = [0 as u8; 100000]; // using 50 byte buffer
     let mut imgbuf: Vec<u8> = vec![];
     while match stream.read(&mut data) {
@@ -15,15 +102,20 @@ fn handle_client(mut stream: TcpStream, texture_sender: Sender<Frame>) -> Result
             let x: Vec<u8> = data[0..size].try_into()?;
             imgbuf.extend(x);
 
-            match image::load_from_memory(imgbuf.as_ref()) {
-                Ok(i) => {
-                    // println!("got image");
-                    imgbuf.clear();
-                    let _ = texture_sender.send(Frame::new_still(i.to_rgba8()));
-                    std::thread::sleep(std::time::Duration::from_millis(30));
-                    false
-                }
-                Err(_) => true,
+            match split_header(&imgbuf)? {
+                // wait for the rest of the header
+                None => true,
+                Some((metadata, image_data)) => match image::load_from_memory(image_data) {
+                    Ok(i) => {
+                        imgbuf.clear();
+                        let _ = metadata_sender.send(metadata);
+                        let _ = texture_sender.send(Frame::new_still(i.to_rgba8()));
+                        waker.wake();
+                        std::thread::sleep(std::time::Duration::from_millis(30));
+                        false
+                    }
+                    Err(_) => true,
+                },
             }
         }
         Err(e) => {
@@ -38,7 +130,12 @@ fn handle_client(mut stream: TcpStream, texture_sender: Sender<Frame>) -> Result
     Ok(())
 }
 
-pub fn recv(port: i32, texture_sender: Sender<Frame>) {
+pub fn recv(
+    port: i32,
+    texture_sender: Sender<Frame>,
+    metadata_sender: Sender<Option<NetMetadata>>,
+    waker: Waker,
+) {
     thread::spawn(move || {
         // FIXME remove unwrap
         let listener = TcpListener::bind(format!("0.0.0.0:{port}")).unwrap();
@@ -49,9 +146,11 @@ pub fn recv(port: i32, texture_sender: Sender<Frame>) {
             match stream {
                 Ok(stream) => {
                     let t_s = texture_sender.clone();
+                    let m_s = metadata_sender.clone();
+                    let waker = waker.clone();
                     thread::spawn(move || {
                         // connection succeeded
-                        _ = handle_client(stream, t_s)
+                        _ = handle_client(stream, t_s, m_s, waker)
                     });
                 }
                 Err(e) => {
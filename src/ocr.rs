@@ -0,0 +1,58 @@
+//! Text recognition with the tesseract command line tool, which needs to be installed with the
+//! data of the languages to recognize.
+
+use anyhow::{anyhow, bail, Result};
+use image::{ImageOutputFormat, RgbaImage};
+use std::io::{Cursor, ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// The text in `img`, in the reading order tesseract finds
+pub fn recognize(img: &RgbaImage) -> Result<String> {
+    let mut png = Cursor::new(vec![]);
+    img.write_to(&mut png, ImageOutputFormat::Png)?;
+    let mut child = Command::new("tesseract")
+        .args(["stdin", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => anyhow!("tesseract is not installed"),
+            _ => anyhow!("Can't run tesseract: {e}"),
+        })?;
+    // tesseract reads the whole image before writing anything
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(png.get_ref())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(clean(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Remove trailing whitespace, the form feed tesseract ends pages with and runs of empty lines
+pub fn clean(text: &str) -> String {
+    let mut lines: Vec<&str> = vec![];
+    for line in text.lines().map(str::trim_end) {
+        if !(line.is_empty() && lines.last().map(|l| l.is_empty()).unwrap_or(true)) {
+            lines.push(line);
+        }
+    }
+    while lines.last().map(|l| l.is_empty()) == Some(true) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// State of the recognized text window
+#[derive(Debug, Default)]
+pub struct TextWindow {
+    pub enabled: bool,
+    /// Text of the last recognition, or why it failed
+    pub text: Option<Result<String, String>>,
+    pub running: bool,
+}
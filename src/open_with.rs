@@ -0,0 +1,236 @@
+//! Register oculante as an "Open With" handler for images with the OS, and remove it again.
+//!
+//! Windows gets per-user registry entries, written with `winreg`, Linux a desktop entry with added associations in
+//! `mimeapps.list`, and macOS registers the app bundle with LaunchServices.
+
+// the desktop entry helpers are only used on Linux, but tested everywhere
+#![cfg_attr(any(target_os = "windows", target_os = "macos"), allow(dead_code))]
+
+#[cfg(not(target_os = "windows"))]
+use anyhow::bail;
+use anyhow::Result;
+use std::path::Path;
+
+const DESKTOP_ENTRY: &str = include_str!("../res/oculante.desktop");
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const DESKTOP_FILE: &str = "oculante.desktop";
+const ADDED: &str = "[Added Associations]";
+
+/// The desktop entry shipped with oculante, launching `exe`
+pub fn desktop_entry(exe: &Path) -> String {
+    DESKTOP_ENTRY
+        .lines()
+        .map(|line| {
+            if line.starts_with("Exec=") {
+                format!("Exec=\"{}\" %U", exe.display())
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// The mime types of the desktop entry
+pub fn mime_types() -> Vec<&'static str> {
+    DESKTOP_ENTRY
+        .lines()
+        .find_map(|line| line.strip_prefix("MimeType="))
+        .unwrap_or_default()
+        .split(';')
+        .filter(|m| !m.is_empty())
+        .collect()
+}
+
+/// Remove `desktop_file` from the associations of a `mimeapps.list`
+pub fn mimeapps_remove(content: &str, desktop_file: &str) -> String {
+    let mut lines = vec![];
+    for line in content.lines() {
+        match line.split_once('=') {
+            Some((mime, apps)) if apps.split(';').any(|app| app == desktop_file) => {
+                let apps = apps
+                    .split(';')
+                    .filter(|app| !app.is_empty() && *app != desktop_file)
+                    .map(|app| format!("{app};"))
+                    .collect::<String>();
+                if !apps.is_empty() {
+                    lines.push(format!("{mime}={apps}"));
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Add `desktop_file` to the added associations of `mimes` in a `mimeapps.list`
+pub fn mimeapps_add(content: &str, desktop_file: &str, mimes: &[&str]) -> String {
+    let content = mimeapps_remove(content, desktop_file);
+    let mut lines = content.lines().map(String::from).collect::<Vec<_>>();
+    let section = match lines.iter().position(|l| l.trim() == ADDED) {
+        Some(section) => section,
+        None => {
+            if lines.last().map(|l| !l.is_empty()).unwrap_or_default() {
+                lines.push(String::new());
+            }
+            lines.push(ADDED.into());
+            lines.len() - 1
+        }
+    };
+    let end = lines[section + 1..]
+        .iter()
+        .position(|l| l.starts_with('['))
+        .map(|i| section + 1 + i)
+        .unwrap_or(lines.len());
+    let mut added = vec![];
+    for mime in mimes {
+        let prefix = format!("{mime}=");
+        match lines[section + 1..end]
+            .iter_mut()
+            .find(|l| l.starts_with(&prefix))
+        {
+            Some(line) => {
+                // the list may lack the trailing separator
+                if !line.ends_with(';') {
+                    line.push(';');
+                }
+                line.push_str(&format!("{desktop_file};"));
+            }
+            None => added.push(format!("{mime}={desktop_file};")),
+        }
+    }
+    // keep a blank line before the next section
+    let at = if end > section + 1 && lines[end - 1].is_empty() {
+        end - 1
+    } else {
+        end
+    };
+    lines.splice(at..at, added);
+    lines.join("\n") + "\n"
+}
+
+/// Register the running executable as a handler for supported images
+pub fn register() -> Result<()> {
+    let exe = std::env::current_exe()?;
+    #[cfg(target_os = "windows")]
+    {
+        let classes = classes()?;
+        let (key, _) = classes.create_subkey(PROG_ID)?;
+        key.set_value("", &"Oculante image")?;
+        let (command, _) = key.create_subkey(r"shell\open\command")?;
+        command.set_value("", &format!("\"{}\" \"%1\"", exe.display()))?;
+        for ext in crate::utils::SUPPORTED_EXTENSIONS {
+            let (progids, _) = classes.create_subkey(format!(r".{ext}\OpenWithProgids"))?;
+            progids.set_raw_value(
+                PROG_ID,
+                &winreg::RegValue {
+                    vtype: winreg::enums::REG_NONE,
+                    bytes: vec![],
+                },
+            )?;
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        lsregister(&exe, "-f")
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let applications = data_dir()?.join("applications");
+        std::fs::create_dir_all(&applications)?;
+        std::fs::write(applications.join(DESKTOP_FILE), desktop_entry(&exe))?;
+        update_mimeapps(|content| mimeapps_add(content, DESKTOP_FILE, &mime_types()))
+    }
+}
+
+/// Remove everything `register` added
+pub fn unregister() -> Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        let classes = classes()?;
+        for ext in crate::utils::SUPPORTED_EXTENSIONS {
+            // extensions that were never registered have no value to delete
+            if let Ok(progids) = classes.open_subkey_with_flags(
+                format!(r".{ext}\OpenWithProgids"),
+                winreg::enums::KEY_SET_VALUE,
+            ) {
+                _ = progids.delete_value(PROG_ID);
+            }
+        }
+        Ok(classes.delete_subkey_all(PROG_ID)?)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        lsregister(&std::env::current_exe()?, "-u")
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let desktop_file = data_dir()?.join("applications").join(DESKTOP_FILE);
+        if desktop_file.exists() {
+            std::fs::remove_file(desktop_file)?;
+        }
+        update_mimeapps(|content| mimeapps_remove(content, DESKTOP_FILE))
+    }
+}
+
+#[cfg(target_os = "windows")]
+const PROG_ID: &str = "Oculante.Image";
+
+/// The file classes of the current user
+#[cfg(target_os = "windows")]
+fn classes() -> Result<winreg::RegKey> {
+    Ok(winreg::RegKey::predef(winreg::enums::HKEY_CURRENT_USER)
+        .open_subkey_with_flags(r"Software\Classes", winreg::enums::KEY_ALL_ACCESS)?)
+}
+
+#[cfg(target_os = "macos")]
+fn lsregister(exe: &Path, flag: &str) -> Result<()> {
+    // the executable is in Oculante.app/Contents/MacOS
+    let bundle = exe
+        .ancestors()
+        .find(|p| p.extension().map(|e| e == "app").unwrap_or_default());
+    let bundle = match bundle {
+        Some(bundle) => bundle,
+        None => bail!("Oculante is not running from an app bundle"),
+    };
+    let status = std::process::Command::new(
+        "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister",
+    )
+    .arg(flag)
+    .arg(bundle)
+    .status()?;
+    if !status.success() {
+        bail!("lsregister failed with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn data_dir() -> Result<std::path::PathBuf> {
+    match dirs::data_dir() {
+        Some(dir) => Ok(dir),
+        None => bail!("Can't get data dir"),
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn update_mimeapps(update: impl Fn(&str) -> String) -> Result<()> {
+    let config = match dirs::config_dir() {
+        Some(dir) => dir,
+        None => bail!("Can't get config dir"),
+    };
+    let path = config.join("mimeapps.list");
+    let content = std::fs::read_to_string(&path).unwrap_or_default();
+    std::fs::create_dir_all(&config)?;
+    std::fs::write(&path, update(&content))?;
+    // not all desktops need the cache, so a missing tool is fine
+    _ = std::process::Command::new("update-desktop-database")
+        .arg(data_dir()?.join("applications"))
+        .status();
+    Ok(())
+}
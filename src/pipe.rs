@@ -0,0 +1,286 @@
+//! Float frames pushed through a named pipe, e.g. by plugins of render or paint apps, at
+//! interactive rates without the overhead of the network. A frame is a header of `FRAME_MAGIC`
+//! followed by width, height and channel count as little endian `u32`, then the pixels as
+//! interleaved little endian `f32` in linear light. One to four channels are read as gray,
+//! gray and alpha, RGB or RGBA.
+
+use crate::mjpeg::StreamStatus;
+use crate::redraw::Waker;
+use crate::utils::{Frame, FrameSource};
+use anyhow::{bail, Result};
+use image::RgbaImage;
+use log::debug;
+#[cfg(not(target_os = "windows"))]
+use nix::poll::{poll, PollFd, PollFlags};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+type Pipe = interprocess::os::windows::named_pipe::ByteReaderPipeStream;
+#[cfg(not(target_os = "windows"))]
+type Pipe = std::fs::File;
+
+pub const FRAME_MAGIC: &[u8; 4] = b"OCUF";
+/// Frames with more pixel data are refused
+const MAX_FRAME: u64 = 2 << 30;
+/// How long to wait before opening the pipe again when it is missing
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How long reading waits for data before it checks whether to stop
+#[cfg(not(target_os = "windows"))]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long reading sleeps when there is no data. Named pipes can't be polled.
+#[cfg(target_os = "windows")]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameHeader {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+}
+
+impl FrameHeader {
+    pub const SIZE: usize = 16;
+
+    pub fn parse(data: &[u8; Self::SIZE]) -> Result<Self> {
+        if &data[..4] != FRAME_MAGIC {
+            bail!("The frame doesn't start with {:?}", FRAME_MAGIC);
+        }
+        let field = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let header = Self {
+            width: field(4),
+            height: field(8),
+            channels: field(12),
+        };
+        if header.width == 0 || header.height == 0 {
+            bail!("The frame is empty");
+        }
+        if !(1..=4).contains(&header.channels) {
+            bail!("Frames need 1 to 4 channels, not {}", header.channels);
+        }
+        if header.data_len() > MAX_FRAME {
+            bail!(
+                "The frame of {}x{} is too large",
+                header.width,
+                header.height
+            );
+        }
+        Ok(header)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = FRAME_MAGIC.to_vec();
+        for field in [self.width, self.height, self.channels] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data
+    }
+
+    /// The bytes of pixel data that follow the header
+    pub fn data_len(&self) -> u64 {
+        self.width as u64 * self.height as u64 * self.channels as u64 * 4
+    }
+}
+
+/// Convert linear float pixels to a displayable image. Colors are gamma encoded, alpha is not.
+pub fn to_rgba(header: &FrameHeader, data: &[u8]) -> Option<RgbaImage> {
+    if data.len() as u64 != header.data_len() {
+        return None;
+    }
+    let color = |v: f32| (v.max(0.0).powf(1.0 / 2.2).min(1.0) * 255.0) as u8;
+    let alpha = |v: f32| (v.clamp(0.0, 1.0) * 255.0) as u8;
+    let channels = header.channels as usize;
+    let mut img = RgbaImage::new(header.width, header.height);
+    for (out, px) in img.chunks_exact_mut(4).zip(data.chunks_exact(4 * channels)) {
+        let v =
+            |c: usize| f32::from_le_bytes([px[c * 4], px[c * 4 + 1], px[c * 4 + 2], px[c * 4 + 3]]);
+        let rgba = match channels {
+            1 => [color(v(0)), color(v(0)), color(v(0)), 255],
+            2 => [color(v(0)), color(v(0)), color(v(0)), alpha(v(1))],
+            3 => [color(v(0)), color(v(1)), color(v(2)), 255],
+            _ => [color(v(0)), color(v(1)), color(v(2)), alpha(v(3))],
+        };
+        out.copy_from_slice(&rgba);
+    }
+    Some(img)
+}
+
+/// Read the next frame. Returns `None` when the writer closed the pipe between frames.
+pub fn read_frame(reader: &mut impl Read) -> Result<Option<RgbaImage>> {
+    let mut header = [0; FrameHeader::SIZE];
+    match reader.read_exact(&mut header) {
+        Ok(_) => (),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let header = FrameHeader::parse(&header)?;
+    let mut data = vec![0; header.data_len() as usize];
+    reader.read_exact(&mut data)?;
+    match to_rgba(&header, &data) {
+        Some(img) => Ok(Some(img)),
+        None => bail!("The frame has the wrong size"),
+    }
+}
+
+/// The pipe for `address`, like `pipe://oculante`. A bare name is a pipe in the temp folder, or
+/// `\\.\pipe\name` on Windows.
+pub fn pipe_path(address: &str) -> PathBuf {
+    let name = address.trim_start_matches("pipe://");
+    if name.contains('/') || name.contains('\\') {
+        return PathBuf::from(name);
+    }
+    #[cfg(target_os = "windows")]
+    return PathBuf::from(format!(r"\\.\pipe\{name}"));
+    #[cfg(not(target_os = "windows"))]
+    return std::env::temp_dir().join(name);
+}
+
+/// Open the pipe for reading without waiting for a writer. The writing app creates the pipe and
+/// oculante connects to it.
+#[cfg(target_os = "windows")]
+fn open(path: &Path) -> Result<Pipe> {
+    let path = path.to_string_lossy();
+    let pipe = Pipe::connect(path.strip_prefix(r"\\.\pipe\").unwrap_or(&path))?;
+    pipe.set_nonblocking(true)?;
+    Ok(pipe)
+}
+
+/// Open the pipe for reading without waiting for a writer, and create it if it is missing
+#[cfg(not(target_os = "windows"))]
+fn open(path: &Path) -> Result<Pipe> {
+    use nix::fcntl::OFlag;
+    use nix::sys::stat::Mode;
+    use std::os::unix::fs::OpenOptionsExt;
+    if !path.exists() {
+        nix::unistd::mkfifo(path, Mode::S_IRUSR | Mode::S_IWUSR)?;
+    }
+    Ok(std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(path)?)
+}
+
+/// Reads a pipe opened by `open`, waiting for data in steps of `POLL_INTERVAL`, so reading ends
+/// with an error soon after the flag is set
+struct PipeReader<'a> {
+    pipe: Pipe,
+    stop: &'a AtomicBool,
+}
+
+impl Read for PipeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return Err(std::io::Error::other("Stopped"));
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                use std::os::unix::io::AsRawFd;
+                // data, or the writer closing its end
+                let mut fds = [PollFd::new(self.pipe.as_raw_fd(), PollFlags::POLLIN)];
+                if poll(&mut fds, POLL_INTERVAL.as_millis() as i32)? == 0 {
+                    continue;
+                }
+            }
+            match self.pipe.read(buf) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    #[cfg(target_os = "windows")]
+                    thread::sleep(POLL_INTERVAL);
+                }
+                // a named pipe reports a closed writer as an error
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(0),
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Send frames until the flag is set, opening the pipe again whenever a writer is done
+fn receive(
+    path: PathBuf,
+    sender: Sender<Frame>,
+    status: Sender<StreamStatus>,
+    stop: Arc<AtomicBool>,
+    waker: Waker,
+) {
+    let mut arrivals: VecDeque<Instant> = VecDeque::new();
+    let mut reported = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        _ = status.send(StreamStatus::Connecting);
+        let mut pipe = match open(&path) {
+            Ok(pipe) => PipeReader { pipe, stop: &stop },
+            Err(e) => {
+                _ = status.send(StreamStatus::Reconnecting(e.to_string()));
+                waker.wake();
+                thread::sleep(RETRY_DELAY);
+                continue;
+            }
+        };
+        debug!("Reading frames from {}", path.display());
+        let mut frames = 0;
+        while !stop.load(Ordering::Relaxed) {
+            let img = match read_frame(&mut pipe) {
+                Ok(Some(img)) => img,
+                Ok(None) => {
+                    // some systems report a pipe without a writer as closed right away
+                    if frames == 0 {
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                    break;
+                }
+                Err(_) if stop.load(Ordering::Relaxed) => break,
+                Err(e) => {
+                    // the rest of the data can't be told apart from pixels
+                    _ = status.send(StreamStatus::Reconnecting(e.to_string()));
+                    waker.wake();
+                    thread::sleep(RETRY_DELAY);
+                    break;
+                }
+            };
+            if stop.load(Ordering::Relaxed)
+                || sender
+                    .send(Frame::new(img, 0, FrameSource::Stream))
+                    .is_err()
+            {
+                return;
+            }
+            waker.wake();
+            frames += 1;
+            arrivals.push_back(Instant::now());
+            while arrivals
+                .front()
+                .map(|t| t.elapsed() > Duration::from_secs(1))
+                .unwrap_or_default()
+            {
+                arrivals.pop_front();
+            }
+            if reported.elapsed() > Duration::from_millis(500) {
+                reported = Instant::now();
+                _ = status.send(StreamStatus::Playing(arrivals.len() as f32));
+            }
+        }
+    }
+}
+
+/// Show the frames written to the pipe at `address` until the returned flag is set. `waker` wakes
+/// the app for each frame and when reading fails.
+pub fn play(
+    address: String,
+    sender: Sender<Frame>,
+    status: Sender<StreamStatus>,
+    waker: Waker,
+) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || {
+        receive(pipe_path(&address), sender, status, stopped, waker);
+        debug!("Stopped reading {address}");
+    });
+    stop
+}
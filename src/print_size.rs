@@ -0,0 +1,90 @@
+//! Showing images at their physical size: the resolution stored in the file, and the zoom that
+//! makes an inch of the image an inch on the screen.
+
+use std::convert::TryInto;
+use std::io::Read;
+use std::path::Path;
+
+/// Physical pixels per inch that window systems assume for a scale factor of 1
+pub const DEFAULT_PPI: f32 = 96.;
+
+/// The horizontal resolution of an image file in pixels per inch, from its EXIF data, JFIF header
+/// or PNG pHYs chunk
+pub fn read_dpi(path: &Path) -> Option<f32> {
+    exif_dpi(path)
+        .or_else(|| {
+            let mut head = vec![];
+            std::fs::File::open(path)
+                .ok()?
+                .take(1 << 16)
+                .read_to_end(&mut head)
+                .ok()?;
+            jfif_dpi(&head).or_else(|| png_dpi(&head))
+        })
+        .filter(|dpi| dpi.is_finite() && *dpi > 1.)
+}
+
+fn exif_dpi(path: &Path) -> Option<f32> {
+    let file = std::fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()?;
+    let resolution = match &exif
+        .get_field(exif::Tag::XResolution, exif::In::PRIMARY)?
+        .value
+    {
+        exif::Value::Rational(values) => values.first()?.to_f64() as f32,
+        _ => return None,
+    };
+    let unit = exif
+        .get_field(exif::Tag::ResolutionUnit, exif::In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+        .unwrap_or(2);
+    match unit {
+        2 => Some(resolution),
+        3 => Some(resolution * 2.54),
+        _ => None,
+    }
+}
+
+/// The density of the APP0 segment that starts a JFIF file
+pub fn jfif_dpi(head: &[u8]) -> Option<f32> {
+    if head.get(..4)? != [0xFF, 0xD8, 0xFF, 0xE0] || head.get(6..11)? != b"JFIF\0" {
+        return None;
+    }
+    let density = u16::from_be_bytes([*head.get(14)?, *head.get(15)?]) as f32;
+    match head.get(13)? {
+        1 => Some(density),
+        2 => Some(density * 2.54),
+        // only the aspect ratio
+        _ => None,
+    }
+}
+
+/// The pixels per meter of the pHYs chunk before the image data of a PNG file
+pub fn png_dpi(head: &[u8]) -> Option<f32> {
+    if head.get(..8)? != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let mut pos = 8;
+    loop {
+        let length = u32::from_be_bytes(head.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let data = pos + 8;
+        match head.get(pos + 4..pos + 8)? {
+            b"pHYs" => {
+                let pixels_per_unit =
+                    u32::from_be_bytes(head.get(data..data + 4)?.try_into().ok()?) as f32;
+                // unit 1 is the meter, 0 only gives the aspect ratio
+                return (*head.get(data + 8)? == 1).then_some(pixels_per_unit * 0.0254);
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => pos = data + length + 4,
+        }
+    }
+}
+
+/// The zoom that shows an image of `dpi` at its physical size, on a screen with `screen_ppi`
+/// physical pixels per inch and a window that scales logical pixels by `scale_factor`
+pub fn print_scale(dpi: f32, screen_ppi: f32, scale_factor: f32) -> f32 {
+    screen_ppi / scale_factor.max(0.01) / dpi
+}
@@ -0,0 +1,54 @@
+//! Pixel values along a line dragged across the image, to inspect gradients, scanlines and
+//! test charts.
+
+use image::RgbaImage;
+use nalgebra::Vector2;
+
+/// A pixel on the line, with its position in the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileSample {
+    pub x: u32,
+    pub y: u32,
+    pub value: [u8; 4],
+}
+
+/// The pixels under the line from `start` to `end`, in image coordinates, one per pixel of length
+pub fn sample_line(img: &RgbaImage, start: Vector2<f32>, end: Vector2<f32>) -> Vec<ProfileSample> {
+    if img.width() == 0 || img.height() == 0 {
+        return vec![];
+    }
+    let steps = (end - start).norm().ceil().max(1.) as usize;
+    (0..=steps)
+        .map(|i| {
+            let p = start + (end - start) * (i as f32 / steps as f32);
+            let x = (p.x.max(0.) as u32).min(img.width() - 1);
+            let y = (p.y.max(0.) as u32).min(img.height() - 1);
+            ProfileSample {
+                x,
+                y,
+                value: img.get_pixel(x, y).0,
+            }
+        })
+        .collect()
+}
+
+/// The samples as comma separated values, with a header
+pub fn to_csv(samples: &[ProfileSample]) -> String {
+    let mut csv = "index,x,y,r,g,b,a\n".to_string();
+    for (i, s) in samples.iter().enumerate() {
+        let [r, g, b, a] = s.value;
+        csv.push_str(&format!("{i},{},{},{r},{g},{b},{a}\n", s.x, s.y));
+    }
+    csv
+}
+
+/// State of the line profile tool
+#[derive(Debug, Default)]
+pub struct LineProfile {
+    /// Dragging on the image draws a line instead of panning
+    pub enabled: bool,
+    /// Start and end of the line in image coordinates
+    pub line: Option<(Vector2<f32>, Vector2<f32>)>,
+    /// The end of the line follows the mouse
+    pub dragging: bool,
+}
@@ -0,0 +1,194 @@
+//! Invalidation for the lazy event loop.
+//!
+//! Frames are only drawn when something asks for one. Code that changes what is on screen calls
+//! `Redraw::invalidate`, which draws the next frame right away. Listeners that wait for rare events
+//! (the network, later launches, streams, the clipboard, the hot folder) wake the event loop from
+//! their thread with a `Waker` when something arrives, and playback schedules a wakeup for its next
+//! frame with `Redraw::wake_at`. Work the app waits for to finish (loading, analysis) is registered
+//! with `Redraw::poll` instead: while it runs, every frame requests the next one and the display
+//! paces them, so input is never held up. With nothing invalidated and nothing polled, the app
+//! idles.
+
+use notan::backend::EventLoopWaker;
+use std::collections::HashSet;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// Keyboard, mouse or window events
+    Input,
+    /// A new image or texture arrived
+    Image,
+    /// egui asked for a repaint
+    Ui,
+    /// Waiting for an image to load
+    Loading,
+    /// Waiting for extended image info
+    Info,
+    /// A file dialog is open
+    Dialog,
+    /// The folder of the current image is listed
+    Folder,
+    /// Looking for an update
+    Update,
+    /// The log viewer shows new messages
+    Log,
+    /// Images of the folder are analyzed, e.g. scored for sharpness
+    Analysis,
+    /// A screenshot is taken while the window is hidden
+    Screenshot,
+}
+
+/// Wakes the event loop from other threads, which draws a frame. Does nothing without an event
+/// loop, e.g. in tests.
+#[derive(Debug, Clone, Default)]
+pub struct Waker(Option<Arc<Mutex<EventLoopWaker>>>);
+
+impl Waker {
+    pub fn wake(&self) {
+        if let Some(waker) = &self.0 {
+            waker.lock().unwrap_or_else(|e| e.into_inner()).wake();
+        }
+    }
+}
+
+/// How often a hidden window checks its background work. It presents nothing the display could
+/// pace and gets no input, so waiting between its frames stalls nothing.
+const HIDDEN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub struct Redraw {
+    /// Why the next frame is drawn
+    reasons: HashSet<Reason>,
+    /// The previous frame asked for the next one to be drawn right away
+    urgent: bool,
+    /// Background work that is checked for results
+    polling: HashSet<Reason>,
+    /// Polled as long as the other copy of the token is alive
+    tokens: Vec<(Reason, Arc<()>)>,
+    last_frame: Instant,
+    waker: Waker,
+    /// Wakes the event loop at the instants sent to it
+    timer: Option<Sender<Instant>>,
+}
+
+impl Default for Redraw {
+    fn default() -> Self {
+        Self {
+            reasons: Default::default(),
+            urgent: Default::default(),
+            polling: Default::default(),
+            tokens: Default::default(),
+            last_frame: Instant::now(),
+            waker: Default::default(),
+            timer: Default::default(),
+        }
+    }
+}
+
+impl Redraw {
+    /// Draw the next frame as soon as possible
+    pub fn invalidate(&mut self, reason: Reason) {
+        self.reasons.insert(reason);
+    }
+
+    /// Start or stop checking background work
+    pub fn poll(&mut self, reason: Reason, active: bool) {
+        if active {
+            self.polling.insert(reason);
+        } else {
+            self.polling.remove(&reason);
+        }
+    }
+
+    /// Poll until the returned token is dropped, e.g. by the thread doing the work
+    pub fn poll_while_alive(&mut self, reason: Reason) -> Arc<()> {
+        let token = Arc::new(());
+        self.tokens.push((reason, token.clone()));
+        token
+    }
+
+    /// Let background threads wake the event loop. Until this is called, wakers do nothing.
+    pub fn set_waker(&mut self, waker: EventLoopWaker) {
+        self.waker = Waker(Some(Arc::new(Mutex::new(waker))));
+        self.timer = None;
+    }
+
+    /// A waker for a thread that waits for events
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    /// Draw a frame at `at`, e.g. when the next frame of an animation is due
+    pub fn wake_at(&mut self, at: Instant) {
+        if self.waker.0.is_none() {
+            return;
+        }
+        let waker = &self.waker;
+        let timer = self.timer.get_or_insert_with(|| spawn_timer(waker.clone()));
+        _ = timer.send(at);
+    }
+
+    /// All background work that is currently checked
+    pub fn polled(&self) -> Vec<Reason> {
+        let mut polled = self.polling.iter().copied().collect::<Vec<_>>();
+        for (reason, _) in &self.tokens {
+            if !polled.contains(reason) {
+                polled.push(*reason);
+            }
+        }
+        polled
+    }
+
+    /// Call at the start of a frame of a hidden window (background mode, screenshots). Frames
+    /// that only poll background work wait for the rest of `HIDDEN_POLL_INTERVAL`, as nothing
+    /// paces them. Frames of a visible window never wait.
+    pub fn throttle_hidden(&mut self) {
+        if self.urgent || !self.reasons.is_empty() || self.polled().is_empty() {
+            return;
+        }
+        let remaining = HIDDEN_POLL_INTERVAL.saturating_sub(self.last_frame.elapsed());
+        if !remaining.is_zero() {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    /// Call at the end of a frame. Returns whether another frame is needed.
+    pub fn end_frame(&mut self) -> bool {
+        // finished work gets one more frame to pick up its results
+        let tokens = self.tokens.len();
+        self.tokens
+            .retain(|(_, token)| Arc::strong_count(token) > 1);
+        self.urgent = !self.reasons.is_empty() || self.tokens.len() < tokens;
+        self.reasons.clear();
+        let needed = self.urgent || !self.polled().is_empty();
+        self.last_frame = Instant::now();
+        needed
+    }
+}
+
+/// A thread that wakes the event loop at the earliest instant it was sent, then waits for the next
+fn spawn_timer(waker: Waker) -> Sender<Instant> {
+    let (sender, receiver) = mpsc::channel::<Instant>();
+    thread::spawn(move || {
+        let mut due: Option<Instant> = None;
+        loop {
+            let received = match due {
+                Some(due) => receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(at) => due = Some(due.map_or(at, |due| due.min(at))),
+                Err(RecvTimeoutError::Timeout) => {
+                    due = None;
+                    waker.wake();
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+    sender
+}
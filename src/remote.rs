@@ -0,0 +1,529 @@
+//! Remote folders on SFTP servers or SMB shares, e.g. a NAS, browsed without mounting them.
+//! Files are downloaded on demand into a mirror of the remote folder in the cache, so the rest
+//! of the app sees ordinary paths. Listing and downloading use the `sftp` and `smbclient` tools,
+//! on Windows SMB shares are opened by their UNC path instead.
+
+use crate::utils::is_ext_compatible;
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Credentials for every remote folder, shared with the threads that list and load
+static CREDENTIALS: Mutex<Option<RemoteCredentials>> = Mutex::new(None);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RemoteCredentials {
+    /// Used if the URL names no user
+    pub user: String,
+    /// Needs `sshpass` for SFTP. Stored in the settings as plain text, but never exported.
+    pub password: String,
+    /// SSH private key for SFTP
+    pub identity: Option<PathBuf>,
+}
+
+pub fn set_credentials(credentials: RemoteCredentials) {
+    if let Ok(mut current) = CREDENTIALS.lock() {
+        *current = Some(credentials);
+    }
+}
+
+fn credentials() -> RemoteCredentials {
+    CREDENTIALS
+        .lock()
+        .ok()
+        .and_then(|c| c.clone())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Sftp,
+    Smb,
+}
+
+impl Protocol {
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Self::Sftp => "sftp",
+            Self::Smb => "smb",
+        }
+    }
+
+    fn from_scheme(scheme: &str) -> Option<Self> {
+        match scheme {
+            "sftp" => Some(Self::Sftp),
+            "smb" => Some(Self::Smb),
+            _ => None,
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        match self {
+            Self::Sftp => 22,
+            Self::Smb => 445,
+        }
+    }
+
+    /// Whether a file or folder name can be quoted in the commands of the tool. `smbclient`
+    /// separates commands by `;` even inside quotes.
+    fn can_quote(&self, name: &str) -> bool {
+        let reserved: &[char] = match self {
+            Self::Sftp => &['"', '\n', '\r'],
+            Self::Smb => &['"', ';', '\n', '\r'],
+        };
+        name != ".." && !name.contains(reserved)
+    }
+}
+
+/// Whether a host name can be passed to the tools without being taken for an option
+fn is_plain_host(host: &str) -> bool {
+    !host.is_empty()
+        && !host.starts_with('-')
+        && host
+            .chars()
+            .all(|c| c.is_alphanumeric() || ".-_".contains(c))
+}
+
+/// Whether a user name can be passed to the tools without being taken for an option
+fn is_plain_user(user: Option<&str>) -> bool {
+    user.map(|u| !u.starts_with('-') && !u.contains(|c: char| c.is_whitespace() || c.is_control()))
+        .unwrap_or(true)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteFolder {
+    pub protocol: Protocol,
+    pub user: Option<String>,
+    pub host: String,
+    pub port: u16,
+    /// The folders below the root. For SMB the first one is the share.
+    pub path: Vec<String>,
+}
+
+impl RemoteFolder {
+    /// Parse `sftp://user@host:port/path` or `smb://user@host/share/path`
+    pub fn parse(url: &str) -> Result<Self> {
+        let (scheme, rest) = url
+            .trim()
+            .split_once("://")
+            .ok_or_else(|| anyhow!("{url} is no URL"))?;
+        let protocol = Protocol::from_scheme(&scheme.to_lowercase())
+            .ok_or_else(|| anyhow!("Only sftp:// and smb:// folders are supported"))?;
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, authority),
+        };
+        let (host, port) = match host.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().context("Invalid port")?),
+            None => (host, protocol.default_port()),
+        };
+        if host.is_empty() {
+            bail!("No host in {url}");
+        }
+        if !is_plain_host(host) || !is_plain_user(user.as_deref()) {
+            bail!("The host or user of {url} is not supported");
+        }
+        let path: Vec<String> = path
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(|c| c.to_string())
+            .collect();
+        if !path.iter().all(|c| protocol.can_quote(c)) {
+            bail!("The path of {url} is not supported");
+        }
+        if protocol == Protocol::Smb && path.is_empty() {
+            bail!("No share in {url}");
+        }
+        Ok(Self {
+            protocol,
+            user,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        let user = self
+            .user
+            .as_ref()
+            .map(|u| format!("{u}@"))
+            .unwrap_or_default();
+        let port = if self.port == self.protocol.default_port() {
+            String::new()
+        } else {
+            format!(":{}", self.port)
+        };
+        format!(
+            "{}://{user}{}{port}/{}",
+            self.protocol.scheme(),
+            self.host,
+            self.path.join("/")
+        )
+    }
+
+    /// Where remote folders are mirrored
+    pub fn mirror_root() -> Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .ok_or(anyhow!("Can't get cache dir"))?
+            .join("oculante")
+            .join("remote"))
+    }
+
+    /// The local folder files of this folder are downloaded to
+    pub fn mirror(&self) -> Result<PathBuf> {
+        let authority = match &self.user {
+            Some(user) => format!("{user}@{}", self.host),
+            None => self.host.clone(),
+        };
+        let mut folder = Self::mirror_root()?
+            .join(self.protocol.scheme())
+            .join(authority)
+            .join(self.port.to_string());
+        for component in &self.path {
+            folder.push(component);
+        }
+        Ok(folder)
+    }
+
+    /// The remote folder a local folder is the mirror of
+    pub fn from_mirror(folder: &Path) -> Option<Self> {
+        let relative = folder.strip_prefix(Self::mirror_root().ok()?).ok()?;
+        let mut components = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string());
+        let protocol = Protocol::from_scheme(&components.next()?)?;
+        let authority = components.next()?;
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host.to_string()),
+            None => (None, authority),
+        };
+        let port = components.next()?.parse().ok()?;
+        let path: Vec<String> = components.collect();
+        if !is_plain_host(&host)
+            || !is_plain_user(user.as_deref())
+            || !path.iter().all(|c| protocol.can_quote(c))
+            || (protocol == Protocol::Smb && path.is_empty())
+        {
+            return None;
+        }
+        Some(Self {
+            protocol,
+            user,
+            host,
+            port,
+            path,
+        })
+    }
+
+    /// The UNC path of an SMB folder, like `\\host\share\path`
+    pub fn unc(&self) -> PathBuf {
+        PathBuf::from(format!(r"\\{}\{}", self.host, self.path.join(r"\")))
+    }
+
+    /// Run the commands in a session of `sftp` or `smbclient` and return the output
+    fn run(&self, commands: &str) -> Result<String> {
+        let credentials = credentials();
+        let user = self
+            .user
+            .clone()
+            .or_else(|| Some(credentials.user.clone()).filter(|u| !u.is_empty()));
+        let mut command = match self.protocol {
+            Protocol::Sftp => {
+                let mut command = if credentials.password.is_empty() {
+                    Command::new("sftp")
+                } else {
+                    // the password is handed over in the environment, not on the command line
+                    let mut command = Command::new("sshpass");
+                    command
+                        .arg("-e")
+                        .arg("sftp")
+                        .env("SSHPASS", &credentials.password);
+                    command
+                };
+                command.arg("-P").arg(self.port.to_string());
+                if let Some(identity) = &credentials.identity {
+                    command.arg("-i").arg(identity);
+                }
+                if credentials.password.is_empty() {
+                    // fail instead of waiting for a password
+                    command.args(["-o", "BatchMode=yes"]);
+                }
+                if !is_plain_host(&self.host) || !is_plain_user(user.as_deref()) {
+                    bail!("The host or user of {} is not supported", self.url());
+                }
+                // nothing after this is an option
+                command.arg("--").arg(match &user {
+                    Some(user) => format!("{user}@{}", self.host),
+                    None => self.host.clone(),
+                });
+                command
+            }
+            Protocol::Smb => {
+                let share = self
+                    .path
+                    .first()
+                    .ok_or_else(|| anyhow!("No share in {}", self.url()))?;
+                let mut command = Command::new("smbclient");
+                command
+                    .arg(format!("//{}/{share}", self.host))
+                    .arg("-p")
+                    .arg(self.port.to_string());
+                if let Some(user) = &user {
+                    command.arg("-U").arg(user);
+                }
+                if credentials.password.is_empty() {
+                    command.arg("-N");
+                } else {
+                    command.env("PASSWD", &credentials.password);
+                }
+                command.arg("-c").arg(commands);
+                command
+            }
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => anyhow!(
+                    "{} is not installed",
+                    match self.protocol {
+                        Protocol::Sftp if !credentials.password.is_empty() => "sshpass",
+                        Protocol::Sftp => "sftp",
+                        Protocol::Smb => "smbclient",
+                    }
+                ),
+                _ => e.into(),
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            if self.protocol == Protocol::Sftp {
+                stdin.write_all(commands.as_bytes())?;
+            }
+        }
+        let output = child.wait_with_output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .chain(stdout.lines())
+                .map(str::trim)
+                .find(|l| !l.is_empty())
+                .unwrap_or("unknown error")
+                .to_string();
+            bail!("Can't reach {}: {reason}", self.url());
+        }
+        Ok(stdout)
+    }
+
+    /// The path of this folder on the server, as the tools expect it
+    fn remote_path(&self) -> String {
+        match self.protocol {
+            Protocol::Sftp => format!("/{}", self.path.join("/")),
+            // below the share
+            Protocol::Smb => format!("\\{}", self.path.get(1..).unwrap_or_default().join("\\")),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<RemoteEntry>> {
+        match self.protocol {
+            Protocol::Sftp => {
+                let output = self.run(&format!("ls -l \"{}\"\n", self.remote_path()))?;
+                Ok(parse_sftp_listing(&output))
+            }
+            Protocol::Smb => {
+                let output = self.run(&format!("cd \"{}\"; ls", self.remote_path()))?;
+                Ok(parse_smb_listing(&output))
+            }
+        }
+    }
+
+    /// Download `name` to `target`
+    fn download(&self, name: &str, target: &Path) -> Result<()> {
+        if !self.protocol.can_quote(name) {
+            bail!("{name} can't be downloaded");
+        }
+        let local = target.to_string_lossy();
+        match self.protocol {
+            Protocol::Sftp => self.run(&format!(
+                "get \"{}/{name}\" \"{local}\"\n",
+                self.remote_path().trim_end_matches('/')
+            ))?,
+            Protocol::Smb => self.run(&format!(
+                "cd \"{}\"; get \"{name}\" \"{local}\"",
+                self.remote_path()
+            ))?,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Read the output of `ls -l` in `sftp`, like
+/// `-rw-r--r--    1 user     group       12345 Jan  1 12:00 /photos/a.jpg`
+pub fn parse_sftp_listing(output: &str) -> Vec<RemoteEntry> {
+    output
+        .lines()
+        .filter(|line| !line.starts_with("sftp>"))
+        .filter_map(|line| {
+            let mut rest = line.trim_start();
+            let mut fields = vec![];
+            for _ in 0..8 {
+                let end = rest.find(char::is_whitespace)?;
+                fields.push(&rest[..end]);
+                rest = rest[end..].trim_start();
+            }
+            // listings of a path name the entries with it
+            let name = rest.trim_end().rsplit('/').next()?.to_string();
+            let size = fields[4].parse().ok()?;
+            if name.is_empty() || name == "." || name == ".." {
+                return None;
+            }
+            Some(RemoteEntry {
+                name,
+                size,
+                is_dir: fields[0].starts_with('d'),
+            })
+        })
+        .collect()
+}
+
+/// Read the output of `ls` in `smbclient`, like
+/// `  a.jpg                               A    12345  Mon Jan  1 12:00:00 2024`
+pub fn parse_smb_listing(output: &str) -> Vec<RemoteEntry> {
+    output
+        .lines()
+        .filter(|line| line.starts_with("  "))
+        .filter_map(|line| {
+            // the name may contain spaces, so the fields are taken from the end
+            let mut rest = line.trim_end();
+            let mut fields = vec![];
+            for _ in 0..7 {
+                let start = rest.rfind(char::is_whitespace)?;
+                fields.push(&rest[start + 1..]);
+                rest = rest[..start].trim_end();
+            }
+            let (size, attributes) = (fields[5], fields[6]);
+            let size = size.parse().ok()?;
+            if !attributes.chars().all(|c| "ADHNRS".contains(c)) {
+                return None;
+            }
+            let name = rest.trim().to_string();
+            if name.is_empty() || name == "." || name == ".." {
+                return None;
+            }
+            Some(RemoteEntry {
+                name,
+                size,
+                is_dir: attributes.contains('D'),
+            })
+        })
+        .collect()
+}
+
+/// The images of a remote folder as paths in its mirror. Downloaded files whose size changed on
+/// the server are removed, so they are downloaded again.
+pub fn list_images(folder: &RemoteFolder) -> Result<Vec<PathBuf>> {
+    let mirror = folder.mirror()?;
+    std::fs::create_dir_all(&mirror)?;
+    let mut images = vec![];
+    for entry in folder.list()? {
+        let path = mirror.join(&entry.name);
+        if entry.is_dir || !is_ext_compatible(&path) {
+            continue;
+        }
+        let cached = std::fs::metadata(&path).map(|m| m.len()).ok();
+        if cached.map(|size| size != entry.size).unwrap_or_default() {
+            debug!("{} changed on the server", entry.name);
+            _ = std::fs::remove_file(&path);
+        }
+        images.push(path);
+    }
+    images.sort_unstable_by(|a, b| {
+        lexical_sort::natural_lexical_cmp(
+            &a.file_name().unwrap_or_default().to_string_lossy(),
+            &b.file_name().unwrap_or_default().to_string_lossy(),
+        )
+    });
+    Ok(images)
+}
+
+/// List the remote folder if `path` is in a mirror, or the folder of a file in it
+pub fn list_mirror(path: &Path) -> Option<Result<Vec<PathBuf>>> {
+    #[cfg(feature = "cloud")]
+    if let Some(images) = crate::cloud::list_mirror(path) {
+        return Some(images);
+    }
+    let folder = if path.is_dir() { path } else { path.parent()? };
+    RemoteFolder::from_mirror(folder).map(|folder| list_images(&folder))
+}
+
+/// Download `path` if it is in a mirror and not downloaded yet
+pub fn fetch(path: &Path) -> Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    #[cfg(feature = "cloud")]
+    if let Some(result) = crate::cloud::fetch(path) {
+        return result;
+    }
+    let (folder, name) = match (
+        path.parent().and_then(RemoteFolder::from_mirror),
+        path.file_name(),
+    ) {
+        (Some(folder), Some(name)) => (folder, name.to_string_lossy().to_string()),
+        _ => return Ok(()),
+    };
+    debug!("Downloading {name} from {}", folder.url());
+    // a partly downloaded file is never taken for a complete one
+    let part = path.with_file_name(format!(".{name}.part"));
+    folder.download(&name, &part)?;
+    if !part.exists() {
+        bail!("Can't download {name} from {}", folder.url());
+    }
+    std::fs::rename(&part, path)?;
+    Ok(())
+}
+
+/// List the folder at `url`, or with the `cloud` feature the objects in a bucket. Returns the
+/// images and the one to show first.
+pub fn open(url: &str, all_files: bool) -> Result<(Vec<PathBuf>, usize)> {
+    #[cfg(feature = "cloud")]
+    if crate::cloud::ObjectUri::is_uri(url) {
+        return crate::cloud::open(url);
+    }
+    #[cfg(not(feature = "cloud"))]
+    if url.trim().starts_with("s3://") || url.trim().starts_with("gs://") {
+        bail!("Oculante was built without cloud storage support");
+    }
+    let folder = RemoteFolder::parse(url)?;
+    // Windows opens shares itself
+    let images = if cfg!(target_os = "windows") && folder.protocol == Protocol::Smb {
+        crate::scrubber::get_image_filenames_for_directory(&folder.unc(), all_files)?
+    } else {
+        list_images(&folder)?
+    };
+    Ok((images, 0))
+}
+
+/// State of the remote folder window
+#[derive(Debug, Default)]
+pub struct RemoteBrowser {
+    pub enabled: bool,
+    /// A folder is being listed
+    pub listing: bool,
+}
@@ -0,0 +1,158 @@
+//! Screenshots of the desktop, taken with the tools of each OS while the window is hidden, and
+//! then opened like any other image.
+
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureArea {
+    /// All screens
+    Screen,
+    /// A region dragged out on the screen
+    Region,
+}
+
+impl CaptureArea {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Screen => "screen",
+            Self::Region => "region",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "screen" => Some(Self::Screen),
+            "region" => Some(Self::Region),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a region can be selected on this OS. Otherwise crop a screen capture.
+pub fn supports_region() -> bool {
+    !cfg!(target_os = "windows")
+}
+
+/// Run a capture command. Returns false if the tool is not installed.
+fn run(program: &str, args: &[&str]) -> Result<bool> {
+    let status = match Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    // tools exit with an error when the selection is cancelled
+    if !status.success() {
+        log::debug!("{program} exited with {status}");
+    }
+    Ok(true)
+}
+
+/// Save a screenshot of `area` as PNG to `path`. Returns false if it was cancelled.
+pub fn capture(area: CaptureArea, path: &Path) -> Result<bool> {
+    let file = path.to_string_lossy().to_string();
+    #[cfg(target_os = "windows")]
+    {
+        if area == CaptureArea::Region {
+            bail!("Region captures are not supported, crop a screen capture instead");
+        }
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms,System.Drawing;\
+             Add-Type -Name Dpi -Namespace Oculante -MemberDefinition \
+             '[DllImport(\"user32.dll\")] public static extern bool SetProcessDPIAware();';\
+             [Oculante.Dpi]::SetProcessDPIAware() | Out-Null;\
+             $b = [System.Windows.Forms.SystemInformation]::VirtualScreen;\
+             $bmp = New-Object System.Drawing.Bitmap $b.Width, $b.Height;\
+             $g = [System.Drawing.Graphics]::FromImage($bmp);\
+             $g.CopyFromScreen($b.Left, $b.Top, 0, 0, $bmp.Size);\
+             $bmp.Save({}, [System.Drawing.Imaging.ImageFormat]::Png)",
+            crate::file_manager::powershell_quote(&file)
+        );
+        if !run("powershell", &["-NoProfile", "-Command", &script])? {
+            bail!("PowerShell is not available");
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // -x keeps quiet, -i lets a region be dragged out
+        let args: &[&str] = match area {
+            CaptureArea::Screen => &["-x", &file],
+            CaptureArea::Region => &["-x", "-i", &file],
+        };
+        if !run("screencapture", args)? {
+            bail!("screencapture is not available");
+        }
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        if !capture_linux(area, &file)? {
+            bail!("No screenshot tool found, install grim and slurp, gnome-screenshot, spectacle, scrot or ImageMagick");
+        }
+    }
+    // nothing is written when the selection was cancelled
+    Ok(std::fs::metadata(path)
+        .map(|m| m.len() > 0)
+        .unwrap_or(false))
+}
+
+/// Try the screenshot tools of Wayland and X11 desktops in turn
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn capture_linux(area: CaptureArea, file: &str) -> Result<bool> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match area {
+            CaptureArea::Screen => {
+                if run("grim", &[file])? {
+                    return Ok(true);
+                }
+            }
+            CaptureArea::Region => {
+                if let Ok(output) = Command::new("slurp").stderr(Stdio::null()).output() {
+                    if !output.status.success() {
+                        // cancelled
+                        return Ok(true);
+                    }
+                    let geometry = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                    if run("grim", &["-g", &geometry, file])? {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+    }
+    let region = area == CaptureArea::Region;
+    let tools: [(&str, Vec<&str>); 4] = [
+        (
+            "gnome-screenshot",
+            if region {
+                vec!["-a", "-f", file]
+            } else {
+                vec!["-f", file]
+            },
+        ),
+        (
+            "spectacle",
+            vec!["-b", "-n", if region { "-r" } else { "-f" }, "-o", file],
+        ),
+        ("scrot", if region { vec!["-s", file] } else { vec![file] }),
+        (
+            "import",
+            if region {
+                vec![file]
+            } else {
+                vec!["-window", "root", file]
+            },
+        ),
+    ];
+    for (program, args) in &tools {
+        if run(program, args)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,304 @@
+//! User scripts for custom actions.
+//!
+//! Scripts are plain text files in `<config dir>/oculante/scripts`, written in the same
+//! expression language as the `Expression` edit operator (see the evalexpr docs).
+//! A script is run from the "Scripts" menu, or by a shortcut declared in its first line:
+//!
+//! ```text
+//! // keys: LControl+K
+//! message("Opened " + filename + " (" + str::from(index + 1) + "/" + str::from(count) + ")");
+//! next()
+//! ```
+//!
+//! Variables: `path`, `filename`, `index`, `count`, `width`, `height`.
+//! Functions: `message(text)`, `error(text)`, `next()`, `prev()`, `goto(index)`, `entry(index)`,
+//! `open(path)`, `pixel(x, y, channel)` and `set_pixel(x, y, r, g, b, a)`.
+//! Function arguments are always evaluated, so navigation can't be made conditional with `if`.
+
+use crate::appstate::OculanteState;
+use crate::shortcuts::SimultaneousKeypresses;
+use crate::utils::{load_image_from_path, next_image, prev_image, Frame};
+use anyhow::{anyhow, Result};
+use evalexpr::*;
+use image::RgbaImage;
+use log::debug;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const KEYS_PREFIX: &str = "// keys:";
+
+/// A user script loaded from the scripts folder
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    pub name: String,
+    pub source: String,
+    /// Shortcut running this script, if any
+    pub keys: SimultaneousKeypresses,
+}
+
+/// Everything the script is allowed to see
+#[derive(Debug, Clone, Default)]
+pub struct ScriptInput {
+    pub path: Option<PathBuf>,
+    pub index: usize,
+    pub entries: Vec<PathBuf>,
+    pub image: Option<RgbaImage>,
+}
+
+/// Something the script asked the viewer to do, applied after it finished
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+    Message(String),
+    Error(String),
+    Next,
+    Prev,
+    Goto(usize),
+    Open(PathBuf),
+}
+
+#[derive(Debug, Default)]
+struct ScriptEnv {
+    actions: Vec<ScriptAction>,
+    image: Option<RgbaImage>,
+    modified: bool,
+}
+
+/// The result of a script run
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    pub actions: Vec<ScriptAction>,
+    /// Pixels changed with `set_pixel`
+    pub image: Option<RgbaImage>,
+}
+
+impl Script {
+    pub fn new(name: &str, source: &str) -> Self {
+        let keys = source
+            .lines()
+            .next()
+            .and_then(|l| l.strip_prefix(KEYS_PREFIX))
+            .map(|keys| {
+                keys.split('+')
+                    .map(|k| k.trim().to_string())
+                    .filter(|k| !k.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            name: name.into(),
+            source: source.into(),
+            keys,
+        }
+    }
+
+    pub fn dir() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or(anyhow!("Can't get config dir"))?
+            .join("oculante")
+            .join("scripts"))
+    }
+
+    /// Load all scripts, sorted by name
+    pub fn load_all() -> Result<Vec<Self>> {
+        let mut scripts = std::fs::read_dir(Self::dir()?)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter_map(|p| {
+                let name = p.file_stem()?.to_string_lossy().to_string();
+                let source = std::fs::read_to_string(&p).ok()?;
+                Some(Self::new(&name, &source))
+            })
+            .collect::<Vec<_>>();
+        scripts.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(scripts)
+    }
+
+    /// Evaluate the script. Nothing in the viewer is changed yet, see `run_script`.
+    pub fn eval(&self, input: ScriptInput) -> Result<ScriptOutput> {
+        let (width, height) = input
+            .image
+            .as_ref()
+            .map(|i| i.dimensions())
+            .unwrap_or_default();
+        let path = input.path.clone().unwrap_or_default();
+        let env = Arc::new(Mutex::new(ScriptEnv {
+            image: input.image,
+            ..Default::default()
+        }));
+
+        let mut context = context_map! {
+            "path" => path.to_string_lossy().to_string(),
+            "filename" => path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default(),
+            "index" => input.index as i64,
+            "count" => input.entries.len() as i64,
+            "width" => width as i64,
+            "height" => height as i64,
+        }?;
+
+        context.set_function(
+            "message".into(),
+            action_fn(&env, |a| Ok(ScriptAction::Message(value_to_string(a)))),
+        )?;
+        context.set_function(
+            "error".into(),
+            action_fn(&env, |a| Ok(ScriptAction::Error(value_to_string(a)))),
+        )?;
+        context.set_function("next".into(), action_fn(&env, |_| Ok(ScriptAction::Next)))?;
+        context.set_function("prev".into(), action_fn(&env, |_| Ok(ScriptAction::Prev)))?;
+        context.set_function(
+            "goto".into(),
+            action_fn(&env, |a| {
+                Ok(ScriptAction::Goto(a.as_int()?.max(0) as usize))
+            }),
+        )?;
+        context.set_function(
+            "open".into(),
+            action_fn(&env, |a| Ok(ScriptAction::Open(a.as_string()?.into()))),
+        )?;
+
+        let entries = input.entries;
+        context.set_function(
+            "entry".into(),
+            Function::new(move |arg| {
+                let index = arg.as_int()?;
+                Ok(usize::try_from(index)
+                    .ok()
+                    .and_then(|i| entries.get(i))
+                    .map(|p| Value::String(p.to_string_lossy().to_string()))
+                    .unwrap_or(Value::Empty))
+            }),
+        )?;
+
+        let pixel_env = env.clone();
+        context.set_function(
+            "pixel".into(),
+            Function::new(move |arg| {
+                let args = arg.as_fixed_len_tuple(3)?;
+                let (x, y) = (args[0].as_int()?, args[1].as_int()?);
+                let channel = args[2].as_int()?;
+                let env = pixel_env.lock().map_err(|_| poisoned())?;
+                let value = env
+                    .image
+                    .as_ref()
+                    .and_then(|img| get_pixel(img, x, y))
+                    .and_then(|p| {
+                        usize::try_from(channel)
+                            .ok()
+                            .and_then(|c| p.get(c).copied())
+                    })
+                    .ok_or_else(|| {
+                        EvalexprError::CustomMessage(format!("No pixel at {x}, {y}, {channel}"))
+                    })?;
+                Ok(Value::Int(value as i64))
+            }),
+        )?;
+
+        let set_pixel_env = env.clone();
+        context.set_function(
+            "set_pixel".into(),
+            Function::new(move |arg| {
+                let args = arg.as_fixed_len_tuple(6)?;
+                let (x, y) = (args[0].as_int()?, args[1].as_int()?);
+                let mut color = [0_u8; 4];
+                for (c, v) in color.iter_mut().zip(&args[2..]) {
+                    *c = v.as_number()?.clamp(0., 255.) as u8;
+                }
+                let mut env = set_pixel_env.lock().map_err(|_| poisoned())?;
+                let img = env
+                    .image
+                    .as_mut()
+                    .filter(|img| get_pixel(img, x, y).is_some())
+                    .ok_or_else(|| EvalexprError::CustomMessage(format!("No pixel at {x}, {y}")))?;
+                img.put_pixel(x as u32, y as u32, image::Rgba(color));
+                env.modified = true;
+                Ok(Value::Empty)
+            }),
+        )?;
+
+        eval_empty_with_context_mut(&self.source, &mut context)
+            .map_err(|e| anyhow!("Script '{}' failed: {e}", self.name))?;
+
+        // the functions hold the other references, release them first
+        drop(context);
+        let env = Arc::try_unwrap(env)
+            .map_err(|_| anyhow!("Script environment is still in use"))?
+            .into_inner()
+            .map_err(|_| anyhow!("Script environment is poisoned"))?;
+        let modified = env.modified;
+        Ok(ScriptOutput {
+            actions: env.actions,
+            image: env.image.filter(|_| modified),
+        })
+    }
+}
+
+/// A script function that only records an action
+fn action_fn(
+    env: &Arc<Mutex<ScriptEnv>>,
+    f: fn(&Value) -> EvalexprResult<ScriptAction>,
+) -> Function {
+    let env = env.clone();
+    Function::new(move |arg| {
+        let action = f(arg)?;
+        env.lock().map_err(|_| poisoned())?.actions.push(action);
+        Ok(Value::Empty)
+    })
+}
+
+fn poisoned() -> EvalexprError {
+    EvalexprError::CustomMessage("Script environment is poisoned".into())
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn get_pixel(img: &RgbaImage, x: i64, y: i64) -> Option<[u8; 4]> {
+    let (x, y) = (u32::try_from(x).ok()?, u32::try_from(y).ok()?);
+    (x < img.width() && y < img.height()).then(|| img.get_pixel(x, y).0)
+}
+
+/// Run a script on the current image and apply what it asked for
+pub fn run_script(script: &Script, state: &mut OculanteState) {
+    let input = ScriptInput {
+        path: state.current_path.clone(),
+        index: state.scrubber.index,
+        entries: state.scrubber.entries.clone(),
+        image: state.current_image.clone(),
+    };
+    let output = match script.eval(input) {
+        Ok(output) => output,
+        Err(e) => {
+            state.send_message_err(&e.to_string());
+            return;
+        }
+    };
+    debug!("Script '{}' returned {:?}", script.name, output.actions);
+
+    if let Some(image) = output.image {
+        // Stop in the event that an animation is running
+        state.player.stop();
+        _ = state.player.image_sender.send(Frame::new_still(image));
+    }
+
+    for action in output.actions {
+        match action {
+            ScriptAction::Message(m) => state.send_message(&m),
+            ScriptAction::Error(m) => state.send_message_err(&m),
+            ScriptAction::Next => next_image(state),
+            ScriptAction::Prev => prev_image(state),
+            ScriptAction::Goto(index) => {
+                if state.scrubber.entries.get(index).is_some() {
+                    let p = state.scrubber.set(index);
+                    load_image_from_path(&p, state);
+                }
+            }
+            ScriptAction::Open(p) => load_image_from_path(Path::new(&p), state),
+        }
+    }
+}
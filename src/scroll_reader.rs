@@ -0,0 +1,122 @@
+//! Scroll reading: the images of the folder stacked vertically at one width, to read manga
+//! chapters or long screenshots by scrolling instead of flipping pages. Only the pages near the
+//! window are decoded and kept as textures.
+
+use image::RgbaImage;
+use notan::prelude::Texture;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Pages kept decoded before and after the visible ones
+const PRELOAD: usize = 2;
+
+/// Height to width ratio of pages that are not decoded yet, like a manga page
+const ESTIMATED_RATIO: f32 = 1.5;
+
+/// How far a key scrolls while reading, by the name of the key: the arrow keys by `step` and
+/// the page keys by `page`
+pub fn scroll_key(key: &str, step: f32, page: f32) -> Option<f32> {
+    match key {
+        "Down" => Some(step),
+        "Up" => Some(-step),
+        "PageDown" => Some(page),
+        "PageUp" => Some(-page),
+        _ => None,
+    }
+}
+
+/// A decoded page and its index
+pub type DecodedPage = (usize, RgbaImage);
+
+#[derive(Debug, Default)]
+pub struct ScrollReader {
+    pub active: bool,
+    pub pages: Vec<PathBuf>,
+    /// Height to width ratios of the pages decoded so far
+    pub ratios: HashMap<usize, f32>,
+    pub textures: HashMap<usize, Texture>,
+    /// Pages being decoded
+    pub loading: HashSet<usize>,
+    /// The page at the top of the window, and how far it is scrolled past the top, in window
+    /// points. Pages above it can change their height without moving the view.
+    pub position: (usize, f32),
+}
+
+impl ScrollReader {
+    /// Read `pages`, starting at the top of the page at `index`
+    pub fn start(&mut self, pages: Vec<PathBuf>, index: usize) {
+        self.position = (index.min(pages.len().saturating_sub(1)), 0.);
+        self.pages = pages;
+        self.ratios.clear();
+        self.textures.clear();
+        self.loading.clear();
+        self.active = true;
+    }
+
+    /// Stop reading and free the textures. Returns the page at the top of the window.
+    pub fn stop(&mut self) -> Option<PathBuf> {
+        self.active = false;
+        self.textures.clear();
+        self.pages.get(self.position.0).cloned()
+    }
+
+    /// Height of the page at `index` in window points, if pages are `width` wide
+    pub fn page_height(&self, index: usize, width: f32) -> f32 {
+        width * self.ratios.get(&index).copied().unwrap_or(ESTIMATED_RATIO)
+    }
+
+    /// Scroll down by `delta` window points, or up if negative. The view stays between the top
+    /// of the first page and the bottom of the last one.
+    pub fn scroll(&mut self, delta: f32, width: f32, window_height: f32) {
+        self.move_by(delta, width);
+        let (page, offset) = self.position;
+        let below = (page..self.pages.len())
+            .map(|i| self.page_height(i, width))
+            .sum::<f32>()
+            - offset;
+        if below < window_height {
+            self.move_by(below - window_height, width);
+        }
+    }
+
+    fn move_by(&mut self, delta: f32, width: f32) {
+        let (mut page, mut offset) = self.position;
+        offset += delta;
+        while offset < 0. && page > 0 {
+            page -= 1;
+            offset += self.page_height(page, width);
+        }
+        while page + 1 < self.pages.len() && offset >= self.page_height(page, width) {
+            offset -= self.page_height(page, width);
+            page += 1;
+        }
+        self.position = (page, offset.max(0.));
+    }
+
+    /// Go to the top of the page `pages` after the one at the top of the window, or before it
+    /// if negative
+    pub fn turn(&mut self, pages: isize) {
+        let last = self.pages.len().saturating_sub(1) as isize;
+        let page = (self.position.0 as isize + pages).clamp(0, last);
+        self.position = (page as usize, 0.);
+    }
+
+    /// The pages in the window
+    pub fn visible(&self, width: f32, window_height: f32) -> Range<usize> {
+        let (first, offset) = self.position;
+        let mut y = -offset;
+        let mut end = first;
+        while end < self.pages.len() && y < window_height {
+            y += self.page_height(end, width);
+            end += 1;
+        }
+        first..end
+    }
+
+    /// The pages to keep decoded: the visible ones and a few around them
+    pub fn wanted(&self, width: f32, window_height: f32) -> Range<usize> {
+        let visible = self.visible(width, window_height);
+        visible.start.saturating_sub(PRELOAD)..(visible.end + PRELOAD).min(self.pages.len())
+    }
+}
@@ -1,4 +1,6 @@
-use crate::utils::is_ext_compatible;
+use crate::sharpness::SharpnessCache;
+use crate::similarity::{distance, HashCache};
+use crate::utils::{is_content_compatible, is_ext_compatible};
 use anyhow::{bail, Context, Result};
 use log::debug;
 use std::path::{Path, PathBuf};
@@ -11,8 +13,8 @@ pub struct Scrubber {
 }
 
 impl Scrubber {
-    pub fn new(path: &Path) -> Self {
-        let entries = get_image_filenames_for_directory(path).unwrap_or_default();
+    pub fn new(path: &Path, all_files: bool) -> Self {
+        let entries = get_image_filenames_for_directory(path, all_files).unwrap_or_default();
         let index = entries.iter().position(|p| p == path).unwrap_or_default();
         Self {
             index,
@@ -20,6 +22,18 @@ impl Scrubber {
             wrap: true,
         }
     }
+
+    /// Point at `path`. Returns false if it is not listed, e.g. because it is in another folder.
+    pub fn select(&mut self, path: &Path) -> bool {
+        match self.entries.iter().position(|p| p == path) {
+            Some(index) => {
+                self.index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn next(&mut self) -> PathBuf {
         self.index += 1;
         if self.index > self.entries.len().saturating_sub(1) {
@@ -53,15 +67,94 @@ impl Scrubber {
         self.entries.get(self.index).cloned().unwrap_or_default()
     }
 
+    /// Order the entries from the sharpest to the softest if `sort` is set, and hide the ones
+    /// scoring below `min`. Entries without a score yet and `keep` are never hidden.
+    pub fn arrange_by_sharpness(
+        &mut self,
+        scores: &SharpnessCache,
+        sort: bool,
+        min: f32,
+        keep: &Path,
+    ) {
+        self.arrange(|p| scores.get(p).map(|s| -s), sort, -min, keep);
+    }
+
+    /// Order the entries from the most to the least similar to an image hash and hide the ones
+    /// differing by more than `max_distance` bits. Entries without a hash yet and `keep` are
+    /// never hidden.
+    pub fn arrange_by_similarity(
+        &mut self,
+        hashes: &HashCache,
+        reference: u64,
+        max_distance: u32,
+        keep: &Path,
+    ) {
+        let rank = |p: &Path| hashes.get(p).map(|h| distance(h, reference) as f32);
+        self.arrange(rank, true, max_distance as f32, keep);
+    }
+
+    /// Order the entries by ascending rank if `sort` is set and hide those ranked above `max`.
+    /// Entries without a rank stay in their previous order after the others.
+    fn arrange(&mut self, rank: impl Fn(&Path) -> Option<f32>, sort: bool, max: f32, keep: &Path) {
+        let mut entries = self
+            .entries
+            .drain(..)
+            .map(|p| {
+                let rank = rank(&p);
+                (p, rank)
+            })
+            .filter(|(p, rank)| p == keep || rank.map(|r| r <= max).unwrap_or(true))
+            .collect::<Vec<_>>();
+        if sort {
+            entries.sort_by(|a, b| {
+                let a = a.1.unwrap_or(f32::INFINITY);
+                a.total_cmp(&b.1.unwrap_or(f32::INFINITY))
+            });
+        }
+        self.entries = entries.into_iter().map(|(p, _)| p).collect();
+        if !self.select(keep) {
+            self.index = self.index.min(self.entries.len().saturating_sub(1));
+        }
+    }
+
     pub fn len(&mut self) -> usize {
         self.entries.len()
     }
+
+    /// The next and previous entry, which are likely to be opened next
+    pub fn neighbours(&self) -> Vec<PathBuf> {
+        let len = self.entries.len();
+        let mut indices = vec![];
+        if self.index + 1 < len {
+            indices.push(self.index + 1);
+        } else if self.wrap {
+            indices.push(0);
+        }
+        if self.index > 0 {
+            indices.push(self.index - 1);
+        } else if self.wrap {
+            indices.push(len.saturating_sub(1));
+        }
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter(|i| *i != self.index)
+            .filter_map(|i| self.entries.get(i).cloned())
+            .collect()
+    }
 }
 
 // Get sorted list of files in a folder
 // TODO: Should probably return an Result<T,E> instead, but am too lazy to figure out + handle a dedicated error type here
 // TODO: Cache this result, instead of doing it each time we need to fetch another file from the folder
-pub fn get_image_filenames_for_directory(folder_path: &Path) -> Result<Vec<PathBuf>> {
+// With `all_files`, files with unknown extensions are included if their content looks like an image
+pub fn get_image_filenames_for_directory(
+    folder_path: &Path,
+    all_files: bool,
+) -> Result<Vec<PathBuf>> {
+    if let Some(images) = crate::remote::list_mirror(folder_path) {
+        return images;
+    }
     let mut folder_path = folder_path.to_path_buf();
     if folder_path.is_file() {
         folder_path = folder_path
@@ -75,7 +168,7 @@ pub fn get_image_filenames_for_directory(folder_path: &Path) -> Result<Vec<PathB
     let mut dir_files = info
         .flat_map(|x| x)
         .map(|x| x.path())
-        .filter(|x| is_ext_compatible(x))
+        .filter(|x| is_ext_compatible(x) || (all_files && is_content_compatible(x)))
         .collect::<Vec<PathBuf>>();
 
     dir_files.sort_unstable_by(|a, b| {
@@ -94,11 +187,11 @@ pub fn get_image_filenames_for_directory(folder_path: &Path) -> Result<Vec<PathB
 
 /// Find first valid image from the directory
 /// Assumes the given path is a directory and not a file
-pub fn find_first_image_in_directory(folder_path: &PathBuf) -> Result<PathBuf> {
+pub fn find_first_image_in_directory(folder_path: &PathBuf, all_files: bool) -> Result<PathBuf> {
     if !folder_path.is_dir() {
         bail!("This is not a folder");
     };
-    get_image_filenames_for_directory(folder_path).map(|x| {
+    get_image_filenames_for_directory(folder_path, all_files).map(|x| {
         x.first()
             .cloned()
             .context("Folder does not have any supported images in it")
@@ -0,0 +1,177 @@
+//! Searching the images below a folder by their file names, EXIF data and descriptive metadata.
+//! The searchable text of each file is cached in the cache dir, the index of its words is built
+//! in memory when searching starts.
+
+use crate::cache::FileCache;
+use crate::metadata::EditableMetadata;
+use crate::scrubber::get_image_filenames_for_directory;
+use log::debug;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Name of the cache file
+pub const CACHE_FILE: &str = "search.json";
+/// Larger trees are only indexed in part
+const MAX_FILES: usize = 100_000;
+
+/// Searchable text of files, without their names
+pub type TextCache = FileCache<String>;
+
+/// Lower case words of a text
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// The EXIF fields and editable metadata of a file as text
+pub fn document(path: &Path) -> String {
+    let mut text = vec![];
+    if let Ok(file) = std::fs::File::open(path) {
+        if let Ok(exif) =
+            exif::Reader::new().read_from_container(&mut std::io::BufReader::new(file))
+        {
+            // skip binary fields, the text of the XP tags is read with the metadata below
+            for field in exif.fields() {
+                if !matches!(
+                    field.value,
+                    exif::Value::Byte(_) | exif::Value::Undefined(..) | exif::Value::Unknown(..)
+                ) {
+                    text.push(field.display_value().to_string());
+                }
+            }
+        }
+    }
+    if let Ok(metadata) = EditableMetadata::read(path) {
+        text.extend([
+            metadata.title,
+            metadata.description,
+            metadata.keywords,
+            metadata.copyright,
+        ]);
+    }
+    text.join(" ")
+}
+
+/// All images below `root`, in the order of the scrubber per folder. Hidden folders are skipped.
+pub fn image_files(root: &Path, all_files: bool) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut folders = vec![root.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        files.extend(get_image_filenames_for_directory(&folder, all_files).unwrap_or_default());
+        if files.len() >= MAX_FILES {
+            files.truncate(MAX_FILES);
+            break;
+        }
+        let mut subfolders = std::fs::read_dir(&folder)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir())
+                    .filter(|p| {
+                        !p.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .starts_with('.')
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        // popped from the end, so the first folder comes next
+        subfolders.sort_by(|a, b| b.cmp(a));
+        folders.extend(subfolders);
+    }
+    files
+}
+
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    pub root: PathBuf,
+    pub files: Vec<PathBuf>,
+    /// Every word, with the files that contain it
+    words: BTreeMap<String, Vec<usize>>,
+    /// Words of the paths below the root, which rank higher
+    names: BTreeMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    /// Index the images below `root`, reading the text of files missing in `cache`
+    pub fn build(root: &Path, all_files: bool, cache: &mut TextCache) -> Self {
+        let mut index = Self {
+            root: root.to_path_buf(),
+            files: image_files(root, all_files),
+            ..Default::default()
+        };
+        for (i, path) in index.files.iter().enumerate() {
+            let text = cache.get(path).unwrap_or_else(|| {
+                let text = document(path);
+                cache.insert(path, text.clone());
+                text
+            });
+            let name = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+            let name_words = words(&name).into_iter().collect::<HashSet<_>>();
+            let all_words = words(&text).into_iter().chain(name_words.iter().cloned());
+            for word in all_words.collect::<HashSet<_>>() {
+                index.words.entry(word).or_default().push(i);
+            }
+            for word in name_words {
+                index.names.entry(word).or_default().push(i);
+            }
+        }
+        debug!(
+            "Indexed {} images with {} words below {}",
+            index.files.len(),
+            index.words.len(),
+            root.display()
+        );
+        index
+    }
+
+    /// The files containing all words of the query, or words starting with them. Files matching
+    /// by name come first.
+    pub fn search(&self, query: &str) -> Vec<PathBuf> {
+        let terms = words(query);
+        if terms.is_empty() {
+            return vec![];
+        }
+        let matching = |map: &BTreeMap<String, Vec<usize>>, prefix: &str| {
+            map.range(prefix.to_string()..)
+                .take_while(|(word, _)| word.starts_with(prefix))
+                .flat_map(|(_, files)| files.iter().cloned())
+                .collect::<HashSet<_>>()
+        };
+        let mut found = matching(&self.words, &terms[0]);
+        for term in &terms[1..] {
+            let files = matching(&self.words, term);
+            found.retain(|i| files.contains(i));
+        }
+        let by_name = terms
+            .iter()
+            .map(|term| matching(&self.names, term))
+            .collect::<Vec<_>>();
+        let mut found = found
+            .into_iter()
+            .map(|i| (by_name.iter().filter(|files| files.contains(&i)).count(), i))
+            .collect::<Vec<_>>();
+        found.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        found
+            .into_iter()
+            .map(|(_, i)| self.files[i].clone())
+            .collect()
+    }
+}
+
+/// State of the search window
+#[derive(Debug, Default)]
+pub struct Search {
+    pub enabled: bool,
+    pub query: String,
+    pub index: Option<SearchIndex>,
+    /// An index is being built
+    pub indexing: bool,
+    pub results: Vec<PathBuf>,
+    /// The scrubber lists the results instead of the folder
+    pub browsing: bool,
+}
@@ -0,0 +1,111 @@
+//! A rectangular selection dragged on the image, and statistics of the pixels inside it.
+
+use image::RgbaImage;
+use nalgebra::Vector2;
+use std::path::PathBuf;
+
+/// A rectangle of whole pixels: x, y, width and height
+pub type PixelRect = (u32, u32, u32, u32);
+
+/// The pixels of `rect` in `img`
+pub fn crop(img: &RgbaImage, rect: PixelRect) -> RgbaImage {
+    image::imageops::crop_imm(img, rect.0, rect.1, rect.2, rect.3).to_image()
+}
+
+/// The region of an image of `to` dimensions that covers `rect` of the same image at `from`
+/// dimensions, like a preview and its full resolution
+pub fn scale_rect(rect: PixelRect, from: (u32, u32), to: (u32, u32)) -> PixelRect {
+    let sx = to.0 as f64 / from.0.max(1) as f64;
+    let sy = to.1 as f64 / from.1.max(1) as f64;
+    let x0 = ((rect.0 as f64 * sx).floor() as u32).min(to.0);
+    let y0 = ((rect.1 as f64 * sy).floor() as u32).min(to.1);
+    let x1 = (((rect.0 + rect.2) as f64 * sx).ceil() as u32).min(to.0);
+    let y1 = (((rect.1 + rect.3) as f64 * sy).ceil() as u32).min(to.1);
+    (x0, y0, x1 - x0, y1 - y0)
+}
+
+/// Per-channel statistics of the pixels in a region
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionStats {
+    pub pixels: u64,
+    pub mean: [f64; 4],
+    pub min: [u8; 4],
+    pub max: [u8; 4],
+    /// Population standard deviation
+    pub stddev: [f64; 4],
+}
+
+impl RegionStats {
+    pub fn compute(img: &RgbaImage, rect: PixelRect) -> Self {
+        let (x, y, width, height) = rect;
+        let mut sum = [0_f64; 4];
+        let mut sum_sq = [0_f64; 4];
+        let mut min = [u8::MAX; 4];
+        let mut max = [u8::MIN; 4];
+        for row in y..y + height {
+            for column in x..x + width {
+                for (c, &v) in img.get_pixel(column, row).0.iter().enumerate() {
+                    sum[c] += v as f64;
+                    sum_sq[c] += v as f64 * v as f64;
+                    min[c] = min[c].min(v);
+                    max[c] = max[c].max(v);
+                }
+            }
+        }
+        let pixels = width as u64 * height as u64;
+        let n = pixels.max(1) as f64;
+        let mean = sum.map(|s| s / n);
+        let stddev = [0, 1, 2, 3].map(|c| (sum_sq[c] / n - mean[c] * mean[c]).max(0.).sqrt());
+        if pixels == 0 {
+            (min, max) = ([0; 4], [0; 4]);
+        }
+        Self {
+            pixels,
+            mean,
+            min,
+            max,
+            stddev,
+        }
+    }
+}
+
+/// State of the selection tool
+#[derive(Debug, Default)]
+pub struct Selection {
+    /// Dragging on the image draws a selection instead of panning
+    pub enabled: bool,
+    /// Corners where the drag started and ended, in image coordinates
+    pub corners: Option<(Vector2<f32>, Vector2<f32>)>,
+    /// The second corner follows the mouse
+    pub dragging: bool,
+    /// Statistics of the last region, with the image and region they were computed for
+    pub stats: Option<(Option<PathBuf>, PixelRect, RegionStats)>,
+}
+
+impl Selection {
+    /// The pixels covered by the selection, clipped to an image of `dimensions`.
+    /// Both corner pixels are included.
+    pub fn rect(&self, dimensions: (u32, u32)) -> Option<PixelRect> {
+        let (a, b) = self.corners?;
+        let clamp = |v: f32, size: u32| (v.floor().max(0.) as u32).min(size);
+        let x0 = clamp(a.x.min(b.x), dimensions.0);
+        let y0 = clamp(a.y.min(b.y), dimensions.1);
+        let x1 = clamp(a.x.max(b.x) + 1., dimensions.0);
+        let y1 = clamp(a.y.max(b.y) + 1., dimensions.1);
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+        Some((x0, y0, x1 - x0, y1 - y0))
+    }
+
+    /// Statistics of the selected region of `img`, computed again only when the region or the
+    /// image at `path` changed
+    pub fn stats(&mut self, img: &RgbaImage, path: Option<&PathBuf>) -> Option<&RegionStats> {
+        let rect = self.rect(img.dimensions())?;
+        let cached = matches!(&self.stats, Some((p, r, _)) if p.as_ref() == path && *r == rect);
+        if !cached {
+            self.stats = Some((path.cloned(), rect, RegionStats::compute(img, rect)));
+        }
+        self.stats.as_ref().map(|(_, _, stats)| stats)
+    }
+}
@@ -0,0 +1,140 @@
+//! Numbered image sequences, e.g. `render.0001.exr`, `render.0002.exr`, ...
+//!
+//! A sequence is detected from the files of the current folder and can be played back like a
+//! flipbook. Frames are loaded through the regular player, so the memory cache speeds up loops.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    /// File name before the frame number
+    pub prefix: String,
+    /// File name after the frame number, including the extension
+    pub suffix: String,
+    /// Frame numbers and files, sorted by number
+    pub frames: Vec<(u64, PathBuf)>,
+}
+
+/// Split a file name at the last number before the extension
+fn split_number(path: &Path) -> Option<(String, u64, String)> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let stem_len = path.file_stem()?.to_string_lossy().len();
+    let stem = &name[..stem_len];
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|i| i + 1)
+        .unwrap_or_default();
+    let number = stem[start..end].parse().ok()?;
+    Some((name[..start].to_string(), number, name[end..].to_string()))
+}
+
+impl Sequence {
+    /// Find the sequence `path` belongs to among `entries`. Needs at least two frames.
+    pub fn detect(path: &Path, entries: &[PathBuf]) -> Option<Self> {
+        let (prefix, _, suffix) = split_number(path)?;
+        let mut frames = entries
+            .iter()
+            .filter(|p| p.parent() == path.parent())
+            .filter_map(|p| match split_number(p) {
+                Some((pre, number, suf)) if pre == prefix && suf == suffix => {
+                    Some((number, p.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if frames.len() < 2 {
+            return None;
+        }
+        frames.sort_by_key(|(number, _)| *number);
+        Some(Self {
+            prefix,
+            suffix,
+            frames,
+        })
+    }
+
+    /// Index of a file in the sequence
+    pub fn position(&self, path: &Path) -> Option<usize> {
+        self.frames.iter().position(|(_, p)| p == path)
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}#{}", self.prefix, self.suffix)
+    }
+}
+
+/// Flipbook playback of a sequence
+#[derive(Debug, Clone)]
+pub struct SequencePlayer {
+    pub sequence: Sequence,
+    pub playing: bool,
+    pub fps: f32,
+    /// Index of the first and last frame of the loop
+    pub range: (usize, usize),
+    last_advance: Instant,
+}
+
+impl SequencePlayer {
+    pub fn new(sequence: Sequence) -> Self {
+        let last = sequence.frames.len().saturating_sub(1);
+        Self {
+            sequence,
+            playing: false,
+            fps: 24.,
+            range: (0, last),
+            last_advance: Instant::now(),
+        }
+    }
+
+    /// The index after `current`, wrapping around in the loop range
+    pub fn next_index(&self, current: usize) -> usize {
+        if current < self.range.0 || current >= self.range.1 {
+            self.range.0
+        } else {
+            current + 1
+        }
+    }
+
+    /// The frame to show after `current` if it is due, while playing
+    pub fn advance(&mut self, current: usize) -> Option<(usize, PathBuf)> {
+        if !self.playing {
+            return None;
+        }
+        if self.last_advance.elapsed() < self.interval() {
+            return None;
+        }
+        self.last_advance = Instant::now();
+        let next = self.next_index(current);
+        self.sequence
+            .frames
+            .get(next)
+            .map(|(_, path)| (next, path.clone()))
+    }
+
+    /// When the next frame is due, while playing
+    pub fn next_due(&self) -> Option<Instant> {
+        self.playing.then(|| self.last_advance + self.interval())
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f32(1. / self.fps.max(0.1))
+    }
+
+    /// The next frames of the loop, which are worth decoding ahead
+    pub fn upcoming(&self, current: usize, count: usize) -> Vec<PathBuf> {
+        let mut index = current;
+        (0..count)
+            .filter_map(|_| {
+                index = self.next_index(index);
+                self.sequence.frames.get(index).map(|(_, p)| p.clone())
+            })
+            .collect()
+    }
+
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+        self.last_advance = Instant::now();
+    }
+}
@@ -1,8 +1,18 @@
-use crate::{shortcuts::*, utils::ColorChannel};
+use crate::{guides::Guide, metadata::GeoTag, shortcuts::*, utils::ColorChannel};
 use anyhow::{anyhow, Result};
-use notan::egui::{Context, Visuals};
+use notan::egui::{style::Spacing, vec2, Color32, Context, Stroke, Style, Visuals};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fs::File, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+use strum_macros::EnumIter;
+
+/// When the settings file was changed by this instance, to notice changes of other apps
+static SYNCED: Mutex<Option<SystemTime>> = Mutex::new(None);
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ColorTheme {
@@ -14,6 +24,212 @@ pub enum ColorTheme {
     System,
 }
 
+/// A shell command the current file can be sent to
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ExternalCommand {
+    pub name: String,
+    /// The command line. `{path}` is replaced by the quoted path of the current file.
+    pub command: String,
+    /// Load the file again once the command has finished
+    pub reload: bool,
+}
+
+impl ExternalCommand {
+    /// The command line to run for `path`
+    pub fn expand(&self, path: &Path) -> String {
+        let path = path.to_string_lossy();
+        #[cfg(windows)]
+        let quoted = format!("\"{path}\"");
+        #[cfg(not(windows))]
+        let quoted = format!("'{}'", path.replace('\'', "'\\''"));
+        self.command.replace("{path}", &quoted)
+    }
+}
+
+/// How the view is set up when a new image is opened
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, EnumIter)]
+pub enum ZoomPolicy {
+    /// Scale the image to fit the window
+    Fit,
+    /// Scale large images down to fit the window, show small ones at 1:1
+    FitIfLarger,
+    /// Scale the image to cover the whole window
+    Fill,
+    /// Show the image at 1:1
+    Original,
+    /// Keep the current zoom and center the image
+    Keep,
+    /// Show the image at its physical size, using the resolution in the file
+    PrintSize,
+}
+
+impl ZoomPolicy {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Fit => "Fit to window",
+            Self::FitIfLarger => "Fit if larger than window",
+            Self::Fill => "Fill window",
+            Self::Original => "1:1",
+            Self::Keep => "Keep zoom and center",
+            Self::PrintSize => "Print size",
+        }
+    }
+
+    /// The scale of an image of `image_size` in a window of `window_size`
+    pub fn scale(&self, image_size: (f32, f32), window_size: (f32, f32), current: f32) -> f32 {
+        let (x, y) = (window_size.0 / image_size.0, window_size.1 / image_size.1);
+        match self {
+            Self::Fit => x.min(y),
+            Self::FitIfLarger => x.min(y).min(1.),
+            Self::Fill => x.max(y),
+            Self::Original => 1.,
+            Self::Keep => current,
+            // needs the resolution of the file, see `utils::print_zoom`
+            Self::PrintSize => 1.,
+        }
+    }
+}
+
+/// Where the minimap is shown
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, EnumIter)]
+pub enum MinimapCorner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl MinimapCorner {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::TopLeft => "Top left",
+            Self::TopRight => "Top right",
+            Self::BottomLeft => "Bottom left",
+            Self::BottomRight => "Bottom right",
+        }
+    }
+}
+
+/// Images up to this size are shown with sharp pixels by `Sampling::Auto`, like pixel art
+const SMALL_IMAGE: u32 = 512;
+
+/// How the image is sampled when it is drawn zoomed in or out
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, EnumIter)]
+pub enum Sampling {
+    /// Sharp pixels when zooming into small images, mipmapped otherwise
+    #[default]
+    Auto,
+    Nearest,
+    Linear,
+    /// Linear, and smooth instead of grainy when zoomed out
+    Mipmap,
+}
+
+impl Sampling {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Auto => "Auto",
+            Self::Nearest => "Nearest",
+            Self::Linear => "Linear",
+            Self::Mipmap => "Mipmap",
+        }
+    }
+
+    /// Whether pixels stay sharp when zooming past 100% into an image of `dimensions`
+    pub fn nearest_on_zoom(&self, (width, height): (u32, u32)) -> bool {
+        match self {
+            Self::Auto => width <= SMALL_IMAGE && height <= SMALL_IMAGE,
+            Self::Nearest => true,
+            Self::Linear | Self::Mipmap => false,
+        }
+    }
+}
+
+/// Which releases updates come from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum UpdateChannel {
+    /// Releases without a pre-release version
+    #[default]
+    Stable,
+    /// All releases, including pre-releases
+    Nightly,
+}
+
+/// A display the window was on. The window system doesn't list displays, so they are remembered
+/// by a window position on them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Monitor {
+    pub name: String,
+    /// Physical window position on the display
+    pub position: (i32, i32),
+    /// Physical position of the top left corner of the display
+    #[serde(default)]
+    pub origin: (i32, i32),
+    /// Physical size of the display
+    pub size: (i32, i32),
+}
+
+impl Monitor {
+    /// Whether a window at `position` on a display of `size` is on this display
+    pub fn contains(&self, position: (i32, i32), size: (i32, i32)) -> bool {
+        size == self.size
+            && (self.origin.0..self.origin.0 + self.size.0).contains(&position.0)
+            && (self.origin.1..self.origin.1 + self.size.1).contains(&position.1)
+    }
+}
+
+/// View settings that can be remembered per folder, e.g. to view textures and photos differently
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct FolderView {
+    pub current_channel: ColorChannel,
+    pub zen_mode: bool,
+    pub sampling: Sampling,
+    pub show_checker_background: bool,
+    pub zoom_policy: ZoomPolicy,
+}
+
+impl Default for FolderView {
+    fn default() -> Self {
+        Self::from(&PersistentSettings::default())
+    }
+}
+
+impl From<&PersistentSettings> for FolderView {
+    fn from(s: &PersistentSettings) -> Self {
+        Self {
+            current_channel: s.current_channel,
+            zen_mode: s.zen_mode,
+            sampling: s.sampling,
+            show_checker_background: s.show_checker_background,
+            zoom_policy: s.zoom_policy,
+        }
+    }
+}
+
+impl FolderView {
+    pub fn apply(&self, s: &mut PersistentSettings) {
+        s.current_channel = self.current_channel;
+        s.zen_mode = self.zen_mode;
+        s.sampling = self.sampling;
+        s.show_checker_background = self.show_checker_background;
+        s.zoom_policy = self.zoom_policy;
+    }
+}
+
+/// The folder whose remembered view is in use
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ActiveFolderView {
+    pub folder: PathBuf,
+    /// The view when the folder was entered, to detect changes
+    pub entered: FolderView,
+    /// The view outside of the folder, restored when leaving it
+    pub base: FolderView,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct PersistentSettings {
@@ -26,15 +242,25 @@ pub struct PersistentSettings {
     pub force_redraw: bool,
     /// Keyboard map to actions
     pub shortcuts: Shortcuts,
-    /// Do not reset view when receiving a new image
-    pub keep_view: bool,
+    /// How the view is set up when receiving a new image
+    pub zoom_policy: ZoomPolicy,
     /// How many images to keep in cache
     pub max_cache: usize,
     pub show_scrub_bar: bool,
     pub wrap_folder: bool,
+    /// Order the folder from the sharpest image to the softest
+    pub sharpness_sort: bool,
+    /// Hide images of the folder with a lower sharpness score. 0 shows all.
+    pub min_sharpness: f32,
+    /// How many bits of their perceptual hashes similar images may differ in
+    pub max_hash_distance: u32,
     /// Whether to keep the image edit stack
     pub keep_edits: bool,
     pub favourite_images: HashSet<PathBuf>,
+    /// Write edited metadata to XMP sidecars instead of into the files
+    pub metadata_sidecars: bool,
+    /// Recently assigned GPS locations, newest first
+    pub recent_geotags: Vec<GeoTag>,
     pub recent_images: Vec<PathBuf>,
     pub title_format: String,
     pub info_enabled: bool,
@@ -44,15 +270,119 @@ pub struct PersistentSettings {
     pub last_open_directory: PathBuf,
     pub show_checker_background: bool,
     pub show_minimap: bool,
+    pub minimap_corner: MinimapCorner,
+    /// Composition guides drawn over the image
+    pub guides: HashSet<Guide>,
+    /// Cover the image outside of a frame of this aspect ratio (width / height)
+    pub matte_ratio: Option<f32>,
+    pub matte_opacity: f32,
     pub show_frame: bool,
     pub current_channel: ColorChannel,
+    /// Per-pixel expression of the expression channel view, like `r - g`
+    pub channel_expression: String,
+    /// Show normal maps lit from `normal_light` instead of their colors
+    pub normal_relight: bool,
+    /// Direction towards the light, of unit length
+    pub normal_light: [f32; 3],
+    /// Normal maps have green pointing down, like in DirectX
+    pub normal_green_down: bool,
+    /// The printer ICC profile to soft proof with
+    pub proof_profile: Option<PathBuf>,
+    /// Highlight colors the printer can't reproduce while soft proofing
+    pub gamut_warning: bool,
     /// How much to scale SVG images when rendering
     pub svg_scale: f32,
     pub zen_mode: bool,
     pub theme: ColorTheme,
-    pub linear_mag_filter: bool,
+    /// How images are sampled when zoomed, unless they have their own mode
+    pub sampling: Sampling,
+    /// Images with their own sampling mode
+    pub image_sampling: HashMap<PathBuf, Sampling>,
     pub fit_image_on_window_resize: bool,
     pub zoom_multiplier: f32,
+    /// How far the pan shortcuts move the image, in pixels
+    pub pan_step: f32,
+    /// How much the zoom shortcuts zoom
+    pub key_zoom_step: f32,
+    /// Measured physical pixels per inch of the monitor, instead of the one the scale factor implies
+    pub monitor_ppi: Option<f32>,
+    /// Width of the pages while scroll reading, relative to the window
+    pub scroll_reader_width: f32,
+    /// Zoom steps land on whole zoom levels like 2x when they get near, for pixel art
+    pub snap_zoom: bool,
+    /// Zoom in when scrolling down, and go to the next image with the modifier when scrolling up
+    pub invert_scroll: bool,
+    /// Evaluate per-pixel edits in a shader while adjusting them
+    pub gpu_edits: bool,
+    /// Copy EXIF, XMP and ICC data of the original file when saving
+    pub keep_metadata: bool,
+    /// The edit preset applied by the shortcut
+    pub last_preset: Option<String>,
+    /// Commands the current file can be sent to
+    pub external_commands: Vec<ExternalCommand>,
+    /// Include files with unknown or missing extensions if their content looks like an image
+    pub load_all_files: bool,
+    /// Never change, move or delete files, e.g. for shared archives or kiosks
+    pub read_only: bool,
+    /// Show all images blurred until the reveal key is held
+    pub blur_all: bool,
+    /// Folders whose images, also in subfolders, are shown blurred
+    pub blurred_folders: HashSet<PathBuf>,
+    /// Show a magnified area and the pixel value next to the cursor
+    pub loupe_enabled: bool,
+    /// Screen points per image pixel in the loupe
+    pub loupe_zoom: f32,
+    /// Width and height of the loupe in screen points
+    pub loupe_size: f32,
+    /// Keep decoded large images on disk to open them faster next time
+    pub disk_cache: bool,
+    /// Size limit of the disk cache in megabytes
+    pub disk_cache_size: u64,
+    /// Share thumbnails of opened images with file managers
+    pub write_thumbnails: bool,
+    /// Decode large JPEGs at screen size first and load the full image when zooming in or editing
+    pub downsample_on_decode: bool,
+    /// Remember view changes made in a folder and apply them when it is opened again
+    pub remember_folder_views: bool,
+    pub folder_views: HashMap<PathBuf, FolderView>,
+    pub active_folder_view: Option<ActiveFolderView>,
+    /// Offer playback of numbered image sequences
+    pub detect_sequences: bool,
+    /// Where viewport captures are saved
+    pub capture_folder: PathBuf,
+    /// Folder that is watched for new images, like camera tethering or render output
+    pub hot_folder: Option<PathBuf>,
+    /// Play a sound when an image arrives in the hot folder
+    pub hot_folder_sound: bool,
+    /// Bring the window to the front when an image arrives in the hot folder
+    pub hot_folder_raise: bool,
+    /// The last MJPEG stream that was opened
+    pub stream_url: String,
+    /// The last remote folder that was opened
+    pub remote_url: String,
+    pub remote_credentials: crate::remote::RemoteCredentials,
+    /// Folders of the number keys 1 to 9 when sorting
+    pub sort_targets: Vec<crate::sorting::SortTarget>,
+    pub displays: Vec<Monitor>,
+    /// Index of the display to go fullscreen on, the current one if not set
+    pub fullscreen_display: Option<usize>,
+    /// Quitting hides the window, and the tray icon, the hotkey or `oculante --show` show it
+    /// again
+    pub background_mode: bool,
+    /// Global hotkey that shows the window in background mode, like `Ctrl+Alt+O`
+    pub background_hotkey: String,
+    pub update_channel: UpdateChannel,
+    /// Look for updates in the background once a day. Off until the user turns it on, as it
+    /// contacts GitHub.
+    pub check_for_updates: bool,
+    /// Unix time of the last background update check
+    pub last_update_check: u64,
+    /// Stronger text and outline colors in the UI
+    pub high_contrast: bool,
+    /// Bigger buttons and more space between them
+    pub large_targets: bool,
+    /// Show and hide UI elements without transitions
+    pub reduce_motion: bool,
 }
 
 impl Default for PersistentSettings {
@@ -63,12 +393,17 @@ impl Default for PersistentSettings {
             vsync: true,
             force_redraw: false,
             shortcuts: Shortcuts::default_keys(),
-            keep_view: Default::default(),
+            zoom_policy: ZoomPolicy::FitIfLarger,
             max_cache: 30,
             show_scrub_bar: Default::default(),
             wrap_folder: true,
+            sharpness_sort: false,
+            min_sharpness: 0.,
+            max_hash_distance: 10,
             keep_edits: Default::default(),
             favourite_images: Default::default(),
+            metadata_sidecars: false,
+            recent_geotags: Default::default(),
             recent_images: Default::default(),
             title_format: "{APP} | {VERSION} | {FULLPATH}".into(),
             info_enabled: Default::default(),
@@ -77,48 +412,325 @@ impl Default for PersistentSettings {
             last_open_directory: std::env::current_dir().unwrap_or_default(),
             show_checker_background: Default::default(),
             show_minimap: Default::default(),
+            minimap_corner: Default::default(),
+            guides: Default::default(),
+            matte_ratio: None,
+            matte_opacity: 0.8,
             show_frame: Default::default(),
             current_channel: ColorChannel::Rgba,
+            channel_expression: "r - g".into(),
+            normal_relight: true,
+            normal_light: [-0.5, 0.5, 0.707],
+            normal_green_down: false,
+            proof_profile: None,
+            gamut_warning: true,
             svg_scale: 1.0,
             zen_mode: false,
             theme: ColorTheme::Dark,
-            linear_mag_filter: false,
+            sampling: Sampling::Auto,
+            image_sampling: Default::default(),
             fit_image_on_window_resize: false,
             zoom_multiplier: 1.0,
+            pan_step: 40.,
+            key_zoom_step: 3.5,
+            monitor_ppi: None,
+            scroll_reader_width: 0.6,
+            snap_zoom: false,
+            invert_scroll: false,
+            gpu_edits: true,
+            keep_metadata: true,
+            last_preset: None,
+            external_commands: Default::default(),
+            load_all_files: false,
+            read_only: false,
+            blur_all: false,
+            blurred_folders: Default::default(),
+            loupe_enabled: false,
+            loupe_zoom: 8.,
+            loupe_size: 160.,
+            disk_cache: false,
+            disk_cache_size: 2048,
+            write_thumbnails: false,
+            downsample_on_decode: false,
+            remember_folder_views: false,
+            folder_views: Default::default(),
+            active_folder_view: None,
+            detect_sequences: true,
+            capture_folder: dirs::picture_dir()
+                .or_else(dirs::home_dir)
+                .unwrap_or_default()
+                .join("oculante"),
+            hot_folder: None,
+            hot_folder_sound: false,
+            hot_folder_raise: false,
+            stream_url: Default::default(),
+            remote_url: Default::default(),
+            remote_credentials: Default::default(),
+            sort_targets: Default::default(),
+            displays: Default::default(),
+            fullscreen_display: None,
+            background_mode: false,
+            background_hotkey: "Ctrl+Alt+O".into(),
+            update_channel: UpdateChannel::Stable,
+            check_for_updates: false,
+            last_update_check: 0,
+            high_contrast: false,
+            large_targets: false,
+            reduce_motion: false,
         }
     }
 }
 
+/// Settings of earlier versions that were replaced, read to carry the choices over
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct LegacySettings {
+    /// Replaced by `zoom_policy`
+    keep_view: Option<bool>,
+    /// Replaced by `sampling`
+    linear_mag_filter: Option<bool>,
+}
+
 impl PersistentSettings {
     pub fn load() -> Result<Self> {
-        let local_dir = dirs::data_local_dir().ok_or(anyhow!("Can't get local dir"))?;
-        let f = File::open(local_dir.join(".oculante"))?;
-        Ok(serde_json::from_reader::<_, PersistentSettings>(f)?)
+        let mut synced = SYNCED.lock().map_err(|_| anyhow!("Settings are locked"))?;
+        let f = File::open(settings_file()?)?;
+        let settings = Self::from_reader(f)?;
+        *synced = modified();
+        Ok(settings)
+    }
+
+    /// Read settings, including the choices of settings that were replaced since they were saved
+    fn from_reader(reader: impl std::io::Read) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        let legacy = serde_json::from_value::<LegacySettings>(value.clone()).unwrap_or_default();
+        let mut settings = serde_json::from_value::<PersistentSettings>(value)?;
+        if legacy.keep_view == Some(true) {
+            settings.zoom_policy = ZoomPolicy::Keep;
+        }
+        // nearest was the default, which can't be told apart from a choice, so it gets the new one
+        if legacy.linear_mag_filter == Some(true) {
+            settings.sampling = Sampling::Linear;
+        }
+        Ok(settings)
     }
 
     // save settings in a thread so we don't block
     pub fn save(&self) {
         let settings = self.clone();
         std::thread::spawn(move || {
-            _ = save(&settings);
+            if let Err(e) = save(&settings) {
+                log::error!("Could not save the settings: {e}");
+            }
         });
     }
 
+    /// Save before quitting. Favourites another app added meanwhile are kept.
     pub fn save_blocking(&self) {
-        _ = save(&self);
+        let mut settings = self.clone();
+        if let Some(theirs) = changed_favourites() {
+            settings.favourite_images.extend(theirs);
+            mark_synced();
+        }
+        if let Err(e) = save(&settings) {
+            log::error!("Could not save the settings: {e}");
+        }
+    }
+
+    /// Switch to the view remembered for `folder`. Changes to the view of the previous folder are
+    /// remembered.
+    pub fn enter_folder(&mut self, folder: &Path) {
+        if self
+            .active_folder_view
+            .as_ref()
+            .map(|active| active.folder == folder)
+            .unwrap_or_default()
+        {
+            return;
+        }
+        self.leave_folder();
+        if !self.remember_folder_views {
+            return;
+        }
+        let base = FolderView::from(&*self);
+        if let Some(view) = self.folder_views.get(folder).cloned() {
+            view.apply(self);
+        }
+        self.active_folder_view = Some(ActiveFolderView {
+            folder: folder.to_path_buf(),
+            entered: FolderView::from(&*self),
+            base,
+        });
+    }
+
+    /// Remember changes to the view of the current folder and restore the view outside of it
+    pub fn leave_folder(&mut self) {
+        if let Some(active) = self.active_folder_view.take() {
+            let view = FolderView::from(&*self);
+            if view != active.entered {
+                self.folder_views.insert(active.folder, view);
+            }
+            active.base.apply(self);
+        }
+    }
+
+    /// Write all settings, including shortcuts, to a file, e.g. to use them on another machine.
+    /// The password for remote folders is left out.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let mut settings = self.clone();
+        settings.remote_credentials.password.clear();
+        let f = File::create(path)?;
+        Ok(serde_json::to_writer_pretty(f, &settings)?)
+    }
+
+    /// Read settings written by `export`. The window geometry and the remote password of this
+    /// machine are kept.
+    pub fn import(&self, path: &Path) -> Result<Self> {
+        let f = File::open(path)?;
+        let mut settings = Self::from_reader(f)?;
+        settings.window_geometry = self.window_geometry;
+        settings.remote_credentials.password = self.remote_credentials.password.clone();
+        Ok(settings)
     }
 }
 
-fn save(s: &PersistentSettings) -> Result<()> {
+fn settings_file() -> Result<PathBuf> {
     let local_dir = dirs::data_local_dir().ok_or(anyhow!("Can't get local dir"))?;
-    let f = File::create(local_dir.join(".oculante"))?;
-    Ok(serde_json::to_writer_pretty(f, s)?)
+    Ok(local_dir.join(".oculante"))
+}
+
+fn modified() -> Option<SystemTime> {
+    modified_at(&settings_file().ok()?)
+}
+
+fn modified_at(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// The favourites of the settings file if another app changed it since this instance read or
+/// wrote it, e.g. a sync client like Dropbox or Syncthing
+pub fn changed_favourites() -> Option<HashSet<PathBuf>> {
+    let synced = SYNCED.lock().ok()?;
+    if synced.is_none() || *synced == modified() {
+        return None;
+    }
+    let f = File::open(settings_file().ok()?).ok()?;
+    let settings = serde_json::from_reader::<_, PersistentSettings>(f).ok()?;
+    Some(settings.favourite_images)
+}
+
+/// Accept the settings file as it is, so the next save may replace it
+pub fn mark_synced() {
+    if let Ok(mut synced) = SYNCED.lock() {
+        *synced = modified();
+    }
+}
+
+fn save(s: &PersistentSettings) -> Result<()> {
+    // checks for changes wait until the file is written
+    let mut synced = SYNCED.lock().map_err(|_| anyhow!("Settings are locked"))?;
+    write_settings(&settings_file()?, s, &mut synced)
 }
 
-pub fn set_system_theme(ctx: &Context) {
-    match dark_light::detect() {
-        dark_light::Mode::Dark => ctx.set_visuals(Visuals::dark()),
-        dark_light::Mode::Light => ctx.set_visuals(Visuals::light()),
-        dark_light::Mode::Default => ctx.set_visuals(Visuals::dark()),
+/// Write the settings to `file`, which was last read or written at `synced`. If another app changed
+/// it since, its favourites are written instead of ours until they are merged, and `synced` stays,
+/// so the merge is still offered.
+pub fn write_settings(
+    file: &Path,
+    s: &PersistentSettings,
+    synced: &mut Option<SystemTime>,
+) -> Result<()> {
+    let theirs = if synced.is_some() && *synced != modified_at(file) {
+        File::open(file)
+            .ok()
+            .and_then(|f| serde_json::from_reader::<_, PersistentSettings>(f).ok())
+            .map(|theirs| theirs.favourite_images)
+    } else {
+        None
+    };
+    let f = File::create(file)?;
+    match theirs {
+        Some(favourite_images) => {
+            let settings = PersistentSettings {
+                favourite_images,
+                ..s.clone()
+            };
+            serde_json::to_writer_pretty(f, &settings)?;
+        }
+        None => {
+            serde_json::to_writer_pretty(f, s)?;
+            *synced = modified_at(file);
+        }
+    }
+    Ok(())
+}
+
+fn theme_visuals(theme: &ColorTheme) -> Visuals {
+    match theme {
+        ColorTheme::Light => Visuals::light(),
+        ColorTheme::Dark => Visuals::dark(),
+        ColorTheme::System => match dark_light::detect() {
+            dark_light::Mode::Light => Visuals::light(),
+            dark_light::Mode::Dark | dark_light::Mode::Default => Visuals::dark(),
+        },
+    }
+}
+
+/// Black and white instead of shades of gray, with thicker outlines
+pub fn high_contrast(visuals: &mut Visuals) {
+    let (fg, bg) = if visuals.dark_mode {
+        (Color32::WHITE, Color32::BLACK)
+    } else {
+        (Color32::BLACK, Color32::WHITE)
+    };
+    visuals.override_text_color = Some(fg);
+    visuals.panel_fill = bg;
+    visuals.window_fill = bg;
+    visuals.extreme_bg_color = bg;
+    visuals.faint_bg_color = bg;
+    visuals.window_stroke = Stroke::new(2., fg);
+    let widgets = &mut visuals.widgets;
+    for widget in [
+        &mut widgets.noninteractive,
+        &mut widgets.inactive,
+        &mut widgets.hovered,
+        &mut widgets.active,
+        &mut widgets.open,
+    ]
+    .iter_mut()
+    {
+        widget.fg_stroke = Stroke::new(widget.fg_stroke.width.max(1.5), fg);
+        widget.bg_stroke = Stroke::new(widget.bg_stroke.width.max(1.5), fg);
+    }
+}
+
+/// Apply the theme, accent color and accessibility settings to the UI
+pub fn apply_style(ctx: &Context, settings: &PersistentSettings) {
+    let mut style = (*ctx.style()).clone();
+    style.visuals = theme_visuals(&settings.theme);
+    let [r, g, b] = settings.accent_color;
+    style.visuals.selection.bg_fill = Color32::from_rgb(r, g, b);
+    let accent_color_luma =
+        (r as f32 * 0.299 + g as f32 * 0.587 + b as f32 * 0.114).clamp(0., 255.) as u8;
+    let accent_color_luma = if accent_color_luma < 80 { 220 } else { 80 };
+    // Set text on highlighted elements
+    style.visuals.selection.stroke = Stroke::new(2.0, Color32::from_gray(accent_color_luma));
+    if settings.high_contrast {
+        high_contrast(&mut style.visuals);
+    }
+
+    style.spacing = Spacing::default();
+    if settings.large_targets {
+        style.spacing.item_spacing = vec2(12., 8.);
+        style.spacing.button_padding = vec2(10., 6.);
+        style.spacing.interact_size = vec2(56., 32.);
+        style.spacing.icon_width = 22.;
+        style.spacing.icon_spacing = 8.;
     }
+    style.animation_time = if settings.reduce_motion {
+        0.
+    } else {
+        Style::default().animation_time
+    };
+    ctx.set_style(style);
 }
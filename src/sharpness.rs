@@ -0,0 +1,73 @@
+//! Sharpness scores for culling: the variance of the Laplacian of the luma, which is low for
+//! blurry or out of focus shots. Images are scored at a fixed size, so scores of different
+//! resolutions compare. Scores are cached in the cache dir.
+
+use crate::cache::{compute_files, FileCache, FileResults};
+use crate::image_loader::open_reduced;
+use anyhow::Result;
+use image::{imageops, RgbaImage};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+/// Images are scored with their longer side scaled down to this
+const ANALYSIS_SIZE: u32 = 1024;
+/// Scores of a folder are sent in batches of this many files
+const BATCH: usize = 16;
+
+/// Scores of files, as sent by `score_files`
+pub type Scores = FileResults<f32>;
+
+pub type SharpnessCache = FileCache<f32>;
+
+/// Name of the cache file
+pub const CACHE_FILE: &str = "sharpness.json";
+
+/// Variance of the 4-neighbour Laplacian of the luma
+pub fn score(img: &RgbaImage) -> f32 {
+    let longest = img.width().max(img.height());
+    let small;
+    let img = if longest > ANALYSIS_SIZE {
+        let factor = ANALYSIS_SIZE as f32 / longest as f32;
+        small = imageops::thumbnail(
+            img,
+            ((img.width() as f32 * factor) as u32).max(1),
+            ((img.height() as f32 * factor) as u32).max(1),
+        );
+        &small
+    } else {
+        img
+    };
+    let (width, height) = img.dimensions();
+    if width < 3 || height < 3 {
+        return 0.;
+    }
+    let luma = img
+        .pixels()
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect::<Vec<_>>();
+    let at = |x: u32, y: u32| luma[(y * width + x) as usize];
+    let (mut sum, mut sum_sq) = (0., 0.);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let laplacian =
+                at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4. * at(x, y);
+            sum += laplacian;
+            sum_sq += laplacian * laplacian;
+        }
+    }
+    let n = ((width - 2) * (height - 2)) as f64;
+    let mean = sum / n;
+    (sum_sq / n - mean * mean) as f32
+}
+
+/// Decode and score a file. Large JPEGs are decoded at a reduced size.
+pub fn score_file(path: &Path) -> Result<f32> {
+    Ok(score(&open_reduced(path, (ANALYSIS_SIZE, ANALYSIS_SIZE))?))
+}
+
+/// Score files on a thread, sending the results in batches. Files that can't be decoded are
+/// skipped. `polling` is dropped when all files are done.
+pub fn score_files(paths: Vec<PathBuf>, sender: Sender<Scores>, polling: Arc<()>) {
+    compute_files(paths, score_file, BATCH, sender, polling);
+}
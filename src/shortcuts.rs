@@ -11,6 +11,7 @@ use serde::{Deserialize, Serialize};
 pub enum InputEvent {
     AlwaysOnTop,
     Fullscreen,
+    NextDisplay,
     InfoMode,
     EditMode,
     NextImage,
@@ -24,6 +25,17 @@ pub enum InputEvent {
     RGBChannel,
     RGBAChannel,
     ResetView,
+    LockView,
+    PlaySequence,
+    NextFrame,
+    PreviousFrame,
+    CaptureViewport,
+    Screenshot,
+    ScreenshotRegion,
+    SoftProof,
+    CompareOriginal,
+    Search,
+    FocusMenu,
     ZoomOut,
     ZoomIn,
     ZoomActualSize,
@@ -31,11 +43,17 @@ pub enum InputEvent {
     ZoomThree,
     ZoomFour,
     ZoomFive,
+    ZoomPrintSize,
+    ZoomNextWhole,
     CompareNext,
     PanLeft,
     PanRight,
     PanUp,
     PanDown,
+    PanPageLeft,
+    PanPageRight,
+    PanPageUp,
+    PanPageDown,
     DeleteFile,
     LosslessRotateRight,
     LosslessRotateLeft,
@@ -44,6 +62,14 @@ pub enum InputEvent {
     Browse,
     Quit,
     ZenMode,
+    ApplyPreset,
+    ReviewAccept,
+    ReviewReject,
+    ToggleSorting,
+    ToggleFavourite,
+    RevealImage,
+    ToggleLoupe,
+    ScrollReading,
 }
 
 pub type Shortcuts = BTreeMap<InputEvent, SimultaneousKeypresses>;
@@ -105,7 +131,19 @@ impl ShortcutExt for Shortcuts {
         let mut s = Shortcuts::default()
             .add_key(InputEvent::AlwaysOnTop, "T")
             .add_key(InputEvent::Fullscreen, "F")
+            .add_keys(InputEvent::NextDisplay, &["LShift", "F"])
             .add_key(InputEvent::ResetView, "V")
+            .add_key(InputEvent::LockView, "L")
+            .add_key(InputEvent::PlaySequence, "Space")
+            .add_key(InputEvent::NextFrame, "Period")
+            .add_key(InputEvent::PreviousFrame, "Comma")
+            .add_key(InputEvent::CaptureViewport, "F12")
+            .add_keys(InputEvent::Screenshot, &["LShift", "F12"])
+            .add_keys(InputEvent::ScreenshotRegion, &["LControl", "F12"])
+            .add_key(InputEvent::FocusMenu, "F10")
+            .add_keys(InputEvent::SoftProof, &["LControl", "Y"])
+            .add_key(InputEvent::CompareOriginal, "Backslash")
+            .add_keys(InputEvent::Search, &["LControl", "F"])
             .add_key(InputEvent::Quit, "Q")
             .add_key(InputEvent::InfoMode, "I")
             .add_key(InputEvent::EditMode, "E")
@@ -127,9 +165,19 @@ impl ShortcutExt for Shortcuts {
             .add_key(InputEvent::ZoomThree, "Key3")
             .add_key(InputEvent::ZoomFour, "Key4")
             .add_key(InputEvent::ZoomFive, "Key5")
+            .add_key(InputEvent::ZoomPrintSize, "Key0")
+            .add_key(InputEvent::ZoomNextWhole, "W")
             .add_key(InputEvent::LosslessRotateLeft, "LBracket")
             .add_key(InputEvent::LosslessRotateRight, "RBracket")
             .add_key(InputEvent::ZenMode, "Z")
+            .add_key(InputEvent::ApplyPreset, "P")
+            .add_key(InputEvent::ReviewAccept, "K")
+            .add_key(InputEvent::ReviewReject, "X")
+            .add_keys(InputEvent::ToggleSorting, &["LShift", "S"])
+            .add_key(InputEvent::ToggleFavourite, "M")
+            .add_key(InputEvent::RevealImage, "H")
+            .add_key(InputEvent::ToggleLoupe, "O")
+            .add_keys(InputEvent::ScrollReading, &["LShift", "V"])
             .add_key(InputEvent::DeleteFile, "Delete")
             // .add_key(InputEvent::Browse, "F1") // FIXME: As Shortcuts is a HashMap, only the newer key-sequence will be registered
             .add_keys(InputEvent::Browse, &["LControl", "O"])
@@ -137,6 +185,10 @@ impl ShortcutExt for Shortcuts {
             .add_keys(InputEvent::PanLeft, &["LShift", "Left"])
             .add_keys(InputEvent::PanDown, &["LShift", "Down"])
             .add_keys(InputEvent::PanUp, &["LShift", "Up"])
+            .add_keys(InputEvent::PanPageRight, &["LControl", "Right"])
+            .add_keys(InputEvent::PanPageLeft, &["LControl", "Left"])
+            .add_keys(InputEvent::PanPageDown, &["LControl", "Down"])
+            .add_keys(InputEvent::PanPageUp, &["LControl", "Up"])
             .add_keys(InputEvent::Paste, &["LControl", "V"])
             .add_keys(InputEvent::Copy, &["LControl", "C"]);
         #[cfg(target_os = "macos")]
@@ -194,27 +246,8 @@ pub fn key_pressed(app: &mut App, state: &mut OculanteState, command: InputEvent
         }
 
         // make sure all modifiers are down
-        for m in keys.modifiers() {
-            if m.contains("Shift") {
-                if !app.keyboard.shift() {
-                    return false;
-                }
-            }
-            if m.contains("Alt") {
-                if !app.keyboard.alt() {
-                    return false;
-                }
-            }
-            if m.contains("Control") {
-                if !app.keyboard.ctrl() {
-                    return false;
-                }
-            }
-            if m.contains("Win") {
-                if !app.keyboard.logo() {
-                    return false;
-                }
-            }
+        if !modifiers_down(app, keys) {
+            return false;
         }
 
         // debug!("Down {:?}", app.keyboard.down);
@@ -276,6 +309,59 @@ pub fn key_pressed(app: &mut App, state: &mut OculanteState, command: InputEvent
     false
 }
 
+/// Whether the keys of a command are held down, for commands that last while the keys are held
+pub fn key_held(app: &App, state: &OculanteState, command: InputEvent) -> bool {
+    if state.key_grab {
+        return false;
+    }
+    match state.persistent_settings.shortcuts.get(&command) {
+        Some(keys) => {
+            modifiers_down(app, keys)
+                && keys.alphanumeric().iter().all(|key| {
+                    app.keyboard
+                        .down
+                        .keys()
+                        .any(|down| &format!("{:?}", down) == key)
+                })
+        }
+        None => false,
+    }
+}
+
+fn modifiers_down(app: &App, keys: &SimultaneousKeypresses) -> bool {
+    for m in keys.modifiers() {
+        if m.contains("Shift") && !app.keyboard.shift() {
+            return false;
+        }
+        if m.contains("Alt") && !app.keyboard.alt() {
+            return false;
+        }
+        if m.contains("Control") && !app.keyboard.ctrl() {
+            return false;
+        }
+        if m.contains("Win") && !app.keyboard.logo() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Check a key combination that is not bound to an `InputEvent`, such as a script shortcut
+pub fn keys_pressed(app: &App, state: &OculanteState, keys: &SimultaneousKeypresses) -> bool {
+    if state.key_grab || keys.is_empty() || app.keyboard.down.len() != keys.len() {
+        return false;
+    }
+    if !modifiers_down(app, keys) {
+        return false;
+    }
+    keys.alphanumeric().iter().any(|key| {
+        app.keyboard
+            .pressed
+            .iter()
+            .any(|pressed| &format!("{:?}", pressed) == key)
+    })
+}
+
 pub fn lookup(shortcuts: &Shortcuts, command: &InputEvent) -> String {
     if let Some(keys) = shortcuts.get(&command) {
         return keypresses_as_string(keys);
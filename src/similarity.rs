@@ -0,0 +1,56 @@
+//! Finding similar images in a folder, like edited variants or resized copies. Images are compared
+//! by a perceptual difference hash, which changes little with scaling, compression or color edits.
+//! Hashes are cached in the cache dir.
+
+use crate::cache::{compute_files, FileCache, FileResults};
+use crate::image_loader::open_reduced;
+use anyhow::Result;
+use image::{imageops, RgbaImage};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+/// Images are decoded at about this size for hashing
+const ANALYSIS_SIZE: u32 = 256;
+/// Hashes of a folder are sent in batches of this many files
+const BATCH: usize = 32;
+
+/// Hashes of files, as sent by `hash_files`
+pub type Hashes = FileResults<u64>;
+
+pub type HashCache = FileCache<u64>;
+
+/// Name of the cache file
+pub const CACHE_FILE: &str = "hashes.json";
+
+/// 64 bit difference hash: for each pixel of a 9x8 thumbnail, whether it is brighter than its
+/// right neighbour
+pub fn dhash(img: &RgbaImage) -> u64 {
+    let small = imageops::thumbnail(img, 9, 8);
+    let luma = |x: u32, y: u32| {
+        let p = small.get_pixel(x, y);
+        (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32) * p[3] as f32 / 255.
+    };
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = hash << 1 | (luma(x, y) > luma(x + 1, y)) as u64;
+        }
+    }
+    hash
+}
+
+/// Number of differing bits. Below 10 of 64 images usually look alike.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+pub fn hash_file(path: &Path) -> Result<u64> {
+    Ok(dhash(&open_reduced(path, (ANALYSIS_SIZE, ANALYSIS_SIZE))?))
+}
+
+/// Hash files on a thread, sending the results in batches. Files that can't be decoded are
+/// skipped. `polling` is dropped when all files are done.
+pub fn hash_files(paths: Vec<PathBuf>, sender: Sender<Hashes>, polling: Arc<()>) {
+    compute_files(paths, hash_file, BATCH, sender, polling);
+}
@@ -0,0 +1,122 @@
+//! Estimated file sizes of the current image saved as JPEG, WebP or AVIF at several qualities.
+//! A downscaled proxy is encoded, and its size scaled up by the number of pixels.
+
+use anyhow::{bail, Result};
+use image::{imageops::FilterType, RgbaImage};
+use std::path::PathBuf;
+use strum::Display;
+use strum_macros::EnumIter;
+
+/// Longer side of the encoded proxy
+pub const PROXY_SIZE: u32 = 512;
+/// Qualities to estimate, from 0 to 100
+pub const QUALITIES: [u8; 4] = [50, 75, 85, 95];
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumIter)]
+pub enum Codec {
+    #[strum(serialize = "JPEG")]
+    Jpeg,
+    #[strum(serialize = "WebP")]
+    Webp,
+    #[cfg(feature = "avif_encoder")]
+    #[strum(serialize = "AVIF")]
+    Avif,
+}
+
+impl Codec {
+    /// The encoded file
+    pub fn encode(&self, img: &RgbaImage, quality: u8) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        match self {
+            Self::Jpeg => {
+                // JPEG has no alpha
+                let rgb = image::DynamicImage::ImageRgba8(img.clone()).into_rgb8();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, quality)
+                    .encode_image(&rgb)?;
+            }
+            Self::Webp => {
+                let mut output: *mut u8 = std::ptr::null_mut();
+                let size = unsafe {
+                    libwebp_sys::WebPEncodeRGBA(
+                        img.as_ptr(),
+                        img.width() as i32,
+                        img.height() as i32,
+                        img.width() as i32 * 4,
+                        quality as f32,
+                        &mut output,
+                    )
+                };
+                if size == 0 || output.is_null() {
+                    bail!("Can't encode WebP");
+                }
+                unsafe {
+                    data.extend_from_slice(std::slice::from_raw_parts(output, size));
+                    libwebp_sys::WebPFree(output as *mut std::ffi::c_void);
+                }
+            }
+            #[cfg(feature = "avif_encoder")]
+            Self::Avif => {
+                use image::ImageEncoder;
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut data, 10, quality)
+                    .write_image(
+                        img.as_raw(),
+                        img.width(),
+                        img.height(),
+                        image::ColorType::Rgba8,
+                    )?;
+            }
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub codec: Codec,
+    pub quality: u8,
+    pub bytes: u64,
+}
+
+/// The image scaled down to fit `PROXY_SIZE`, and how many more pixels the image has
+pub fn proxy(img: &RgbaImage) -> (RgbaImage, f64) {
+    let longer = img.width().max(img.height()).max(1);
+    if longer <= PROXY_SIZE {
+        return (img.clone(), 1.);
+    }
+    let scale = PROXY_SIZE as f64 / longer as f64;
+    let width = ((img.width() as f64 * scale).round() as u32).max(1);
+    let height = ((img.height() as f64 * scale).round() as u32).max(1);
+    let proxy = image::imageops::resize(img, width, height, FilterType::Triangle);
+    let ratio = (img.width() as f64 * img.height() as f64) / (width as f64 * height as f64);
+    (proxy, ratio)
+}
+
+/// Estimates for every codec at every quality. Codecs that fail are skipped.
+pub fn estimate(img: &RgbaImage, codecs: &[Codec]) -> Vec<Estimate> {
+    let (proxy, ratio) = proxy(img);
+    let mut estimates = vec![];
+    for codec in codecs {
+        for quality in QUALITIES {
+            match codec.encode(&proxy, quality) {
+                Ok(data) => estimates.push(Estimate {
+                    codec: *codec,
+                    quality,
+                    bytes: (data.len() as f64 * ratio).round() as u64,
+                }),
+                Err(e) => log::warn!("Can't estimate the size of {codec}: {e}"),
+            }
+        }
+    }
+    estimates
+}
+
+/// State of the size estimate window
+#[derive(Debug, Default)]
+pub struct SizeEstimates {
+    pub enabled: bool,
+    /// The image the estimates are for
+    pub path: Option<PathBuf>,
+    pub estimates: Option<Vec<Estimate>>,
+    /// Estimate again, like after editing the image
+    pub refresh: bool,
+}
@@ -0,0 +1,482 @@
+//! Soft-proofing: show how an image prints, by sending it through a printer ICC profile and back.
+//!
+//! Only ICC v2 LUT based profiles (`mft1`/`mft2` tags) are read, which is what most printer and
+//! paper profiles use. Images are assumed to be sRGB and the relative colorimetric intent is used.
+//! Colors that change by more than `GAMUT_DELTA_E` on the round trip are out of gamut.
+
+use anyhow::{anyhow, bail, Result};
+use image::RgbaImage;
+use rayon::prelude::*;
+use std::path::Path;
+
+/// Grid points per axis of the precomputed transform
+const GRID: usize = 33;
+/// Color difference above which a color counts as out of gamut
+const GAMUT_DELTA_E: f32 = 5.;
+/// Shown instead of out of gamut colors
+const GAMUT_WARNING: [u8; 3] = [255, 0, 255];
+/// Profiles with more CLUT entries than this are rejected
+const MAX_CLUT: usize = 1 << 24;
+
+const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+const SRGB_TO_XYZ_D50: [f32; 9] = [
+    0.436_074_7,
+    0.385_064_9,
+    0.143_080_4,
+    0.222_504_5,
+    0.716_878_6,
+    0.060_616_9,
+    0.013_932_2,
+    0.097_104_5,
+    0.714_173_3,
+];
+const XYZ_D50_TO_SRGB: [f32; 9] = [
+    3.133_856,
+    -1.616_866_7,
+    -0.490_614_6,
+    -0.978_768_4,
+    1.916_141_5,
+    0.033_454,
+    0.071_945_3,
+    -0.228_991_4,
+    1.405_242_7,
+];
+
+fn mul(m: &[f32; 9], v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0] * v[0] + m[1] * v[1] + m[2] * v[2],
+        m[3] * v[0] + m[4] * v[1] + m[5] * v[2],
+        m[6] * v[0] + m[7] * v[1] + m[8] * v[2],
+    ]
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0., 1.);
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    }
+}
+
+pub fn xyz_to_lab(xyz: [f32; 3]) -> [f32; 3] {
+    let f = |t: f32| {
+        if t > 216. / 24389. {
+            t.cbrt()
+        } else {
+            (24389. / 27. * t + 16.) / 116.
+        }
+    };
+    let [x, y, z] = [f(xyz[0] / D50[0]), f(xyz[1] / D50[1]), f(xyz[2] / D50[2])];
+    [116. * y - 16., 500. * (x - y), 200. * (y - z)]
+}
+
+pub fn lab_to_xyz(lab: [f32; 3]) -> [f32; 3] {
+    let y = (lab[0] + 16.) / 116.;
+    let x = y + lab[1] / 500.;
+    let z = y - lab[2] / 200.;
+    let f = |t: f32| {
+        if t > 6. / 29. {
+            t * t * t
+        } else {
+            (116. * t - 16.) * 27. / 24389.
+        }
+    };
+    [f(x) * D50[0], f(y) * D50[1], f(z) * D50[2]]
+}
+
+fn delta_e(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Linear interpolation in a 1D table of values from 0 to 1
+fn curve(table: &[f32], v: f32) -> f32 {
+    if table.len() < 2 {
+        return v;
+    }
+    let pos = v.clamp(0., 1.) * (table.len() - 1) as f32;
+    let i = (pos as usize).min(table.len() - 2);
+    let t = pos - i as f32;
+    table[i] * (1. - t) + table[i + 1] * t
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pcs {
+    Xyz,
+    Lab,
+}
+
+/// A `lut8Type` or `lut16Type` transform
+#[derive(Debug, Clone)]
+pub struct Lut {
+    inputs: usize,
+    outputs: usize,
+    /// Only applied to XYZ input
+    matrix: [f32; 9],
+    input_curves: Vec<Vec<f32>>,
+    grid_points: usize,
+    clut: Vec<f32>,
+    output_curves: Vec<Vec<f32>>,
+    /// 8 bit tables encode Lab differently
+    eight_bit: bool,
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&self, at: usize, len: usize) -> Result<&[u8]> {
+        self.0
+            .get(at..at + len)
+            .ok_or_else(|| anyhow!("The profile is truncated"))
+    }
+    fn u8(&self, at: usize) -> Result<u8> {
+        Ok(self.bytes(at, 1)?[0])
+    }
+    fn u16(&self, at: usize) -> Result<u16> {
+        let b = self.bytes(at, 2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+    fn u32(&self, at: usize) -> Result<u32> {
+        let b = self.bytes(at, 4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn s15f16(&self, at: usize) -> Result<f32> {
+        Ok(self.u32(at)? as i32 as f32 / 65536.)
+    }
+}
+
+impl Lut {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let r = Reader(data);
+        let eight_bit = match r.bytes(0, 4)? {
+            b"mft1" => true,
+            b"mft2" => false,
+            other => bail!(
+                "Unsupported transform type '{}', only ICC v2 LUT profiles are supported",
+                String::from_utf8_lossy(other)
+            ),
+        };
+        let inputs = r.u8(8)? as usize;
+        let outputs = r.u8(9)? as usize;
+        let grid_points = r.u8(10)? as usize;
+        if inputs == 0 || inputs > 8 || outputs == 0 || outputs > 8 || grid_points < 2 {
+            bail!("Invalid transform");
+        }
+        let mut matrix = [0.; 9];
+        for (i, m) in matrix.iter_mut().enumerate() {
+            *m = r.s15f16(12 + i * 4)?;
+        }
+        let (input_entries, output_entries, mut at, size, max) = if eight_bit {
+            (256, 256, 48, 1, 255.)
+        } else {
+            (r.u16(48)? as usize, r.u16(50)? as usize, 52, 2, 65535.)
+        };
+        let mut values = |count: usize| -> Result<Vec<f32>> {
+            let values = (0..count)
+                .map(|i| {
+                    let v = if size == 1 {
+                        r.u8(at + i)? as f32
+                    } else {
+                        r.u16(at + i * 2)? as f32
+                    };
+                    Ok(v / max)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            at += count * size;
+            Ok(values)
+        };
+        let input_curves = (0..inputs)
+            .map(|_| values(input_entries))
+            .collect::<Result<Vec<_>>>()?;
+        let clut_size = grid_points
+            .checked_pow(inputs as u32)
+            .and_then(|size| size.checked_mul(outputs))
+            .filter(|size| *size <= MAX_CLUT)
+            .ok_or_else(|| anyhow!("The color table of the profile is too large"))?;
+        let clut = values(clut_size)?;
+        let output_curves = (0..outputs)
+            .map(|_| values(output_entries))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            inputs,
+            outputs,
+            matrix,
+            input_curves,
+            grid_points,
+            clut,
+            output_curves,
+            eight_bit,
+        })
+    }
+
+    /// Multilinear interpolation in the CLUT
+    fn interpolate(&self, input: &[f32]) -> Vec<f32> {
+        let g = self.grid_points;
+        let mut base = vec![0; self.inputs];
+        let mut frac = vec![0.; self.inputs];
+        for i in 0..self.inputs {
+            let pos = input[i].clamp(0., 1.) * (g - 1) as f32;
+            base[i] = (pos as usize).min(g - 2);
+            frac[i] = pos - base[i] as f32;
+        }
+        let mut out = vec![0.; self.outputs];
+        for corner in 0..(1 << self.inputs) {
+            let mut weight = 1.;
+            let mut index = 0;
+            for i in 0..self.inputs {
+                let upper = corner >> (self.inputs - 1 - i) & 1;
+                weight *= if upper == 1 { frac[i] } else { 1. - frac[i] };
+                index = index * g + base[i] + upper;
+            }
+            if weight == 0. {
+                continue;
+            }
+            for (o, value) in out.iter_mut().enumerate() {
+                *value += weight * self.clut[index * self.outputs + o];
+            }
+        }
+        out
+    }
+
+    /// Transform normalized values
+    pub fn eval(&self, input: &[f32], xyz_input: bool) -> Vec<f32> {
+        let mut values = input.to_vec();
+        if xyz_input && self.inputs == 3 {
+            values = mul(&self.matrix, [values[0], values[1], values[2]]).to_vec();
+        }
+        for (v, curve_table) in values.iter_mut().zip(&self.input_curves) {
+            *v = curve(curve_table, *v);
+        }
+        let mut out = self.interpolate(&values);
+        for (v, curve_table) in out.iter_mut().zip(&self.output_curves) {
+            *v = curve(curve_table, *v);
+        }
+        out
+    }
+
+    fn encode_pcs(&self, pcs: Pcs, lab: [f32; 3]) -> [f32; 3] {
+        match pcs {
+            Pcs::Xyz => {
+                let xyz = lab_to_xyz(lab);
+                let scale = 32768. / 65535.;
+                [xyz[0] * scale, xyz[1] * scale, xyz[2] * scale]
+            }
+            Pcs::Lab if self.eight_bit => [
+                lab[0] / 100.,
+                (lab[1] + 128.) / 255.,
+                (lab[2] + 128.) / 255.,
+            ],
+            // the legacy 16 bit encoding, with 0xFF00 as L 100
+            Pcs::Lab => [
+                lab[0] / 100. * 65280. / 65535.,
+                (lab[1] + 128.) * 256. / 65535.,
+                (lab[2] + 128.) * 256. / 65535.,
+            ],
+        }
+    }
+
+    fn decode_pcs(&self, pcs: Pcs, v: &[f32]) -> [f32; 3] {
+        match pcs {
+            Pcs::Xyz => {
+                let scale = 65535. / 32768.;
+                xyz_to_lab([v[0] * scale, v[1] * scale, v[2] * scale])
+            }
+            Pcs::Lab if self.eight_bit => [v[0] * 100., v[1] * 255. - 128., v[2] * 255. - 128.],
+            Pcs::Lab => [
+                v[0] * 65535. / 65280. * 100.,
+                v[1] * 65535. / 256. - 128.,
+                v[2] * 65535. / 256. - 128.,
+            ],
+        }
+    }
+}
+
+/// A printer profile and the transforms to and from its device colors
+#[derive(Debug, Clone)]
+pub struct ProofProfile {
+    pub description: String,
+    pcs: Pcs,
+    to_device: Lut,
+    to_pcs: Lut,
+}
+
+impl ProofProfile {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let r = Reader(data);
+        if r.bytes(36, 4)? != b"acsp" {
+            bail!("Not an ICC profile");
+        }
+        let pcs = match r.bytes(20, 4)? {
+            b"XYZ " => Pcs::Xyz,
+            b"Lab " => Pcs::Lab,
+            _ => bail!("Unsupported profile connection space"),
+        };
+        let count = r.u32(128)? as usize;
+        let mut tags = vec![];
+        for i in 0..count {
+            let at = 132 + i * 12;
+            let offset = r.u32(at + 4)? as usize;
+            let size = r.u32(at + 8)? as usize;
+            tags.push((r.bytes(at, 4)?, r.bytes(offset, size)?));
+        }
+        let tag = |names: &[&[u8; 4]]| {
+            names.iter().find_map(|name| {
+                tags.iter()
+                    .find(|(sig, _)| sig == name)
+                    .map(|(_, data)| *data)
+            })
+        };
+        // prefer relative colorimetric, perceptual is always there
+        let to_device = tag(&[b"B2A1", b"B2A0"])
+            .ok_or_else(|| anyhow!("The profile can't convert to device colors"))?;
+        let to_pcs = tag(&[b"A2B1", b"A2B0"])
+            .ok_or_else(|| anyhow!("The profile can't convert from device colors"))?;
+        let description = tag(&[b"desc"])
+            .and_then(|desc| {
+                // textDescriptionType, the ASCII part
+                let len = Reader(desc).u32(8).ok()? as usize;
+                let text = desc.get(12..12 + len)?;
+                Some(
+                    String::from_utf8_lossy(text)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                )
+            })
+            .unwrap_or_default();
+        let profile = Self {
+            description,
+            pcs,
+            to_device: Lut::parse(to_device)?,
+            to_pcs: Lut::parse(to_pcs)?,
+        };
+        if profile.to_device.inputs != 3 || profile.to_pcs.outputs != 3 {
+            bail!("Invalid profile");
+        }
+        if profile.to_device.outputs != profile.to_pcs.inputs {
+            bail!("The transforms of the profile don't match");
+        }
+        Ok(profile)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// The printed version of a Lab color
+    pub fn round_trip(&self, lab: [f32; 3]) -> [f32; 3] {
+        let pcs = self.to_device.encode_pcs(self.pcs, lab);
+        let device = self.to_device.eval(&pcs, self.pcs == Pcs::Xyz);
+        let back = self.to_pcs.eval(&device, false);
+        self.to_pcs.decode_pcs(self.pcs, &back)
+    }
+}
+
+/// The transform of sRGB colors through a printer profile, sampled on a grid
+#[derive(Debug, Clone)]
+pub struct SoftProof {
+    pub description: String,
+    /// Proofed sRGB values from 0 to 1 and whether the color is out of gamut
+    table: Vec<([f32; 3], bool)>,
+}
+
+impl SoftProof {
+    pub fn new(profile: &ProofProfile) -> Self {
+        let step = 1. / (GRID - 1) as f32;
+        let table = (0..GRID * GRID * GRID)
+            .into_par_iter()
+            .map(|i| {
+                let rgb = [
+                    (i / (GRID * GRID)) as f32 * step,
+                    (i / GRID % GRID) as f32 * step,
+                    (i % GRID) as f32 * step,
+                ];
+                let linear = [
+                    srgb_to_linear(rgb[0]),
+                    srgb_to_linear(rgb[1]),
+                    srgb_to_linear(rgb[2]),
+                ];
+                let lab = xyz_to_lab(mul(&SRGB_TO_XYZ_D50, linear));
+                let printed = profile.round_trip(lab);
+                let out = mul(&XYZ_D50_TO_SRGB, lab_to_xyz(printed));
+                (
+                    [
+                        linear_to_srgb(out[0]),
+                        linear_to_srgb(out[1]),
+                        linear_to_srgb(out[2]),
+                    ],
+                    delta_e(lab, printed) > GAMUT_DELTA_E,
+                )
+            })
+            .collect();
+        Self {
+            description: profile.description.clone(),
+            table,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::new(&ProofProfile::load(path)?))
+    }
+
+    /// The proofed color of an sRGB pixel, and whether it is out of gamut
+    pub fn proof(&self, rgb: [u8; 3]) -> ([u8; 3], bool) {
+        let scale = (GRID - 1) as f32 / 255.;
+        let pos = [
+            rgb[0] as f32 * scale,
+            rgb[1] as f32 * scale,
+            rgb[2] as f32 * scale,
+        ];
+        let base = pos.map(|p| (p as usize).min(GRID - 2));
+        let frac = [
+            pos[0] - base[0] as f32,
+            pos[1] - base[1] as f32,
+            pos[2] - base[2] as f32,
+        ];
+        let mut out = [0.; 3];
+        for corner in 0..8 {
+            let offset = [corner >> 2 & 1, corner >> 1 & 1, corner & 1];
+            let weight = (0..3)
+                .map(|i| {
+                    if offset[i] == 1 {
+                        frac[i]
+                    } else {
+                        1. - frac[i]
+                    }
+                })
+                .product::<f32>();
+            let index = (base[0] + offset[0]) * GRID * GRID
+                + (base[1] + offset[1]) * GRID
+                + base[2]
+                + offset[2];
+            for (o, v) in out.iter_mut().zip(self.table[index].0.iter()) {
+                *o += weight * v;
+            }
+        }
+        let nearest = pos.map(|p| p.round() as usize);
+        let out_of_gamut = self.table[nearest[0] * GRID * GRID + nearest[1] * GRID + nearest[2]].1;
+        (out.map(|v| (v * 255.).round() as u8), out_of_gamut)
+    }
+
+    /// The image as it would print, with out of gamut colors highlighted if `gamut_warning` is set
+    pub fn apply(&self, img: &RgbaImage, gamut_warning: bool) -> RgbaImage {
+        let mut proofed = img.clone();
+        proofed.par_chunks_mut(4).for_each(|pixel| {
+            let (rgb, out_of_gamut) = self.proof([pixel[0], pixel[1], pixel[2]]);
+            let rgb = if gamut_warning && out_of_gamut {
+                GAMUT_WARNING
+            } else {
+                rgb
+            };
+            pixel[..3].copy_from_slice(&rgb);
+        });
+        proofed
+    }
+}
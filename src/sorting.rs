@@ -0,0 +1,73 @@
+//! Fast triage: while sorting, the number keys 1 to 9 move or copy the current image to a
+//! folder each, and the next image is shown.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The folder of a number key
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortTarget {
+    /// Empty if the key is not used
+    pub folder: PathBuf,
+    /// Copy instead of moving, so the image stays in the folder
+    pub copy: bool,
+}
+
+/// The target index of a number key, like `Key1` or `Numpad1`
+pub fn sort_key(key: &str) -> Option<usize> {
+    let digit = key
+        .strip_prefix("Key")
+        .or_else(|| key.strip_prefix("Numpad"))?;
+    match digit.parse::<usize>() {
+        Ok(n @ 1..=9) => Some(n - 1),
+        _ => None,
+    }
+}
+
+/// A free path for `file` in `folder`. Names that are taken get a number, like `a (1).png`.
+pub fn target_path(file: &Path, folder: &Path) -> PathBuf {
+    let name = file.file_name().unwrap_or_default();
+    let mut target = folder.join(name);
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 1;
+    while target.exists() {
+        target = folder.join(format!("{stem} ({n}){extension}"));
+        n += 1;
+    }
+    target
+}
+
+/// Move or copy `file` into the folder of `target`, which is created if needed. Returns the new
+/// path.
+pub fn sort_file(file: &Path, target: &SortTarget) -> Result<PathBuf> {
+    std::fs::create_dir_all(&target.folder)
+        .with_context(|| format!("Can't create {}", target.folder.display()))?;
+    let destination = target_path(file, &target.folder);
+    if target.copy {
+        std::fs::copy(file, &destination)?;
+    } else {
+        match std::fs::rename(file, &destination) {
+            // renaming fails across drives
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                std::fs::copy(file, &destination)?;
+                std::fs::remove_file(file)?;
+            }
+            result => result.with_context(|| format!("Can't move {}", file.display()))?,
+        }
+    }
+    Ok(destination)
+}
+
+/// State of the sorting window
+#[derive(Debug, Default)]
+pub struct Sorting {
+    pub enabled: bool,
+    /// The number keys sort instead of zooming
+    pub active: bool,
+}
@@ -0,0 +1,158 @@
+//! Thumbnail cache following the freedesktop.org thumbnail spec.
+//!
+//! Thumbnails are PNG files named after the MD5 hash of the file URI and tagged with the URI and
+//! modification time of the original, so stale ones are detected. On Linux and the BSDs they are
+//! stored in `$XDG_CACHE_HOME/thumbnails` and shared with file managers. Other systems use the same
+//! layout in oculante's own cache dir.
+
+use anyhow::{anyhow, Context, Result};
+use image::RgbaImage;
+use log::debug;
+use md5::{Digest, Md5};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The sizes defined by the spec
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbnailSize {
+    Normal,
+    Large,
+    XLarge,
+    XXLarge,
+}
+
+impl ThumbnailSize {
+    /// Largest side of a thumbnail of this size
+    pub fn pixels(&self) -> u32 {
+        match self {
+            Self::Normal => 128,
+            Self::Large => 256,
+            Self::XLarge => 512,
+            Self::XXLarge => 1024,
+        }
+    }
+
+    fn dir_name(&self) -> &str {
+        match self {
+            Self::Normal => "normal",
+            Self::Large => "large",
+            Self::XLarge => "x-large",
+            Self::XXLarge => "xx-large",
+        }
+    }
+}
+
+/// The shared thumbnail folder, passed as `cache` to the other functions
+pub fn dir() -> Result<PathBuf> {
+    let cache = dirs::cache_dir().ok_or(anyhow!("Can't get cache dir"))?;
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        Ok(cache.join("thumbnails"))
+    } else {
+        Ok(cache.join("oculante").join("thumbnails"))
+    }
+}
+
+/// The `file://` URI of a file, escaped like file managers do
+pub fn uri(path: &Path) -> Result<String> {
+    let path = std::fs::canonicalize(path)?;
+    let path = path.to_string_lossy().replace('\\', "/");
+    // canonical windows paths are verbatim paths
+    let path = path.trim_start_matches("//?/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    Ok(uri)
+}
+
+/// Modification time of a file in seconds, as stored in `Thumb::MTime`
+fn mtime(path: &Path) -> Result<u64> {
+    Ok(std::fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs())
+}
+
+/// Where the thumbnail of a file is stored in `cache`
+pub fn thumbnail_path(cache: &Path, path: &Path, size: ThumbnailSize) -> Result<PathBuf> {
+    let hash = Md5::digest(uri(path)?.as_bytes());
+    Ok(cache.join(size.dir_name()).join(format!("{hash:x}.png")))
+}
+
+/// Load an existing thumbnail if it is still valid for the file
+pub fn get(cache: &Path, path: &Path, size: ThumbnailSize) -> Option<RgbaImage> {
+    let data = std::fs::read(thumbnail_path(cache, path, size).ok()?).ok()?;
+    let reader = png::Decoder::new(Cursor::new(&data)).read_info().ok()?;
+    let text = |key: &str| {
+        reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .find(|t| t.keyword == key)
+            .map(|t| t.text.clone())
+    };
+    if text("Thumb::URI")? != uri(path).ok()?
+        || text("Thumb::MTime")?.parse::<u64>().ok()? != mtime(path).ok()?
+    {
+        debug!("Thumbnail of {} is outdated", path.display());
+        return None;
+    }
+    Some(image::load_from_memory(&data).ok()?.into_rgba8())
+}
+
+/// Scale down a decoded image and store it as the thumbnail of `path`
+pub fn create(
+    cache: &Path,
+    path: &Path,
+    img: &RgbaImage,
+    size: ThumbnailSize,
+) -> Result<RgbaImage> {
+    let (width, height) = img.dimensions();
+    let scale = (size.pixels() as f32 / width.max(height) as f32).min(1.);
+    let thumbnail = image::imageops::thumbnail(
+        img,
+        ((width as f32 * scale) as u32).max(1),
+        ((height as f32 * scale) as u32).max(1),
+    );
+
+    let mut data = vec![];
+    let mut encoder = png::Encoder::new(&mut data, thumbnail.width(), thumbnail.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk("Thumb::URI".into(), uri(path)?)?;
+    encoder.add_text_chunk("Thumb::MTime".into(), mtime(path)?.to_string())?;
+    encoder.add_text_chunk(
+        "Thumb::Size".into(),
+        std::fs::metadata(path)?.len().to_string(),
+    )?;
+    encoder.add_text_chunk("Thumb::Image::Width".into(), width.to_string())?;
+    encoder.add_text_chunk("Thumb::Image::Height".into(), height.to_string())?;
+    encoder.add_text_chunk("Software".into(), "oculante".into())?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(thumbnail.as_raw())?;
+    writer.finish()?;
+
+    // the spec asks for a private folder and an atomic write
+    let target = thumbnail_path(cache, path, size)?;
+    let folder = target.parent().context("Thumbnail has no folder")?;
+    std::fs::create_dir_all(folder)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        _ = std::fs::set_permissions(folder, std::fs::Permissions::from_mode(0o700));
+    }
+    let temp = target.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&temp, data)?;
+    std::fs::rename(temp, &target)?;
+    debug!("Wrote thumbnail {}", target.display());
+    Ok(thumbnail)
+}
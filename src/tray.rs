@@ -0,0 +1,178 @@
+//! Tray icon and global hotkey of background mode. The tray menu shows the hidden window or quits,
+//! the hotkey shows the window. Both send the same requests as later launches do.
+//!
+//! On Linux the tray icon is an AppIndicator, which needs a GTK main loop. It runs on a thread of
+//! its own, as the window toolkit does not use GTK. The hotkey only works on X11.
+
+use crate::instance::InstanceRequest;
+use crate::redraw::Waker;
+use anyhow::{anyhow, Result};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager};
+#[cfg(target_os = "linux")]
+use log::error;
+use std::fmt;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+/// Where events of the tray menu and the hotkey go, and how the hidden window is woken for them.
+/// Their receivers are global, so they are forwarded by one thread each for the whole run.
+static FORWARD_TO: Mutex<Option<(Sender<InstanceRequest>, Waker)>> = Mutex::new(None);
+
+/// The tray icon and hotkey, removed again when dropped
+pub struct Tray {
+    _icon: ShownIcon,
+    hotkeys: GlobalHotKeyManager,
+    hotkey: Option<HotKey>,
+}
+
+impl fmt::Debug for Tray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tray")
+            .field("hotkey", &self.hotkey)
+            .finish()
+    }
+}
+
+impl Tray {
+    /// Show the tray icon and register `hotkey`, like `Ctrl+Alt+O`, if it is not empty. Must be
+    /// called on the main thread once the event loop runs.
+    pub fn new(sender: Sender<InstanceRequest>, waker: Waker, hotkey: &str) -> Result<Self> {
+        forward(sender, waker);
+        let hotkeys = GlobalHotKeyManager::new().map_err(|e| anyhow!("{e}"))?;
+        let hotkey = match hotkey.trim() {
+            "" => None,
+            hotkey => {
+                let hotkey = hotkey
+                    .parse::<HotKey>()
+                    .map_err(|e| anyhow!("{hotkey} is no valid hotkey: {e}"))?;
+                hotkeys
+                    .register(hotkey)
+                    .map_err(|e| anyhow!("Can't register the hotkey: {e}"))?;
+                Some(hotkey)
+            }
+        };
+        Ok(Self {
+            _icon: show_icon()?,
+            hotkeys,
+            hotkey,
+        })
+    }
+}
+
+impl Drop for Tray {
+    fn drop(&mut self) {
+        if let Some(hotkey) = self.hotkey {
+            _ = self.hotkeys.unregister(hotkey);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+type ShownIcon = TrayIcon;
+
+#[cfg(not(target_os = "linux"))]
+fn show_icon() -> Result<ShownIcon> {
+    icon()
+}
+
+/// Stops the GTK thread of the icon when dropped
+#[cfg(target_os = "linux")]
+struct ShownIcon(Arc<AtomicBool>);
+
+#[cfg(target_os = "linux")]
+impl Drop for ShownIcon {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn show_icon() -> Result<ShownIcon> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || {
+        if let Err(e) = gtk::init() {
+            error!("No tray icon without GTK: {e}");
+            return;
+        }
+        let _icon = match icon() {
+            Ok(icon) => icon,
+            Err(e) => {
+                error!("Can't show the tray icon: {e}");
+                return;
+            }
+        };
+        gtk::glib::timeout_add_local(Duration::from_millis(250), move || {
+            if stopped.load(Ordering::Relaxed) {
+                gtk::main_quit();
+                gtk::glib::ControlFlow::Break
+            } else {
+                gtk::glib::ControlFlow::Continue
+            }
+        });
+        gtk::main();
+    });
+    Ok(ShownIcon(stop))
+}
+
+fn icon() -> Result<TrayIcon> {
+    let menu = Menu::new();
+    menu.append_items(&[
+        &MenuItem::with_id("show", "Show oculante", true, None),
+        &MenuItem::with_id("quit", "Quit", true, None),
+    ])?;
+    let image = image::load_from_memory(include_bytes!("../icon.ico"))?
+        .thumbnail(32, 32)
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip("oculante")
+        .with_icon(Icon::from_rgba(image.into_raw(), width, height)?)
+        .build()?)
+}
+
+/// Pass menu and hotkey events on to `sender`, from now on
+fn forward(sender: Sender<InstanceRequest>, waker: Waker) {
+    let mut forward_to = FORWARD_TO.lock().unwrap_or_else(|e| e.into_inner());
+    let started = forward_to.is_some();
+    *forward_to = Some((sender, waker));
+    if started {
+        return;
+    }
+    let send = |request: InstanceRequest| {
+        if let Some((sender, waker)) = FORWARD_TO
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            _ = sender.send(request);
+            waker.wake();
+        }
+    };
+    thread::spawn(move || {
+        while let Ok(event) = MenuEvent::receiver().recv() {
+            match event.id.0.as_str() {
+                "show" => send(InstanceRequest::Show),
+                "quit" => send(InstanceRequest::Quit),
+                _ => (),
+            }
+        }
+    });
+    thread::spawn(move || {
+        while GlobalHotKeyEvent::receiver().recv().is_ok() {
+            send(InstanceRequest::Show);
+        }
+    });
+}
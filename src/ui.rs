@@ -1,16 +1,49 @@
 #[cfg(feature = "file_open")]
 use crate::browse_for_image_path;
+use crate::redraw::Reason;
 use crate::{
+    animation_encoder::AnimationFormat,
     appstate::{ImageGeometry, Message, OculanteState},
-    image_editing::{process_pixels, Channel, GradientStop, ImageOperation, ScaleFilter},
+    colormap::Colormap,
+    compression::{CompressionInfo, CompressionView},
+    dataset::{Dataset, Verdict},
+    dicom::DicomView,
+    favourites::{CollectMode, ListFormat, Resolution},
+    fits::{FitsView, Stretch},
+    gpu::{encode_pixel_ops, PixelShader},
+    guides::{Guide, MATTE_RATIOS},
+    heightmap::{self, HeightMesh, HeightRenderer},
+    image_editing::{
+        auto_enhance, crop_bounds, level_angle, preview_proxy, process_image_preview,
+        process_image_stack, process_pixel_stack, trimmed_range, Anchor, Channel, EditPreset,
+        GradientStop, ImageOperation, ImgOpItem, ScaleFilter, TRIM_TOLERANCE,
+    },
+    loupe,
+    metadata::{EditableMetadata, GeoTag},
+    mjpeg::StreamStatus,
+    montage::Direction,
     paint::PaintStroke,
+    profile,
+    screenshot::CaptureArea,
+    scripting::{run_script, Script},
     set_zoom,
-    settings::{set_system_theme, ColorTheme},
-    shortcuts::{key_pressed, keypresses_as_string, lookup},
+    settings::{apply_style, ColorTheme, MinimapCorner, Sampling, ZoomPolicy},
+    shortcuts::{key_held, key_pressed, keypresses_as_string, lookup, InputEvent},
+    size_estimate::{self, Codec},
     utils::{
-        clipboard_copy, disp_col, disp_col_norm, fix_exif, highlight_bleed, highlight_semitrans,
-        load_image_from_path, next_image, prev_image, send_extended_info, set_title, solo_channel,
-        toggle_fullscreen, unpremult, ColorChannel, ImageExt,
+        alpha_mode, animation_sources, apply_data_view, channel_expression, clipboard_copy,
+        collect_favourites, color_encoding, combine_channels, compute_folder_stats,
+        create_animation, crop_selection, disp_col, disp_col_norm, enable_background_mode,
+        export_animation, export_favourites, filter_dataset_images, find_similar,
+        folder_favourites, hide_to_background, highlight_bleed, highlight_semitrans,
+        import_favourites, index_search, judge_image, list_folder, load_image_from_path,
+        next_image, open_remote, play_stream, prepare_channel_shader, prev_image, refresh_sampling,
+        remember_display, resolve_favourites, run_external_command, save_edits, send_extended_info,
+        set_title, show_clipboard_entry, split_channels, stitch_images, stop_browsing_search,
+        take_screenshot, toggle_clipboard_watch, toggle_favourite, toggle_folder_stats,
+        toggle_fullscreen, toggle_hot_folder, toggle_lock_view, toggle_scroll_reader,
+        toggle_search, toggle_soft_proof, update_display_texture, zoom_to_print_size, AlphaMode,
+        ColorChannel, ColorEncoding, ImageExt, ImageMetadata,
     },
 };
 
@@ -19,9 +52,9 @@ const ICON_SIZE: f32 = 24.;
 use egui_phosphor::regular::*;
 
 use arboard::Clipboard;
-use egui_plot::{Plot, PlotPoints, Points};
+use egui_plot::{Legend, Line, Plot, PlotPoints, Points};
 use image::RgbaImage;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use notan::{
     egui::{self, *},
     prelude::{App, Graphics},
@@ -32,7 +65,7 @@ const PANEL_WIDTH: f32 = 240.0;
 const PANEL_WIDGET_OFFSET: f32 = 10.0;
 
 #[cfg(feature = "turbo")]
-use crate::image_editing::{cropped_range, lossless_tx};
+use crate::image_editing::{cropped_range, lossless_tx, LosslessInfo};
 pub trait EguiExt {
     fn label_i(&mut self, _text: &str) -> Response {
         unimplemented!()
@@ -222,8 +255,10 @@ pub fn info_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
 
                     ui.label(
                         RichText::new(format!(
-                            "{}x{}",
-                            state.image_dimension.0, state.image_dimension.1
+                            "{}x{}{}",
+                            state.image_dimension.0,
+                            state.image_dimension.1,
+                            if state.is_preview { " (preview)" } else { "" }
                         ))
                         .monospace(),
                     );
@@ -341,7 +376,7 @@ pub fn info_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
                                 .player
                                 .load(&path, state.message_channel.0.clone());
                             state.current_path = Some(path);
-                            state.persistent_settings.keep_view = true;
+                            state.keep_view = true;
                         }
                     }
                     if ui.button("Clear").clicked() {
@@ -349,12 +384,33 @@ pub fn info_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
                     }
                 }
                 if state.is_loaded {
-                    state.persistent_settings.keep_view = false;
+                    state.keep_view = false;
                 }
             });
             });
 
             ui.collapsing("Alpha tools", |ui| {
+                if let Some(path) = state.current_path.clone() {
+                    let detected = state.alpha_detected.as_ref().filter(|(p, _)| *p == path).map(|(_, mode)| *mode);
+                    let mut mode = alpha_mode(state);
+                    ui.horizontal(|ui| {
+                        ui.label("Alpha");
+                        egui::ComboBox::from_id_source("alpha_mode")
+                            .selected_text(mode.to_string())
+                            .show_ui(ui, |ui| {
+                                for m in AlphaMode::iter() {
+                                    let label = if Some(m) == detected { format!("{m} (detected)") } else { m.to_string() };
+                                    ui.selectable_value(&mut mode, m, label);
+                                }
+                            })
+                            .response
+                            .on_hover_text("Whether the color of translucent pixels is multiplied by their alpha. Premultiplied images are divided by alpha for display.");
+                    });
+                    if mode != alpha_mode(state) {
+                        state.alpha_overrides.insert(path, mode);
+                        update_display_texture(state, gfx);
+                    }
+                }
                 ui.vertical_centered_justified(|ui| {
                     if let Some(img) = &state.current_image {
                         if ui
@@ -362,7 +418,7 @@ pub fn info_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
                             .on_hover_text("Highlight pixels with zero alpha and color information")
                             .clicked()
                         {
-                            state.current_texture = highlight_bleed(img).to_texture(gfx, state.persistent_settings.linear_mag_filter);
+                            state.current_texture = highlight_bleed(img).to_texture(gfx, state.sampling());
                         }
                         if ui
                             .button("Show semi-transparent pixels")
@@ -371,29 +427,326 @@ pub fn info_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
                             )
                             .clicked()
                         {
-                            state.current_texture = highlight_semitrans(img).to_texture(gfx, state.persistent_settings.linear_mag_filter);
+                            state.current_texture = highlight_semitrans(img).to_texture(gfx, state.sampling());
                         }
                         if ui.button("Reset image").clicked() {
-                            state.current_texture = img.to_texture(gfx, state.persistent_settings.linear_mag_filter);
+                            state.current_texture = img.to_texture(gfx, state.sampling());
                         }
 
                     }
                 });
             });
+            dicom_ui(ui, state, gfx);
+            fits_ui(ui, state, gfx);
+            data_ui(ui, state, gfx);
+            compression_ui(ui, state);
+            selection_ui(ui, state);
+            metadata_ui(ui, state);
+
             // ui.add(egui::Slider::new(&mut state.tiling, 1..=10).text("Image tiling"));
 
             ui.horizontal(|ui| {
                 ui.label("Tiling");
-                ui.slider_styled(&mut state.tiling, 1..=10);
+                // the channel shader only draws untiled images
+                if ui.slider_styled(&mut state.tiling, 1..=10).changed() {
+                    update_display_texture(state, gfx);
+                }
             });
             advanced_ui(ui, state);
+            checksum_ui(ui, state);
+
+        });
+
+
+
+
+
+    });
+}
 
+/// Window/level controls for DICOM images
+fn dicom_ui(ui: &mut Ui, state: &mut OculanteState, gfx: &mut Graphics) {
+    let path = match &state.current_path {
+        Some(p) if state.is_loaded => p.clone(),
+        _ => return,
+    };
+    if state.dicom.as_ref().map(|d| &d.path) != Some(&path) {
+        state.dicom = Some(DicomView::new(&path));
+    }
+    let (dicom, window) = match &mut state.dicom {
+        Some(DicomView { image, window, .. }) => (image, window),
+        None => return,
+    };
+    let dicom = match dicom {
+        Some(image) if image.color.is_none() => image,
+        _ => return,
+    };
+
+    let mut changed = false;
+    ui.collapsing("DICOM window", |ui| {
+        let (low, high) = dicom.range;
+        let speed = ((high - low) / 500.).max(0.1);
+        egui::Grid::new("dicom_window").show(ui, |ui| {
+            ui.label("Center");
+            changed |= ui
+                .add(egui::DragValue::new(&mut window.center).speed(speed))
+                .changed();
+            ui.end_row();
+            ui.label("Width");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut window.width)
+                        .speed(speed)
+                        .clamp_range(1.0..=f32::MAX),
+                )
+                .changed();
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            if let Some(default_window) = dicom.default_window {
+                if ui
+                    .button("Default")
+                    .on_hover_text("Window stored in the file")
+                    .clicked()
+                {
+                    *window = default_window;
+                    changed = true;
+                }
+            }
+            if ui
+                .button("Full range")
+                .on_hover_text(format!("{low} to {high}"))
+                .clicked()
+            {
+                *window = dicom.full_window();
+                changed = true;
+            }
         });
+    });
 
+    if changed {
+        let img = dicom.render(*window);
+        match &mut state.current_texture {
+            Some(tex)
+                if tex.width() as u32 == img.width() && tex.height() as u32 == img.height() =>
+            {
+                img.update_texture(gfx, tex)
+            }
+            _ => state.current_texture = img.to_texture(gfx, state.sampling()),
+        }
+        state.current_image = Some(img);
+    }
+}
 
+/// Stretch and black/white point controls for FITS images
+fn fits_ui(ui: &mut Ui, state: &mut OculanteState, gfx: &mut Graphics) {
+    let path = match &state.current_path {
+        Some(p) if state.is_loaded => p.clone(),
+        _ => return,
+    };
+    if state.fits.as_ref().map(|f| &f.path) != Some(&path) {
+        state.fits = Some(FitsView::new(&path));
+    }
+    let view = match &mut state.fits {
+        Some(view) => view,
+        None => return,
+    };
+    let channels = match &view.image {
+        Some(image) => image.planes.len(),
+        None => return,
+    };
+
+    let mut changed = false;
+    ui.collapsing("FITS stretch", |ui| {
+        egui::ComboBox::from_label("Stretch")
+            .selected_text(view.stretch.to_string())
+            .show_ui(ui, |ui| {
+                for stretch in Stretch::iter() {
+                    changed |= ui
+                        .selectable_value(&mut view.stretch, stretch, stretch.to_string())
+                        .changed();
+                }
+            });
+        let names = if channels == 1 {
+            vec!["Gray"]
+        } else {
+            vec!["Red", "Green", "Blue"]
+        };
+        egui::Grid::new("fits_levels").show(ui, |ui| {
+            for (i, name) in names.iter().enumerate() {
+                ui.label(*name);
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut view.black[i])
+                            .speed(0.001)
+                            .clamp_range(0.0..=1.0)
+                            .prefix("Black: "),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut view.white[i])
+                            .speed(0.001)
+                            .clamp_range(0.0..=1.0)
+                            .prefix("White: "),
+                    )
+                    .changed();
+                ui.end_row();
+            }
+        });
+        if ui.button("Reset").clicked() {
+            view.black = [0.; 3];
+            view.white = [1.; 3];
+            changed = true;
+        }
+    });
 
+    if changed {
+        if let Some(img) = view.render() {
+            match &mut state.current_texture {
+                Some(tex)
+                    if tex.width() as u32 == img.width() && tex.height() as u32 == img.height() =>
+                {
+                    img.update_texture(gfx, tex)
+                }
+                _ => state.current_texture = img.to_texture(gfx, state.sampling()),
+            }
+            state.current_image = Some(img);
+        }
+    }
+}
 
+/// Edit the title, description, keywords, copyright and date of the current file
+fn metadata_ui(ui: &mut Ui, state: &mut OculanteState) {
+    let path = match &state.current_path {
+        Some(p) if state.is_loaded => p.clone(),
+        _ => return,
+    };
+    ui.collapsing("Metadata", |ui| {
+        if state.metadata.as_ref().map(|(p, _)| p) != Some(&path) {
+            let metadata = EditableMetadata::read(&path).unwrap_or_else(|e| {
+                warn!("Can't read metadata of {}: {e}", path.display());
+                Default::default()
+            });
+            state.metadata = Some((path.clone(), metadata));
+            state.geotag = GeoTag::read(&path)
+                .unwrap_or_else(|e| {
+                    warn!("Can't read the location of {}: {e}", path.display());
+                    None
+                })
+                .map(|location| location.to_string())
+                .unwrap_or_default();
+        }
+        let metadata = match &mut state.metadata {
+            Some((_, metadata)) => metadata,
+            None => return,
+        };
+        egui::Grid::new("metadata").num_columns(2).show(ui, |ui| {
+            ui.label("Title");
+            ui.text_edit_singleline(&mut metadata.title);
+            ui.end_row();
+            ui.label("Description");
+            ui.text_edit_multiline(&mut metadata.description);
+            ui.end_row();
+            ui.label("Keywords");
+            ui.text_edit_singleline(&mut metadata.keywords)
+                .on_hover_text("Separated by commas");
+            ui.end_row();
+            ui.label("Copyright");
+            ui.text_edit_singleline(&mut metadata.copyright);
+            ui.end_row();
+            ui.label("Date");
+            ui.add(egui::TextEdit::singleline(&mut metadata.date).hint_text("2024-12-31 23:59:59"));
+            ui.end_row();
+        });
+        ui.checkbox(&mut state.persistent_settings.metadata_sidecars, "Write XMP sidecars")
+            .on_hover_text("Leave the files untouched and write the metadata to a .xmp file next to them. Files other than JPEG, PNG and WebP always get a sidecar.");
+        let sidecars = state.persistent_settings.metadata_sidecars;
+        let metadata = metadata.clone();
+
+        ui.vertical_centered_justified(|ui| {
+            if ui.button(format!("{FLOPPY_DISK} Save")).clicked() && state.check_writable() {
+                match metadata.save(&path, sidecars) {
+                    Ok(target) => state.send_message(&format!("Metadata written to {}", target.display())),
+                    Err(e) => state.send_message_err(&format!("Could not write metadata: {e}")),
+                }
+            }
+            let favourites = state.persistent_settings.favourite_images.iter().cloned().collect::<Vec<_>>();
+            if ui
+                .add_enabled(!favourites.is_empty(), egui::Button::new(format!("{STAR} Apply to {} favourites", favourites.len())))
+                .on_hover_text("Set the fields that are filled in on all favourites, keeping their other fields")
+                .clicked()
+                && state.check_writable()
+            {
+                let changes = metadata;
+                let msg_sender = state.message_channel.0.clone();
+                std::thread::spawn(move || {
+                    let failed = favourites
+                        .iter()
+                        .filter(|favourite| {
+                            let merged = EditableMetadata::read(favourite)
+                                .unwrap_or_default()
+                                .merged(&changes);
+                            merged
+                                .save(favourite, sidecars)
+                                .map_err(|e| error!("Could not write metadata to {}: {e}", favourite.display()))
+                                .is_err()
+                        })
+                        .count();
+                    _ = msg_sender.send(if failed == 0 {
+                        Message::info(&format!("Metadata written to {} favourites", favourites.len()))
+                    } else {
+                        Message::err(&format!("Could not write metadata to {failed} of {} favourites", favourites.len()))
+                    });
+                });
+            }
+        });
 
+        ui.separator();
+        egui::Grid::new("geotag").num_columns(2).show(ui, |ui| {
+            ui.label(format!("{MAP_PIN} Location"));
+            ui.add(egui::TextEdit::singleline(&mut state.geotag).hint_text("48.8584, 2.2945"))
+                .on_hover_text("Latitude and longitude, in decimal degrees or as 48°51'30\"N 2°17'40\"E");
+            ui.end_row();
+            ui.label("");
+            ui.horizontal(|ui| {
+                let recent = state.persistent_settings.recent_geotags.clone();
+                ui.add_enabled_ui(!recent.is_empty(), |ui| {
+                    egui::ComboBox::from_id_source("recent_geotags")
+                        .selected_text(format!("{CLOCK_COUNTER_CLOCKWISE} Recent"))
+                        .show_ui(ui, |ui| {
+                            for location in recent {
+                                if ui.selectable_label(false, location.to_string()).clicked() {
+                                    state.geotag = location.to_string();
+                                }
+                            }
+                        });
+                });
+                if ui.button(format!("{CHECK} Set")).on_hover_text("Write the location to the EXIF data").clicked() && state.check_writable() {
+                    match GeoTag::parse(&state.geotag).and_then(|location| GeoTag::write(Some(&location), &path).map(|_| location)) {
+                        Ok(location) => {
+                            let recent = &mut state.persistent_settings.recent_geotags;
+                            recent.retain(|l| *l != location);
+                            recent.insert(0, location);
+                            recent.truncate(10);
+                            state.geotag = location.to_string();
+                            state.send_message(&format!("Location set to {location}"));
+                        }
+                        Err(e) => state.send_message_err(&format!("Could not set the location: {e}")),
+                    }
+                }
+                if ui.button(format!("{TRASH} Remove")).clicked() && state.check_writable() {
+                    match GeoTag::write(None, &path) {
+                        Ok(_) => {
+                            state.geotag.clear();
+                            state.send_message("Location removed");
+                        }
+                        Err(e) => state.send_message_err(&format!("Could not remove the location: {e}")),
+                    }
+                }
+            });
+            ui.end_row();
+        });
     });
 }
 
@@ -425,14 +778,7 @@ pub fn settings_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx:
                     }
 
                     if r.changed() {
-                        match state.persistent_settings.theme {
-                            ColorTheme::Light =>
-                                ctx.set_visuals(Visuals::light()),
-                            ColorTheme::Dark =>
-                                ctx.set_visuals(Visuals::dark()),
-                            ColorTheme::System =>
-                                set_system_theme(ctx),
-                        }
+                        apply_style(ctx, &state.persistent_settings);
                     }
                 }
                 );
@@ -446,20 +792,19 @@ pub fn settings_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx:
                             .color_edit_button_srgb(&mut state.persistent_settings.accent_color)
                             .changed()
                         {
-                            let mut style: egui::Style = (*ctx.style()).clone();
-                            style.visuals.selection.bg_fill = Color32::from_rgb(
-                                state.persistent_settings.accent_color[0],
-                                state.persistent_settings.accent_color[1],
-                                state.persistent_settings.accent_color[2],
-                            );
-                            ctx.set_style(style);
+                            apply_style(ctx, &state.persistent_settings);
                         }
                         ui.label("Accent color");
                     });
 
                     ui.horizontal(|ui| {
                         ui.color_edit_button_srgb(&mut state.persistent_settings.background_color);
-                        ui.label("Background color");
+                        ui.label("Background color")
+                            .on_hover_text("Alt-click the image to use a color from it");
+                        let default = crate::settings::PersistentSettings::default().background_color;
+                        if state.persistent_settings.background_color != default && ui.small_button("Reset").clicked() {
+                            state.persistent_settings.background_color = default;
+                        }
                     });
 
                     ui.end_row();
@@ -501,11 +846,15 @@ pub fn settings_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx:
                 });
 
                 ui.end_row();
-                ui
-                    .checkbox(&mut state.persistent_settings.keep_view, "Do not reset image view")
-                    .on_hover_text(
-                        "When a new image is loaded, keep current zoom and offset",
-                    );
+                egui::ComboBox::from_label("Zoom on new image")
+                    .selected_text(state.persistent_settings.zoom_policy.label())
+                    .show_ui(ui, |ui| {
+                        for policy in ZoomPolicy::iter() {
+                            ui.selectable_value(&mut state.persistent_settings.zoom_policy, policy, policy.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("How the view is set up when a new image is loaded");
 
                 ui
                     .checkbox(&mut state.persistent_settings.keep_edits, "Keep image edits")
@@ -534,20 +883,215 @@ pub fn settings_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx:
 
                 // ui.label(format!("lazy {}", app.window().lazy_loop()));
                 ui.end_row();
-                if ui.checkbox(&mut state.persistent_settings.linear_mag_filter, "Interpolate pixels on zoom").on_hover_text("When zooming in, do you prefer to see individual pixels or an interpolation?").changed(){
-                    if let Some(img) = &state.current_image {
-                        if state.edit_state.result_image_op.is_empty() {
-                            state.current_texture = img.to_texture(gfx, state.persistent_settings.linear_mag_filter);
-                        } else {
-                            state.current_texture =  state.edit_state.result_pixel_op.to_texture(gfx, state.persistent_settings.linear_mag_filter);
-                        }
-                    }
+                let mut sampling_changed = false;
+                ui.horizontal(|ui| {
+                    ui.label("Sampling").on_hover_text("How images look when zoomed. Nearest shows sharp pixels, for pixel art. Linear is smooth, for photos, and Mipmap also stays smooth when zoomed out. Auto shows sharp pixels when zooming into small images and is mipmapped otherwise. Single images can have their own mode from the menu.");
+                    egui::ComboBox::from_id_source("sampling")
+                        .selected_text(state.persistent_settings.sampling.label())
+                        .show_ui(ui, |ui| {
+                            for sampling in Sampling::iter() {
+                                sampling_changed |= ui.selectable_value(&mut state.persistent_settings.sampling, sampling, sampling.label()).changed();
+                            }
+                        });
+                });
+                if sampling_changed {
+                    refresh_sampling(state, gfx);
                 }
 
                 ui.checkbox(&mut state.persistent_settings.fit_image_on_window_resize, "Fit image on window resize").on_hover_text("When you resize the main window, fir the image with it?");
                 ui.end_row();
 
                 ui.add(egui::DragValue::new(&mut state.persistent_settings.zoom_multiplier).clamp_range(0.05..=10.0).prefix("Zoom multiplier: ").speed(0.01)).on_hover_text("Adjust how much you zoom when you use the mouse wheel or the trackpad.");
+                ui.checkbox(&mut state.persistent_settings.invert_scroll, "Invert scrolling").on_hover_text("Zoom in when scrolling down, and go to the next image when scrolling up with Ctrl held.");
+                ui.end_row();
+
+                ui.checkbox(&mut state.persistent_settings.snap_zoom, "Snap to whole zoom levels")
+                    .on_hover_text(format!(
+                        "Zooming stops at 1x, 2x, 3x and so on when it gets near, so pixel art is shown without shimmering. {} goes to the next whole zoom level.",
+                        lookup(&state.persistent_settings.shortcuts, &InputEvent::ZoomNextWhole)
+                    ));
+                ui.end_row();
+
+                let mut calibrated = state.persistent_settings.monitor_ppi.is_some();
+                if ui.checkbox(&mut calibrated, "Calibrate print size").on_hover_text("Measure the resolution of the monitor to show images at their physical size. Without it, the scale factor of the window is used.").changed() {
+                    state.persistent_settings.monitor_ppi = calibrated.then(|| crate::print_size::DEFAULT_PPI * ctx.pixels_per_point());
+                }
+                if let Some(ppi) = &mut state.persistent_settings.monitor_ppi {
+                    ui.vertical(|ui| {
+                        ui.add(egui::DragValue::new(ppi).clamp_range(30.0..=1000.0).suffix(" ppi").speed(0.1))
+                            .on_hover_text("Adjust until the bar below is one inch (25.4 mm) wide on a ruler");
+                        let width = *ppi / ctx.pixels_per_point();
+                        let (rect, _) = ui.allocate_exact_size(egui::Vec2::new(width, 8.), Sense::hover());
+                        ui.painter().rect_filled(rect, 0., ui.visuals().text_color());
+                    });
+                }
+                ui.end_row();
+
+                ui.add(egui::DragValue::new(&mut state.persistent_settings.pan_step).clamp_range(1.0..=1000.0).prefix("Pan step: ").suffix(" px").speed(1.0)).on_hover_text("How far the pan shortcuts move the image.");
+                ui.add(egui::DragValue::new(&mut state.persistent_settings.key_zoom_step).clamp_range(0.1..=10.0).prefix("Zoom step: ").speed(0.05)).on_hover_text("How much the zoom shortcuts zoom.");
+                ui.end_row();
+
+                ui.checkbox(&mut state.persistent_settings.keep_metadata, "Keep metadata when saving").on_hover_text("Copy EXIF, XMP and color profile of the original file into saved images.");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.persistent_settings.show_minimap, "Show minimap").on_hover_text("Show the whole image and the visible part of it while zoomed in. Drag the visible part to move around, or click where to go.");
+                    egui::ComboBox::from_id_source("minimap_corner")
+                        .selected_text(state.persistent_settings.minimap_corner.label())
+                        .show_ui(ui, |ui| {
+                            for corner in MinimapCorner::iter() {
+                                ui.selectable_value(&mut state.persistent_settings.minimap_corner, corner, corner.label());
+                            }
+                        });
+                });
+                ui.end_row();
+                ui.checkbox(&mut state.persistent_settings.gpu_edits, "GPU edit preview").on_hover_text("Run simple color adjustments on the graphics card while dragging sliders. The final result is always computed on the CPU.");
+                ui.checkbox(&mut state.persistent_settings.load_all_files, "Load all files").on_hover_text("Also list and open files with unknown or missing extensions if their content looks like an image. Folders load slower.");
+                ui.end_row();
+
+                ui.add_enabled(!state.read_only, egui::Checkbox::new(&mut state.persistent_settings.read_only, "Read-only"))
+                    .on_hover_text("Never change, move or delete files, e.g. to browse shared archives or for kiosks. Always on with --read-only.");
+                ui.checkbox(&mut state.persistent_settings.blur_all, "Blur all images")
+                    .on_hover_text(format!(
+                        "Show images blurred until {} is held. Single folders can be blurred from the menu.",
+                        lookup(&state.persistent_settings.shortcuts, &InputEvent::RevealImage)
+                    ));
+                ui.end_row();
+
+                ui.checkbox(&mut state.persistent_settings.loupe_enabled, "Loupe")
+                    .on_hover_text(format!(
+                        "Show the area under the cursor magnified, and the value of its center pixel. Toggle with {}.",
+                        lookup(&state.persistent_settings.shortcuts, &InputEvent::ToggleLoupe)
+                    ));
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut state.persistent_settings.loupe_zoom).clamp_range(2.0..=64.0).prefix("Zoom: ").suffix("x").speed(0.1)).on_hover_text("Screen points per image pixel in the loupe.");
+                    ui.add(egui::DragValue::new(&mut state.persistent_settings.loupe_size).clamp_range(64.0..=512.0).prefix("Size: ").suffix(" px").speed(1.0));
+                });
+                ui.end_row();
+
+                let mut disk_cache_changed = ui
+                    .checkbox(&mut state.persistent_settings.disk_cache, "Disk cache")
+                    .on_hover_text("Keep decoded large images on disk, so they open instantly next time.")
+                    .changed();
+                ui.horizontal(|ui| {
+                    disk_cache_changed |= ui
+                        .add(egui::DragValue::new(&mut state.persistent_settings.disk_cache_size).clamp_range(64..=100000).suffix(" MB"))
+                        .on_hover_text("Oldest images are removed when the cache grows larger than this.")
+                        .changed();
+                    if let Some(cache) = &state.player.disk_cache {
+                        if ui.button(format!("{TRASH} Clear")).on_hover_ui(|ui| {
+                            ui.label(format!("{:.1} MB used", cache.size() as f64 / 1024. / 1024.));
+                        }).clicked() {
+                            match cache.clear() {
+                                Ok(_) => state.send_message("Disk cache cleared"),
+                                Err(e) => state.send_message_err(&format!("Could not clear disk cache: {e}")),
+                            }
+                        }
+                    }
+                });
+                if disk_cache_changed {
+                    state.player.set_disk_cache(state.persistent_settings.disk_cache, state.persistent_settings.disk_cache_size);
+                }
+                ui.end_row();
+
+                if ui
+                    .checkbox(&mut state.persistent_settings.write_thumbnails, "Write thumbnails")
+                    .on_hover_text("Store thumbnails of opened images in the shared thumbnail cache, so file managers can show them without decoding the images again.")
+                    .changed()
+                {
+                    state.player.write_thumbnails = state.persistent_settings.write_thumbnails;
+                }
+                ui.checkbox(&mut state.persistent_settings.downsample_on_decode, "Fast browsing").on_hover_text("Decode large JPEGs at screen size. The full image is loaded when you zoom in past 100% or start editing.");
+                ui.end_row();
+
+                if ui
+                    .checkbox(&mut state.persistent_settings.detect_sequences, "Image sequences")
+                    .on_hover_text("Detect numbered image sequences like render.0001.exr and show controls to play them.")
+                    .changed()
+                    && !state.persistent_settings.detect_sequences
+                {
+                    state.sequence = None;
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut state.persistent_settings.remember_folder_views, "Remember view per folder")
+                        .on_hover_text("Changes to the channel, zen mode, pixel interpolation, checker background and zoom only apply to the folder they were made in, and come back when it is opened again.")
+                        .changed()
+                    {
+                        if state.persistent_settings.remember_folder_views {
+                            if let Some(folder) = state.current_path.as_ref().and_then(|p| p.parent()) {
+                                state.persistent_settings.enter_folder(folder);
+                            }
+                        } else {
+                            state.persistent_settings.leave_folder();
+                        }
+                    }
+                    let folders = state.persistent_settings.folder_views.len();
+                    if folders > 0 && ui.button(format!("{TRASH} Forget")).on_hover_text(format!("Forget the views of {folders} folders")).clicked() {
+                        state.persistent_settings.folder_views.clear();
+                    }
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut state.persistent_settings.background_mode, "Background mode")
+                        .on_hover_text(format!(
+                            "'{}' hides the window instead of quitting. The tray icon, the hotkey or 'oculante --show' show it again with the last image.",
+                            lookup(&state.persistent_settings.shortcuts, &InputEvent::Quit)
+                        ))
+                        .changed()
+                    {
+                        if state.persistent_settings.background_mode {
+                            enable_background_mode(state);
+                        } else {
+                            #[cfg(feature = "tray")]
+                            {
+                                state.tray = None;
+                            }
+                        }
+                    }
+                    #[cfg(feature = "tray")]
+                    {
+                        ui.label("Hotkey");
+                        if ui
+                            .add(egui::TextEdit::singleline(&mut state.persistent_settings.background_hotkey).desired_width(100.))
+                            .on_hover_text("Shows the window in background mode, like Ctrl+Alt+O. Leave empty for none. Works on Windows, macOS and X11.")
+                            .lost_focus()
+                            && state.persistent_settings.background_mode
+                        {
+                            enable_background_mode(state);
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Add to Open With").on_hover_text("Offer oculante to open images in the file manager of your system").clicked() {
+                        match crate::open_with::register() {
+                            Ok(_) => state.send_message("Oculante can now open images from your file manager"),
+                            Err(e) => state.send_message_err(&format!("Could not register: {e}")),
+                        }
+                    }
+                    if ui.button("Remove").on_hover_text("Undo 'Add to Open With'").clicked() {
+                        match crate::open_with::unregister() {
+                            Ok(_) => state.send_message("Removed from Open With"),
+                            Err(e) => state.send_message_err(&format!("Could not unregister: {e}")),
+                        }
+                    }
+                });
+                ui.end_row();
+
+                #[cfg(feature = "update")]
+                {
+                    ui.checkbox(&mut state.persistent_settings.check_for_updates, "Check for updates")
+                        .on_hover_text("Look for a new version once a day. An icon in the top bar shows when there is one.");
+                    ui.horizontal(|ui| {
+                        ui.label("Update channel");
+                        use crate::settings::UpdateChannel;
+                        let channel = &mut state.persistent_settings.update_channel;
+                        ui.selectable_value(channel, UpdateChannel::Stable, "Stable");
+                        ui.selectable_value(channel, UpdateChannel::Nightly, "Nightly")
+                            .on_hover_text("Include pre-releases with the newest features");
+                    });
+                    ui.end_row();
+                }
             });
 
                 ui.horizontal(|ui| {
@@ -563,6 +1107,71 @@ pub fn settings_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx:
                     }
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("Capture folder");
+                    let mut folder = state.persistent_settings.capture_folder.to_string_lossy().to_string();
+                    if ui
+                    .text_edit_singleline(&mut folder)
+                    .on_hover_text(format!(
+                        "Where '{}' saves what is on screen, and where screenshots go. The folder is created if needed.",
+                        lookup(&state.persistent_settings.shortcuts, &InputEvent::CaptureViewport)
+                    ))
+                    .changed()
+                    {
+                        state.persistent_settings.capture_folder = folder.into();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Hot folder");
+                    let mut folder = state
+                        .persistent_settings
+                        .hot_folder
+                        .as_ref()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    if ui
+                    .text_edit_singleline(&mut folder)
+                    .on_hover_text("A folder that receives images, like from a tethered camera or a renderer. When it is watched, new images are shown as they arrive.")
+                    .changed()
+                    {
+                        state.persistent_settings.hot_folder = (!folder.is_empty()).then(|| folder.into());
+                    }
+                    ui.checkbox(&mut state.persistent_settings.hot_folder_sound, "Sound")
+                        .on_hover_text("Play a sound when an image arrives");
+                    ui.checkbox(&mut state.persistent_settings.hot_folder_raise, "Bring to front")
+                        .on_hover_text("Raise the window when an image arrives while it is in the background");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Fullscreen on");
+                    let selected = state
+                        .persistent_settings
+                        .fullscreen_display
+                        .and_then(|i| state.persistent_settings.displays.get(i))
+                        .map(|d| d.name.clone())
+                        .unwrap_or_else(|| "Current display".into());
+                    egui::ComboBox::from_id_source("fullscreen_display")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut state.persistent_settings.fullscreen_display, None, "Current display");
+                            for (i, display) in state.persistent_settings.displays.iter().enumerate() {
+                                ui.selectable_value(&mut state.persistent_settings.fullscreen_display, Some(i), &display.name);
+                            }
+                        });
+                    if ui.button("Remember this display").on_hover_text(format!(
+                        "Displays are remembered when the window is on them. '{}' moves the window to the next one.",
+                        lookup(&state.persistent_settings.shortcuts, &InputEvent::NextDisplay)
+                    )).clicked() {
+                        let index = remember_display(app, state);
+                        state.send_message(&format!("Remembered {}", state.persistent_settings.displays[index].name));
+                    }
+                    if !state.persistent_settings.displays.is_empty() && ui.button(format!("{TRASH} Forget")).on_hover_text("Forget all displays").clicked() {
+                        state.persistent_settings.displays.clear();
+                        state.persistent_settings.fullscreen_display = None;
+                    }
+                });
+
                 if ui.link("Visit github repo").on_hover_text("Check out the source code, request a feature, submit a bug or leave a star if you like it!").clicked() {
                     _ = webbrowser::open("https://github.com/woelper/oculante");
                 }
@@ -571,31 +1180,343 @@ pub fn settings_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx:
                 ui.vertical_centered_justified(|ui| {
 
                     #[cfg(feature = "update")]
-                    if ui.button("Check for updates").on_hover_text("Check and install update if available. You will need to restart the app to use the new version.").clicked() {
+                    if ui.button("Check for updates").on_hover_text("Check for an update and show what changed before installing it. You will need to restart the app to use the new version.").clicked() {
                         state.send_message("Checking for updates...");
-                        crate::update::update(Some(state.message_channel.0.clone()));
+                        state.show_update = true;
+                        crate::update::check_in_background(state, true);
                         state.settings_enabled = false;
                     }
 
                     if ui.button("Reset all settings").clicked() {
                         state.persistent_settings = Default::default();
+                        apply_settings(app, ctx, state);
+                        state.send_message("Settings have been reset");
+                    }
+
+                    #[cfg(feature = "file_open")]
+                    if ui.button("Export settings...").on_hover_text("Save all settings, including keybindings, to a file. Import it on another machine to use the same configuration.").clicked() && state.check_writable() {
+                        let settings = state.persistent_settings.clone();
+                        let msg_sender = state.message_channel.0.clone();
+                        let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                        std::thread::spawn(move || {
+                            let _dialog = dialog;
+                            let file_dialog_result = rfd::FileDialog::new()
+                                .add_filter("Settings", &["json"])
+                                .set_file_name("oculante_settings.json")
+                                .save_file();
+                            if let Some(file_path) = file_dialog_result {
+                                let msg = match settings.export(&file_path) {
+                                    Ok(_) => Message::info(&format!("Settings exported to {}", file_path.display())),
+                                    Err(e) => Message::err(&format!("Could not export settings: {e}")),
+                                };
+                                _ = msg_sender.send(msg);
+                            }
+                        });
+                    }
+
+                    #[cfg(feature = "file_open")]
+                    if ui.button("Import settings...").on_hover_text("Replace all settings with ones exported before.").clicked() {
+                        let settings = state.persistent_settings.clone();
+                        let settings_sender = state.settings_channel.0.clone();
+                        let msg_sender = state.message_channel.0.clone();
+                        let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                        std::thread::spawn(move || {
+                            let _dialog = dialog;
+                            let file_dialog_result = rfd::FileDialog::new()
+                                .add_filter("Settings", &["json"])
+                                .pick_file();
+                            if let Some(file_path) = file_dialog_result {
+                                match settings.import(&file_path) {
+                                    Ok(imported) => _ = settings_sender.send(imported),
+                                    Err(e) => _ = msg_sender.send(Message::err(&format!("Could not import settings: {e}"))),
+                                }
+                            }
+                        });
+                    }
+                });
+
+                if let Ok(imported) = state.settings_channel.1.try_recv() {
+                    state.persistent_settings = imported;
+                    apply_settings(app, ctx, state);
+                    state.send_message("Settings have been imported");
+                }
+
+                ui.collapsing("Accessibility", |ui| {
+                    let mut changed = ui
+                        .checkbox(&mut state.persistent_settings.high_contrast, "High contrast")
+                        .on_hover_text("Black and white instead of shades of gray, with thicker outlines")
+                        .changed();
+                    changed |= ui
+                        .checkbox(&mut state.persistent_settings.large_targets, "Large buttons")
+                        .on_hover_text("Bigger buttons and more space between them")
+                        .changed();
+                    changed |= ui
+                        .checkbox(&mut state.persistent_settings.reduce_motion, "Reduce motion")
+                        .on_hover_text("Show and hide panels and menus without transitions")
+                        .changed();
+                    if changed {
+                        apply_style(ctx, &state.persistent_settings);
+                    }
+                });
+
+                ui.collapsing("Soft proof", |ui| {
+                    let mut changed = false;
+                    ui.horizontal(|ui| {
+                        ui.label("Printer profile");
+                        let mut path = state
+                            .persistent_settings
+                            .proof_profile
+                            .as_ref()
+                            .map(|p| p.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        if ui
+                            .text_edit_singleline(&mut path)
+                            .on_hover_text("The ICC profile of the printer and paper to simulate")
+                            .changed()
+                        {
+                            state.persistent_settings.proof_profile =
+                                Some(path).filter(|p| !p.is_empty()).map(PathBuf::from);
+                            // the loaded profile is outdated
+                            changed = state.soft_proof.take().is_some();
+                        }
+                    });
+                    let mut proofing = state.soft_proof.is_some();
+                    if ui
+                        .checkbox(&mut proofing, "Soft proof")
+                        .on_hover_text(format!(
+                            "Show the image as it would print, taking its colors as sRGB. Press '{}' to toggle.",
+                            lookup(&state.persistent_settings.shortcuts, &InputEvent::SoftProof)
+                        ))
+                        .changed()
+                    {
+                        toggle_soft_proof(state);
+                        changed = true;
+                    }
+                    changed |= ui
+                        .checkbox(&mut state.persistent_settings.gamut_warning, "Gamut warning")
+                        .on_hover_text("Show colors the printer can't reproduce in magenta")
+                        .changed();
+                    if changed {
+                        update_display_texture(state, gfx);
                     }
                 });
 
+                ui.collapsing("External commands", |ui| {
+                    external_commands_ui(state, ui);
+                });
+
                 ui.collapsing("Keybindings",|ui| {
                     keybinding_ui(app, state, ui);
                 });
 
+                ui.collapsing("Diagnostics", |ui| {
+                    diagnostics_ui(state, ui);
+                });
+
             });
     state.settings_enabled = settings_enabled;
 }
 
-pub fn advanced_ui(ui: &mut Ui, state: &mut OculanteState) {
-    if let Some(info) = &state.image_info {
-        egui::Grid::new("extended").show(ui, |ui| {
-            ui.label("Number of colors");
-            ui.label(format!("{}", info.num_colors));
-            ui.end_row();
+/// Make settings take effect after they were replaced as a whole
+fn apply_settings(app: &mut App, ctx: &Context, state: &mut OculanteState) {
+    apply_style(ctx, &state.persistent_settings);
+
+    state.player.cache.cache_size = state.persistent_settings.max_cache;
+    state.player.write_thumbnails = state.persistent_settings.write_thumbnails;
+    crate::remote::set_credentials(state.persistent_settings.remote_credentials.clone());
+    state.player.set_disk_cache(
+        state.persistent_settings.disk_cache,
+        state.persistent_settings.disk_cache_size,
+    );
+    state.scrubber.wrap = state.persistent_settings.wrap_folder;
+    app.window()
+        .set_lazy_loop(!state.persistent_settings.force_redraw);
+    set_title(app, state);
+    state.persistent_settings.save();
+}
+
+/// Memory use of caches and textures, and how long images took to load
+fn diagnostics_ui(state: &mut OculanteState, ui: &mut Ui) {
+    let mb = |bytes: f64| format!("{:.1} MB", bytes / 1024. / 1024.);
+    egui::Grid::new("diagnostics").show(ui, |ui| {
+        ui.label("Memory cache");
+        ui.label(format!(
+            "{} of {} images, {}",
+            state.player.cache.data.len(),
+            state.player.cache.cache_size,
+            mb(state.player.cache.bytes() as f64)
+        ));
+        ui.end_row();
+
+        if let Some(cache) = &state.player.disk_cache {
+            ui.label("Disk cache");
+            ui.label(format!(
+                "{} of {}",
+                mb(cache.size() as f64),
+                mb(cache.max_size as f64)
+            ));
+            ui.end_row();
+        }
+
+        ui.label("Current image");
+        ui.label(
+            state
+                .current_image
+                .as_ref()
+                .map(|img| mb(img.len() as f64))
+                .unwrap_or_default(),
+        );
+        ui.end_row();
+
+        ui.label("Textures");
+        let textures = [&state.current_texture, &state.checker_texture]
+            .iter()
+            .filter_map(|t| t.as_ref())
+            .map(|t| t.width() as f64 * t.height() as f64 * 4.)
+            .sum::<f64>();
+        ui.label(mb(textures));
+        ui.end_row();
+
+        let (pending, running) = state.player.pool.queue_len();
+        ui.label("Decode queue");
+        ui.label(format!("{pending} waiting, {running} decoding"));
+        ui.end_row();
+
+        ui.label("Polling");
+        let polled = state.redraw.polled();
+        if polled.is_empty() {
+            ui.label("Idle");
+        } else {
+            ui.label(format!("{polled:?}"));
+        }
+        ui.end_row();
+    });
+
+    ui.label("Recent loads");
+    egui::Grid::new("load_timings")
+        .striped(true)
+        .show(ui, |ui| {
+            for timing in state.player.pool.timings() {
+                ui.label(
+                    timing
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                );
+                ui.label(format!("{:?}", timing.priority));
+                ui.label(format!("{} ms", timing.duration.as_millis()));
+                ui.end_row();
+            }
+        });
+
+    if ui.button(format!("{TRASH} Clear caches")).clicked() {
+        state.player.cache.clear();
+        if let Some(cache) = &state.player.disk_cache {
+            if let Err(e) = cache.clear() {
+                state.send_message_err(&format!("Could not clear disk cache: {e}"));
+            }
+        }
+        state.send_message("Caches cleared");
+    }
+}
+
+fn external_commands_ui(state: &mut OculanteState, ui: &mut Ui) {
+    let commands = &mut state.persistent_settings.external_commands;
+    let mut delete = None;
+    egui::Grid::new("external_commands").show(ui, |ui| {
+        for (i, command) in commands.iter_mut().enumerate() {
+            ui.add(
+                egui::TextEdit::singleline(&mut command.name)
+                    .hint_text("Name")
+                    .desired_width(80.),
+            );
+            ui.add(egui::TextEdit::singleline(&mut command.command).hint_text("oxipng {path}"))
+                .on_hover_text(
+                    "The shell command to run. {path} is replaced by the path of the current file.",
+                );
+            ui.checkbox(&mut command.reload, "Reload")
+                .on_hover_text("Load the file again after the command has finished");
+            if ui.button(TRASH).clicked() {
+                delete = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = delete {
+        commands.remove(i);
+    }
+    if ui.button(format!("{PLUS} Add command")).clicked() {
+        commands.push(Default::default());
+    }
+}
+
+/// The checksum of the current file, and other files with the same content
+fn checksum_ui(ui: &mut Ui, state: &mut OculanteState) {
+    let checksum = match &state.checksum.checksum {
+        Some(Ok(checksum)) if state.current_path.as_ref() == Some(&checksum.path) => {
+            checksum.clone()
+        }
+        Some(Err(e)) => {
+            ui.label(format!("No checksum: {e}"));
+            return;
+        }
+        _ if state.checksum.path.is_some() => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Computing checksum...");
+            });
+            return;
+        }
+        _ => return,
+    };
+    egui::Grid::new("checksum").show(ui, |ui| {
+        ui.label("SHA-256");
+        if ui
+            .add(
+                egui::Label::new(
+                    RichText::new(format!("{}...", &checksum.sha256[..16])).monospace(),
+                )
+                .sense(Sense::click()),
+            )
+            .on_hover_text(format!("{}\nClick to copy", checksum.sha256))
+            .clicked()
+        {
+            match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(&checksum.sha256)) {
+                Ok(_) => state.send_message("Checksum copied"),
+                Err(e) => state.send_message_err(&format!("Could not copy: {e}")),
+            }
+        }
+        ui.end_row();
+        ui.label("Duplicates");
+        if checksum.duplicates.is_empty() {
+            ui.label("None");
+        } else {
+            ui.label(format!("{} files", checksum.duplicates.len()))
+                .on_hover_text(
+                    "Files with the same content below the searched folder, or in this folder",
+                );
+        }
+        ui.end_row();
+    });
+    for path in &checksum.duplicates {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if ui
+            .selectable_label(false, format!("{COPY} {name}"))
+            .on_hover_text(path.display().to_string())
+            .clicked()
+        {
+            load_image_from_path(path, state);
+        }
+    }
+}
+
+pub fn advanced_ui(ui: &mut Ui, state: &mut OculanteState) {
+    if let Some(info) = &state.image_info {
+        egui::Grid::new("extended").show(ui, |ui| {
+            ui.label("Number of colors");
+            ui.label(format!("{}", info.num_colors));
+            ui.end_row();
 
             ui.label("Fully transparent");
             ui.label(format!(
@@ -606,6 +1527,46 @@ pub fn advanced_ui(ui: &mut Ui, state: &mut OculanteState) {
             ui.label("Pixels");
             ui.label(format!("{}", info.num_pixels));
             ui.end_row();
+
+            ui.label("Sharpness");
+            ui.label(format!("{:.1}", info.sharpness)).on_hover_text(
+                "Variance of the Laplacian. Blurry or out of focus images score low.",
+            );
+            ui.end_row();
+
+            #[cfg(feature = "detection")]
+            {
+                use crate::detection::DetectionKind;
+                ui.label("Faces");
+                ui.label(format!(
+                    "{}",
+                    info.detections
+                        .iter()
+                        .filter(|d| d.kind == DetectionKind::Face)
+                        .count()
+                ))
+                .on_hover_text(
+                    "Outlined in yellow. Faces are found by skin colour, so faces under \
+                     coloured light can be missed, and skin coloured surfaces like wood or \
+                     sand can be taken for faces.",
+                );
+                ui.end_row();
+                ui.label("Subject");
+                match info
+                    .detections
+                    .iter()
+                    .find(|d| d.kind == DetectionKind::Subject)
+                {
+                    Some(subject) => ui
+                        .label(format!(
+                            "{}x{} at {},{}",
+                            subject.rect.2, subject.rect.3, subject.rect.0, subject.rect.1
+                        ))
+                        .on_hover_text("Outlined in blue"),
+                    None => ui.label("None"),
+                };
+                ui.end_row();
+            }
         });
 
         if !info.exif.is_empty() {
@@ -674,6 +1635,8 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
             let mut image_changed = false;
             let mut pixels_changed = false;
 
+            state.edit_state.show_original = key_held(app, state, InputEvent::CompareOriginal);
+
             if let Some(img) = &state.current_image {
                 // Ensure that edit result image is always filled
                 if state.edit_state.result_pixel_op.width() == 0 {
@@ -702,11 +1665,29 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                         ImageOperation::Posterize(8),
                         ImageOperation::ChannelSwap((Channel::Red, Channel::Red)),
                         ImageOperation::Rotate(90),
+                        ImageOperation::Straighten {
+                            angle: 0,
+                            crop: true,
+                            editing: false,
+                        },
                         ImageOperation::HSV((0, 100, 100)),
                         ImageOperation::Crop([0, 0, 0, 0]),
+                        ImageOperation::Trim(TRIM_TOLERANCE),
+                        ImageOperation::Perspective {
+                            corners: [(0, 0), (10000, 0), (10000, 10000), (0, 10000)],
+                            editing: true,
+                        },
                         ImageOperation::Mult([255, 255, 255]),
                         ImageOperation::Fill([255, 255, 255, 255]),
                         ImageOperation::Blur(0),
+                        ImageOperation::Denoise {
+                            strength: 30,
+                            radius: 3,
+                        },
+                        ImageOperation::Sharpen {
+                            amt: 50,
+                            radius: 2,
+                        },
                         ImageOperation::GradientMap(vec![GradientStop::new(0, [155,33,180]), GradientStop::new(128, [255,83,0]),GradientStop::new(255, [224,255,0])]),
                         ImageOperation::MMult,
                         ImageOperation::MDiv,
@@ -720,10 +1701,28 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                             dimensions: state.image_dimension,
                             aspect: true,
                             filter: ScaleFilter::Hamming,
+                            percent: None,
                         },
                         ImageOperation::Invert,
                         ImageOperation::Flip(false),
                         ImageOperation::ChromaticAberration(15),
+                        ImageOperation::Text {
+                            text: "Text".into(),
+                            font: Default::default(),
+                            size: 5,
+                            color: [255, 255, 255, 255],
+                            outline: 8,
+                            outline_color: [0, 0, 0, 255],
+                            anchor: Anchor::BottomLeft,
+                            margin: 2,
+                        },
+                        ImageOperation::Watermark {
+                            path: Default::default(),
+                            anchor: Anchor::BottomRight,
+                            scale: 20,
+                            margin: 2,
+                            opacity: 80,
+                        },
                     ];
 
                     ui.label_i("➕ Filter");
@@ -738,10 +1737,10 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                             for op in &mut ops {
                                 if ui.label_i_selected(false, &format!("{op}")).clicked() {
                                     if op.is_per_pixel() {
-                                        state.edit_state.pixel_op_stack.push(op.clone());
+                                        state.edit_state.pixel_op_stack.push(ImgOpItem::new(op.clone()));
                                         pixels_changed = true;
                                     } else {
-                                        state.edit_state.image_op_stack.push(op.clone());
+                                        state.edit_state.image_op_stack.push(ImgOpItem::new(op.clone()));
                                         image_changed = true;
                                     }
                                 }
@@ -749,12 +1748,108 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                         });
                     ui.end_row();
 
+                    ui.label_i(&format!("{BOOKMARKS} Preset"));
+                    ui.horizontal(|ui| {
+                        let names = state.edit_presets.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+                        let mut apply = None;
+                        let mut delete = None;
+                        egui::ComboBox::from_id_source("presets")
+                            .selected_text("Apply a preset...")
+                            .width(ui.available_width() / 2.)
+                            .show_ui(ui, |ui| {
+                                for (i, name) in names.iter().enumerate() {
+                                    let r = ui.selectable_label(false, name).on_hover_text("Right click to delete");
+                                    if r.clicked() {
+                                        apply = Some(i);
+                                    }
+                                    if r.secondary_clicked() {
+                                        delete = Some(i);
+                                    }
+                                }
+                            });
+                        if let Some(i) = apply {
+                            state.edit_presets[i].apply(&mut state.edit_state);
+                            state.persistent_settings.last_preset = Some(names[i].clone());
+                            image_changed = true;
+                        }
+                        if let Some(i) = delete {
+                            state.edit_presets.remove(i);
+                            if let Err(e) = EditPreset::save_all(&state.edit_presets) {
+                                state.send_message_err(&format!("Could not save presets: {e}"));
+                            }
+                        }
+
+                        let name_id = Id::new("preset_name");
+                        let mut name = ui.data_mut(|d| d.get_temp::<String>(name_id)).unwrap_or_default();
+                        ui.add(egui::TextEdit::singleline(&mut name).hint_text("Name").desired_width(ui.available_width() - 30.));
+                        if ui
+                            .add_enabled(!name.is_empty(), egui::Button::new(FLOPPY_DISK))
+                            .on_hover_text("Save the current edits as a preset")
+                            .clicked()
+                        {
+                            state.edit_presets.retain(|p| p.name != name);
+                            state.edit_presets.push(EditPreset::new(&name, &state.edit_state));
+                            if let Err(e) = EditPreset::save_all(&state.edit_presets) {
+                                state.send_message_err(&format!("Could not save presets: {e}"));
+                            }
+                            state.persistent_settings.last_preset = Some(name.clone());
+                            name.clear();
+                        }
+                        ui.data_mut(|d| d.insert_temp(name_id, name));
+                    });
+                    ui.end_row();
+
+                    ui.label_i(&format!("{MAGIC_WAND} Auto"));
+                    ui.horizontal(|ui| {
+                        let mut enhance = ui
+                            .button("Enhance")
+                            .on_hover_text("Balance the colors and stretch the contrast, as a starting point for further edits")
+                            .clicked();
+                        // a new strength updates the last adjustment
+                        enhance |= ui
+                            .add(egui::Slider::new(&mut state.edit_state.auto_strength, 0.0..=1.0).text("Strength"))
+                            .changed()
+                            && state.edit_state.auto_enhanced();
+                        if enhance {
+                            let strength = state.edit_state.auto_strength;
+                            let ops = auto_enhance(&state.edit_state.result_image_op, strength);
+                            state.edit_state.set_auto_ops(ops);
+                            pixels_changed = true;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label_i(&format!("{SCISSORS} Trim"));
+                    if ui
+                        .button("Trim borders")
+                        .on_hover_text("Crop away uniform borders, like the margins of a scan or the background around a screenshot")
+                        .clicked()
+                    {
+                        let img = &state.edit_state.result_image_op;
+                        match trimmed_range(img, TRIM_TOLERANCE) {
+                            Some(range) if range[2..] != [img.width(), img.height()] => {
+                                let bounds = crop_bounds(range, img.dimensions());
+                                state
+                                    .edit_state
+                                    .image_op_stack
+                                    .push(ImgOpItem::new(ImageOperation::Crop(bounds)));
+                                image_changed = true;
+                            }
+                            _ => state.send_message("No borders found"),
+                        }
+                    }
+                    ui.end_row();
+
                     modifier_stack_ui(&mut state.edit_state.image_op_stack, &mut image_changed, ui);
                     modifier_stack_ui(
                         &mut state.edit_state.pixel_op_stack,
                         &mut pixels_changed,
                         ui,
                     );
+                    // painting an operator mask and painting on the image are exclusive
+                    if state.edit_state.mask_painting() {
+                        state.edit_state.painting = false;
+                    }
 
                     ui.label_i(&format!("{RECYCLE} Reset"));
                     ui.centered_and_justified(|ui| {
@@ -778,7 +1873,7 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                         {
                             if let Some(img) = &state.current_image {
                                 state.image_dimension = img.dimensions();
-                                state.current_texture = img.to_texture(gfx, state.persistent_settings.linear_mag_filter);
+                                state.current_texture = img.to_texture(gfx, state.sampling());
                             }
                         }
                         if ui
@@ -792,6 +1887,25 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                         }
                     });
                     ui.end_row();
+
+                    ui.label_i(&format!("{SQUARE_SPLIT_HORIZONTAL} Split"));
+                    ui.horizontal(|ui| {
+                        let mut split = state.edit_state.split.is_some();
+                        if ui
+                            .checkbox(&mut split, "")
+                            .on_hover_text(format!(
+                                "Show the original left of a line that can be dragged. Hold '{}' to see all of the original.",
+                                lookup(&state.persistent_settings.shortcuts, &InputEvent::CompareOriginal)
+                            ))
+                            .changed()
+                        {
+                            state.edit_state.split = Some(0.5).filter(|_| split);
+                        }
+                        if let Some(split) = &mut state.edit_state.split {
+                            ui.add(egui::Slider::new(split, 0.0..=1.0).show_value(false));
+                        }
+                    });
+                    ui.end_row();
                 });
 
             ui.vertical_centered_justified(|ui| {
@@ -983,25 +2097,94 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                     if let Some(img) = &mut state.current_image {
                         *img = state.edit_state.result_pixel_op.clone();
                         state.edit_state = Default::default();
+                        state.original_texture = None;
                         // state.image_dimension = img.dimensions();
                         pixels_changed = true;
                         image_changed = true;
                     }
                 }
+                ui.checkbox(&mut state.edit_state.full_res_preview, "1:1 preview")
+                    .on_hover_text("Evaluate edits at full resolution while adjusting them. Slower on large images. The final result is always computed at full resolution.");
             });
 
             // Do the processing
 
+            if edit_overlay_ui(ctx, state) {
+                image_changed = true;
+            }
+
+            // paint into the mask of an operator
+            if state.edit_state.mask_painting() && !state.pointer_over_ui {
+                let uv = (
+                    state.cursor_relative.x / state.image_dimension.0 as f32,
+                    state.cursor_relative.y / state.image_dimension.1 as f32,
+                );
+                // use the settings of the paint brush
+                let brush = PaintStroke {
+                    color: [1., 1., 1., 1.],
+                    ..state
+                        .edit_state
+                        .paint_strokes
+                        .last()
+                        .map(|stroke| stroke.without_points())
+                        .unwrap_or_else(PaintStroke::new)
+                };
+                let (pressed, down) =
+                    ctx.input(|i| (i.pointer.primary_pressed(), i.pointer.primary_down()));
+                for (stack, changed) in [
+                    (&mut state.edit_state.image_op_stack, &mut image_changed),
+                    (&mut state.edit_state.pixel_op_stack, &mut pixels_changed),
+                ] {
+                    for item in stack.iter_mut().filter(|item| item.mask_editing) {
+                        if pressed || item.mask.is_empty() {
+                            item.mask.push(brush.clone());
+                        }
+                        if let (true, Some(stroke)) = (down, item.mask.last_mut()) {
+                            stroke.points.push(uv);
+                            *changed = true;
+                        }
+                    }
+                }
+            }
+
+            let dragging = ctx.input(|i| i.pointer.primary_down());
+
+            // While dragging, edits are evaluated on a downscaled proxy unless a 1:1 preview was requested
+            let preview_factor = match &state.current_image {
+                Some(img) if dragging && !state.edit_state.painting => {
+                    state.edit_state.preview_factor(img.dimensions())
+                }
+                _ => 1.0,
+            };
+
+            // A downscaled preview was shown while dragging, render it at full resolution now
+            if !dragging {
+                if state.edit_state.preview_pending {
+                    state.edit_state.preview_pending = false;
+                    image_changed = true;
+                }
+                if state.edit_state.pixel_preview_pending {
+                    state.edit_state.pixel_preview_pending = false;
+                    pixels_changed = true;
+                }
+            }
+
             // If expensive operations happened (modifying image geometry), process them here
             if image_changed {
                 if let Some(img) = &mut state.current_image {
                     let stamp = Instant::now();
-                    // start with a fresh copy of the unmodified image
-                    state.edit_state.result_image_op = img.clone();
-                    for operation in &mut state.edit_state.image_op_stack {
-                        if let Err(e) = operation.process_image(&mut state.edit_state.result_image_op) {
-                            error!("{e}")
-                        }
+                    if preview_factor < 1.0 {
+                        state.edit_state.preview_base = Some(process_image_preview(img, &state.edit_state.image_op_stack, &state.edit_state.brushes, preview_factor));
+                        state.edit_state.preview_pending = true;
+                    } else {
+                        // only evaluate the steps from the first changed one on
+                        state.edit_state.result_image_op = process_image_stack(
+                            img,
+                            &state.edit_state.image_op_stack,
+                            &mut state.edit_state.image_op_cache,
+                            &state.edit_state.brushes,
+                        );
+                        state.edit_state.preview_base = None;
                     }
                     info!(
                         "Image changed. Finished evaluating in {}s",
@@ -1016,7 +2199,76 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                 pixels_changed = true;
             }
 
-            if pixels_changed {
+            // The GPU preview only lasts while dragging, compute the real result afterwards
+            if state.edit_state.gpu_preview && !dragging {
+                pixels_changed = true;
+            }
+
+            let mut gpu_used = false;
+            if pixels_changed
+                && dragging
+                && !image_changed
+                && !state.edit_state.preview_pending
+                && state.persistent_settings.gpu_edits
+                && state.edit_state.paint_strokes.iter().all(|s| s.points.is_empty())
+            {
+                if let Some(data) = encode_pixel_ops(&state.edit_state.pixel_op_stack) {
+                    if state.pixel_shader.is_none() {
+                        match PixelShader::new(gfx) {
+                            Ok(shader) => state.pixel_shader = Some(shader),
+                            Err(e) => {
+                                error!("{e}, falling back to CPU edits");
+                                state.persistent_settings.gpu_edits = false;
+                            }
+                        }
+                    }
+                    if let Some(shader) = &state.pixel_shader {
+                        shader.set_operators(gfx, &data);
+                        if !state.edit_state.gpu_preview {
+                            // The shader works on the result of the image operators
+                            let result = &state.edit_state.result_image_op;
+                            match &mut state.current_texture {
+                                Some(tex) if tex.width() as u32 == result.width() && tex.height() as u32 == result.height() => {
+                                    result.update_texture(gfx, tex);
+                                }
+                                _ => {
+                                    state.current_texture = result.to_texture(gfx, state.sampling());
+                                }
+                            }
+                            state.edit_state.gpu_preview = true;
+                        }
+                        gpu_used = true;
+                    }
+                }
+            }
+
+            if pixels_changed && !gpu_used && preview_factor < 1.0 {
+                let stamp = Instant::now();
+                let edit_state = &mut state.edit_state;
+                let result_image_op = &edit_state.result_image_op;
+                let base = edit_state
+                    .preview_base
+                    .get_or_insert_with(|| preview_proxy(result_image_op, preview_factor));
+                let mut preview = base.clone();
+                process_pixel_stack(&mut preview, &state.edit_state.pixel_op_stack, &state.edit_state.brushes);
+                for stroke in &state.edit_state.paint_strokes {
+                    if !stroke.committed {
+                        stroke.render(&mut preview, &state.edit_state.brushes);
+                    }
+                }
+                match &mut state.current_texture {
+                    Some(tex) if tex.width() as u32 == preview.width() && tex.height() as u32 == preview.height() => {
+                        preview.update_texture(gfx, tex);
+                    }
+                    _ => {
+                        state.current_texture = preview.to_texture(gfx, state.sampling());
+                    }
+                }
+                state.edit_state.gpu_preview = false;
+                state.edit_state.pixel_preview_pending = true;
+                debug!("Finished preview in {} s", stamp.elapsed().as_secs_f32());
+            } else if pixels_changed && !gpu_used {
+                state.edit_state.gpu_preview = false;
                 // init result as a clean copy of image operation result
                 let stamp = Instant::now();
 
@@ -1026,7 +2278,7 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                 // only process pixel stack if it is empty so we don't run through pixels without need
                 if !state.edit_state.pixel_op_stack.is_empty() {
                     let ops = &state.edit_state.pixel_op_stack;
-                    process_pixels(&mut state.edit_state.result_pixel_op, ops);
+                    process_pixel_stack(&mut state.edit_state.result_pixel_op, ops, &state.edit_state.brushes);
 
                 }
 
@@ -1054,7 +2306,7 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                             state.edit_state.result_pixel_op.update_texture(gfx, tex);
                         } else {
                             state.current_texture =
-                                state.edit_state.result_pixel_op.to_texture(gfx, state.persistent_settings.linear_mag_filter);
+                                state.edit_state.result_pixel_op.to_texture(gfx, state.sampling());
                         }
                     }
                 }
@@ -1093,13 +2345,25 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                                 &state.edit_state.brushes,
                             );
                             stroke.committed = true;
+                            state.edit_state.preview_base = None;
+                            state.edit_state.image_op_cache.clear();
                             info!("Committed stroke {}", i);
                         }
                     }
                 }
             }
 
-            state.image_dimension = state.edit_state.result_pixel_op.dimensions();
+            state.image_dimension = match (&state.edit_state.preview_base, &state.current_image) {
+                // the proxy stands in for the full resolution result
+                (Some(base), Some(img)) if state.edit_state.previewing() => {
+                    let factor = state.edit_state.preview_factor(img.dimensions());
+                    (
+                        (base.width() as f32 / factor).round() as u32,
+                        (base.height() as f32 / factor).round() as u32,
+                    )
+                }
+                _ => state.edit_state.result_pixel_op.dimensions(),
+            };
 
             ui.vertical_centered_justified(|ui| {
                 if let Some(path) = &state.current_path {
@@ -1160,14 +2424,19 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
 
                 #[cfg(feature = "file_open")]
                 if state.current_image.is_some() {
-                    if ui.button(format!("{FLOPPY_DISK} Save as...")).clicked() {
+                    if ui.button(format!("{FLOPPY_DISK} Save as...")).clicked() && state.check_writable() {
 
                         let start_directory = state.persistent_settings.last_open_directory.clone();
 
                         let image_to_save = state.edit_state.result_pixel_op.clone();
                         let msg_sender = state.message_channel.0.clone();
                         let err_sender = state.message_channel.0.clone();
-                        let image_info = state.image_info.clone();
+                        let metadata = state
+                            .current_path
+                            .as_ref()
+                            .filter(|_| state.persistent_settings.keep_metadata)
+                            .and_then(|p| ImageMetadata::read(p).ok());
+                        let reset_orientation = state.edit_state.reorients();
 
                         std::thread::spawn(move || {
                             let file_dialog_result = rfd::FileDialog::new()
@@ -1185,15 +2454,12 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                                             Ok(_) => {
                                                 _ = msg_sender.send(Message::Saved(file_path.clone()));
                                                 debug!("Saved to {}", file_path.display());
-                                                // Re-apply exif
-                                                if let Some(info) = &image_info {
-                                                    // before doing anything, make sure we have raw exif data
-                                                    if info.raw_exif.is_some() {
-                                                        if let Err(e) = fix_exif(&file_path, info.raw_exif.clone()) {
-                                                            error!("{e}");
-                                                        } else {
-                                                            info!("Saved EXIF.")
-                                                        }
+                                                // Re-apply metadata of the original file
+                                                if let Some(metadata) = metadata.filter(|m| !m.is_empty()) {
+                                                    if let Err(e) = metadata.write(&file_path, image_to_save.dimensions(), reset_orientation) {
+                                                        error!("{e}");
+                                                    } else {
+                                                        info!("Saved metadata.")
                                                     }
                                                 }
                                             }
@@ -1225,7 +2491,11 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                         format!("{FLOPPY_DISK} Save")
                     };
 
-                    if ui.button(text).on_hover_text("Save the image. This will create a new file or overwrite.").clicked() {
+                    if ui.button(text).on_hover_text("Save the image. This will create a new file or overwrite.").clicked() && state.check_writable() {
+                        // read the metadata before the file is overwritten
+                        let metadata = Some(p)
+                            .filter(|_| state.persistent_settings.keep_metadata)
+                            .and_then(|p| ImageMetadata::read(p).ok());
                         match state
                         .edit_state
                         .result_pixel_op
@@ -1234,15 +2504,12 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                                 debug!("Saved to {}", p.display());
 
                                 state.send_message("Saved");
-                                //Re-apply exif
-                                if let Some(info) = &state.image_info {
-                                    // before doing anything, make sure we have raw exif data
-                                    if info.raw_exif.is_some() {
-                                        if let Err(e) = fix_exif(&p, info.raw_exif.clone()) {
-                                            error!("{e}");
-                                        } else {
-                                            info!("Saved EXIF.")
-                                        }
+                                // Re-apply metadata of the original file
+                                if let Some(metadata) = metadata.filter(|m| !m.is_empty()) {
+                                    if let Err(e) = metadata.write(p, state.edit_state.result_pixel_op.dimensions(), state.edit_state.reorients()) {
+                                        error!("{e}");
+                                    } else {
+                                        info!("Saved metadata.")
                                     }
                                 }
                             }
@@ -1253,23 +2520,15 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
                     }
 
                     if ui.button(format!("{ARCHIVE_TRAY} Save edits")).on_hover_text("Saves an .oculante metafile in the same directory as the image. This file will contain all edits and will be restored automatically if you open the image again. This leaves the original image unmodified and allows you to continue editing later.").clicked() {
-                        if let Ok(f) = std::fs::File::create(p.with_extension("oculante")) {
-                            _ = serde_json::to_writer_pretty(&f, &state.edit_state);
-                        }
+                        save_edits(state, false);
                     }
                     if ui.button(format!("{ARCHIVE_TRAY} Save directory edits")).on_hover_text("Saves an .oculante metafile in the same directory as the image. This file will contain all edits and will be restored automatically if you open the image again. This leaves the original image unmodified and allows you to continue editing later.").clicked() {
-                        if let Some(parent) = p.parent() {
-                            if let Ok(f) = std::fs::File::create(parent.join(".oculante")) {
-                                _ = serde_json::to_writer_pretty(&f, &state.edit_state);
-                            }
-
-                        }
-
+                        save_edits(state, true);
                     }
                 }
             });
 
-            if pixels_changed && state.persistent_settings.info_enabled {
+            if pixels_changed && !gpu_used && state.persistent_settings.info_enabled {
                 state.image_info = None;
                 send_extended_info(
                     &Some(state.edit_state.result_pixel_op.clone()),
@@ -1280,8 +2539,92 @@ pub fn edit_ui(app: &mut App, ctx: &Context, state: &mut OculanteState, gfx: &mu
         });
 }
 
+/// Interactive tools drawn on top of the image, such as perspective corners or the horizon line.
+/// Returns true if an operator was modified and the image needs to be processed again.
+fn edit_overlay_ui(ctx: &Context, state: &mut OculanteState) -> bool {
+    let mut changed = false;
+    let handle_size = 10.;
+    let size = vec2(
+        state.image_dimension.0 as f32,
+        state.image_dimension.1 as f32,
+    ) * state.image_geometry.scale;
+    let origin = pos2(state.image_geometry.offset.x, state.image_geometry.offset.y);
+    let color = ctx.style().visuals.selection.bg_fill;
+
+    for item in &mut state.edit_state.image_op_stack {
+        let op = &mut item.operation;
+        if let ImageOperation::Perspective {
+            corners,
+            editing: true,
+        } = op
+        {
+            let points = corners
+                .map(|c| origin + vec2(c.0 as f32 / 10000., c.1 as f32 / 10000.) * size)
+                .to_vec();
+            ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("perspective")))
+                .add(Shape::closed_line(points.clone(), Stroke::new(2., color)));
+
+            for (i, corner) in corners.iter_mut().enumerate() {
+                let r = egui::Area::new(Id::new("perspective_handle").with(i))
+                    .fixed_pos(points[i] - Vec2::splat(handle_size))
+                    .show(ctx, |ui| {
+                        let (rect, r) =
+                            ui.allocate_exact_size(Vec2::splat(handle_size * 2.), Sense::drag());
+                        ui.painter().circle(
+                            rect.center(),
+                            handle_size * 0.6,
+                            color,
+                            Stroke::new(2., Color32::WHITE),
+                        );
+                        r
+                    })
+                    .inner;
+                if r.dragged() {
+                    let uv = (points[i] + r.drag_delta() - origin) / size;
+                    corner.0 = (uv.x.clamp(0., 1.) * 10000.) as u32;
+                    corner.1 = (uv.y.clamp(0., 1.) * 10000.) as u32;
+                }
+            }
+        }
+
+        if let ImageOperation::Straighten { angle, editing, .. } = op {
+            if !*editing {
+                continue;
+            }
+            let r = egui::Area::new("horizon")
+                .fixed_pos(origin)
+                .show(ctx, |ui| ui.allocate_exact_size(size, Sense::drag()).1)
+                .inner
+                .on_hover_cursor(CursorIcon::Crosshair);
+            let start_id = Id::new("horizon_start");
+            if r.drag_started() {
+                if let Some(pos) = r.interact_pointer_pos() {
+                    ctx.data_mut(|d| d.insert_temp(start_id, pos));
+                }
+            }
+            let start = ctx.data(|d| d.get_temp::<Pos2>(start_id));
+            if let (Some(start), Some(end)) = (start, ctx.pointer_latest_pos()) {
+                ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("horizon_line")))
+                    .line_segment([start, end], Stroke::new(2., color));
+                if r.drag_released() {
+                    ctx.data_mut(|d| d.remove::<Pos2>(start_id));
+                    if start.distance(end) > handle_size {
+                        *angle =
+                            (level_angle((start.x, start.y), (end.x, end.y)) * 100.).round() as i32;
+                        *editing = false;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    changed
+}
+
 // TODO redo as impl UI
 pub fn tooltip(r: Response, tooltip: &str, hotkey: &str, _ui: &mut Ui) -> Response {
+    // screen readers name icon buttons by their tooltip
+    r.widget_info(|| WidgetInfo::labeled(WidgetType::Button, tooltip));
     r.on_hover_ui(|ui| {
         let avg = (ui.style().visuals.selection.bg_fill.r() as i32
             + ui.style().visuals.selection.bg_fill.g() as i32
@@ -1301,6 +2644,20 @@ pub fn tooltip(r: Response, tooltip: &str, hotkey: &str, _ui: &mut Ui) -> Respon
 }
 
 // TODO redo as impl UI
+/// A menu entry that shows the keys of its shortcut
+pub fn shortcut_button(
+    ui: &mut Ui,
+    text: &str,
+    state: &OculanteState,
+    event: InputEvent,
+) -> Response {
+    let mut button = egui::Button::new(text);
+    if let Some(keys) = state.persistent_settings.shortcuts.get(&event) {
+        button = button.shortcut_text(keypresses_as_string(keys));
+    }
+    ui.add(button)
+}
+
 pub fn unframed_button(text: impl Into<String>, ui: &mut Ui) -> Response {
     ui.add(egui::Button::new(RichText::new(text).size(ICON_SIZE)).frame(false))
 }
@@ -1415,13 +2772,17 @@ pub fn stroke_ui(
     combined_response
 }
 
-fn modifier_stack_ui(stack: &mut Vec<ImageOperation>, image_changed: &mut bool, ui: &mut Ui) {
+fn modifier_stack_ui(stack: &mut Vec<ImgOpItem>, image_changed: &mut bool, ui: &mut Ui) {
     let mut delete: Option<usize> = None;
     let mut swap: Option<(usize, usize)> = None;
+    // Drag handles of all operators, used to find where a dragged operator is dropped
+    let mut handles: Vec<Rect> = vec![];
+    let mut dragged: Option<usize> = None;
+    let mut dropped: Option<usize> = None;
 
     // egui::Grid::new("dfdfd").num_columns(2).show(ui, |ui| {
-    for (i, operation) in stack.iter_mut().enumerate() {
-        ui.label_i(&format!("{operation}"));
+    for (i, item) in stack.iter_mut().enumerate() {
+        ui.label_i(&format!("{}", item.operation));
 
         // let op draw itself and check for response
 
@@ -1429,9 +2790,11 @@ fn modifier_stack_ui(stack: &mut Vec<ImageOperation>, image_changed: &mut bool,
             // ui.end_row();
 
             // draw the image operator
-            if operation.ui(ui).changed() {
-                *image_changed = true;
-            }
+            ui.add_enabled_ui(item.active, |ui| {
+                if item.operation.ui(ui).changed() {
+                    *image_changed = true;
+                }
+            });
 
             // now draw the ordering/delete ui
             ui.add_space(45.);
@@ -1457,6 +2820,48 @@ fn modifier_stack_ui(stack: &mut Vec<ImageOperation>, image_changed: &mut bool,
                     *image_changed = true;
                 }
 
+                let handle = ui
+                    .add(egui::Label::new(DOTS_SIX_VERTICAL).sense(Sense::drag()))
+                    .on_hover_cursor(CursorIcon::Grab)
+                    .on_hover_text("Drag to reorder");
+                if handle.dragged() {
+                    dragged = Some(i);
+                }
+                if handle.drag_released() {
+                    dropped = Some(i);
+                }
+                handles.push(handle.rect);
+
+                if egui::Button::new(if item.active { EYE } else { EYE_SLASH })
+                    .small()
+                    .frame(false)
+                    .ui(ui)
+                    .on_hover_text("Enable or disable this operator")
+                    .clicked()
+                {
+                    item.active = !item.active;
+                    *image_changed = true;
+                }
+
+                let mask_color = if item.mask_editing || item.has_mask() {
+                    ui.style().visuals.selection.bg_fill
+                } else {
+                    ui.style().visuals.text_color()
+                };
+                let r = egui::Button::new(RichText::new(MASK_HAPPY).color(mask_color))
+                    .small()
+                    .frame(false)
+                    .ui(ui)
+                    .on_hover_text("Paint a mask on the image to limit this operator. Right click to clear the mask.");
+                if r.clicked() {
+                    item.mask_editing = !item.mask_editing;
+                }
+                if r.secondary_clicked() {
+                    item.mask.clear();
+                    item.mask_editing = false;
+                    *image_changed = true;
+                }
+
                 if egui::Button::new("⏶")
                     .small()
                     .frame(false)
@@ -1487,6 +2892,31 @@ fn modifier_stack_ui(stack: &mut Vec<ImageOperation>, image_changed: &mut bool,
     }
     // });
 
+    // Insert a dragged operator in front of the first operator below the pointer
+    if let Some(pointer) = ui.ctx().pointer_interact_pos() {
+        let target = handles.iter().filter(|r| r.center().y < pointer.y).count();
+        if dragged.is_some() {
+            let y = handles
+                .get(target)
+                .map(|r| r.top())
+                .or(handles.last().map(|r| r.bottom()))
+                .unwrap_or(pointer.y);
+            ui.painter().hline(
+                ui.clip_rect().x_range(),
+                y,
+                Stroke::new(2., ui.style().visuals.selection.bg_fill),
+            );
+        }
+        if let Some(from) = dropped {
+            let to = if target > from { target - 1 } else { target };
+            if to != from && to < stack.len() {
+                let item = stack.remove(from);
+                stack.insert(to, item);
+                *image_changed = true;
+            }
+        }
+    }
+
     if let Some(delete) = delete {
         stack.remove(delete);
     }
@@ -1513,168 +2943,2419 @@ fn jpg_lossless_ui(state: &mut OculanteState, ui: &mut Ui) {
             ui.label("These operations will immediately write changes to disk.");
             let mut reload = false;
 
-            ui.columns(3, |col| {
-                if col[0].button("➡ Rotate 90°").clicked() {
-                    if lossless_tx(
-                        p,
-                        turbojpeg::Transform {
-                            op: turbojpeg::TransformOp::Rot90,
-                            ..turbojpeg::Transform::default()
-                        },
-                    )
-                    .is_ok()
-                    {
-                        reload = true;
-                    }
+            // Reading the header needs the whole file, so only do it once per image
+            let info_id = Id::new(("lossless_info", p, state.image_dimension));
+            let info = match ui.data_mut(|d| d.get_temp::<Option<LosslessInfo>>(info_id)) {
+                Some(info) => info,
+                None => {
+                    let info = LosslessInfo::read(p).ok();
+                    ui.data_mut(|d| d.insert_temp(info_id, info));
+                    info
                 }
-                //◑
-                if col[1].button("⬅ Rotate -90°").clicked() {
-                    if lossless_tx(
-                        p,
-                        turbojpeg::Transform {
-                            op: turbojpeg::TransformOp::Rot270,
-                            ..turbojpeg::Transform::default()
-                        },
-                    )
-                    .is_ok()
-                    {
-                        reload = true;
+            };
+
+            let transforms = [
+                ("➡ Rotate 90°", turbojpeg::TransformOp::Rot90),
+                ("⬅ Rotate -90°", turbojpeg::TransformOp::Rot270),
+                ("⬇ Rotate 180°", turbojpeg::TransformOp::Rot180),
+                ("Flip H", turbojpeg::TransformOp::Hflip),
+                ("Flip V", turbojpeg::TransformOp::Vflip),
+                ("Transpose", turbojpeg::TransformOp::Transpose),
+                ("Transverse", turbojpeg::TransformOp::Transverse),
+            ];
+
+            for row in transforms.chunks(3) {
+                ui.columns(3, |col| {
+                    for (i, (label, op)) in row.iter().enumerate() {
+                        let perfect = info.map(|info| info.is_perfect(*op)).unwrap_or(true);
+                        let mut r = col[i].button(if perfect {
+                            label.to_string()
+                        } else {
+                            format!("{label} {WARNING}")
+                        });
+                        if let Some(info) = info.filter(|_| !perfect) {
+                            r = r.on_hover_text(format!(
+                                "The image size is not a multiple of {}x{} blocks. Partial blocks at the edges will be trimmed.",
+                                info.mcu.0, info.mcu.1
+                            ));
+                        }
+                        if r.clicked() && state.check_writable() {
+                            match lossless_tx(
+                                p,
+                                turbojpeg::Transform {
+                                    op: *op,
+                                    ..turbojpeg::Transform::default()
+                                },
+                            ) {
+                                Ok(_) => reload = true,
+                                Err(e) => log::warn!("{e}"),
+                            }
+                        }
+                    }
+                });
+            }
+
+            ui.vertical_centered_justified(|ui| {
+
+
+
+                let crop_ops = state
+                    .edit_state
+                    .image_op_stack
+                    .iter()
+                    .map(|item| &item.operation)
+                    .filter(|op| matches!(op, ImageOperation::Crop(_)))
+                    .collect::<Vec<_>>();
+
+                let crop = crop_ops
+                    .first()
+                    .cloned()
+                    .cloned()
+                    .unwrap_or(ImageOperation::Crop([0, 0, 0, 0]));
+
+                if crop_ops.is_empty() {
+                    info!("A missing crop operator was added.");
+                    state
+                        .edit_state
+                        .image_op_stack
+                        .push(ImgOpItem::new(ImageOperation::Crop([0, 0, 0, 0])))
+                }
+
+                ui.add_enabled_ui(crop != ImageOperation::Crop([0, 0, 0, 0]), |ui| {
+
+                    let crop_hint = match info {
+                        Some(info) => format!("Crop according to values defined in the operator stack above. The top left corner snaps to {}x{} blocks.", info.mcu.0, info.mcu.1),
+                        None => "Crop according to values defined in the operator stack above".to_string(),
+                    };
+                    if ui
+                        .button("Crop")
+                        .on_hover_text(crop_hint)
+                        .on_disabled_hover_text("Please modify crop values above before cropping. You would be cropping nothing right now.")
+                        .clicked()
+                        && state.check_writable()
+                    {
+                        match crop {
+                            ImageOperation::Crop(amt) => {
+                                debug!("CROP {:?}", amt);
+
+                                let dim = state
+                                    .current_image
+                                    .as_ref()
+                                    .map(|i| i.dimensions())
+                                    .unwrap_or_default();
+
+                                let crop_range = cropped_range(&amt, &dim);
+
+                                match lossless_tx(
+                                    p,
+                                    turbojpeg::Transform {
+                                        op: turbojpeg::TransformOp::None,
+                                        crop: Some(turbojpeg::TransformCrop {
+                                            x: crop_range[0] as usize,
+                                            y: crop_range[1] as usize,
+                                            width: Some(crop_range[2] as usize),
+                                            height: Some(crop_range[3] as usize),
+                                        }),
+                                        ..turbojpeg::Transform::default()
+                                    },
+                                ) {
+                                    Ok(_) => reload = true,
+                                    Err(e) => log::warn!("{e}"),
+                                };
+                            }
+                            _ => (),
+                        };
+                    }
+                });
+                });
+
+
+            if reload {
+                state.is_loaded = false;
+                state.player.cache.clear();
+                state.player.load(&p, state.message_channel.0.clone());
+            }
+        });
+    }
+}
+
+pub fn scrubber_ui(state: &mut OculanteState, ui: &mut Ui) {
+    let len = state.scrubber.len().saturating_sub(1);
+
+    ui.horizontal(|ui| {
+        let filtered = state.persistent_settings.sharpness_sort
+            || state.persistent_settings.min_sharpness > 0.
+            || state.similar_to.is_some();
+        let icon = if filtered {
+            RichText::new(FUNNEL).color(ui.style().visuals.selection.bg_fill)
+        } else {
+            RichText::new(FUNNEL)
+        };
+        ui.menu_button(icon, |ui| {
+            let mut relist = ui
+                .checkbox(&mut state.persistent_settings.sharpness_sort, "Sharpest first")
+                .changed();
+            ui.horizontal(|ui| {
+                ui.label("Hide sharpness below");
+                let r = ui.add(
+                    egui::DragValue::new(&mut state.persistent_settings.min_sharpness)
+                        .clamp_range(0.0..=10000.)
+                        .speed(1.),
+                );
+                relist |= r.drag_released() || (r.changed() && !r.dragged());
+            });
+            ui.separator();
+            match &state.similar_to {
+                Some((reference, _)) => {
+                    ui.label(format!(
+                        "Similar to {}",
+                        reference.file_name().unwrap_or_default().to_string_lossy()
+                    ));
+                    let r = ui
+                        .add(egui::Slider::new(&mut state.persistent_settings.max_hash_distance, 0..=32).text("Max difference"))
+                        .on_hover_text("How many of the 64 bits of the perceptual hashes may differ");
+                    relist |= r.drag_released() || (r.changed() && !r.dragged());
+                    if ui.button(format!("{X} Show all")).clicked() {
+                        state.similar_to = None;
+                        relist = true;
+                    }
+                }
+                None => {
+                    if ui
+                        .add_enabled(state.current_image.is_some(), egui::Button::new(format!("{IMAGES} Find similar images")))
+                        .on_hover_text("Show the images of the folder that look like this one, like edited variants, most similar first")
+                        .clicked()
+                    {
+                        find_similar(state);
+                        ui.close_menu();
+                    }
+                }
+            }
+            if relist {
+                // start from the complete folder, then sort and filter it when it arrives
+                if let Some(path) = state.current_path.clone() {
+                    list_folder(state, path);
+                }
+            }
+        })
+        .response
+        .on_hover_text("Sort and filter the folder by sharpness or similarity. Images are analyzed in the background.");
+
+        if ui
+            .slider_timeline(&mut state.scrubber.index, 0..=len)
+            .changed()
+        {
+            let p = state.scrubber.set(state.scrubber.index);
+            state.current_path = Some(p.clone());
+            state.player.load(&p, state.message_channel.0.clone());
+        }
+    });
+}
+
+/// Playback controls of the current image sequence
+pub fn sequence_ui(state: &mut OculanteState, ui: &mut Ui) {
+    let current = state
+        .current_path
+        .as_ref()
+        .and_then(|p| state.sequence.as_ref()?.sequence.position(p));
+    let shortcut = lookup(
+        &state.persistent_settings.shortcuts,
+        &InputEvent::PlaySequence,
+    );
+    let sequence = match &mut state.sequence {
+        Some(sequence) => sequence,
+        None => return,
+    };
+    let numbers = sequence
+        .sequence
+        .frames
+        .iter()
+        .map(|f| f.0)
+        .collect::<Vec<_>>();
+    let number = |i: usize| numbers.get(i).copied().unwrap_or_default();
+    ui.horizontal(|ui| {
+        let icon = if sequence.playing { PAUSE } else { PLAY };
+        if ui
+            .button(icon)
+            .on_hover_text(format!("Play the sequence ({shortcut})"))
+            .clicked()
+        {
+            sequence.toggle();
+        }
+        ui.label(sequence.sequence.name());
+        if let Some(current) = current {
+            ui.label(format!(
+                "Frame {} ({}/{})",
+                number(current),
+                current + 1,
+                sequence.sequence.frames.len()
+            ));
+        }
+        ui.add(
+            egui::DragValue::new(&mut sequence.fps)
+                .clamp_range(1.0..=120.0)
+                .speed(0.5)
+                .suffix(" fps"),
+        );
+
+        ui.label(format!(
+            "Loop {}-{}",
+            number(sequence.range.0),
+            number(sequence.range.1)
+        ));
+        if let Some(current) = current {
+            if ui
+                .button("Set in")
+                .on_hover_text("Start the loop at this frame")
+                .clicked()
+            {
+                sequence.range = (current, sequence.range.1.max(current));
+            }
+            if ui
+                .button("Set out")
+                .on_hover_text("End the loop at this frame")
+                .clicked()
+            {
+                sequence.range = (sequence.range.0.min(current), current);
+            }
+        }
+        if ui.button("Whole sequence").clicked() {
+            sequence.range = (0, sequence.sequence.frames.len() - 1);
+        }
+    });
+}
+
+/// Playback controls of an animated image
+pub fn animation_ui(state: &mut OculanteState, ui: &mut Ui) {
+    let shortcuts = &state.persistent_settings.shortcuts;
+    let play = lookup(shortcuts, &InputEvent::PlaySequence);
+    let previous = lookup(shortcuts, &InputEvent::PreviousFrame);
+    let next = lookup(shortcuts, &InputEvent::NextFrame);
+    let animation = match &mut state.animation {
+        Some(animation) => animation,
+        None => return,
+    };
+    let before = (animation.index, animation.playing);
+    // whether to save a spritesheet, and whether to ask for the folder
+    let mut export = None;
+    ui.horizontal(|ui| {
+        if ui
+            .button(SKIP_BACK)
+            .on_hover_text(format!("Previous frame ({previous})"))
+            .clicked()
+        {
+            animation.step(false);
+        }
+        let icon = if animation.playing { PAUSE } else { PLAY };
+        if ui
+            .button(icon)
+            .on_hover_text(format!("Play the animation ({play})"))
+            .clicked()
+        {
+            animation.toggle();
+        }
+        if ui
+            .button(SKIP_FORWARD)
+            .on_hover_text(format!("Next frame ({next})"))
+            .clicked()
+        {
+            animation.step(true);
+        }
+        let count = animation.frames.len();
+        ui.label(format!("Frame {}/{count}", animation.index + 1));
+        let mut index = animation.index;
+        if ui
+            .add(egui::Slider::new(&mut index, 0..=count - 1).show_value(false))
+            .changed()
+        {
+            animation.playing = false;
+            animation.seek(index);
+        }
+        ui.label(format!(
+            "{} ms",
+            animation.delay(animation.index).as_millis()
+        ))
+        .on_hover_text(format!(
+            "How long this frame is shown. One loop takes {:.2} s at normal speed.",
+            animation.duration().as_secs_f32()
+        ));
+        ui.add(
+            egui::DragValue::new(&mut animation.speed)
+                .clamp_range(0.1..=10.0)
+                .speed(0.05)
+                .suffix("x"),
+        )
+        .on_hover_text("Playback speed");
+        ui.toggle_value(&mut animation.looping, format!("{REPEAT} Loop"));
+        ui.menu_button(format!("{EXPORT} Export"), |ui| {
+            ui.add(
+                egui::DragValue::new(&mut animation.sheet_columns)
+                    .clamp_range(1..=256)
+                    .prefix("Columns: "),
+            )
+            .on_hover_text("Frames per row of the spritesheet");
+            if ui
+                .button("Frames next to image")
+                .on_hover_text(
+                    "Save every frame as a numbered PNG, in a folder named after the image",
+                )
+                .clicked()
+            {
+                export = Some((false, false));
+                ui.close_menu();
+            }
+            if ui
+                .button("Spritesheet next to image")
+                .on_hover_text(
+                    "Save all frames in one image, with a JSON atlas of where each frame is",
+                )
+                .clicked()
+            {
+                export = Some((true, false));
+                ui.close_menu();
+            }
+            #[cfg(feature = "file_open")]
+            {
+                if ui.button("Frames to...").clicked() {
+                    export = Some((false, true));
+                    ui.close_menu();
+                }
+                if ui.button("Spritesheet to...").clicked() {
+                    export = Some((true, true));
+                    ui.close_menu();
+                }
+            }
+        });
+    });
+    // show the frame that was picked
+    if before != (animation.index, animation.playing) {
+        state.redraw.invalidate(Reason::Ui);
+    }
+    let (spritesheet, choose_folder) = match export {
+        Some(export) => export,
+        None => return,
+    };
+    let (parent, stem) = match state
+        .current_path
+        .as_ref()
+        .and_then(|p| Some((p.parent()?, p.file_stem()?)))
+    {
+        Some((parent, stem)) => (parent.to_path_buf(), stem.to_string_lossy().to_string()),
+        None => return,
+    };
+    if choose_folder {
+        #[cfg(feature = "file_open")]
+        {
+            let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+            export_animation(state, spritesheet, move || {
+                let _dialog = dialog;
+                rfd::FileDialog::new().set_directory(&parent).pick_folder()
+            });
+        }
+    } else if spritesheet {
+        export_animation(state, spritesheet, move || Some(parent));
+    } else {
+        export_animation(state, spritesheet, move || {
+            Some(parent.join(format!("{stem}_frames")))
+        });
+    }
+}
+
+/// The frame number on top of a playing sequence
+pub fn sequence_osd(ctx: &Context, state: &OculanteState) {
+    let frame = state.current_path.as_ref().and_then(|p| {
+        let sequence = &state.sequence.as_ref()?.sequence;
+        sequence.frames.get(sequence.position(p)?)
+    });
+    if let Some((number, _)) = frame {
+        egui::Area::new("sequence_osd")
+            .anchor(Align2::RIGHT_TOP, [-20., 60.])
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(number.to_string()).heading().strong());
+            });
+    }
+}
+
+/// The page at the top of the window while scroll reading
+pub fn scroll_reader_osd(ctx: &Context, state: &OculanteState) {
+    let reader = &state.scroll_reader;
+    egui::Area::new("scroll_reader_osd")
+        .anchor(Align2::RIGHT_BOTTOM, [-20., -20.])
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.label(
+                RichText::new(format!(
+                    "{} / {}",
+                    reader.position.0 + 1,
+                    reader.pages.len()
+                ))
+                .heading()
+                .strong(),
+            );
+        });
+}
+
+/// Name, frame number and other labels sent with an image over the network
+pub fn network_osd(ctx: &Context, state: &OculanteState) {
+    let metadata = match &state.network_metadata {
+        Some(metadata) => metadata,
+        None => return,
+    };
+    egui::Area::new("network_osd")
+        .anchor(Align2::LEFT_TOP, [20., 60.])
+        .interactable(false)
+        .show(ctx, |ui| {
+            if let Some(name) = &metadata.name {
+                ui.label(RichText::new(name).heading().strong());
+            }
+            if let Some(frame) = metadata.frame {
+                ui.label(RichText::new(format!("Frame {frame}")).strong());
+            }
+            egui::Grid::new("network_osd_fields").show(ui, |ui| {
+                for (key, value) in &metadata.fields {
+                    ui.label(RichText::new(key).weak());
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Boxes and labels of the dataset under review over the image, and its verdict
+pub fn dataset_osd(ctx: &Context, state: &OculanteState) {
+    let (dataset, path) = match (&state.dataset.dataset, &state.current_path) {
+        (Some(dataset), Some(path)) => (dataset, path),
+        _ => return,
+    };
+    let painter = ctx.layer_painter(LayerId::background());
+    let origin = pos2(state.image_geometry.offset.x, state.image_geometry.offset.y);
+    let scale = state.image_geometry.scale;
+    let (width, height) = state.image_dimension;
+    let font = FontId::proportional(13.);
+    let mut image_labels = vec![];
+    for annotation in dataset.visible(path) {
+        let [r, g, b] = dataset.color(&annotation.label);
+        let color = Color32::from_rgb(r, g, b);
+        let [x, y, w, h] = match annotation.pixel_rect(width, height) {
+            Some(rect) => rect,
+            None => {
+                image_labels.push((annotation.label, color));
+                continue;
+            }
+        };
+        let rect = Rect::from_min_size(origin + vec2(x, y) * scale, vec2(w, h) * scale);
+        painter.rect_stroke(rect, 0., Stroke::new(2., color));
+        let galley = painter.layout_no_wrap(annotation.label, font.clone(), Color32::WHITE);
+        let tag = Rect::from_min_size(
+            rect.left_top() - vec2(0., galley.size().y + 2.),
+            galley.size() + vec2(6., 2.),
+        );
+        painter.rect_filled(tag, 0., color);
+        painter.galley(tag.min + vec2(3., 1.), galley);
+    }
+    let verdict = dataset.verdicts.get(path).copied();
+    if image_labels.is_empty() && verdict.is_none() {
+        return;
+    }
+    egui::Area::new("dataset_osd")
+        .anchor(Align2::RIGHT_TOP, [-20., 60.])
+        .interactable(false)
+        .show(ctx, |ui| {
+            match verdict {
+                Some(Verdict::Accepted) => ui.label(
+                    RichText::new(format!("{CHECK} Accepted"))
+                        .heading()
+                        .strong()
+                        .color(Color32::from_rgb(44, 160, 44)),
+                ),
+                Some(Verdict::Rejected) => ui.label(
+                    RichText::new(format!("{X} Rejected"))
+                        .heading()
+                        .strong()
+                        .color(Color32::from_rgb(214, 39, 40)),
+                ),
+                None => ui.label(""),
+            };
+            for (label, color) in image_labels {
+                ui.label(RichText::new(label).strong().color(color));
+            }
+        });
+}
+
+/// The magnified area under the cursor, with the value of the center pixel
+pub fn loupe_osd(ctx: &Context, state: &OculanteState, gfx: &mut Graphics) {
+    let (texture, img) = match (&state.current_texture, &state.current_image) {
+        (Some(texture), Some(img)) => (texture, img),
+        _ => return,
+    };
+    // prefer edit result if present
+    let img = if state.edit_state.result_pixel_op.width() > 0 {
+        &state.edit_state.result_pixel_op
+    } else {
+        img
+    };
+    let cursor = (state.cursor_relative.x, state.cursor_relative.y);
+    let pixel = match img.get_pixel_checked(cursor.0 as u32, cursor.1 as u32) {
+        Some(p) if !state.pointer_over_ui && cursor.0 >= 0. && cursor.1 >= 0. => p,
+        _ => return,
+    };
+    let zoom = state.persistent_settings.loupe_zoom;
+    let size = state.persistent_settings.loupe_size;
+    let [uv_min, uv_max] = loupe::uv_rect(cursor, state.image_dimension, size, zoom);
+    let screen = ctx.screen_rect();
+    let (x, y) = loupe::position(
+        (state.cursor.x, state.cursor.y),
+        size,
+        (screen.width(), screen.height()),
+    );
+    let rect = Rect::from_min_size(pos2(x, y), vec2(size, size));
+
+    let tex_id = gfx.egui_register_texture(texture).id;
+    let painter = ctx.layer_painter(LayerId::new(Order::Tooltip, Id::new("loupe")));
+    painter.rect_filled(rect, 0., Color32::BLACK);
+    painter.image(
+        tex_id,
+        rect,
+        Rect::from_min_max(pos2(uv_min[0], uv_min[1]), pos2(uv_max[0], uv_max[1])),
+        Color32::WHITE,
+    );
+    // the pixel whose value is shown
+    let center = Rect::from_center_size(rect.center(), vec2(zoom, zoom));
+    painter.rect_stroke(center.expand(1.), 0., Stroke::new(1., Color32::BLACK));
+    painter.rect_stroke(center, 0., Stroke::new(1., Color32::WHITE));
+    painter.rect_stroke(rect, 0., Stroke::new(2., Color32::from_gray(80)));
+
+    let color = [
+        pixel[0] as f32,
+        pixel[1] as f32,
+        pixel[2] as f32,
+        pixel[3] as f32,
+    ];
+    let galley = painter.layout_no_wrap(
+        format!(
+            "{} @ {:.0},{:.0}",
+            disp_col(color),
+            cursor.0.floor(),
+            cursor.1.floor()
+        ),
+        FontId::monospace(11.),
+        Color32::WHITE,
+    );
+    let label = Rect::from_min_size(
+        rect.left_bottom() - vec2(0., galley.size().y + 4.),
+        vec2(rect.width(), galley.size().y + 4.),
+    );
+    painter.rect_filled(label, 0., Color32::from_black_alpha(180));
+    painter.galley(label.min + vec2(4., 2.), galley);
+}
+
+/// The changelog of an available update, and whether to install it
+#[cfg(feature = "update")]
+pub fn update_ui(ctx: &Context, state: &mut OculanteState) {
+    let update = match &state.available_update {
+        Some(update) if state.show_update => update.clone(),
+        _ => return,
+    };
+    let mut open = true;
+    egui::Window::new(format!("Update to {}", update.version))
+        .open(&mut open)
+        .collapsible(false)
+        .anchor(Align2::CENTER_CENTER, [0., 0.])
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    ui.label(&update.changelog);
+                });
+            let verifies = crate::update::verifies_signatures();
+            if !verifies {
+                ui.label(RichText::new("This build can't verify the signature of updates. Download the release from GitHub instead.").weak());
+            }
+            ui.horizontal(|ui| {
+                if ui.add_enabled(verifies, egui::Button::new("Install")).clicked() {
+                    state.send_message(&format!("Installing {}...", update.version));
+                    crate::update::install(update.clone(), state.message_channel.0.clone());
+                    state.available_update = None;
+                }
+                if ui.button("Later").clicked() {
+                    state.show_update = false;
+                }
+            });
+        });
+    if !open {
+        state.show_update = false;
+    }
+}
+
+/// Recent log messages, for systems without a console
+pub fn log_ui(ctx: &Context, state: &mut OculanteState) {
+    let lines = crate::logging::recent(state.log_level);
+    let mut log_enabled = state.log_enabled;
+    egui::Window::new("Log")
+        .open(&mut log_enabled)
+        .resizable(true)
+        .default_width(600.)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Level")
+                    .selected_text(state.log_level.to_string())
+                    .show_ui(ui, |ui| {
+                        for level in log::Level::iter() {
+                            ui.selectable_value(
+                                &mut state.log_level,
+                                level.to_level_filter(),
+                                level.to_string(),
+                            );
+                        }
+                    });
+                if ui.button("Copy").clicked() {
+                    let text = lines
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+                        Ok(_) => state.send_message("Log copied"),
+                        Err(e) => state.send_message_err(&format!("Could not copy: {e}")),
+                    }
+                }
+            });
+            egui::ScrollArea::both()
+                .max_height(400.)
+                .stick_to_bottom(true)
+                .auto_shrink([false, true])
+                .show_rows(
+                    ui,
+                    ui.text_style_height(&egui::TextStyle::Monospace),
+                    lines.len(),
+                    |ui, range| {
+                        for line in &lines[range] {
+                            let color = match line.level {
+                                log::Level::Error => Color32::RED,
+                                log::Level::Warn => Color32::GOLD,
+                                _ => ui.visuals().text_color(),
+                            };
+                            ui.label(RichText::new(line.to_string()).monospace().color(color));
+                        }
+                    },
+                );
+        });
+    state.log_enabled = log_enabled;
+}
+
+/// Search the images below the current folder and browse the results
+pub fn search_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.search.enabled;
+    egui::Window::new("Search")
+        .open(&mut enabled)
+        .resizable(true)
+        .default_width(400.)
+        .show(ctx, |ui| {
+            let r = ui.add(
+                egui::TextEdit::singleline(&mut state.search.query)
+                    .hint_text("Words in file names, EXIF data or metadata")
+                    .desired_width(f32::INFINITY),
+            );
+            if r.changed() {
+                if let Some(index) = &state.search.index {
+                    state.search.results = index.search(&state.search.query);
+                }
+            }
+            ui.horizontal(|ui| {
+                let root = state.search.index.as_ref().map(|i| i.root.clone());
+                if state.search.indexing {
+                    ui.spinner();
+                    ui.label("Indexing...");
+                } else if let Some(index) = &state.search.index {
+                    ui.label(format!(
+                        "{} of {} images in {}",
+                        state.search.results.len(),
+                        index.files.len(),
+                        index.root.display()
+                    ));
+                }
+                let folder = state
+                    .current_path
+                    .as_ref()
+                    .and_then(|p| p.parent())
+                    .map(|p| p.to_path_buf());
+                if let Some(folder) = folder.filter(|_| !state.search.indexing) {
+                    let text = if root.as_ref() == Some(&folder) {
+                        "Reindex"
+                    } else {
+                        "Index this folder"
+                    };
+                    if ui.button(format!("{ARROWS_CLOCKWISE} {text}")).clicked() {
+                        index_search(state, folder);
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                if state.search.browsing {
+                    if ui.button(format!("{FOLDER} Back to folder")).clicked() {
+                        stop_browsing_search(state);
+                    }
+                } else if ui
+                    .add_enabled(
+                        !state.search.results.is_empty(),
+                        egui::Button::new(format!("{IMAGES} Browse results")),
+                    )
+                    .on_hover_text(
+                        "List the results in the index slider and with next and previous image",
+                    )
+                    .clicked()
+                {
+                    state.search.browsing = true;
+                    let current = state.current_path.clone().unwrap_or_default();
+                    state.scrubber = crate::scrubber::Scrubber {
+                        index: state
+                            .search
+                            .results
+                            .iter()
+                            .position(|p| *p == current)
+                            .unwrap_or_default(),
+                        entries: state.search.results.clone(),
+                        wrap: state.persistent_settings.wrap_folder,
+                    };
+                    if !state.search.results.contains(&current) {
+                        let first = state.scrubber.set(0);
+                        load_image_from_path(&first, state);
+                    }
+                }
+            });
+            ui.separator();
+            let root = state
+                .search
+                .index
+                .as_ref()
+                .map(|i| i.root.clone())
+                .unwrap_or_default();
+            let results = state.search.results.clone();
+            egui::ScrollArea::vertical()
+                .max_height(400.)
+                .auto_shrink([false, true])
+                .show_rows(
+                    ui,
+                    ui.text_style_height(&egui::TextStyle::Body),
+                    results.len(),
+                    |ui, range| {
+                        for path in &results[range] {
+                            let name = path.strip_prefix(&root).unwrap_or(path).to_string_lossy();
+                            let current = state.current_path.as_ref() == Some(path);
+                            if ui.selectable_label(current, name).clicked() && !current {
+                                if state.search.browsing {
+                                    state.scrubber.select(path);
+                                }
+                                load_image_from_path(path, state);
+                            }
+                        }
+                    },
+                );
+        });
+    if !enabled {
+        state.search.enabled = false;
+        stop_browsing_search(state);
+    }
+}
+
+/// A summary of the images in the folder of the current image
+pub fn folder_stats_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.folder_stats.enabled;
+    egui::Window::new("Folder statistics")
+        .open(&mut enabled)
+        .resizable(true)
+        .default_width(360.)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if state.folder_stats.computing {
+                    ui.spinner();
+                    ui.label("Reading files...");
+                } else if let Some(stats) = &state.folder_stats.stats {
+                    ui.label(stats.folder.display().to_string());
+                }
+                let folder = state
+                    .current_path
+                    .as_ref()
+                    .and_then(|p| p.parent())
+                    .map(|p| p.to_path_buf());
+                if let Some(folder) = folder.filter(|_| !state.folder_stats.computing) {
+                    let computed = state
+                        .folder_stats
+                        .stats
+                        .as_ref()
+                        .map(|s| s.folder == folder)
+                        == Some(true);
+                    let text = if computed { "Refresh" } else { "This folder" };
+                    if ui.button(format!("{ARROWS_CLOCKWISE} {text}")).clicked() {
+                        compute_folder_stats(state, folder);
+                    }
+                }
+            });
+            let stats = match &state.folder_stats.stats {
+                Some(stats) => stats,
+                None => return,
+            };
+            ui.separator();
+            let mb = |bytes: u64| format!("{:.1} MB", bytes as f64 / 1024. / 1024.);
+            // one row per value, with a bar relative to the largest count
+            let bars = |ui: &mut Ui, id: &str, rows: Vec<(String, usize, String)>| {
+                let max = rows.iter().map(|r| r.1).max().unwrap_or_default().max(1);
+                egui::Grid::new(id).num_columns(3).show(ui, |ui| {
+                    for (label, count, extra) in rows {
+                        ui.label(label);
+                        ui.add(
+                            egui::ProgressBar::new(count as f32 / max as f32)
+                                .desired_width(120.)
+                                .text(count.to_string()),
+                        );
+                        ui.label(extra);
+                        ui.end_row();
+                    }
+                });
+            };
+            egui::ScrollArea::vertical()
+                .max_height(500.)
+                .show(ui, |ui| {
+                    ui.label(format!("{} images, {}", stats.files, mb(stats.total_size)));
+
+                    ui.collapsing("Formats", |ui| {
+                        let rows = stats
+                            .formats
+                            .iter()
+                            .map(|(format, (count, size))| (format.clone(), *count, mb(*size)))
+                            .collect();
+                        bars(ui, "stats_formats", rows);
+                    });
+                    ui.collapsing("Dimensions", |ui| {
+                        let rows = stats
+                            .dimensions
+                            .iter()
+                            .map(|(class, count)| (class.clone(), *count, String::new()))
+                            .collect();
+                        bars(ui, "stats_dimensions", rows);
+                    });
+                    ui.collapsing("Dates", |ui| {
+                        let rows = stats
+                            .months
+                            .iter()
+                            .map(|(month, count)| (month.clone(), *count, String::new()))
+                            .collect();
+                        bars(ui, "stats_months", rows);
+                    });
+                    ui.collapsing("Cameras", |ui| {
+                        if stats.cameras.is_empty() {
+                            ui.label("No camera in EXIF data");
+                        }
+                        let rows = stats
+                            .cameras
+                            .iter()
+                            .map(|(camera, count)| (camera.clone(), *count, String::new()))
+                            .collect();
+                        bars(ui, "stats_cameras", rows);
+                    });
+                });
+        });
+    state.folder_stats.enabled &= enabled;
+}
+
+/// Colormap and range of single channel images
+fn data_ui(ui: &mut Ui, state: &mut OculanteState, gfx: &mut Graphics) {
+    let view = match &mut state.data_view {
+        Some(view) if Some(&view.path) == state.current_path.as_ref() => view,
+        _ => return,
+    };
+    let (range, max) = match &view.image {
+        Some(image) => (image.range, image.max),
+        None => return,
+    };
+
+    let mut changed = false;
+    ui.collapsing("Colormap", |ui| {
+        changed |= ui
+            .checkbox(&mut view.active, "Show in false color")
+            .changed();
+        ui.add_enabled_ui(view.active, |ui| {
+            egui::ComboBox::from_label("Colormap")
+                .selected_text(view.colormap.to_string())
+                .show_ui(ui, |ui| {
+                    for colormap in Colormap::iter() {
+                        changed |= ui
+                            .selectable_value(&mut view.colormap, colormap, colormap.to_string())
+                            .changed();
+                    }
+                });
+            ui.label(format!("Data from {:.0} to {:.0}", range.0, range.1));
+            changed |= ui
+                .checkbox(&mut view.autoscale, "Autoscale")
+                .on_hover_text("Map the lowest and highest value of the image to the colormap")
+                .changed();
+            ui.add_enabled_ui(!view.autoscale, |ui| {
+                ui.horizontal(|ui| {
+                    let speed = max / 1000.;
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut view.low)
+                                .speed(speed)
+                                .clamp_range(0.0..=max)
+                                .prefix("Min: "),
+                        )
+                        .changed();
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut view.high)
+                                .speed(speed)
+                                .clamp_range(0.0..=max)
+                                .prefix("Max: "),
+                        )
+                        .changed();
+                });
+            });
+        });
+    });
+
+    if changed {
+        apply_data_view(state, gfx);
+    }
+}
+
+/// How the current JPEG or PNG file was encoded
+fn compression_ui(ui: &mut Ui, state: &mut OculanteState) {
+    let path = match &state.current_path {
+        Some(p) if state.is_loaded => p.clone(),
+        _ => return,
+    };
+    if state.compression.as_ref().map(|c| &c.path) != Some(&path) {
+        state.compression = Some(CompressionView::new(&path));
+    }
+    let info = match state.compression.as_ref().and_then(|c| c.info.as_ref()) {
+        Some(info) => info,
+        None => return,
+    };
+
+    ui.collapsing("Compression", |ui| {
+        egui::Grid::new("compression").striped(true).show(ui, |ui| {
+            match info {
+                CompressionInfo::Jpeg(jpeg) => {
+                    ui.label("Quality");
+                    match jpeg.quality() {
+                        Some(quality) => ui.label(format!("~{quality}"))
+                            .on_hover_text("Estimated from the luminance quantization table, assuming libjpeg scaling"),
+                        None => ui.label("Unknown"),
+                    };
+                    ui.end_row();
+                    ui.label("Subsampling");
+                    ui.label(jpeg.subsampling());
+                    ui.end_row();
+                    ui.label("Process");
+                    ui.label(jpeg.process());
+                    ui.end_row();
+                    for table in &jpeg.tables {
+                        ui.label(format!("Table {}", table.id));
+                        ui.label(
+                            RichText::new(format!("{}-{}, mean {:.1}", table.min(), table.max(), table.mean()))
+                                .monospace(),
+                        )
+                        .on_hover_text(
+                            table
+                                .values
+                                .chunks(8)
+                                .map(|row| row.iter().map(|v| format!("{v:3}")).collect::<Vec<_>>().join(" "))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        );
+                        ui.end_row();
+                    }
+                }
+                CompressionInfo::Png(png) => {
+                    ui.label("Bit depth");
+                    ui.label(png.bit_depth.to_string());
+                    ui.end_row();
+                    ui.label("Color type");
+                    ui.label(png.color_type_name());
+                    ui.end_row();
+                    ui.label("Interlaced");
+                    ui.label(if png.interlaced { "Adam7" } else { "No" });
+                    ui.end_row();
+                    ui.label("Chunks");
+                    if png.ancillary.is_empty() {
+                        ui.label("None");
+                    } else {
+                        ui.label(
+                            png.ancillary
+                                .iter()
+                                .map(|(name, count)| if *count > 1 { format!("{name} x{count}") } else { name.clone() })
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        )
+                        .on_hover_text("Ancillary chunks, in file order");
+                    }
+                    ui.end_row();
+                }
+            }
+        });
+    });
+}
+
+/// Statistics of the pixels in a rectangle dragged on the image
+fn selection_ui(ui: &mut Ui, state: &mut OculanteState) {
+    ui.collapsing("Selection", |ui| {
+        if ui
+            .checkbox(&mut state.selection.enabled, "Select region")
+            .on_hover_text(
+                "Drag on the image to select a rectangle. Pan with the middle mouse button.",
+            )
+            .changed()
+            && state.selection.enabled
+        {
+            state.profile.enabled = false;
+        }
+        let img = match &state.current_image {
+            // prefer edit result if present
+            Some(_) if state.edit_state.result_pixel_op.width() > 0 => {
+                &state.edit_state.result_pixel_op
+            }
+            Some(img) => img,
+            None => return,
+        };
+        let rect = match state.selection.rect(img.dimensions()) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let stats = match state.selection.stats(img, state.current_path.as_ref()) {
+            Some(stats) => stats.clone(),
+            None => return,
+        };
+        ui.label(format!(
+            "{},{} {}x{}, {} pixels",
+            rect.0, rect.1, rect.2, rect.3, stats.pixels
+        ));
+        egui::Grid::new("selection_stats")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["", "Mean", "Min", "Max", "Std dev"] {
+                    ui.label(header);
+                }
+                ui.end_row();
+                for (c, channel) in ["R", "G", "B", "A"].iter().enumerate() {
+                    ui.label(*channel);
+                    for value in [
+                        format!("{:.2}", stats.mean[c]),
+                        stats.min[c].to_string(),
+                        stats.max[c].to_string(),
+                        format!("{:.2}", stats.stddev[c]),
+                    ] {
+                        ui.label(RichText::new(value).monospace());
                     }
+                    ui.end_row();
                 }
+            });
+        ui.horizontal(|ui| {
+            if ui
+                .button(format!("{COPY} Copy"))
+                .on_hover_text("Copy the selected region at the resolution of the file")
+                .clicked()
+            {
+                let msg_sender = state.message_channel.0.clone();
+                crop_selection(state, move |crop| {
+                    let msg = match crop {
+                        Ok(img) => {
+                            clipboard_copy(&img);
+                            Message::info(&format!(
+                                "Copied {}x{} pixels",
+                                img.width(),
+                                img.height()
+                            ))
+                        }
+                        Err(e) => Message::err(&format!("Could not copy the selection: {e}")),
+                    };
+                    _ = msg_sender.send(msg);
+                });
+            }
+            #[cfg(feature = "file_open")]
+            if ui
+                .button(format!("{FLOPPY_DISK} Save as..."))
+                .on_hover_text("Save the selected region at the resolution of the file")
+                .clicked()
+                && state.check_writable()
+            {
+                let start_directory = state.persistent_settings.last_open_directory.clone();
+                let file_name = state
+                    .current_path
+                    .as_ref()
+                    .and_then(|p| p.file_stem())
+                    .map(|stem| format!("{}_crop.png", stem.to_string_lossy()))
+                    .unwrap_or_else(|| "crop.png".to_string());
+                let msg_sender = state.message_channel.0.clone();
+                let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                crop_selection(state, move |crop| {
+                    let _dialog = dialog;
+                    let saved = crop.and_then(|img| {
+                        let file_dialog_result = rfd::FileDialog::new()
+                            .set_directory(start_directory)
+                            .set_file_name(&file_name)
+                            .save_file();
+                        match file_dialog_result {
+                            Some(file_path) => {
+                                img.save(&file_path)?;
+                                Ok(Some(file_path))
+                            }
+                            None => Ok(None),
+                        }
+                    });
+                    match saved {
+                        Ok(Some(file_path)) => {
+                            _ = msg_sender.send(Message::info(&format!(
+                                "Selection saved to {}",
+                                file_path.display()
+                            )))
+                        }
+                        Ok(None) => (),
+                        Err(e) => {
+                            _ = msg_sender
+                                .send(Message::err(&format!("Could not save the selection: {e}")))
+                        }
+                    }
+                });
+            }
+            if ui.button("Clear selection").clicked() {
+                state.selection.corners = None;
+            }
+        });
+    });
+}
 
-                if col[2].button("⬇ Rotate 180°").clicked() {
-                    if lossless_tx(
-                        p,
-                        turbojpeg::Transform {
-                            op: turbojpeg::TransformOp::Rot180,
-                            ..turbojpeg::Transform::default()
-                        },
+/// A legend of the colormap the image is shown with
+pub fn colorbar_ui(ctx: &Context, state: &OculanteState) {
+    let view = match &state.data_view {
+        Some(view) if view.active && Some(&view.path) == state.current_path.as_ref() => view,
+        _ => return,
+    };
+    let (low, high) = match view.range() {
+        Some(range) => range,
+        None => return,
+    };
+    egui::Area::new("colorbar")
+        .anchor(Align2::RIGHT_BOTTOM, vec2(-20., -60.))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!("{high:.0}"));
+                let (rect, _) = ui.allocate_exact_size(vec2(16., 200.), Sense::hover());
+                let steps = 100;
+                let step = rect.height() / steps as f32;
+                for i in 0..steps {
+                    let [r, g, b] = view.colormap.color(1. - (i as f32 + 0.5) / steps as f32);
+                    ui.painter().rect_filled(
+                        Rect::from_min_size(
+                            rect.min + vec2(0., i as f32 * step),
+                            vec2(rect.width(), step + 0.5),
+                        ),
+                        0.,
+                        Color32::from_rgb(r, g, b),
+                    );
+                }
+                ui.label(format!("{low:.0}"));
+            });
+        });
+}
+
+/// Relighting options of the normal map view, with a sphere to drag the light around
+pub fn normal_map_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
+    let mut changed = false;
+    egui::Window::new("Normal map")
+        .resizable(false)
+        .default_width(160.)
+        .show(ctx, |ui| {
+            let settings = &mut state.persistent_settings;
+            changed |= ui
+                .checkbox(&mut settings.normal_relight, "Relight")
+                .on_hover_text(
+                    "Shade the normal map lit from a direction instead of showing its colors",
+                )
+                .changed();
+            changed |= ui
+                .checkbox(&mut settings.normal_green_down, "Green down (DirectX)")
+                .changed();
+            ui.add_enabled_ui(settings.normal_relight, |ui| {
+                let size = 120.;
+                let (rect, r) = ui.allocate_exact_size(Vec2::splat(size), Sense::click_and_drag());
+                if let Some(pos) = r
+                    .interact_pointer_pos()
+                    .filter(|_| r.dragged() || r.clicked())
+                {
+                    let mut v = (pos - rect.center()) / (size / 2.);
+                    if v.length() > 1. {
+                        v = v.normalized();
+                    }
+                    settings.normal_light = [v.x, -v.y, (1. - v.length_sq()).max(0.).sqrt()];
+                    changed = true;
+                }
+                // a sphere seen from the front, lit like the image
+                let cells = 24;
+                let cell = size / cells as f32;
+                let [lx, ly, lz] = settings.normal_light;
+                for row in 0..cells {
+                    for col in 0..cells {
+                        let x = (col as f32 + 0.5) / cells as f32 * 2. - 1.;
+                        let y = 1. - (row as f32 + 0.5) / cells as f32 * 2.;
+                        let z_sq = 1. - x * x - y * y;
+                        if z_sq < 0. {
+                            continue;
+                        }
+                        let shade = (x * lx + y * ly + z_sq.sqrt() * lz).max(0.);
+                        ui.painter().rect_filled(
+                            Rect::from_min_size(
+                                rect.min + vec2(col as f32, row as f32) * cell,
+                                Vec2::splat(cell),
+                            ),
+                            0.,
+                            Color32::from_gray((shade * 255.) as u8),
+                        );
+                    }
+                }
+                let marker = rect.center() + vec2(lx, -ly) * size / 2.;
+                ui.painter().circle_stroke(
+                    marker,
+                    4.,
+                    Stroke::new(2., ui.style().visuals.selection.bg_fill),
+                );
+            });
+        });
+    if changed {
+        // while dragging the light on the GPU, only the shader program changes
+        if !(state.channel_view_gpu && prepare_channel_shader(state, gfx)) {
+            update_display_texture(state, gfx);
+        }
+    }
+}
+
+/// A 3D preview of the current image as a height map, turned around by dragging
+pub fn heightmap_ui(ctx: &Context, state: &mut OculanteState, gfx: &mut Graphics) {
+    let view = &mut state.heightmap;
+    if view.needs_mesh || view.mesh_source != state.current_path {
+        if let Some(img) = &state.current_image {
+            let mesh =
+                HeightMesh::build(img, heightmap::DEFAULT_RESOLUTION, view.scale, view.invert);
+            let result = match &mut view.renderer {
+                Some(renderer) => renderer.set_mesh(gfx, &mesh),
+                None => {
+                    HeightRenderer::new(gfx, &mesh).map(|renderer| view.renderer = Some(renderer))
+                }
+            };
+            if let Err(e) = result {
+                error!("{e}");
+                view.enabled = false;
+                return;
+            }
+            view.mesh_source = state.current_path.clone();
+            view.needs_mesh = false;
+            view.needs_render = true;
+        }
+    }
+    let background = ctx.style().visuals.extreme_bg_color;
+    if let Some(renderer) = view.renderer.as_ref().filter(|_| view.needs_render) {
+        renderer.render(
+            gfx,
+            &view.orbit,
+            notan::prelude::Color::from_rgb(
+                background.r() as f32 / 255.,
+                background.g() as f32 / 255.,
+                background.b() as f32 / 255.,
+            ),
+        );
+        view.needs_render = false;
+    }
+
+    let mut enabled = view.enabled;
+    egui::Window::new("3D preview")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let texture = match &view.renderer {
+                Some(renderer) => renderer.texture,
+                None => {
+                    ui.label("No image");
+                    return;
+                }
+            };
+            let r = ui
+                .add(egui::Image::new(texture).sense(Sense::click_and_drag()))
+                .on_hover_text("Drag to turn, scroll to zoom, double click to reset");
+            if r.dragged() {
+                let delta = r.drag_delta();
+                view.orbit.drag((delta.x, delta.y));
+                view.needs_render = true;
+            }
+            if r.hovered() {
+                let scroll = ui.input(|i| i.scroll_delta.y);
+                if scroll != 0. {
+                    view.orbit.zoom(scroll);
+                    view.needs_render = true;
+                }
+            }
+            if r.double_clicked() {
+                view.orbit = Default::default();
+                view.needs_render = true;
+            }
+            ui.horizontal(|ui| {
+                ui.label("Height");
+                if ui.slider_styled(&mut view.scale, 0.0..=1.0).changed() {
+                    view.needs_mesh = true;
+                }
+                if ui
+                    .checkbox(&mut view.invert, "Dark is high")
+                    .on_hover_text("For depth maps where near is dark")
+                    .changed()
+                {
+                    view.needs_mesh = true;
+                }
+            });
+        });
+    view.enabled &= enabled;
+}
+
+/// Values of the pixels along the dragged line, one plot line per channel
+pub fn profile_ui(ctx: &Context, state: &mut OculanteState) {
+    let samples = match (&state.current_image, state.profile.line) {
+        (Some(img), Some((start, end))) => profile::sample_line(img, start, end),
+        _ => vec![],
+    };
+    let mut enabled = state.profile.enabled;
+    egui::Window::new("Line profile")
+        .open(&mut enabled)
+        .default_width(400.)
+        .show(ctx, |ui| {
+            let (start, end) = match state.profile.line {
+                Some(line) if !samples.is_empty() => line,
+                _ => {
+                    ui.label("Drag across the image to draw a line");
+                    return;
+                }
+            };
+            ui.label(format!(
+                "{:.0},{:.0} to {:.0},{:.0}, {:.1} px, {} samples",
+                start.x,
+                start.y,
+                end.x,
+                end.y,
+                (end - start).norm(),
+                samples.len()
+            ));
+            let channels = [
+                ("R", Color32::RED),
+                ("G", Color32::GREEN),
+                ("B", Color32::BLUE),
+                ("A", Color32::GRAY),
+            ];
+            Plot::new("line_profile")
+                .height(200.)
+                .include_y(0.)
+                .include_y(255.)
+                .legend(Legend::default())
+                .show(ui, |plot_ui| {
+                    for (i, (name, color)) in channels.iter().enumerate() {
+                        let points = samples
+                            .iter()
+                            .enumerate()
+                            .map(|(x, s)| [x as f64, s.value[i] as f64])
+                            .collect::<PlotPoints>();
+                        plot_ui.line(Line::new(points).color(*color).name(name));
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button(format!("{COPY} Copy CSV")).clicked() {
+                    let csv = profile::to_csv(&samples);
+                    match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(csv)) {
+                        Ok(_) => state.send_message("Profile copied"),
+                        Err(e) => state.send_message_err(&format!("Could not copy: {e}")),
+                    }
+                }
+                #[cfg(feature = "file_open")]
+                if ui.button(format!("{FLOPPY_DISK} Save CSV...")).clicked()
+                    && state.check_writable()
+                {
+                    let csv = profile::to_csv(&samples);
+                    let msg_sender = state.message_channel.0.clone();
+                    let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                    std::thread::spawn(move || {
+                        let _dialog = dialog;
+                        let file_dialog_result = rfd::FileDialog::new()
+                            .add_filter("CSV", &["csv"])
+                            .set_file_name("profile.csv")
+                            .save_file();
+                        if let Some(file_path) = file_dialog_result {
+                            let msg = match std::fs::write(&file_path, csv) {
+                                Ok(_) => Message::info(&format!(
+                                    "Profile saved to {}",
+                                    file_path.display()
+                                )),
+                                Err(e) => Message::err(&format!("Could not save profile: {e}")),
+                            };
+                            _ = msg_sender.send(msg);
+                        }
+                    });
+                }
+                if ui.button("Clear").clicked() {
+                    state.profile.line = None;
+                }
+            });
+        });
+    state.profile.enabled &= enabled;
+}
+
+/// Text recognized in the selection or the whole image
+#[cfg(feature = "ocr")]
+pub fn text_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.ocr.enabled;
+    egui::Window::new("Text")
+        .open(&mut enabled)
+        .default_width(400.)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if state.ocr.running {
+                    ui.spinner();
+                    ui.label("Recognizing text...");
+                } else {
+                    let source = if state.selection.corners.is_some() {
+                        "selection"
+                    } else {
+                        "image"
+                    };
+                    if ui
+                        .button(format!("{ARROWS_CLOCKWISE} Extract from {source}"))
+                        .clicked()
+                    {
+                        crate::utils::extract_text(state);
+                    }
+                }
+                if let Some(Ok(text)) = &state.ocr.text {
+                    if ui.button(format!("{COPY} Copy")).clicked() {
+                        match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+                            Ok(_) => state.send_message("Text copied"),
+                            Err(e) => state.send_message_err(&format!("Could not copy: {e}")),
+                        }
+                    }
+                }
+            });
+            match &state.ocr.text {
+                Some(Ok(text)) => {
+                    egui::ScrollArea::vertical()
+                        .max_height(400.)
+                        .show(ui, |ui| {
+                            // read only, but selectable
+                            ui.add(
+                                egui::TextEdit::multiline(&mut text.as_str())
+                                    .desired_width(f32::INFINITY)
+                                    .font(egui::TextStyle::Monospace),
+                            );
+                        });
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(Color32::RED, e);
+                }
+                None => (),
+            }
+        });
+    state.ocr.enabled &= enabled;
+}
+
+/// Estimated file sizes of the current image in other formats
+pub fn size_estimate_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.size_estimates.enabled;
+    egui::Window::new("Export size")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mb = |bytes: u64| format!("{:.2} MB", bytes as f64 / 1024. / 1024.);
+            let file_size = state
+                .current_path
+                .as_ref()
+                .and_then(|p| std::fs::metadata(p).ok())
+                .map(|m| m.len());
+            ui.horizontal(|ui| {
+                if let Some(img) = &state.current_image {
+                    ui.label(format!("{}x{}", img.width(), img.height()));
+                }
+                if let Some(size) = file_size {
+                    ui.label(format!("now {}", mb(size)));
+                }
+                if ui
+                    .button(format!("{ARROWS_CLOCKWISE} Refresh"))
+                    .on_hover_text("Estimate again, for example after editing")
+                    .clicked()
+                {
+                    state.size_estimates.refresh = true;
+                }
+            });
+            let estimates = match &state.size_estimates.estimates {
+                Some(estimates) => estimates,
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Encoding...");
+                    });
+                    return;
+                }
+            };
+            egui::Grid::new("size_estimates")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Quality");
+                    let codecs = Codec::iter().collect::<Vec<_>>();
+                    for codec in &codecs {
+                        ui.label(RichText::new(codec.to_string()).strong());
+                    }
+                    ui.end_row();
+                    for quality in size_estimate::QUALITIES {
+                        ui.label(quality.to_string());
+                        for codec in &codecs {
+                            match estimates
+                                .iter()
+                                .find(|e| e.codec == *codec && e.quality == quality)
+                            {
+                                Some(estimate) => {
+                                    let r = ui.label(RichText::new(mb(estimate.bytes)).monospace());
+                                    if let Some(size) = file_size.filter(|s| *s > 0) {
+                                        r.on_hover_text(format!(
+                                            "{:.0}% of the current file",
+                                            estimate.bytes as f64 / size as f64 * 100.
+                                        ));
+                                    }
+                                }
+                                None => {
+                                    ui.label("-");
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            ui.label(
+                RichText::new(format!(
+                    "Estimated from a copy scaled to {} px",
+                    size_estimate::PROXY_SIZE
+                ))
+                .weak(),
+            );
+        });
+    state.size_estimates.enabled &= enabled;
+}
+
+/// Split the current image into grayscale channel files, or combine files into one image
+pub fn channel_pack_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.channel_pack.enabled;
+    egui::Window::new("Channel packing")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(RichText::new("Split").strong());
+            let folder = state
+                .current_path
+                .as_ref()
+                .and_then(|p| p.parent())
+                .map(|p| p.to_path_buf());
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        folder.is_some(),
+                        egui::Button::new(format!("{FLOPPY_DISK} Save next to image")),
+                    )
+                    .on_hover_text("Save each channel as a grayscale PNG, like name_r.png")
+                    .clicked()
+                {
+                    split_channels(state, move || folder);
+                }
+                #[cfg(feature = "file_open")]
+                if ui
+                    .add_enabled(
+                        state.current_path.is_some(),
+                        egui::Button::new(format!("{FOLDER} Save to...")),
+                    )
+                    .clicked()
+                {
+                    let start_directory = state.persistent_settings.last_open_directory.clone();
+                    let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                    split_channels(state, move || {
+                        let _dialog = dialog;
+                        rfd::FileDialog::new()
+                            .set_directory(start_directory)
+                            .pick_folder()
+                    });
+                }
+            });
+            ui.separator();
+            ui.label(RichText::new("Combine").strong());
+            egui::Grid::new("channel_pack").show(ui, |ui| {
+                for (channel, name) in ["Red", "Green", "Blue", "Alpha"].iter().enumerate() {
+                    ui.label(*name);
+                    let source = &mut state.channel_pack.sources[channel];
+                    match source {
+                        Some(path) => {
+                            ui.label(path.file_name().unwrap_or_default().to_string_lossy())
+                                .on_hover_text(path.to_string_lossy());
+                        }
+                        None => {
+                            ui.label(
+                                RichText::new(if channel == 3 { "opaque" } else { "black" }).weak(),
+                            );
+                        }
+                    }
+                    if ui
+                        .add_enabled(state.current_path.is_some(), egui::Button::new("Current"))
+                        .on_hover_text("Use the current image, as grayscale")
+                        .clicked()
+                    {
+                        *source = state.current_path.clone();
+                    }
+                    #[cfg(feature = "file_open")]
+                    if ui.button(format!("{FOLDER} Browse...")).clicked() {
+                        let start_directory = state.persistent_settings.last_open_directory.clone();
+                        let sender = state.channel_pack_channel.0.clone();
+                        let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                        std::thread::spawn(move || {
+                            let _dialog = dialog;
+                            if let Some(path) = rfd::FileDialog::new()
+                                .set_directory(start_directory)
+                                .pick_file()
+                            {
+                                _ = sender.send((channel, path));
+                            }
+                        });
+                    }
+                    if ui
+                        .add_enabled(
+                            state.channel_pack.sources[channel].is_some(),
+                            egui::Button::new(X),
+                        )
+                        .clicked()
+                    {
+                        state.channel_pack.sources[channel] = None;
+                    }
+                    ui.end_row();
+                }
+            });
+            if ui
+                .add_enabled(
+                    state.channel_pack.sources.iter().any(|s| s.is_some()),
+                    egui::Button::new(format!("{STACK} Combine")),
+                )
+                .on_hover_text("Show an image with these channels, which can then be saved")
+                .clicked()
+            {
+                combine_channels(state);
+            }
+        });
+    state.channel_pack.enabled &= enabled;
+}
+
+/// Assemble the frames of a sequence or the images of a folder into an animation
+pub fn animation_encoder_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.animation_encoder.enabled;
+    let (frames, name) = animation_sources(state);
+    egui::Window::new("Create animation")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let source = if state.sequence.is_some() {
+                "frames of the sequence"
+            } else {
+                "images in this folder"
+            };
+            ui.label(format!("From the {} {source}", frames.len()));
+            ui.label(RichText::new("All frames need the same size").weak());
+            ui.separator();
+            let settings = &mut state.animation_encoder.settings;
+            egui::Grid::new("animation_encoder").show(ui, |ui| {
+                ui.label("Format");
+                egui::ComboBox::from_id_source("animation_format")
+                    .selected_text(settings.format.to_string())
+                    .show_ui(ui, |ui| {
+                        for format in AnimationFormat::iter() {
+                            ui.selectable_value(&mut settings.format, format, format.to_string());
+                        }
+                    });
+                ui.end_row();
+                ui.label("Frame rate");
+                ui.add(
+                    egui::DragValue::new(&mut settings.fps)
+                        .clamp_range(0.1..=100.)
+                        .speed(0.1)
+                        .suffix(" fps"),
+                );
+                ui.end_row();
+                ui.label("Loops");
+                ui.add(
+                    egui::DragValue::new(&mut settings.loops).custom_formatter(|n, _| {
+                        if n == 0. {
+                            "forever".to_string()
+                        } else {
+                            format!("{n}")
+                        }
+                    }),
+                );
+                ui.end_row();
+                if settings.format == AnimationFormat::Webp {
+                    ui.label("Quality");
+                    ui.add(egui::Slider::new(&mut settings.quality, 0..=100))
+                        .on_hover_text("100 is lossless");
+                    ui.end_row();
+                }
+            });
+            let format = settings.format;
+            let folder = frames
+                .first()
+                .and_then(|p| p.parent())
+                .map(|p| p.to_path_buf());
+            ui.horizontal(|ui| {
+                let ready = !frames.is_empty() && !state.animation_encoder.running;
+                if ui
+                    .add_enabled(
+                        ready && folder.is_some(),
+                        egui::Button::new(format!("{FILM_STRIP} Create next to frames")),
                     )
-                    .is_ok()
+                    .on_hover_text(format!("Save as {name}.{}", format.extension()))
+                    .clicked()
+                {
+                    let folder = folder.clone().unwrap_or_default();
+                    let name = name.clone();
+                    create_animation(state, frames.clone(), move || {
+                        Some(crate::animation_encoder::default_path(
+                            &folder, &name, format,
+                        ))
+                    });
+                }
+                #[cfg(feature = "file_open")]
+                if ui
+                    .add_enabled(ready, egui::Button::new(format!("{FOLDER} Create...")))
+                    .clicked()
+                {
+                    let start_directory = folder
+                        .clone()
+                        .unwrap_or_else(|| state.persistent_settings.last_open_directory.clone());
+                    let file_name =
+                        crate::animation_encoder::default_path(&start_directory, &name, format)
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                    let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                    create_animation(state, frames.clone(), move || {
+                        let _dialog = dialog;
+                        rfd::FileDialog::new()
+                            .set_directory(start_directory)
+                            .set_file_name(&file_name)
+                            .add_filter(&format.to_string(), &[format.extension()])
+                            .save_file()
+                    });
+                }
+                if state.animation_encoder.running {
+                    ui.spinner();
+                }
+            });
+        });
+    state.animation_encoder.enabled &= enabled;
+}
+
+/// Place images side by side or stacked, and show the result as a new image
+pub fn montage_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.montage.enabled;
+    egui::Window::new("Montage")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let count = state.montage.sources.len();
+            let mut swap = None;
+            let mut remove = None;
+            egui::Grid::new("montage_sources").show(ui, |ui| {
+                for (index, path) in state.montage.sources.iter().enumerate() {
+                    ui.label(path.file_name().unwrap_or_default().to_string_lossy())
+                        .on_hover_text(path.to_string_lossy());
+                    if ui
+                        .add_enabled(index > 0, egui::Button::new(ARROW_UP))
+                        .clicked()
+                    {
+                        swap = Some(index - 1);
+                    }
+                    if ui
+                        .add_enabled(index + 1 < count, egui::Button::new(ARROW_DOWN))
+                        .clicked()
                     {
-                        reload = true;
+                        swap = Some(index);
+                    }
+                    if ui.button(X).clicked() {
+                        remove = Some(index);
                     }
+                    ui.end_row();
+                }
+            });
+            if let Some(index) = swap {
+                state.montage.sources.swap(index, index + 1);
+            }
+            if let Some(index) = remove {
+                state.montage.sources.remove(index);
+            }
+            if count == 0 {
+                ui.label(RichText::new("No images added").weak());
+            }
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        state.current_path.is_some(),
+                        egui::Button::new(format!("{PLUS} Current")),
+                    )
+                    .clicked()
+                {
+                    state.montage.sources.extend(state.current_path.clone());
+                }
+                if ui
+                    .add_enabled(
+                        !state.compare_list.is_empty(),
+                        egui::Button::new(format!("{PLUS} Compare list")),
+                    )
+                    .on_hover_text("Add the images of the compare list")
+                    .clicked()
+                {
+                    let mut paths: Vec<PathBuf> = state.compare_list.keys().cloned().collect();
+                    paths.sort();
+                    state.montage.sources.extend(paths);
+                }
+                #[cfg(feature = "file_open")]
+                if ui.button(format!("{FOLDER} Browse...")).clicked() {
+                    let start_directory = state.persistent_settings.last_open_directory.clone();
+                    let sender = state.montage_channel.0.clone();
+                    let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                    std::thread::spawn(move || {
+                        let _dialog = dialog;
+                        if let Some(paths) = rfd::FileDialog::new()
+                            .set_directory(start_directory)
+                            .pick_files()
+                        {
+                            _ = sender.send(paths);
+                        }
+                    });
+                }
+                if ui
+                    .add_enabled(count > 0, egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    state.montage.sources.clear();
                 }
             });
+            ui.separator();
+            egui::Grid::new("montage_settings").show(ui, |ui| {
+                ui.label("Layout");
+                egui::ComboBox::from_id_source("montage_direction")
+                    .selected_text(state.montage.direction.to_string())
+                    .show_ui(ui, |ui| {
+                        for direction in Direction::iter() {
+                            ui.selectable_value(
+                                &mut state.montage.direction,
+                                direction,
+                                direction.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+                ui.label("Gap");
+                ui.add(
+                    egui::DragValue::new(&mut state.montage.gap)
+                        .clamp_range(0..=10000)
+                        .suffix(" px"),
+                );
+                ui.end_row();
+                ui.label("Background");
+                ui.color_edit_button_srgba_unmultiplied(&mut state.montage.background)
+                    .on_hover_text("Fills the gaps and the space next to smaller images");
+                ui.end_row();
+            });
+            if ui
+                .add_enabled(
+                    count > 1,
+                    egui::Button::new(format!("{SQUARE_SPLIT_HORIZONTAL} Stitch")),
+                )
+                .on_hover_text("Show the montage as a new image, which can then be saved")
+                .clicked()
+            {
+                stitch_images(state);
+            }
+        });
+    state.montage.enabled &= enabled;
+}
 
-            ui.columns(2,|col| {
-                if col[0].button("Flip H").clicked() {
-                    if lossless_tx(
-                        p,
-                        turbojpeg::Transform {
-                            op: turbojpeg::TransformOp::Hflip,
-                            ..turbojpeg::Transform::default()
-                        },
+/// Watch the clipboard for images, and go back to images that were copied before
+pub fn clipboard_watch_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.clipboard_watch.enabled;
+    egui::Window::new("Clipboard watcher")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let watching = state.clipboard_watch.is_watching();
+                if ui
+                    .selectable_label(watching, format!("{CLIPBOARD} Watch"))
+                    .on_hover_text("Show every image that is copied in any app")
+                    .clicked()
+                {
+                    toggle_clipboard_watch(state);
+                }
+                if watching {
+                    ui.spinner();
+                }
+                if ui
+                    .add_enabled(
+                        !state.clipboard_watch.history.is_empty(),
+                        egui::Button::new("Clear"),
                     )
-                    .is_ok()
-                    {
-                        reload = true;
-                    }
+                    .clicked()
+                {
+                    state.clipboard_watch.history.clear();
+                    state.clipboard_watch.selected = None;
                 }
+            });
+            ui.separator();
+            if state.clipboard_watch.history.is_empty() {
+                ui.label(RichText::new("No images copied yet").weak());
+            }
+            let mut show = None;
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    for (index, img) in state.clipboard_watch.history.iter().enumerate() {
+                        let selected = state.clipboard_watch.selected == Some(index);
+                        if ui
+                            .selectable_label(
+                                selected,
+                                format!("{}  {} x {}", index + 1, img.width(), img.height()),
+                            )
+                            .clicked()
+                        {
+                            show = Some(index);
+                        }
+                    }
+                });
+            if let Some(index) = show {
+                show_clipboard_entry(state, index);
+            }
+            ui.label(
+                RichText::new(format!(
+                    "The last {} images are kept until oculante is closed",
+                    crate::clipboard_watch::HISTORY_SIZE
+                ))
+                .weak(),
+            );
+        });
+    state.clipboard_watch.enabled &= enabled;
+}
 
-                if col[1].button("Flip V").clicked() {
-                    if lossless_tx(
-                        p,
-                        turbojpeg::Transform {
-                            op: turbojpeg::TransformOp::Vflip,
-                            ..turbojpeg::Transform::default()
-                        },
+/// Open a live MJPEG stream, and show how it is doing
+pub fn stream_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.stream.enabled;
+    egui::Window::new("Stream")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("URL");
+                ui.text_edit_singleline(&mut state.persistent_settings.stream_url)
+                    .on_hover_text("An MJPEG stream like http://camera/video.mjpg, or tcp://host:port for raw JPEG frames, e.g. from ffmpeg -f mjpeg, or pipe://name for float frames written to a named pipe");
+            });
+            ui.horizontal(|ui| {
+                if state.stream.playing.is_some() {
+                    if ui.button(format!("{STOP} Stop")).clicked() {
+                        state.stream.stop();
+                    }
+                } else if ui
+                    .add_enabled(
+                        !state.persistent_settings.stream_url.trim().is_empty(),
+                        egui::Button::new(format!("{PLAY} Play")),
                     )
-                    .is_ok()
-                    {
-                        reload = true;
+                    .clicked()
+                {
+                    let url = state.persistent_settings.stream_url.clone();
+                    play_stream(state, &url);
+                }
+                match &state.stream.status {
+                    Some(StreamStatus::Connecting) => {
+                        ui.spinner();
+                        ui.label("Connecting...");
                     }
+                    Some(StreamStatus::Playing(fps)) => {
+                        ui.label(format!("{fps:.0} fps"));
+                    }
+                    Some(StreamStatus::Reconnecting(reason)) => {
+                        ui.spinner();
+                        ui.label(RichText::new("Reconnecting").color(ui.visuals().warn_fg_color))
+                            .on_hover_text(reason);
+                    }
+                    None => (),
                 }
             });
+        });
+    state.stream.enabled &= enabled;
+}
 
-            ui.vertical_centered_justified(|ui| {
-
-
-
-                let crop_ops = state
-                    .edit_state
-                    .image_op_stack
-                    .iter()
-                    .filter(|op| matches!(op, ImageOperation::Crop(_)))
-                    .collect::<Vec<_>>();
-
-                let crop = crop_ops
-                    .first()
-                    .cloned()
-                    .cloned()
-                    .unwrap_or(ImageOperation::Crop([0, 0, 0, 0]));
-
-                if crop_ops.is_empty() {
-                    info!("A missing crop operator was added.");
-                    state
-                        .edit_state
-                        .image_op_stack
-                        .push(ImageOperation::Crop([0, 0, 0, 0]))
+pub fn remote_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.remote.enabled;
+    egui::Window::new("Remote folder")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("remote").num_columns(2).show(ui, |ui| {
+                ui.label("URL");
+                ui.text_edit_singleline(&mut state.persistent_settings.remote_url)
+                    .on_hover_text(if cfg!(feature = "cloud") {
+                        "A folder like sftp://user@nas/photos or smb://nas/share/photos, or objects like s3://bucket/prefix/ or gs://bucket/image.png"
+                    } else {
+                        "A folder like sftp://user@nas/photos or smb://nas/share/photos"
+                    });
+                ui.end_row();
+                let credentials = &mut state.persistent_settings.remote_credentials;
+                ui.label("User");
+                ui.text_edit_singleline(&mut credentials.user)
+                    .on_hover_text("Used if the URL names no user");
+                ui.end_row();
+                ui.label("Password");
+                ui.add(egui::TextEdit::singleline(&mut credentials.password).password(true))
+                    .on_hover_text("Stored in the settings as plain text. SFTP passwords need sshpass, prefer a key.");
+                ui.end_row();
+                ui.label("SSH key");
+                ui.horizontal(|ui| {
+                    let mut identity = credentials
+                        .identity
+                        .as_ref()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    if ui.text_edit_singleline(&mut identity).changed() {
+                        credentials.identity = Some(PathBuf::from(identity)).filter(|p| !p.as_os_str().is_empty());
+                    }
+                    #[cfg(feature = "file_open")]
+                    if ui.button(format!("{FOLDER_NOTCH_OPEN}")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_file() {
+                            credentials.identity = Some(path);
+                        }
+                    }
+                });
+                ui.end_row();
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !state.remote.listing && !state.persistent_settings.remote_url.trim().is_empty(),
+                        egui::Button::new(format!("{HARD_DRIVES} Open")),
+                    )
+                    .clicked()
+                {
+                    _ = state.persistent_settings.save();
+                    let url = state.persistent_settings.remote_url.clone();
+                    open_remote(state, &url);
                 }
+                if state.remote.listing {
+                    ui.spinner();
+                    ui.label("Listing...");
+                }
+            });
+            ui.label("Images are downloaded when they are opened and kept in the cache.");
+        });
+    state.remote.enabled &= enabled;
+}
 
-                ui.add_enabled_ui(crop != ImageOperation::Crop([0, 0, 0, 0]), |ui| {
-
+pub fn dataset_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.dataset.enabled;
+    let mut filter = false;
+    egui::Window::new("Dataset review")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                #[cfg(feature = "file_open")]
+                {
                     if ui
-                        .button("Crop")
-                        .on_hover_text("Crop according to values defined in the operator stack above")
-                        .on_disabled_hover_text("Please modify crop values above before cropping. You would be cropping nothing right now.")
+                        .button(format!("{FOLDER_NOTCH_OPEN} Annotations..."))
+                        .on_hover_text("COCO JSON, a CSV of path,label,bbox, JSON lines with file_name, label and bbox, or the classes.txt or data.yaml of YOLO labels")
                         .clicked()
                     {
-                        match crop {
-                            ImageOperation::Crop(amt) => {
-                                debug!("CROP {:?}", amt);
-
-                                let dim = state
-                                    .current_image
-                                    .as_ref()
-                                    .map(|i| i.dimensions())
-                                    .unwrap_or_default();
-
-                                let crop_range = cropped_range(&amt, &dim);
+                        let file = rfd::FileDialog::new()
+                            .add_filter("Annotations", &["json", "jsonl", "csv", "txt", "names", "yaml", "yml"])
+                            .pick_file();
+                        if let Some(file) = file {
+                            match Dataset::load(&file) {
+                                Ok(dataset) => state.dataset.dataset = Some(dataset),
+                                Err(e) => state.send_message_err(&format!("Can't read the annotations: {e}")),
+                            }
+                        }
+                    }
+                    if ui
+                        .button(format!("{FOLDER_NOTCH_OPEN} YOLO labels..."))
+                        .on_hover_text("A folder of YOLO label files")
+                        .clicked()
+                    {
+                        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                            match Dataset::load(&folder) {
+                                Ok(dataset) => state.dataset.dataset = Some(dataset),
+                                Err(e) => state.send_message_err(&format!("Can't read the annotations: {e}")),
+                            }
+                        }
+                    }
+                }
+            });
+            let dataset = match &mut state.dataset.dataset {
+                Some(dataset) => dataset,
+                None => {
+                    ui.label("Open the annotations of a dataset to review its images.");
+                    return;
+                }
+            };
+            ui.label(RichText::new(dataset.path.to_string_lossy()).weak());
+            ui.label(format!(
+                "{} accepted, {} rejected",
+                dataset.count(Verdict::Accepted),
+                dataset.count(Verdict::Rejected)
+            ))
+            .on_hover_text(format!("Saved to {}", dataset.results_file().display()));
+            ui.checkbox(&mut state.dataset.show, "Show annotations");
+            ui.checkbox(&mut state.dataset.advance, "Next image after a verdict");
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+                for class in dataset.classes.clone() {
+                    let [r, g, b] = dataset.color(&class);
+                    let mut visible = !dataset.hidden.contains(&class);
+                    ui.horizontal(|ui| {
+                        let (rect, _) = ui.allocate_exact_size(vec2(10., 10.), Sense::hover());
+                        ui.painter().rect_filled(rect, 2., Color32::from_rgb(r, g, b));
+                        if ui.checkbox(&mut visible, class.as_str()).changed() {
+                            if visible {
+                                dataset.hidden.remove(&class);
+                            } else {
+                                dataset.hidden.insert(class.clone());
+                            }
+                        }
+                    });
+                }
+            });
+            if ui
+                .button("Only images with shown classes")
+                .on_hover_text("Hide the images of the folder without annotations of the checked classes")
+                .clicked()
+            {
+                filter = true;
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                let accept = state.persistent_settings.shortcuts.get(&InputEvent::ReviewAccept).map(keypresses_as_string).unwrap_or_default();
+                let reject = state.persistent_settings.shortcuts.get(&InputEvent::ReviewReject).map(keypresses_as_string).unwrap_or_default();
+                if ui.button(format!("{CHECK} Accept")).on_hover_text(accept).clicked() {
+                    judge_image(state, Verdict::Accepted);
+                }
+                if ui.button(format!("{X} Reject")).on_hover_text(reject).clicked() {
+                    judge_image(state, Verdict::Rejected);
+                }
+            });
+        });
+    if filter {
+        filter_dataset_images(state);
+    }
+    state.dataset.enabled &= enabled;
+}
 
-                                match lossless_tx(
-                                    p,
-                                    turbojpeg::Transform {
-                                        op: turbojpeg::TransformOp::None,
-                                        crop: Some(turbojpeg::TransformCrop {
-                                            x: crop_range[0] as usize,
-                                            y: crop_range[1] as usize,
-                                            width: Some(crop_range[2] as usize),
-                                            height: Some(crop_range[3] as usize),
-                                        }),
-                                        ..turbojpeg::Transform::default()
-                                    },
-                                ) {
-                                    Ok(_) => reload = true,
-                                    Err(e) => log::warn!("{e}"),
-                                };
+pub fn sorting_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.sorting.enabled;
+    egui::Window::new("Sort into folders")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let toggle = state
+                .persistent_settings
+                .shortcuts
+                .get(&InputEvent::ToggleSorting)
+                .map(keypresses_as_string)
+                .unwrap_or_default();
+            ui.checkbox(&mut state.sorting.active, "Sort with the number keys")
+                .on_hover_text(format!("Instead of zooming. Toggle with {toggle}"));
+            let targets = &mut state.persistent_settings.sort_targets;
+            targets.resize(9, Default::default());
+            egui::Grid::new("sort targets")
+                .num_columns(4)
+                .show(ui, |ui| {
+                    for (i, target) in targets.iter_mut().enumerate() {
+                        ui.label(format!("{}", i + 1));
+                        let mut folder = target.folder.to_string_lossy().to_string();
+                        if ui
+                            .add(egui::TextEdit::singleline(&mut folder).hint_text("Folder"))
+                            .changed()
+                        {
+                            target.folder = PathBuf::from(folder);
+                        }
+                        #[cfg(feature = "file_open")]
+                        if ui.button(FOLDER_NOTCH_OPEN).clicked() {
+                            if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                                target.folder = folder;
                             }
-                            _ => (),
-                        };
+                        }
+                        ui.checkbox(&mut target.copy, "Copy")
+                            .on_hover_text("Keep the image in its folder");
+                        ui.end_row();
                     }
                 });
-                });
-
+        });
+    state.sorting.enabled &= enabled;
+}
 
-            if reload {
-                state.is_loaded = false;
-                state.player.cache.clear();
-                state.player.load(&p, state.message_channel.0.clone());
+pub fn favourites_ui(ctx: &Context, state: &mut OculanteState) {
+    let mut enabled = state.favourites.enabled;
+    egui::Window::new("Favourites")
+        .open(&mut enabled)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if let Some(theirs) = &state.favourites.conflict {
+                let mine = &state.persistent_settings.favourite_images;
+                ui.label(RichText::new(format!("{WARNING} Another app changed the favourites")).strong())
+                    .on_hover_text("Like a sync client, or oculante running elsewhere");
+                ui.label(format!(
+                    "{} only there, {} only here",
+                    theirs.difference(mine).count(),
+                    mine.difference(theirs).count()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Merge").on_hover_text("Keep the favourites of both").clicked() {
+                        resolve_favourites(state, Resolution::Merge);
+                    }
+                    if ui.button("Keep mine").clicked() {
+                        resolve_favourites(state, Resolution::Mine);
+                    }
+                    if ui.button("Use theirs").clicked() {
+                        resolve_favourites(state, Resolution::Theirs);
+                    }
+                });
+                ui.separator();
+            }
+            let favourites = folder_favourites(state);
+            let toggle = state
+                .persistent_settings
+                .shortcuts
+                .get(&InputEvent::ToggleFavourite)
+                .map(keypresses_as_string)
+                .unwrap_or_default();
+            ui.label(format!("{} favourites in this folder", favourites.len()))
+                .on_hover_text(format!("Add or remove the image with {toggle}"));
+            ui.separator();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("favourites_format")
+                    .selected_text(state.favourites.format.name())
+                    .show_ui(ui, |ui| {
+                        for format in ListFormat::ALL {
+                            ui.selectable_value(&mut state.favourites.format, format, format.name());
+                        }
+                    });
+                #[cfg(feature = "file_open")]
+                if ui
+                    .add_enabled(!favourites.is_empty(), egui::Button::new(format!("{FLOPPY_DISK} Save list...")))
+                    .clicked()
+                {
+                    let extension = state.favourites.format.extension();
+                    let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                    export_favourites(state, move || {
+                        let _dialog = dialog;
+                        rfd::FileDialog::new()
+                            .add_filter(extension, &[extension])
+                            .set_file_name(format!("favourites.{extension}"))
+                            .save_file()
+                    });
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut state.favourites.mode, CollectMode::Copy, "Copy");
+                ui.radio_value(&mut state.favourites.mode, CollectMode::HardLink, "Hard link")
+                    .on_hover_text("Takes no space, but only works on the same drive");
+                #[cfg(feature = "file_open")]
+                if ui
+                    .add_enabled(
+                        !favourites.is_empty() && state.favourites.progress.is_none(),
+                        egui::Button::new(format!("{FOLDER_NOTCH_OPEN} Into folder...")),
+                    )
+                    .clicked()
+                {
+                    collect_favourites(state, || rfd::FileDialog::new().pick_folder());
+                }
+            });
+            if let Some((done, total)) = state.favourites.progress {
+                ui.add(egui::ProgressBar::new(done as f32 / total as f32).text(format!("{done} / {total}")));
+            }
+            ui.separator();
+            #[cfg(feature = "file_open")]
+            if ui
+                .button(format!("{DOWNLOAD_SIMPLE} Import..."))
+                .on_hover_text(format!(
+                    "Add the favourites of a list, or of the settings of another installation. Backups are kept in {}",
+                    crate::favourites::backup_dir().unwrap_or_default().display()
+                ))
+                .clicked()
+            {
+                let dialog = state.redraw.poll_while_alive(Reason::Dialog);
+                import_favourites(state, move || {
+                    let _dialog = dialog;
+                    rfd::FileDialog::new().pick_file()
+                });
+            }
+            if !state.favourites.missing.is_empty() {
+                ui.collapsing(format!("{} missing files", state.favourites.missing.len()), |ui| {
+                    egui::ScrollArea::vertical().max_height(150.).show(ui, |ui| {
+                        for path in &state.favourites.missing {
+                            ui.label(RichText::new(path.to_string_lossy()).weak());
+                        }
+                    });
+                    if ui.button("Clear").clicked() {
+                        state.favourites.missing.clear();
+                    }
+                });
             }
         });
-    }
+    state.favourites.enabled &= enabled;
 }
 
-pub fn scrubber_ui(state: &mut OculanteState, ui: &mut Ui) {
-    let len = state.scrubber.len().saturating_sub(1);
-
-    if ui
-        .slider_timeline(&mut state.scrubber.index, 0..=len)
-        .changed()
-    {
-        let p = state.scrubber.set(state.scrubber.index);
-        state.current_path = Some(p.clone());
-        state.player.load(&p, state.message_channel.0.clone());
+/// Offer the crash log of a previous run
+pub fn crash_ui(ctx: &Context, state: &mut OculanteState) {
+    let path = match &state.crash_log {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    let mut open = true;
+    let mut dismissed = false;
+    egui::Window::new("Oculante crashed")
+        .open(&mut open)
+        .collapsible(false)
+        .anchor(Align2::CENTER_CENTER, [0., 0.])
+        .show(ctx, |ui| {
+            ui.label("Oculante crashed last time. A crash log was written to");
+            ui.label(RichText::new(path.to_string_lossy()).monospace());
+            ui.label("Please consider attaching it to a bug report.");
+            ui.horizontal(|ui| {
+                if ui.button("Show in file manager").clicked() {
+                    if let Err(e) = crate::file_manager::reveal(&path) {
+                        state.send_message_err(&format!("Could not show the crash log: {e}"));
+                    }
+                }
+                if ui.button("Copy").clicked() {
+                    let copied = std::fs::read_to_string(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|log| Ok(Clipboard::new()?.set_text(log)?));
+                    match copied {
+                        Ok(_) => state.send_message("Crash log copied"),
+                        Err(e) => state.send_message_err(&format!("Could not copy: {e}")),
+                    }
+                }
+                if ui.button("Report issue").clicked() {
+                    _ = webbrowser::open("https://github.com/woelper/oculante/issues/new");
+                }
+                if ui.button("Dismiss").clicked() {
+                    dismissed = true;
+                }
+            });
+        });
+    if !open || dismissed {
+        if let Err(e) = crate::crash::dismiss() {
+            warn!("Could not dismiss the crash log: {e}");
+        }
+        state.crash_log = None;
     }
 }
 
@@ -1807,32 +5488,50 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
                             ui,
                         )
                         .clicked()
-                        {
-                            changed_channels = true;
-                        }
-                    }
-                });
-        });
-
-        // TODO: remove redundancy
-        if changed_channels {
-            if let Some(img) = &state.current_image {
-                match &state.persistent_settings.current_channel {
-                    ColorChannel::Rgb => {
-                        state.current_texture = unpremult(img)
-                            .to_texture(gfx, state.persistent_settings.linear_mag_filter)
-                    }
-                    ColorChannel::Rgba => {
-                        state.current_texture =
-                            img.to_texture(gfx, state.persistent_settings.linear_mag_filter)
-                    }
-                    _ => {
-                        state.current_texture =
-                            solo_channel(img, state.persistent_settings.current_channel as usize)
-                                .to_texture(gfx, state.persistent_settings.linear_mag_filter)
+                        {
+                            changed_channels = true;
+                        }
                     }
+                });
+
+            if state.persistent_settings.current_channel == ColorChannel::Expression {
+                let error = channel_expression(state).and_then(|expr| expr.err());
+                let r = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut state.persistent_settings.channel_expression)
+                            .desired_width(120.)
+                            .text_color_opt(error.as_ref().map(|_| Color32::LIGHT_RED)),
+                    )
+                    .on_hover_text(match &error {
+                        Some(e) => e.to_string(),
+                        None => "r, g, b, a, luma, hue, saturation and chroma from 0 to 1, with + - * / ^, abs, sqrt, min, max and pow".into(),
+                    });
+                if r.changed() {
+                    changed_channels = true;
                 }
             }
+
+            if let Some(path) = state.current_path.clone() {
+                let linear = color_encoding(state) == ColorEncoding::Linear;
+                if ui
+                    .selectable_label(linear, RichText::new("Linear").size(combobox_text_size))
+                    .on_hover_text("Interpret the values of this image as linear, like roughness maps or masks, instead of sRGB")
+                    .clicked()
+                {
+                    let encoding = if linear { ColorEncoding::Srgb } else { ColorEncoding::Linear };
+                    state.color_encodings.insert(path, encoding);
+                    changed_channels = true;
+                }
+            }
+        });
+
+        if key_pressed(app, state, SoftProof) {
+            toggle_soft_proof(state);
+            changed_channels = true;
+        }
+
+        if changed_channels {
+            update_display_texture(state, gfx);
         }
 
         if state.current_path.is_some() {
@@ -1892,6 +5591,20 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
             {
                 state.persistent_settings.edit_enabled = !state.persistent_settings.edit_enabled;
             }
+
+            if tooltip(
+                ui.selectable_label(
+                    state.persistent_settings.loupe_enabled,
+                    RichText::new(format!("{}", MAGNIFYING_GLASS_PLUS)).size(ICON_SIZE * 0.8),
+                ),
+                "Show the loupe",
+                &lookup(&state.persistent_settings.shortcuts, &ToggleLoupe),
+                ui,
+            )
+            .clicked()
+            {
+                state.persistent_settings.loupe_enabled = !state.persistent_settings.loupe_enabled;
+            }
         }
 
         // FIXME This crashes/freezes!
@@ -1937,12 +5650,52 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
                 ui,
             )
             .clicked()
+                && state.check_writable()
             {
                 _ = trash::delete(p);
                 state.send_message("Deleted image");
             }
         }
 
+        #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+        if let Some(path) = state
+            .current_path
+            .clone()
+            .filter(|p| p.is_file() && crate::file_manager::can_drag_out())
+        {
+            let drag = ui.add(
+                egui::Button::new(RichText::new(HAND_GRABBING).size(ICON_SIZE))
+                    .frame(false)
+                    .sense(Sense::click_and_drag()),
+            );
+            let hint = if cfg!(target_os = "linux") {
+                "Drag the file into other apps with dragon"
+            } else {
+                "Drag the file into other apps"
+            };
+            if tooltip(drag, hint, "", ui).drag_started() {
+                if let Err(e) =
+                    crate::file_manager::drag_out(app, &path, state.current_image.as_ref())
+                {
+                    state.send_message_err(&format!("Could not drag file: {e}"));
+                }
+            }
+        }
+
+        #[cfg(feature = "update")]
+        if let Some(update) = &state.available_update {
+            if tooltip(
+                unframed_button_colored(ARROW_CIRCLE_UP, true, ui),
+                &format!("Oculante {} is available", update.version),
+                "",
+                ui,
+            )
+            .clicked()
+            {
+                state.show_update = !state.show_update;
+            }
+        }
+
         ui.add_space(ui.available_width() - 32.);
 
         ui.scope(|ui| {
@@ -1956,12 +5709,70 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
 
             ui.style_mut().override_text_style = Some(egui::TextStyle::Heading);
 
-            ui.menu_button(RichText::new(LIST).size(ICON_SIZE), |ui| {
-                if ui.button("Reset view").clicked() {
+            let menu = ui.menu_button(RichText::new(LIST).size(ICON_SIZE), |ui| {
+                if shortcut_button(ui, "Reset view", state, ResetView).clicked() {
                     state.reset_image = true;
                     ui.close_menu();
                 }
-                if ui.button("View 1:1").clicked() {
+                let mut locked = state.lock_view.is_some();
+                if ui
+                    .checkbox(&mut locked, "Lock view")
+                    .on_hover_text("Keep zoom and position when switching to images of the same size, to compare them pixel by pixel.")
+                    .clicked()
+                {
+                    toggle_lock_view(state);
+                    ui.close_menu();
+                }
+                if let Some(folder) = state.current_path.as_ref().and_then(|p| p.parent()).map(|p| p.to_path_buf()) {
+                    let folders = &mut state.persistent_settings.blurred_folders;
+                    let mut blurred = folders.contains(&folder);
+                    let reveal = lookup(&state.persistent_settings.shortcuts, &RevealImage);
+                    if ui
+                        .checkbox(&mut blurred, "Blur this folder")
+                        .on_hover_text(format!("Show the images of this folder and its subfolders blurred. Hold {reveal} to see them."))
+                        .clicked()
+                    {
+                        if blurred {
+                            folders.insert(folder);
+                        } else {
+                            folders.remove(&folder);
+                        }
+                        ui.close_menu();
+                    }
+                }
+                if let Some(path) = state.current_path.clone() {
+                    let own = state.persistent_settings.image_sampling.get(&path).copied();
+                    let mut sampling_changed = false;
+                    ui.menu_button(format!("Sampling: {}", state.sampling().label()), |ui| {
+                        if ui.selectable_label(own.is_none(), format!("Default ({})", state.persistent_settings.sampling.label())).clicked() {
+                            state.persistent_settings.image_sampling.remove(&path);
+                            sampling_changed = true;
+                        }
+                        for sampling in Sampling::iter() {
+                            if ui.selectable_label(own == Some(sampling), sampling.label()).clicked() {
+                                state.persistent_settings.image_sampling.insert(path.clone(), sampling);
+                                sampling_changed = true;
+                            }
+                        }
+                    });
+                    if sampling_changed {
+                        refresh_sampling(state, gfx);
+                        ui.close_menu();
+                    }
+                }
+                let mut reading = state.scroll_reader.active;
+                if ui
+                    .checkbox(&mut reading, "Scroll reading")
+                    .on_hover_text(format!(
+                        "Show the images of the folder below each other and scroll through them, for manga or long screenshots. Ctrl and the mouse wheel change the width of the pages. Toggle with {}.",
+                        lookup(&state.persistent_settings.shortcuts, &ScrollReading)
+                    ))
+                    .clicked()
+                {
+                    toggle_scroll_reader(state);
+                    ui.close_menu();
+                }
+                if shortcut_button(ui, "View 1:1", state, ZoomActualSize).clicked() {
                     set_zoom(
                         1.0,
                         Some(nalgebra::Vector2::new(
@@ -1973,10 +5784,59 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
                     ui.close_menu();
                 }
 
+                if shortcut_button(ui, "View at print size", state, ZoomPrintSize)
+                    .on_hover_text("Show the image at its physical size, using the resolution in the file")
+                    .clicked()
+                {
+                    zoom_to_print_size(app, state);
+                    ui.close_menu();
+                }
+
+                ui.menu_button("Guides", |ui| {
+                    for guide in Guide::iter() {
+                        let mut shown = state.persistent_settings.guides.contains(&guide);
+                        if ui.checkbox(&mut shown, guide.label()).changed() {
+                            if shown {
+                                state.persistent_settings.guides.insert(guide);
+                            } else {
+                                state.persistent_settings.guides.remove(&guide);
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.menu_button("Matte", |ui| {
+                        let matte = &mut state.persistent_settings.matte_ratio;
+                        ui.radio_value(matte, None, "None");
+                        for (name, ratio) in MATTE_RATIOS.iter() {
+                            ui.radio_value(matte, Some(*ratio), *name);
+                        }
+                        // any other ratio can be dialed in, starting from a preset
+                        if let Some(ratio) = matte {
+                            ui.add(
+                                egui::DragValue::new(ratio)
+                                    .clamp_range(0.1..=10.0)
+                                    .speed(0.01)
+                                    .prefix("Ratio: ")
+                                    .suffix(":1"),
+                            );
+                        }
+                        ui.add(
+                            egui::Slider::new(
+                                &mut state.persistent_settings.matte_opacity,
+                                0.0..=1.0,
+                            )
+                            .text("Opacity"),
+                        );
+                    })
+                    .response
+                    .on_hover_text(
+                        "Cover the image outside of a frame with another aspect ratio, to preview crops",
+                    );
+                });
+
                 let copy_pressed = key_pressed(app, state, Copy);
                 if let Some(img) = &state.current_image {
-                    if ui
-                        .button("🗐 Copy")
+                    if shortcut_button(ui, "🗐 Copy", state, Copy)
                         .on_hover_text("Copy image to clipboard")
                         .clicked()
                         || copy_pressed
@@ -1986,8 +5846,34 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
                     }
                 }
 
-                if ui
-                    .button("📋 Paste")
+                if let Some(path) = state.current_path.clone().filter(|p| p.is_file()) {
+                    if ui
+                        .button("Copy file")
+                        .on_hover_text("Copy the file itself, to paste it into a file manager or chat")
+                        .clicked()
+                    {
+                        let path = path.clone();
+                        let message_sender = state.message_channel.0.clone();
+                        std::thread::spawn(move || {
+                            _ = message_sender.send(match crate::file_manager::copy_file(&path) {
+                                Ok(_) => Message::info("File copied"),
+                                Err(e) => Message::err(&format!("Could not copy file: {e}")),
+                            });
+                        });
+                        ui.close_menu();
+                    }
+                    if ui.button("Reveal in file manager").clicked() {
+                        let message_sender = state.message_channel.0.clone();
+                        std::thread::spawn(move || {
+                            if let Err(e) = crate::file_manager::reveal(&path) {
+                                _ = message_sender.send(Message::err(&format!("Could not show file: {e}")));
+                            }
+                        });
+                        ui.close_menu();
+                    }
+                }
+
+                if shortcut_button(ui, "📋 Paste", state, Paste)
                     .on_hover_text("Paste image from clipboard")
                     .clicked()
                     || key_pressed(app, state, Paste)
@@ -2016,11 +5902,199 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
                     ui.close_menu();
                 }
 
+                if shortcut_button(ui, "Capture viewport", state, CaptureViewport)
+                    .on_hover_text(format!(
+                        "Save what is on screen, including overlays, to {}",
+                        state.persistent_settings.capture_folder.display()
+                    ))
+                    .clicked()
+                {
+                    // wait for the menu to close
+                    state.capture_viewport = Some(2);
+                    ui.close_menu();
+                }
+
+                if shortcut_button(ui, "Screenshot", state, Screenshot)
+                    .on_hover_text("Hide the window, capture all screens and open the capture")
+                    .clicked()
+                {
+                    take_screenshot(app, state, CaptureArea::Screen);
+                    ui.close_menu();
+                }
+
+                if crate::screenshot::supports_region()
+                    && shortcut_button(ui, "Screenshot of region", state, ScreenshotRegion)
+                        .on_hover_text("Hide the window, drag out a region to capture and open it")
+                        .clicked()
+                {
+                    take_screenshot(app, state, CaptureArea::Region);
+                    ui.close_menu();
+                }
+
+                if state.persistent_settings.background_mode
+                    && ui
+                        .button("Hide")
+                        .on_hover_text("Keep running in the background, the tray icon shows the window again")
+                        .clicked()
+                {
+                    hide_to_background(app, state);
+                    ui.close_menu();
+                }
+
                 if ui.button("⛭ Preferences").clicked() {
                     state.settings_enabled = !state.settings_enabled;
                     ui.close_menu();
                 }
 
+                if ui
+                    .button("Log")
+                    .on_hover_text("Show recent log messages")
+                    .clicked()
+                {
+                    state.log_enabled = !state.log_enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{MAGNIFYING_GLASS} Search"))
+                    .on_hover_text("Find images below this folder by name, EXIF data or metadata")
+                    .clicked()
+                {
+                    toggle_search(state);
+                    ui.close_menu();
+                }
+
+                #[cfg(feature = "ocr")]
+                if ui
+                    .button(format!("{TEXT_AA} Extract text"))
+                    .on_hover_text("Recognize the text in the selection or the whole image and copy it")
+                    .clicked()
+                {
+                    crate::utils::extract_text(state);
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{CHART_LINE} Line profile"))
+                    .on_hover_text("Drag a line across the image to plot the pixel values along it")
+                    .clicked()
+                {
+                    state.profile.enabled = !state.profile.enabled;
+                    state.selection.enabled &= !state.profile.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{CUBE} 3D preview"))
+                    .on_hover_text("Show the image as a height or depth map in 3D")
+                    .clicked()
+                {
+                    state.heightmap.enabled = !state.heightmap.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{SCALES} Export size"))
+                    .on_hover_text("Estimate the file size of this image as JPEG, WebP or AVIF")
+                    .clicked()
+                {
+                    state.size_estimates.enabled = !state.size_estimates.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{STACK} Channel packing"))
+                    .on_hover_text("Split the channels into grayscale files, or combine files into channels")
+                    .clicked()
+                {
+                    state.channel_pack.enabled = !state.channel_pack.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .selectable_label(state.hot_folder_watch.is_some(), format!("{FOLDER_NOTCH_OPEN} Watch hot folder"))
+                    .on_hover_text(match &state.persistent_settings.hot_folder {
+                        Some(folder) => format!("Show new images as they arrive in {}", folder.display()),
+                        None => "Show new images as they arrive in the hot folder from the settings".into(),
+                    })
+                    .clicked()
+                {
+                    toggle_hot_folder(state);
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{VIDEO_CAMERA} Stream"))
+                    .on_hover_text("Show a live MJPEG stream, like from an IP camera")
+                    .clicked()
+                {
+                    state.stream.enabled = !state.stream.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{LIST_CHECKS} Dataset review"))
+                    .on_hover_text("Show the annotations of an image dataset, and accept or reject its images")
+                    .clicked()
+                {
+                    state.dataset.enabled = !state.dataset.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{FOLDERS} Sort into folders"))
+                    .on_hover_text("Move or copy images to a folder per number key")
+                    .clicked()
+                {
+                    state.sorting.enabled = !state.sorting.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{HARD_DRIVES} Remote folder"))
+                    .on_hover_text("Browse images on an SFTP server or SMB share, like a NAS, without mounting it")
+                    .clicked()
+                {
+                    state.remote.enabled = !state.remote.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{CLIPBOARD} Clipboard watcher"))
+                    .on_hover_text("Show images as they are copied in any app, and keep a history of them")
+                    .clicked()
+                {
+                    state.clipboard_watch.enabled = !state.clipboard_watch.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{SQUARE_SPLIT_HORIZONTAL} Montage"))
+                    .on_hover_text("Stitch images side by side or stacked, like before and after composites")
+                    .clicked()
+                {
+                    state.montage.enabled = !state.montage.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{FILM_STRIP} Create animation"))
+                    .on_hover_text("Assemble the frames of a sequence or the images of this folder into a GIF, WebP or APNG")
+                    .clicked()
+                {
+                    state.animation_encoder.enabled = !state.animation_encoder.enabled;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button(format!("{CHART_BAR} Folder statistics"))
+                    .on_hover_text("Formats, sizes, dimensions, dates and cameras of the images in this folder")
+                    .clicked()
+                {
+                    toggle_folder_stats(state);
+                    ui.close_menu();
+                }
+
                 ui.menu_button("Recent", |ui| {
                     for r in &state.persistent_settings.recent_images.clone() {
                         if let Some(filename) = r.file_name() {
@@ -2032,19 +6106,97 @@ pub fn main_menu(ui: &mut Ui, state: &mut OculanteState, app: &mut App, gfx: &mu
                     }
                 });
 
-                // TODO: expose favourites with a tool button
-                // ui.menu_button("Favourites", |ui| {
-                //     for r in &state.persistent_settings.favourite_images.clone() {
-                //         if let Some(filename) = r.file_name() {
-                //             if ui.button(filename.to_string_lossy()).clicked() {
-                //ui.close_menu();
+                ui.add_enabled_ui(
+                    state.current_path.is_some()
+                        && !state.persistent_settings.external_commands.is_empty(),
+                    |ui| {
+                        ui.menu_button("Send to", |ui| {
+                            for command in state.persistent_settings.external_commands.clone() {
+                                if ui
+                                    .button(&command.name)
+                                    .on_hover_text(&command.command)
+                                    .clicked()
+                                {
+                                    run_external_command(&command, state);
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    },
+                );
 
-                //             }
-                //         }
-                //     }
+                ui.menu_button("Scripts", |ui| {
+                    for script in state.scripts.clone() {
+                        let mut button = ui.button(&script.name);
+                        if !script.keys.is_empty() {
+                            button = button.on_hover_text(keypresses_as_string(&script.keys));
+                        }
+                        if button.clicked() {
+                            run_script(&script, state);
+                            ui.close_menu();
+                        }
+                    }
+                    if state.scripts.is_empty() {
+                        ui.label("No scripts found");
+                    }
+                    ui.separator();
+                    if ui
+                        .button(format!("{ARROWS_CLOCKWISE} Reload scripts"))
+                        .on_hover_text(format!(
+                            "Scripts are read from {}",
+                            Script::dir().unwrap_or_default().display()
+                        ))
+                        .clicked()
+                    {
+                        match Script::load_all() {
+                            Ok(scripts) => state.scripts = scripts,
+                            Err(e) => state.send_message_err(&format!("Could not load scripts: {e}")),
+                        }
+                        ui.close_menu();
+                    }
+                });
 
-                // });
+                ui.menu_button("Favourites", |ui| {
+                    let favourite = state
+                        .current_path
+                        .as_ref()
+                        .map(|p| state.persistent_settings.favourite_images.contains(p))
+                        .unwrap_or_default();
+                    let label = if favourite { "Remove from favourites" } else { "Add to favourites" };
+                    if ui
+                        .add_enabled(state.current_path.is_some(), egui::Button::new(format!("{STAR} {label}")))
+                        .clicked()
+                    {
+                        toggle_favourite(state);
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    for path in folder_favourites(state) {
+                        if let Some(filename) = path.file_name() {
+                            if ui.button(filename.to_string_lossy()).clicked() {
+                                load_image_from_path(&path, state);
+                                state.scrubber.select(&path);
+                                ui.close_menu();
+                            }
+                        }
+                    }
+                    ui.separator();
+                    if ui.button(format!("{EXPORT} Export...")).clicked() {
+                        state.favourites.enabled = true;
+                        ui.close_menu();
+                    }
+                });
             });
+            let menu = tooltip(
+                menu.response,
+                "Menu",
+                &lookup(&state.persistent_settings.shortcuts, &FocusMenu),
+                ui,
+            );
+            if state.focus_menu {
+                menu.request_focus();
+                state.focus_menu = false;
+            }
 
             // });
         });
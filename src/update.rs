@@ -1,36 +1,174 @@
+//! Updates from GitHub releases.
+//!
+//! A background check looks for a newer release in the chosen channel, and its changelog is shown
+//! before anything is installed. Only builds made with an ed25519 public key in
+//! `OCULANTE_UPDATE_PUBLIC_KEY` (hex) install updates, and only binaries whose `<asset>.sig`
+//! release asset holds a valid signature of the binary. The release workflow builds with the key
+//! and signs the binaries it uploads.
+
+use anyhow::{anyhow, bail, Result};
 use self_update::cargo_crate_version;
+use self_update::update::{Release, ReleaseAsset};
+use std::cmp::Ordering;
+use std::io::Write;
 use std::{sync::mpsc::Sender, thread};
 
-use crate::appstate::Message;
+use crate::appstate::{Message, OculanteState};
+use crate::redraw::Reason;
+use crate::settings::UpdateChannel;
+
+const PUBLIC_KEY: Option<&str> = option_env!("OCULANTE_UPDATE_PUBLIC_KEY");
+
+/// A newer release with a binary for this platform
+#[derive(Debug, Clone)]
+pub struct AvailableUpdate {
+    pub version: String,
+    pub changelog: String,
+    binary: ReleaseAsset,
+    signature: Option<ReleaseAsset>,
+}
 
-fn gh_update() -> Result<String, Box<dyn std::error::Error>> {
-    #[cfg(not(target_os = "linux"))]
-    let target = "";
-    #[cfg(target_os = "linux")]
-    let target = "_linux";
-    #[cfg(target_os = "macos")]
-    let target = "_mac";
+/// The release asset with the binary for this platform
+pub fn asset_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "oculante.exe"
+    } else if cfg!(target_os = "macos") {
+        "oculante_mac"
+    } else {
+        "oculante_linux"
+    }
+}
 
-    let status = self_update::backends::github::Update::configure()
+/// Whether updates can be verified, which they must be to be installed
+pub fn verifies_signatures() -> bool {
+    PUBLIC_KEY.is_some()
+}
+
+/// The newest release of `channel` that is newer than `current`
+pub fn newest(releases: &[Release], current: &str, channel: UpdateChannel) -> Option<Release> {
+    releases
+        .iter()
+        .filter(|r| channel == UpdateChannel::Nightly || !r.version.contains('-'))
+        .filter(|r| r.has_target_asset(asset_name()))
+        .filter(|r| self_update::version::bump_is_greater(current, &r.version).unwrap_or_default())
+        .max_by(|a, b| {
+            if self_update::version::bump_is_greater(&a.version, &b.version).unwrap_or_default() {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        })
+        .cloned()
+}
+
+fn find_update(channel: UpdateChannel) -> Result<Option<AvailableUpdate>> {
+    let releases = self_update::backends::github::ReleaseList::configure()
         .repo_owner("woelper")
         .repo_name("oculante")
-        .bin_name("oculante")
-        .target(target)
-        .current_version(cargo_crate_version!())
-        .no_confirm(true)
         .build()?
-        .update()?;
-    println!("Update status: `{}`!", status.version());
-    Ok(format!("{status:?}"))
+        .fetch()?;
+    Ok(
+        newest(&releases, cargo_crate_version!(), channel).and_then(|release| {
+            let asset = |name: &str| release.assets.iter().find(|a| a.name == name).cloned();
+            Some(AvailableUpdate {
+                version: release.version.clone(),
+                changelog: release.body.clone().unwrap_or_default(),
+                binary: asset(asset_name())?,
+                signature: asset(&format!("{}.sig", asset_name())),
+            })
+        }),
+    )
 }
 
-pub fn update(sender: Option<Sender<Message>>) {
-    thread::spawn(move || match gh_update() {
-        Ok(res) => {
-            _ = sender.map(|s| s.send(Message::Info(res)));
-        }
-        Err(e) => {
-            _ = sender.map(|s| s.send(Message::Error(format!("{e:?}"))));
+/// Look for an update in the background. A `manual` check also reports that there is none.
+pub fn check_in_background(state: &mut OculanteState, manual: bool) {
+    let channel = state.persistent_settings.update_channel;
+    let sender = state.update_channel.0.clone();
+    let messages = state.message_channel.0.clone();
+    let checking = state.redraw.poll_while_alive(Reason::Update);
+    thread::spawn(move || {
+        let _checking = checking;
+        let message = match find_update(channel) {
+            Ok(Some(update)) => {
+                _ = sender.send(update);
+                return;
+            }
+            Ok(None) => Message::info("Oculante is up to date"),
+            Err(e) => Message::err(&format!("Could not check for updates: {e}")),
+        };
+        if manual {
+            _ = messages.send(message);
         }
     });
 }
+
+fn download(asset: &ReleaseAsset) -> Result<Vec<u8>> {
+    let response = reqwest::blocking::Client::new()
+        .get(&asset.download_url)
+        .header(reqwest::header::ACCEPT, "application/octet-stream")
+        .header(reqwest::header::USER_AGENT, "oculante")
+        .send()?
+        .error_for_status()?;
+    Ok(response.bytes()?.to_vec())
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    hex.trim()
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() != 2 {
+                bail!("Odd number of hex digits");
+            }
+            Ok(u8::from_str_radix(std::str::from_utf8(pair)?, 16)?)
+        })
+        .collect()
+}
+
+/// Check an ed25519 signature of `data`. The signature may be raw or hex.
+pub fn verify(data: &[u8], signature: &[u8], public_key_hex: &str) -> Result<()> {
+    let signature = match std::str::from_utf8(signature).map(decode_hex) {
+        Ok(Ok(decoded)) => decoded,
+        _ => signature.to_vec(),
+    };
+    let key = decode_hex(public_key_hex)?;
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+        .verify(data, &signature)
+        .map_err(|_| anyhow!("The signature of the update is not valid"))
+}
+
+fn install_update(update: &AvailableUpdate) -> Result<()> {
+    let key = match PUBLIC_KEY {
+        Some(key) => key,
+        None => bail!("This build can't verify updates"),
+    };
+    let signature = match &update.signature {
+        Some(signature) => download(signature)?,
+        None => bail!("The update is not signed"),
+    };
+    let binary = download(&update.binary)?;
+    verify(&binary, &signature, key)?;
+    let dir = self_update::TempDir::new()?;
+    let path = dir.path().join(asset_name());
+    std::fs::File::create(&path)?.write_all(&binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    self_update::self_replace::self_replace(&path)?;
+    Ok(())
+}
+
+/// Download, verify and install an update in the background
+pub fn install(update: AvailableUpdate, sender: Sender<Message>) {
+    thread::spawn(move || {
+        _ = sender.send(match install_update(&update) {
+            Ok(_) => Message::info(&format!(
+                "Updated to {}. Restart oculante to use it.",
+                update.version
+            )),
+            Err(e) => Message::err(&format!("Could not update: {e}")),
+        });
+    });
+}
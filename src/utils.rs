@@ -2,8 +2,8 @@ use arboard::Clipboard;
 
 // use image::codecs::gif::GifDecoder;
 
-use img_parts::{Bytes, DynImage, ImageEXIF};
-use log::{debug, error, info};
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+use log::{debug, error, info, warn};
 use nalgebra::{clamp, Vector2};
 use notan::graphics::Texture;
 use notan::prelude::{App, Graphics, TextureFilter};
@@ -16,20 +16,24 @@ use std::ffi::OsStr;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
 use image::{self};
 use image::{EncodableLayout, Rgba, RgbaImage};
 use std::sync::mpsc::{self};
 use std::sync::mpsc::{Receiver, Sender};
-use strum::Display;
+use strum::{Display, IntoEnumIterator};
 use strum_macros::EnumIter;
 
 use crate::appstate::{ImageGeometry, Message, OculanteState};
-use crate::cache::Cache;
-use crate::image_editing::{self, ImageOperation};
-use crate::image_loader::open_image;
+use crate::cache::{Cache, DiskCache};
+use crate::channel_view::ChannelExpr;
+use crate::colormap::{Colormap, DataView};
+use crate::decode_pool::{DecodeOptions, DecodePool, LoadRequest};
+use crate::gpu::ChannelShader;
+use crate::redraw::Reason;
+use crate::settings::{ExternalCommand, Monitor, Sampling, ZoomPolicy};
 use crate::shortcuts::{lookup, InputEvent, Shortcuts};
 
 pub const SUPPORTED_EXTENSIONS: &[&str] = &[
@@ -90,6 +94,11 @@ pub struct ExtendedImageInfo {
     pub exif: HashMap<String, String>,
     pub raw_exif: Option<Bytes>,
     pub name: String,
+    /// See `sharpness::score`
+    pub sharpness: f32,
+    /// Faces and the main subject
+    #[cfg(feature = "detection")]
+    pub detections: Vec<crate::detection::Detection>,
 }
 
 impl ExtendedImageInfo {
@@ -169,6 +178,9 @@ impl ExtendedImageInfo {
             raw_exif: Default::default(),
             name: Default::default(),
             exif: Default::default(),
+            sharpness: crate::sharpness::score(img),
+            #[cfg(feature = "detection")]
+            detections: crate::detection::detect(img),
         }
     }
 }
@@ -178,7 +190,16 @@ pub struct Player {
     pub image_sender: Sender<Frame>,
     pub stop_sender: Sender<()>,
     pub cache: Cache,
+    /// Decoded images kept across sessions, if enabled
+    pub disk_cache: Option<DiskCache>,
+    /// Store thumbnails of opened images in the shared thumbnail cache
+    pub write_thumbnails: bool,
     pub max_texture_size: u32,
+    /// Decode large images at about this size first, see `FrameSource::Preview`
+    pub downsample_to: Option<(u32, u32)>,
+    /// The file whose full image is loaded to replace its preview
+    pub full_resolution: Option<PathBuf>,
+    pub pool: DecodePool,
     watcher: HashMap<PathBuf, SystemTime>,
 }
 
@@ -192,11 +213,59 @@ impl Player {
                 data: Default::default(),
                 cache_size,
             },
+            disk_cache: None,
+            write_thumbnails: false,
             max_texture_size,
+            downsample_to: None,
+            full_resolution: None,
+            pool: DecodePool::with_default_workers(),
             watcher: Default::default(),
         }
     }
 
+    /// Enable or disable the disk cache, with a size limit in megabytes
+    pub fn set_disk_cache(&mut self, enabled: bool, max_size_mb: u64) {
+        self.disk_cache = if enabled {
+            DiskCache::new(max_size_mb)
+                .map_err(|e| error!("Disk cache unavailable: {e}"))
+                .ok()
+        } else {
+            None
+        };
+    }
+
+    fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions {
+            max_texture_size: self.max_texture_size,
+            disk_cache: self.disk_cache.clone(),
+            write_thumbnails: self.write_thumbnails,
+            downsample_to: self.downsample_to,
+        }
+    }
+
+    /// Move finished prefetches into the memory cache
+    pub fn receive_prefetched(&mut self) {
+        for (path, img) in self.pool.prefetched.try_iter() {
+            if self.cache.cache_size != 0 {
+                self.cache.insert(&path, img);
+            }
+        }
+    }
+
+    /// Decode images that are likely to be shown next, if they are not cached yet
+    pub fn prefetch(&mut self, paths: &[PathBuf]) {
+        self.receive_prefetched();
+        if self.cache.cache_size == 0 {
+            return;
+        }
+        let paths = paths
+            .iter()
+            .filter(|p| !self.cache.data.contains_key(*p))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.pool.prefetch(&paths, self.decode_options());
+    }
+
     pub fn check_modified(&mut self, path: &Path, message_sender: Sender<Message>) {
         if let Some(watched_mod) = self.watcher.get(path) {
             // info!("{:?}", self.watcher);
@@ -220,10 +289,33 @@ impl Player {
     }
 
     pub fn load(&mut self, img_location: &Path, message_sender: Sender<Message>) {
+        self.full_resolution = None;
+        let options = self.decode_options();
+        self.start_load(img_location, message_sender, options);
+    }
+
+    /// Load the full image to replace a preview of it
+    pub fn load_full(&mut self, img_location: &Path, message_sender: Sender<Message>) {
+        debug!("Loading full image of {}", img_location.display());
+        self.full_resolution = Some(img_location.to_path_buf());
+        let options = DecodeOptions {
+            downsample_to: None,
+            ..self.decode_options()
+        };
+        self.start_load(img_location, message_sender, options);
+    }
+
+    fn start_load(
+        &mut self,
+        img_location: &Path,
+        message_sender: Sender<Message>,
+        options: DecodeOptions,
+    ) {
         debug!("Stopping player on load");
         self.stop();
         let (stop_sender, stop_receiver): (Sender<()>, Receiver<()>) = mpsc::channel();
         self.stop_sender = stop_sender;
+        self.receive_prefetched();
 
         if let Some(cached_image) = self.cache.get(img_location) {
             _ = self.image_sender.send(Frame::new_still(cached_image));
@@ -231,12 +323,14 @@ impl Player {
             return;
         }
 
-        send_image_threaded(
+        self.pool.load(
             img_location,
-            self.image_sender.clone(),
-            message_sender,
-            stop_receiver,
-            self.max_texture_size,
+            LoadRequest {
+                texture_sender: self.image_sender.clone(),
+                message_sender,
+                stop_receiver,
+            },
+            options,
         );
 
         if let Ok(meta) = std::fs::metadata(img_location) {
@@ -251,107 +345,6 @@ impl Player {
     }
 }
 
-pub fn send_image_threaded(
-    img_location: &Path,
-    texture_sender: Sender<Frame>,
-    message_sender: Sender<Message>,
-    stop_receiver: Receiver<()>,
-    max_texture_size: u32,
-) {
-    let loc = img_location.to_owned();
-
-    thread::spawn(move || {
-        let mut framecache = vec![];
-        let mut timer = std::time::Instant::now();
-
-        match open_image(&loc) {
-            Ok(frame_receiver) => {
-                // _ = texture_sender
-                // .clone()
-                // .send(Frame::new_reset(f.buffer.clone()));
-
-                let mut first = true;
-                for f in frame_receiver.iter() {
-                    if stop_receiver.try_recv().is_ok() {
-                        info!("Stopped from receiver.");
-                        return;
-                    }
-                    // a "normal image (no animation)"
-                    if f.source == FrameSource::Still {
-                        let largest_side = f.buffer.dimensions().0.max(f.buffer.dimensions().1);
-
-                        // Check if texture is too large to fit on the texture
-                        if largest_side > max_texture_size {
-                            _ = message_sender.send(Message::warn("This image exceeded the maximum resolution and will be be scaled down."));
-                            let scale_factor = max_texture_size as f32 / largest_side as f32;
-                            let new_dimensions = (
-                                (f.buffer.dimensions().0 as f32 * scale_factor)
-                                    .min(max_texture_size as f32)
-                                    as u32,
-                                (f.buffer.dimensions().1 as f32 * scale_factor)
-                                    .min(max_texture_size as f32)
-                                    as u32,
-                            );
-
-                            let mut frame = f;
-                            let op = ImageOperation::Resize {
-                                dimensions: new_dimensions,
-                                aspect: true,
-                                filter: image_editing::ScaleFilter::Box,
-                            };
-                            _ = op.process_image(&mut frame.buffer);
-                            let _ = texture_sender.send(frame);
-                        } else {
-                            let _ = texture_sender.send(f);
-                        }
-
-                        return;
-                    }
-                    if f.source == FrameSource::Animation {
-                        framecache.push(f.clone());
-                        if first {
-                            _ = texture_sender
-                                .clone()
-                                .send(Frame::new_reset(f.buffer.clone()));
-                        } else {
-                            let _ = texture_sender.send(f.clone());
-                        }
-                        let elapsed = timer.elapsed().as_millis();
-                        let wait_time_after_loading = f.delay.saturating_sub(elapsed as u16);
-                        debug!("elapsed {elapsed}, wait {wait_time_after_loading}");
-                        std::thread::sleep(Duration::from_millis(wait_time_after_loading as u64));
-                        timer = std::time::Instant::now();
-                    }
-
-                    first = false;
-                }
-
-                // loop over the image. For sanity, stop at a limit of iterations.
-                for _ in 0..500 {
-                    // let frames = col.frames.clone();
-                    for frame in &framecache {
-                        if stop_receiver.try_recv().is_ok() {
-                            info!("Stopped from receiver.");
-                            return;
-                        }
-                        let _ = texture_sender.send(frame.clone());
-                        if frame.delay > 0 {
-                            //                                                  cap at 60fps
-                            thread::sleep(Duration::from_millis(frame.delay.max(17) as u64));
-                        } else {
-                            thread::sleep(Duration::from_millis(40_u64));
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                error!("{e}");
-                _ = message_sender.send(Message::LoadError(e.to_string()));
-            }
-        }
-    });
-}
-
 /// A single frame
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq)]
@@ -361,6 +354,12 @@ pub enum FrameSource {
     ///First frame of animation. This is necessary to reset the image and stop the player.
     AnimationStart,
     Still,
+    ///A reduced decode of a large image. The full image is loaded when it is needed.
+    Preview,
+    ///A large still image that is partially decoded. The complete image follows as `Still`.
+    Partial,
+    ///A frame of a live stream. The view stays while frames arrive.
+    Stream,
     EditResult,
 }
 
@@ -382,10 +381,10 @@ impl Frame {
         }
     }
 
-    pub fn new_reset(buffer: RgbaImage) -> Frame {
+    pub fn new_reset(buffer: RgbaImage, delay: u16) -> Frame {
         Frame {
             buffer,
-            delay: 0,
+            delay,
             source: FrameSource::AnimationStart,
         }
     }
@@ -406,6 +405,22 @@ impl Frame {
             source: FrameSource::Still,
         }
     }
+
+    pub fn new_preview(buffer: RgbaImage) -> Frame {
+        Frame {
+            buffer,
+            delay: 0,
+            source: FrameSource::Preview,
+        }
+    }
+
+    pub fn new_partial(buffer: RgbaImage) -> Frame {
+        Frame {
+            buffer,
+            delay: 0,
+            source: FrameSource::Partial,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, EnumIter, Display, Clone, Copy)]
@@ -416,6 +431,20 @@ pub enum ColorChannel {
     Alpha,
     Rgb,
     Rgba,
+    /// Simulated red blindness
+    Protanopia,
+    /// Simulated green blindness
+    Deuteranopia,
+    /// Simulated blue blindness
+    Tritanopia,
+    Luma,
+    Hue,
+    Saturation,
+    Chroma,
+    /// A custom per-pixel expression of the channels
+    Expression,
+    /// Normal map vectors, re-normalized or relit
+    NormalMap,
 }
 
 impl ColorChannel {
@@ -427,6 +456,39 @@ impl ColorChannel {
             Self::Alpha => lookup(shortcuts, &InputEvent::AlphaChannel),
             Self::Rgb => lookup(shortcuts, &InputEvent::RGBChannel),
             Self::Rgba => lookup(shortcuts, &InputEvent::RGBAChannel),
+            _ => String::new(),
+        }
+    }
+
+    /// The linear RGB matrix simulating this color vision deficiency, from Machado et al. 2009
+    pub fn color_blindness(&self) -> Option<[f32; 9]> {
+        match self {
+            Self::Protanopia => Some([
+                0.152_286, 1.052_583, -0.204_868, 0.114_503, 0.786_281, 0.099_216, -0.003_882,
+                -0.048_116, 1.051_998,
+            ]),
+            Self::Deuteranopia => Some([
+                0.367_322, 0.860_646, -0.227_968, 0.280_085, 0.672_501, 0.047_413, -0.011_820,
+                0.042_940, 0.968_881,
+            ]),
+            Self::Tritanopia => Some([
+                1.255_528, -0.076_749, -0.178_779, -0.078_411, 0.930_809, 0.147_602, 0.004_733,
+                0.691_367, 0.303_900,
+            ]),
+            _ => None,
+        }
+    }
+
+    /// The expression computing this channel view, if it is computed. A custom expression is
+    /// taken from `custom`.
+    pub fn expression<'a>(&self, custom: &'a str) -> Option<&'a str> {
+        match self {
+            Self::Luma => Some("luma"),
+            Self::Hue => Some("hue"),
+            Self::Saturation => Some("saturation"),
+            Self::Chroma => Some("chroma"),
+            Self::Expression => Some(custom),
+            _ => None,
         }
     }
 }
@@ -435,6 +497,44 @@ pub fn zoomratio(i: f32, s: f32) -> f32 {
     i * s * 0.1
 }
 
+/// How close, relative to a whole zoom level, a zoom step must get to snap to it
+const SNAP_DISTANCE: f32 = 0.1;
+
+/// The highest zoom level the whole zoom shortcut goes to before starting over
+pub const MAX_WHOLE_ZOOM: f32 = 8.;
+
+/// The scale after zooming from `old` to `new` while snapping: the next whole zoom level like 2x
+/// in the zoom direction, if the step passes or nearly reaches it. Zooming away from a whole
+/// level is not held back.
+pub fn snapped_zoom(old: f32, new: f32) -> f32 {
+    let zoom_in = new > old;
+    let level = if zoom_in {
+        old.floor() + 1.
+    } else {
+        old.ceil() - 1.
+    };
+    let reached = if zoom_in {
+        new >= level * (1. - SNAP_DISTANCE)
+    } else {
+        new <= level * (1. + SNAP_DISTANCE)
+    };
+    if level >= 1. && reached {
+        level
+    } else {
+        new
+    }
+}
+
+/// The whole zoom level after `scale`, back to 1x after the highest
+pub fn next_whole_zoom(scale: f32) -> f32 {
+    let next = scale.floor() + 1.;
+    if next > MAX_WHOLE_ZOOM {
+        1.
+    } else {
+        next
+    }
+}
+
 /// Display RGBA values nicely
 pub fn disp_col(col: [f32; 4]) -> String {
     format!("{:.0},{:.0},{:.0},{:.0}", col[0], col[1], col[2], col[3])
@@ -455,6 +555,21 @@ pub fn toggle_fullscreen(app: &mut App, state: &mut OculanteState) {
     let fullscreen = app.window().is_fullscreen();
 
     if !fullscreen {
+        remember_display(app, state);
+        // the window goes fullscreen on the display it is on
+        let target = state
+            .persistent_settings
+            .fullscreen_display
+            .and_then(|i| state.persistent_settings.displays.get(i));
+        if let Some(display) = target {
+            if current_display(app, &state.persistent_settings.displays)
+                != state.persistent_settings.fullscreen_display
+            {
+                app.window()
+                    .set_position(display.position.0, display.position.1);
+            }
+        }
+
         let mut window_pos = app.window().position();
         window_pos.1 += 40;
 
@@ -483,17 +598,112 @@ pub fn toggle_fullscreen(app: &mut App, state: &mut OculanteState) {
     app.window().set_fullscreen(!fullscreen);
 }
 
+/// The physical window position, and the physical origin and size of the display the window is on
+fn window_display(app: &mut App) -> ((i32, i32), (i32, i32), (i32, i32)) {
+    let position = app.window().position();
+    let monitor = app
+        .backend
+        .downcast_ref::<notan::backend::WinitBackend>()
+        .and_then(|backend| backend.current_monitor());
+    if let Some((origin, size)) = monitor {
+        return (position, origin, size);
+    }
+    // without the monitor, the display is taken to start at the window
+    let dpi = app.window().dpi();
+    let (width, height) = app.window().screen_size();
+    (
+        position,
+        position,
+        ((width as f64 * dpi) as i32, (height as f64 * dpi) as i32),
+    )
+}
+
+/// Index of the remembered display the window is on
+pub fn current_display(app: &mut App, displays: &[Monitor]) -> Option<usize> {
+    let (position, _, size) = window_display(app);
+    displays.iter().position(|d| d.contains(position, size))
+}
+
+/// Remember the display the window is on, so it can be chosen for fullscreen and moved to later
+pub fn remember_display(app: &mut App, state: &mut OculanteState) -> usize {
+    let displays = &mut state.persistent_settings.displays;
+    if let Some(index) = current_display(app, displays) {
+        return index;
+    }
+    let (position, origin, size) = window_display(app);
+    displays.push(Monitor {
+        name: format!("Display {} ({}x{})", displays.len() + 1, size.0, size.1),
+        position,
+        origin,
+        size,
+    });
+    displays.len() - 1
+}
+
+/// Move the window to the next remembered display. An image that fits the window is fitted
+/// again, as the size of the window may change.
+pub fn move_to_next_display(app: &mut App, state: &mut OculanteState) {
+    let current = remember_display(app, state);
+    let displays = &state.persistent_settings.displays;
+    if displays.len() < 2 {
+        state.send_message_warn(
+            "Only this display is known. Move the window to the others once to remember them.",
+        );
+        return;
+    }
+    let next = displays[(current + 1) % displays.len()].clone();
+
+    let window_size = app.window().size();
+    let fitted = (state.image_geometry.scale
+        - ZoomPolicy::FitIfLarger.scale(
+            (
+                state.image_dimension.0 as f32,
+                state.image_dimension.1 as f32,
+            ),
+            (window_size.0 as f32, window_size.1 as f32),
+            state.image_geometry.scale,
+        ))
+    .abs()
+        < 0.001;
+
+    let fullscreen = app.window().is_fullscreen();
+    if fullscreen {
+        app.window().set_fullscreen(false);
+    }
+    app.window().set_position(next.position.0, next.position.1);
+    if fullscreen {
+        app.window().set_fullscreen(true);
+    }
+    if fitted || fullscreen {
+        state.reset_image = true;
+    }
+    state.send_message(&format!("Moved to {}", next.name));
+}
+
 /// Determine if an enxtension is compatible with oculante
 pub fn is_ext_compatible(fname: &Path) -> bool {
-    SUPPORTED_EXTENSIONS.contains(
-        &fname
-            .extension()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default()
-            .to_lowercase()
-            .as_str(),
-    )
+    let ext = fname
+        .extension()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or_default()
+        .to_lowercase();
+    SUPPORTED_EXTENSIONS.contains(&ext.as_str()) || crate::decoders::extensions().contains(&ext)
+}
+
+/// Check the first bytes of a file for a format we can decode, ignoring its extension
+pub fn is_content_compatible(fname: &Path) -> bool {
+    if !fname.is_file() {
+        return false;
+    }
+    let mut header = Vec::with_capacity(crate::decoders::HEADER_LEN);
+    let read = std::fs::File::open(fname).and_then(|f| {
+        std::io::Read::read_to_end(
+            &mut std::io::Read::take(f, crate::decoders::HEADER_LEN as u64),
+            &mut header,
+        )
+    });
+    read.is_ok() && (image::guess_format(&header).is_ok() || crate::decoders::find(fname).is_some())
 }
 
 pub fn solo_channel(img: &RgbaImage, channel: usize) -> RgbaImage {
@@ -507,7 +717,8 @@ pub fn solo_channel(img: &RgbaImage, channel: usize) -> RgbaImage {
     updated_img
 }
 
-pub fn unpremult(img: &RgbaImage) -> RgbaImage {
+/// Show the color of every pixel, ignoring alpha
+pub fn drop_alpha(img: &RgbaImage) -> RgbaImage {
     let mut updated_img = img.clone();
     updated_img.par_chunks_mut(4).for_each(|pixel| {
         pixel[3] = 255;
@@ -515,6 +726,354 @@ pub fn unpremult(img: &RgbaImage) -> RgbaImage {
     updated_img
 }
 
+/// How the color of translucent pixels relates to their alpha
+#[derive(Debug, PartialEq, EnumIter, Display, Clone, Copy)]
+pub enum AlphaMode {
+    /// Color independent of alpha, as most files store it
+    Straight,
+    /// Color multiplied by alpha, as renderers and compositing tools often write it
+    Premultiplied,
+}
+
+impl AlphaMode {
+    /// Premultiplied if there are translucent colored pixels, and no color exceeds its alpha.
+    /// Images without such pixels look the same in both modes.
+    pub fn detect(img: &RgbaImage) -> Self {
+        let mut translucent = false;
+        for pixel in img.pixels() {
+            let color = pixel[0].max(pixel[1]).max(pixel[2]);
+            if color > pixel[3] {
+                return Self::Straight;
+            }
+            translucent |= color > 0 && pixel[3] < 255;
+        }
+        if translucent {
+            Self::Premultiplied
+        } else {
+            Self::Straight
+        }
+    }
+}
+
+/// Divide the color of translucent pixels by their alpha
+pub fn unpremultiply(img: &RgbaImage) -> RgbaImage {
+    let mut updated_img = img.clone();
+    updated_img.par_chunks_mut(4).for_each(|pixel| {
+        let alpha = pixel[3] as u32;
+        if alpha > 0 && alpha < 255 {
+            for value in &mut pixel[..3] {
+                *value = ((*value as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    });
+    updated_img
+}
+
+/// How the color values of an image are interpreted for display
+#[derive(Debug, PartialEq, EnumIter, Display, Clone, Copy)]
+pub enum ColorEncoding {
+    /// Gamma encoded, like photos and color textures, shown as they are
+    #[strum(serialize = "sRGB")]
+    Srgb,
+    /// Proportional to light or data, like roughness maps and masks, encoded to sRGB for display
+    Linear,
+}
+
+/// Encode the linear color values of an image to sRGB
+pub fn linear_to_srgb(img: &RgbaImage) -> RgbaImage {
+    let to_srgb: Vec<u8> = (0..=255)
+        .map(|v| {
+            let v = v as f32 / 255.;
+            let v = if v <= 0.003_130_8 {
+                v * 12.92
+            } else {
+                1.055 * v.powf(1. / 2.4) - 0.055
+            };
+            (v * 255.).round() as u8
+        })
+        .collect();
+    let mut updated_img = img.clone();
+    updated_img.par_chunks_mut(4).for_each(|pixel| {
+        for value in &mut pixel[..3] {
+            *value = to_srgb[*value as usize];
+        }
+    });
+    updated_img
+}
+
+/// How the current image is interpreted, sRGB unless chosen otherwise
+pub fn color_encoding(state: &OculanteState) -> ColorEncoding {
+    state
+        .current_path
+        .as_ref()
+        .and_then(|path| state.color_encodings.get(path))
+        .copied()
+        .unwrap_or(ColorEncoding::Srgb)
+}
+
+/// The alpha mode of the current image: chosen by the user, or detected
+pub fn alpha_mode(state: &OculanteState) -> AlphaMode {
+    let path = match &state.current_path {
+        Some(path) => path,
+        None => return AlphaMode::Straight,
+    };
+    if let Some(mode) = state.alpha_overrides.get(path) {
+        return *mode;
+    }
+    match &state.alpha_detected {
+        Some((p, mode)) if p == path => *mode,
+        _ => AlphaMode::Straight,
+    }
+}
+
+/// Detect the alpha mode of a new image at the current path
+pub fn detect_alpha_mode(img: &RgbaImage, state: &mut OculanteState) {
+    let path = match &state.current_path {
+        Some(path) => path,
+        None => return,
+    };
+    if state.alpha_detected.as_ref().map(|(p, _)| p) != Some(path) {
+        state.alpha_detected = Some((path.clone(), AlphaMode::detect(img)));
+    }
+}
+
+/// Show the image as seen with a color vision deficiency, by transforming the linear RGB values
+pub fn simulate_color_blindness(img: &RgbaImage, matrix: &[f32; 9]) -> RgbaImage {
+    let to_linear: Vec<f32> = (0..=255)
+        .map(|v| {
+            let v = v as f32 / 255.;
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        })
+        .collect();
+    // linear values in steps of 1/4095 back to sRGB
+    let to_srgb: Vec<u8> = (0..4096)
+        .map(|v| {
+            let v = v as f32 / 4095.;
+            let v = if v <= 0.003_130_8 {
+                v * 12.92
+            } else {
+                1.055 * v.powf(1. / 2.4) - 0.055
+            };
+            (v * 255.).round() as u8
+        })
+        .collect();
+    let mut updated_img = img.clone();
+    updated_img.par_chunks_mut(4).for_each(|pixel| {
+        let rgb = [
+            to_linear[pixel[0] as usize],
+            to_linear[pixel[1] as usize],
+            to_linear[pixel[2] as usize],
+        ];
+        for (i, value) in pixel[..3].iter_mut().enumerate() {
+            let m = &matrix[i * 3..i * 3 + 3];
+            let linear = m[0] * rgb[0] + m[1] * rgb[1] + m[2] * rgb[2];
+            *value = to_srgb[(linear.clamp(0., 1.) * 4095.).round() as usize];
+        }
+    });
+    updated_img
+}
+
+/// Scale the RGB vectors of a normal map to unit length
+pub fn normalize_normals(img: &RgbaImage) -> RgbaImage {
+    let mut updated_img = img.clone();
+    updated_img.par_chunks_mut(4).for_each(|pixel| {
+        let n = [0, 1, 2].map(|i| pixel[i] as f32 / 255. * 2. - 1.);
+        let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if len > 0. {
+            for (value, n) in pixel[..3].iter_mut().zip(n) {
+                *value = ((n / len + 1.) / 2. * 255.).round() as u8;
+            }
+        }
+        pixel[3] = 255;
+    });
+    updated_img
+}
+
+/// The image as it is displayed with the selected channel and soft proof, or `None` if it is
+/// displayed unchanged
+pub fn display_image(img: &RgbaImage, state: &OculanteState) -> Option<RgbaImage> {
+    let channel = match &state.persistent_settings.current_channel {
+        ColorChannel::Rgb => match alpha_mode(state) {
+            AlphaMode::Straight => Some(drop_alpha(img)),
+            AlphaMode::Premultiplied => Some(drop_alpha(&unpremultiply(img))),
+        },
+        // textures are blended with straight alpha
+        ColorChannel::Rgba => match alpha_mode(state) {
+            AlphaMode::Straight => None,
+            AlphaMode::Premultiplied => Some(unpremultiply(img)),
+        },
+        // computed views are drawn by the channel shader if it is available
+        ColorChannel::Luma
+        | ColorChannel::Hue
+        | ColorChannel::Saturation
+        | ColorChannel::Chroma
+        | ColorChannel::Expression => match channel_expression(state) {
+            Some(Ok(expr)) if !state.channel_view_gpu => Some(expr.apply(img)),
+            _ => None,
+        },
+        ColorChannel::NormalMap => match channel_expression(state) {
+            Some(Ok(_)) if state.channel_view_gpu => None,
+            Some(Ok(expr)) => Some(expr.apply(img)),
+            _ => Some(normalize_normals(img)),
+        },
+        channel => match channel.color_blindness() {
+            Some(matrix) => Some(simulate_color_blindness(img, &matrix)),
+            None => Some(solo_channel(img, *channel as usize)),
+        },
+    };
+    // computed views and simulations take the values as they are
+    let channel = match state.persistent_settings.current_channel {
+        ColorChannel::Rgb
+        | ColorChannel::Rgba
+        | ColorChannel::Red
+        | ColorChannel::Green
+        | ColorChannel::Blue
+            if color_encoding(state) == ColorEncoding::Linear =>
+        {
+            Some(linear_to_srgb(channel.as_ref().unwrap_or(img)))
+        }
+        _ => channel,
+    };
+    match &state.soft_proof {
+        Some(proof) => Some(proof.apply(
+            channel.as_ref().unwrap_or(img),
+            state.persistent_settings.gamut_warning,
+        )),
+        None => channel,
+    }
+}
+
+/// The compiled expression of the selected channel, if it is computed
+pub fn channel_expression(state: &OculanteState) -> Option<Result<ChannelExpr>> {
+    let settings = &state.persistent_settings;
+    if settings.current_channel == ColorChannel::NormalMap {
+        return settings.normal_relight.then(|| {
+            Ok(ChannelExpr::normal_lighting(
+                settings.normal_light,
+                settings.normal_green_down,
+            ))
+        });
+    }
+    settings
+        .current_channel
+        .expression(&settings.channel_expression)
+        .map(ChannelExpr::parse)
+}
+
+/// Prepare the channel shader to draw the selected channel, if it is computed and the image is
+/// not tiled. Returns whether the shader is used.
+pub fn prepare_channel_shader(state: &mut OculanteState, gfx: &mut Graphics) -> bool {
+    let expr = match channel_expression(state) {
+        Some(Ok(expr)) if state.tiling < 2 => expr,
+        _ => return false,
+    };
+    if state.channel_shader.is_none() {
+        match ChannelShader::new(gfx) {
+            Ok(shader) => state.channel_shader = Some(shader),
+            Err(e) => {
+                error!("{e}, computing channel views on the CPU");
+                return false;
+            }
+        }
+    }
+    match &state.channel_shader {
+        Some(shader) => {
+            shader.set_program(gfx, &expr.encode());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Show the current image with the selected channel and soft proof
+pub fn update_display_texture(state: &mut OculanteState, gfx: &mut Graphics) {
+    state.channel_view_gpu = prepare_channel_shader(state, gfx);
+    if let Some(img) = &state.current_image {
+        let sampling = state.sampling();
+        state.current_texture = match display_image(img, state) {
+            Some(shown) => shown.to_texture(gfx, sampling),
+            None => img.to_texture(gfx, sampling),
+        };
+    }
+}
+
+/// Upload the shown image again, after its sampling mode changed
+pub fn refresh_sampling(state: &mut OculanteState, gfx: &mut Graphics) {
+    if let Some(img) = &state.current_image {
+        if state.edit_state.result_image_op.is_empty() {
+            state.current_texture = img.to_texture(gfx, state.sampling());
+        } else {
+            state.current_texture = state
+                .edit_state
+                .result_pixel_op
+                .to_texture(gfx, state.sampling());
+        }
+    }
+}
+
+/// Show a frame of the playing animation
+pub fn show_animation_frame(img: RgbaImage, state: &mut OculanteState, gfx: &mut Graphics) {
+    let sampling = state.sampling();
+    match (display_image(&img, state), &mut state.current_texture) {
+        (Some(shown), _) => state.current_texture = shown.to_texture(gfx, sampling),
+        (None, Some(tex))
+            if tex.width() as u32 == img.width() && tex.height() as u32 == img.height() =>
+        {
+            img.update_texture(gfx, tex)
+        }
+        (None, _) => state.current_texture = img.to_texture(gfx, sampling),
+    }
+    state.current_image = Some(img);
+}
+
+/// Show the current image through its colormap view, or as it was decoded if the view is not
+/// active
+pub fn apply_data_view(state: &mut OculanteState, gfx: &mut Graphics) {
+    let img = match &state.data_view {
+        Some(view) if view.active => view.render(),
+        Some(view) => view
+            .image
+            .as_ref()
+            .map(|image| image.render(Colormap::Gray, 0., image.max)),
+        None => None,
+    };
+    if let Some(img) = img {
+        match &mut state.current_texture {
+            Some(tex)
+                if tex.width() as u32 == img.width() && tex.height() as u32 == img.height() =>
+            {
+                img.update_texture(gfx, tex)
+            }
+            _ => state.current_texture = img.to_texture(gfx, state.sampling()),
+        }
+        state.current_image = Some(img);
+    }
+}
+
+/// Follow the current image with the colormap view. Images are only read while the info panel
+/// is open or a colormap is shown.
+pub fn update_data_view(state: &mut OculanteState, gfx: &mut Graphics) {
+    let path = match &state.current_path {
+        Some(p) if state.is_loaded => p.clone(),
+        _ => return,
+    };
+    if state.data_view.as_ref().map(|v| &v.path) == Some(&path) {
+        return;
+    }
+    let active = state.data_view.as_ref().map(|v| v.active) == Some(true);
+    if !active && !state.persistent_settings.info_enabled {
+        return;
+    }
+    state.data_view = Some(DataView::new(&path, state.data_view.as_ref()));
+    if active {
+        apply_data_view(state, gfx);
+    }
+}
+
 /// Mark pixels with no alpha but color info
 pub fn highlight_bleed(img: &RgbaImage) -> RgbaImage {
     let mut updated_img = img.clone();
@@ -579,12 +1138,29 @@ pub fn send_extended_info(
     }
 }
 
+/// The minifying and magnifying filter of a texture of `dimensions`
+pub fn texture_filters(
+    sampling: Sampling,
+    dimensions: (u32, u32),
+) -> (TextureFilter, TextureFilter) {
+    let min_filter = match sampling {
+        Sampling::Nearest => TextureFilter::Nearest,
+        _ => TextureFilter::Linear,
+    };
+    let mag_filter = if sampling.nearest_on_zoom(dimensions) {
+        TextureFilter::Nearest
+    } else {
+        TextureFilter::Linear
+    };
+    (min_filter, mag_filter)
+}
+
 pub trait ImageExt {
     fn size_vec(&self) -> Vector2<f32> {
         unimplemented!()
     }
 
-    fn to_texture(&self, _: &mut Graphics, _linear_mag_filter: bool) -> Option<Texture> {
+    fn to_texture(&self, _: &mut Graphics, _sampling: Sampling) -> Option<Texture> {
         unimplemented!()
     }
 
@@ -606,20 +1182,14 @@ impl ImageExt for RgbaImage {
         Vector2::new(self.width() as f32, self.height() as f32)
     }
 
-    fn to_texture(&self, gfx: &mut Graphics, linear_mag_filter: bool) -> Option<Texture> {
+    fn to_texture(&self, gfx: &mut Graphics, sampling: Sampling) -> Option<Texture> {
+        let (min_filter, mag_filter) = texture_filters(sampling, self.dimensions());
         gfx.create_texture()
             .from_bytes(self, self.width(), self.height())
-            .with_mipmaps(true)
+            .with_mipmaps(matches!(sampling, Sampling::Auto | Sampling::Mipmap))
             // .with_format(notan::prelude::TextureFormat::SRgba8)
             // .with_premultiplied_alpha()
-            .with_filter(
-                TextureFilter::Linear,
-                if linear_mag_filter {
-                    TextureFilter::Linear
-                } else {
-                    TextureFilter::Nearest
-                },
-            )
+            .with_filter(min_filter, mag_filter)
             // .with_wrap(TextureWrap::Clamp, TextureWrap::Clamp)
             .build()
             .ok()
@@ -685,6 +1255,7 @@ pub fn prev_image(state: &mut OculanteState) {
 }
 
 pub fn load_image_from_path(p: &Path, state: &mut OculanteState) {
+    state.stream.stop();
     state.is_loaded = false;
     state.player.load(p, state.message_channel.0.clone());
     state.current_path = Some(p.to_owned());
@@ -733,6 +1304,80 @@ pub fn next_image(state: &mut OculanteState) {
     }
 }
 
+/// Run an external command on the current file in the background.
+/// Its output is shown as a message, and the file is loaded again afterwards if requested.
+pub fn run_external_command(command: &ExternalCommand, state: &mut OculanteState) {
+    // commands can change the file or write new ones
+    if !state.check_writable() {
+        return;
+    }
+    let path = match state.current_path.clone() {
+        Some(p) => p,
+        None => return,
+    };
+    let command = command.clone();
+    let command_line = command.expand(&path);
+    let message_sender = state.message_channel.0.clone();
+    let load_sender = state.load_channel.0.clone();
+    if command.reload {
+        // make sure the reload does not hit a stale cached image
+        state.player.cache.data.remove(&path);
+        if let Some(cache) = &state.player.disk_cache {
+            if let Err(e) = cache.remove(&path) {
+                warn!("Can't remove {} from the disk cache: {e}", path.display());
+            }
+        }
+    }
+    debug!("Running {command_line}");
+    state.send_message(&format!("Running {}...", command.name));
+
+    thread::spawn(move || {
+        // cmd does not understand the quoting of `arg`, so the line is passed as it is. With /S
+        // cmd only strips the outer quotes, and keeps the ones of quoted paths.
+        #[cfg(windows)]
+        let output = {
+            use std::os::windows::process::CommandExt;
+            std::process::Command::new("cmd")
+                .args(["/S", "/C"])
+                .raw_arg(format!("\"{command_line}\""))
+                .output()
+        };
+        #[cfg(not(windows))]
+        let output = std::process::Command::new("sh")
+            .args(["-c", &command_line])
+            .output();
+
+        match output {
+            Ok(output) => {
+                let text = [output.stdout, output.stderr]
+                    .iter()
+                    .map(|o| String::from_utf8_lossy(o).trim().to_string())
+                    .filter(|o| !o.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let msg = if output.status.success() {
+                    Message::info(&format!("{}: {}", command.name, text))
+                } else {
+                    Message::err(&format!(
+                        "{} failed ({}): {}",
+                        command.name, output.status, text
+                    ))
+                };
+                _ = message_sender.send(msg);
+                if command.reload {
+                    _ = load_sender.send(path);
+                }
+            }
+            Err(e) => {
+                _ = message_sender.send(Message::err(&format!(
+                    "Could not run {}: {e}",
+                    command.name
+                )));
+            }
+        }
+    });
+}
+
 /// Set the window title
 pub fn set_title(app: &mut App, state: &mut OculanteState) {
     let p = state.current_path.clone().unwrap_or_default();
@@ -787,7 +1432,7 @@ pub fn compare_next(state: &mut OculanteState) {
             state.current_image = None;
             state.player.load(path, state.message_channel.0.clone());
             state.current_path = Some(path.clone());
-            state.persistent_settings.keep_view = true;
+            state.keep_view = true;
         }
     }
 }
@@ -807,13 +1452,1216 @@ pub fn toggle_zen_mode(state: &mut OculanteState, app: &mut App) {
     set_title(app, state);
 }
 
-/// Fix missing exif by re-applying exif to saved files
-pub fn fix_exif(p: &Path, exif: Option<Bytes>) -> Result<()> {
-    use std::fs::{self, File};
-    let input = fs::read(p)?;
-    let mut dynimage = DynImage::from_bytes(input.into())?.context("Unsupported EXIF format")?;
-    dynimage.set_exif(exif);
-    let output = File::create(p)?;
-    dynimage.encoder().write_to(output)?;
-    Ok(())
+/// Listen for later launches, show the tray icon and register the hotkey of background mode
+pub fn enable_background_mode(state: &mut OculanteState) {
+    if let Err(e) = crate::instance::listen(state.instance_channel.0.clone(), state.redraw.waker())
+    {
+        state.send_message_err(&format!("Background mode is not available: {e}"));
+    }
+    #[cfg(feature = "tray")]
+    {
+        // the old icon and hotkey are removed first
+        state.tray = None;
+        match crate::tray::Tray::new(
+            state.instance_channel.0.clone(),
+            state.redraw.waker(),
+            &state.persistent_settings.background_hotkey,
+        ) {
+            Ok(tray) => state.tray = Some(tray),
+            Err(e) => state.send_message_err(&format!("No tray icon or hotkey: {e}")),
+        }
+    }
+}
+
+/// Hide the window in background mode, until the tray icon, the hotkey or another launch with
+/// `--show` asks for it
+pub fn hide_to_background(app: &mut App, state: &mut OculanteState) {
+    state.persistent_settings.save();
+    // requests wake the hidden window
+    app.window().set_visible(false);
+}
+
+/// Lock the view to the dimensions of the current image, so sequences can be compared pixel by
+/// pixel, or unlock it
+pub fn toggle_lock_view(state: &mut OculanteState) {
+    if state.lock_view.take().is_some() {
+        state.send_message("View unlocked");
+    } else if state.current_image.is_some() {
+        let (width, height) = state.image_dimension;
+        state.lock_view = Some((width, height));
+        state.send_message(&format!(
+            "View locked for {width}x{height} images. Press '{}' to unlock.",
+            lookup(&state.persistent_settings.shortcuts, &InputEvent::LockView)
+        ));
+    }
+}
+
+/// Show the image as printed through the soft proof profile from the settings, or stop
+pub fn toggle_soft_proof(state: &mut OculanteState) {
+    if state.soft_proof.take().is_some() {
+        state.send_message("Soft proof off");
+        return;
+    }
+    match &state.persistent_settings.proof_profile {
+        Some(path) => match crate::soft_proof::SoftProof::load(path) {
+            Ok(proof) => {
+                state.send_message(&format!("Soft proofing with {}", proof.description));
+                state.soft_proof = Some(proof);
+            }
+            Err(e) => state.send_message_err(&format!("Can't load proof profile: {e}")),
+        },
+        None => state.send_message_warn("Choose a proof profile in the settings first"),
+    }
+}
+
+/// List the folder of `path` on a thread, as it may be large. The listing arrives through the
+/// scrubber channel.
+pub fn list_folder(state: &mut OculanteState, path: PathBuf) {
+    let all_files = state.load_all_files();
+    let sender = state.scrubber_channel.0.clone();
+    let listing = state.redraw.poll_while_alive(Reason::Folder);
+    thread::spawn(move || {
+        let _listing = listing;
+        _ = sender.send(crate::scrubber::Scrubber::new(&path, all_files));
+    });
+}
+
+/// Sort and filter the folder by sharpness as the settings say, or by similarity to an image.
+/// Images that have no score or hash yet are analyzed on a thread.
+pub fn arrange_folder(state: &mut OculanteState) {
+    let path = match &state.current_path {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    let sort = state.persistent_settings.sharpness_sort;
+    let min = state.persistent_settings.min_sharpness;
+    if sort || min > 0. {
+        let unscored = state.sharpness.claim_missing(&state.scrubber.entries);
+        if !unscored.is_empty() {
+            debug!("Scoring sharpness of {} images", unscored.len());
+            crate::sharpness::score_files(
+                unscored,
+                state.sharpness_channel.0.clone(),
+                state.redraw.poll_while_alive(Reason::Analysis),
+            );
+        }
+        state
+            .scrubber
+            .arrange_by_sharpness(&state.sharpness, sort, min, &path);
+    }
+    if let Some((_, reference)) = state.similar_to {
+        let unhashed = state.hashes.claim_missing(&state.scrubber.entries);
+        if !unhashed.is_empty() {
+            debug!("Hashing {} images", unhashed.len());
+            crate::similarity::hash_files(
+                unhashed,
+                state.hash_channel.0.clone(),
+                state.redraw.poll_while_alive(Reason::Analysis),
+            );
+        }
+        let max_distance = state.persistent_settings.max_hash_distance;
+        state
+            .scrubber
+            .arrange_by_similarity(&state.hashes, reference, max_distance, &path);
+    }
+}
+
+/// Filter the folder to the images that look like the current one, most similar first
+pub fn find_similar(state: &mut OculanteState) {
+    let (path, img) = match (&state.current_path, &state.current_image) {
+        (Some(path), Some(img)) => (path.clone(), img),
+        _ => return,
+    };
+    let hash = match state.hashes.get(&path) {
+        Some(hash) => hash,
+        None => {
+            let hash = crate::similarity::dhash(img);
+            state.hashes.insert(&path, hash);
+            hash
+        }
+    };
+    state.similar_to = Some((path.clone(), hash));
+    // start from the complete folder
+    list_folder(state, path);
+}
+
+/// Open the search window, indexing the folder of the current image if needed, or close it
+pub fn toggle_search(state: &mut OculanteState) {
+    state.search.enabled = !state.search.enabled;
+    if !state.search.enabled {
+        stop_browsing_search(state);
+        return;
+    }
+    let root = match state.current_path.as_ref().and_then(|p| p.parent()) {
+        Some(root) => root.to_path_buf(),
+        None => return,
+    };
+    let indexed = state.search.index.as_ref().map(|i| i.root == root) == Some(true);
+    if !indexed && !state.search.indexing {
+        index_search(state, root);
+    }
+}
+
+/// Build the search index of the images below `root` on a thread
+pub fn index_search(state: &mut OculanteState, root: PathBuf) {
+    state.search.indexing = true;
+    let all_files = state.load_all_files();
+    let sender = state.search_channel.0.clone();
+    let indexing = state.redraw.poll_while_alive(Reason::Analysis);
+    thread::spawn(move || {
+        let _indexing = indexing;
+        let file = crate::search::TextCache::default_file(crate::search::CACHE_FILE);
+        let mut cache = file
+            .as_ref()
+            .map(|file| crate::search::TextCache::load(file))
+            .unwrap_or_default();
+        let index = crate::search::SearchIndex::build(&root, all_files, &mut cache);
+        if let Err(e) = cache.save() {
+            error!("Can't save the search cache: {e}");
+        }
+        _ = sender.send(index);
+    });
+}
+
+/// List the folder in the scrubber again instead of the search results
+pub fn stop_browsing_search(state: &mut OculanteState) {
+    if state.search.browsing {
+        state.search.browsing = false;
+        if let Some(path) = state.current_path.clone() {
+            list_folder(state, path);
+        }
+    }
+}
+
+/// Open the statistics window, computing them for the folder of the current image if needed, or
+/// close it
+pub fn toggle_folder_stats(state: &mut OculanteState) {
+    state.folder_stats.enabled = !state.folder_stats.enabled;
+    if !state.folder_stats.enabled {
+        return;
+    }
+    let folder = match state.current_path.as_ref().and_then(|p| p.parent()) {
+        Some(folder) => folder.to_path_buf(),
+        None => return,
+    };
+    let computed = state
+        .folder_stats
+        .stats
+        .as_ref()
+        .map(|s| s.folder == folder)
+        == Some(true);
+    if !computed && !state.folder_stats.computing {
+        compute_folder_stats(state, folder);
+    }
+}
+
+/// Hash the current file on a thread while the info panel is open. Its duplicates are looked for
+/// among the files of the search index, or the folder if the file is not indexed.
+pub fn update_checksum(state: &mut OculanteState) {
+    let path = match &state.current_path {
+        Some(path) if state.persistent_settings.info_enabled && state.is_loaded => path.clone(),
+        _ => return,
+    };
+    if state.checksum.path.as_ref() == Some(&path) {
+        return;
+    }
+    let candidates = match &state.search.index {
+        Some(index) if path.starts_with(&index.root) => index.files.clone(),
+        _ => state.scrubber.entries.clone(),
+    };
+    state.checksum.path = Some(path.clone());
+    state.checksum.checksum = None;
+    let sender = state.checksum_channel.0.clone();
+    let computing = state.redraw.poll_while_alive(Reason::Analysis);
+    thread::spawn(move || {
+        let _computing = computing;
+        let file = crate::checksum::ChecksumCache::default_file(crate::checksum::CACHE_FILE);
+        let mut cache = file
+            .as_ref()
+            .map(|file| crate::checksum::ChecksumCache::load(file))
+            .unwrap_or_default();
+        let checksum = crate::checksum::FileChecksum::compute(&path, &candidates, &mut cache);
+        if let Err(e) = cache.save() {
+            error!("Can't save the checksum cache: {e}");
+        }
+        _ = sender.send((path, checksum.map_err(|e| e.to_string())));
+    });
+}
+
+/// Estimate the file sizes of the current image on a thread while the window is open
+pub fn update_size_estimates(state: &mut OculanteState) {
+    if !state.size_estimates.enabled
+        || !state.is_loaded
+        || (state.size_estimates.path == state.current_path && !state.size_estimates.refresh)
+    {
+        return;
+    }
+    let img = match &state.current_image {
+        Some(img) => img.clone(),
+        None => return,
+    };
+    let path = state.current_path.clone();
+    state.size_estimates.path = path.clone();
+    state.size_estimates.estimates = None;
+    state.size_estimates.refresh = false;
+    let sender = state.size_estimates_channel.0.clone();
+    let computing = state.redraw.poll_while_alive(Reason::Analysis);
+    thread::spawn(move || {
+        let _computing = computing;
+        let codecs = crate::size_estimate::Codec::iter().collect::<Vec<_>>();
+        _ = sender.send((path, crate::size_estimate::estimate(&img, &codecs)));
+    });
+}
+
+/// The zoom that shows the current image at its physical size, if its file has a resolution
+pub fn print_zoom(app: &mut App, state: &mut OculanteState) -> Option<f32> {
+    let path = state.current_path.clone()?;
+    if state.print_dpi.as_ref().map(|(p, _)| p) != Some(&path) {
+        state.print_dpi = Some((path.clone(), crate::print_size::read_dpi(&path)));
+    }
+    let dpi = state.print_dpi.as_ref()?.1?;
+    let img = state.current_image.as_ref()?;
+    // a preview has fewer pixels than the file
+    let file_width = match image::image_dimensions(&path) {
+        Ok((width, _)) if state.is_preview => width,
+        _ => img.width(),
+    };
+    let scale_factor = app.window().dpi() as f32;
+    let screen_ppi = state
+        .persistent_settings
+        .monitor_ppi
+        .unwrap_or(crate::print_size::DEFAULT_PPI * scale_factor);
+    Some(
+        crate::print_size::print_scale(dpi, screen_ppi, scale_factor) * file_width as f32
+            / img.width().max(1) as f32,
+    )
+}
+
+/// Zoom around the center of the window until the current image has its physical size
+pub fn zoom_to_print_size(app: &mut App, state: &mut OculanteState) {
+    match print_zoom(app, state) {
+        Some(zoom) => {
+            let center = app.window().size().size_vec() / 2.;
+            crate::set_zoom(zoom, Some(center), state);
+        }
+        None => state.send_message_warn("The file has no resolution to show its print size"),
+    }
+}
+
+/// Summarize the images of `folder` on a thread
+pub fn compute_folder_stats(state: &mut OculanteState, folder: PathBuf) {
+    state.folder_stats.computing = true;
+    let all_files = state.load_all_files();
+    let sender = state.folder_stats_channel.0.clone();
+    let computing = state.redraw.poll_while_alive(Reason::Analysis);
+    thread::spawn(move || {
+        let _computing = computing;
+        let file = crate::folder_stats::FactsCache::default_file(crate::folder_stats::CACHE_FILE);
+        let mut cache = file
+            .as_ref()
+            .map(|file| crate::folder_stats::FactsCache::load(file))
+            .unwrap_or_default();
+        let stats = crate::folder_stats::FolderStats::compute(&folder, all_files, &mut cache);
+        if let Err(e) = cache.save() {
+            error!("Can't save the folder statistics cache: {e}");
+        }
+        _ = sender.send(stats);
+    });
+}
+
+/// Cut the selection, or the whole image without one, out of the image at the resolution of its
+/// file and pass it to `done` on a thread. If only a preview is shown, the file is decoded again
+/// in full.
+pub fn crop_selection(
+    state: &mut OculanteState,
+    done: impl FnOnce(Result<RgbaImage>) + Send + 'static,
+) {
+    let img = match &state.current_image {
+        Some(img) => img,
+        None => return,
+    };
+    let rect = state
+        .selection
+        .rect(img.dimensions())
+        .unwrap_or((0, 0, img.width(), img.height()));
+    crop_full_resolution(state, rect, done);
+}
+
+/// Crop `rect` of the current image on a thread, from the file if the current image is a preview
+pub fn crop_full_resolution(
+    state: &mut OculanteState,
+    rect: crate::selection::PixelRect,
+    done: impl FnOnce(Result<RgbaImage>) + Send + 'static,
+) {
+    let img = match &state.current_image {
+        Some(img) => img,
+        None => return,
+    };
+    let path = match state.current_path.clone() {
+        Some(path) if state.is_preview => path,
+        _ => {
+            let crop = crate::selection::crop(img, rect);
+            thread::spawn(move || done(Ok(crop)));
+            return;
+        }
+    };
+    let preview = img.dimensions();
+    let loading = state.redraw.poll_while_alive(Reason::Loading);
+    thread::spawn(move || {
+        let _loading = loading;
+        let full = crate::image_loader::open_image(&path).and_then(|frames| {
+            frames
+                .recv()
+                .map_err(|_| anyhow::anyhow!("No image in {}", path.display()))
+        });
+        done(full.map(|frame| {
+            let rect = crate::selection::scale_rect(rect, preview, frame.buffer.dimensions());
+            crate::selection::crop(&frame.buffer, rect)
+        }));
+    });
+}
+
+/// Save the channels of the current image as grayscale files on a thread, in the folder `folder`
+/// returns. Nothing is saved if it returns `None`, like when a dialog is cancelled.
+pub fn split_channels(
+    state: &mut OculanteState,
+    folder: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) {
+    if !state.check_writable() {
+        return;
+    }
+    let (img, path) = match (&state.current_image, state.current_path.clone()) {
+        (Some(img), Some(path)) => (img, path),
+        _ => return,
+    };
+    let rect = (0, 0, img.width(), img.height());
+    let msg_sender = state.message_channel.0.clone();
+    crop_full_resolution(state, rect, move |img| {
+        let folder = match folder() {
+            Some(folder) => folder,
+            None => return,
+        };
+        let paths = crate::channel_pack::split_paths(&path, &folder);
+        let msg = match img.and_then(|img| crate::channel_pack::save_split(&img, &paths)) {
+            Ok(()) => Message::info(&format!("Channels saved to {}", folder.display())),
+            Err(e) => Message::err(&format!("Could not split the channels: {e}")),
+        };
+        _ = msg_sender.send(msg);
+    });
+}
+
+/// Save the frames of the current animation on a thread, as numbered PNGs or as a spritesheet
+/// with its atlas, in the folder `folder` returns
+pub fn export_animation(
+    state: &mut OculanteState,
+    spritesheet: bool,
+    folder: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) {
+    if !state.check_writable() {
+        return;
+    }
+    let (animation, path) = match (&state.animation, state.current_path.clone()) {
+        (Some(animation), Some(path)) => (animation, path),
+        _ => return,
+    };
+    let frames = animation.frames.clone();
+    let columns = animation.sheet_columns;
+    let msg_sender = state.message_channel.0.clone();
+    thread::spawn(move || {
+        let folder = match folder() {
+            Some(folder) => folder,
+            None => return,
+        };
+        let saved = if spritesheet {
+            crate::animation::save_spritesheet(&frames, columns, &path, &folder)
+                .map(|sheet| format!("Spritesheet saved to {}", sheet.display()))
+        } else {
+            crate::animation::save_frames(&frames, &path, &folder)
+                .map(|_| format!("{} frames saved to {}", frames.len(), folder.display()))
+        };
+        _ = msg_sender.send(match saved {
+            Ok(msg) => Message::info(&msg),
+            Err(e) => Message::err(&format!("Could not export the animation: {e}")),
+        });
+    });
+}
+
+/// Files to assemble an animation from, with a name for it: the loop of the numbered sequence,
+/// or else the images of the folder
+pub fn animation_sources(state: &OculanteState) -> (Vec<PathBuf>, String) {
+    if let Some(sequence) = &state.sequence {
+        let (start, end) = sequence.range;
+        let frames = sequence
+            .sequence
+            .frames
+            .get(start..=end)
+            .unwrap_or_default()
+            .iter()
+            .map(|(_, path)| path.clone())
+            .collect();
+        let name = sequence
+            .sequence
+            .prefix
+            .trim_end_matches(|c: char| "._- ".contains(c))
+            .to_string();
+        return (frames, name);
+    }
+    let name = state
+        .scrubber
+        .entries
+        .first()
+        .and_then(|p| p.parent()?.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    (state.scrubber.entries.clone(), name)
+}
+
+/// Assemble an animation from `frames` on a thread, and save it to the path `target` returns
+pub fn create_animation(
+    state: &mut OculanteState,
+    frames: Vec<PathBuf>,
+    target: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) {
+    if !state.check_writable() {
+        return;
+    }
+    let settings = state.animation_encoder.settings;
+    let sender = state.animation_encoder_channel.0.clone();
+    let running = state.redraw.poll_while_alive(Reason::Analysis);
+    state.animation_encoder.running = true;
+    thread::spawn(move || {
+        let _running = running;
+        let path = match target() {
+            Some(path) => path,
+            None => {
+                _ = sender.send(None);
+                return;
+            }
+        };
+        let created = (|| -> Result<PathBuf> {
+            let mut images = vec![];
+            for frame in &frames {
+                let img = crate::image_loader::open_image(frame)?
+                    .recv()
+                    .map_err(|_| anyhow::anyhow!("No image in {}", frame.display()))?;
+                images.push(img.buffer);
+            }
+            let data = crate::animation_encoder::encode(&images, &settings)?;
+            std::fs::write(&path, data)?;
+            Ok(path)
+        })();
+        _ = sender.send(Some(created.map_err(|e| e.to_string())));
+    });
+}
+
+/// Combine the grayscale sources of the channel packer into a new image on a thread, and show it
+pub fn combine_channels(state: &mut OculanteState) {
+    let sources = state.channel_pack.sources.clone();
+    let image_sender = state.player.image_sender.clone();
+    let msg_sender = state.message_channel.0.clone();
+    let loading = state.redraw.poll_while_alive(Reason::Loading);
+    state.player.stop();
+    thread::spawn(move || {
+        let _loading = loading;
+        let mut channels: [Option<image::GrayImage>; 4] = Default::default();
+        for (channel, path) in channels.iter_mut().zip(&sources) {
+            if let Some(path) = path {
+                let frame = crate::image_loader::open_image(path).and_then(|frames| {
+                    frames
+                        .recv()
+                        .map_err(|_| anyhow::anyhow!("No image in {}", path.display()))
+                });
+                match frame {
+                    Ok(frame) => {
+                        *channel = Some(image::DynamicImage::ImageRgba8(frame.buffer).into_luma8())
+                    }
+                    Err(e) => {
+                        _ = msg_sender.send(Message::err(&format!(
+                            "Could not open {}: {e}",
+                            path.display()
+                        )));
+                        return;
+                    }
+                }
+            }
+        }
+        match crate::channel_pack::combine(&channels) {
+            Ok(img) => {
+                _ = image_sender.send(Frame::new_still(img));
+                _ = msg_sender.send(Message::info(
+                    "Channels combined, save the image to keep it",
+                ));
+            }
+            Err(e) => {
+                _ = msg_sender.send(Message::err(&format!(
+                    "Could not combine the channels: {e}"
+                )))
+            }
+        }
+    });
+}
+
+/// Stitch the montage sources into one image and show it
+pub fn stitch_images(state: &mut OculanteState) {
+    let sources = state.montage.sources.clone();
+    let (direction, gap, background) = (
+        state.montage.direction,
+        state.montage.gap,
+        state.montage.background,
+    );
+    let image_sender = state.player.image_sender.clone();
+    let msg_sender = state.message_channel.0.clone();
+    let loading = state.redraw.poll_while_alive(Reason::Loading);
+    state.player.stop();
+    thread::spawn(move || {
+        let _loading = loading;
+        let mut images = vec![];
+        for path in &sources {
+            let frame = crate::image_loader::open_image(path).and_then(|frames| {
+                frames
+                    .recv()
+                    .map_err(|_| anyhow::anyhow!("No image in {}", path.display()))
+            });
+            match frame {
+                Ok(frame) => images.push(frame.buffer),
+                Err(e) => {
+                    _ = msg_sender.send(Message::err(&format!(
+                        "Could not open {}: {e}",
+                        path.display()
+                    )));
+                    return;
+                }
+            }
+        }
+        match crate::montage::stitch(&images, direction, gap, background) {
+            Ok(img) => {
+                _ = image_sender.send(Frame::new_still(img));
+                _ = msg_sender.send(Message::info("Images stitched, save the image to keep it"));
+            }
+            Err(e) => {
+                _ = msg_sender.send(Message::err(&format!("Could not stitch the images: {e}")))
+            }
+        }
+    });
+}
+
+/// Recognize the text in the selection or the whole image on a thread
+#[cfg(feature = "ocr")]
+pub fn extract_text(state: &mut OculanteState) {
+    if state.current_image.is_none() || state.ocr.running {
+        return;
+    }
+    state.ocr.enabled = true;
+    state.ocr.running = true;
+    let sender = state.ocr_channel.0.clone();
+    let running = state.redraw.poll_while_alive(Reason::Analysis);
+    crop_selection(state, move |crop| {
+        let _running = running;
+        let text = crop.and_then(|img| crate::ocr::recognize(&img));
+        _ = sender.send(text.map_err(|e| e.to_string()));
+    });
+}
+
+/// A file name for a new viewport capture in `folder` that doesn't overwrite older ones
+pub fn capture_path(folder: &Path) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut path = folder.join(format!("oculante_{millis}.png"));
+    let mut count = 1;
+    while path.exists() {
+        path = folder.join(format!("oculante_{millis}_{count}.png"));
+        count += 1;
+    }
+    path
+}
+
+/// Show the frames of an MJPEG stream or a pipe as they arrive
+pub fn play_stream(state: &mut OculanteState, url: &str) {
+    state.stream.stop();
+    state.player.stop();
+    let url = url.trim().to_string();
+    let play = if url.starts_with("pipe://") {
+        crate::pipe::play
+    } else {
+        crate::mjpeg::play
+    };
+    state.stream.playing = Some(play(
+        url,
+        state.texture_channel.0.clone(),
+        state.stream_status_channel.0.clone(),
+        state.redraw.waker(),
+    ));
+    // like pasted images, frames have no path
+    state.current_path = None;
+}
+
+/// List a remote folder or bucket on a thread and show its image when it arrives
+pub fn open_remote(state: &mut OculanteState, url: &str) {
+    crate::remote::set_credentials(state.persistent_settings.remote_credentials.clone());
+    state.remote.listing = true;
+    let url = url.to_string();
+    let all_files = state.load_all_files();
+    let sender = state.remote_channel.0.clone();
+    let listing = state.redraw.poll_while_alive(Reason::Folder);
+    thread::spawn(move || {
+        let _listing = listing;
+        let images = crate::remote::open(&url, all_files);
+        _ = sender.send(images.map_err(|e| e.to_string()));
+    });
+}
+
+/// Accept or reject the current image of the dataset under review
+pub fn judge_image(state: &mut OculanteState, verdict: crate::dataset::Verdict) {
+    if !state.check_writable() {
+        return;
+    }
+    let (dataset, path) = match (&mut state.dataset.dataset, &state.current_path) {
+        (Some(dataset), Some(path)) => (dataset, path.clone()),
+        _ => return,
+    };
+    if let Err(e) = dataset.judge(&path, verdict) {
+        state.send_message_err(&format!("Can't save the review: {e}"));
+        return;
+    }
+    if state.dataset.advance {
+        next_image(state);
+    }
+}
+
+/// Keep the images with annotations of visible classes in the folder
+pub fn filter_dataset_images(state: &mut OculanteState) {
+    if let Some(dataset) = &state.dataset.dataset {
+        let current = state.current_path.clone();
+        state
+            .scrubber
+            .entries
+            .retain(|p| Some(p) == current.as_ref() || !dataset.visible(p).is_empty());
+        if let Some(current) = current {
+            state.scrubber.select(&current);
+        }
+    }
+}
+
+/// Save the edits of the current image in an .oculante file next to it, or for its whole folder
+pub fn save_edits(state: &OculanteState, directory: bool) {
+    if !state.check_writable() {
+        return;
+    }
+    let file = match &state.current_path {
+        Some(path) if directory => match path.parent() {
+            Some(parent) => parent.join(".oculante"),
+            None => return,
+        },
+        Some(path) => path.with_extension("oculante"),
+        None => return,
+    };
+    if let Ok(f) = std::fs::File::create(file) {
+        _ = serde_json::to_writer_pretty(&f, &state.edit_state);
+    }
+}
+
+/// Move or copy the current image to the folder of sort key `index`, then show the next image
+pub fn sort_image(state: &mut OculanteState, index: usize) {
+    if !state.check_writable() {
+        return;
+    }
+    let target = match state.persistent_settings.sort_targets.get(index) {
+        Some(target) if !target.folder.as_os_str().is_empty() => target.clone(),
+        _ => {
+            state.send_message_err(&format!("No folder for key {}", index + 1));
+            return;
+        }
+    };
+    let path = match state.current_path.clone() {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(e) = crate::sorting::sort_file(&path, &target) {
+        state.send_message_err(&format!("Can't sort {}: {e}", path.display()));
+        return;
+    }
+    let folder = target
+        .folder
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if target.copy {
+        state.send_message(&format!("Copied to {folder}"));
+        next_image(state);
+        return;
+    }
+    state.send_message(&format!("Moved to {folder}"));
+    // the image left the folder, so the next one takes its place
+    state.scrubber.entries.retain(|p| p != &path);
+    if state.scrubber.entries.is_empty() {
+        return;
+    }
+    let mut index = state.scrubber.index;
+    if index >= state.scrubber.entries.len() {
+        index = if state.scrubber.wrap {
+            0
+        } else {
+            state.scrubber.entries.len() - 1
+        };
+    }
+    let next = state.scrubber.set(index);
+    state.is_loaded = false;
+    state.player.load(&next, state.message_channel.0.clone());
+    state.current_path = Some(next);
+}
+
+/// Add the current image to the favourites, or remove it
+pub fn toggle_favourite(state: &mut OculanteState) {
+    if !state.check_writable() {
+        return;
+    }
+    let path = match &state.current_path {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    let favourites = &mut state.persistent_settings.favourite_images;
+    if favourites.remove(&path) {
+        state.send_message("Removed from favourites");
+    } else {
+        favourites.insert(path);
+        state.send_message("Added to favourites");
+    }
+}
+
+/// The favourites in the folder of the current image
+pub fn folder_favourites(state: &OculanteState) -> Vec<PathBuf> {
+    match state.current_path.as_ref().and_then(|p| p.parent()) {
+        Some(folder) => {
+            crate::favourites::in_folder(&state.persistent_settings.favourite_images, folder)
+        }
+        None => vec![],
+    }
+}
+
+/// Save the favourites of the folder as a list, to a file that may be picked on a thread
+pub fn export_favourites(
+    state: &mut OculanteState,
+    file: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) {
+    if !state.check_writable() {
+        return;
+    }
+    let favourites = folder_favourites(state);
+    let format = state.favourites.format;
+    let msg_sender = state.message_channel.0.clone();
+    thread::spawn(move || {
+        let file = match file() {
+            Some(file) => file,
+            None => return,
+        };
+        _ = msg_sender.send(match std::fs::write(&file, format.render(&favourites)) {
+            Ok(_) => Message::info(&format!(
+                "{} favourites saved to {}",
+                favourites.len(),
+                file.display()
+            )),
+            Err(e) => Message::err(&format!("Could not save the favourites: {e}")),
+        });
+    });
+}
+
+/// Copy or link the favourites of the folder into a folder that may be picked on a thread
+pub fn collect_favourites(
+    state: &mut OculanteState,
+    folder: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) {
+    if !state.check_writable() {
+        return;
+    }
+    let favourites = folder_favourites(state);
+    let mode = state.favourites.mode;
+    let progress = state.favourites_channel.0.clone();
+    let msg_sender = state.message_channel.0.clone();
+    let running = state.redraw.poll_while_alive(Reason::Analysis);
+    thread::spawn(move || {
+        let _running = running;
+        let folder = match folder() {
+            Some(folder) => folder,
+            None => return,
+        };
+        let total = favourites.len();
+        _ = progress.send((0, total));
+        let collected = crate::favourites::collect(&favourites, &folder, mode, &progress);
+        // also ends the progress if nothing was collected
+        _ = progress.send((total, total));
+        _ = msg_sender.send(match collected {
+            Ok(0) => Message::info(&format!(
+                "{total} favourites collected in {}",
+                folder.display()
+            )),
+            Ok(failed) => {
+                Message::err(&format!("Could not collect {failed} of {total} favourites"))
+            }
+            Err(e) => Message::err(&format!("Could not collect the favourites: {e}")),
+        });
+    });
+}
+
+/// Add the favourites of a list or of other settings, from a file that may be picked on a thread
+pub fn import_favourites(
+    state: &mut OculanteState,
+    file: impl FnOnce() -> Option<PathBuf> + Send + 'static,
+) {
+    if !state.check_writable() {
+        return;
+    }
+    let sender = state.favourites_import_channel.0.clone();
+    let msg_sender = state.message_channel.0.clone();
+    let reading = state.redraw.poll_while_alive(Reason::Analysis);
+    thread::spawn(move || {
+        let _reading = reading;
+        let file = match file() {
+            Some(file) => file,
+            None => return,
+        };
+        match crate::favourites::read_favourites(&file) {
+            Ok(paths) => {
+                _ = sender.send(crate::favourites::Import::check(paths));
+            }
+            Err(e) => {
+                _ = msg_sender.send(Message::err(&format!(
+                    "Could not read favourites from {}: {e}",
+                    file.display()
+                )));
+            }
+        }
+    });
+}
+
+/// How often the settings file is checked for favourites changed by other apps
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the favourites are backed up, if they changed
+const BACKUP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Notice favourites changed by other apps, and back them up from time to time
+pub fn sync_favourites(state: &mut OculanteState) {
+    let due = |time: Option<Instant>, interval: Duration| {
+        time.map(|t| t.elapsed() > interval).unwrap_or(true)
+    };
+    if state.favourites.conflict.is_none() && due(state.favourites.checked, SYNC_INTERVAL) {
+        state.favourites.checked = Some(Instant::now());
+        if let Some(theirs) = crate::settings::changed_favourites() {
+            if theirs == state.persistent_settings.favourite_images {
+                crate::settings::mark_synced();
+            } else {
+                state.favourites.conflict = Some(theirs);
+                state.favourites.enabled = true;
+            }
+        }
+    }
+    if due(state.favourites.backed_up, BACKUP_INTERVAL) {
+        state.favourites.backed_up = Some(Instant::now());
+        if let Some(dir) = crate::favourites::backup_dir() {
+            let favourites = state.persistent_settings.favourite_images.clone();
+            thread::spawn(move || {
+                if let Err(e) = crate::favourites::backup(&favourites, &dir, SystemTime::now()) {
+                    error!("Could not back up the favourites: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// Bring the favourites changed by another app together with ours, and save them
+pub fn resolve_favourites(state: &mut OculanteState, resolution: crate::favourites::Resolution) {
+    if let Some(theirs) = state.favourites.conflict.take() {
+        resolution.apply(&mut state.persistent_settings.favourite_images, theirs);
+        crate::settings::mark_synced();
+        state.persistent_settings.save();
+    }
+}
+
+/// Start or stop scroll reading through the images of the folder. When it stops, the page at
+/// the top of the window is opened.
+pub fn toggle_scroll_reader(state: &mut OculanteState) {
+    if state.scroll_reader.active {
+        if let Some(path) = state.scroll_reader.stop() {
+            if state.current_path.as_ref() != Some(&path) {
+                load_image_from_path(&path, state);
+            }
+        }
+    } else if state.scrubber.entries.is_empty() {
+        state.send_message_warn("No images to read");
+    } else {
+        state
+            .scroll_reader
+            .start(state.scrubber.entries.clone(), state.scrubber.index);
+    }
+}
+
+/// Decode the pages near the window on threads, and drop the textures of pages far from it
+pub fn stream_pages(state: &mut OculanteState, width: f32, window_height: f32) {
+    let wanted = state.scroll_reader.wanted(width, window_height);
+    state
+        .scroll_reader
+        .textures
+        .retain(|index, _| wanted.contains(index));
+    for index in wanted {
+        if state.scroll_reader.textures.contains_key(&index)
+            || !state.scroll_reader.loading.insert(index)
+        {
+            continue;
+        }
+        let path = state.scroll_reader.pages[index].clone();
+        let sender = state.scroll_reader_channel.0.clone();
+        let loading = state.redraw.poll_while_alive(Reason::Loading);
+        thread::spawn(move || {
+            let _loading = loading;
+            // JPEGs are decoded at about the width they are shown at
+            match crate::image_loader::open_reduced(&path, (width as u32, u32::MAX)) {
+                Ok(img) => _ = sender.send((index, img)),
+                Err(e) => error!("Could not read {}: {e}", path.display()),
+            }
+        });
+    }
+}
+
+/// Start showing images as they are copied in any app, or stop
+pub fn toggle_clipboard_watch(state: &mut OculanteState) {
+    if state.clipboard_watch.is_watching() {
+        state.clipboard_watch.stop();
+        state.send_message("Stopped watching the clipboard");
+        return;
+    }
+    state.clipboard_watch.watching = Some(crate::clipboard_watch::watch(
+        state.clipboard_watch_channel.0.clone(),
+        state.redraw.waker(),
+    ));
+    state.clipboard_watch.enabled = true;
+    state.send_message("Watching the clipboard, copied images are shown");
+}
+
+/// Start showing images as they arrive in the hot folder from the settings, or stop
+pub fn toggle_hot_folder(state: &mut OculanteState) {
+    if let Some(stop) = state.hot_folder_watch.take() {
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        state.send_message("Stopped watching the hot folder");
+        return;
+    }
+    let folder = match &state.persistent_settings.hot_folder {
+        Some(folder) if folder.is_dir() => folder.clone(),
+        _ => {
+            state.send_message_err("Choose an existing hot folder in the settings first");
+            return;
+        }
+    };
+    state.send_message(&format!("Watching {} for new images", folder.display()));
+    state.hot_folder_watch = Some(crate::hot_folder::watch(
+        folder,
+        state.load_all_files(),
+        state.hot_folder_channel.0.clone(),
+        state.redraw.waker(),
+    ));
+}
+
+/// Show an image of the clipboard history
+pub fn show_clipboard_entry(state: &mut OculanteState, index: usize) {
+    if let Some(img) = state.clipboard_watch.history.get(index) {
+        // like pasted images, copied ones have no path
+        state.current_path = None;
+        state.player.stop();
+        _ = state
+            .player
+            .image_sender
+            .send(Frame::new_still(img.clone()));
+        state.clipboard_watch.selected = Some(index);
+    }
+}
+
+/// Hide the window, take a screenshot into the capture folder on a thread, and open it
+pub fn take_screenshot(
+    app: &mut App,
+    state: &mut OculanteState,
+    area: crate::screenshot::CaptureArea,
+) {
+    let folder = state.persistent_settings.capture_folder.clone();
+    let sender = state.screenshot_channel.0.clone();
+    // a hidden window gets no events, so the result is polled
+    let capturing = state.redraw.poll_while_alive(Reason::Screenshot);
+    app.window().set_visible(false);
+    thread::spawn(move || {
+        let _capturing = capturing;
+        // give the window time to disappear
+        thread::sleep(std::time::Duration::from_millis(300));
+        let result = std::fs::create_dir_all(&folder)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                let path = capture_path(&folder);
+                Ok(crate::screenshot::capture(area, &path)?.then_some(path))
+            });
+        _ = sender.send(result.map_err(|e| e.to_string()));
+    });
+}
+
+/// Save the pixels of a render texture, which are stored bottom row first, as PNG in `folder`
+pub fn save_capture(
+    pixels: Vec<u8>,
+    (width, height): (u32, u32),
+    folder: PathBuf,
+    message_sender: Sender<Message>,
+) {
+    thread::spawn(move || {
+        let rows = pixels
+            .chunks(width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let result = std::fs::create_dir_all(&folder)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                let path = capture_path(&folder);
+                image::save_buffer(&path, &rows, width, height, image::ColorType::Rgba8)?;
+                Ok(path)
+            });
+        _ = message_sender.send(match result {
+            Ok(path) => Message::info(&format!("Viewport saved to {}", path.display())),
+            Err(e) => Message::err(&format!("Could not save viewport: {e}")),
+        });
+    });
+}
+
+const XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Metadata of the original file that is copied into saved images
+#[derive(Debug, Clone, Default)]
+pub struct ImageMetadata {
+    pub exif: Option<Bytes>,
+    pub icc: Option<Bytes>,
+    /// XMP packets, only supported for JPEG
+    pub xmp: Vec<Bytes>,
+}
+
+impl ImageMetadata {
+    pub fn read(p: &Path) -> Result<Self> {
+        let dynimage = DynImage::from_bytes(std::fs::read(p)?.into())?
+            .context("Unsupported metadata format")?;
+        let xmp = match &dynimage {
+            DynImage::Jpeg(jpeg) => jpeg
+                .segments_by_marker(img_parts::jpeg::markers::APP1)
+                .filter(|s| s.contents().starts_with(XMP_PREFIX))
+                .map(|s| s.contents().clone())
+                .collect(),
+            _ => vec![],
+        };
+        Ok(Self {
+            exif: dynimage.exif(),
+            icc: dynimage.icc_profile(),
+            xmp,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc.is_none() && self.xmp.is_empty()
+    }
+
+    /// Write the metadata into a saved file. The EXIF pixel dimensions are updated and the
+    /// orientation is reset if the pixels were rotated by the edits.
+    pub fn write(&self, p: &Path, dimensions: (u32, u32), reset_orientation: bool) -> Result<()> {
+        let mut dynimage = DynImage::from_bytes(std::fs::read(p)?.into())?
+            .context("Unsupported metadata format")?;
+        let exif = self.exif.as_ref().map(|exif| {
+            let mut exif = exif.to_vec();
+            patch_exif(&mut exif, dimensions, reset_orientation);
+            Bytes::from(exif)
+        });
+        dynimage.set_exif(exif);
+        dynimage.set_icc_profile(self.icc.clone());
+        if let DynImage::Jpeg(jpeg) = &mut dynimage {
+            // place XMP after the EXIF segment
+            let pos = jpeg
+                .segments()
+                .iter()
+                .position(|s| s.contents().starts_with(b"Exif\0\0"))
+                .map(|i| i + 1)
+                .unwrap_or(jpeg.segments().len().min(1));
+            for (i, xmp) in self.xmp.iter().enumerate() {
+                jpeg.segments_mut().insert(
+                    pos + i,
+                    img_parts::jpeg::JpegSegment::new_with_contents(
+                        img_parts::jpeg::markers::APP1,
+                        xmp.clone(),
+                    ),
+                );
+            }
+        }
+        dynimage.encoder().write_to(std::fs::File::create(p)?)?;
+        Ok(())
+    }
+}
+
+/// Update raw EXIF (TIFF) data in place: set the pixel dimensions and optionally reset the orientation
+pub fn patch_exif(data: &mut [u8], dimensions: (u32, u32), reset_orientation: bool) -> Option<()> {
+    const ORIENTATION: u16 = 0x0112;
+    const EXIF_IFD: u16 = 0x8769;
+    const PIXEL_X: u16 = 0xA002;
+    const PIXEL_Y: u16 = 0xA003;
+    const SHORT: u16 = 3;
+    use std::convert::{TryFrom, TryInto};
+
+    let le = match data.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read16 = |d: &[u8], o: usize| -> Option<u16> {
+        let b = d.get(o..o + 2)?.try_into().ok()?;
+        Some(if le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let read32 = |d: &[u8], o: usize| -> Option<u32> {
+        let b = d.get(o..o + 4)?.try_into().ok()?;
+        Some(if le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+    let write = |d: &mut [u8], o: usize, typ: u16, val: u32| -> Option<()> {
+        if typ == SHORT {
+            let v = u16::try_from(val).ok()?;
+            let b = if le { v.to_le_bytes() } else { v.to_be_bytes() };
+            d.get_mut(o..o + 2)?.copy_from_slice(&b);
+        } else {
+            let b = if le {
+                val.to_le_bytes()
+            } else {
+                val.to_be_bytes()
+            };
+            d.get_mut(o..o + 4)?.copy_from_slice(&b);
+        }
+        Some(())
+    };
+
+    let mut ifds = vec![read32(data, 4)? as usize];
+    // a pointer back to an IFD we've seen would otherwise never end
+    let mut visited = HashSet::new();
+    while let Some(ifd) = ifds.pop() {
+        if !visited.insert(ifd) {
+            continue;
+        }
+        for i in 0..read16(data, ifd)? as usize {
+            let entry = ifd + 2 + i * 12;
+            let typ = read16(data, entry + 2)?;
+            let value = entry + 8;
+            match read16(data, entry)? {
+                ORIENTATION if reset_orientation => write(data, value, typ, 1)?,
+                EXIF_IFD => ifds.push(read32(data, value)? as usize),
+                PIXEL_X => write(data, value, typ, dimensions.0)?,
+                PIXEL_Y => write(data, value, typ, dimensions.1)?,
+                _ => (),
+            }
+        }
+    }
+    Some(())
 }